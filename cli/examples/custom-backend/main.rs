@@ -14,7 +14,7 @@
 
 use std::any::Any;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use async_trait::async_trait;
@@ -24,7 +24,8 @@ use jj_cli::command_error::CommandError;
 use jj_cli::ui::Ui;
 use jj_lib::backend::{
     Backend, BackendInitError, BackendLoadError, BackendResult, ChangeId, Commit, CommitId,
-    Conflict, ConflictId, CopyRecord, FileId, SigningFn, SymlinkId, Tree, TreeId,
+    CompactOptions, CompactStats, Conflict, ConflictId, CopyRecord, FileId, SigningFn, SymlinkId,
+    Tree, TreeId,
 };
 use jj_lib::git_backend::GitBackend;
 use jj_lib::index::Index;
@@ -187,4 +188,12 @@ impl Backend for JitBackend {
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         self.inner.gc(index, keep_newer)
     }
+
+    fn compact(&self, options: &CompactOptions) -> BackendResult<CompactStats> {
+        self.inner.compact(options)
+    }
+
+    fn add_alternates(&self, object_dirs: &[PathBuf]) -> BackendResult<()> {
+        self.inner.add_alternates(object_dirs)
+    }
 }
@@ -32,7 +32,7 @@ use jj_lib::signing::Signer;
 use jj_lib::store::Store;
 use jj_lib::working_copy::{
     CheckoutError, CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions,
-    WorkingCopy, WorkingCopyFactory, WorkingCopyStateError,
+    UntrackedEntry, WorkingCopy, WorkingCopyFactory, WorkingCopyStateError,
 };
 use jj_lib::workspace::{WorkingCopyFactories, Workspace, WorkspaceInitError};
 
@@ -157,6 +157,10 @@ impl WorkingCopy for ConflictsWorkingCopy {
         self.inner.sparse_patterns()
     }
 
+    fn untracked(&self, options: SnapshotOptions) -> Result<Vec<UntrackedEntry>, SnapshotError> {
+        self.inner.untracked(options)
+    }
+
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError> {
         let inner = self.inner.start_mutation()?;
         Ok(Box::new(LockedConflictsWorkingCopy {
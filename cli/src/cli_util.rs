@@ -65,7 +65,8 @@ use jj_lib::str_util::StringPattern;
 use jj_lib::transaction::Transaction;
 use jj_lib::view::View;
 use jj_lib::working_copy::{
-    CheckoutStats, LockedWorkingCopy, SnapshotOptions, WorkingCopy, WorkingCopyFactory,
+    commit_snapshot, CheckoutStats, LockedWorkingCopy, SnapshotOptions, WorkingCopy,
+    WorkingCopyFactory,
 };
 use jj_lib::workspace::{
     default_working_copy_factories, LockedWorkspace, WorkingCopyFactories, Workspace,
@@ -78,7 +79,7 @@ use tracing_chrome::ChromeLayerBuilder;
 use tracing_subscriber::prelude::*;
 
 use crate::command_error::{
-    cli_error, config_error_with_message, handle_command_result, internal_error,
+    cli_error, config_error, config_error_with_message, handle_command_result, internal_error,
     internal_error_with_message, user_error, user_error_with_hint, user_error_with_message,
     CommandError,
 };
@@ -299,6 +300,15 @@ impl CommandHelper {
         let workspace = self.load_workspace()?;
         let op_head = self.resolve_operation(ui, workspace.repo_loader())?;
         let repo = workspace.repo_loader().load_at(&op_head)?;
+        let stale_heads = repo.stale_heads()?;
+        if !stale_heads.is_empty() {
+            writeln!(
+                ui.warning_default(),
+                "{} visible heads are missing from the store: {}",
+                stale_heads.len(),
+                stale_heads.iter().map(|id| id.hex()).join(", ")
+            )?;
+        }
         self.for_loaded_repo(ui, workspace, repo)
     }
 
@@ -838,10 +848,21 @@ impl WorkspaceCommandHelper {
         tool_name: Option<&str>,
     ) -> Result<DiffEditor, CommandError> {
         let base_ignores = self.base_ignores()?;
+        let scratch_dir = self.repo().settings().scratch_dir().clone();
         if let Some(name) = tool_name {
-            Ok(DiffEditor::with_name(name, &self.settings, base_ignores)?)
+            Ok(DiffEditor::with_name(
+                name,
+                &self.settings,
+                base_ignores,
+                scratch_dir,
+            )?)
         } else {
-            Ok(DiffEditor::from_settings(ui, &self.settings, base_ignores)?)
+            Ok(DiffEditor::from_settings(
+                ui,
+                &self.settings,
+                base_ignores,
+                scratch_dir,
+            )?)
         }
     }
 
@@ -869,10 +890,11 @@ impl WorkspaceCommandHelper {
         ui: &Ui,
         tool_name: Option<&str>,
     ) -> Result<MergeEditor, MergeToolConfigError> {
+        let scratch_dir = self.repo().settings().scratch_dir().clone();
         if let Some(name) = tool_name {
-            MergeEditor::with_name(name, &self.settings)
+            MergeEditor::with_name(name, &self.settings, scratch_dir)
         } else {
-            MergeEditor::from_settings(ui, &self.settings)
+            MergeEditor::from_settings(ui, &self.settings, scratch_dir)
         }
     }
 
@@ -1167,12 +1189,7 @@ impl WorkspaceCommandHelper {
     #[instrument(skip_all)]
     fn snapshot_working_copy(&mut self, ui: &mut Ui) -> Result<(), CommandError> {
         let workspace_id = self.workspace_id().to_owned();
-        let get_wc_commit = |repo: &ReadonlyRepo| -> Result<Option<_>, _> {
-            repo.view()
-                .get_wc_commit_id(&workspace_id)
-                .map(|id| repo.store().get_commit(id))
-                .transpose()
-        };
+        let get_wc_commit = |repo: &ReadonlyRepo| repo.working_copy_commit(&workspace_id);
         let repo = self.repo().clone();
         let Some(wc_commit) = get_wc_commit(&repo)? else {
             // If the workspace has been deleted, it's unclear what to do, so we just skip
@@ -1240,10 +1257,9 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
                 start_repo_transaction(&self.user_repo.repo, &self.settings, &self.string_args);
             tx.set_is_snapshot(true);
             let mut_repo = tx.mut_repo();
-            let commit = mut_repo
-                .rewrite_commit(&self.settings, &wc_commit)
-                .set_tree_id(new_tree_id)
-                .write()?;
+            let commit = commit_snapshot(mut_repo, &self.settings, &wc_commit, new_tree_id)?
+                .wc_commit
+                .expect("working-copy tree should have changed");
             mut_repo.set_wc_commit(workspace_id, commit.id().clone())?;
 
             // Rebase descendants
@@ -1310,10 +1326,39 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
     }
 
     pub fn start_transaction(&mut self) -> WorkspaceCommandTransaction {
-        let tx = start_repo_transaction(self.repo(), &self.settings, &self.string_args);
+        let mut tx = start_repo_transaction(self.repo(), &self.settings, &self.string_args);
+        // An invalid `immutable_heads()` is reported by `check_rewritable()`,
+        // which commands call before rewriting anything.
+        if let Ok(immutable_heads) = self.immutable_heads() {
+            tx.mut_repo().set_immutable_heads(immutable_heads);
+        }
         WorkspaceCommandTransaction { helper: self, tx }
     }
 
+    /// Evaluates `immutable_heads()`, including the root commit, so new
+    /// transactions refuse to rewrite or abandon their ancestors. Returns no
+    /// heads if `--ignore-immutable` was passed.
+    fn immutable_heads(&self) -> Result<Vec<CommitId>, CommandError> {
+        if self.global_args.ignore_immutable {
+            return Ok(vec![]);
+        }
+        let id_prefix_context = IdPrefixContext::new(self.revset_extensions.clone());
+        let heads = revset_util::parse_immutable_heads_expression(&self.revset_parse_context())
+            .map_err(|e| {
+                config_error_with_message("Invalid `revset-aliases.immutable_heads()`", e)
+            })?;
+        let expression = RevsetExpressionEvaluator::new(
+            self.repo().as_ref(),
+            self.revset_extensions.clone(),
+            &id_prefix_context,
+            heads,
+        );
+        let heads = expression.evaluate_to_commit_ids().map_err(|e| {
+            config_error_with_message("Invalid `revset-aliases.immutable_heads()`", e)
+        })?;
+        Ok(heads.collect())
+    }
+
     fn finish_transaction(
         &mut self,
         ui: &mut Ui,
@@ -1409,8 +1454,8 @@ See https://github.com/martinvonz/jj/blob/main/docs/working-copy.md#stale-workin
         let old_view = old_repo.view();
         let new_repo = self.repo().as_ref();
         let new_view = new_repo.view();
-        let old_heads = RevsetExpression::commits(old_view.heads().iter().cloned().collect());
-        let new_heads = RevsetExpression::commits(new_view.heads().iter().cloned().collect());
+        let old_heads = RevsetExpression::commits(old_view.sorted_heads());
+        let new_heads = RevsetExpression::commits(new_view.sorted_heads());
         // Filter the revsets by conflicts instead of reading all commits and doing the
         // filtering here. That way, we can afford to evaluate the revset even if there
         // are millions of commits added to the repo, assuming the revset engine can
@@ -1725,18 +1770,32 @@ jj git init --colocate",
             "The repository directory at {} is missing. Was it moved?",
             repo_dir.display(),
         )),
-        WorkspaceLoadError::StoreLoadError(err @ StoreLoadError::UnsupportedType { .. }) => {
-            internal_error_with_message(
-                "This version of the jj binary doesn't support this type of repo",
+        WorkspaceLoadError::StoreLoadError(
+            err @ (StoreLoadError::UnsupportedType { .. }
+            | StoreLoadError::UnsupportedFormat { .. }),
+        ) => internal_error_with_message(
+            "This version of the jj binary doesn't support this type of repo",
+            err,
+        ),
+        WorkspaceLoadError::StoreLoadError(
+            err @ (StoreLoadError::ReadError { .. }
+            | StoreLoadError::Backend(_)
+            | StoreLoadError::ReadRepoId(_)
+            | StoreLoadError::ReadFormat(_)),
+        ) => internal_error_with_message("The repository appears broken or inaccessible", err),
+        WorkspaceLoadError::StoreLoadError(err @ StoreLoadError::UpgradeRequired { .. }) => {
+            user_error_with_hint(err, "Run `jj util upgrade-repo` to upgrade it")
+        }
+        WorkspaceLoadError::StoreLoadError(err @ StoreLoadError::StoreViewMismatch { .. }) => {
+            user_error_with_hint(
                 err,
+                "Parts of the repository directory may have been copied from another repository",
             )
         }
-        WorkspaceLoadError::StoreLoadError(
-            err @ (StoreLoadError::ReadError { .. } | StoreLoadError::Backend(_)),
-        ) => internal_error_with_message("The repository appears broken or inaccessible", err),
         WorkspaceLoadError::StoreLoadError(StoreLoadError::Signing(
             err @ SignInitError::UnknownBackend(_),
         )) => user_error(err),
+        WorkspaceLoadError::StoreLoadError(StoreLoadError::Config(err)) => config_error(err),
         WorkspaceLoadError::StoreLoadError(err) => internal_error(err),
         WorkspaceLoadError::WorkingCopyState(err) => internal_error(err),
         WorkspaceLoadError::NonUnicodePath | WorkspaceLoadError::Path(_) => user_error(err),
@@ -1939,12 +1998,27 @@ pub fn print_checkout_stats(
             stats.removed_files
         )?;
     }
-    if stats.skipped_files != 0 {
+    if !stats.skipped_paths.is_empty() {
+        writeln!(
+            ui.warning_default(),
+            "{} paths can't be represented on this platform and were not written:",
+            stats.skipped_paths.len()
+        )?;
+        for path in &stats.skipped_paths {
+            writeln!(
+                ui.warning_no_heading(),
+                "  {}",
+                path.as_internal_file_string()
+            )?;
+        }
+    }
+    let conflicting_files = stats.skipped_files - stats.skipped_paths.len() as u32;
+    if conflicting_files != 0 {
         writeln!(
             ui.warning_default(),
             "{} of those updates were skipped because there were conflicting changes in the \
              working copy.",
-            stats.skipped_files
+            conflicting_files
         )?;
         writeln!(
             ui.hint_default(),
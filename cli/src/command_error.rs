@@ -20,12 +20,17 @@ use std::{error, io, iter, str};
 use itertools::Itertools as _;
 use jj_lib::backend::BackendError;
 use jj_lib::fileset::{FilePatternParseError, FilesetParseError, FilesetParseErrorKind};
-use jj_lib::git::{GitConfigParseError, GitExportError, GitImportError, GitRemoteManagementError};
+use jj_lib::git::{
+    GitConfigParseError, GitExportError, GitImportError, GitRemoteManagementError,
+    GitResetHeadError,
+};
 use jj_lib::gitignore::GitIgnoreError;
 use jj_lib::op_heads_store::OpHeadResolutionError;
 use jj_lib::op_store::OpStoreError;
 use jj_lib::op_walk::OpsetEvaluationError;
-use jj_lib::repo::{CheckOutCommitError, EditCommitError, RepoLoaderError, RewriteRootCommit};
+use jj_lib::repo::{
+    CheckOutCommitError, EditCommitError, RepoError, RepoLoaderError, RewriteRootCommit,
+};
 use jj_lib::repo_path::{RepoPathBuf, UiPathParseError};
 use jj_lib::revset::{
     RevsetEvaluationError, RevsetParseError, RevsetParseErrorKind, RevsetResolutionError,
@@ -242,6 +247,20 @@ impl From<CheckOutCommitError> for CommandError {
     }
 }
 
+impl From<RepoError> for CommandError {
+    fn from(err: RepoError) -> Self {
+        match err {
+            RepoError::Backend(err) => err.into(),
+            RepoError::Immutable(_) => user_error_with_hint(
+                err,
+                "Pass `--ignore-immutable` or configure the set of immutable commits via \
+                 `revset-aliases.immutable_heads()`.",
+            ),
+            _ => internal_error_with_message("Failed to rewrite a commit", err),
+        }
+    }
+}
+
 impl From<BackendError> for CommandError {
     fn from(err: BackendError) -> Self {
         match &err {
@@ -263,6 +282,12 @@ impl From<WorkspaceInitError> for CommandError {
             WorkspaceInitError::CheckOutCommit(err) => {
                 internal_error_with_message("Failed to check out the initial commit", err)
             }
+            WorkspaceInitError::CheckOut(err) => {
+                internal_error_with_message("Failed to check out the working-copy commit", err)
+            }
+            WorkspaceInitError::Clone(err) => {
+                internal_error_with_message("Failed to copy the repository", err)
+            }
             WorkspaceInitError::Path(err) => {
                 internal_error_with_message("Failed to access the repository", err)
             }
@@ -274,6 +299,7 @@ impl From<WorkspaceInitError> for CommandError {
             }
             WorkspaceInitError::SignInit(err @ SignInitError::UnknownBackend(_)) => user_error(err),
             WorkspaceInitError::SignInit(err) => internal_error(err),
+            WorkspaceInitError::Config(err) => config_error(err),
         }
     }
 }
@@ -442,6 +468,15 @@ impl From<GitExportError> for CommandError {
     }
 }
 
+impl From<GitResetHeadError> for CommandError {
+    fn from(err: GitResetHeadError) -> Self {
+        match err {
+            GitResetHeadError::NoWorkingCopy => user_error(err),
+            GitResetHeadError::InternalGitError(err) => err.into(),
+        }
+    }
+}
+
 impl From<GitRemoteManagementError> for CommandError {
     fn from(err: GitRemoteManagementError) -> Self {
         user_error(err)
@@ -630,9 +665,9 @@ fn revset_resolution_error_hint(err: &RevsetResolutionError) -> Option<String> {
 fn string_pattern_parse_error_hint(err: &StringPatternParseError) -> Option<String> {
     match err {
         StringPatternParseError::InvalidKind(_) => {
-            Some("Try prefixing with one of `exact:`, `glob:` or `substring:`".into())
+            Some("Try prefixing with one of `exact:`, `glob:`, `regex:` or `substring:`".into())
         }
-        StringPatternParseError::GlobPattern(_) => None,
+        StringPatternParseError::GlobPattern(_) | StringPatternParseError::Regex(_) => None,
     }
 }
 
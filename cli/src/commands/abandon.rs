@@ -63,7 +63,7 @@ pub(crate) fn cmd_abandon(
 
     let mut tx = workspace_command.start_transaction();
     for commit in &to_abandon {
-        tx.mut_repo().record_abandoned_commit(commit.id().clone());
+        tx.mut_repo().abandon_commit(commit.id())?;
     }
     let num_rebased = tx.mut_repo().rebase_descendants(command.settings())?;
 
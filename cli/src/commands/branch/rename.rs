@@ -12,8 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::op_store::RefTarget;
-
 use super::has_tracked_remote_branches;
 use crate::cli_util::CommandHelper;
 use crate::command_error::{user_error, CommandError};
@@ -37,23 +35,13 @@ pub fn cmd_branch_rename(
     args: &BranchRenameArgs,
 ) -> Result<(), CommandError> {
     let mut workspace_command = command.workspace_helper(ui)?;
-    let view = workspace_command.repo().view();
     let old_branch = &args.old;
-    let ref_target = view.get_local_branch(old_branch).clone();
-    if ref_target.is_absent() {
-        return Err(user_error(format!("No such branch: {old_branch}")));
-    }
-
     let new_branch = &args.new;
-    if view.get_local_branch(new_branch).is_present() {
-        return Err(user_error(format!("Branch already exists: {new_branch}")));
-    }
 
     let mut tx = workspace_command.start_transaction();
     tx.mut_repo()
-        .set_local_branch_target(new_branch, ref_target);
-    tx.mut_repo()
-        .set_local_branch_target(old_branch, RefTarget::absent());
+        .rename_branch(old_branch, new_branch)
+        .map_err(user_error)?;
     tx.finish(ui, format!("rename branch {old_branch} to {new_branch}"))?;
 
     let view = workspace_command.repo().view();
@@ -75,7 +75,7 @@ fn cmd_submodule_print(
         }
     };
 
-    let submodules = parse_gitmodules(&mut gitmodules_file)?;
+    let submodules = parse_gitmodules(&mut gitmodules_file, repo.settings().scratch_dir())?;
     for (name, submodule) in submodules {
         writeln!(
             ui.stdout(),
@@ -88,5 +88,8 @@ fn view_with_desired_portions_restored(
         git_refs: current_view.git_refs.clone(),
         git_head: current_view.git_head.clone(),
         wc_commit_ids: repo_source.wc_commit_ids.clone(),
+        checkout_history: repo_source.checkout_history.clone(),
+        change_metadata: repo_source.change_metadata.clone(),
+        accepted_divergences: repo_source.accepted_divergences.clone(),
     }
 }
@@ -18,10 +18,11 @@ use std::time::{Duration, SystemTime};
 
 use clap::{Command, Subcommand};
 use jj_lib::repo::Repo;
+use jj_lib::repo_format::{self, UpgradeOptions, CURRENT_FORMAT_VERSION};
 use tracing::instrument;
 
 use crate::cli_util::CommandHelper;
-use crate::command_error::{user_error, CommandError};
+use crate::command_error::{user_error, user_error_with_message, CommandError};
 use crate::ui::Ui;
 
 /// Infrequently used commands such as for generating shell completions
@@ -32,6 +33,7 @@ pub(crate) enum UtilCommand {
     Mangen(UtilMangenArgs),
     MarkdownHelp(UtilMarkdownHelp),
     ConfigSchema(UtilConfigSchemaArgs),
+    UpgradeRepo(UtilUpgradeRepoArgs),
 }
 
 // Using an explicit `doc` attribute prevents rustfmt from mangling the list
@@ -96,6 +98,15 @@ pub(crate) struct UtilMarkdownHelp {}
 #[derive(clap::Args, Clone, Debug)]
 pub(crate) struct UtilConfigSchemaArgs {}
 
+/// Upgrade the repository to the format used by this version of jj
+///
+/// Repositories in an older format are upgraded automatically when they're
+/// modified if the upgrade only adds data older versions of jj don't need.
+/// Otherwise, they can't be modified until they're upgraded with this command.
+/// Older versions of jj may not be able to read the repository afterwards.
+#[derive(clap::Args, Clone, Debug)]
+pub(crate) struct UtilUpgradeRepoArgs {}
+
 /// Available shell completions
 #[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum ShellCompletion {
@@ -119,6 +130,7 @@ pub(crate) fn cmd_util(
         UtilCommand::Mangen(args) => cmd_util_mangen(ui, command, args),
         UtilCommand::MarkdownHelp(args) => cmd_util_markdownhelp(ui, command, args),
         UtilCommand::ConfigSchema(args) => cmd_util_config_schema(ui, command, args),
+        UtilCommand::UpgradeRepo(args) => cmd_util_upgrade_repo(ui, command, args),
     }
 }
 
@@ -227,6 +239,34 @@ fn cmd_util_config_schema(
     Ok(())
 }
 
+fn cmd_util_upgrade_repo(
+    ui: &mut Ui,
+    command: &CommandHelper,
+    _args: &UtilUpgradeRepoArgs,
+) -> Result<(), CommandError> {
+    let repo_path = command.workspace_loader()?.repo_path();
+    let report = repo_format::upgrade(
+        repo_path,
+        CURRENT_FORMAT_VERSION,
+        &UpgradeOptions::default(),
+    )
+    .map_err(|err| user_error_with_message("Failed to upgrade the repository", err))?;
+    if report.migrations.is_empty() {
+        writeln!(ui.status(), "The repository is already up to date")?;
+        return Ok(());
+    }
+    for migration in &report.migrations {
+        writeln!(ui.status(), "{migration}")?;
+    }
+    writeln!(
+        ui.status(),
+        "Upgraded the repository from format version {} to {}",
+        report.from_version,
+        report.to_version
+    )?;
+    Ok(())
+}
+
 impl ShellCompletion {
     fn generate(&self, cmd: &mut Command) -> Vec<u8> {
         use clap_complete::{generate, Shell};
@@ -20,11 +20,11 @@ use std::{io, mem};
 
 use futures::{try_join, Stream, StreamExt};
 use itertools::Itertools;
-use jj_lib::backend::{BackendError, BackendResult, TreeValue};
+use jj_lib::backend::{BackendError, BackendResult, FileId, TreeValue};
 use jj_lib::commit::Commit;
 use jj_lib::conflicts::{materialize_tree_value, MaterializedTreeValue};
 use jj_lib::diff::{Diff, DiffHunk};
-use jj_lib::files::DiffLine;
+use jj_lib::files::{ContentType, DiffLine};
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::{MergedTree, TreeDiffStream};
@@ -289,10 +289,16 @@ impl<'a> DiffRenderer<'a> {
                                 path_converter,
                             )
                         }
-                        DiffToolMode::Dir => {
-                            generate_diff(ui, formatter.raw(), from_tree, to_tree, matcher, tool)
-                                .map_err(DiffRenderError::DiffGenerate)
-                        }
+                        DiffToolMode::Dir => generate_diff(
+                            ui,
+                            formatter.raw(),
+                            from_tree,
+                            to_tree,
+                            matcher,
+                            tool,
+                            repo.settings().scratch_dir(),
+                        )
+                        .map_err(DiffRenderError::DiffGenerate),
                     }?;
                 }
             }
@@ -454,33 +460,38 @@ impl FileContent {
     }
 }
 
-fn file_content_for_diff(reader: &mut dyn io::Read) -> io::Result<FileContent> {
-    // If this is a binary file, don't show the full contents.
-    // Determine whether it's binary by whether the first 8k bytes contain a null
-    // character; this is the same heuristic used by git as of writing: https://github.com/git/git/blob/eea0e59ffbed6e33d171ace5be13cde9faa41639/xdiff-interface.c#L192-L198
-    const PEEK_SIZE: usize = 8000;
+fn file_content_for_diff(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+    reader: &mut dyn io::Read,
+) -> Result<FileContent, DiffRenderError> {
+    // If this is a binary file, don't show the full contents. The store
+    // classifies it the same way as when merging.
     // TODO: currently we look at the whole file, even though for binary files we
     // only need to know the file size. To change that we'd have to extend all
     // the data backends to support getting the length.
     let mut contents = vec![];
     reader.read_to_end(&mut contents)?;
-
-    let start = &contents[..PEEK_SIZE.min(contents.len())];
     Ok(FileContent {
-        is_binary: start.contains(&b'\0'),
+        is_binary: store.file_content_type(path, id)? == ContentType::Binary,
         contents,
     })
 }
 
-fn diff_content(path: &RepoPath, value: MaterializedTreeValue) -> io::Result<FileContent> {
+fn diff_content(
+    store: &Store,
+    path: &RepoPath,
+    value: MaterializedTreeValue,
+) -> Result<FileContent, DiffRenderError> {
     match value {
         MaterializedTreeValue::Absent => Ok(FileContent::empty()),
         MaterializedTreeValue::AccessDenied(err) => Ok(FileContent {
             is_binary: false,
             contents: format!("Access denied: {err}").into_bytes(),
         }),
-        MaterializedTreeValue::File { mut reader, .. } => {
-            file_content_for_diff(&mut reader).map_err(Into::into)
+        MaterializedTreeValue::File { id, mut reader, .. } => {
+            file_content_for_diff(store, path, &id, &mut reader)
         }
         MaterializedTreeValue::Symlink { id: _, target } => Ok(FileContent {
             // Unix file paths can't contain null bytes.
@@ -491,13 +502,12 @@ fn diff_content(path: &RepoPath, value: MaterializedTreeValue) -> io::Result<Fil
             is_binary: false,
             contents: format!("Git submodule checked out at {}", id.hex()).into_bytes(),
         }),
-        // TODO: are we sure this is never binary?
         MaterializedTreeValue::Conflict {
-            id: _,
+            id,
             contents,
             executable: _,
         } => Ok(FileContent {
-            is_binary: false,
+            is_binary: store.value_content_type(path, &id)? == ContentType::Binary,
             contents,
         }),
         MaterializedTreeValue::Tree(id) => {
@@ -558,7 +568,7 @@ pub fn show_color_words_diff(
                     formatter.labeled("header"),
                     "Added {description} {ui_path}:"
                 )?;
-                let right_content = diff_content(&path, right_value)?;
+                let right_content = diff_content(repo.store(), &path, right_value)?;
                 if right_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
                 } else if right_content.is_binary {
@@ -619,8 +629,8 @@ pub fn show_color_words_diff(
                         )
                     }
                 };
-                let left_content = diff_content(&path, left_value)?;
-                let right_content = diff_content(&path, right_value)?;
+                let left_content = diff_content(repo.store(), &path, left_value)?;
+                let right_content = diff_content(repo.store(), &path, right_value)?;
                 writeln!(formatter.labeled("header"), "{description} {ui_path}:")?;
                 if left_content.is_binary || right_content.is_binary {
                     writeln!(formatter.labeled("binary"), "    (binary)")?;
@@ -638,7 +648,7 @@ pub fn show_color_words_diff(
                     formatter.labeled("header"),
                     "Removed {description} {ui_path}:"
                 )?;
-                let left_content = diff_content(&path, left_value)?;
+                let left_content = diff_content(repo.store(), &path, left_value)?;
                 if left_content.is_empty() {
                     writeln!(formatter.labeled("empty"), "    (empty)")?;
                 } else if left_content.is_binary {
@@ -669,18 +679,19 @@ pub fn show_file_by_file_diff(
     path_converter: &RepoPathUiConverter,
 ) -> Result<(), DiffRenderError> {
     fn create_file(
+        store: &Store,
         path: &RepoPath,
         wc_dir: &Path,
         value: MaterializedTreeValue,
     ) -> Result<PathBuf, DiffRenderError> {
         let fs_path = path.to_fs_path(wc_dir);
         std::fs::create_dir_all(fs_path.parent().unwrap())?;
-        let content = diff_content(path, value)?;
+        let content = diff_content(store, path, value)?;
         std::fs::write(&fs_path, content.contents)?;
         Ok(fs_path)
     }
 
-    let temp_dir = new_utf8_temp_dir("jj-diff-")?;
+    let temp_dir = new_utf8_temp_dir(repo.settings().scratch_dir(), "diff-")?;
     let left_wc_dir = temp_dir.path().join("left");
     let right_wc_dir = temp_dir.path().join("right");
     let mut diff_stream = materialized_diff_stream(repo.store(), tree_diff);
@@ -701,8 +712,8 @@ pub fn show_file_by_file_diff(
                 }
                 _ => {}
             }
-            let left_path = create_file(&path, &left_wc_dir, left_value)?;
-            let right_path = create_file(&path, &right_wc_dir, right_value)?;
+            let left_path = create_file(repo.store(), &path, &left_wc_dir, left_value)?;
+            let right_path = create_file(repo.store(), &path, &right_wc_dir, right_value)?;
 
             invoke_external_diff(
                 ui,
@@ -728,6 +739,7 @@ struct GitDiffPart {
 }
 
 fn git_diff_part(
+    store: &Store,
     path: &RepoPath,
     value: MaterializedTreeValue,
 ) -> Result<GitDiffPart, DiffRenderError> {
@@ -756,7 +768,7 @@ fn git_diff_part(
         } => {
             mode = if executable { "100755" } else { "100644" };
             hash = id.hex();
-            content = file_content_for_diff(&mut reader)?;
+            content = file_content_for_diff(store, path, &id, &mut reader)?;
         }
         MaterializedTreeValue::Symlink { id, target } => {
             mode = "120000";
@@ -774,14 +786,14 @@ fn git_diff_part(
             content = FileContent::empty();
         }
         MaterializedTreeValue::Conflict {
-            id: _,
+            id,
             contents,
             executable,
         } => {
             mode = if executable { "100755" } else { "100644" };
             hash = DUMMY_HASH.to_owned();
             content = FileContent {
-                is_binary: false, // TODO: are we sure this is never binary?
+                is_binary: store.value_content_type(path, &id)? == ContentType::Binary,
                 contents,
             };
         }
@@ -1032,8 +1044,8 @@ pub fn show_git_diff(
         while let Some((path, diff)) = diff_stream.next().await {
             let path_string = path.as_internal_file_string();
             let (left_value, right_value) = diff?;
-            let left_part = git_diff_part(&path, left_value)?;
-            let right_part = git_diff_part(&path, right_value)?;
+            let left_part = git_diff_part(repo.store(), &path, left_value)?;
+            let right_part = git_diff_part(repo.store(), &path, right_value)?;
             formatter.with_label("file_header", |formatter| {
                 writeln!(formatter, "diff --git a/{path_string} b/{path_string}")?;
                 let left_hash = &left_part.hash;
@@ -1142,19 +1154,8 @@ fn get_diff_stat(
     // TODO: this matches git's behavior, which is to count the number of newlines
     // in the file. but that behavior seems unhelpful; no one really cares how
     // many `0x0a` characters are in an image.
-    let diff = Diff::by_line([&left_content.contents, &right_content.contents]);
-    let mut added = 0;
-    let mut removed = 0;
-    for hunk in diff.hunks() {
-        match hunk {
-            DiffHunk::Matching(_) => {}
-            DiffHunk::Different(contents) => {
-                let [left, right] = contents.try_into().unwrap();
-                removed += left.split_inclusive(|b| *b == b'\n').count();
-                added += right.split_inclusive(|b| *b == b'\n').count();
-            }
-        }
-    }
+    let (removed, added) =
+        diff::count_changed_lines(&left_content.contents, &right_content.contents);
     DiffStat {
         path,
         added,
@@ -1178,8 +1179,8 @@ pub fn show_diff_stat(
         while let Some((repo_path, diff)) = diff_stream.next().await {
             let (left, right) = diff?;
             let path = path_converter.format_file_path(&repo_path);
-            let left_content = diff_content(&repo_path, left)?;
-            let right_content = diff_content(&repo_path, right)?;
+            let left_content = diff_content(repo.store(), &repo_path, left)?;
+            let right_content = diff_content(repo.store(), &repo_path, right)?;
             max_path_width = max(max_path_width, path.width());
             let stat = get_diff_stat(path, &left_content, &right_content);
             max_diffs = max(max_diffs, stat.added + stat.removed);
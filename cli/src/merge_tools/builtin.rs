@@ -7,7 +7,7 @@ use itertools::Itertools;
 use jj_lib::backend::{BackendError, BackendResult, FileId, MergedTreeId, TreeValue};
 use jj_lib::conflicts::{materialize_tree_value, MaterializedTreeValue};
 use jj_lib::diff::{Diff, DiffHunk};
-use jj_lib::files::{self, ContentHunk, MergeResult};
+use jj_lib::files::{self, ContentHunk, ContentType, MergeResult};
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::Merge;
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
@@ -106,9 +106,13 @@ fn describe_binary(hash: Option<&str>, num_bytes: u64) -> String {
     }
 }
 
-fn buf_to_file_contents(hash: Option<String>, buf: Vec<u8>) -> FileContents {
+fn buf_to_file_contents(
+    hash: Option<String>,
+    buf: Vec<u8>,
+    content_type: ContentType,
+) -> FileContents {
     let num_bytes: u64 = buf.len().try_into().unwrap();
-    let text = if buf.contains(&0) {
+    let text = if content_type == ContentType::Binary {
         None
     } else {
         String::from_utf8(buf).ok()
@@ -165,7 +169,10 @@ fn read_file_contents(
             } else {
                 scm_record::FileMode(mode::NORMAL)
             };
-            let contents = buf_to_file_contents(Some(id.hex()), buf);
+            let content_type = store
+                .file_content_type(path, &id)
+                .map_err(BuiltinToolError::BackendError)?;
+            let contents = buf_to_file_contents(Some(id.hex()), buf, content_type);
             Ok(FileInfo {
                 file_mode,
                 contents,
@@ -193,12 +200,15 @@ fn read_file_contents(
             id: id.hex(),
         }),
         MaterializedTreeValue::Conflict {
-            id: _,
+            id,
             contents,
             executable: _,
         } => {
+            let content_type = store
+                .value_content_type(path, &id)
+                .map_err(BuiltinToolError::BackendError)?;
             // TODO: Render the ID somehow?
-            let contents = buf_to_file_contents(None, contents);
+            let contents = buf_to_file_contents(None, contents, content_type);
             Ok(FileInfo {
                 file_mode: scm_record::FileMode(mode::NORMAL),
                 contents,
@@ -517,11 +527,12 @@ pub fn edit_diff_builtin(
 
 fn make_merge_sections(
     merge_result: MergeResult,
+    content_type: ContentType,
 ) -> Result<Vec<scm_record::Section<'static>>, BuiltinToolError> {
     let mut sections = Vec::new();
     match merge_result {
         MergeResult::Resolved(ContentHunk(buf)) => {
-            let contents = buf_to_file_contents(None, buf);
+            let contents = buf_to_file_contents(None, buf, content_type);
             let section = match contents {
                 FileContents::Absent => None,
                 FileContents::Text {
@@ -602,8 +613,17 @@ pub fn edit_merge_builtin(
     content: Merge<ContentHunk>,
 ) -> Result<MergedTreeId, BuiltinToolError> {
     let slices = content.map(|ContentHunk(v)| v.as_slice());
+    let content_types = tree.store().content_types();
+    let content_type = if slices
+        .iter()
+        .any(|slice| content_types.classify(path, slice) == ContentType::Binary)
+    {
+        ContentType::Binary
+    } else {
+        ContentType::Text
+    };
     let merge_result = files::merge(&slices);
-    let sections = make_merge_sections(merge_result)?;
+    let sections = make_merge_sections(merge_result, content_type)?;
     let mut input = scm_record::helpers::CrosstermInput;
     let recorder = scm_record::Recorder::new(
         scm_record::RecordState {
@@ -1016,7 +1036,7 @@ mod tests {
             .unwrap();
         let slices = content.map(|ContentHunk(buf)| buf.as_slice());
         let merge_result = files::merge(&slices);
-        let sections = make_merge_sections(merge_result).unwrap();
+        let sections = make_merge_sections(merge_result, ContentType::Text).unwrap();
         insta::assert_debug_snapshot!(sections, @r###"
         [
             Changed {
@@ -12,6 +12,7 @@ use jj_lib::local_working_copy::{TreeState, TreeStateError};
 use jj_lib::matchers::Matcher;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::scratch_dir::ScratchDir;
 use jj_lib::store::Store;
 use jj_lib::working_copy::{CheckoutError, SnapshotOptions};
 use pollster::FutureExt;
@@ -87,8 +88,8 @@ fn check_out(
     Ok(tree_state)
 }
 
-pub(crate) fn new_utf8_temp_dir(prefix: &str) -> io::Result<TempDir> {
-    let temp_dir = tempfile::Builder::new().prefix(prefix).tempdir()?;
+pub(crate) fn new_utf8_temp_dir(scratch_dir: &ScratchDir, prefix: &str) -> io::Result<TempDir> {
+    let temp_dir = scratch_dir.new_dir(prefix)?;
     if temp_dir.path().to_str().is_none() {
         // Not using .display() as we know the path contains unprintable character
         let message = format!("path {:?} is not valid UTF-8", temp_dir.path());
@@ -125,6 +126,7 @@ pub(crate) enum DiffSide {
 /// in the sparse checkout patterns.
 pub(crate) fn check_out_trees(
     store: &Arc<Store>,
+    scratch_dir: &ScratchDir,
     left_tree: &MergedTree,
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
@@ -136,7 +138,7 @@ pub(crate) fn check_out_trees(
         .collect()
         .block_on();
 
-    let temp_dir = new_utf8_temp_dir("jj-diff-").map_err(DiffCheckoutError::SetUpDir)?;
+    let temp_dir = new_utf8_temp_dir(scratch_dir, "diff-").map_err(DiffCheckoutError::SetUpDir)?;
     let left_wc_dir = temp_dir.path().join("left");
     let left_state_dir = temp_dir.path().join("left_state");
     let right_wc_dir = temp_dir.path().join("right");
@@ -189,13 +191,21 @@ impl DiffEditWorkingCopies {
     /// sides readonly.
     pub fn check_out(
         store: &Arc<Store>,
+        scratch_dir: &ScratchDir,
         left_tree: &MergedTree,
         right_tree: &MergedTree,
         matcher: &dyn Matcher,
         output_is: Option<DiffSide>,
         instructions: Option<&str>,
     ) -> Result<Self, DiffEditError> {
-        let diff_wc = check_out_trees(store, left_tree, right_tree, matcher, output_is)?;
+        let diff_wc = check_out_trees(
+            store,
+            scratch_dir,
+            left_tree,
+            right_tree,
+            matcher,
+            output_is,
+        )?;
         let got_output_field = output_is.is_some();
 
         set_readonly_recursively(diff_wc.left_working_copy_path())
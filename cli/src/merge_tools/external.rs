@@ -11,6 +11,7 @@ use jj_lib::matchers::Matcher;
 use jj_lib::merge::{Merge, MergedTreeValue};
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::repo_path::RepoPath;
+use jj_lib::scratch_dir::ScratchDir;
 use pollster::FutureExt;
 use thiserror::Error;
 
@@ -136,6 +137,7 @@ pub enum ExternalToolError {
 
 pub fn run_mergetool_external(
     editor: &ExternalMergeTool,
+    scratch_dir: &ScratchDir,
     file_merge: Merge<Option<FileId>>,
     content: Merge<jj_lib::files::ContentHunk>,
     repo_path: &RepoPath,
@@ -158,7 +160,8 @@ pub fn run_mergetool_external(
         "output" => initial_output_content.as_slice(),
     };
 
-    let temp_dir = new_utf8_temp_dir("jj-resolve-").map_err(ExternalToolError::SetUpDir)?;
+    let temp_dir =
+        new_utf8_temp_dir(scratch_dir, "resolve-").map_err(ExternalToolError::SetUpDir)?;
     let suffix = repo_path
         .components()
         .last()
@@ -234,6 +237,7 @@ pub fn run_mergetool_external(
 
 pub fn edit_diff_external(
     editor: &ExternalMergeTool,
+    scratch_dir: &ScratchDir,
     left_tree: &MergedTree,
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
@@ -244,6 +248,7 @@ pub fn edit_diff_external(
     let store = left_tree.store();
     let diffedit_wc = DiffEditWorkingCopies::check_out(
         store,
+        scratch_dir,
         left_tree,
         right_tree,
         matcher,
@@ -278,9 +283,10 @@ pub fn generate_diff(
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
     tool: &ExternalMergeTool,
+    scratch_dir: &ScratchDir,
 ) -> Result<(), DiffGenerateError> {
     let store = left_tree.store();
-    let diff_wc = check_out_trees(store, left_tree, right_tree, matcher, None)?;
+    let diff_wc = check_out_trees(store, scratch_dir, left_tree, right_tree, matcher, None)?;
     set_readonly_recursively(diff_wc.left_working_copy_path())
         .map_err(ExternalToolError::SetUpDir)?;
     set_readonly_recursively(diff_wc.right_working_copy_path())
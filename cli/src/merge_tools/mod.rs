@@ -25,6 +25,7 @@ use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::matchers::Matcher;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::scratch_dir::ScratchDir;
 use jj_lib::settings::{ConfigResultExt as _, UserSettings};
 use jj_lib::working_copy::SnapshotError;
 use pollster::FutureExt;
@@ -169,6 +170,7 @@ pub fn get_external_tool_config(
 pub struct DiffEditor {
     tool: MergeTool,
     base_ignores: Arc<GitIgnoreFile>,
+    scratch_dir: ScratchDir,
     use_instructions: bool,
 }
 
@@ -179,10 +181,11 @@ impl DiffEditor {
         name: &str,
         settings: &UserSettings,
         base_ignores: Arc<GitIgnoreFile>,
+        scratch_dir: ScratchDir,
     ) -> Result<Self, MergeToolConfigError> {
         let tool = get_tool_config(settings, name)?
             .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_program(name)));
-        Self::new_inner(tool, settings, base_ignores)
+        Self::new_inner(tool, settings, base_ignores, scratch_dir)
     }
 
     /// Loads the default diff editor from the settings.
@@ -190,6 +193,7 @@ impl DiffEditor {
         ui: &Ui,
         settings: &UserSettings,
         base_ignores: Arc<GitIgnoreFile>,
+        scratch_dir: ScratchDir,
     ) -> Result<Self, MergeToolConfigError> {
         let args = editor_args_from_settings(ui, settings, "ui.diff-editor")?;
         let tool = if let CommandNameAndArgs::String(name) = &args {
@@ -198,17 +202,19 @@ impl DiffEditor {
             None
         }
         .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_edit_args(&args)));
-        Self::new_inner(tool, settings, base_ignores)
+        Self::new_inner(tool, settings, base_ignores, scratch_dir)
     }
 
     fn new_inner(
         tool: MergeTool,
         settings: &UserSettings,
         base_ignores: Arc<GitIgnoreFile>,
+        scratch_dir: ScratchDir,
     ) -> Result<Self, MergeToolConfigError> {
         Ok(DiffEditor {
             tool,
             base_ignores,
+            scratch_dir,
             use_instructions: settings.config().get_bool("ui.diff-instructions")?,
         })
     }
@@ -229,6 +235,7 @@ impl DiffEditor {
                 let instructions = self.use_instructions.then(format_instructions);
                 edit_diff_external(
                     editor,
+                    &self.scratch_dir,
                     left_tree,
                     right_tree,
                     matcher,
@@ -244,19 +251,28 @@ impl DiffEditor {
 #[derive(Clone, Debug)]
 pub struct MergeEditor {
     tool: MergeTool,
+    scratch_dir: ScratchDir,
 }
 
 impl MergeEditor {
     /// Creates 3-way merge editor of the given name, and loads parameters from
     /// the settings.
-    pub fn with_name(name: &str, settings: &UserSettings) -> Result<Self, MergeToolConfigError> {
+    pub fn with_name(
+        name: &str,
+        settings: &UserSettings,
+        scratch_dir: ScratchDir,
+    ) -> Result<Self, MergeToolConfigError> {
         let tool = get_tool_config(settings, name)?
             .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_program(name)));
-        Self::new_inner(name, tool)
+        Self::new_inner(name, tool, scratch_dir)
     }
 
     /// Loads the default 3-way merge editor from the settings.
-    pub fn from_settings(ui: &Ui, settings: &UserSettings) -> Result<Self, MergeToolConfigError> {
+    pub fn from_settings(
+        ui: &Ui,
+        settings: &UserSettings,
+        scratch_dir: ScratchDir,
+    ) -> Result<Self, MergeToolConfigError> {
         let args = editor_args_from_settings(ui, settings, "ui.merge-editor")?;
         let tool = if let CommandNameAndArgs::String(name) = &args {
             get_tool_config(settings, name)?
@@ -264,16 +280,20 @@ impl MergeEditor {
             None
         }
         .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_merge_args(&args)));
-        Self::new_inner(&args, tool)
+        Self::new_inner(&args, tool, scratch_dir)
     }
 
-    fn new_inner(name: impl ToString, tool: MergeTool) -> Result<Self, MergeToolConfigError> {
+    fn new_inner(
+        name: impl ToString,
+        tool: MergeTool,
+        scratch_dir: ScratchDir,
+    ) -> Result<Self, MergeToolConfigError> {
         if matches!(&tool, MergeTool::External(mergetool) if mergetool.merge_args.is_empty()) {
             return Err(MergeToolConfigError::MergeArgsNotConfigured {
                 tool_name: name.to_string(),
             });
         }
-        Ok(MergeEditor { tool })
+        Ok(MergeEditor { tool, scratch_dir })
     }
 
     /// Starts a merge editor for the specified file.
@@ -314,7 +334,13 @@ impl MergeEditor {
                 Ok(tree_id)
             }
             MergeTool::External(editor) => external::run_mergetool_external(
-                editor, file_merge, content, repo_path, conflict, tree,
+                editor,
+                &self.scratch_dir,
+                file_merge,
+                content,
+                repo_path,
+                conflict,
+                tree,
             ),
         }
     }
@@ -338,7 +364,13 @@ mod tests {
         let get = |name, config_text| {
             let config = config_from_string(config_text);
             let settings = UserSettings::from_config(config);
-            DiffEditor::with_name(name, &settings, GitIgnoreFile::empty()).map(|editor| editor.tool)
+            DiffEditor::with_name(
+                name,
+                &settings,
+                GitIgnoreFile::empty(),
+                ScratchDir::os_temp(),
+            )
+            .map(|editor| editor.tool)
         };
 
         insta::assert_debug_snapshot!(get(":builtin", "").unwrap(), @"Builtin");
@@ -397,8 +429,13 @@ mod tests {
             let config = config_from_string(text);
             let ui = Ui::with_config(&config).unwrap();
             let settings = UserSettings::from_config(config);
-            DiffEditor::from_settings(&ui, &settings, GitIgnoreFile::empty())
-                .map(|editor| editor.tool)
+            DiffEditor::from_settings(
+                &ui,
+                &settings,
+                GitIgnoreFile::empty(),
+                ScratchDir::os_temp(),
+            )
+            .map(|editor| editor.tool)
         };
 
         // Default
@@ -552,7 +589,7 @@ mod tests {
         let get = |name, config_text| {
             let config = config_from_string(config_text);
             let settings = UserSettings::from_config(config);
-            MergeEditor::with_name(name, &settings).map(|editor| editor.tool)
+            MergeEditor::with_name(name, &settings, ScratchDir::os_temp()).map(|editor| editor.tool)
         };
 
         insta::assert_debug_snapshot!(get(":builtin", "").unwrap(), @"Builtin");
@@ -601,7 +638,8 @@ mod tests {
             let config = config_from_string(text);
             let ui = Ui::with_config(&config).unwrap();
             let settings = UserSettings::from_config(config);
-            MergeEditor::from_settings(&ui, &settings).map(|editor| editor.tool)
+            MergeEditor::from_settings(&ui, &settings, ScratchDir::os_temp())
+                .map(|editor| editor.tool)
         };
 
         // Default
@@ -236,7 +236,7 @@ fn test_debug_operation_id() {
     let stdout =
         test_env.jj_cmd_success(&workspace_path, &["debug", "operation", "--display", "id"]);
     assert_snapshot!(filter_index_stats(&stdout), @r###"
-    b51416386f2685fd5493f2b20e8eec3c24a1776d9e1a7cb5ed7e30d2d9c88c0c1e1fe71b0b7358cba60de42533d1228ed9878f2f89817d892c803395ccf9fe92
+    848ee96f8124690f13c6fc0e6ac6b1d0323e84476e22862859c2ff5986a7e1fb655bc673e171e27e7b06e505da35b3681dc7cc5dec7105275fca3ff243d5b768
     "###
     );
 }
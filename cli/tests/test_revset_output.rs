@@ -208,7 +208,7 @@ fn test_bad_function_call() {
       |
       = Invalid string pattern
     2: Invalid string pattern kind "bad:"
-    Hint: Try prefixing with one of `exact:`, `glob:` or `substring:`
+    Hint: Try prefixing with one of `exact:`, `glob:`, `regex:` or `substring:`
     "###);
 
     let stderr = test_env.jj_cmd_failure(&repo_path, &["log", "-r", "root()::whatever()"]);
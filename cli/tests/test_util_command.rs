@@ -91,7 +91,7 @@ fn test_gc_operation_log() {
     // Now this doesn't work.
     let stderr = test_env.jj_cmd_failure(&repo_path, &["debug", "operation", &op_to_remove]);
     insta::assert_snapshot!(stderr, @r###"
-    Error: No operation ID matching "1708ccd0d25f313f1559dfd1c4d16f0424de23a58e946830b0f27eb1252ce9295fe018e03fa4356e6aa39520cd8b5d44b7688024428988fe4d015291a4706172"
+    Error: No operation ID matching "b0d37ba38aa22b79a39e83d4b5564f0cd4fab5ce154bb3b031368bd863b29ebf7d47c56240d8a951225ec91856a0e77ec96e316eb64b8a31aedd08c91e4942d2"
     "###);
 }
 
@@ -112,3 +112,29 @@ fn test_shell_completions() {
     test("nushell");
     test("zsh");
 }
+
+#[test]
+fn test_upgrade_repo() {
+    let test_env = TestEnvironment::default();
+    test_env.jj_cmd_ok(test_env.env_root(), &["git", "init", "repo"]);
+    let repo_path = test_env.env_root().join("repo");
+
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["util", "upgrade-repo"]);
+    insta::assert_snapshot!(stderr, @r###"
+    The repository is already up to date
+    "###);
+
+    let format_path = repo_path.join(".jj/repo/format");
+    std::fs::remove_file(&format_path).unwrap();
+    let (_stdout, stderr) = test_env.jj_cmd_ok(&repo_path, &["util", "upgrade-repo"]);
+    insta::assert_snapshot!(stderr, @r###"
+    Add repository ids to the store and the operation log
+    Upgraded the repository from format version 1 to 2
+    "###);
+
+    // Repos created before the format was versioned are upgraded implicitly,
+    // since the migrations only add optional files
+    std::fs::remove_file(&format_path).unwrap();
+    test_env.jj_cmd_ok(&repo_path, &["log"]);
+    assert_eq!(std::fs::read_to_string(&format_path).unwrap(), "2");
+}
@@ -0,0 +1,182 @@
+//! Benchmarks of the hot paths on repositories generated by
+//! `testutils::synthetic_repo()`.
+//!
+//! For output that other tools can parse, run
+//! `cargo bench --bench synthetic_repo_bench -- --output-format bencher`.
+//! Criterion also writes the estimates as JSON under `target/criterion/`.
+
+use std::fs;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use jj_lib::default_index::DefaultIndexStore;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::repo::Repo as _;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom as _;
+use rand::SeedableRng as _;
+use testutils::{load_repo_at_head, synthetic_repo, SyntheticRepo, SyntheticRepoSpec};
+
+fn specs() -> Vec<(&'static str, SyntheticRepoSpec)> {
+    vec![
+        (
+            "small",
+            SyntheticRepoSpec {
+                seed: 0,
+                n_commits: 100,
+                tree_width: 10,
+                tree_depth: 1,
+                file_size: 1000,
+                files_changed_per_commit: 3,
+            },
+        ),
+        (
+            "large",
+            SyntheticRepoSpec {
+                seed: 0,
+                n_commits: 1000,
+                tree_width: 10,
+                tree_depth: 2,
+                file_size: 4000,
+                files_changed_per_commit: 10,
+            },
+        ),
+    ]
+}
+
+fn bench_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index");
+    group.sample_size(10);
+    for (label, spec) in specs() {
+        let synthetic = synthetic_repo(&spec);
+        let repo = &synthetic.test_workspace.repo;
+        let index_store: &DefaultIndexStore = repo.index_store().as_any().downcast_ref().unwrap();
+        group.bench_function(BenchmarkId::new("build_cold", label), |b| {
+            b.iter(|| {
+                index_store.reinit().unwrap();
+                index_store
+                    .build_index_at_operation(repo.operation(), repo.store())
+                    .unwrap()
+            });
+        });
+        let settings = testutils::user_settings();
+        group.bench_function(BenchmarkId::new("load_warm", label), |b| {
+            b.iter(|| load_repo_at_head(&settings, repo.repo_path()));
+        });
+    }
+}
+
+fn bench_tree_diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_diff");
+    for (label, spec) in specs() {
+        let synthetic = synthetic_repo(&spec);
+        let first_tree = synthetic.commits.first().unwrap().tree().unwrap();
+        let last_tree = synthetic.commits.last().unwrap().tree().unwrap();
+        group.bench_function(BenchmarkId::new("first_to_last", label), |b| {
+            b.iter(|| first_tree.diff(&last_tree, &EverythingMatcher).count());
+        });
+    }
+}
+
+fn bench_checkout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checkout");
+    group.sample_size(10);
+    for (label, spec) in specs() {
+        let SyntheticRepo {
+            mut test_workspace,
+            commits,
+            ..
+        } = synthetic_repo(&spec);
+        let op_id = test_workspace.repo.op_id().clone();
+        let targets = [commits.first().unwrap(), commits.last().unwrap()];
+        let mut next = 0;
+        group.bench_function(BenchmarkId::new("first_and_last", label), |b| {
+            b.iter(|| {
+                next = 1 - next;
+                test_workspace
+                    .workspace
+                    .check_out(op_id.clone(), None, targets[next])
+                    .unwrap()
+            });
+        });
+    }
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot");
+    group.sample_size(10);
+    for (label, spec) in specs() {
+        let SyntheticRepo {
+            mut test_workspace,
+            commits,
+            file_paths,
+            ..
+        } = synthetic_repo(&spec);
+        let op_id = test_workspace.repo.op_id().clone();
+        test_workspace
+            .workspace
+            .check_out(op_id, None, commits.last().unwrap())
+            .unwrap();
+        let workspace_root = test_workspace.workspace.workspace_root().clone();
+        let mut rng = StdRng::seed_from_u64(spec.seed);
+        let mut generation = 0;
+        for dirty_percent in [0, 10, 100] {
+            let dirty_count = file_paths.len() * dirty_percent / 100;
+            group.bench_function(
+                BenchmarkId::new(format!("{dirty_percent}%_dirty"), label),
+                |b| {
+                    b.iter_batched(
+                        || {
+                            generation += 1;
+                            for path in file_paths.choose_multiple(&mut rng, dirty_count) {
+                                let disk_path = path.to_fs_path(&workspace_root);
+                                fs::write(disk_path, format!("modified {generation}\n")).unwrap();
+                            }
+                        },
+                        |()| test_workspace.snapshot().unwrap(),
+                        BatchSize::PerIteration,
+                    );
+                },
+            );
+        }
+    }
+}
+
+fn bench_rebase_descendants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebase_descendants");
+    group.sample_size(10);
+    for (label, spec) in specs() {
+        let synthetic = synthetic_repo(&spec);
+        let repo = &synthetic.test_workspace.repo;
+        let settings = testutils::user_settings();
+        let root_commit = synthetic.commits.first().unwrap();
+        group.bench_function(BenchmarkId::new("rewrite_root", label), |b| {
+            b.iter_batched(
+                || {
+                    let mut tx = repo.start_transaction(&settings);
+                    tx.mut_repo()
+                        .rewrite_commit(&settings, root_commit)
+                        .set_description("rewritten")
+                        .write()
+                        .unwrap();
+                    tx
+                },
+                |mut tx| {
+                    let num_rebased = tx.mut_repo().rebase_descendants(&settings).unwrap();
+                    assert_eq!(num_rebased, synthetic.commits.len() - 1);
+                    tx
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_index,
+    bench_tree_diff,
+    bench_checkout,
+    bench_snapshot,
+    bench_rebase_descendants,
+);
+criterion_main!(benches);
@@ -18,7 +18,7 @@ pub fn generate_hash_impl(data: &Data) -> TokenStream {
     match *data {
         Data::Struct(ref data) => match data.fields {
             Fields::Named(ref fields) => {
-                let hash_statements = fields.named.iter().map(|f| {
+                let hash_statements = fields.named.iter().filter(|f| !is_ignored(f)).map(|f| {
                     let field_name = &f.ident;
                     let ty = &f.ty;
                     quote_spanned! {ty.span()=>
@@ -31,13 +31,18 @@ pub fn generate_hash_impl(data: &Data) -> TokenStream {
                 }
             }
             Fields::Unnamed(ref fields) => {
-                let hash_statements = fields.unnamed.iter().enumerate().map(|(i, f)| {
-                    let index = Index::from(i);
-                    let ty = &f.ty;
-                    quote_spanned! {ty.span() =>
-                        <#ty as ::jj_lib::content_hash::ContentHash>::hash(&self.#index, state);
-                    }
-                });
+                let hash_statements = fields
+                    .unnamed
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| !is_ignored(f))
+                    .map(|(i, f)| {
+                        let index = Index::from(i);
+                        let ty = &f.ty;
+                        quote_spanned! {ty.span() =>
+                            <#ty as ::jj_lib::content_hash::ContentHash>::hash(&self.#index, state);
+                        }
+                    });
                 quote! {
                     #(#hash_statements)*
                 }
@@ -92,6 +97,27 @@ pub fn generate_hash_impl(data: &Data) -> TokenStream {
     }
 }
 
+// Whether the struct field is annotated with `#[content_hash(ignore)]`.
+fn is_ignored(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("content_hash"))
+        .any(|attr| {
+            let mut ignore = false;
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("ignore") {
+                    ignore = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported content_hash attribute"))
+                }
+            })
+            .unwrap();
+            ignore
+        })
+}
+
 // The documentation for `ContentHash` specifies that the hash impl for each
 // enum variant should hash the ordinal number of the enum variant as a little
 // endian u32 before hashing the variant's fields, if any.
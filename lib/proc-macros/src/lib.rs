@@ -8,7 +8,11 @@ use syn::{parse_macro_input, DeriveInput};
 /// Derives the `ContentHash` trait for a struct by calling `ContentHash::hash`
 /// on each of the struct members in the order that they're declared. All
 /// members of the struct must implement the `ContentHash` trait.
-#[proc_macro_derive(ContentHash)]
+///
+/// Struct fields annotated with `#[content_hash(ignore)]` are not hashed. This
+/// is meant for data that's derived from the other fields, so it doesn't
+/// affect the identity of the value.
+#[proc_macro_derive(ContentHash, attributes(content_hash))]
 pub fn derive_content_hash(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -18,13 +18,16 @@ use std::any::Any;
 use std::collections::{BTreeMap, HashSet};
 use std::fmt::Debug;
 use std::io::Read;
+use std::path::PathBuf;
 use std::time::SystemTime;
 
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use itertools::Itertools as _;
 use thiserror::Error;
 
 use crate::content_hash::ContentHash;
+use crate::dag_walk::CycleError;
 use crate::index::Index;
 use crate::merge::Merge;
 use crate::object_id::{id_type, ObjectId};
@@ -133,6 +136,12 @@ pub struct Commit {
     pub root_tree: MergedTreeId,
     pub change_id: ChangeId,
     pub description: String,
+    /// Whether the description couldn't be decoded exactly from the backend's
+    /// storage (e.g. invalid bytes in its declared encoding), so `description`
+    /// contains replacement characters. Commits written by jj are always
+    /// stored as UTF-8.
+    #[content_hash(ignore)]
+    pub description_lossy: bool,
     pub author: Signature,
     pub committer: Signature,
     pub secure_sig: Option<SecureSig>,
@@ -247,6 +256,14 @@ pub enum BackendError {
         id: FileId,
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+    #[error(
+        "Object {hash} of type {object_type} is corrupt (its content hashes to {actual_hash})"
+    )]
+    Corrupt {
+        object_type: String,
+        hash: String,
+        actual_hash: String,
+    },
     #[error("Could not write object of type {object_type}")]
     WriteObject {
         object_type: &'static str,
@@ -260,6 +277,13 @@ pub enum BackendError {
     Unsupported(String),
 }
 
+impl From<CycleError<CommitId>> for BackendError {
+    fn from(err: CycleError<CommitId>) -> Self {
+        let ids = err.ids.iter().map(|id| id.hex()).sorted().join(", ");
+        BackendError::Other(format!("Commit graph contains a cycle involving commits {ids}").into())
+    }
+}
+
 pub type BackendResult<T> = Result<T, BackendError>;
 
 #[derive(ContentHash, Debug, PartialEq, Eq, Clone, Hash)]
@@ -383,12 +407,42 @@ pub fn make_root_commit(root_change_id: ChangeId, empty_tree_id: TreeId) -> Comm
         root_tree: MergedTreeId::Legacy(empty_tree_id),
         change_id: root_change_id,
         description: String::new(),
+        description_lossy: false,
         author: signature.clone(),
         committer: signature,
         secure_sig: None,
     }
 }
 
+/// Options for `Backend::compact()`.
+#[derive(Clone, Debug, Default)]
+pub struct CompactOptions {
+    /// Compress the compacted objects, where the backend supports it and it
+    /// makes them smaller.
+    pub compress: bool,
+    /// Remove the files holding objects that are now in packed storage.
+    /// Readers that don't find a loose file look in new packs, so this is safe
+    /// for backends that support it.
+    pub remove_loose_files: bool,
+}
+
+/// Statistics about a `Backend::compact()` run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    /// The number of objects written to new packed storage.
+    pub packed_objects: usize,
+    /// The total size of the new packed storage in bytes.
+    pub packed_bytes: u64,
+    /// Files holding objects that are now also in packed storage. They are not
+    /// removed by `compact()` unless `CompactOptions::remove_loose_files` is
+    /// set, because concurrent readers may still be about to read them. They
+    /// can be deleted once that's no longer a concern.
+    pub redundant_files: Vec<PathBuf>,
+    /// The number of files removed because of
+    /// `CompactOptions::remove_loose_files`.
+    pub removed_files: usize,
+}
+
 /// Defines the interface for commit backends.
 #[async_trait]
 pub trait Backend: Send + Sync + Debug {
@@ -482,4 +536,29 @@ pub trait Backend: Send + Sync + Debug {
     /// objects created after `keep_newer` will be preserved. This mitigates a
     /// risk of deleting new commits created concurrently by another process.
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()>;
+
+    /// Rewrites the stored objects into a more compact form, if the backend
+    /// has one. New objects may be written concurrently, and existing objects
+    /// must stay readable throughout.
+    ///
+    /// The default implementation returns `BackendError::Unsupported`.
+    fn compact(&self, _options: &CompactOptions) -> BackendResult<CompactStats> {
+        Err(BackendError::Unsupported(format!(
+            "The {} backend doesn't support compaction",
+            self.name()
+        )))
+    }
+
+    /// Makes objects in the object directories `object_dirs` readable through
+    /// this backend, as if they were stored in it. New objects are still
+    /// written to this backend's own storage. The alternates only apply to
+    /// this instance and aren't persisted.
+    ///
+    /// The default implementation returns `BackendError::Unsupported`.
+    fn add_alternates(&self, _object_dirs: &[PathBuf]) -> BackendResult<()> {
+        Err(BackendError::Unsupported(format!(
+            "The {} backend doesn't support alternate object directories",
+            self.name()
+        )))
+    }
 }
@@ -17,13 +17,20 @@
 use std::cmp::Ordering;
 use std::fmt::{Debug, Error, Formatter};
 use std::hash::{Hash, Hasher};
+use std::io::Read as _;
 use std::sync::Arc;
 
 use itertools::Itertools;
 
-use crate::backend::{self, BackendResult, ChangeId, CommitId, MergedTreeId, Signature};
+use crate::backend::{
+    self, BackendError, BackendResult, ChangeId, CommitId, MergedTreeId, Signature, TreeValue,
+};
+use crate::diff;
+use crate::files::ContentType;
+use crate::matchers::EverythingMatcher;
 use crate::merged_tree::MergedTree;
 use crate::repo::Repo;
+use crate::repo_path::RepoPath;
 use crate::rewrite::merge_commit_trees;
 use crate::signing::{SignResult, Verification};
 use crate::store::Store;
@@ -145,6 +152,12 @@ impl Commit {
         &self.data.description
     }
 
+    /// Whether the description was decoded lossily, e.g. from a git commit
+    /// with invalid bytes in its declared encoding.
+    pub fn description_was_lossy(&self) -> bool {
+        self.data.description_lossy
+    }
+
     pub fn author(&self) -> &Signature {
         &self.data.author
     }
@@ -159,6 +172,34 @@ impl Commit {
         Ok(self.description().is_empty() && self.is_empty(repo)?)
     }
 
+    /// Counts the lines this commit adds and removes relative to its parent
+    /// tree, as in `jj diff --stat`. Symlinks are compared by their targets.
+    /// Binary files and conflicts count as changed files, but their lines
+    /// aren't counted.
+    pub fn churn(&self, repo: &dyn Repo) -> BackendResult<Churn> {
+        let parent_tree = self.parent_tree(repo)?;
+        let tree = self.tree()?;
+        let mut churn = Churn::default();
+        for (path, diff) in parent_tree.diff(&tree, &EverythingMatcher) {
+            let (before, after) = diff?;
+            churn.files_changed += 1;
+            if self.store.value_content_type(&path, &before)? == ContentType::Binary
+                || self.store.value_content_type(&path, &after)? == ContentType::Binary
+            {
+                continue;
+            }
+            let (Some(before), Some(after)) = (before.as_resolved(), after.as_resolved()) else {
+                continue;
+            };
+            let left = read_resolved_contents(&self.store, &path, before.as_ref())?;
+            let right = read_resolved_contents(&self.store, &path, after.as_ref())?;
+            let (deletions, insertions) = diff::count_changed_lines(&left, &right);
+            churn.insertions += insertions;
+            churn.deletions += deletions;
+        }
+        Ok(churn)
+    }
+
     /// A quick way to just check if a signature is present.
     pub fn is_signed(&self) -> bool {
         self.data.secure_sig.is_some()
@@ -174,6 +215,39 @@ impl Commit {
     }
 }
 
+/// Line counts of the changes a commit makes. See [`Commit::churn()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Churn {
+    pub insertions: usize,
+    pub deletions: usize,
+    pub files_changed: usize,
+}
+
+/// Reads the contents of a file or the target of a symlink. Other values read
+/// as empty.
+fn read_resolved_contents(
+    store: &Store,
+    path: &RepoPath,
+    value: Option<&TreeValue>,
+) -> BackendResult<Vec<u8>> {
+    match value {
+        Some(TreeValue::File { id, .. }) => {
+            let mut contents = vec![];
+            store
+                .read_file(path, id)?
+                .read_to_end(&mut contents)
+                .map_err(|err| BackendError::ReadFile {
+                    path: path.to_owned(),
+                    id: id.clone(),
+                    source: err.into(),
+                })?;
+            Ok(contents)
+        }
+        Some(TreeValue::Symlink(id)) => Ok(store.read_symlink(path, id)?.into_bytes()),
+        _ => Ok(vec![]),
+    }
+}
+
 pub trait CommitIteratorExt<'c, I> {
     fn ids(self) -> impl Iterator<Item = &'c CommitId>;
 }
@@ -14,11 +14,15 @@
 
 #![allow(missing_docs)]
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
-use crate::backend::{self, BackendResult, ChangeId, CommitId, MergedTreeId, Signature, SigningFn};
+use crate::backend::{
+    self, BackendError, BackendResult, ChangeId, CommitId, MergedTreeId, Signature, SigningFn,
+};
 use crate::commit::Commit;
-use crate::repo::{MutableRepo, Repo};
+use crate::object_id::ObjectId as _;
+use crate::repo::{MutableRepo, Repo, RepoError};
 use crate::settings::{JJRng, SignSettings, UserSettings};
 use crate::signing::SignBehavior;
 
@@ -27,7 +31,12 @@ pub struct CommitBuilder<'repo> {
     mut_repo: &'repo mut MutableRepo,
     rng: Arc<JJRng>,
     commit: backend::Commit,
-    rewrite_source: Option<Commit>,
+    /// The commit being rewritten, and the change id the new commit must have
+    /// for the rewrite to be recorded. That's the change id of the rewritten
+    /// commit, or a new one if evolution is disabled.
+    rewrite_source: Option<(Commit, ChangeId)>,
+    /// Whether evolution is enabled. If not, no predecessors are recorded.
+    record_predecessors: bool,
     sign_settings: SignSettings,
 }
 
@@ -49,6 +58,7 @@ impl CommitBuilder<'_> {
             root_tree: tree_id,
             change_id,
             description: String::new(),
+            description_lossy: false,
             author: signature.clone(),
             committer: signature,
             secure_sig: None,
@@ -58,6 +68,7 @@ impl CommitBuilder<'_> {
             rng,
             commit,
             rewrite_source: None,
+            record_predecessors: settings.evolution_enabled(),
             sign_settings: settings.sign_settings(),
         }
     }
@@ -70,8 +81,21 @@ impl CommitBuilder<'_> {
         settings: &UserSettings,
         predecessor: &Commit,
     ) -> CommitBuilder<'repo> {
+        // Commit ids are just bytes, so a commit read from another repo would
+        // otherwise only be noticed when walking the rewritten graph.
+        debug_assert!(
+            Arc::ptr_eq(predecessor.store(), mut_repo.store()),
+            "Commit {} to rewrite was read from another store",
+            predecessor.id().hex()
+        );
+        let rng = settings.get_rng();
         let mut commit = predecessor.store_commit().clone();
         commit.predecessors = vec![predecessor.id().clone()];
+        if !settings.evolution_enabled() {
+            // The new commit won't be linked to the old one, so it starts a
+            // new change
+            commit.change_id = rng.new_change_id(mut_repo.store().change_id_length());
+        }
         commit.committer = settings.signature();
         // If the user had not configured a name and email before but now they have,
         // update the author fields with the new information.
@@ -96,11 +120,13 @@ impl CommitBuilder<'_> {
             commit.author.timestamp = commit.committer.timestamp.clone();
         }
 
+        let change_id = commit.change_id.clone();
         CommitBuilder {
             mut_repo,
             commit,
-            rng: settings.get_rng(),
-            rewrite_source: Some(predecessor.clone()),
+            rng,
+            rewrite_source: Some((predecessor.clone(), change_id)),
+            record_predecessors: settings.evolution_enabled(),
             sign_settings: settings.sign_settings(),
         }
     }
@@ -190,16 +216,111 @@ impl CommitBuilder<'_> {
         self
     }
 
-    pub fn write(mut self) -> BackendResult<Commit> {
+    /// Like `write()`, but first checks that the commit wouldn't corrupt the
+    /// commit graph: all parents and predecessors must exist, and a rewritten
+    /// commit can't be moved onto one of its own descendants (which would
+    /// make those descendants need rebasing onto themselves). A rewrite of an
+    /// immutable commit fails with [`RepoError::Immutable`].
+    pub fn try_write(self) -> Result<Commit, RepoError> {
+        self.validate()?;
+        Ok(self.write()?)
+    }
+
+    /// Like `try_write()`, but also fails without writing the commit if its
+    /// change id is already used by visible commits none of which is a
+    /// predecessor of it. Importers can use this to avoid creating divergent
+    /// changes by accident.
+    pub fn write_checked(self) -> Result<Commit, RepoError> {
+        self.validate()?;
+        if let Some(commit_ids) = self.mut_repo.resolve_change_id(&self.commit.change_id) {
+            if !commit_ids
+                .iter()
+                .any(|id| self.commit.predecessors.contains(id))
+            {
+                return Err(RepoError::ChangeIdCollision {
+                    change_id: self.commit.change_id.clone(),
+                    commit_ids,
+                });
+            }
+        }
+        Ok(self.write()?)
+    }
+
+    /// Writes the commit without the checks of `try_write()`, so it can also
+    /// rewrite immutable commits.
+    pub fn write(self) -> BackendResult<Commit> {
+        let (commit, _) = self.write_and_return_repo()?;
+        Ok(commit)
+    }
+
+    /// Like `write()`, but also returns how the write changed the set of
+    /// rewritten commits and of their descendants that need rebasing.
+    pub fn write_with_effects(self) -> BackendResult<(Commit, WriteEffects)> {
+        let obsolete_before: HashSet<CommitId> =
+            self.mut_repo.rewritten_commit_ids().cloned().collect();
+        let orphans_before: HashSet<CommitId> = self.mut_repo.orphan_ids()?.into_iter().collect();
+        let (commit, mut_repo) = self.write_and_return_repo()?;
+        let effects = WriteEffects {
+            newly_obsolete: mut_repo
+                .rewritten_commit_ids()
+                .filter(|id| !obsolete_before.contains(id))
+                .cloned()
+                .collect(),
+            new_orphans: mut_repo
+                .orphan_ids()?
+                .into_iter()
+                .filter(|id| !orphans_before.contains(id))
+                .collect(),
+        };
+        Ok((commit, effects))
+    }
+}
+
+impl<'repo> CommitBuilder<'repo> {
+    fn validate(&self) -> Result<(), RepoError> {
+        let index = self.mut_repo.index();
+        for id in self.commit.parents.iter().chain(&self.commit.predecessors) {
+            if !index.has_id(id) {
+                return Err(RepoError::MissingCommit(id.clone()));
+            }
+        }
+        if let Some((rewrite_source, change_id)) = &self.rewrite_source {
+            if change_id == &self.commit.change_id {
+                self.mut_repo.check_mutable(rewrite_source.id())?;
+                if let Some(parent_id) = self
+                    .commit
+                    .parents
+                    .iter()
+                    .find(|id| index.is_ancestor(rewrite_source.id(), id))
+                {
+                    return Err(RepoError::RewriteCycle {
+                        commit_id: rewrite_source.id().clone(),
+                        parent_id: parent_id.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_and_return_repo(mut self) -> BackendResult<(Commit, &'repo mut MutableRepo)> {
         let sign_settings = &self.sign_settings;
         let store = self.mut_repo.store();
-
+        if &self.commit.change_id == store.root_change_id() {
+            return Err(BackendError::Other(
+                "Cannot write a commit with the root change id".into(),
+            ));
+        }
         let mut signing_fn = (store.signer().can_sign() && sign_settings.should_sign(&self.commit))
             .then(|| -> Box<SigningFn> {
                 let store = store.clone();
                 Box::new(move |data: &_| store.signer().sign(data, sign_settings.key.as_deref()))
             });
 
+        if !self.record_predecessors {
+            self.commit.predecessors.clear();
+        }
+
         // Commit backend doesn't use secure_sig for writing and enforces it with an
         // assert, but sign_settings.should_sign check above will want to know
         // if we're rewriting a signed commit
@@ -208,12 +329,24 @@ impl CommitBuilder<'_> {
         let commit = self
             .mut_repo
             .write_commit(self.commit, signing_fn.as_deref_mut())?;
-        if let Some(rewrite_source) = self.rewrite_source {
-            if rewrite_source.change_id() == commit.change_id() {
+        if let Some((rewrite_source, change_id)) = self.rewrite_source {
+            if &change_id == commit.change_id() {
                 self.mut_repo
                     .set_rewritten_commit(rewrite_source.id().clone(), commit.id().clone());
             }
         }
-        Ok(commit)
+        Ok((commit, self.mut_repo))
     }
 }
+
+/// How [`CommitBuilder::write_with_effects()`] changed the pending rewrites of
+/// the repo.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct WriteEffects {
+    /// Commits that became rewritten by the write, i.e. the predecessor if the
+    /// new commit has the same change id.
+    pub newly_obsolete: Vec<CommitId>,
+    /// Visible commits that weren't but now are descendants of rewritten
+    /// commits, and so need rebasing. Children come before their parents.
+    pub new_orphans: Vec<CommitId>,
+}
@@ -114,6 +114,35 @@ pub async fn extract_as_single_hunk(
     Ok(builder.build())
 }
 
+/// Selects a single term of a conflict.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConflictSide {
+    /// The n-th added term. In a 3-way merge, `Add(0)` is the left side and
+    /// `Add(1)` is the right side.
+    Add(usize),
+    /// The n-th removed term, i.e. a merge base.
+    Remove(usize),
+}
+
+/// Reads the contents of a single term of `merge` without reading the other
+/// terms, which is cheaper than `extract_as_single_hunk()` when only one side
+/// is needed. Returns `None` if `merge` has no such term.
+pub async fn extract_conflict_side(
+    merge: &Merge<Option<FileId>>,
+    store: &Store,
+    path: &RepoPath,
+    side: ConflictSide,
+) -> BackendResult<Option<ContentHunk>> {
+    let term = match side {
+        ConflictSide::Add(index) => merge.get_add(index),
+        ConflictSide::Remove(index) => merge.get_remove(index),
+    };
+    match term {
+        Some(term) => Ok(Some(get_file_contents(store, path, term).await?)),
+        None => Ok(None),
+    }
+}
+
 /// A type similar to `MergedTreeValue` but with associated data to include in
 /// e.g. the working copy or in a diff.
 pub enum MaterializedTreeValue {
@@ -131,6 +131,18 @@ where
     }
 }
 
+impl<K> ContentHash for std::collections::BTreeSet<K>
+where
+    K: ContentHash,
+{
+    fn hash(&self, state: &mut impl DigestUpdate) {
+        state.update(&(self.len() as u64).to_le_bytes());
+        for k in self.iter() {
+            k.hash(state);
+        }
+    }
+}
+
 impl<K, V> ContentHash for std::collections::BTreeMap<K, V>
 where
     K: ContentHash,
@@ -193,6 +205,22 @@ mod tests {
         assert_ne!(hash(&Foo { x: 42 }), hash(&Foo { x: 12 }));
     }
 
+    #[test]
+    fn test_struct_ignored_field() {
+        #[derive(ContentHash)]
+        struct Foo {
+            x: i32,
+            #[allow(dead_code)]
+            #[content_hash(ignore)]
+            y: bool,
+        }
+        assert_eq!(
+            hash(&Foo { x: 42, y: false }),
+            hash(&Foo { x: 42, y: true })
+        );
+        assert_eq!(hash(&Foo { x: 42, y: false }), hash(&42i32));
+    }
+
     #[test]
     fn test_option_sanity() {
         assert_ne!(hash(&Some(42)), hash(&42));
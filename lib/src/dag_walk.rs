@@ -21,6 +21,13 @@ use std::{iter, mem};
 
 use itertools::Itertools as _;
 
+/// Error returned by the checked walks if the graph has a cycle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CycleError<ID> {
+    /// The nodes on the cycle, in no particular order.
+    pub ids: Vec<ID>,
+}
+
 /// Traverses nodes from `start` in depth-first order.
 pub fn dfs<T, ID, II, NI>(
     start: II,
@@ -91,9 +98,23 @@ where
 /// If `start` or `neighbors_fn()` yields an `Err`, this function terminates and
 /// returns the error.
 pub fn topo_order_forward_ok<T, ID, E, II, NI>(
+    start: II,
+    id_fn: impl Fn(&T) -> ID,
+    neighbors_fn: impl FnMut(&T) -> NI,
+) -> Result<Vec<T>, E>
+where
+    ID: Hash + Eq + Clone,
+    II: IntoIterator<Item = Result<T, E>>,
+    NI: IntoIterator<Item = Result<T, E>>,
+{
+    topo_order_forward_impl(start, id_fn, neighbors_fn, |_| panic!("graph has cycle"))
+}
+
+fn topo_order_forward_impl<T, ID, E, II, NI>(
     start: II,
     id_fn: impl Fn(&T) -> ID,
     mut neighbors_fn: impl FnMut(&T) -> NI,
+    cycle_err: impl FnOnce(Vec<ID>) -> E,
 ) -> Result<Vec<T>, E>
 where
     ID: Hash + Eq + Clone,
@@ -110,7 +131,17 @@ where
             continue;
         }
         if !neighbors_visited {
-            assert!(visiting.insert(id.clone()), "graph has cycle");
+            if !visiting.insert(id.clone()) {
+                // The nodes whose neighbors are being visited form the current
+                // path, which leads back to the node.
+                let cycle_ids = stack
+                    .iter()
+                    .filter(|(_, neighbors_visited)| *neighbors_visited)
+                    .map(|(node, _)| id_fn(node))
+                    .skip_while(|path_id| *path_id != id)
+                    .collect();
+                return Err(cycle_err(cycle_ids));
+            }
             let neighbors_iter = neighbors_fn(&node).into_iter();
             stack.reserve(neighbors_iter.size_hint().0 + 1);
             stack.push((node, true));
@@ -162,6 +193,25 @@ where
     Ok(result)
 }
 
+/// Like `topo_order_reverse_ok()`, but returns an error instead of panicking
+/// if the graph has a cycle.
+pub fn topo_order_reverse_checked<T, ID, E, II, NI>(
+    start: II,
+    id_fn: impl Fn(&T) -> ID,
+    neighbors_fn: impl FnMut(&T) -> NI,
+) -> Result<Vec<T>, E>
+where
+    ID: Hash + Eq + Clone,
+    E: From<CycleError<ID>>,
+    II: IntoIterator<Item = Result<T, E>>,
+    NI: IntoIterator<Item = Result<T, E>>,
+{
+    let mut result =
+        topo_order_forward_impl(start, id_fn, neighbors_fn, |ids| CycleError { ids }.into())?;
+    result.reverse();
+    Ok(result)
+}
+
 /// Like `topo_order_reverse()`, but can iterate linear DAG lazily.
 ///
 /// The DAG is supposed to be (mostly) topologically ordered by `T: Ord`.
@@ -374,9 +424,41 @@ where
 /// If `start` or `neighbors_fn()` yields an `Err`, this function terminates and
 /// returns the error.
 pub fn topo_order_reverse_ord_ok<T, ID, E, II, NI>(
+    start: II,
+    id_fn: impl Fn(&T) -> ID,
+    neighbors_fn: impl FnMut(&T) -> NI,
+) -> Result<Vec<T>, E>
+where
+    T: Ord,
+    ID: Hash + Eq + Clone,
+    II: IntoIterator<Item = Result<T, E>>,
+    NI: IntoIterator<Item = Result<T, E>>,
+{
+    topo_order_reverse_ord_impl(start, id_fn, neighbors_fn, |_| panic!("graph has cycle"))
+}
+
+/// Like `topo_order_reverse_ord_ok()`, but returns an error instead of
+/// panicking if the graph has a cycle.
+pub fn topo_order_reverse_ord_checked<T, ID, E, II, NI>(
+    start: II,
+    id_fn: impl Fn(&T) -> ID,
+    neighbors_fn: impl FnMut(&T) -> NI,
+) -> Result<Vec<T>, E>
+where
+    T: Ord,
+    ID: Hash + Eq + Clone,
+    E: From<CycleError<ID>>,
+    II: IntoIterator<Item = Result<T, E>>,
+    NI: IntoIterator<Item = Result<T, E>>,
+{
+    topo_order_reverse_ord_impl(start, id_fn, neighbors_fn, |ids| CycleError { ids }.into())
+}
+
+fn topo_order_reverse_ord_impl<T, ID, E, II, NI>(
     start: II,
     id_fn: impl Fn(&T) -> ID,
     mut neighbors_fn: impl FnMut(&T) -> NI,
+    cycle_err: impl FnOnce(Vec<ID>) -> E,
 ) -> Result<Vec<T>, E>
 where
     T: Ord,
@@ -449,7 +531,28 @@ where
         }
     }
 
-    assert!(inner_node_map.is_empty(), "graph has cycle");
+    if !inner_node_map.is_empty() {
+        // The remaining nodes are on cycles or reachable from them. Drop the
+        // ones that only lead out of the remainder until the cycles are left.
+        loop {
+            let dead_end_ids = neighbor_ids_map
+                .iter()
+                .filter(|(_, neighbor_ids)| {
+                    neighbor_ids
+                        .iter()
+                        .all(|id| !neighbor_ids_map.contains_key(id))
+                })
+                .map(|(id, _)| id.clone())
+                .collect_vec();
+            if dead_end_ids.is_empty() {
+                break;
+            }
+            for id in &dead_end_ids {
+                neighbor_ids_map.remove(id);
+            }
+        }
+        return Err(cycle_err(neighbor_ids_map.into_keys().collect()));
+    }
     Ok(result)
 }
 
@@ -1098,6 +1201,48 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_topo_order_reverse_checked_cycle() {
+        // This graph:
+        //  o D
+        //  |\
+        //  | o C
+        //  |/
+        //  o B
+        //  o A (to C)
+        //  o R
+
+        let neighbors = hashmap! {
+            'R' => vec![],
+            'A' => vec!['C', 'R'],
+            'B' => vec!['A'],
+            'C' => vec!['B'],
+            'D' => vec!['B', 'C'],
+        };
+        let id_fn = |node: &char| *node;
+        let neighbors_fn = |node: &char| {
+            neighbors[node]
+                .iter()
+                .copied()
+                .map(Ok::<_, CycleError<char>>)
+                .collect_vec()
+        };
+        let sorted_cycle = |result: Result<Vec<char>, CycleError<char>>| {
+            result.map_err(|err| err.ids.into_iter().sorted().collect_vec())
+        };
+
+        let result = topo_order_reverse_checked([Ok('D')], id_fn, neighbors_fn);
+        assert_eq!(sorted_cycle(result), Err(vec!['A', 'B', 'C']));
+        let result = topo_order_reverse_ord_checked([Ok('D')], id_fn, neighbors_fn);
+        assert_eq!(sorted_cycle(result), Err(vec!['A', 'B', 'C']));
+
+        // No cycle is reachable from R
+        let result = topo_order_reverse_checked([Ok('R')], id_fn, neighbors_fn);
+        assert_eq!(sorted_cycle(result), Ok(vec!['R']));
+        let result = topo_order_reverse_ord_checked([Ok('R')], id_fn, neighbors_fn);
+        assert_eq!(sorted_cycle(result), Ok(vec!['R']));
+    }
+
     #[test]
     fn test_topo_order_ok() {
         let neighbors = hashmap! {
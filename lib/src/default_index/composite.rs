@@ -15,7 +15,7 @@
 #![allow(missing_docs)]
 
 use std::cmp::{max, min, Ordering};
-use std::collections::{BTreeSet, BinaryHeap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashSet};
 use std::iter;
 use std::sync::{Arc, Mutex};
 
@@ -31,7 +31,7 @@ use super::rev_walk::AncestorsBitSet;
 use super::revset_engine;
 use crate::backend::{ChangeId, CommitId};
 use crate::hex_util;
-use crate::index::{AllHeadsForGcUnsupported, ChangeIdIndex, Index};
+use crate::index::{AllHeadsForGcUnsupported, ChangeIdIndex, CommitNotIndexed, Index};
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::revset::{ResolvedExpression, Revset, RevsetEvaluationError};
 use crate::store::Store;
@@ -339,6 +339,48 @@ impl CompositeIndex {
         self.heads_pos(result)
     }
 
+    /// Returns the number of entries reachable from `set1` but not from
+    /// `set2`, and vice versa.
+    pub(super) fn ahead_behind_pos(
+        &self,
+        set1: &[IndexPosition],
+        set2: &[IndexPosition],
+    ) -> (u64, u64) {
+        const SIDE1: u8 = 0b01;
+        const SIDE2: u8 = 0b10;
+        let is_one_side = |sides: u8| sides == SIDE1 || sides == SIDE2;
+        // Entries are visited from the highest position down, so an entry has
+        // been marked with the sides it's reachable from by all its children
+        // when it's popped. The walk stops once only entries reachable from
+        // both sides, i.e. the merge bases and their ancestors, are pending.
+        let mut pending: BTreeMap<IndexPosition, u8> = BTreeMap::new();
+        for &pos in set1 {
+            *pending.entry(pos).or_default() |= SIDE1;
+        }
+        for &pos in set2 {
+            *pending.entry(pos).or_default() |= SIDE2;
+        }
+        let mut num_pending_one_side = pending.values().filter(|&&s| is_one_side(s)).count();
+        let mut ahead = 0;
+        let mut behind = 0;
+        while num_pending_one_side > 0 {
+            let (pos, sides) = pending.pop_last().unwrap();
+            match sides {
+                SIDE1 => ahead += 1,
+                SIDE2 => behind += 1,
+                _ => {}
+            }
+            num_pending_one_side -= usize::from(is_one_side(sides));
+            for parent_pos in self.entry_by_pos(pos).parent_positions() {
+                let parent_sides = pending.entry(parent_pos).or_default();
+                num_pending_one_side -= usize::from(is_one_side(*parent_sides));
+                *parent_sides |= sides;
+                num_pending_one_side += usize::from(is_one_side(*parent_sides));
+            }
+        }
+        (ahead, behind)
+    }
+
     pub(super) fn all_heads(&self) -> impl Iterator<Item = CommitId> + '_ {
         self.all_heads_pos()
             .map(move |pos| self.entry_by_pos(pos).commit_id())
@@ -445,6 +487,11 @@ impl Index for &CompositeIndex {
         self.commit_id_to_pos(commit_id).is_some()
     }
 
+    fn parent_ids(&self, commit_id: &CommitId) -> Vec<CommitId> {
+        let entry = self.entry_by_id(commit_id).unwrap();
+        entry.parents().map(|parent| parent.commit_id()).collect()
+    }
+
     fn is_ancestor(&self, ancestor_id: &CommitId, descendant_id: &CommitId) -> bool {
         let ancestor_pos = self.commit_id_to_pos(ancestor_id).unwrap();
         let descendant_pos = self.commit_id_to_pos(descendant_id).unwrap();
@@ -466,6 +513,20 @@ impl Index for &CompositeIndex {
             .collect()
     }
 
+    fn ahead_behind_sets(
+        &self,
+        set1: &[CommitId],
+        set2: &[CommitId],
+    ) -> Result<(u64, u64), CommitNotIndexed> {
+        let to_pos = |id: &CommitId| {
+            self.commit_id_to_pos(id)
+                .ok_or_else(|| CommitNotIndexed(id.clone()))
+        };
+        let pos1: Vec<_> = set1.iter().map(to_pos).try_collect()?;
+        let pos2: Vec<_> = set2.iter().map(to_pos).try_collect()?;
+        Ok(self.ahead_behind_pos(&pos1, &pos2))
+    }
+
     fn all_heads_for_gc(
         &self,
     ) -> Result<Box<dyn Iterator<Item = CommitId> + '_>, AllHeadsForGcUnsupported> {
@@ -38,8 +38,10 @@ pub use self::store::{DefaultIndexStore, DefaultIndexStoreError, DefaultIndexSto
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use std::sync::Arc;
 
+    use assert_matches::assert_matches;
     use itertools::Itertools;
     use smallvec::smallvec_inline;
     use test_case::test_case;
@@ -50,7 +52,7 @@ mod tests {
     use super::*;
     use crate::backend::{ChangeId, CommitId};
     use crate::default_index::entry::{LocalPosition, SmallLocalPositionsVec};
-    use crate::index::Index;
+    use crate::index::{CommitNotIndexed, Index};
     use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 
     /// Generator of unique 16-byte CommitId excluding root id
@@ -1202,4 +1204,133 @@ mod tests {
             vec![id_3.clone(), id_5.clone()]
         );
     }
+
+    #[test]
+    fn test_ahead_behind() {
+        let mut new_change_id = change_id_generator();
+        let mut index = DefaultMutableIndex::full(3, 16);
+        // 5
+        // |\
+        // 4 | 3
+        // | |/
+        // 1 2
+        // |/
+        // 0
+        let id_0 = CommitId::from_hex("000000");
+        let id_1 = CommitId::from_hex("111111");
+        let id_2 = CommitId::from_hex("222222");
+        let id_3 = CommitId::from_hex("333333");
+        let id_4 = CommitId::from_hex("444444");
+        let id_5 = CommitId::from_hex("555555");
+        index.add_commit_data(id_0.clone(), new_change_id(), &[]);
+        index.add_commit_data(id_1.clone(), new_change_id(), &[id_0.clone()]);
+        index.add_commit_data(id_2.clone(), new_change_id(), &[id_0.clone()]);
+        index.add_commit_data(id_3.clone(), new_change_id(), &[id_2.clone()]);
+        index.add_commit_data(id_4.clone(), new_change_id(), &[id_1.clone()]);
+        index.add_commit_data(id_5.clone(), new_change_id(), &[id_4.clone(), id_2.clone()]);
+
+        // Same commit
+        assert_eq!(index.ahead_behind(&id_3, &id_3).unwrap(), (0, 0));
+        // Descendant and ancestor
+        assert_eq!(index.ahead_behind(&id_5, &id_0).unwrap(), (4, 0));
+        assert_eq!(index.ahead_behind(&id_0, &id_5).unwrap(), (0, 4));
+        assert_eq!(index.ahead_behind(&id_5, &id_2).unwrap(), (3, 0));
+        // Diverged
+        assert_eq!(index.ahead_behind(&id_5, &id_3).unwrap(), (3, 1));
+        assert_eq!(index.ahead_behind(&id_4, &id_3).unwrap(), (2, 2));
+        // Multiple commits on each side
+        assert_eq!(
+            index
+                .ahead_behind_sets(&[id_4.clone(), id_3.clone()], &[id_1.clone()])
+                .unwrap(),
+            (3, 0)
+        );
+        assert_eq!(
+            index
+                .ahead_behind_sets(&[id_4.clone(), id_3.clone()], &[id_5.clone()])
+                .unwrap(),
+            (1, 1)
+        );
+        assert_eq!(
+            index.ahead_behind_sets(&[], &[id_3.clone()]).unwrap(),
+            (0, 3)
+        );
+        // Range counts
+        assert_eq!(
+            index.count_range(&[id_5.clone()], &[id_3.clone()]).unwrap(),
+            3
+        );
+        assert_eq!(index.count_range(&[id_5.clone()], &[]).unwrap(), 5);
+        assert_eq!(
+            index.count_range(&[id_2], &[id_4, id_3.clone()]).unwrap(),
+            0
+        );
+        // Unknown commits
+        let id_unknown = CommitId::from_hex("999999");
+        assert_matches!(
+            index.ahead_behind(&id_unknown, &id_3),
+            Err(CommitNotIndexed(id)) if id == id_unknown
+        );
+    }
+
+    #[test]
+    fn test_ahead_behind_random_dags() {
+        // Compare against the brute-force set difference
+        use rand::prelude::*;
+        use rand_chacha::ChaCha20Rng;
+
+        fn ancestors(
+            parents: &[Vec<usize>],
+            heads: impl IntoIterator<Item = usize>,
+        ) -> HashSet<usize> {
+            let mut visited = HashSet::new();
+            let mut work = heads.into_iter().collect_vec();
+            while let Some(i) = work.pop() {
+                if visited.insert(i) {
+                    work.extend(&parents[i]);
+                }
+            }
+            visited
+        }
+
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut new_commit_id = commit_id_generator();
+            let mut new_change_id = change_id_generator();
+            let mut index = DefaultMutableIndex::full(16, 16);
+            let num_commits = rng.gen_range(1..300);
+            let mut ids: Vec<CommitId> = vec![];
+            let mut parents = vec![];
+            for i in 0..num_commits {
+                let num_parents = if i == 0 {
+                    0
+                } else {
+                    rng.gen_range(1..=3_usize.min(i))
+                };
+                let commit_parents = rand::seq::index::sample(&mut rng, i, num_parents).into_vec();
+                let id = new_commit_id();
+                let parent_ids = commit_parents.iter().map(|&j| ids[j].clone()).collect_vec();
+                index.add_commit_data(id.clone(), new_change_id(), &parent_ids);
+                ids.push(id);
+                parents.push(commit_parents);
+            }
+
+            for _ in 0..10 {
+                let amount1 = rng.gen_range(0..=2_usize.min(num_commits));
+                let amount2 = rng.gen_range(0..=2_usize.min(num_commits));
+                let set1 = rand::seq::index::sample(&mut rng, num_commits, amount1);
+                let set2 = rand::seq::index::sample(&mut rng, num_commits, amount2);
+                let ancestors1 = ancestors(&parents, set1.iter());
+                let ancestors2 = ancestors(&parents, set2.iter());
+                let expected = (
+                    ancestors1.difference(&ancestors2).count() as u64,
+                    ancestors2.difference(&ancestors1).count() as u64,
+                );
+                let ids1 = set1.iter().map(|i| ids[i].clone()).collect_vec();
+                let ids2 = set2.iter().map(|i| ids[i].clone()).collect_vec();
+                assert_eq!(index.ahead_behind_sets(&ids1, &ids2).unwrap(), expected);
+                assert_eq!(index.count_range(&ids1, &ids2).unwrap(), expected.0);
+            }
+        }
+    }
 }
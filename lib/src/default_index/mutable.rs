@@ -39,7 +39,9 @@ use super::readonly::{
 use crate::backend::{ChangeId, CommitId};
 use crate::commit::Commit;
 use crate::file_util::persist_content_addressed_temp_file;
-use crate::index::{AllHeadsForGcUnsupported, ChangeIdIndex, Index, MutableIndex, ReadonlyIndex};
+use crate::index::{
+    AllHeadsForGcUnsupported, ChangeIdIndex, CommitNotIndexed, Index, MutableIndex, ReadonlyIndex,
+};
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::revset::{ResolvedExpression, Revset, RevsetEvaluationError};
 use crate::store::Store;
@@ -331,6 +333,16 @@ impl MutableIndexSegment {
     }
 
     pub(super) fn save_in(self, dir: &Path) -> io::Result<Arc<ReadonlyIndexSegment>> {
+        self.into_readonly(Some(dir))
+    }
+
+    /// Like `save_in()`, but the segment is only kept in memory.
+    pub(super) fn into_readonly_in_memory(self) -> Arc<ReadonlyIndexSegment> {
+        self.into_readonly(None)
+            .expect("converting an index segment in memory shouldn't fail")
+    }
+
+    fn into_readonly(self, dir: Option<&Path>) -> io::Result<Arc<ReadonlyIndexSegment>> {
         if self.num_local_commits() == 0 && self.parent_file.is_some() {
             return Ok(self.parent_file.unwrap());
         }
@@ -343,12 +355,13 @@ impl MutableIndexSegment {
         let mut hasher = Blake2b512::new();
         hasher.update(&buf);
         let index_file_id_hex = hex::encode(hasher.finalize());
-        let index_file_path = dir.join(&index_file_id_hex);
-
-        let mut temp_file = NamedTempFile::new_in(dir)?;
-        let file = temp_file.as_file_mut();
-        file.write_all(&buf)?;
-        persist_content_addressed_temp_file(temp_file, index_file_path)?;
+        if let Some(dir) = dir {
+            let index_file_path = dir.join(&index_file_id_hex);
+            let mut temp_file = NamedTempFile::new_in(dir)?;
+            let file = temp_file.as_file_mut();
+            file.write_all(&buf)?;
+            persist_content_addressed_temp_file(temp_file, index_file_path)?;
+        }
 
         Ok(ReadonlyIndexSegment::load_with_parent_file(
             &mut &buf[local_entries_offset..],
@@ -464,6 +477,11 @@ impl DefaultMutableIndex {
     pub(super) fn squash_and_save_in(self, dir: &Path) -> io::Result<Arc<ReadonlyIndexSegment>> {
         self.0.maybe_squash_with_ancestors().save_in(dir)
     }
+
+    /// Converts the index to a readonly one without writing it anywhere.
+    pub(super) fn into_readonly_in_memory(self) -> Arc<ReadonlyIndexSegment> {
+        self.0.into_readonly_in_memory()
+    }
 }
 
 impl AsCompositeIndex for DefaultMutableIndex {
@@ -486,6 +504,10 @@ impl Index for DefaultMutableIndex {
         self.as_composite().has_id(commit_id)
     }
 
+    fn parent_ids(&self, commit_id: &CommitId) -> Vec<CommitId> {
+        self.as_composite().parent_ids(commit_id)
+    }
+
     fn is_ancestor(&self, ancestor_id: &CommitId, descendant_id: &CommitId) -> bool {
         self.as_composite().is_ancestor(ancestor_id, descendant_id)
     }
@@ -494,6 +516,14 @@ impl Index for DefaultMutableIndex {
         self.as_composite().common_ancestors(set1, set2)
     }
 
+    fn ahead_behind_sets(
+        &self,
+        set1: &[CommitId],
+        set2: &[CommitId],
+    ) -> Result<(u64, u64), CommitNotIndexed> {
+        self.as_composite().ahead_behind_sets(set1, set2)
+    }
+
     fn all_heads_for_gc(
         &self,
     ) -> Result<Box<dyn Iterator<Item = CommitId> + '_>, AllHeadsForGcUnsupported> {
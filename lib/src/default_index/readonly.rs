@@ -30,7 +30,9 @@ use super::composite::{AsCompositeIndex, ChangeIdIndexImpl, CompositeIndex, Inde
 use super::entry::{IndexPosition, LocalPosition, SmallIndexPositionsVec, SmallLocalPositionsVec};
 use super::mutable::DefaultMutableIndex;
 use crate::backend::{ChangeId, CommitId};
-use crate::index::{AllHeadsForGcUnsupported, ChangeIdIndex, Index, MutableIndex, ReadonlyIndex};
+use crate::index::{
+    AllHeadsForGcUnsupported, ChangeIdIndex, CommitNotIndexed, Index, MutableIndex, ReadonlyIndex,
+};
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::revset::{ResolvedExpression, Revset, RevsetEvaluationError};
 use crate::store::Store;
@@ -603,6 +605,10 @@ impl Index for DefaultReadonlyIndex {
         self.as_composite().has_id(commit_id)
     }
 
+    fn parent_ids(&self, commit_id: &CommitId) -> Vec<CommitId> {
+        self.as_composite().parent_ids(commit_id)
+    }
+
     fn is_ancestor(&self, ancestor_id: &CommitId, descendant_id: &CommitId) -> bool {
         self.as_composite().is_ancestor(ancestor_id, descendant_id)
     }
@@ -611,6 +617,14 @@ impl Index for DefaultReadonlyIndex {
         self.as_composite().common_ancestors(set1, set2)
     }
 
+    fn ahead_behind_sets(
+        &self,
+        set1: &[CommitId],
+        set2: &[CommitId],
+    ) -> Result<(u64, u64), CommitNotIndexed> {
+        self.as_composite().ahead_behind_sets(set1, set2)
+    }
+
     fn all_heads_for_gc(
         &self,
     ) -> Result<Box<dyn Iterator<Item = CommitId> + '_>, AllHeadsForGcUnsupported> {
@@ -51,6 +51,18 @@ pub(super) trait RevWalk<I: ?Sized> {
         }
     }
 
+    /// Wraps in adapter that yields at most `count` items. The underlying walk
+    /// isn't advanced once `count` items have been yielded.
+    fn take(self, count: usize) -> TakeRevWalk<Self>
+    where
+        Self: Sized,
+    {
+        TakeRevWalk {
+            walk: self,
+            remaining: count,
+        }
+    }
+
     /// Wraps in adapter that can peek one more item without consuming.
     fn peekable(self) -> PeekableRevWalk<I, Self>
     where
@@ -126,6 +138,31 @@ where
     }
 }
 
+#[derive(Clone, Debug)]
+#[must_use]
+pub(super) struct TakeRevWalk<W> {
+    walk: W,
+    remaining: usize,
+}
+
+impl<I: ?Sized, W: RevWalk<I>> RevWalk<I> for TakeRevWalk<W> {
+    type Item = W::Item;
+
+    fn next(&mut self, index: &I) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let item = self.walk.next(index);
+        // Stay fused if the underlying walk is exhausted early
+        self.remaining = if item.is_some() {
+            self.remaining - 1
+        } else {
+            0
+        };
+        item
+    }
+}
+
 #[derive(Clone, Debug)]
 #[must_use]
 pub(super) struct PeekableRevWalk<I: ?Sized, W: RevWalk<I>> {
@@ -831,6 +868,37 @@ mod tests {
         assert_eq!(filtered.next(&()), None);
     }
 
+    #[test]
+    fn test_take_rev_walk() {
+        let source = EagerRevWalk::new(vec![0, 1, 2, 3].into_iter());
+        let mut taken = RevWalk::<()>::take(source, 2);
+        assert_eq!(taken.next(&()), Some(0));
+        assert_eq!(taken.next(&()), Some(1));
+        assert_eq!(taken.next(&()), None);
+        assert_eq!(taken.next(&()), None);
+
+        // The source isn't advanced past the last item taken
+        let mut num_visited = 0;
+        let source = EagerRevWalk::new(vec![0, 1, 2, 3].into_iter()).filter(|_, _| {
+            num_visited += 1;
+            true
+        });
+        let taken = source.take(2);
+        assert_eq!(taken.attach(&()).collect_vec(), vec![0, 1]);
+        assert_eq!(num_visited, 2);
+
+        let source = EagerRevWalk::new(vec![0, 1].into_iter());
+        let mut taken = RevWalk::<()>::take(source, 3);
+        assert_eq!(taken.next(&()), Some(0));
+        assert_eq!(taken.next(&()), Some(1));
+        assert_eq!(taken.next(&()), None);
+        assert_eq!(taken.next(&()), None);
+
+        let source = EagerRevWalk::new(vec![0, 1].into_iter());
+        let mut taken = RevWalk::<()>::take(source, 0);
+        assert_eq!(taken.next(&()), None);
+    }
+
     #[test]
     fn test_walk_ancestors() {
         let mut new_change_id = change_id_generator();
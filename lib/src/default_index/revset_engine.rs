@@ -395,6 +395,39 @@ impl<S: ToPredicateFn> ToPredicateFn for NotInPredicate<S> {
     }
 }
 
+/// Revset containing the first `count` commits of `candidates`, in index
+/// order. Evaluation stops once that many commits have been found.
+#[derive(Debug)]
+struct LimitRevset<S> {
+    candidates: S,
+    count: usize,
+}
+
+impl<S: InternalRevset> InternalRevset for LimitRevset<S> {
+    fn positions<'a>(&self) -> BoxedRevWalk<'a>
+    where
+        Self: 'a,
+    {
+        Box::new(self.candidates.positions().take(self.count))
+    }
+
+    fn into_predicate<'a>(self: Box<Self>) -> Box<dyn ToPredicateFn + 'a>
+    where
+        Self: 'a,
+    {
+        self
+    }
+}
+
+impl<S: InternalRevset> ToPredicateFn for LimitRevset<S> {
+    fn to_predicate_fn<'a>(&self) -> BoxedPredicateFn<'a>
+    where
+        Self: 'a,
+    {
+        predicate_fn_from_rev_walk(self.candidates.positions().take(self.count))
+    }
+}
+
 #[derive(Debug)]
 struct UnionRevset<S1, S2> {
     set1: S1,
@@ -892,6 +925,10 @@ impl<'index> EvaluationContext<'index> {
                     self.take_latest_revset(candidate_set.as_ref(), *count),
                 ))
             }
+            ResolvedExpression::Limit { candidates, count } => Ok(Box::new(LimitRevset {
+                candidates: self.evaluate(candidates)?,
+                count: *count,
+            })),
             ResolvedExpression::Union(expression1, expression2) => {
                 let set1 = self.evaluate(expression1)?;
                 let set2 = self.evaluate(expression2)?;
@@ -1068,6 +1105,14 @@ fn build_predicate_fn(
                     || pattern.matches(&commit.committer().email)
             })
         }
+        RevsetFilterPredicate::CommitterDate(pattern) => {
+            let pattern = pattern.clone();
+            box_pure_predicate_fn(move |index, pos| {
+                let entry = index.entry_by_pos(pos);
+                let commit = store.get_commit(&entry.commit_id()).unwrap();
+                pattern.matches(&commit.committer().timestamp)
+            })
+        }
         RevsetFilterPredicate::File(expr) => {
             let matcher: Rc<dyn Matcher> = expr.to_matcher().into();
             box_pure_predicate_fn(move |index, pos| {
@@ -1193,6 +1238,21 @@ mod tests {
         assert!(!p(index, get_pos(&id_1)));
         assert!(!p(index, get_pos(&id_0)));
 
+        let set = LimitRevset {
+            candidates: make_set(&[&id_4, &id_2, &id_1, &id_0]),
+            count: 2,
+        };
+        assert_eq!(
+            set.positions().attach(index).collect_vec(),
+            make_positions(&[&id_4, &id_2])
+        );
+        let mut p = set.to_predicate_fn();
+        assert!(p(index, get_pos(&id_4)));
+        assert!(!p(index, get_pos(&id_3)));
+        assert!(p(index, get_pos(&id_2)));
+        assert!(!p(index, get_pos(&id_1)));
+        assert!(!p(index, get_pos(&id_0)));
+
         let set = UnionRevset {
             set1: make_set(&[&id_4, &id_2]),
             set2: make_set(&[&id_3, &id_2, &id_1]),
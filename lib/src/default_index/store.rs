@@ -29,7 +29,7 @@ use super::mutable::DefaultMutableIndex;
 use super::readonly::{DefaultReadonlyIndex, ReadonlyIndexLoadError, ReadonlyIndexSegment};
 use crate::backend::{BackendError, BackendInitError, CommitId};
 use crate::commit::CommitByCommitterTimestamp;
-use crate::dag_walk;
+use crate::dag_walk::{self, CycleError};
 use crate::file_util::{self, persist_content_addressed_temp_file, IoResultExt as _, PathError};
 use crate::index::{
     Index, IndexReadError, IndexStore, IndexWriteError, MutableIndex, ReadonlyIndex,
@@ -74,10 +74,23 @@ pub enum DefaultIndexStoreError {
         op_id: OperationId,
         source: BackendError,
     },
+    #[error(
+        "Commit graph contains a cycle involving commits {ids}",
+        ids = commits.iter().map(|id| id.hex()).join(", ")
+    )]
+    CycleDetected { commits: Vec<CommitId> },
     #[error(transparent)]
     OpStore(#[from] OpStoreError),
 }
 
+impl From<CycleError<CommitId>> for DefaultIndexStoreError {
+    fn from(err: CycleError<CommitId>) -> Self {
+        let mut commits = err.ids;
+        commits.sort();
+        DefaultIndexStoreError::CycleDetected { commits }
+    }
+}
+
 #[derive(Debug)]
 pub struct DefaultIndexStore {
     dir: PathBuf,
@@ -165,15 +178,19 @@ impl DefaultIndexStore {
         operation: &Operation,
         store: &Arc<Store>,
     ) -> Result<DefaultReadonlyIndex, DefaultIndexStoreError> {
-        let index_segment = self.build_index_segments_at_operation(operation, store)?;
+        let index_segment = self.build_index_segments_at_operation(operation, store, true)?;
         Ok(DefaultReadonlyIndex::from_segment(index_segment))
     }
 
+    /// Builds the index segments for `operation` on top of the segments of the
+    /// latest indexed ancestor operation. Unless `persist` is true, the new
+    /// segment is only kept in memory.
     #[tracing::instrument(skip(self, store))]
     fn build_index_segments_at_operation(
         &self,
         operation: &Operation,
         store: &Arc<Store>,
+        persist: bool,
     ) -> Result<Arc<ReadonlyIndexSegment>, DefaultIndexStoreError> {
         let view = operation.view()?;
         let operations_dir = self.operations_dir();
@@ -230,7 +247,8 @@ impl DefaultIndexStore {
             "indexing commits reachable from historical heads"
         );
         // Build a list of ancestors of heads where parents and predecessors come after
-        // the commit itself.
+        // the commit itself. Since commits are indexed after their parents, this
+        // fails if they form a cycle.
         let parent_file_has_id = |id: &CommitId| {
             maybe_parent_file
                 .as_ref()
@@ -246,7 +264,7 @@ impl DefaultIndexStore {
                 Err(source) => Err(DefaultIndexStoreError::IndexCommits { op_id, source }),
             }
         };
-        let commits = dag_walk::topo_order_reverse_ord_ok(
+        let commits = dag_walk::topo_order_reverse_ord_checked(
             historical_heads
                 .iter()
                 .filter(|&(commit_id, _)| !parent_file_has_id(commit_id))
@@ -263,6 +281,9 @@ impl DefaultIndexStore {
             mutable_index.add_commit(commit);
         }
 
+        if !persist {
+            return Ok(mutable_index.into_readonly_in_memory());
+        }
         let index_file = self.save_mutable_index(mutable_index, operation.id())?;
         tracing::info!(
             ?index_file,
@@ -325,7 +346,7 @@ impl IndexStore for DefaultIndexStore {
             Err(DefaultIndexStoreError::LoadAssociation(err))
                 if err.kind() == io::ErrorKind::NotFound =>
             {
-                self.build_index_segments_at_operation(op, store)
+                self.build_index_segments_at_operation(op, store, true)
             }
             Err(DefaultIndexStoreError::LoadIndex(err)) if err.is_corrupt_or_not_found() => {
                 // If the index was corrupt (maybe it was written in a different format),
@@ -348,8 +369,30 @@ impl IndexStore for DefaultIndexStore {
                     }
                 }
                 self.reinit().map_err(|err| IndexReadError(err.into()))?;
-                self.build_index_segments_at_operation(op, store)
+                self.build_index_segments_at_operation(op, store, true)
+            }
+            result => result,
+        }
+        .map_err(|err| IndexReadError(err.into()))?;
+        Ok(Box::new(DefaultReadonlyIndex::from_segment(index_segment)))
+    }
+
+    fn get_index_at_op_in_memory(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError> {
+        let index_segment = match self.load_index_segments_at_operation(
+            op.id(),
+            store.commit_id_length(),
+            store.change_id_length(),
+        ) {
+            Err(DefaultIndexStoreError::LoadAssociation(err))
+                if err.kind() == io::ErrorKind::NotFound =>
+            {
+                self.build_index_segments_at_operation(op, store, false)
             }
+            // A corrupt index can't be rebuilt without removing it first
             result => result,
         }
         .map_err(|err| IndexReadError(err.into()))?;
@@ -15,13 +15,22 @@
 #![allow(missing_docs)]
 
 use std::cmp::{max, min, Ordering};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
 use std::ops::Range;
 use std::{iter, slice};
 
 use bstr::BStr;
 use itertools::Itertools;
 
+use crate::backend::{BackendError, BackendResult, FileId, TreeValue};
+use crate::files::ContentType;
+use crate::matchers::EverythingMatcher;
+use crate::merge::MergedTreeValue;
+use crate::merged_tree::MergedTree;
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::store::Store;
+
 pub fn find_line_ranges(text: &[u8]) -> Vec<Range<usize>> {
     text.split_inclusive(|b| *b == b'\n')
         .scan(0, |total, line| {
@@ -666,6 +675,385 @@ pub fn diff<'a>(left: &'a [u8], right: &'a [u8]) -> Vec<DiffHunk<'a>> {
         .collect_vec()
 }
 
+/// Counts the lines removed from `left` and added in `right`, as in
+/// `jj diff --stat`. Changed partial lines count as whole lines.
+pub fn count_changed_lines(left: &[u8], right: &[u8]) -> (usize, usize) {
+    let diff = Diff::by_line([left, right]);
+    let mut removed = 0;
+    let mut added = 0;
+    for hunk in diff.hunks() {
+        match hunk {
+            DiffHunk::Matching(_) => {}
+            DiffHunk::Different(contents) => {
+                let [left, right] = contents.try_into().unwrap();
+                removed += left.split_inclusive(|b| *b == b'\n').count();
+                added += right.split_inclusive(|b| *b == b'\n').count();
+            }
+        }
+    }
+    (removed, added)
+}
+
+/// Which differences to ignore when comparing file contents, like Git's
+/// `--ignore-all-space`, `--ignore-space-change` and `--ignore-blank-lines`.
+/// Carriage returns count as whitespace, so line ending changes are ignored
+/// along with the other whitespace. This only affects comparison, not how
+/// contents are stored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Ignore all whitespace within lines.
+    pub ignore_all_whitespace: bool,
+    /// Ignore changes in the amount of whitespace, and whitespace at the end
+    /// of lines.
+    pub ignore_whitespace_amount: bool,
+    /// Ignore lines that are empty or contain only whitespace.
+    pub ignore_blank_lines: bool,
+}
+
+impl DiffOptions {
+    fn ignores_anything(&self) -> bool {
+        self.ignore_all_whitespace || self.ignore_whitespace_amount || self.ignore_blank_lines
+    }
+
+    /// Returns the contents with the ignored differences normalized away.
+    /// Every line ends with a newline in the result.
+    pub fn normalize(&self, contents: &[u8]) -> Vec<u8> {
+        if !self.ignores_anything() {
+            return contents.to_vec();
+        }
+        let mut normalized = Vec::with_capacity(contents.len());
+        for line in contents.split_inclusive(|b| *b == b'\n') {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            if self.ignore_blank_lines && line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            if self.ignore_all_whitespace {
+                normalized.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+            } else if self.ignore_whitespace_amount {
+                let mut words = line
+                    .split(u8::is_ascii_whitespace)
+                    .enumerate()
+                    .filter(|(i, word)| *i == 0 || !word.is_empty())
+                    .map(|(_, word)| word)
+                    .peekable();
+                // A leading whitespace run becomes a single space
+                while let Some(word) = words.next() {
+                    normalized.extend_from_slice(word);
+                    if words.peek().is_some() {
+                        normalized.push(b' ');
+                    }
+                }
+            } else {
+                normalized.extend_from_slice(line);
+            }
+            normalized.push(b'\n');
+        }
+        normalized
+    }
+}
+
+/// How two file contents compare under some [`DiffOptions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentComparison {
+    Identical,
+    /// The contents differ, but only in ways the options ignore. Callers may
+    /// still want to show the file, e.g. dimmed.
+    OnlyIgnoredChanges,
+    Different,
+}
+
+/// Compares two file contents, ignoring the differences `options` ignores.
+pub fn compare_contents(left: &[u8], right: &[u8], options: &DiffOptions) -> ContentComparison {
+    if left == right {
+        ContentComparison::Identical
+    } else if options.ignores_anything() && options.normalize(left) == options.normalize(right) {
+        ContentComparison::OnlyIgnoredChanges
+    } else {
+        ContentComparison::Different
+    }
+}
+
+/// Summary of the changes to a file between two trees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: RepoPathBuf,
+    pub added_lines: usize,
+    pub removed_lines: usize,
+    /// Whether either side of the file is classified as binary by
+    /// [`Store::file_content_type()`](crate::store::Store::file_content_type).
+    /// Lines aren't counted for binary files.
+    pub is_binary: bool,
+    /// Whether the file only has changes ignored by the [`DiffOptions`]. No
+    /// lines are counted for such files.
+    pub only_ignored_changes: bool,
+}
+
+/// Returns whether the value is a file classified as binary by the store.
+fn is_binary_value(
+    store: &Store,
+    path: &RepoPath,
+    value: Option<&TreeValue>,
+) -> BackendResult<bool> {
+    match value {
+        Some(TreeValue::File { id, .. }) => is_binary_file(store, path, id),
+        _ => Ok(false),
+    }
+}
+
+fn is_binary_file(store: &Store, path: &RepoPath, id: &FileId) -> BackendResult<bool> {
+    Ok(store.file_content_type(path, id)? == ContentType::Binary)
+}
+
+/// Reads up to `limit` bytes of a file. Returns `None` if the file is larger.
+fn read_file_contents(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+    limit: u64,
+) -> BackendResult<Option<Vec<u8>>> {
+    let mut contents = vec![];
+    store
+        .read_file(path, id)?
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut contents)
+        .map_err(|err| BackendError::ReadFile {
+            path: path.to_owned(),
+            id: id.clone(),
+            source: err.into(),
+        })?;
+    Ok((contents.len() as u64 <= limit).then_some(contents))
+}
+
+/// Reads the contents of a file or the target of a symlink. Other values read
+/// as empty.
+fn read_value_contents(
+    store: &Store,
+    path: &RepoPath,
+    value: Option<&TreeValue>,
+) -> BackendResult<Vec<u8>> {
+    match value {
+        Some(TreeValue::File { id, .. }) => {
+            Ok(read_file_contents(store, path, id, u64::MAX)?.unwrap())
+        }
+        Some(TreeValue::Symlink(id)) => Ok(store.read_symlink(path, id)?.into_bytes()),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Computes per-file line counts of the changes from `base` to `other`, sorted
+/// by path. Symlinks are compared by their targets, and conflicts count as
+/// empty files.
+pub fn stat(base: &MergedTree, other: &MergedTree) -> BackendResult<Vec<FileStat>> {
+    stat_with_options(base, other, &DiffOptions::default())
+}
+
+/// Like `stat()`, but ignores the differences `options` ignores when counting
+/// lines. Binary files are always compared exactly.
+pub fn stat_with_options(
+    base: &MergedTree,
+    other: &MergedTree,
+    options: &DiffOptions,
+) -> BackendResult<Vec<FileStat>> {
+    let store = base.store();
+    let mut stats = vec![];
+    for (path, diff) in base.diff(other, &EverythingMatcher) {
+        let (before, after) = diff?;
+        let before = before.as_resolved().and_then(Option::as_ref);
+        let after = after.as_resolved().and_then(Option::as_ref);
+        let left = read_value_contents(store, &path, before)?;
+        let right = read_value_contents(store, &path, after)?;
+        let is_binary =
+            is_binary_value(store, &path, before)? || is_binary_value(store, &path, after)?;
+        let only_ignored_changes = !is_binary
+            && compare_contents(&left, &right, options) == ContentComparison::OnlyIgnoredChanges;
+        let (removed_lines, added_lines) = if is_binary || only_ignored_changes {
+            (0, 0)
+        } else {
+            count_changed_lines(&options.normalize(&left), &options.normalize(&right))
+        };
+        stats.push(FileStat {
+            path,
+            added_lines,
+            removed_lines,
+            is_binary,
+            only_ignored_changes,
+        });
+    }
+    Ok(stats)
+}
+
+/// A changed path between two trees, with removed and added files of similar
+/// contents paired up as renames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    Added(RepoPathBuf),
+    Removed(RepoPathBuf),
+    Modified(RepoPathBuf),
+    /// The file at `from` was removed and a file with similar contents was
+    /// added at `to`. The similarity is a percentage.
+    Rename {
+        from: RepoPathBuf,
+        to: RepoPathBuf,
+        similarity: u8,
+    },
+}
+
+/// The similarity percentage from which `changes_with_renames()` pairs a
+/// removed and an added file by default, as in Git.
+pub const DEFAULT_RENAME_SIMILARITY: u8 = 50;
+
+/// `changes_with_renames()` compares the contents of at most this many pairs
+/// of removed and added files. If there are more pairs, only files with
+/// identical contents are paired up.
+pub const MAX_RENAME_CANDIDATE_PAIRS: usize = 10_000;
+
+/// Files larger than this many bytes are only paired up as renames by
+/// `changes_with_renames()` if their contents are identical.
+pub const MAX_RENAME_FILE_SIZE: u64 = 1 << 20;
+
+/// Returns how similar the contents are as a percentage of lines in common.
+/// Binary files are only similar if they're identical, and empty files are
+/// never similar so they aren't all paired with each other.
+fn content_similarity(left: &[u8], right: &[u8], is_binary: bool) -> u8 {
+    if left.is_empty() || right.is_empty() {
+        0
+    } else if left == right {
+        100
+    } else if is_binary {
+        0
+    } else {
+        let left_lines = left.split_inclusive(|b| *b == b'\n').count();
+        let right_lines = right.split_inclusive(|b| *b == b'\n').count();
+        let (removed, _added) = count_changed_lines(left, right);
+        let common = left_lines.saturating_sub(removed);
+        (common * 200 / (left_lines + right_lines)) as u8
+    }
+}
+
+/// Reads a removed or added file for comparison with the files on the other
+/// side. Returns `None` for files larger than `MAX_RENAME_FILE_SIZE`.
+fn read_rename_candidate(
+    store: &Store,
+    path: &RepoPath,
+    id: &FileId,
+) -> BackendResult<Option<(Vec<u8>, bool)>> {
+    let Some(contents) = read_file_contents(store, path, id, MAX_RENAME_FILE_SIZE)? else {
+        return Ok(None);
+    };
+    Ok(Some((contents, is_binary_file(store, path, id)?)))
+}
+
+/// Lists the paths that differ between `base` and `other`, sorted by path
+/// (by the new path for renames). A removed file and an added file are
+/// reported as a rename if their contents are at least `min_similarity`
+/// percent similar. Each file is paired at most once, most similar pairs
+/// first. See `MAX_RENAME_CANDIDATE_PAIRS` and `MAX_RENAME_FILE_SIZE` for the
+/// limits on the comparisons.
+pub fn changes_with_renames(
+    base: &MergedTree,
+    other: &MergedTree,
+    min_similarity: u8,
+) -> BackendResult<Vec<FileChange>> {
+    let store = base.store();
+    let entries: Vec<_> = base
+        .diff(other, &EverythingMatcher)
+        .map(|(path, diff)| diff.map(|(before, after)| (path, before, after)))
+        .try_collect()?;
+    let resolved_file_id = |value: &MergedTreeValue| match value.as_resolved() {
+        Some(Some(TreeValue::File { id, .. })) => Some(id.clone()),
+        _ => None,
+    };
+    let removed = entries
+        .iter()
+        .filter(|(_, _, after)| after.is_absent())
+        .filter_map(|(path, before, _)| Some((path, resolved_file_id(before)?)))
+        .collect_vec();
+    let added = entries
+        .iter()
+        .filter(|(_, before, _)| before.is_absent())
+        .filter_map(|(path, _, after)| Some((path, resolved_file_id(after)?)))
+        .collect_vec();
+
+    // Files with identical contents are found by id, so they can be paired up
+    // without reading more than their first byte
+    let mut added_by_id: HashMap<&FileId, Vec<&RepoPathBuf>> = HashMap::new();
+    for (path, id) in &added {
+        added_by_id.entry(id).or_default().push(path);
+    }
+    let mut candidates = vec![];
+    for (from, id) in &removed {
+        let Some(tos) = added_by_id.get(id) else {
+            continue;
+        };
+        let is_empty = read_file_contents(store, from, id, 0)?.is_some();
+        if !is_empty && min_similarity <= 100 {
+            candidates.extend(tos.iter().map(|to| (100, *from, *to)));
+        }
+    }
+    if removed.len().saturating_mul(added.len()) <= MAX_RENAME_CANDIDATE_PAIRS {
+        let read_candidates = |files: &[(&RepoPathBuf, FileId)]| {
+            files
+                .iter()
+                .map(|(path, id)| read_rename_candidate(store, path, id))
+                .collect::<BackendResult<Vec<_>>>()
+        };
+        let removed_contents = read_candidates(&removed)?;
+        let added_contents = read_candidates(&added)?;
+        for ((from, left_id), left) in removed.iter().zip(&removed_contents) {
+            let Some((left, left_is_binary)) = left else {
+                continue;
+            };
+            for ((to, right_id), right) in added.iter().zip(&added_contents) {
+                let Some((right, right_is_binary)) = right else {
+                    continue;
+                };
+                if left_id == right_id {
+                    continue;
+                }
+                let similarity =
+                    content_similarity(left, right, *left_is_binary || *right_is_binary);
+                if similarity > 0 && similarity >= min_similarity {
+                    candidates.push((similarity, *from, *to));
+                }
+            }
+        }
+    }
+    // Most similar first, then by path for determinism
+    candidates.sort_by(|(s1, from1, to1), (s2, from2, to2)| {
+        s2.cmp(s1).then_with(|| (from1, to1).cmp(&(from2, to2)))
+    });
+    let mut renamed_from = HashSet::new();
+    let mut renamed_to = HashMap::new();
+    for (similarity, from, to) in candidates {
+        if !renamed_from.contains(from) && !renamed_to.contains_key(to) {
+            renamed_from.insert(from);
+            renamed_to.insert(to, (from, similarity));
+        }
+    }
+
+    let mut changes = vec![];
+    for (path, before, after) in &entries {
+        if renamed_from.contains(path) {
+            continue;
+        }
+        let change = if let Some((from, similarity)) = renamed_to.get(path) {
+            FileChange::Rename {
+                from: (*from).clone(),
+                to: path.clone(),
+                similarity: *similarity,
+            }
+        } else if before.is_absent() {
+            FileChange::Added(path.clone())
+        } else if after.is_absent() {
+            FileChange::Removed(path.clone())
+        } else {
+            FileChange::Modified(path.clone())
+        };
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
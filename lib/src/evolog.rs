@@ -0,0 +1,138 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builds the graph of how a change evolved, following the predecessors
+//! recorded on commits.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::{ChangeId, CommitId};
+use crate::commit::Commit;
+use crate::evolution::{Evolution, EvolutionError};
+use crate::repo::Repo;
+
+/// How a commit was derived from one of its predecessors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvologEdgeKind {
+    /// The predecessor was rewritten into this commit alone.
+    Rewrite,
+    /// The predecessor was split into several commits, including this one.
+    Split,
+    /// This commit was created from several predecessors, e.g. by squashing.
+    Squash,
+}
+
+/// An edge from a commit to one of its predecessors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvologEdge {
+    /// The older commit.
+    pub predecessor: CommitId,
+    /// The newer commit.
+    pub successor: CommitId,
+    /// How `successor` was derived from `predecessor`.
+    pub kind: EvologEdgeKind,
+}
+
+/// A commit in the evolution graph.
+#[derive(Clone, Debug)]
+pub struct EvologNode {
+    /// The commit.
+    pub commit: Commit,
+    /// Whether the commit is visible.
+    pub is_visible: bool,
+}
+
+/// The commits a change evolved through, with edges to their predecessors.
+#[derive(Clone, Debug)]
+pub struct EvologGraph {
+    /// The commits, successors before their predecessors.
+    pub nodes: Vec<EvologNode>,
+    /// The edges between the nodes, in the order of the successor nodes.
+    pub edges: Vec<EvologEdge>,
+}
+
+impl EvologGraph {
+    /// Edges from `id` to its predecessors.
+    pub fn predecessor_edges<'a>(
+        &'a self,
+        id: &'a CommitId,
+    ) -> impl Iterator<Item = &'a EvologEdge> + 'a {
+        self.edges.iter().filter(move |edge| edge.successor == *id)
+    }
+
+    /// Edges from `id` to its successors.
+    pub fn successor_edges<'a>(
+        &'a self,
+        id: &'a CommitId,
+    ) -> impl Iterator<Item = &'a EvologEdge> + 'a {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.predecessor == *id)
+    }
+}
+
+/// Builds the evolution graph of the change with id `change_id`.
+///
+/// The graph contains the visible commits of the change and all their
+/// predecessors, transitively. It also contains the visible commits of other
+/// changes that were split off from those commits, as found by
+/// [`Evolution::history()`].
+pub fn evolog(repo: &dyn Repo, change_id: &ChangeId) -> Result<EvologGraph, EvolutionError> {
+    let evolution = Evolution::new(repo)?;
+    let history = evolution.history(change_id)?;
+    let change_ids: HashMap<&CommitId, &ChangeId> = history
+        .commits
+        .iter()
+        .map(|commit| (commit.id(), commit.change_id()))
+        .collect();
+    let edges = history
+        .commits
+        .iter()
+        .flat_map(|commit| {
+            let num_predecessors = commit.predecessor_ids().len();
+            let change_ids = &change_ids;
+            let history = &history;
+            commit.predecessor_ids().iter().map(move |predecessor_id| {
+                // A commit rewritten more than once within its change (i.e.
+                // into a divergent change) wasn't split
+                let successor_change_ids: HashSet<&ChangeId> = history
+                    .direct_successor_ids(predecessor_id)
+                    .iter()
+                    .map(|id| change_ids[id])
+                    .collect();
+                let kind = if successor_change_ids.len() > 1 {
+                    EvologEdgeKind::Split
+                } else if num_predecessors > 1 {
+                    EvologEdgeKind::Squash
+                } else {
+                    EvologEdgeKind::Rewrite
+                };
+                EvologEdge {
+                    predecessor: predecessor_id.clone(),
+                    successor: commit.id().clone(),
+                    kind,
+                }
+            })
+        })
+        .collect();
+    let nodes = history
+        .commits
+        .iter()
+        .map(|commit| EvologNode {
+            commit: commit.clone(),
+            is_visible: evolution.is_visible(commit.id()),
+        })
+        .collect();
+    Ok(EvologGraph { nodes, edges })
+}
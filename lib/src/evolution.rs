@@ -0,0 +1,900 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds out which commits are obsolete, and why, from the predecessors
+//! recorded on the visible commits. This is the one place that defines
+//! obsolescence, orphans, and divergence; `evolve()`, the evolution log, and
+//! the summaries all build on it.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::backend::{BackendError, ChangeId, CommitId};
+use crate::commit::Commit;
+use crate::dag_walk::{self, CycleError};
+use crate::object_id::ObjectId as _;
+use crate::repo::{MutableRepo, Repo};
+use crate::revset::{RevsetEvaluationError, RevsetExpression};
+
+/// Error while computing the evolution state of a repo.
+#[derive(Debug, Error)]
+pub enum EvolutionError {
+    /// Failed to read a commit.
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    /// Failed to find visible commits.
+    #[error(transparent)]
+    Evaluation(#[from] RevsetEvaluationError),
+    /// A visible commit outside the scope of a scoped [`Evolution`] was
+    /// queried.
+    #[error("Commit {} is not in the scope of the evolution", .0.hex())]
+    NotInScope(CommitId),
+    /// The commit isn't in the repo.
+    #[error("Commit {} is not in the repo", .0.hex())]
+    UnknownCommit(CommitId),
+    /// The predecessors of the commits form a cycle.
+    #[error(
+        "Predecessors form a cycle involving commits {ids}",
+        ids = commits.iter().map(|id| id.hex()).join(", ")
+    )]
+    CycleDetected {
+        /// The commits on the cycle, sorted.
+        commits: Vec<CommitId>,
+    },
+}
+
+impl From<CycleError<CommitId>> for EvolutionError {
+    fn from(err: CycleError<CommitId>) -> Self {
+        let mut commits = err.ids;
+        commits.sort();
+        EvolutionError::CycleDetected { commits }
+    }
+}
+
+/// Why a commit is obsolete.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ObsolescenceReason {
+    /// The commit was rewritten into commits of a single change. There's more
+    /// than one successor if that change is divergent.
+    Rewritten {
+        /// The visible successors, sorted by commit id.
+        successors: Vec<CommitId>,
+    },
+    /// The commit is hidden and wasn't rewritten into any visible commit,
+    /// e.g. because it was abandoned.
+    Pruned,
+    /// The commit was split into commits of several changes.
+    Split {
+        /// The visible successors, sorted by commit id.
+        pieces: Vec<CommitId>,
+    },
+}
+
+/// The commits a change evolved through, as found by walking the predecessors
+/// of its visible commits.
+#[derive(Clone, Debug, Default)]
+pub struct ChangeHistory {
+    /// The commits, successors before their predecessors. Includes the
+    /// visible commits of other changes that were split off from a commit in
+    /// the history, and their predecessors.
+    pub commits: Vec<Commit>,
+    /// The commits recording each commit as a direct predecessor.
+    direct_successors: HashMap<CommitId, Vec<CommitId>>,
+}
+
+impl ChangeHistory {
+    /// Whether the commit is part of this history.
+    pub fn contains(&self, commit_id: &CommitId) -> bool {
+        self.commits.iter().any(|commit| commit.id() == commit_id)
+    }
+
+    /// The commits in the history recording `commit_id` as a direct
+    /// predecessor.
+    pub fn direct_successor_ids(&self, commit_id: &CommitId) -> &[CommitId] {
+        self.direct_successors
+            .get(commit_id)
+            .map_or(&[], |ids| ids.as_slice())
+    }
+}
+
+/// What `evolve()` would do about a commit, as found by
+/// [`Evolution::analyze()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitEvolutionStatus {
+    /// Why the commit is obsolete, if it is.
+    pub obsolescence: Option<ObsolescenceReason>,
+    /// Whether the commit is an orphan.
+    pub is_orphan: bool,
+    /// The visible commits of the commit's change, sorted, if it's divergent.
+    /// Empty otherwise.
+    pub divergent_commit_ids: Vec<CommitId>,
+    /// Whether the user accepted the divergence of the commit's change.
+    pub is_divergence_accepted: bool,
+    /// What `evolve()` would do about the commit.
+    pub action: EvolutionAction,
+}
+
+/// What `evolve()` would do about a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvolutionAction {
+    /// Nothing, since the commit doesn't need evolving.
+    None,
+    /// The commit is an obsolete commit of a divergent change. It's replaced
+    /// by the latest commit of the change, and its descendants are rebased
+    /// onto that.
+    Replace {
+        /// The commit replacing this one.
+        replacement_id: CommitId,
+    },
+    /// The commit is an orphan, and is rebased onto new parents.
+    Rebase {
+        /// What each parent of the commit is replaced with, in order.
+        parents: Vec<NewParent>,
+    },
+    /// The commit needs evolving, but `evolve()` can't do it.
+    Blocked(EvolutionBlocker),
+}
+
+/// What a parent of an orphan is replaced with when it's rebased.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewParent {
+    /// The current parent.
+    pub old_parent_id: CommitId,
+    /// The commits the orphan is rebased onto instead of the current parent.
+    pub new_parent_ids: Vec<CommitId>,
+    /// Why the parent is replaced with these commits.
+    pub reason: NewParentReason,
+}
+
+/// Why a parent of an orphan is replaced with its new parents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NewParentReason {
+    /// The parent doesn't need evolving, so it's kept.
+    Unchanged,
+    /// The parent was rewritten into the new parent.
+    Rewritten,
+    /// The parent was abandoned in the transaction, so it's replaced by what
+    /// its own parents are replaced with.
+    Abandoned,
+    /// The parent is an orphan too. It's rebased first, and the commit is
+    /// then rebased onto its new version. The new parent is the parent
+    /// itself, since the id of its new version isn't known yet.
+    RebasedFirst,
+}
+
+/// Why `evolve()` can't evolve a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvolutionBlocker {
+    /// The change of the commit, which is the evolved commit or one of its
+    /// parents, is divergent, and `evolve()` can't tell which of its visible
+    /// commits to keep.
+    UnresolvedDivergence {
+        /// The commit of the divergent change.
+        commit_id: CommitId,
+    },
+    /// A parent was rewritten into several commits, so it's ambiguous which
+    /// one to rebase onto.
+    AmbiguousParent {
+        /// The parent.
+        parent_id: CommitId,
+        /// The visible commits the parent was rewritten into that aren't
+        /// obsolete, sorted.
+        candidates: Vec<CommitId>,
+    },
+    /// A parent was rewritten into a descendant of the commit, so rebasing
+    /// onto it would create a cycle.
+    ParentRewrittenIntoDescendant {
+        /// The parent.
+        parent_id: CommitId,
+        /// The descendant the parent was rewritten into.
+        new_parent_id: CommitId,
+    },
+    /// Evolving would rewrite or hide the commit, but it's immutable.
+    Immutable,
+}
+
+/// The evolution state of the visible commits of a repo.
+///
+/// A successor of a commit is a visible commit that has it among its
+/// transitive predecessors. A commit is obsolete if it has successors, or if
+/// it's hidden. An orphan is a visible commit that isn't obsolete, but has an
+/// obsolete ancestor. A change is divergent if it has more than one visible
+/// commit.
+///
+/// Only the change ids of the visible commits are looked up upfront, from the
+/// index. Commits are read lazily, and only for the changes involved in a
+/// query. Visible commits are only considered obsolete if their change is
+/// divergent, so finding the orphans reads the history of the divergent
+/// changes only. (A visible commit that was split into other changes without
+/// being rewritten within its own change isn't considered obsolete.)
+///
+/// Split pieces are found among the visible children of the visible commits
+/// of a change and of their parents, which is where `jj split` puts them.
+///
+/// If evolution is disabled in the repo settings, no commit is obsolete, so
+/// there are no orphans either.
+pub struct Evolution<'repo> {
+    repo: &'repo dyn Repo,
+    /// Whether evolution is enabled in the repo settings.
+    enabled: bool,
+    /// The visible commits, with the ones rewritten in a transaction removed.
+    visible: Rc<RevsetExpression>,
+    /// The visible commits and their change ids.
+    visible_change_ids: HashMap<CommitId, ChangeId>,
+    /// The visible commits of each change, sorted.
+    change_commit_ids: HashMap<ChangeId, Vec<CommitId>>,
+    /// Commits recorded as rewritten or abandoned in a transaction. They're
+    /// treated as hidden.
+    rewritten_ids: HashSet<CommitId>,
+    /// The ancestors of the revset the evolution is scoped to.
+    scope: Option<(Rc<RevsetExpression>, HashSet<CommitId>)>,
+    histories: RefCell<HashMap<ChangeId, Rc<ChangeHistory>>>,
+    orphan_ids: RefCell<Option<Rc<Vec<CommitId>>>>,
+}
+
+impl<'repo> Evolution<'repo> {
+    /// Finds the visible commits of the repo.
+    pub fn new(repo: &'repo dyn Repo) -> Result<Self, EvolutionError> {
+        Self::with_rewritten(repo, HashSet::new(), None)
+    }
+
+    /// Like [`Evolution::new()`], but queries about visible commits outside
+    /// the ancestry of `revset` fail with [`EvolutionError::NotInScope`], and
+    /// only the commits of divergent changes in the ancestry are read to find
+    /// the orphans. Hidden commits are always in scope.
+    ///
+    /// Scoping only saves reading commits. The change ids of all visible
+    /// commits are still looked up in the index, as by [`Evolution::new()`],
+    /// because obsolescence is decided against all visible commits: a commit
+    /// in scope that was rewritten into a commit outside of it, e.g. on
+    /// another branch, is obsolete. Limiting the lookup to the descendants of
+    /// the scope wouldn't help, since the root commit is in every scope.
+    pub fn scoped_to(
+        repo: &'repo dyn Repo,
+        revset: &Rc<RevsetExpression>,
+    ) -> Result<Self, EvolutionError> {
+        let ancestors = revset.ancestors();
+        let scope_ids = ancestors
+            .clone()
+            .evaluate_programmatic(repo)?
+            .iter()
+            .collect();
+        Self::with_rewritten(repo, HashSet::new(), Some((ancestors, scope_ids)))
+    }
+
+    /// Like [`Evolution::new()`], but treats the commits recorded as
+    /// rewritten or abandoned in the transaction as hidden, as they will be
+    /// once their descendants have been rebased.
+    pub fn for_transaction(mut_repo: &'repo MutableRepo) -> Result<Self, EvolutionError> {
+        let rewritten_ids = mut_repo.rewritten_commit_ids().cloned().collect();
+        Self::with_rewritten(mut_repo, rewritten_ids, None)
+    }
+
+    fn with_rewritten(
+        repo: &'repo dyn Repo,
+        rewritten_ids: HashSet<CommitId>,
+        scope: Option<(Rc<RevsetExpression>, HashSet<CommitId>)>,
+    ) -> Result<Self, EvolutionError> {
+        let visible = if rewritten_ids.is_empty() {
+            RevsetExpression::all()
+        } else {
+            RevsetExpression::all().minus(&RevsetExpression::commits(
+                rewritten_ids.iter().cloned().collect(),
+            ))
+        };
+        let visible_change_ids: HashMap<CommitId, ChangeId> = visible
+            .clone()
+            .evaluate_programmatic(repo)?
+            .commit_change_ids()
+            .collect();
+        let mut change_commit_ids: HashMap<ChangeId, Vec<CommitId>> = HashMap::new();
+        for (commit_id, change_id) in &visible_change_ids {
+            change_commit_ids
+                .entry(change_id.clone())
+                .or_default()
+                .push(commit_id.clone());
+        }
+        for commit_ids in change_commit_ids.values_mut() {
+            commit_ids.sort();
+        }
+        Ok(Evolution {
+            repo,
+            enabled: repo.settings().evolution_enabled(),
+            visible,
+            visible_change_ids,
+            change_commit_ids,
+            rewritten_ids,
+            scope,
+            histories: RefCell::new(HashMap::new()),
+            orphan_ids: RefCell::new(None),
+        })
+    }
+
+    /// Whether queries about the commit are answered. Always true for hidden
+    /// commits and if the evolution isn't scoped.
+    pub fn is_in_scope(&self, commit_id: &CommitId) -> bool {
+        match &self.scope {
+            Some((_, scope_ids)) => {
+                !self.visible_change_ids.contains_key(commit_id) || scope_ids.contains(commit_id)
+            }
+            None => true,
+        }
+    }
+
+    /// Whether the commit is visible, and not rewritten in the transaction
+    /// for an evolution created by [`Evolution::for_transaction()`].
+    pub fn is_visible(&self, commit_id: &CommitId) -> bool {
+        self.visible_change_ids.contains_key(commit_id)
+    }
+
+    fn is_visible_in_scope(&self, commit_id: &CommitId) -> Result<bool, EvolutionError> {
+        if !self.repo.index().has_id(commit_id) {
+            return Err(EvolutionError::UnknownCommit(commit_id.clone()));
+        }
+        if !self.is_in_scope(commit_id) {
+            return Err(EvolutionError::NotInScope(commit_id.clone()));
+        }
+        Ok(self.visible_change_ids.contains_key(commit_id))
+    }
+
+    /// Returns why the commit is obsolete, or `None` if it isn't.
+    pub fn obsolescence_reason(
+        &self,
+        commit_id: &CommitId,
+    ) -> Result<Option<ObsolescenceReason>, EvolutionError> {
+        let is_visible = self.is_visible_in_scope(commit_id)?;
+        if !self.enabled {
+            return Ok(None);
+        }
+        let successors = self.successor_ids(commit_id, is_visible)?;
+        if successors.is_empty() {
+            return Ok((!is_visible).then_some(ObsolescenceReason::Pruned));
+        }
+        let is_single_change = successors
+            .iter()
+            .map(|id| &self.visible_change_ids[id])
+            .all_equal();
+        if is_single_change {
+            Ok(Some(ObsolescenceReason::Rewritten { successors }))
+        } else {
+            Ok(Some(ObsolescenceReason::Split { pieces: successors }))
+        }
+    }
+
+    /// Whether the commit is obsolete. See [`Self::obsolescence_reason()`].
+    pub fn is_obsolete(&self, commit_id: &CommitId) -> Result<bool, EvolutionError> {
+        Ok(self.obsolescence_reason(commit_id)?.is_some())
+    }
+
+    /// Whether the commit is a visible descendant of a visible obsolete
+    /// commit (or of a commit rewritten in the transaction), without being
+    /// obsolete itself.
+    pub fn is_orphan(&self, commit_id: &CommitId) -> Result<bool, EvolutionError> {
+        if !self.is_visible_in_scope(commit_id)? {
+            return Ok(false);
+        }
+        Ok(self.orphan_ids()?.contains(commit_id))
+    }
+
+    /// Whether the commit is visible and its change has other visible
+    /// commits.
+    pub fn is_divergent(&self, commit_id: &CommitId) -> Result<bool, EvolutionError> {
+        if !self.is_visible_in_scope(commit_id)? {
+            return Ok(false);
+        }
+        let change_id = &self.visible_change_ids[commit_id];
+        Ok(self.change_commit_ids[change_id].len() > 1)
+    }
+
+    /// Whether the commit is divergent, and the user accepted the divergence
+    /// of its change, e.g. to keep several variants of it.
+    pub fn is_intentionally_divergent(&self, commit_id: &CommitId) -> Result<bool, EvolutionError> {
+        if !self.is_divergent(commit_id)? {
+            return Ok(false);
+        }
+        let change_id = &self.visible_change_ids[commit_id];
+        Ok(self.repo.view().is_divergence_accepted(change_id))
+    }
+
+    /// Returns the visible commits of each divergent change that has a commit
+    /// in scope, sorted by change id and then by commit id.
+    pub fn divergent_commit_ids(&self) -> Vec<Vec<CommitId>> {
+        self.divergent_changes()
+            .into_values()
+            .map(|commit_ids| commit_ids.to_vec())
+            .collect()
+    }
+
+    /// Returns the visible commits of each divergent change that has a commit
+    /// in scope, sorted by commit id.
+    pub fn divergent_changes(&self) -> BTreeMap<&ChangeId, &[CommitId]> {
+        self.change_commit_ids
+            .iter()
+            .filter(|(_, commit_ids)| commit_ids.len() > 1)
+            .filter(|(_, commit_ids)| commit_ids.iter().any(|id| self.is_in_scope(id)))
+            .map(|(change_id, commit_ids)| (change_id, commit_ids.as_slice()))
+            .collect()
+    }
+
+    /// Returns the visible commits in scope that are obsolete. They all
+    /// belong to divergent changes.
+    pub fn obsolete_visible_ids(&self) -> Result<Vec<CommitId>, EvolutionError> {
+        let mut obsolete_ids = vec![];
+        if !self.enabled {
+            return Ok(obsolete_ids);
+        }
+        for commit_ids in self.divergent_changes().into_values() {
+            for commit_id in commit_ids {
+                if self.is_in_scope(commit_id) && !self.successor_ids(commit_id, true)?.is_empty() {
+                    obsolete_ids.push(commit_id.clone());
+                }
+            }
+        }
+        Ok(obsolete_ids)
+    }
+
+    /// Returns the orphans in scope, children before their parents.
+    pub fn orphan_ids(&self) -> Result<Rc<Vec<CommitId>>, EvolutionError> {
+        if let Some(orphan_ids) = self.orphan_ids.borrow().as_ref() {
+            return Ok(orphan_ids.clone());
+        }
+        let orphan_ids = Rc::new(self.orphan_ids_of(self.obsolete_visible_ids()?)?);
+        *self.orphan_ids.borrow_mut() = Some(orphan_ids.clone());
+        Ok(orphan_ids)
+    }
+
+    /// Returns the orphans in scope given the obsolete visible commits in
+    /// scope, for callers that already know them.
+    pub(crate) fn orphan_ids_of(
+        &self,
+        obsolete_visible_ids: Vec<CommitId>,
+    ) -> Result<Vec<CommitId>, EvolutionError> {
+        if !self.enabled {
+            return Ok(vec![]);
+        }
+        let root_ids = obsolete_visible_ids
+            .into_iter()
+            .chain(self.rewritten_ids.iter().cloned())
+            .collect_vec();
+        let orphan_ids = if root_ids.is_empty() {
+            vec![]
+        } else {
+            let roots = RevsetExpression::commits(root_ids);
+            let mut orphans = roots
+                .descendants()
+                .minus(&roots)
+                .intersection(&self.visible);
+            if let Some((ancestors, _)) = &self.scope {
+                orphans = orphans.intersection(ancestors);
+            }
+            orphans.evaluate_programmatic(self.repo)?.iter().collect()
+        };
+        Ok(orphan_ids)
+    }
+
+    /// Returns the orphans that can't be rebased to their final location until
+    /// the orphan `commit_id` has been, i.e. the orphans descending from it.
+    /// Returns an empty list if `commit_id` isn't an orphan. Children come
+    /// before their parents.
+    pub fn orphans_blocked_on(
+        &self,
+        commit_id: &CommitId,
+    ) -> Result<Vec<CommitId>, EvolutionError> {
+        if !self.is_orphan(commit_id)? {
+            return Ok(vec![]);
+        }
+        let orphan_ids = self.orphan_ids()?;
+        let commit = RevsetExpression::commit(commit_id.clone());
+        let blocked_ids = commit
+            .descendants()
+            .minus(&commit)
+            .intersection(&RevsetExpression::commits(orphan_ids.to_vec()))
+            .evaluate_programmatic(self.repo)?
+            .iter()
+            .collect();
+        Ok(blocked_ids)
+    }
+
+    /// Finds out what `evolve()` would do about the commit, and why.
+    ///
+    /// An obsolete commit is replaced by the latest commit of its change,
+    /// unless the divergence of the change was accepted, in which case it's
+    /// left alone. An orphan is rebased, with each obsolete parent replaced
+    /// by what `evolve()` replaces it with. A commit of a divergent change
+    /// that `evolve()` can't resolve is blocked on its divergence. Commits
+    /// that are obsolete and orphans at the same time are treated as
+    /// obsolete.
+    ///
+    /// `evolve()` doesn't know which commits are immutable, so callers pass
+    /// the heads of the immutable commits, and commits that would be
+    /// rewritten or hidden among their ancestors are reported as blocked.
+    pub fn analyze(
+        &self,
+        commit_id: &CommitId,
+        immutable_heads: &[CommitId],
+    ) -> Result<CommitEvolutionStatus, EvolutionError> {
+        let obsolescence = self.obsolescence_reason(commit_id)?;
+        let is_orphan = self.is_orphan(commit_id)?;
+        let change_id = self.visible_change_ids.get(commit_id);
+        let mut divergent_commit_ids = vec![];
+        let mut is_divergence_accepted = false;
+        if let Some(change_id) = change_id {
+            if self.change_commit_ids[change_id].len() > 1 {
+                divergent_commit_ids = self.change_commit_ids[change_id].clone();
+                is_divergence_accepted = self.repo.view().is_divergence_accepted(change_id);
+            }
+        }
+        let is_resolvable = |change_id: &ChangeId| -> Result<bool, EvolutionError> {
+            Ok(self.divergence_resolution(change_id)?.is_some())
+        };
+        let action = match change_id {
+            // Hidden commits are left alone
+            None => EvolutionAction::None,
+            Some(_) if divergent_commit_ids.is_empty() || is_divergence_accepted => {
+                if is_orphan {
+                    self.orphan_action(commit_id)?
+                } else {
+                    EvolutionAction::None
+                }
+            }
+            Some(change_id) if obsolescence.is_some() => {
+                match self.divergence_resolution(change_id)? {
+                    Some((_, latest_id)) => EvolutionAction::Replace {
+                        replacement_id: latest_id,
+                    },
+                    None => EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+                        commit_id: commit_id.clone(),
+                    }),
+                }
+            }
+            Some(_) if is_orphan => self.orphan_action(commit_id)?,
+            Some(change_id) if is_resolvable(change_id)? => EvolutionAction::None,
+            Some(_) => EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+                commit_id: commit_id.clone(),
+            }),
+        };
+        let index = self.repo.index();
+        let action = match action {
+            EvolutionAction::Replace { .. } | EvolutionAction::Rebase { .. }
+                if immutable_heads
+                    .iter()
+                    .any(|head_id| index.is_ancestor(commit_id, head_id)) =>
+            {
+                EvolutionAction::Blocked(EvolutionBlocker::Immutable)
+            }
+            action => action,
+        };
+        Ok(CommitEvolutionStatus {
+            obsolescence,
+            is_orphan,
+            divergent_commit_ids,
+            is_divergence_accepted,
+            action,
+        })
+    }
+
+    fn orphan_action(&self, commit_id: &CommitId) -> Result<EvolutionAction, EvolutionError> {
+        let commit = self.repo.store().get_commit(commit_id)?;
+        let mut parents = vec![];
+        for parent_id in commit.parent_ids() {
+            match self.new_parent(parent_id)? {
+                Ok(parent) => parents.push(parent),
+                Err(blocker) => return Ok(EvolutionAction::Blocked(blocker)),
+            }
+        }
+        let index = self.repo.index();
+        for parent in &parents {
+            if let Some(new_parent_id) = parent
+                .new_parent_ids
+                .iter()
+                .find(|new_parent_id| index.is_ancestor(commit_id, new_parent_id))
+            {
+                return Ok(EvolutionAction::Blocked(
+                    EvolutionBlocker::ParentRewrittenIntoDescendant {
+                        parent_id: parent.old_parent_id.clone(),
+                        new_parent_id: new_parent_id.clone(),
+                    },
+                ));
+            }
+        }
+        Ok(EvolutionAction::Rebase { parents })
+    }
+
+    /// Returns what a parent of an orphan is replaced with when the orphan is
+    /// rebased, or why it can't be.
+    fn new_parent(
+        &self,
+        parent_id: &CommitId,
+    ) -> Result<Result<NewParent, EvolutionBlocker>, EvolutionError> {
+        let new_parent = |new_parent_ids, reason| NewParent {
+            old_parent_id: parent_id.clone(),
+            new_parent_ids,
+            reason,
+        };
+        let ambiguous = |candidates| EvolutionBlocker::AmbiguousParent {
+            parent_id: parent_id.clone(),
+            candidates,
+        };
+        if self.rewritten_ids.contains(parent_id) {
+            return match self.obsolescence_reason(parent_id)? {
+                Some(ObsolescenceReason::Rewritten { successors }) if successors.len() == 1 => {
+                    Ok(Ok(new_parent(successors, NewParentReason::Rewritten)))
+                }
+                Some(ObsolescenceReason::Rewritten { successors }) => {
+                    Ok(Err(ambiguous(successors)))
+                }
+                Some(ObsolescenceReason::Split { pieces }) => Ok(Err(ambiguous(pieces))),
+                Some(ObsolescenceReason::Pruned) => {
+                    let mut new_parent_ids = vec![];
+                    for grandparent_id in self.repo.index().parent_ids(parent_id) {
+                        match self.new_parent(&grandparent_id)? {
+                            Ok(parent) => new_parent_ids.extend(parent.new_parent_ids),
+                            Err(blocker) => return Ok(Err(blocker)),
+                        }
+                    }
+                    Ok(Ok(new_parent(new_parent_ids, NewParentReason::Abandoned)))
+                }
+                None => Ok(Ok(new_parent(
+                    vec![parent_id.clone()],
+                    NewParentReason::Unchanged,
+                ))),
+            };
+        }
+        let Some(change_id) = self.visible_change_ids.get(parent_id) else {
+            // Hidden before the transaction, so `evolve()` leaves it alone
+            return Ok(Ok(new_parent(
+                vec![parent_id.clone()],
+                NewParentReason::Unchanged,
+            )));
+        };
+        if self.is_obsolete(parent_id)? {
+            if !self.repo.view().is_divergence_accepted(change_id) {
+                return match self.divergence_resolution(change_id)? {
+                    Some((_, latest_id)) => {
+                        Ok(Ok(new_parent(vec![latest_id], NewParentReason::Rewritten)))
+                    }
+                    None => Ok(Err(EvolutionBlocker::UnresolvedDivergence {
+                        commit_id: parent_id.clone(),
+                    })),
+                };
+            }
+            let latest_ids = match self.obsolescence_reason(parent_id)? {
+                Some(ObsolescenceReason::Split { pieces }) => return Ok(Err(ambiguous(pieces))),
+                _ => self.latest_successor_ids(parent_id)?,
+            };
+            return if latest_ids.len() == 1 {
+                Ok(Ok(new_parent(latest_ids, NewParentReason::Rewritten)))
+            } else {
+                Ok(Err(ambiguous(latest_ids)))
+            };
+        }
+        if self.is_orphan(parent_id)? {
+            return Ok(Ok(new_parent(
+                vec![parent_id.clone()],
+                NewParentReason::RebasedFirst,
+            )));
+        }
+        Ok(Ok(new_parent(
+            vec![parent_id.clone()],
+            NewParentReason::Unchanged,
+        )))
+    }
+
+    /// Returns the obsolete commits of the divergent change and the commit
+    /// `evolve()` replaces them with, regardless of whether the divergence
+    /// was accepted. Returns `None` if all visible commits but one weren't
+    /// rewritten into that one, or if replacing them would create a cycle.
+    pub(crate) fn divergence_resolution(
+        &self,
+        change_id: &ChangeId,
+    ) -> Result<Option<(Vec<CommitId>, CommitId)>, EvolutionError> {
+        let index = self.repo.index();
+        let mut latest_ids = vec![];
+        let mut obsolete_ids = vec![];
+        for commit_id in self.change_commit_ids[change_id].iter().cloned() {
+            match self.obsolescence_reason(&commit_id)? {
+                None => latest_ids.push(commit_id),
+                Some(ObsolescenceReason::Rewritten { .. }) => obsolete_ids.push(commit_id),
+                // Split into other changes, which the user has to sort out
+                Some(_) => return Ok(None),
+            }
+        }
+        let [latest_id] = latest_ids.as_slice() else {
+            return Ok(None);
+        };
+        // Rebasing onto a descendant of the obsolete commit would create a
+        // cycle
+        if obsolete_ids
+            .iter()
+            .any(|obsolete_id| index.is_ancestor(obsolete_id, latest_id))
+        {
+            return Ok(None);
+        }
+        Ok(Some((obsolete_ids, latest_id.clone())))
+    }
+
+    /// Returns the visible commits the commit was rewritten into that aren't
+    /// obsolete, sorted. Empty if the commit wasn't rewritten within its
+    /// change.
+    pub(crate) fn latest_successor_ids(
+        &self,
+        commit_id: &CommitId,
+    ) -> Result<Vec<CommitId>, EvolutionError> {
+        let Some(ObsolescenceReason::Rewritten { successors }) =
+            self.obsolescence_reason(commit_id)?
+        else {
+            return Ok(vec![]);
+        };
+        let mut latest_ids = vec![];
+        for successor_id in successors {
+            if !self.is_obsolete(&successor_id)? {
+                latest_ids.push(successor_id);
+            }
+        }
+        Ok(latest_ids)
+    }
+
+    /// Returns the history of the change, starting from its visible commits.
+    pub fn history(&self, change_id: &ChangeId) -> Result<Rc<ChangeHistory>, EvolutionError> {
+        if let Some(history) = self.histories.borrow().get(change_id) {
+            return Ok(history.clone());
+        }
+        let history = Rc::new(self.build_history(change_id, None)?);
+        self.histories
+            .borrow_mut()
+            .insert(change_id.clone(), history.clone());
+        Ok(history)
+    }
+
+    fn build_history(
+        &self,
+        change_id: &ChangeId,
+        hidden_commit: Option<Commit>,
+    ) -> Result<ChangeHistory, EvolutionError> {
+        let store = self.repo.store();
+        let index = self.repo.index();
+        let visible_ids = self
+            .change_commit_ids
+            .get(change_id)
+            .map_or(&[][..], |ids| ids.as_slice());
+        let mut heads: Vec<Commit> = visible_ids
+            .iter()
+            .map(|id| store.get_commit(id))
+            .try_collect()?;
+        heads.extend(hidden_commit);
+        let walk_predecessors = |heads: Vec<Commit>| {
+            dag_walk::topo_order_reverse_checked(
+                heads.into_iter().map(Ok),
+                |commit: &Commit| commit.id().clone(),
+                |commit: &Commit| {
+                    commit
+                        .predecessors()
+                        .map(|predecessor| predecessor.map_err(EvolutionError::from))
+                        .collect_vec()
+                },
+            )
+        };
+        let commits = walk_predecessors(heads.clone())?;
+
+        // Look for the pieces split off into other changes
+        let ids: HashSet<&CommitId> = commits.iter().map(|commit| commit.id()).collect();
+        let base_ids = visible_ids
+            .iter()
+            .cloned()
+            .chain(
+                heads
+                    .iter()
+                    .flat_map(|commit| index.parent_ids(commit.id())),
+            )
+            .filter(|id| id != store.root_commit_id())
+            .unique()
+            .collect_vec();
+        let candidate_ids = if base_ids.is_empty() {
+            vec![]
+        } else {
+            RevsetExpression::commits(base_ids)
+                .children()
+                .intersection(&self.visible)
+                .evaluate_programmatic(self.repo)?
+                .iter()
+                .filter(|id| !ids.contains(id) && self.visible_change_ids[id] != *change_id)
+                .collect_vec()
+        };
+        let mut pieces = vec![];
+        for candidate_id in candidate_ids {
+            let candidate = store.get_commit(&candidate_id)?;
+            let mut pending = candidate.predecessor_ids().to_vec();
+            let mut seen = HashSet::new();
+            while let Some(id) = pending.pop() {
+                if ids.contains(&id) {
+                    pieces.push(candidate);
+                    break;
+                }
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                // Only follow the rewrites of the piece itself
+                let predecessor = store.get_commit(&id)?;
+                if predecessor.change_id() == candidate.change_id() {
+                    pending.extend(predecessor.predecessor_ids().iter().cloned());
+                }
+            }
+        }
+        let commits = if pieces.is_empty() {
+            commits
+        } else {
+            walk_predecessors(heads.into_iter().chain(pieces).collect())?
+        };
+
+        let mut direct_successors: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
+        for commit in &commits {
+            for predecessor_id in commit.predecessor_ids() {
+                direct_successors
+                    .entry(predecessor_id.clone())
+                    .or_default()
+                    .push(commit.id().clone());
+            }
+        }
+        Ok(ChangeHistory {
+            commits,
+            direct_successors,
+        })
+    }
+
+    /// Returns the visible commits that have `commit_id` among their
+    /// transitive predecessors, sorted by commit id.
+    fn successor_ids(
+        &self,
+        commit_id: &CommitId,
+        is_visible: bool,
+    ) -> Result<Vec<CommitId>, EvolutionError> {
+        let history = if is_visible {
+            let change_id = &self.visible_change_ids[commit_id];
+            if self.change_commit_ids[change_id].len() < 2 {
+                return Ok(vec![]);
+            }
+            self.history(change_id)?
+        } else {
+            let commit = self.repo.store().get_commit(commit_id)?;
+            let history = self.history(commit.change_id())?;
+            if history.contains(commit_id) {
+                history
+            } else {
+                // Not an ancestor of the visible commits of its change
+                let change_id = commit.change_id().clone();
+                Rc::new(self.build_history(&change_id, Some(commit))?)
+            }
+        };
+        let mut successor_ids = vec![];
+        let mut pending = vec![commit_id];
+        let mut seen = HashSet::from([commit_id]);
+        while let Some(id) = pending.pop() {
+            for successor_id in history.direct_successor_ids(id) {
+                if !seen.insert(successor_id) {
+                    continue;
+                }
+                if self.visible_change_ids.contains_key(successor_id) {
+                    successor_ids.push(successor_id.clone());
+                }
+                pending.push(successor_id);
+            }
+        }
+        successor_ids.sort();
+        Ok(successor_ids)
+    }
+}
@@ -69,6 +69,39 @@ pub fn remove_dir_contents(dirname: &Path) -> Result<(), PathError> {
     Ok(())
 }
 
+/// Copies the file or directory `src` to `dest`, recursively. If `hard_link`
+/// is true, files are hard-linked instead of copied. Temporary files and lock
+/// files are skipped, as are files that are removed while being copied.
+pub fn copy_recursive(src: &Path, dest: &Path, hard_link: bool) -> Result<(), PathError> {
+    let metadata = match fs::metadata(src) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).context(src),
+    };
+    if metadata.is_dir() {
+        fs::create_dir(dest).context(dest)?;
+        for entry in src.read_dir().context(src)? {
+            let entry = entry.context(src)?;
+            let name = entry.file_name();
+            if name == "lock" || name.to_string_lossy().starts_with(".tmp") {
+                continue;
+            }
+            copy_recursive(&entry.path(), &dest.join(&name), hard_link)?;
+        }
+        return Ok(());
+    }
+    let result = if hard_link {
+        fs::hard_link(src, dest)
+    } else {
+        fs::copy(src, dest).map(|_| ())
+    };
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !src.exists() => Ok(()),
+        Err(err) => Err(err).context(src),
+    }
+}
+
 /// Turns the given `to` path into relative path starting from the `from` path.
 ///
 /// Both `from` and `to` paths are supposed to be absolute and normalized in the
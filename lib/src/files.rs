@@ -20,6 +20,7 @@ use std::fmt::{Debug, Error, Formatter};
 use crate::diff;
 use crate::diff::{Diff, DiffHunk};
 use crate::merge::{trivial_merge, Merge};
+use crate::repo_path::RepoPath;
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct DiffLine<'a> {
@@ -157,6 +158,32 @@ pub enum MergeResult {
 }
 
 pub fn merge(slices: &Merge<&[u8]>) -> MergeResult {
+    // Adding or removing the final newline changes the last line, which would
+    // conflict with any change to that line on another side. So merge whether
+    // the final newline is missing separately from the lines themselves.
+    let missing_newline = slices.map(|slice| !slice.is_empty() && !slice.ends_with(b"\n"));
+    if missing_newline.iter().any(|&missing| missing) {
+        if let Some(&merged_missing_newline) = missing_newline.resolve_trivial() {
+            let normalized = slices.map(|slice| {
+                let mut content = slice.to_vec();
+                if !content.is_empty() && !content.ends_with(b"\n") {
+                    content.push(b'\n');
+                }
+                content
+            });
+            let merged = merge_lines(&normalized.map(|content| content.as_slice()));
+            if let MergeResult::Resolved(mut content) = merged {
+                if merged_missing_newline && content.0.ends_with(b"\n") {
+                    content.0.pop();
+                }
+                return MergeResult::Resolved(content);
+            }
+        }
+    }
+    merge_lines(slices)
+}
+
+fn merge_lines(slices: &Merge<&[u8]>) -> MergeResult {
     // TODO: Using the first remove as base (first in the inputs) is how it's
     // usually done for 3-way conflicts. Are there better heuristics when there are
     // more than 3 parts?
@@ -202,6 +229,147 @@ pub fn merge(slices: &Merge<&[u8]>) -> MergeResult {
     }
 }
 
+/// Strategy to merge conflicting file contents. Selected by path with the
+/// `merge.drivers` setting.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeDriver {
+    /// Merges line-based hunks. Overlapping changes remain conflicted.
+    #[default]
+    Text,
+    /// Like `Text`, but resolves overlapping changes by concatenating the
+    /// sides.
+    Union,
+    /// Takes the first side, which is the destination when rebasing.
+    Ours,
+    /// Takes the last side, which is the rebased commit when rebasing.
+    Theirs,
+    /// Never merges the contents.
+    Binary,
+}
+
+/// Merge drivers selected by path. The first matching rule wins.
+#[derive(Clone, Debug, Default)]
+pub struct MergeDrivers {
+    rules: Vec<(glob::Pattern, MergeDriver)>,
+}
+
+impl MergeDrivers {
+    /// Creates rules matching paths relative to the repository root.
+    pub fn new(rules: impl IntoIterator<Item = (glob::Pattern, MergeDriver)>) -> Self {
+        MergeDrivers {
+            rules: rules.into_iter().collect(),
+        }
+    }
+
+    /// Returns the driver to merge the file at `path`.
+    pub fn driver_for(&self, path: &RepoPath) -> MergeDriver {
+        const OPTIONS: glob::MatchOptions = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let path = path.as_internal_file_string();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches_with(path, OPTIONS))
+            .map_or(MergeDriver::Text, |&(_, driver)| driver)
+    }
+}
+
+/// Number of bytes at the start of a file that [`ContentType::detect()`] looks
+/// at.
+pub const CONTENT_TYPE_DETECTION_LEN: usize = 8000;
+
+/// Whether file contents are treated as text or as binary data when diffing
+/// and merging.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContentType {
+    Text,
+    Binary,
+}
+
+impl ContentType {
+    /// Classifies contents by their first [`CONTENT_TYPE_DETECTION_LEN`]
+    /// bytes. Like Git, contents are binary if there's a NUL byte among them,
+    /// so e.g. UTF-16 text is binary.
+    pub fn detect(contents: &[u8]) -> Self {
+        if contents[..contents.len().min(CONTENT_TYPE_DETECTION_LEN)].contains(&0) {
+            ContentType::Binary
+        } else {
+            ContentType::Text
+        }
+    }
+}
+
+/// Content types forced by path, overriding [`ContentType::detect()`].
+/// Selected with the `files.content-types` setting. The first matching rule
+/// wins.
+#[derive(Clone, Debug, Default)]
+pub struct ContentTypes {
+    rules: Vec<(glob::Pattern, ContentType)>,
+}
+
+impl ContentTypes {
+    /// Creates rules matching paths relative to the repository root.
+    pub fn new(rules: impl IntoIterator<Item = (glob::Pattern, ContentType)>) -> Self {
+        ContentTypes {
+            rules: rules.into_iter().collect(),
+        }
+    }
+
+    /// Returns the content type forced for the file at `path`, if any.
+    pub fn override_for(&self, path: &RepoPath) -> Option<ContentType> {
+        const OPTIONS: glob::MatchOptions = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        let path = path.as_internal_file_string();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches_with(path, OPTIONS))
+            .map(|&(_, content_type)| content_type)
+    }
+
+    /// Classifies the `contents` of the file at `path`.
+    pub fn classify(&self, path: &RepoPath, contents: &[u8]) -> ContentType {
+        self.override_for(path)
+            .unwrap_or_else(|| ContentType::detect(contents))
+    }
+}
+
+/// Merges file contents with the given `driver`.
+pub fn merge_with_driver(slices: &Merge<&[u8]>, driver: MergeDriver) -> MergeResult {
+    match driver {
+        MergeDriver::Text => merge(slices),
+        MergeDriver::Union => match merge(slices) {
+            MergeResult::Resolved(content) => MergeResult::Resolved(content),
+            MergeResult::Conflict(hunks) => {
+                let mut content = vec![];
+                for hunk in &hunks {
+                    if let Some(resolved) = hunk.as_resolved() {
+                        content.extend_from_slice(&resolved.0);
+                    } else {
+                        for side in hunk.adds() {
+                            content.extend_from_slice(&side.0);
+                        }
+                    }
+                }
+                MergeResult::Resolved(ContentHunk(content))
+            }
+        },
+        MergeDriver::Ours => MergeResult::Resolved(ContentHunk(slices.first().to_vec())),
+        MergeDriver::Theirs => {
+            MergeResult::Resolved(ContentHunk(slices.adds().last().unwrap().to_vec()))
+        }
+        MergeDriver::Binary => {
+            MergeResult::Conflict(vec![slices.map(|side| ContentHunk(side.to_vec()))])
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,6 +514,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_merge_final_newline() {
+        // One side adds the final newline, the other side changes the line
+        // before it
+        assert_eq!(
+            merge(&[b"a\nb"], &[b"a\nb\n", b"a2\nb"]),
+            MergeResult::Resolved(hunk(b"a2\nb\n"))
+        );
+        assert_eq!(
+            merge(&[b"a\nb"], &[b"a2\nb", b"a\nb\n"]),
+            MergeResult::Resolved(hunk(b"a2\nb\n"))
+        );
+        // One side adds the final newline, the other side changes the last line
+        assert_eq!(
+            merge(&[b"a\nb"], &[b"a\nb\n", b"a\nb2"]),
+            MergeResult::Resolved(hunk(b"a\nb2\n"))
+        );
+        // One side removes the final newline, the other side changes the last
+        // line
+        assert_eq!(
+            merge(&[b"a\nb\n"], &[b"a\nb", b"a\nb2\n"]),
+            MergeResult::Resolved(hunk(b"a\nb2"))
+        );
+        // Both sides change the last line
+        assert_eq!(
+            merge(&[b"a"], &[b"b\n", b"c"]),
+            MergeResult::Conflict(vec![Merge::from_removes_adds(
+                vec![hunk(b"a")],
+                vec![hunk(b"b\n"), hunk(b"c")]
+            )])
+        );
+    }
+
     #[test]
     fn test_merge_multi_hunk() {
         // Two sides left one line unchanged, and added conflicting additional lines
@@ -430,4 +631,84 @@ b {
             ))
         );
     }
+
+    #[test]
+    fn test_merge_with_driver() {
+        let slices = Merge::from_removes_adds(
+            vec![&b"a\nb\n"[..]],
+            vec![&b"a\nours\n"[..], &b"a\ntheirs\n"[..]],
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Text),
+            super::merge(&slices)
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Union),
+            MergeResult::Resolved(hunk(b"a\nours\ntheirs\n"))
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Ours),
+            MergeResult::Resolved(hunk(b"a\nours\n"))
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Theirs),
+            MergeResult::Resolved(hunk(b"a\ntheirs\n"))
+        );
+        // Binary doesn't merge even non-overlapping changes
+        let slices = Merge::from_removes_adds(
+            vec![&b"a\nb\nc\n"[..]],
+            vec![&b"A\nb\nc\n"[..], &b"a\nb\nC\n"[..]],
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Text),
+            MergeResult::Resolved(hunk(b"A\nb\nC\n"))
+        );
+        assert_eq!(
+            merge_with_driver(&slices, MergeDriver::Binary),
+            MergeResult::Conflict(vec![Merge::from_removes_adds(
+                vec![hunk(b"a\nb\nc\n")],
+                vec![hunk(b"A\nb\nc\n"), hunk(b"a\nb\nC\n")]
+            )])
+        );
+    }
+
+    #[test]
+    fn test_merge_drivers_driver_for() {
+        let drivers = MergeDrivers::new([
+            (glob::Pattern::new("Cargo.lock").unwrap(), MergeDriver::Ours),
+            (glob::Pattern::new("*.md").unwrap(), MergeDriver::Union),
+            (glob::Pattern::new("docs/*").unwrap(), MergeDriver::Binary),
+        ]);
+        let driver_for = |path| drivers.driver_for(RepoPath::from_internal_string(path));
+        assert_eq!(driver_for("Cargo.lock"), MergeDriver::Ours);
+        assert_eq!(driver_for("lib/Cargo.lock"), MergeDriver::Text);
+        assert_eq!(driver_for("CHANGELOG.md"), MergeDriver::Union);
+        assert_eq!(driver_for("docs/index.md"), MergeDriver::Binary);
+        assert_eq!(driver_for("docs/sub/index.md"), MergeDriver::Text);
+        assert_eq!(driver_for("src/main.rs"), MergeDriver::Text);
+    }
+
+    #[test]
+    fn test_content_types_classify() {
+        let utf16 = b"h\0i\0\n\0";
+        assert_eq!(ContentType::detect(b""), ContentType::Text);
+        assert_eq!(ContentType::detect(b"hi\n"), ContentType::Text);
+        assert_eq!(ContentType::detect(utf16), ContentType::Binary);
+        // Only the start of the contents is looked at
+        let mut late_nul = vec![b'a'; CONTENT_TYPE_DETECTION_LEN];
+        late_nul.push(0);
+        assert_eq!(ContentType::detect(&late_nul), ContentType::Text);
+
+        let content_types = ContentTypes::new([
+            (glob::Pattern::new("*.utf16").unwrap(), ContentType::Text),
+            (glob::Pattern::new("*.svg").unwrap(), ContentType::Binary),
+        ]);
+        let classify =
+            |path, contents| content_types.classify(RepoPath::from_internal_string(path), contents);
+        assert_eq!(classify("file.utf16", utf16), ContentType::Text);
+        assert_eq!(classify("file.txt", utf16), ContentType::Binary);
+        assert_eq!(classify("dir/file.utf16", utf16), ContentType::Binary);
+        assert_eq!(classify("image.svg", b"<svg/>"), ContentType::Binary);
+        assert_eq!(classify("file.txt", b"text"), ContentType::Text);
+    }
 }
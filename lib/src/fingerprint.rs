@@ -0,0 +1,174 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fingerprints of the state of a repo, e.g. for checking that two mirrors of
+//! a repo are identical.
+//!
+//! A fingerprint is the BLAKE2b-512 hash of the serialization below (version
+//! 1). Integers are little-endian, and a "string" is a `u64` byte length
+//! followed by the bytes. Ids are strings of their raw bytes.
+//!
+//! 1. The string `jj-state-fingerprint`, the `u32` version, and a depth byte (0
+//!    for shallow, 1 for deep).
+//! 2. The id of the operation.
+//! 3. A `u64` count and the visible head ids, sorted by bytes.
+//! 4. A `u64` count and the local branches, sorted by name. Each branch is its
+//!    name followed by a `u64` count of the terms of its target, each term
+//!    being a 0 byte if absent or a 1 byte and the commit id. Absent branches
+//!    aren't included.
+//! 5. A `u64` count and the working-copy commits, sorted by workspace id. Each
+//!    is the workspace id followed by the commit id.
+//! 6. For deep fingerprints only, a `u64` count and the ids of all indexed
+//!    commits, including hidden ones, sorted by bytes. Since objects are
+//!    content-addressed, the commit ids stand for their contents.
+//!
+//! Any change to this format must bump [`FINGERPRINT_VERSION`].
+
+use std::fmt::{Debug, Formatter};
+
+use blake2::{Blake2b512, Digest};
+use itertools::Itertools as _;
+
+use crate::backend::CommitId;
+use crate::object_id::ObjectId as _;
+use crate::op_store::RefTarget;
+use crate::repo::{ReadonlyRepo, Repo as _};
+use crate::revset::{RevsetEvaluationError, RevsetExpression};
+
+/// The version of the serialization the fingerprint is a hash of.
+pub const FINGERPRINT_VERSION: u32 = 1;
+
+/// What to include in a fingerprint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FingerprintDepth {
+    /// The operation, visible heads, local branches, and working-copy commits.
+    Shallow,
+    /// Like `Shallow`, plus the ids of all commits in the index, including
+    /// hidden ones.
+    Deep,
+}
+
+/// A hash of the state of a repo. See the module documentation for what it
+/// covers.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint(Vec<u8>);
+
+impl Fingerprint {
+    /// The raw hash bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The hash in lowercase hex.
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0)
+    }
+}
+
+impl Debug for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Fingerprint").field(&self.to_hex()).finish()
+    }
+}
+
+struct FingerprintHasher(Blake2b512);
+
+impl FingerprintHasher {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    fn write_string(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.write_bytes(bytes);
+    }
+
+    fn write_ids<'a>(&mut self, ids: impl IntoIterator<Item = &'a CommitId>) {
+        let ids = ids.into_iter().sorted().collect_vec();
+        self.write_u64(ids.len() as u64);
+        for id in ids {
+            self.write_string(id.as_bytes());
+        }
+    }
+
+    fn write_ref_target(&mut self, target: &RefTarget) {
+        let terms = target.as_merge().iter().collect_vec();
+        self.write_u64(terms.len() as u64);
+        for term in terms {
+            match term {
+                None => self.write_bytes(&[0]),
+                Some(id) => {
+                    self.write_bytes(&[1]);
+                    self.write_string(id.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Computes the fingerprint of the state of `repo` at its operation.
+///
+/// The deep variant reads the commit ids from the index, without loading the
+/// commits themselves.
+pub fn state_fingerprint(
+    repo: &ReadonlyRepo,
+    depth: FingerprintDepth,
+) -> Result<Fingerprint, RevsetEvaluationError> {
+    let mut hasher = FingerprintHasher(Blake2b512::new());
+    hasher.write_bytes(b"jj-state-fingerprint");
+    hasher.write_bytes(&FINGERPRINT_VERSION.to_le_bytes());
+    hasher.write_bytes(&[match depth {
+        FingerprintDepth::Shallow => 0,
+        FingerprintDepth::Deep => 1,
+    }]);
+    hasher.write_string(repo.op_id().as_bytes());
+
+    let view = repo.view();
+    hasher.write_ids(view.heads());
+    let branches = view.local_branches().collect_vec();
+    hasher.write_u64(branches.len() as u64);
+    for (name, target) in branches {
+        hasher.write_string(name.as_bytes());
+        hasher.write_ref_target(target);
+    }
+    let wc_commit_ids = view
+        .wc_commit_ids()
+        .iter()
+        .sorted_by(|(ws_id1, _), (ws_id2, _)| ws_id1.as_str().cmp(ws_id2.as_str()))
+        .collect_vec();
+    hasher.write_u64(wc_commit_ids.len() as u64);
+    for (workspace_id, commit_id) in wc_commit_ids {
+        hasher.write_string(workspace_id.as_str().as_bytes());
+        hasher.write_string(commit_id.as_bytes());
+    }
+
+    if depth == FingerprintDepth::Deep {
+        let all_heads = repo
+            .index()
+            .all_heads_for_gc()
+            .map_err(|err| RevsetEvaluationError::Other(err.to_string()))?
+            .collect_vec();
+        let commit_ids = RevsetExpression::commits(all_heads)
+            .ancestors()
+            .evaluate_programmatic(repo)?
+            .iter()
+            .collect_vec();
+        hasher.write_ids(&commit_ids);
+    }
+    Ok(Fingerprint(hasher.0.finalize().to_vec()))
+}
@@ -23,7 +23,6 @@ use std::{fmt, iter, str};
 
 use git2::Oid;
 use itertools::Itertools;
-use tempfile::NamedTempFile;
 use thiserror::Error;
 
 use crate::backend::{BackendError, CommitId};
@@ -35,6 +34,7 @@ use crate::op_store::{RefTarget, RefTargetOptionExt, RemoteRef, RemoteRefState};
 use crate::refs::{self, BranchPushUpdate};
 use crate::repo::{MutableRepo, Repo};
 use crate::revset::RevsetExpression;
+use crate::scratch_dir::ScratchDir;
 use crate::settings::GitSettings;
 use crate::store::Store;
 use crate::str_util::StringPattern;
@@ -963,13 +963,24 @@ fn update_git_head(
     Ok(())
 }
 
+#[derive(Debug, Error)]
+pub enum GitResetHeadError {
+    #[error("The Git repo has no working copy")]
+    NoWorkingCopy,
+    #[error(transparent)]
+    InternalGitError(#[from] git2::Error),
+}
+
 /// Sets `HEAD@git` to the parent of the given working-copy commit and resets
-/// the Git index.
+/// the Git index. Fails with `NoWorkingCopy` if the Git repo is bare.
 pub fn reset_head(
     mut_repo: &mut MutableRepo,
     git_repo: &git2::Repository,
     wc_commit: &Commit,
-) -> Result<(), git2::Error> {
+) -> Result<(), GitResetHeadError> {
+    if git_repo.is_bare() {
+        return Err(GitResetHeadError::NoWorkingCopy);
+    }
     let first_parent_id = &wc_commit.parent_ids()[0];
     let first_parent = if first_parent_id != mut_repo.store().root_commit_id() {
         RefTarget::normal(first_parent_id.clone())
@@ -1021,7 +1032,7 @@ pub fn reset_head(
             match git_repo.find_reference(UNBORN_ROOT_REF_NAME) {
                 Ok(mut git_repo_ref) => git_repo_ref.delete()?,
                 Err(err) if err.code() == git2::ErrorCode::NotFound => {}
-                Err(err) => return Err(err),
+                Err(err) => return Err(err.into()),
             }
             git_repo.reference_symbolic("HEAD", UNBORN_ROOT_REF_NAME, true, "unset HEAD by jj")?;
         }
@@ -1731,9 +1742,10 @@ pub enum GitConfigParseError {
 
 pub fn parse_gitmodules(
     config: &mut dyn Read,
+    scratch_dir: &ScratchDir,
 ) -> Result<BTreeMap<String, SubmoduleConfig>, GitConfigParseError> {
     // git2 can only read from a path, so set one up
-    let mut temp_file = NamedTempFile::new()?;
+    let mut temp_file = scratch_dir.new_file("gitmodules-", "")?;
     std::io::copy(config, &mut temp_file)?;
     let path = temp_file.into_temp_path();
     let git_config = git2::Config::open(&path)?;
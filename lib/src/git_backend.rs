@@ -27,7 +27,7 @@ use std::{fs, io, str};
 use async_trait::async_trait;
 use futures::stream::BoxStream;
 use gix::bstr::{BStr, BString};
-use gix::objs::{CommitRef, CommitRefIter, WriteTo};
+use gix::objs::{CommitRef, CommitRefIter, Find as _, WriteTo};
 use itertools::Itertools;
 use pollster::FutureExt;
 use prost::Message;
@@ -36,9 +36,9 @@ use thiserror::Error;
 
 use crate::backend::{
     make_root_commit, Backend, BackendError, BackendInitError, BackendLoadError, BackendResult,
-    ChangeId, Commit, CommitId, Conflict, ConflictId, ConflictTerm, CopyRecord, CopySource,
-    CopySources, FileId, MergedTreeId, MillisSinceEpoch, SecureSig, Signature, SigningFn,
-    SymlinkId, Timestamp, Tree, TreeId, TreeValue,
+    ChangeId, Commit, CommitId, CompactOptions, CompactStats, Conflict, ConflictId, ConflictTerm,
+    CopyRecord, CopySource, CopySources, FileId, MergedTreeId, MillisSinceEpoch, SecureSig,
+    Signature, SigningFn, SymlinkId, Timestamp, Tree, TreeId, TreeValue,
 };
 use crate::file_util::{IoResultExt as _, PathError};
 use crate::index::Index;
@@ -124,6 +124,9 @@ pub struct GitBackend {
     empty_tree_id: TreeId,
     extra_metadata_store: TableStore,
     cached_extra_metadata: Mutex<Option<Arc<ReadonlyTable>>>,
+    /// Object directories added by `add_alternates()`, searched after the Git
+    /// repo itself. They aren't persisted.
+    alternates: Mutex<Vec<gix::odb::Handle>>,
     /// Whether tree of imported commit should be promoted to non-legacy format.
     imported_commit_uses_tree_conflict_format: bool,
 }
@@ -150,6 +153,7 @@ impl GitBackend {
             empty_tree_id,
             extra_metadata_store,
             cached_extra_metadata: Mutex::new(None),
+            alternates: Mutex::new(vec![]),
             imported_commit_uses_tree_conflict_format,
         }
     }
@@ -382,12 +386,41 @@ impl GitBackend {
         self.save_extra_metadata_table(mut_table, &table_lock)
     }
 
+    /// Looks up an object in the Git repo, falling back to the alternate
+    /// object directories.
+    fn find_object<'repo>(
+        &self,
+        repo: &'repo gix::Repository,
+        git_id: gix::ObjectId,
+        id: &impl ObjectId,
+    ) -> BackendResult<gix::Object<'repo>> {
+        let err = match repo.find_object(git_id) {
+            Ok(object) => return Ok(object),
+            Err(err @ gix::object::find::existing::Error::NotFound { .. }) => err,
+            Err(err) => return Err(to_read_object_err(err, id)),
+        };
+        let mut buf = vec![];
+        for alternate in self.alternates.lock().unwrap().iter() {
+            let data = alternate
+                .try_find(&git_id, &mut buf)
+                .map_err(|err| to_read_object_err(err, id))?;
+            if let Some(data) = data {
+                let object = gix::ObjectDetached {
+                    id: git_id,
+                    kind: data.kind,
+                    data: data.data.to_vec(),
+                };
+                return Ok(object.attach(repo));
+            }
+        }
+        Err(map_not_found_err(err, id))
+    }
+
     fn read_file_sync(&self, id: &FileId) -> BackendResult<Box<dyn Read>> {
         let git_blob_id = validate_git_object_id(id)?;
         let locked_repo = self.lock_git_repo();
-        let mut blob = locked_repo
-            .find_object(git_blob_id)
-            .map_err(|err| map_not_found_err(err, id))?
+        let mut blob = self
+            .find_object(&locked_repo, git_blob_id, id)?
             .try_into_blob()
             .map_err(|err| to_read_object_err(err, id))?;
         Ok(Box::new(Cursor::new(blob.take_data())))
@@ -429,8 +462,7 @@ impl GitBackend {
         // TODO(kfm): probably want to do something here if it is a merge
         let tree_id = tree.first().clone();
         let gix_id = validate_git_object_id(&tree_id)?;
-        repo.find_object(gix_id)
-            .map_err(|err| map_not_found_err(err, &tree_id))?
+        self.find_object(repo, gix_id, &tree_id)?
             .try_into_tree()
             .map_err(|err| to_read_object_err(err, &tree_id))
     }
@@ -526,10 +558,7 @@ fn commit_from_git_without_root_parent(
             MergedTreeId::Legacy(tree_id)
         }
     });
-    // Use lossy conversion as commit message with "mojibake" is still better than
-    // nothing.
-    // TODO: what should we do with commit.encoding?
-    let description = String::from_utf8_lossy(commit.message).into_owned();
+    let (description, description_lossy) = decode_message(commit.message, commit.encoding);
     let author = signature_from_git(commit.author());
     let committer = signature_from_git(commit.committer());
 
@@ -560,12 +589,24 @@ fn commit_from_git_without_root_parent(
         root_tree,
         change_id,
         description,
+        description_lossy,
         author,
         committer,
         secure_sig,
     })
 }
 
+/// Decodes a commit message according to the commit's `encoding` header
+/// (UTF-8 if absent or unknown). Returns the message and whether it had to be
+/// decoded lossily, as a message with "mojibake" is still better than nothing.
+fn decode_message(message: &BStr, encoding: Option<&BStr>) -> (String, bool) {
+    let encoding = encoding
+        .and_then(|label| encoding_rs::Encoding::for_label(label))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, had_errors) = encoding.decode_without_bom_handling(message);
+    (text.into_owned(), had_errors)
+}
+
 const EMPTY_STRING_PLACEHOLDER: &str = "JJ_EMPTY_STRING";
 
 fn signature_from_git(signature: gix::actor::SignatureRef) -> Signature {
@@ -919,9 +960,8 @@ impl Backend for GitBackend {
     async fn read_symlink(&self, _path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
         let git_blob_id = validate_git_object_id(id)?;
         let locked_repo = self.lock_git_repo();
-        let mut blob = locked_repo
-            .find_object(git_blob_id)
-            .map_err(|err| map_not_found_err(err, id))?
+        let mut blob = self
+            .find_object(&locked_repo, git_blob_id, id)?
             .try_into_blob()
             .map_err(|err| to_read_object_err(err, id))?;
         let target = String::from_utf8(blob.take_data())
@@ -949,9 +989,8 @@ impl Backend for GitBackend {
         let git_tree_id = validate_git_object_id(id)?;
 
         let locked_repo = self.lock_git_repo();
-        let git_tree = locked_repo
-            .find_object(git_tree_id)
-            .map_err(|err| map_not_found_err(err, id))?
+        let git_tree = self
+            .find_object(&locked_repo, git_tree_id, id)?
             .try_into_tree()
             .map_err(|err| to_read_object_err(err, id))?;
         let mut tree = Tree::default();
@@ -1108,9 +1147,7 @@ impl Backend for GitBackend {
 
         let mut commit = {
             let locked_repo = self.lock_git_repo();
-            let git_object = locked_repo
-                .find_object(git_commit_id)
-                .map_err(|err| map_not_found_err(err, id))?;
+            let git_object = self.find_object(&locked_repo, git_commit_id, id)?;
             commit_from_git_without_root_parent(id, &git_object, false)?
         };
         if commit.parents.is_empty() {
@@ -1247,6 +1284,8 @@ impl Backend for GitBackend {
         // Update the signature to match the one that was actually written to the object
         // store
         contents.committer.timestamp.timestamp = MillisSinceEpoch(committer.time.seconds * 1000);
+        // The description was written as UTF-8 without an encoding header
+        contents.description_lossy = false;
         let mut mut_table = table.start_mutation();
         mut_table.add_entry(id.to_bytes(), extras);
         self.save_extra_metadata_table(mut_table, &table_lock)?;
@@ -1353,6 +1392,22 @@ impl Backend for GitBackend {
         git_repo.refs.force_refresh_packed_buffer().ok();
         Ok(())
     }
+
+    fn compact(&self, _options: &CompactOptions) -> BackendResult<CompactStats> {
+        // Git packs objects itself, e.g. in `gc()`.
+        Ok(CompactStats::default())
+    }
+
+    fn add_alternates(&self, object_dirs: &[PathBuf]) -> BackendResult<()> {
+        let mut alternates = self.alternates.lock().unwrap();
+        for dir in object_dirs {
+            let handle = gix::odb::at(dir)
+                .context(dir)
+                .map_err(|err| BackendError::Other(err.into()))?;
+            alternates.push(handle);
+        }
+        Ok(())
+    }
 }
 
 /// Write a tree conflict as a special tree with `.jjconflict-base-N` and
@@ -1747,6 +1802,59 @@ mod tests {
         assert_eq!(std::str::from_utf8(&sig.data).unwrap(), commit_buf);
     }
 
+    #[test]
+    fn read_git_commit_with_encoding() {
+        let settings = user_settings();
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let git_repo_path = temp_dir.path().join("git");
+        let git_repo = git2::Repository::init(git_repo_path).unwrap();
+        let backend = GitBackend::init_external(&settings, store_path, git_repo.path()).unwrap();
+
+        let write_raw_commit = |headers: &str, message: &[u8]| {
+            let mut data = format!(
+                "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor Someone \
+                 <someone@example.com> 1000 +0000\ncommitter Someone <someone@example.com> 1000 \
+                 +0000\n{headers}\n"
+            )
+            .into_bytes();
+            data.extend_from_slice(message);
+            let git_id = git_repo
+                .odb()
+                .unwrap()
+                .write(git2::ObjectType::Commit, &data)
+                .unwrap();
+            CommitId::from_bytes(git_id.as_bytes())
+        };
+        let read_commit = |id: &CommitId| backend.read_commit(id).block_on().unwrap();
+
+        // The message is decoded according to the encoding header
+        let latin1_id = write_raw_commit("encoding ISO-8859-1\n", b"caf\xe9\n");
+        let latin1_commit = read_commit(&latin1_id);
+        assert_eq!(latin1_commit.description, "caf\u{e9}\n");
+        assert!(!latin1_commit.description_lossy);
+
+        // Invalid bytes are replaced, and the commit is flagged
+        let invalid_id = write_raw_commit("", b"caf\xe9\n");
+        let invalid_commit = read_commit(&invalid_id);
+        assert_eq!(invalid_commit.description, "caf\u{fffd}\n");
+        assert!(invalid_commit.description_lossy);
+
+        // Rewritten commits are written as UTF-8 without an encoding header
+        for commit in [latin1_commit, invalid_commit] {
+            let (new_id, new_commit) = backend.write_commit(commit.clone(), None).unwrap();
+            assert!(!new_commit.description_lossy);
+            let git_commit = git_repo
+                .find_commit(Oid::from_bytes(new_id.as_bytes()).unwrap())
+                .unwrap();
+            assert_eq!(git_commit.message_encoding(), None);
+            assert_eq!(git_commit.message_bytes(), commit.description.as_bytes());
+            let new_commit = read_commit(&new_id);
+            assert_eq!(new_commit.description, commit.description);
+            assert!(!new_commit.description_lossy);
+        }
+    }
+
     #[test]
     fn read_empty_string_placeholder() {
         let git_signature1 = gix::actor::SignatureRef {
@@ -1809,6 +1917,7 @@ mod tests {
             root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
             change_id: ChangeId::from_hex("abc123"),
             description: "".to_string(),
+            description_lossy: false,
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
@@ -1887,6 +1996,7 @@ mod tests {
             root_tree: MergedTreeId::Merge(root_tree.clone()),
             change_id: ChangeId::from_hex("abc123"),
             description: "".to_string(),
+            description_lossy: false,
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
@@ -1976,6 +2086,7 @@ mod tests {
             root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
             change_id: ChangeId::new(vec![]),
             description: "initial".to_string(),
+            description_lossy: false,
             author: signature.clone(),
             committer: signature,
             secure_sig: None,
@@ -2053,6 +2164,7 @@ mod tests {
             root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
             change_id: ChangeId::new(vec![]),
             description: "initial".to_string(),
+            description_lossy: false,
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
@@ -2094,6 +2206,7 @@ mod tests {
             root_tree: MergedTreeId::Legacy(backend.empty_tree_id().clone()),
             change_id: ChangeId::new(vec![]),
             description: "initial".to_string(),
+            description_lossy: false,
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
@@ -14,6 +14,7 @@
 
 #![allow(missing_docs)]
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::{fs, io, iter};
@@ -36,11 +37,26 @@ pub enum GitIgnoreError {
     Underlying(#[from] ignore::Error),
 }
 
+/// The ignore rule that decided that a path is ignored.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GitIgnoreRule {
+    /// The file the rule was read from. For patterns that were not read from a
+    /// file, this is the directory prefix they were chained at.
+    pub source_file: PathBuf,
+    /// The 1-based line number of the rule within `source_file`.
+    pub line: usize,
+}
+
 /// Models the effective contents of multiple .gitignore files.
 #[derive(Debug)]
 pub struct GitIgnoreFile {
     parent: Option<Arc<GitIgnoreFile>>,
     matcher: gitignore::Gitignore,
+    source_file: PathBuf,
+    // Maps the (trimmed) pattern text to the line it was last defined on. Since
+    // the last matching pattern wins, identical patterns on earlier lines can
+    // never be the deciding rule.
+    rule_lines: HashMap<String, usize>,
 }
 
 impl GitIgnoreFile {
@@ -48,6 +64,8 @@ impl GitIgnoreFile {
         Arc::new(GitIgnoreFile {
             parent: None,
             matcher: gitignore::Gitignore::empty(),
+            source_file: PathBuf::new(),
+            rule_lines: HashMap::new(),
         })
     }
 
@@ -59,8 +77,18 @@ impl GitIgnoreFile {
         self: &Arc<GitIgnoreFile>,
         prefix: &str,
         input: &[u8],
+    ) -> Result<Arc<GitIgnoreFile>, GitIgnoreError> {
+        self.chain_from_source(prefix, PathBuf::from(prefix), input)
+    }
+
+    fn chain_from_source(
+        self: &Arc<GitIgnoreFile>,
+        prefix: &str,
+        source_file: PathBuf,
+        input: &[u8],
     ) -> Result<Arc<GitIgnoreFile>, GitIgnoreError> {
         let mut builder = gitignore::GitignoreBuilder::new(prefix);
+        let mut rule_lines = HashMap::new();
         for (i, input_line) in input.split(|b| *b == b'\n').enumerate() {
             let line =
                 std::str::from_utf8(input_line).map_err(|err| GitIgnoreError::InvalidUtf8 {
@@ -72,6 +100,16 @@ impl GitIgnoreFile {
             // FIXME: do we need to provide the `from` argument? Is it for providing
             // diagnostics or correctness?
             builder.add_line(None, line)?;
+            // Same normalization as `GitignoreBuilder::add_line()` applies before
+            // recording the original pattern text.
+            let pattern = if line.ends_with("\\ ") {
+                line
+            } else {
+                line.trim_end()
+            };
+            if !pattern.is_empty() && !pattern.starts_with('#') {
+                rule_lines.insert(pattern.to_owned(), i + 1);
+            }
         }
         let matcher = builder.build()?;
         let parent = if self.matcher.is_empty() {
@@ -79,7 +117,12 @@ impl GitIgnoreFile {
         } else {
             Some(self.clone())
         };
-        Ok(Arc::new(GitIgnoreFile { parent, matcher }))
+        Ok(Arc::new(GitIgnoreFile {
+            parent,
+            matcher,
+            source_file,
+            rule_lines,
+        }))
     }
 
     /// Concatenates new `.gitignore` file at the `prefix` directory.
@@ -96,7 +139,7 @@ impl GitIgnoreFile {
                 path: file.clone(),
                 source: err,
             })?;
-            self.chain(prefix, &buf)
+            self.chain_from_source(prefix, file, &buf)
         } else {
             Ok(self.clone())
         }
@@ -132,6 +175,32 @@ impl GitIgnoreFile {
         };
         self.matches_helper(path, is_dir)
     }
+
+    /// Like `matches()`, but returns the rule that caused the path to be
+    /// ignored. Returns `None` if the path isn't ignored, including when the
+    /// last applicable rule is a negated (`!`) pattern.
+    pub fn matched_rule(&self, path: &str) -> Option<GitIgnoreRule> {
+        let (path, is_dir) = match path.strip_suffix('/') {
+            Some(path) => (path, true),
+            None => (path, false),
+        };
+        iter::successors(Some(self), |file| file.parent.as_deref())
+            .find_map(
+                |file| match file.matcher.matched_path_or_any_parents(path, is_dir) {
+                    ignore::Match::None => None,
+                    ignore::Match::Ignore(glob) => Some(Some(GitIgnoreRule {
+                        source_file: file.source_file.clone(),
+                        line: file
+                            .rule_lines
+                            .get(glob.original())
+                            .copied()
+                            .unwrap_or_default(),
+                    })),
+                    ignore::Match::Whitelist(_) => Some(None),
+                },
+            )
+            .flatten()
+    }
 }
 
 #[cfg(test)]
@@ -391,6 +460,37 @@ mod tests {
         assert!(!file3.matches("foo/bar/qux"));
     }
 
+    #[test]
+    fn test_gitignore_matched_rule() {
+        let file1 = GitIgnoreFile::empty()
+            .chain("", b"# comment\n*.o\n\nfoo/\n")
+            .unwrap();
+        let file2 = file1.chain("foo/", b"!/bar\n/baz  \n").unwrap();
+        assert_eq!(file1.matched_rule("bar"), None);
+        assert_eq!(
+            file1.matched_rule("dir/bar.o"),
+            Some(GitIgnoreRule {
+                source_file: PathBuf::from(""),
+                line: 2,
+            })
+        );
+        assert_eq!(
+            file1.matched_rule("foo/qux"),
+            Some(GitIgnoreRule {
+                source_file: PathBuf::from(""),
+                line: 4,
+            })
+        );
+        assert_eq!(file2.matched_rule("foo/bar"), None);
+        assert_eq!(
+            file2.matched_rule("foo/baz"),
+            Some(GitIgnoreRule {
+                source_file: PathBuf::from("foo/"),
+                line: 2,
+            })
+        );
+    }
+
     #[test]
     fn test_gitignore_negative_parent_directory() {
         // The following script shows that Git ignores the file:
@@ -22,6 +22,7 @@ use std::{io, str};
 
 use thiserror::Error;
 
+use crate::scratch_dir::ScratchDir;
 use crate::signing::{SigStatus, SignError, SigningBackend, Verification};
 
 // Search for one of the:
@@ -103,6 +104,7 @@ pub struct GpgBackend {
     program: OsString,
     allow_expired_keys: bool,
     extra_args: Vec<OsString>,
+    scratch_dir: ScratchDir,
 }
 
 #[derive(Debug, Error)]
@@ -128,9 +130,17 @@ impl GpgBackend {
             program,
             allow_expired_keys,
             extra_args: vec![],
+            scratch_dir: ScratchDir::os_temp(),
         }
     }
 
+    /// Creates the signature files to verify in `scratch_dir` instead of the
+    /// temp directory of the OS.
+    pub fn with_scratch_dir(mut self, scratch_dir: ScratchDir) -> Self {
+        self.scratch_dir = scratch_dir;
+        self
+    }
+
     /// Primarily intended for testing
     pub fn with_extra_args(mut self, args: &[OsString]) -> Self {
         self.extra_args.extend_from_slice(args);
@@ -147,6 +157,12 @@ impl GpgBackend {
                 .get_bool("signing.backends.gpg.allow-expired-keys")
                 .unwrap_or(false),
         )
+        .with_scratch_dir(
+            ScratchDir::from_config(config)
+                .ok()
+                .flatten()
+                .unwrap_or_else(ScratchDir::os_temp),
+        )
     }
 
     fn create_command(&self) -> Command {
@@ -176,9 +192,9 @@ impl SigningBackend for GpgBackend {
     }
 
     fn verify(&self, data: &[u8], signature: &[u8]) -> Result<Verification, SignError> {
-        let mut signature_file = tempfile::Builder::new()
-            .prefix(".jj-gpg-sig-tmp-")
-            .tempfile()
+        let mut signature_file = self
+            .scratch_dir
+            .new_file("gpg-sig-", "")
             .map_err(GpgError::Io)?;
         signature_file.write_all(signature).map_err(GpgError::Io)?;
         signature_file.flush().map_err(GpgError::Io)?;
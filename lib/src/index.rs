@@ -16,13 +16,14 @@
 
 use std::any::Any;
 use std::fmt::Debug;
+use std::slice;
 use std::sync::Arc;
 
 use thiserror::Error;
 
 use crate::backend::{ChangeId, CommitId};
 use crate::commit::Commit;
-use crate::object_id::{HexPrefix, PrefixResolution};
+use crate::object_id::{HexPrefix, ObjectId as _, PrefixResolution};
 use crate::operation::Operation;
 use crate::revset::{ResolvedExpression, Revset, RevsetEvaluationError};
 use crate::store::Store;
@@ -43,6 +44,12 @@ pub struct IndexWriteError(pub Box<dyn std::error::Error + Send + Sync>);
 #[error("Cannot collect all heads by index of this type")]
 pub struct AllHeadsForGcUnsupported;
 
+/// An error returned if a commit passed to `Index::ahead_behind_sets()` and
+/// similar methods isn't in the index.
+#[derive(Debug, Error)]
+#[error("Commit {} is not in the index", .0.hex())]
+pub struct CommitNotIndexed(pub CommitId);
+
 /// Defines the interface for types that provide persistent storage for an
 /// index.
 pub trait IndexStore: Send + Sync + Debug {
@@ -61,6 +68,19 @@ pub trait IndexStore: Send + Sync + Debug {
         store: &Arc<Store>,
     ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError>;
 
+    /// Like `get_index_at_op()`, but never writes to the index store. If the
+    /// index at `op` hasn't been built yet, it's built in memory.
+    ///
+    /// The default implementation calls `get_index_at_op()`, which is only
+    /// correct for index stores that don't write when reading an index.
+    fn get_index_at_op_in_memory(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError> {
+        self.get_index_at_op(op, store)
+    }
+
     /// Writes `index` to the index store and returns a read-only version of the
     /// index.
     fn write_index(
@@ -89,6 +109,10 @@ pub trait Index: Send + Sync {
     /// Returns true if `commit_id` is present in the index.
     fn has_id(&self, commit_id: &CommitId) -> bool;
 
+    /// Returns the parent ids of the `commit_id` commit, which must be present
+    /// in the index.
+    fn parent_ids(&self, commit_id: &CommitId) -> Vec<CommitId>;
+
     /// Returns true if `ancestor_id` commit is an ancestor of the
     /// `descendant_id` commit, or if `ancestor_id` equals `descendant_id`.
     fn is_ancestor(&self, ancestor_id: &CommitId, descendant_id: &CommitId) -> bool;
@@ -98,6 +122,36 @@ pub trait Index: Send + Sync {
     /// common ancestors.
     fn common_ancestors(&self, set1: &[CommitId], set2: &[CommitId]) -> Vec<CommitId>;
 
+    /// Returns the number of commits that are ancestors of `set1` but not of
+    /// `set2`, and vice versa. Commits are counted as their own ancestors.
+    ///
+    /// This is the "N commits ahead, M commits behind" of `set1` compared to
+    /// `set2`.
+    fn ahead_behind_sets(
+        &self,
+        set1: &[CommitId],
+        set2: &[CommitId],
+    ) -> Result<(u64, u64), CommitNotIndexed>;
+
+    /// Same as `ahead_behind_sets()` for a single commit on each side.
+    fn ahead_behind(
+        &self,
+        commit_id1: &CommitId,
+        commit_id2: &CommitId,
+    ) -> Result<(u64, u64), CommitNotIndexed> {
+        self.ahead_behind_sets(slice::from_ref(commit_id1), slice::from_ref(commit_id2))
+    }
+
+    /// Returns the number of commits that are ancestors of `wanted` but not of
+    /// `unwanted`, i.e. the size of the revset `unwanted..wanted`.
+    fn count_range(
+        &self,
+        wanted: &[CommitId],
+        unwanted: &[CommitId],
+    ) -> Result<u64, CommitNotIndexed> {
+        Ok(self.ahead_behind_sets(wanted, unwanted)?.0)
+    }
+
     /// Heads among all indexed commits at the associated operation.
     ///
     /// Suppose the index contains all the historical heads and their
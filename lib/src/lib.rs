@@ -37,11 +37,14 @@ pub mod default_index;
 pub mod default_submodule_store;
 pub mod diff;
 pub mod dsl_util;
+pub mod evolog;
+pub mod evolution;
 pub mod extensions_map;
 pub mod file_util;
 pub mod files;
 pub mod fileset;
 mod fileset_parser;
+pub mod fingerprint;
 pub mod fmt_util;
 pub mod fsmonitor;
 #[cfg(feature = "git")]
@@ -60,6 +63,7 @@ pub mod lock;
 pub mod matchers;
 pub mod merge;
 pub mod merged_tree;
+pub mod object_fetch;
 pub mod object_id;
 pub mod op_heads_store;
 pub mod op_store;
@@ -69,10 +73,12 @@ pub mod operation;
 pub mod protos;
 pub mod refs;
 pub mod repo;
+pub mod repo_format;
 pub mod repo_path;
 pub mod revset;
 mod revset_parser;
 pub mod rewrite;
+pub mod scratch_dir;
 #[cfg(feature = "testing")]
 pub mod secret_backend;
 pub mod settings;
@@ -84,6 +90,8 @@ pub mod stacked_table;
 pub mod store;
 pub mod str_util;
 pub mod submodule_store;
+pub mod summary;
+pub mod time_util;
 pub mod transaction;
 pub mod tree;
 pub mod tree_builder;
@@ -15,12 +15,14 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::fs;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
+use std::{fs, mem};
 
 use async_trait::async_trait;
 use blake2::{Blake2b512, Digest};
@@ -29,9 +31,10 @@ use prost::Message;
 use tempfile::NamedTempFile;
 
 use crate::backend::{
-    make_root_commit, Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict,
-    ConflictId, ConflictTerm, CopyRecord, FileId, MergedTreeId, MillisSinceEpoch, SecureSig,
-    Signature, SigningFn, SymlinkId, Timestamp, Tree, TreeId, TreeValue,
+    make_root_commit, Backend, BackendError, BackendInitError, BackendLoadError, BackendResult,
+    ChangeId, Commit, CommitId, CompactOptions, CompactStats, Conflict, ConflictId, ConflictTerm,
+    CopyRecord, FileId, MergedTreeId, MillisSinceEpoch, SecureSig, Signature, SigningFn, SymlinkId,
+    Timestamp, Tree, TreeId, TreeValue,
 };
 use crate::content_hash::blake2b_hash;
 use crate::file_util::persist_content_addressed_temp_file;
@@ -39,10 +42,13 @@ use crate::index::Index;
 use crate::merge::MergeBuilder;
 use crate::object_id::ObjectId;
 use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponentBuf};
+use crate::settings::UserSettings;
 
 const COMMIT_ID_LENGTH: usize = 64;
 const CHANGE_ID_LENGTH: usize = 16;
 
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
 fn map_not_found_err(err: std::io::Error, id: &impl ObjectId) -> BackendError {
     if err.kind() == std::io::ErrorKind::NotFound {
         BackendError::ObjectNotFound {
@@ -63,12 +69,380 @@ fn to_other_err(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Bac
     BackendError::Other(err.into())
 }
 
+/// Compression applied to the file contents written by the local backend, and
+/// to commits, trees and conflicts if `local-store.compress-objects` is set.
+/// Object ids are computed over the uncompressed contents, so they don't
+/// depend on this setting, and objects written with any setting can be read
+/// back regardless of the current one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalCompression {
+    /// Zstandard compression. Level 0 means zstd's default level.
+    Zstd { level: i32 },
+    /// zlib (deflate) compression, with a level from 0 to 9.
+    Zlib { level: u32 },
+}
+
+impl LocalCompression {
+    /// Reads `local-store.compression` and `local-store.compression-level`.
+    /// Out-of-range levels are clamped to the algorithm's range.
+    pub fn from_config(config: &config::Config) -> Result<Self, config::ConfigError> {
+        let level = match config.get_int("local-store.compression-level") {
+            Ok(level) => Some(level),
+            Err(config::ConfigError::NotFound(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let name = match config.get_string("local-store.compression") {
+            Ok(name) => Some(name),
+            Err(config::ConfigError::NotFound(_)) => None,
+            Err(err) => return Err(err),
+        };
+        match name.as_deref() {
+            None | Some("zstd") => Ok(LocalCompression::Zstd {
+                level: level.map_or(0, |level| level.clamp(-7, 22) as i32),
+            }),
+            Some("zlib") => Ok(LocalCompression::Zlib {
+                level: level.map_or(6, |level| level.clamp(0, 9) as u32),
+            }),
+            Some(name) => Err(config::ConfigError::Message(format!(
+                "Invalid local-store.compression: {name} (expected zstd or zlib)"
+            ))),
+        }
+    }
+
+    fn encoder<W: Write>(self, writer: W) -> std::io::Result<ObjectEncoder<W>> {
+        match self {
+            LocalCompression::Zstd { level } => {
+                Ok(ObjectEncoder::Zstd(zstd::Encoder::new(writer, level)?))
+            }
+            LocalCompression::Zlib { level } => Ok(ObjectEncoder::Zlib(
+                flate2::write::ZlibEncoder::new(writer, flate2::Compression::new(level)),
+            )),
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut encoder = self.encoder(vec![])?;
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+}
+
+impl Default for LocalCompression {
+    fn default() -> Self {
+        LocalCompression::Zstd { level: 0 }
+    }
+}
+
+enum ObjectEncoder<W: Write> {
+    Zstd(zstd::Encoder<'static, W>),
+    Zlib(flate2::write::ZlibEncoder<W>),
+}
+
+impl<W: Write> ObjectEncoder<W> {
+    fn finish(self) -> std::io::Result<W> {
+        match self {
+            ObjectEncoder::Zstd(encoder) => encoder.finish(),
+            ObjectEncoder::Zlib(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for ObjectEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ObjectEncoder::Zstd(encoder) => encoder.write(buf),
+            ObjectEncoder::Zlib(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ObjectEncoder::Zstd(encoder) => encoder.flush(),
+            ObjectEncoder::Zlib(encoder) => encoder.flush(),
+        }
+    }
+}
+
+fn is_zlib_header(buf: &[u8]) -> bool {
+    // The compression method must be deflate and the header checksum must match
+    // (RFC 1950). The protobuf messages we store never start like this.
+    match buf {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && ((u16::from(*cmf) << 8) | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Decompresses a serialized (protobuf) object. Objects written before
+/// compression was supported are returned as is.
+fn decompress_object(buf: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if buf.starts_with(&ZSTD_MAGIC) {
+        zstd::decode_all(buf.as_slice())
+    } else if is_zlib_header(&buf) {
+        let mut decompressed = vec![];
+        flate2::read::ZlibDecoder::new(buf.as_slice()).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    } else {
+        Ok(buf)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ObjectKind {
+    Commit,
+    Tree,
+    File,
+    Symlink,
+    Conflict,
+    Chunk,
+}
+
+impl ObjectKind {
+    const ALL: [ObjectKind; 6] = [
+        ObjectKind::Commit,
+        ObjectKind::Tree,
+        ObjectKind::File,
+        ObjectKind::Symlink,
+        ObjectKind::Conflict,
+        ObjectKind::Chunk,
+    ];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            ObjectKind::Commit => "commits",
+            ObjectKind::Tree => "trees",
+            ObjectKind::File => "files",
+            ObjectKind::Symlink => "symlinks",
+            ObjectKind::Conflict => "conflicts",
+            ObjectKind::Chunk => "chunks",
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            ObjectKind::Commit => 0,
+            ObjectKind::Tree => 1,
+            ObjectKind::File => 2,
+            ObjectKind::Symlink => 3,
+            ObjectKind::Conflict => 4,
+            ObjectKind::Chunk => 5,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        ObjectKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_byte() == byte)
+    }
+}
+
+// Pack files live in the "packs" directory. A pack consists of "<name>.pack",
+// a sequence of entries, and "<name>.idx", which maps object ids to offsets of
+// entries in the pack. Each entry consists of a flag byte (1 if the contents
+// are zstd-compressed in the pack, otherwise 0), the length of the contents as
+// a little-endian u64, and the contents. The contents are the same bytes as
+// the corresponding loose file. The index is a sequence of records consisting
+// of the object kind byte, the id length as a little-endian u16, the id, and
+// the offset as a little-endian u64.
+//
+// The pack file is always written before its index, so the index file can be
+// used to register the pack with concurrent readers.
+const PACK_ENTRY_COMPRESSED: u8 = 1;
+
+#[derive(Clone, Debug)]
+struct PackEntry {
+    pack_path: Arc<PathBuf>,
+    offset: u64,
+}
+
+/// Where an object is stored.
+#[derive(Clone, Debug)]
+enum StoredObject {
+    Packed(PackEntry),
+    Loose(PathBuf),
+}
+
+impl StoredObject {
+    /// Reads the object in the format of a loose file.
+    fn read(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            StoredObject::Packed(entry) => read_pack_entry(&entry.pack_path, entry.offset),
+            StoredObject::Loose(path) => fs::read(path),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PackSet {
+    /// Names of the packs that have been loaded.
+    names: HashSet<String>,
+    entries: HashMap<(ObjectKind, Vec<u8>), PackEntry>,
+}
+
+fn read_pack_index(index_path: &Path) -> std::io::Result<Vec<(ObjectKind, Vec<u8>, u64)>> {
+    let invalid_data =
+        || std::io::Error::new(std::io::ErrorKind::InvalidData, "Corrupt pack index");
+    let buf = fs::read(index_path)?;
+    let mut rest = buf.as_slice();
+    let mut records = vec![];
+    while let [kind, len0, len1, tail @ ..] = rest {
+        let kind = ObjectKind::from_byte(*kind).ok_or_else(invalid_data)?;
+        let id_len = usize::from(u16::from_le_bytes([*len0, *len1]));
+        if tail.len() < id_len + 8 {
+            return Err(invalid_data());
+        }
+        let (id, tail) = tail.split_at(id_len);
+        let (offset, tail) = tail.split_at(8);
+        records.push((
+            kind,
+            id.to_vec(),
+            u64::from_le_bytes(offset.try_into().unwrap()),
+        ));
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(invalid_data());
+    }
+    Ok(records)
+}
+
+/// Reads the contents of the entry at `offset`, in the format of a loose file.
+fn read_pack_entry(pack_path: &Path, offset: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(pack_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut header = [0; 9];
+    file.read_exact(&mut header)?;
+    let len = u64::from_le_bytes(header[1..].try_into().unwrap());
+    let mut contents = vec![];
+    file.take(len).read_to_end(&mut contents)?;
+    if contents.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    if header[0] == PACK_ENTRY_COMPRESSED {
+        zstd::decode_all(contents.as_slice())
+    } else {
+        Ok(contents)
+    }
+}
+
+// With `local-store.chunked-files` enabled, files of at least
+// `CHUNKED_FILE_THRESHOLD` bytes are split into content-defined chunks, which
+// are stored like other objects in the "chunks" directory, identified by the
+// hash of their contents. The file object is then a manifest consisting of
+// `CHUNK_MANIFEST_MAGIC` followed by a record per chunk consisting of the
+// length of the chunk as a little-endian u64 and the chunk id. The file id is
+// still the hash of the whole contents. Since a small edit only changes the
+// chunks around it, the other chunks are shared between versions of the file.
+//
+// Manifests are stored uncompressed. The magic can't be mistaken for the start
+// of compressed file contents. Chunked files are read regardless of the
+// setting.
+const CHUNK_MANIFEST_MAGIC: &[u8] = b"jj-chunks\n";
+const CHUNKED_FILE_THRESHOLD: usize = 256 * 1024;
+const CHUNK_MIN_SIZE: usize = 2 * 1024;
+const CHUNK_AVG_SIZE: usize = 8 * 1024;
+const CHUNK_MAX_SIZE: usize = 64 * 1024;
+// As in FastCDC, cut points are harder to find before the average size and
+// easier after it, which narrows the distribution of chunk sizes. The masks
+// select high bits of the hash, which depend on the last 64 bytes.
+const CHUNK_MASK_SMALL: u64 = !0 << (64 - 15);
+const CHUNK_MASK_LARGE: u64 = !0 << (64 - 11);
+
+/// Random values for the gear hash, generated with SplitMix64.
+const GEAR: [u64; 256] = {
+    let mut table = [0; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Returns the length of the first chunk of `data`.
+fn chunk_len(data: &[u8]) -> usize {
+    if data.len() <= CHUNK_MIN_SIZE {
+        return data.len();
+    }
+    let end = data.len().min(CHUNK_MAX_SIZE);
+    let mut hash: u64 = 0;
+    for (i, &byte) in data.iter().enumerate().take(end).skip(CHUNK_MIN_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[usize::from(byte)]);
+        let mask = if i < CHUNK_AVG_SIZE {
+            CHUNK_MASK_SMALL
+        } else {
+            CHUNK_MASK_LARGE
+        };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Reads the contents of a chunked file one chunk at a time.
+struct ChunkedFileReader {
+    chunks: std::vec::IntoIter<(u64, FileId, StoredObject)>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl Read for ChunkedFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let Some((len, chunk_id, stored)) = self.chunks.next() else {
+                return Ok(0);
+            };
+            let chunk = decompress_object(stored.read()?)?;
+            if chunk.len() as u64 != len {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Chunk {} has unexpected length", chunk_id.hex()),
+                ));
+            }
+            self.current = Cursor::new(chunk);
+        }
+    }
+}
+
+fn parse_chunk_manifest(buf: &[u8]) -> Option<Vec<(u64, FileId)>> {
+    let mut rest = buf.strip_prefix(CHUNK_MANIFEST_MAGIC)?;
+    let mut chunks = vec![];
+    while !rest.is_empty() {
+        if rest.len() < 8 + 64 {
+            return None;
+        }
+        let (len, tail) = rest.split_at(8);
+        let (id, tail) = tail.split_at(64);
+        chunks.push((
+            u64::from_le_bytes(len.try_into().unwrap()),
+            FileId::from_bytes(id),
+        ));
+        rest = tail;
+    }
+    Some(chunks)
+}
+
 #[derive(Debug)]
 pub struct LocalBackend {
     path: PathBuf,
     root_commit_id: CommitId,
     root_change_id: ChangeId,
     empty_tree_id: TreeId,
+    compression: LocalCompression,
+    /// Whether commits, trees and conflicts are compressed too. Older versions
+    /// only read them uncompressed, so this is off unless configured.
+    compress_objects: bool,
+    chunked_files: bool,
+    verify_objects: bool,
+    packs: Mutex<PackSet>,
 }
 
 impl LocalBackend {
@@ -77,49 +451,431 @@ impl LocalBackend {
     }
 
     pub fn init(store_path: &Path) -> Self {
+        Self::create_dirs(store_path);
+        Self::load(store_path).with_empty_tree()
+    }
+
+    /// Like `init()`, but with the `local-store.*` settings applied.
+    pub fn init_with_settings(
+        settings: &UserSettings,
+        store_path: &Path,
+    ) -> Result<Self, BackendInitError> {
+        Self::create_dirs(store_path);
+        let backend = Self::load_with_settings(settings, store_path)
+            .map_err(|BackendLoadError(err)| BackendInitError(err))?;
+        Ok(backend.with_empty_tree())
+    }
+
+    fn create_dirs(store_path: &Path) {
         fs::create_dir(store_path.join("commits")).unwrap();
         fs::create_dir(store_path.join("trees")).unwrap();
         fs::create_dir(store_path.join("files")).unwrap();
         fs::create_dir(store_path.join("symlinks")).unwrap();
         fs::create_dir(store_path.join("conflicts")).unwrap();
-        let backend = Self::load(store_path);
-        let empty_tree_id = backend
-            .write_tree(RepoPath::root(), &Tree::default())
-            .unwrap();
-        assert_eq!(empty_tree_id, backend.empty_tree_id);
-        backend
+    }
+
+    fn with_empty_tree(self) -> Self {
+        let empty_tree_id = self.write_tree(RepoPath::root(), &Tree::default()).unwrap();
+        assert_eq!(empty_tree_id, self.empty_tree_id);
+        self
     }
 
     pub fn load(store_path: &Path) -> Self {
+        Self::new(store_path, LocalCompression::default(), false, false, false)
+    }
+
+    /// Like `load()`, but with the `local-store.*` settings applied.
+    pub fn load_with_settings(
+        settings: &UserSettings,
+        store_path: &Path,
+    ) -> Result<Self, BackendLoadError> {
+        let config = settings.config();
+        let compression =
+            LocalCompression::from_config(config).map_err(|err| BackendLoadError(err.into()))?;
+        let compress_objects = config
+            .get_bool("local-store.compress-objects")
+            .unwrap_or(false);
+        let chunked_files = config
+            .get_bool("local-store.chunked-files")
+            .unwrap_or(false);
+        let verify_objects = config
+            .get_bool("local-store.verify-objects")
+            .unwrap_or(false);
+        Ok(Self::new(
+            store_path,
+            compression,
+            compress_objects,
+            chunked_files,
+            verify_objects,
+        ))
+    }
+
+    fn new(
+        store_path: &Path,
+        compression: LocalCompression,
+        compress_objects: bool,
+        chunked_files: bool,
+        verify_objects: bool,
+    ) -> Self {
         let root_commit_id = CommitId::from_bytes(&[0; COMMIT_ID_LENGTH]);
         let root_change_id = ChangeId::from_bytes(&[0; CHANGE_ID_LENGTH]);
         let empty_tree_id = TreeId::from_hex("482ae5a29fbe856c7272f2071b8b0f0359ee2d89ff392b8a900643fbd0836eccd067b8bf41909e206c90d45d6e7d8b6686b93ecaee5fe1a9060d87b672101310");
-        LocalBackend {
+        let backend = LocalBackend {
             path: store_path.to_path_buf(),
             root_commit_id,
             root_change_id,
             empty_tree_id,
+            compression,
+            compress_objects,
+            chunked_files,
+            verify_objects,
+            packs: Mutex::new(PackSet::default()),
+        };
+        // Packs that can't be loaded now will be retried when an object isn't
+        // found.
+        backend.refresh_packs().ok();
+        backend
+    }
+
+    /// Writes an object to a temporary file in the store, compressed according
+    /// to the configured compression if `compress` is set.
+    fn write_object_temp_file(&self, data: &[u8], compress: bool) -> BackendResult<NamedTempFile> {
+        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        if compress {
+            let compressed = self.compression.compress(data).map_err(to_other_err)?;
+            temp_file
+                .as_file()
+                .write_all(&compressed)
+                .map_err(to_other_err)?;
+        } else {
+            temp_file.as_file().write_all(data).map_err(to_other_err)?;
+        }
+        Ok(temp_file)
+    }
+
+    /// Writes the whole file contents, compressed, to a single object.
+    fn write_whole_file(&self, contents: &mut dyn Read) -> BackendResult<FileId> {
+        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        let mut encoder = self
+            .compression
+            .encoder(temp_file.as_file())
+            .map_err(to_other_err)?;
+        let mut hasher = Blake2b512::new();
+        let mut buff: Vec<u8> = vec![0; 1 << 14];
+        loop {
+            let bytes_read = contents.read(&mut buff).map_err(to_other_err)?;
+            if bytes_read == 0 {
+                break;
+            }
+            let bytes = &buff[..bytes_read];
+            encoder.write_all(bytes).map_err(to_other_err)?;
+            hasher.update(bytes);
+        }
+        encoder.finish().map_err(to_other_err)?;
+        let id = FileId::new(hasher.finalize().to_vec());
+
+        persist_content_addressed_temp_file(temp_file, self.file_path(&id))
+            .map_err(to_other_err)?;
+        Ok(id)
+    }
+
+    /// Writes the chunks of the file contents that aren't already stored, and
+    /// a manifest listing all of them. `head` is the start of the contents,
+    /// which continue in `tail`. At most `CHUNK_MAX_SIZE` bytes are buffered
+    /// beyond `head`.
+    fn write_chunked_file(&self, head: Vec<u8>, tail: &mut dyn Read) -> BackendResult<FileId> {
+        let chunks_dir = self.path.join(ObjectKind::Chunk.dir_name());
+        fs::create_dir_all(chunks_dir).map_err(to_other_err)?;
+        let mut manifest = CHUNK_MANIFEST_MAGIC.to_vec();
+        let mut hasher = Blake2b512::new();
+        let mut buf = head;
+        let mut at_end = false;
+        loop {
+            if !at_end && buf.len() < CHUNK_MAX_SIZE {
+                let missing = CHUNK_MAX_SIZE - buf.len();
+                let bytes_read = (&mut *tail)
+                    .take(missing as u64)
+                    .read_to_end(&mut buf)
+                    .map_err(to_other_err)?;
+                at_end = bytes_read < missing;
+            }
+            if buf.is_empty() {
+                break;
+            }
+            let chunk = &buf[..chunk_len(&buf)];
+            hasher.update(chunk);
+            let chunk_id = FileId::new(Blake2b512::digest(chunk).to_vec());
+            let chunk_path = self.object_path(ObjectKind::Chunk, &chunk_id);
+            if self
+                .find_packed(ObjectKind::Chunk, chunk_id.as_bytes())
+                .is_none()
+                && !chunk_path.exists()
+            {
+                let temp_file = self.write_object_temp_file(chunk, true)?;
+                persist_content_addressed_temp_file(temp_file, chunk_path).map_err(to_other_err)?;
+            }
+            manifest.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+            manifest.extend_from_slice(chunk_id.as_bytes());
+            let chunk_len = chunk.len();
+            buf.drain(..chunk_len);
+        }
+        let id = FileId::new(hasher.finalize().to_vec());
+
+        let mut temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        temp_file.write_all(&manifest).map_err(to_other_err)?;
+        persist_content_addressed_temp_file(temp_file, self.file_path(&id))
+            .map_err(to_other_err)?;
+        Ok(id)
+    }
+
+    /// Checks that the hash of an object's contents matches the id it was read
+    /// by. Does nothing unless `local-store.verify-objects` is enabled.
+    fn verify_object(&self, id: &impl ObjectId, actual_hash: &[u8]) -> BackendResult<()> {
+        if !self.verify_objects || id.as_bytes() == actual_hash {
+            return Ok(());
+        }
+        Err(BackendError::Corrupt {
+            object_type: id.object_type(),
+            hash: id.hex(),
+            actual_hash: hex::encode(actual_hash),
+        })
+    }
+
+    /// Returns a reader of the file contents listed in a chunk manifest. The
+    /// chunks are located up front, but only read as the contents are.
+    fn read_chunked_file(&self, id: &FileId, manifest: &[u8]) -> BackendResult<ChunkedFileReader> {
+        let chunks = parse_chunk_manifest(manifest).ok_or_else(|| BackendError::ReadObject {
+            object_type: id.object_type(),
+            hash: id.hex(),
+            source: "Corrupt chunk manifest".into(),
+        })?;
+        let chunks = chunks
+            .into_iter()
+            .map(|(len, chunk_id)| {
+                let stored = self.locate_object(ObjectKind::Chunk, &chunk_id)?;
+                Ok((len, chunk_id, stored))
+            })
+            .collect::<BackendResult<Vec<_>>>()?;
+        Ok(ChunkedFileReader {
+            chunks: chunks.into_iter(),
+            current: Cursor::new(vec![]),
+        })
+    }
+
+    fn read_object(&self, kind: ObjectKind, id: &impl ObjectId) -> BackendResult<Vec<u8>> {
+        let mut buf = vec![];
+        self.open_stored_object(kind, id)?
+            .read_to_end(&mut buf)
+            .map_err(|err| map_not_found_err(err, id))?;
+        decompress_object(buf).map_err(|err| BackendError::ReadObject {
+            object_type: id.object_type(),
+            hash: id.hex(),
+            source: Box::new(err),
+        })
+    }
+
+    /// Returns a reader of the object as stored in a loose file, looking in the
+    /// packs first.
+    fn open_stored_object(
+        &self,
+        kind: ObjectKind,
+        id: &impl ObjectId,
+    ) -> BackendResult<Box<dyn BufRead>> {
+        let read_packed = |entry: PackEntry| -> BackendResult<Box<dyn BufRead>> {
+            let contents = read_pack_entry(&entry.pack_path, entry.offset).map_err(|err| {
+                BackendError::ReadObject {
+                    object_type: id.object_type(),
+                    hash: id.hex(),
+                    source: Box::new(err),
+                }
+            })?;
+            Ok(Box::new(Cursor::new(contents)))
+        };
+        if let Some(entry) = self.find_packed(kind, id.as_bytes()) {
+            return read_packed(entry);
+        }
+        match File::open(self.object_path(kind, id)) {
+            Ok(file) => Ok(Box::new(BufReader::new(file))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                // The object may have been packed by another process since we
+                // last loaded the packs, and its loose file removed.
+                self.refresh_packs().map_err(to_other_err)?;
+                match self.find_packed(kind, id.as_bytes()) {
+                    Some(entry) => read_packed(entry),
+                    None => Err(map_not_found_err(err, id)),
+                }
+            }
+            Err(err) => Err(map_not_found_err(err, id)),
+        }
+    }
+
+    /// Finds where the object is stored without reading it, looking in the
+    /// packs first.
+    fn locate_object(&self, kind: ObjectKind, id: &impl ObjectId) -> BackendResult<StoredObject> {
+        if let Some(entry) = self.find_packed(kind, id.as_bytes()) {
+            return Ok(StoredObject::Packed(entry));
+        }
+        let path = self.object_path(kind, id);
+        match fs::metadata(&path) {
+            Ok(_) => Ok(StoredObject::Loose(path)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.refresh_packs().map_err(to_other_err)?;
+                match self.find_packed(kind, id.as_bytes()) {
+                    Some(entry) => Ok(StoredObject::Packed(entry)),
+                    None => Err(map_not_found_err(err, id)),
+                }
+            }
+            Err(err) => Err(map_not_found_err(err, id)),
+        }
+    }
+
+    fn find_packed(&self, kind: ObjectKind, id: &[u8]) -> Option<PackEntry> {
+        let packs = self.packs.lock().unwrap();
+        packs.entries.get(&(kind, id.to_vec())).cloned()
+    }
+
+    /// Loads the packs that have been added since the last call.
+    fn refresh_packs(&self) -> std::io::Result<()> {
+        let dir_entries = match fs::read_dir(self.packs_dir()) {
+            Ok(dir_entries) => dir_entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut packs = self.packs.lock().unwrap();
+        for dir_entry in dir_entries {
+            let index_path = dir_entry?.path();
+            if index_path.extension().map_or(true, |ext| ext != "idx") {
+                continue;
+            }
+            let Some(name) = index_path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if packs.names.contains(name) {
+                continue;
+            }
+            let pack_path = Arc::new(index_path.with_extension("pack"));
+            for (kind, id, offset) in read_pack_index(&index_path)? {
+                let entry = PackEntry {
+                    pack_path: pack_path.clone(),
+                    offset,
+                };
+                packs.entries.insert((kind, id), entry);
+            }
+            packs.names.insert(name.to_owned());
         }
+        Ok(())
+    }
+
+    fn packs_dir(&self) -> PathBuf {
+        self.path.join("packs")
+    }
+
+    fn object_path(&self, kind: ObjectKind, id: &impl ObjectId) -> PathBuf {
+        self.path.join(kind.dir_name()).join(id.hex())
     }
 
     fn file_path(&self, id: &FileId) -> PathBuf {
-        self.path.join("files").join(id.hex())
+        self.object_path(ObjectKind::File, id)
     }
 
     fn symlink_path(&self, id: &SymlinkId) -> PathBuf {
-        self.path.join("symlinks").join(id.hex())
+        self.object_path(ObjectKind::Symlink, id)
     }
 
     fn tree_path(&self, id: &TreeId) -> PathBuf {
-        self.path.join("trees").join(id.hex())
+        self.object_path(ObjectKind::Tree, id)
     }
 
     fn commit_path(&self, id: &CommitId) -> PathBuf {
-        self.path.join("commits").join(id.hex())
+        self.object_path(ObjectKind::Commit, id)
     }
 
     fn conflict_path(&self, id: &ConflictId) -> PathBuf {
-        self.path.join("conflicts").join(id.hex())
+        self.object_path(ObjectKind::Conflict, id)
+    }
+
+    /// Writes the loose objects that aren't in any pack to a new pack.
+    fn write_pack(&self, options: &CompactOptions) -> std::io::Result<CompactStats> {
+        self.refresh_packs()?;
+        let mut stats = CompactStats::default();
+        let pack_file = NamedTempFile::new_in(&self.path)?;
+        let mut pack_writer = BufWriter::new(pack_file.as_file());
+        let mut index = vec![];
+        for kind in ObjectKind::ALL {
+            let dir_entries = match fs::read_dir(self.path.join(kind.dir_name())) {
+                Ok(dir_entries) => dir_entries,
+                // Only stores with chunked files have a "chunks" directory
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for dir_entry in dir_entries {
+                let loose_path = dir_entry?.path();
+                let Some(id) = loose_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| hex::decode(name).ok())
+                else {
+                    continue;
+                };
+                if self.find_packed(kind, &id).is_some() {
+                    stats.redundant_files.push(loose_path);
+                    continue;
+                }
+                let contents = fs::read(&loose_path)?;
+                let (flag, contents) = if options.compress {
+                    let compressed = zstd::encode_all(contents.as_slice(), 0)?;
+                    if compressed.len() < contents.len() {
+                        (PACK_ENTRY_COMPRESSED, compressed)
+                    } else {
+                        (0, contents)
+                    }
+                } else {
+                    (0, contents)
+                };
+                index.push(kind.to_byte());
+                index.extend_from_slice(&u16::try_from(id.len()).unwrap().to_le_bytes());
+                index.extend_from_slice(&id);
+                index.extend_from_slice(&stats.packed_bytes.to_le_bytes());
+                pack_writer.write_all(&[flag])?;
+                pack_writer.write_all(&(contents.len() as u64).to_le_bytes())?;
+                pack_writer.write_all(&contents)?;
+                stats.packed_objects += 1;
+                stats.packed_bytes += 9 + contents.len() as u64;
+                stats.redundant_files.push(loose_path);
+            }
+        }
+        pack_writer.flush()?;
+        drop(pack_writer);
+        if stats.packed_objects > 0 {
+            // Name the pack after its index, which identifies the contents.
+            let name = hex::encode(Blake2b512::digest(&index));
+            fs::create_dir_all(self.packs_dir())?;
+            persist_content_addressed_temp_file(
+                pack_file,
+                self.packs_dir().join(format!("{name}.pack")),
+            )?;
+            let mut index_file = NamedTempFile::new_in(&self.path)?;
+            index_file.write_all(&index)?;
+            persist_content_addressed_temp_file(
+                index_file,
+                self.packs_dir().join(format!("{name}.idx")),
+            )?;
+            self.refresh_packs()?;
+        }
+
+        // Only remove the loose files once the pack is in place, so the
+        // objects can be read at any time.
+        if options.remove_loose_files {
+            for path in mem::take(&mut stats.redundant_files) {
+                match fs::remove_file(&path) {
+                    Ok(()) => stats.removed_files += 1,
+                    // Removed by a concurrent compaction
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(stats)
     }
 }
 
@@ -158,36 +914,61 @@ impl Backend for LocalBackend {
     }
 
     async fn read_file(&self, _path: &RepoPath, id: &FileId) -> BackendResult<Box<dyn Read>> {
-        let path = self.file_path(id);
-        let file = File::open(path).map_err(|err| map_not_found_err(err, id))?;
-        Ok(Box::new(zstd::Decoder::new(file).map_err(to_other_err)?))
+        let mut reader = self.open_stored_object(ObjectKind::File, id)?;
+        // File contents have always been compressed, but not necessarily with
+        // the currently configured algorithm.
+        let header = reader
+            .fill_buf()
+            .map_err(|err| map_not_found_err(err, id))?;
+        let mut decoded: Box<dyn Read> = if header.starts_with(CHUNK_MANIFEST_MAGIC) {
+            let mut manifest = vec![];
+            reader
+                .read_to_end(&mut manifest)
+                .map_err(|err| map_not_found_err(err, id))?;
+            Box::new(self.read_chunked_file(id, &manifest)?)
+        } else if is_zlib_header(header) {
+            Box::new(flate2::bufread::ZlibDecoder::new(reader))
+        } else {
+            Box::new(zstd::Decoder::with_buffer(reader).map_err(to_other_err)?)
+        };
+        if !self.verify_objects {
+            return Ok(decoded);
+        }
+        // The whole file has to be read to check its hash before any of it is
+        // returned
+        let mut contents = vec![];
+        decoded
+            .read_to_end(&mut contents)
+            .map_err(|err| BackendError::ReadObject {
+                object_type: id.object_type(),
+                hash: id.hex(),
+                source: Box::new(err),
+            })?;
+        self.verify_object(id, &Blake2b512::digest(&contents))?;
+        Ok(Box::new(Cursor::new(contents)))
     }
 
     fn write_file(&self, _path: &RepoPath, contents: &mut dyn Read) -> BackendResult<FileId> {
-        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
-        let mut encoder = zstd::Encoder::new(temp_file.as_file(), 0).map_err(to_other_err)?;
-        let mut hasher = Blake2b512::new();
-        let mut buff: Vec<u8> = vec![0; 1 << 14];
-        loop {
-            let bytes_read = contents.read(&mut buff).map_err(to_other_err)?;
-            if bytes_read == 0 {
-                break;
-            }
-            let bytes = &buff[..bytes_read];
-            encoder.write_all(bytes).map_err(to_other_err)?;
-            hasher.update(bytes);
+        if !self.chunked_files {
+            return self.write_whole_file(contents);
         }
-        encoder.finish().map_err(to_other_err)?;
-        let id = FileId::new(hasher.finalize().to_vec());
-
-        persist_content_addressed_temp_file(temp_file, self.file_path(&id))
+        let mut head = vec![];
+        (&mut *contents)
+            .take(CHUNKED_FILE_THRESHOLD as u64)
+            .read_to_end(&mut head)
             .map_err(to_other_err)?;
-        Ok(id)
+        if head.len() < CHUNKED_FILE_THRESHOLD {
+            return self.write_whole_file(&mut head.as_slice());
+        }
+        self.write_chunked_file(head, contents)
     }
 
     async fn read_symlink(&self, _path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
-        let path = self.symlink_path(id);
-        let target = fs::read_to_string(path).map_err(|err| map_not_found_err(err, id))?;
+        let mut target = String::new();
+        self.open_stored_object(ObjectKind::Symlink, id)?
+            .read_to_string(&mut target)
+            .map_err(|err| map_not_found_err(err, id))?;
+        self.verify_object(id, &Blake2b512::digest(target.as_bytes()))?;
         Ok(target)
     }
 
@@ -206,21 +987,18 @@ impl Backend for LocalBackend {
     }
 
     async fn read_tree(&self, _path: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
-        let path = self.tree_path(id);
-        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
+        let buf = self.read_object(ObjectKind::Tree, id)?;
 
         let proto = crate::protos::local_store::Tree::decode(&*buf).map_err(to_other_err)?;
-        Ok(tree_from_proto(proto))
+        let tree = tree_from_proto(proto);
+        self.verify_object(id, &blake2b_hash(&tree))?;
+        Ok(tree)
     }
 
     fn write_tree(&self, _path: &RepoPath, tree: &Tree) -> BackendResult<TreeId> {
-        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
-
         let proto = tree_to_proto(tree);
-        temp_file
-            .as_file()
-            .write_all(&proto.encode_to_vec())
-            .map_err(to_other_err)?;
+        let temp_file =
+            self.write_object_temp_file(&proto.encode_to_vec(), self.compress_objects)?;
 
         let id = TreeId::new(blake2b_hash(tree).to_vec());
 
@@ -230,21 +1008,18 @@ impl Backend for LocalBackend {
     }
 
     fn read_conflict(&self, _path: &RepoPath, id: &ConflictId) -> BackendResult<Conflict> {
-        let path = self.conflict_path(id);
-        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
+        let buf = self.read_object(ObjectKind::Conflict, id)?;
 
         let proto = crate::protos::local_store::Conflict::decode(&*buf).map_err(to_other_err)?;
-        Ok(conflict_from_proto(proto))
+        let conflict = conflict_from_proto(proto);
+        self.verify_object(id, &blake2b_hash(&conflict))?;
+        Ok(conflict)
     }
 
     fn write_conflict(&self, _path: &RepoPath, conflict: &Conflict) -> BackendResult<ConflictId> {
-        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
-
         let proto = conflict_to_proto(conflict);
-        temp_file
-            .as_file()
-            .write_all(&proto.encode_to_vec())
-            .map_err(to_other_err)?;
+        let temp_file =
+            self.write_object_temp_file(&proto.encode_to_vec(), self.compress_objects)?;
 
         let id = ConflictId::new(blake2b_hash(conflict).to_vec());
 
@@ -261,11 +1036,12 @@ impl Backend for LocalBackend {
             ));
         }
 
-        let path = self.commit_path(id);
-        let buf = fs::read(path).map_err(|err| map_not_found_err(err, id))?;
+        let buf = self.read_object(ObjectKind::Commit, id)?;
 
         let proto = crate::protos::local_store::Commit::decode(&*buf).map_err(to_other_err)?;
-        Ok(commit_from_proto(proto))
+        let commit = commit_from_proto(proto);
+        self.verify_object(id, &blake2b_hash(&commit))?;
+        Ok(commit)
     }
 
     fn write_commit(
@@ -280,7 +1056,8 @@ impl Backend for LocalBackend {
                 "Cannot write a commit with no parents".into(),
             ));
         }
-        let temp_file = NamedTempFile::new_in(&self.path).map_err(to_other_err)?;
+        // The description is stored as UTF-8, so it reads back exactly
+        commit.description_lossy = false;
 
         let mut proto = commit_to_proto(&commit);
         if let Some(sign) = sign_with {
@@ -290,10 +1067,8 @@ impl Backend for LocalBackend {
             commit.secure_sig = Some(SecureSig { data, sig });
         }
 
-        temp_file
-            .as_file()
-            .write_all(&proto.encode_to_vec())
-            .map_err(to_other_err)?;
+        let temp_file =
+            self.write_object_temp_file(&proto.encode_to_vec(), self.compress_objects)?;
 
         let id = CommitId::new(blake2b_hash(&commit).to_vec());
 
@@ -314,6 +1089,10 @@ impl Backend for LocalBackend {
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {
         Ok(())
     }
+
+    fn compact(&self, options: &CompactOptions) -> BackendResult<CompactStats> {
+        self.write_pack(options).map_err(to_other_err)
+    }
 }
 
 #[allow(unknown_lints)] // XXX FIXME (aseipp): nightly bogons; re-test this occasionally
@@ -342,7 +1121,7 @@ pub fn commit_to_proto(commit: &Commit) -> crate::protos::local_store::Commit {
     proto
 }
 
-fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
+pub(crate) fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
     // Note how .take() sets the secure_sig field to None before we encode the data.
     // Needs to be done first since proto is partially moved a bunch below
     let secure_sig = proto.secure_sig.take().map(|sig| SecureSig {
@@ -366,13 +1145,14 @@ fn commit_from_proto(mut proto: crate::protos::local_store::Commit) -> Commit {
         root_tree,
         change_id,
         description: proto.description,
+        description_lossy: false,
         author: signature_from_proto(proto.author.unwrap_or_default()),
         committer: signature_from_proto(proto.committer.unwrap_or_default()),
         secure_sig,
     }
 }
 
-fn tree_to_proto(tree: &Tree) -> crate::protos::local_store::Tree {
+pub(crate) fn tree_to_proto(tree: &Tree) -> crate::protos::local_store::Tree {
     let mut proto = crate::protos::local_store::Tree::default();
     for entry in tree.entries() {
         proto.entries.push(crate::protos::local_store::tree::Entry {
@@ -383,7 +1163,7 @@ fn tree_to_proto(tree: &Tree) -> crate::protos::local_store::Tree {
     proto
 }
 
-fn tree_from_proto(proto: crate::protos::local_store::Tree) -> Tree {
+pub(crate) fn tree_from_proto(proto: crate::protos::local_store::Tree) -> Tree {
     let mut tree = Tree::default();
     for proto_entry in proto.entries {
         let value = tree_value_from_proto(proto_entry.value.unwrap());
@@ -468,7 +1248,7 @@ fn signature_from_proto(proto: crate::protos::local_store::commit::Signature) ->
     }
 }
 
-fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflict {
+pub(crate) fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflict {
     let mut proto = crate::protos::local_store::Conflict::default();
     for term in &conflict.removes {
         proto.removes.push(conflict_term_to_proto(term));
@@ -479,7 +1259,7 @@ fn conflict_to_proto(conflict: &Conflict) -> crate::protos::local_store::Conflic
     proto
 }
 
-fn conflict_from_proto(proto: crate::protos::local_store::Conflict) -> Conflict {
+pub(crate) fn conflict_from_proto(proto: crate::protos::local_store::Conflict) -> Conflict {
     let mut conflict = Conflict::default();
     for term in proto.removes {
         conflict.removes.push(conflict_term_from_proto(term))
@@ -506,6 +1286,7 @@ fn conflict_term_to_proto(part: &ConflictTerm) -> crate::protos::local_store::co
 mod tests {
     use assert_matches::assert_matches;
     use pollster::FutureExt;
+    use test_case::test_case;
 
     use super::*;
 
@@ -522,6 +1303,7 @@ mod tests {
             root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
             change_id: ChangeId::from_hex("abc123"),
             description: "".to_string(),
+            description_lossy: false,
             author: create_signature(),
             committer: create_signature(),
             secure_sig: None,
@@ -559,6 +1341,550 @@ mod tests {
         assert_eq!(root_merge_commit, commit);
     }
 
+    fn user_settings(toml: &str) -> UserSettings {
+        UserSettings::from_config(
+            testutils::base_config()
+                .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn compression_from_config() {
+        let compression = |toml| LocalCompression::from_config(user_settings(toml).config());
+        assert_eq!(
+            compression("").unwrap(),
+            LocalCompression::Zstd { level: 0 }
+        );
+        assert_eq!(
+            compression("local-store.compression-level = 19").unwrap(),
+            LocalCompression::Zstd { level: 19 }
+        );
+        assert_eq!(
+            compression("local-store.compression = 'zlib'").unwrap(),
+            LocalCompression::Zlib { level: 6 }
+        );
+        assert_eq!(
+            compression("local-store.compression = 'zlib'\nlocal-store.compression-level = 12")
+                .unwrap(),
+            LocalCompression::Zlib { level: 9 }
+        );
+        assert_matches!(
+            compression("local-store.compression = 'lz4'"),
+            Err(config::ConfigError::Message(_))
+        );
+    }
+
+    #[test_case("local-store.compression = 'zstd'" ; "zstd")]
+    #[test_case("local-store.compression = 'zlib'" ; "zlib")]
+    fn write_compressed_objects(toml: &str) {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let toml = format!("{toml}\nlocal-store.compress-objects = true");
+        let backend = LocalBackend::init_with_settings(&user_settings(&toml), store_path).unwrap();
+
+        // Commits are stored compressed, but their ids don't depend on it
+        let commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "compressible\n".repeat(1000),
+            description_lossy: false,
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+        };
+        let (commit_id, _) = backend.write_commit(commit.clone(), None).unwrap();
+        assert_eq!(commit_id, CommitId::new(blake2b_hash(&commit).to_vec()));
+        let on_disk_size = fs::metadata(backend.commit_path(&commit_id)).unwrap().len();
+        assert!(on_disk_size < commit_to_proto(&commit).encoded_len() as u64);
+        assert_eq!(backend.read_commit(&commit_id).block_on().unwrap(), commit);
+
+        // Same for file contents
+        let contents = "compressible\n".repeat(1000).into_bytes();
+        let file_id = backend
+            .write_file(RepoPath::root(), &mut contents.as_slice())
+            .unwrap();
+        let on_disk_size = fs::metadata(backend.file_path(&file_id)).unwrap().len();
+        assert!(on_disk_size < contents.len() as u64);
+        let mut read_contents = vec![];
+        backend
+            .read_file(RepoPath::root(), &file_id)
+            .block_on()
+            .unwrap()
+            .read_to_end(&mut read_contents)
+            .unwrap();
+        assert_eq!(read_contents, contents);
+
+        // Objects written with another compression can still be read
+        let other_toml = if toml.contains("zlib") {
+            "local-store.compression = 'zstd'"
+        } else {
+            "local-store.compression = 'zlib'"
+        };
+        let other_backend =
+            LocalBackend::load_with_settings(&user_settings(other_toml), store_path).unwrap();
+        assert_eq!(
+            other_backend.read_commit(&commit_id).block_on().unwrap(),
+            commit
+        );
+    }
+
+    #[test]
+    fn read_uncompressed_objects() {
+        // Objects written before compression was supported are still readable
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init(store_path);
+        let commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "uncompressed\n".to_string(),
+            description_lossy: false,
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+        };
+        let commit_id = CommitId::new(blake2b_hash(&commit).to_vec());
+        fs::write(
+            backend.commit_path(&commit_id),
+            commit_to_proto(&commit).encode_to_vec(),
+        )
+        .unwrap();
+        assert_eq!(backend.read_commit(&commit_id).block_on().unwrap(), commit);
+    }
+
+    #[test]
+    fn write_uncompressed_objects_by_default() {
+        // Commits and trees are written as older versions read them unless
+        // configured otherwise
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init_with_settings(&user_settings(""), store_path).unwrap();
+        let commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "compressible\n".repeat(1000),
+            description_lossy: false,
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+        };
+        let (commit_id, _) = backend.write_commit(commit.clone(), None).unwrap();
+        assert_eq!(
+            fs::read(backend.commit_path(&commit_id)).unwrap(),
+            commit_to_proto(&commit).encode_to_vec()
+        );
+        assert_eq!(
+            fs::read(backend.tree_path(backend.empty_tree_id())).unwrap(),
+            tree_to_proto(&Tree::default()).encode_to_vec()
+        );
+    }
+
+    #[test]
+    fn verify_objects() {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init(store_path);
+        let mut commit = Commit {
+            parents: vec![backend.root_commit_id().clone()],
+            predecessors: vec![],
+            root_tree: MergedTreeId::resolved(backend.empty_tree_id().clone()),
+            change_id: ChangeId::from_hex("abc123"),
+            description: "original\n".to_string(),
+            description_lossy: false,
+            author: create_signature(),
+            committer: create_signature(),
+            secure_sig: None,
+        };
+        let (commit_id, _) = backend.write_commit(commit.clone(), None).unwrap();
+        let file_id = backend
+            .write_file(RepoPath::root(), &mut b"original\n".as_slice())
+            .unwrap();
+
+        // Corrupt the objects by replacing them with other valid objects
+        commit.description = "corrupt\n".to_string();
+        let (other_commit_id, _) = backend.write_commit(commit.clone(), None).unwrap();
+        fs::copy(
+            backend.commit_path(&other_commit_id),
+            backend.commit_path(&commit_id),
+        )
+        .unwrap();
+        let other_file_id = backend
+            .write_file(RepoPath::root(), &mut b"corrupt\n".as_slice())
+            .unwrap();
+        fs::copy(
+            backend.file_path(&other_file_id),
+            backend.file_path(&file_id),
+        )
+        .unwrap();
+
+        // Without verification, the wrong contents are returned
+        assert_eq!(backend.read_commit(&commit_id).block_on().unwrap(), commit);
+        let mut contents = vec![];
+        backend
+            .read_file(RepoPath::root(), &file_id)
+            .block_on()
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"corrupt\n");
+
+        // With verification, the reads fail
+        let backend = LocalBackend::load_with_settings(
+            &user_settings("local-store.verify-objects = true"),
+            store_path,
+        )
+        .unwrap();
+        assert_matches!(
+            backend.read_commit(&commit_id).block_on(),
+            Err(BackendError::Corrupt { hash, actual_hash, .. })
+                if hash == commit_id.hex() && actual_hash == other_commit_id.hex()
+        );
+        assert_matches!(
+            backend.read_file(RepoPath::root(), &file_id).block_on().err(),
+            Some(BackendError::Corrupt { hash, actual_hash, .. })
+                if hash == file_id.hex() && actual_hash == other_file_id.hex()
+        );
+        // Intact objects can still be read
+        assert_eq!(
+            backend.read_commit(&other_commit_id).block_on().unwrap(),
+            commit
+        );
+    }
+
+    #[test_case(false; "uncompressed packs")]
+    #[test_case(true; "compressed packs")]
+    fn compact_into_packs(compress: bool) {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init(store_path);
+        let read_file = |backend: &LocalBackend, id: &FileId| {
+            let mut contents = vec![];
+            backend
+                .read_file(RepoPath::root(), id)
+                .block_on()
+                .unwrap()
+                .read_to_end(&mut contents)
+                .unwrap();
+            contents
+        };
+
+        let mut file_ids = vec![];
+        let mut tree_ids = vec![];
+        let mut commit_ids = vec![];
+        for i in 0..3000 {
+            let contents = format!("contents {i}\n").repeat(i % 10 + 1);
+            let file_id = backend
+                .write_file(RepoPath::root(), &mut contents.as_bytes())
+                .unwrap();
+            file_ids.push((file_id.clone(), contents.into_bytes()));
+            if i % 10 == 0 {
+                let mut tree = Tree::default();
+                tree.set(
+                    RepoPathComponentBuf::from(format!("file{i}")),
+                    TreeValue::File {
+                        id: file_id,
+                        executable: false,
+                    },
+                );
+                let tree_id = backend.write_tree(RepoPath::root(), &tree).unwrap();
+                tree_ids.push((tree_id.clone(), tree));
+                let commit = Commit {
+                    parents: vec![backend.root_commit_id().clone()],
+                    predecessors: vec![],
+                    root_tree: MergedTreeId::resolved(tree_id),
+                    change_id: ChangeId::from_hex("abc123"),
+                    description: format!("commit {i}"),
+                    description_lossy: false,
+                    author: create_signature(),
+                    committer: create_signature(),
+                    secure_sig: None,
+                };
+                let (commit_id, commit) = backend.write_commit(commit, None).unwrap();
+                commit_ids.push((commit_id, commit));
+            }
+        }
+        // A symlink target that looks like a zlib header
+        let symlink_id = backend.write_symlink(RepoPath::root(), "x^target").unwrap();
+
+        let options = CompactOptions {
+            compress,
+            ..Default::default()
+        };
+        let stats = backend.compact(&options).unwrap();
+        // The files, trees, and commits we wrote, plus the empty tree and the symlink
+        let num_objects = file_ids.len() + tree_ids.len() + commit_ids.len() + 2;
+        assert_eq!(stats.packed_objects, num_objects);
+        assert_eq!(stats.redundant_files.len(), num_objects);
+        for path in &stats.redundant_files {
+            fs::remove_file(path).unwrap();
+        }
+
+        // Objects can be read from the packs, both by the backend that wrote them
+        // and by one that loaded the packs from scratch
+        let loaded_backend = LocalBackend::load(store_path);
+        for backend in [&backend, &loaded_backend] {
+            for (id, contents) in &file_ids {
+                assert_eq!(&read_file(backend, id), contents);
+            }
+            for (id, tree) in &tree_ids {
+                assert_eq!(
+                    &backend.read_tree(RepoPath::root(), id).block_on().unwrap(),
+                    tree
+                );
+            }
+            for (id, commit) in &commit_ids {
+                assert_eq!(&backend.read_commit(id).block_on().unwrap(), commit);
+            }
+            assert_eq!(
+                backend
+                    .read_symlink(RepoPath::root(), &symlink_id)
+                    .block_on()
+                    .unwrap(),
+                "x^target"
+            );
+        }
+
+        // New objects are written as loose files, and only they get packed next
+        let contents = b"new contents\n".to_vec();
+        let file_id = backend
+            .write_file(RepoPath::root(), &mut contents.as_slice())
+            .unwrap();
+        assert!(backend.file_path(&file_id).exists());
+        assert_eq!(read_file(&loaded_backend, &file_id), contents);
+        let stats = loaded_backend.compact(&options).unwrap();
+        assert_eq!(stats.packed_objects, 1);
+        assert_eq!(stats.redundant_files, vec![backend.file_path(&file_id)]);
+        fs::remove_file(backend.file_path(&file_id)).unwrap();
+        // The other backend finds the new pack when the object isn't found
+        assert_eq!(read_file(&backend, &file_id), contents);
+        assert_eq!(read_file(&backend, &file_ids[0].0), file_ids[0].1);
+
+        // Nothing left to pack
+        assert_eq!(backend.compact(&options).unwrap(), CompactStats::default());
+    }
+
+    #[test]
+    fn compact_removing_loose_files() {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init(store_path);
+        let count_loose_files = || {
+            ObjectKind::ALL
+                .iter()
+                .filter_map(|kind| fs::read_dir(store_path.join(kind.dir_name())).ok())
+                .map(|dir_entries| dir_entries.count())
+                .sum::<usize>()
+        };
+
+        let mut tree_ids = vec![];
+        let mut commit_ids = vec![];
+        for i in 0..500 {
+            let mut tree = Tree::default();
+            let file_id = backend
+                .write_file(RepoPath::root(), &mut format!("contents {i}\n").as_bytes())
+                .unwrap();
+            tree.set(
+                RepoPathComponentBuf::from("file"),
+                TreeValue::File {
+                    id: file_id,
+                    executable: false,
+                },
+            );
+            let tree_id = backend.write_tree(RepoPath::root(), &tree).unwrap();
+            tree_ids.push((tree_id.clone(), tree));
+            let commit = Commit {
+                parents: vec![backend.root_commit_id().clone()],
+                predecessors: vec![],
+                root_tree: MergedTreeId::resolved(tree_id),
+                change_id: ChangeId::from_hex("abc123"),
+                description: format!("commit {i}"),
+                description_lossy: false,
+                author: create_signature(),
+                committer: create_signature(),
+                secure_sig: None,
+            };
+            commit_ids.push(backend.write_commit(commit, None).unwrap());
+        }
+        // The files, trees, and commits we wrote, plus the empty tree
+        assert_eq!(count_loose_files(), 1501);
+
+        let options = CompactOptions {
+            remove_loose_files: true,
+            ..Default::default()
+        };
+        let stats = backend.compact(&options).unwrap();
+        assert_eq!(stats.packed_objects, 1501);
+        assert_eq!(stats.removed_files, 1501);
+        assert!(stats.redundant_files.is_empty());
+        assert_eq!(count_loose_files(), 0);
+
+        let loaded_backend = LocalBackend::load(store_path);
+        for backend in [&backend, &loaded_backend] {
+            for (id, commit) in &commit_ids {
+                assert_eq!(&backend.read_commit(id).block_on().unwrap(), commit);
+            }
+            for (id, tree) in &tree_ids {
+                assert_eq!(
+                    &backend.read_tree(RepoPath::root(), id).block_on().unwrap(),
+                    tree
+                );
+            }
+        }
+
+        // New objects are loose until the next compaction
+        let commit = Commit {
+            description: "new commit".to_owned(),
+            ..commit_ids[0].1.clone()
+        };
+        let (commit_id, commit) = backend.write_commit(commit, None).unwrap();
+        assert_eq!(count_loose_files(), 1);
+        let stats = loaded_backend.compact(&options).unwrap();
+        assert_eq!(stats.removed_files, 1);
+        assert_eq!(count_loose_files(), 0);
+        assert_eq!(backend.read_commit(&commit_id).block_on().unwrap(), commit);
+    }
+
+    #[test]
+    fn write_chunked_files() {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path();
+        let backend = LocalBackend::init_with_settings(
+            &user_settings("local-store.chunked-files = true"),
+            store_path,
+        )
+        .unwrap();
+        let read_file = |backend: &LocalBackend, id: &FileId| {
+            let mut contents = vec![];
+            backend
+                .read_file(RepoPath::root(), id)
+                .block_on()
+                .unwrap()
+                .read_to_end(&mut contents)
+                .unwrap();
+            contents
+        };
+        let chunk_sizes = || -> HashMap<PathBuf, u64> {
+            fs::read_dir(store_path.join("chunks"))
+                .unwrap()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    (entry.path(), entry.metadata().unwrap().len())
+                })
+                .collect()
+        };
+
+        // Incompressible contents, so the stored size of the chunks is about
+        // the size of their contents
+        let mut state: u64 = 1;
+        let contents1: Vec<u8> = (0..10 * 1024 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut contents2 = contents1.clone();
+        for byte in &mut contents2[5_000_000..5_000_100] {
+            *byte = !*byte;
+        }
+
+        let file_id1 = backend
+            .write_file(RepoPath::root(), &mut contents1.as_slice())
+            .unwrap();
+        let chunk_sizes1 = chunk_sizes();
+        assert!(chunk_sizes1.values().sum::<u64>() >= contents1.len() as u64);
+        let file_id2 = backend
+            .write_file(RepoPath::root(), &mut contents2.as_slice())
+            .unwrap();
+        let chunk_sizes2 = chunk_sizes();
+        let new_chunk_bytes: u64 = chunk_sizes2
+            .iter()
+            .filter(|(path, _)| !chunk_sizes1.contains_key(*path))
+            .map(|(_, size)| size)
+            .sum();
+        assert!(new_chunk_bytes > 0);
+        assert!(new_chunk_bytes < 4 * CHUNK_MAX_SIZE as u64);
+        let manifest_size = fs::metadata(backend.file_path(&file_id2)).unwrap().len();
+        assert!(manifest_size < 1024 * 1024);
+
+        // The file ids are the hashes of the whole contents, same as without
+        // chunking
+        let unchunked_dir = testutils::new_temp_dir();
+        let unchunked_backend = LocalBackend::init(unchunked_dir.path());
+        assert_eq!(
+            unchunked_backend
+                .write_file(RepoPath::root(), &mut contents1.as_slice())
+                .unwrap(),
+            file_id1
+        );
+        assert_eq!(read_file(&backend, &file_id1), contents1);
+        assert_eq!(read_file(&backend, &file_id2), contents2);
+
+        // Chunking is enabled by the setting, not by the "chunks" directory
+        let mut contents3 = contents1.clone();
+        contents3.truncate(1024 * 1024);
+        let num_chunks = chunk_sizes2.len();
+        let file_id3 = LocalBackend::load(store_path)
+            .write_file(RepoPath::root(), &mut contents3.as_slice())
+            .unwrap();
+        assert_eq!(chunk_sizes().len(), num_chunks);
+        assert_eq!(read_file(&backend, &file_id3), contents3);
+        let rechunked_backend = LocalBackend::load_with_settings(
+            &user_settings("local-store.chunked-files = true"),
+            unchunked_dir.path(),
+        )
+        .unwrap();
+        rechunked_backend
+            .write_file(RepoPath::root(), &mut contents3.as_slice())
+            .unwrap();
+        assert!(unchunked_dir.path().join("chunks").is_dir());
+
+        // Small files aren't chunked
+        let small_contents = b"small\n".to_vec();
+        let small_file_id = backend
+            .write_file(RepoPath::root(), &mut small_contents.as_slice())
+            .unwrap();
+        assert_eq!(chunk_sizes().len(), chunk_sizes2.len());
+        assert_eq!(read_file(&backend, &small_file_id), small_contents);
+
+        // Chunks can be read from packs too
+        let stats = backend
+            .compact(&CompactOptions {
+                compress: true,
+                ..Default::default()
+            })
+            .unwrap();
+        for path in &stats.redundant_files {
+            fs::remove_file(path).unwrap();
+        }
+        assert!(chunk_sizes().is_empty());
+        let loaded_backend = LocalBackend::load(store_path);
+        assert_eq!(read_file(&loaded_backend, &file_id1), contents1);
+        assert_eq!(read_file(&loaded_backend, &file_id2), contents2);
+
+        // Chunks are only read as the contents are
+        let mut reader = loaded_backend
+            .read_file(RepoPath::root(), &file_id1)
+            .block_on()
+            .unwrap();
+        let mut start = vec![0; 1000];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(start, contents1[..1000]);
+        for entry in fs::read_dir(store_path.join("packs")).unwrap() {
+            fs::remove_file(entry.unwrap().path()).unwrap();
+        }
+        assert!(reader.read_to_end(&mut vec![]).is_err());
+    }
+
     fn create_signature() -> Signature {
         Signature {
             name: "Someone".to_string(),
@@ -29,7 +29,7 @@ use std::sync::Arc;
 use std::time::UNIX_EPOCH;
 use std::{fs, iter, mem, slice};
 
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use itertools::{EitherOrBoth, Itertools};
 use once_cell::unsync::OnceCell;
 use pollster::FutureExt;
@@ -65,7 +65,8 @@ use crate::store::Store;
 use crate::tree::Tree;
 use crate::working_copy::{
     CheckoutError, CheckoutStats, LockedWorkingCopy, ResetError, SnapshotError, SnapshotOptions,
-    SnapshotProgress, WorkingCopy, WorkingCopyFactory, WorkingCopyStateError,
+    SnapshotProgress, UntrackedEntry, UntrackedReason, WorkingCopy, WorkingCopyFactory,
+    WorkingCopyStateError,
 };
 
 #[cfg(unix)]
@@ -75,9 +76,14 @@ type FileExecutableFlag = ();
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum FileType {
-    Normal { executable: FileExecutableFlag },
+    Normal {
+        executable: FileExecutableFlag,
+    },
     Symlink,
     GitSubmodule,
+    /// A tracked path that can't be represented on this platform, so it was
+    /// never written to disk. Its value in the tree is kept as is.
+    Unmaterialized,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -136,6 +142,23 @@ impl FileState {
             size: 0,
         }
     }
+
+    fn for_unmaterialized() -> Self {
+        FileState {
+            file_type: FileType::Unmaterialized,
+            mtime: MillisSinceEpoch(0),
+            size: 0,
+        }
+    }
+
+    /// Whether the snapshot should keep the tree value as is instead of
+    /// looking at the disk.
+    fn is_untouchable(&self) -> bool {
+        matches!(
+            self.file_type,
+            FileType::GitSubmodule | FileType::Unmaterialized
+        )
+    }
 }
 
 /// Owned map of path to file states, backed by proto data.
@@ -288,6 +311,94 @@ impl<'a> IntoIterator for FileStates<'a> {
     }
 }
 
+/// Tracked files whose metadata on disk doesn't match the recorded state, as
+/// found by [`TreeState::stat_changes()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatChanges {
+    /// Number of files that were modified, deleted, or replaced.
+    pub changed: usize,
+    /// Number of files whose metadata matches, but was recorded too shortly
+    /// before the last snapshot to rule out a later modification.
+    pub ambiguous: usize,
+}
+
+/// Rules about which paths can be written to the filesystem. Paths violating
+/// them are skipped on checkout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathRules {
+    /// Reject names that Windows reserves or can't store: device names like
+    /// `con` or `aux.c`, names ending in a dot or space, and names containing
+    /// characters such as `:` or `?`.
+    pub windows_names: bool,
+    /// Maximum length in bytes of a full path on disk, including the working
+    /// copy root.
+    pub max_path_len: usize,
+    /// Maximum length in bytes of a single path component.
+    pub max_component_len: usize,
+}
+
+impl PathRules {
+    /// The rules of Windows without long path support.
+    pub fn windows() -> Self {
+        PathRules {
+            windows_names: true,
+            max_path_len: 259, // MAX_PATH minus the terminating NUL
+            max_component_len: 255,
+        }
+    }
+
+    /// The rules of typical Unix filesystems.
+    pub fn unix() -> Self {
+        PathRules {
+            windows_names: false,
+            max_path_len: 4095, // PATH_MAX minus the terminating NUL
+            max_component_len: 255,
+        }
+    }
+
+    pub fn for_current_platform() -> Self {
+        if cfg!(windows) {
+            PathRules::windows()
+        } else {
+            PathRules::unix()
+        }
+    }
+
+    /// Returns true if `path` can be written to `disk_path`.
+    pub fn allows(&self, path: &RepoPath, disk_path: &Path) -> bool {
+        if disk_path.as_os_str().len() > self.max_path_len {
+            return false;
+        }
+        path.components().all(|component| {
+            let name = component.as_str();
+            name.len() <= self.max_component_len
+                && !(self.windows_names && is_reserved_windows_name(name))
+        })
+    }
+}
+
+fn is_reserved_windows_name(name: &str) -> bool {
+    const DEVICE_NAMES: &[&str] = &[
+        "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+        "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+    ];
+    if name.ends_with(['.', ' ']) {
+        return true;
+    }
+    if name
+        .chars()
+        .any(|c| c < ' ' || matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*'))
+    {
+        return true;
+    }
+    // Device names are reserved with any extension, and trailing spaces
+    // before the extension are ignored.
+    let stem = name.split('.').next().unwrap().trim_end_matches(' ');
+    DEVICE_NAMES
+        .iter()
+        .any(|device| stem.eq_ignore_ascii_case(device))
+}
+
 pub struct TreeState {
     store: Arc<Store>,
     working_copy_path: PathBuf,
@@ -298,6 +409,9 @@ pub struct TreeState {
     sparse_patterns: Vec<RepoPathBuf>,
     own_mtime: MillisSinceEpoch,
     symlink_support: bool,
+    path_rules: PathRules,
+    /// Fail the checkout instead of skipping paths that violate `path_rules`.
+    strict_path_rules: bool,
 
     /// The most recent clock value returned by Watchman. Will only be set if
     /// the repo is configured to use the Watchman filesystem monitor and
@@ -320,6 +434,7 @@ fn file_state_from_proto(proto: &crate::protos::working_copy::FileState) -> File
             executable: FileExecutableFlag::default(),
         },
         crate::protos::working_copy::FileType::GitSubmodule => FileType::GitSubmodule,
+        crate::protos::working_copy::FileType::Unmaterialized => FileType::Unmaterialized,
     };
     FileState {
         file_type,
@@ -339,6 +454,7 @@ fn file_state_to_proto(file_state: &FileState) -> crate::protos::working_copy::F
         FileType::Normal { executable: () } => crate::protos::working_copy::FileType::Normal,
         FileType::Symlink => crate::protos::working_copy::FileType::Symlink,
         FileType::GitSubmodule => crate::protos::working_copy::FileType::GitSubmodule,
+        FileType::Unmaterialized => crate::protos::working_copy::FileType::Unmaterialized,
     };
     proto.file_type = file_type as i32;
     proto.mtime_millis_since_epoch = file_state.mtime.0;
@@ -527,10 +643,46 @@ impl TreeState {
         &self.sparse_patterns
     }
 
+    /// Compares the recorded states of the tracked files with their metadata
+    /// on disk, without reading any file contents. Files added since the last
+    /// snapshot aren't tracked yet, so they aren't counted.
+    pub fn stat_changes(&self) -> StatChanges {
+        let mut changes = StatChanges::default();
+        for (path, recorded_state) in self.file_states.all() {
+            if recorded_state.is_untouchable() {
+                continue;
+            }
+            let disk_path = path.to_fs_path(&self.working_copy_path);
+            let current_state = match disk_path.symlink_metadata() {
+                Ok(metadata) => file_state(&metadata),
+                Err(_) => None,
+            };
+            if current_state.as_ref() != Some(&recorded_state) {
+                changes.changed += 1;
+            } else if recorded_state.mtime >= self.own_mtime {
+                // The file may have been modified again within the mtime
+                // granularity after its state was recorded.
+                changes.ambiguous += 1;
+            }
+        }
+        changes
+    }
+
     fn sparse_matcher(&self) -> Box<dyn Matcher> {
         Box::new(PrefixMatcher::new(&self.sparse_patterns))
     }
 
+    /// Overrides the rules for the current platform. Mostly useful for tests.
+    pub fn set_path_rules(&mut self, path_rules: PathRules) {
+        self.path_rules = path_rules;
+    }
+
+    /// If set, checking out a tree with paths that violate the path rules
+    /// fails instead of skipping them.
+    pub fn set_strict_path_rules(&mut self, strict: bool) {
+        self.strict_path_rules = strict;
+    }
+
     pub fn init(
         store: Arc<Store>,
         working_copy_path: PathBuf,
@@ -554,6 +706,8 @@ impl TreeState {
             sparse_patterns: vec![RepoPathBuf::root()],
             own_mtime: MillisSinceEpoch(0),
             symlink_support: check_symlink_support().unwrap_or(false),
+            path_rules: PathRules::for_current_platform(),
+            strict_path_rules: false,
             watchman_clock: None,
         }
     }
@@ -819,7 +973,7 @@ impl TreeState {
                 file_states
                     .iter()
                     .filter(|(path, state)| {
-                        fsmonitor_matcher.matches(path) && state.file_type != FileType::GitSubmodule
+                        fsmonitor_matcher.matches(path) && !state.is_untouchable()
                     })
                     .map(|(path, _state)| path.to_owned())
                     .collect()
@@ -922,7 +1076,7 @@ impl TreeState {
                 let path = dir.join(RepoPathComponent::new(name));
                 let maybe_current_file_state = file_states.get(&path);
                 if let Some(file_state) = &maybe_current_file_state {
-                    if file_state.file_type == FileType::GitSubmodule {
+                    if file_state.is_untouchable() {
                         return Ok(());
                     }
                 }
@@ -1038,6 +1192,138 @@ impl TreeState {
         Ok(())
     }
 
+    /// Lists the files in the working copy that are not tracked, along with the
+    /// reason why. This applies the same rules as `snapshot()`, but nothing is
+    /// written to the store and the tree state is left untouched. The entries
+    /// are sorted by path.
+    #[instrument(skip_all)]
+    pub fn untracked(
+        &self,
+        options: SnapshotOptions,
+    ) -> Result<Vec<UntrackedEntry>, SnapshotError> {
+        let SnapshotOptions {
+            base_ignores,
+            max_new_file_size,
+            ..
+        } = options;
+        let sparse_matcher = self.sparse_matcher();
+        let directory_to_visit = DirectoryToVisit {
+            dir: RepoPathBuf::root(),
+            disk_dir: self.working_copy_path.clone(),
+            git_ignore: base_ignores,
+            file_states: self.file_states.all(),
+        };
+        let mut entries = vec![];
+        self.visit_untracked_directory(
+            sparse_matcher.as_ref(),
+            directory_to_visit,
+            max_new_file_size,
+            &mut entries,
+        )?;
+        entries.sort_unstable_by(|entry1, entry2| entry1.path.cmp(&entry2.path));
+        Ok(entries)
+    }
+
+    fn visit_untracked_directory(
+        &self,
+        sparse_matcher: &dyn Matcher,
+        directory_to_visit: DirectoryToVisit,
+        max_new_file_size: u64,
+        entries: &mut Vec<UntrackedEntry>,
+    ) -> Result<(), SnapshotError> {
+        let DirectoryToVisit {
+            dir,
+            disk_dir,
+            git_ignore,
+            file_states,
+        } = directory_to_visit;
+
+        let git_ignore = git_ignore
+            .chain_with_file(&dir.to_internal_dir_string(), disk_dir.join(".gitignore"))?;
+        let read_dir_error = |err: std::io::Error| SnapshotError::Other {
+            message: format!("Failed to read directory {}", disk_dir.display()),
+            err: err.into(),
+        };
+        for entry in disk_dir.read_dir().map_err(read_dir_error)? {
+            let entry = entry.map_err(read_dir_error)?;
+            let file_type = entry.file_type().map_err(|err| SnapshotError::Other {
+                message: format!("Failed to stat file {}", entry.path().display()),
+                err: err.into(),
+            })?;
+            let file_name = entry.file_name();
+            let name = file_name
+                .to_str()
+                .ok_or_else(|| SnapshotError::InvalidUtf8Path {
+                    path: file_name.clone(),
+                })?;
+            if name == ".jj" || name == ".git" {
+                continue;
+            }
+            let path = dir.join(RepoPathComponent::new(name));
+            let maybe_current_file_state = file_states.get(&path);
+            if let Some(file_state) = &maybe_current_file_state {
+                if file_state.is_untouchable() {
+                    continue;
+                }
+            }
+
+            if file_type.is_dir() {
+                // Paths outside the sparse patterns are reported as such even if
+                // they're also ignored, since snapshot() never gets to check the
+                // ignores for them.
+                if !sparse_matcher.visit(&path).is_nothing() {
+                    if let Some(rule) = git_ignore.matched_rule(&path.to_internal_dir_string()) {
+                        // Untracked files in an ignored directory are all ignored, so
+                        // report the directory itself instead of walking it.
+                        entries.push(UntrackedEntry {
+                            path,
+                            reason: UntrackedReason::Ignored {
+                                source_file: rule.source_file,
+                                line: rule.line,
+                            },
+                        });
+                        continue;
+                    }
+                }
+                let directory_to_visit = DirectoryToVisit {
+                    file_states: file_states.prefixed(&path),
+                    dir: path,
+                    disk_dir: entry.path(),
+                    git_ignore: git_ignore.clone(),
+                };
+                self.visit_untracked_directory(
+                    sparse_matcher,
+                    directory_to_visit,
+                    max_new_file_size,
+                    entries,
+                )?;
+            } else if maybe_current_file_state.is_none() {
+                let metadata = entry.metadata().map_err(|err| SnapshotError::Other {
+                    message: format!("Failed to stat file {}", entry.path().display()),
+                    err: err.into(),
+                })?;
+                if file_state(&metadata).is_none() {
+                    // Special files (sockets, fifos, ...) are never snapshotted.
+                    continue;
+                }
+                let reason = if !sparse_matcher.matches(&path) {
+                    UntrackedReason::SparseExcluded
+                } else if let Some(rule) = git_ignore.matched_rule(path.as_internal_file_string()) {
+                    UntrackedReason::Ignored {
+                        source_file: rule.source_file,
+                        line: rule.line,
+                    }
+                } else if metadata.len() > max_new_file_size {
+                    UntrackedReason::TooLarge
+                } else {
+                    UntrackedReason::NotIgnored
+                };
+                entries.push(UntrackedEntry { path, reason });
+            }
+        }
+        Ok(())
+    }
+
     #[instrument(skip_all)]
     fn make_fsmonitor_matcher(
         &self,
@@ -1129,6 +1415,7 @@ impl TreeState {
                     Merge::normal(TreeValue::Symlink(id))
                 }
                 FileType::GitSubmodule => panic!("git submodule cannot be written to store"),
+                FileType::Unmaterialized => panic!("unmaterialized file cannot be on disk"),
             };
             if new_tree_values != current_tree_values {
                 Ok(Some(new_tree_values))
@@ -1331,6 +1618,7 @@ impl TreeState {
             added_files: added_stats.added_files,
             removed_files: removed_stats.removed_files,
             skipped_files: added_stats.skipped_files,
+            skipped_paths: added_stats.skipped_paths,
         })
     }
 
@@ -1340,6 +1628,29 @@ impl TreeState {
         new_tree: &MergedTree,
         matcher: &dyn Matcher,
     ) -> Result<CheckoutStats, CheckoutError> {
+        // Find the paths we can't write before touching anything, so we don't
+        // fail halfway through. Only the tree values are collected here, the
+        // contents are read below.
+        let mut diff_entries = vec![];
+        let mut unmaterializable_paths = HashSet::new();
+        let mut tree_diff_stream = old_tree.diff_stream(new_tree, matcher);
+        while let Some((path, diff)) = tree_diff_stream.next().await {
+            let (before, after) = diff?;
+            if after.is_present()
+                && !self
+                    .path_rules
+                    .allows(&path, &path.to_fs_path(&self.working_copy_path))
+            {
+                unmaterializable_paths.insert(path.clone());
+            }
+            diff_entries.push((path, before, after));
+        }
+        drop(tree_diff_stream);
+        if self.strict_path_rules && !unmaterializable_paths.is_empty() {
+            return Err(CheckoutError::UnmaterializablePaths {
+                paths: unmaterializable_paths.into_iter().sorted().collect(),
+            });
+        }
         // TODO: maybe it's better not include the skipped counts in the "intended"
         // counts
         let mut stats = CheckoutStats {
@@ -1347,20 +1658,16 @@ impl TreeState {
             added_files: 0,
             removed_files: 0,
             skipped_files: 0,
+            skipped_paths: vec![],
         };
         let mut changed_file_states = Vec::new();
         let mut deleted_files = HashSet::new();
+        let store = &self.store;
         let mut diff_stream = Box::pin(
-            old_tree
-                .diff_stream(new_tree, matcher)
-                .map(|(path, diff)| async {
-                    match diff {
-                        Ok((before, after)) => {
-                            let result = materialize_tree_value(&self.store, &path, after).await;
-                            (path, result.map(|value| (before.is_present(), value)))
-                        }
-                        Err(err) => (path, Err(err)),
-                    }
+            stream::iter(diff_entries)
+                .map(|(path, before, after)| async move {
+                    let result = materialize_tree_value(store, &path, after).await;
+                    (path, result.map(|value| (before.is_present(), value)))
                 })
                 .buffered(self.store.concurrency().max(1)),
         );
@@ -1373,13 +1680,40 @@ impl TreeState {
             } else {
                 stats.updated_files += 1;
             }
+            if unmaterializable_paths.contains(&path) {
+                // We can't write at this path, and whatever may be on disk
+                // there isn't ours. Keep the tree value for the next snapshot.
+                stats.skipped_paths.push(path.clone());
+                changed_file_states.push((path, FileState::for_unmaterialized()));
+                stats.skipped_files += 1;
+                continue;
+            }
             let disk_path = path.to_fs_path(&self.working_copy_path);
 
-            if present_before {
+            // Whatever is on disk at a path we couldn't write isn't ours to
+            // remove.
+            let was_unmaterialized = self
+                .file_states
+                .all()
+                .get(&path)
+                .is_some_and(|state| state.file_type == FileType::Unmaterialized);
+            if present_before && !was_unmaterialized {
+                if self.is_modified_on_disk(&path, &disk_path) {
+                    // Don't clobber changes made since the file was last
+                    // written or snapshotted. Keep a placeholder state so the
+                    // next snapshot picks them up.
+                    changed_file_states.push((path, FileState::placeholder()));
+                    stats.skipped_files += 1;
+                    continue;
+                }
                 fs::remove_file(&disk_path).ok();
             } else if disk_path.exists() {
-                changed_file_states.push((path, FileState::placeholder()));
-                stats.skipped_files += 1;
+                if after.is_absent() {
+                    deleted_files.insert(path);
+                } else {
+                    changed_file_states.push((path, FileState::placeholder()));
+                    stats.skipped_files += 1;
+                }
                 continue;
             }
             if after.is_present() {
@@ -1390,7 +1724,6 @@ impl TreeState {
                     continue;
                 }
             }
-            // TODO: Check that the file has not changed before overwriting/removing it.
             let file_state = match after {
                 MaterializedTreeValue::Absent | MaterializedTreeValue::AccessDenied(_) => {
                     let mut parent_dir = disk_path.parent().unwrap();
@@ -1435,6 +1768,23 @@ impl TreeState {
         Ok(stats)
     }
 
+    /// Returns true if the file at `disk_path` no longer matches the state
+    /// recorded for it, e.g. because it was edited after the last checkout or
+    /// snapshot. Missing files, submodules, and unmaterialized paths aren't
+    /// considered modified.
+    fn is_modified_on_disk(&self, path: &RepoPath, disk_path: &Path) -> bool {
+        let Some(current_file_state) = self.file_states.all().get(path) else {
+            return false;
+        };
+        if current_file_state.is_untouchable() {
+            return false;
+        }
+        match disk_path.symlink_metadata() {
+            Ok(metadata) => file_state(&metadata).as_ref() != Some(&current_file_state),
+            Err(_) => false,
+        }
+    }
+
     pub async fn reset(&mut self, new_tree: &MergedTree) -> Result<(), ResetError> {
         let old_tree = self.current_tree().map_err(|err| match err {
             err @ BackendError::ObjectNotFound { .. } => ResetError::SourceNotFound {
@@ -1451,6 +1801,11 @@ impl TreeState {
             let (_before, after) = diff?;
             if after.is_absent() {
                 deleted_files.insert(path);
+            } else if !self
+                .path_rules
+                .allows(&path, &path.to_fs_path(&self.working_copy_path))
+            {
+                changed_file_states.push((path, FileState::for_unmaterialized()));
             } else {
                 let file_type = match after.into_resolved() {
                     Ok(value) => match value.unwrap() {
@@ -1549,6 +1904,14 @@ impl WorkingCopy for LocalWorkingCopy {
         Ok(self.tree_state()?.sparse_patterns())
     }
 
+    fn untracked(&self, options: SnapshotOptions) -> Result<Vec<UntrackedEntry>, SnapshotError> {
+        let tree_state = self.tree_state().map_err(|err| SnapshotError::Other {
+            message: "Failed to read the working copy state".to_string(),
+            err: err.into(),
+        })?;
+        tree_state.untracked(options)
+    }
+
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError> {
         let lock_path = self.state_path.join("working_copy.lock");
         let lock = FileLock::lock(lock_path);
@@ -1692,6 +2055,12 @@ impl LocalWorkingCopy {
         Ok(self.tree_state()?.file_states())
     }
 
+    /// Finds the tracked files that changed on disk since the last snapshot by
+    /// their metadata only. See [`TreeState::stat_changes()`].
+    pub fn stat_changes(&self) -> Result<StatChanges, WorkingCopyStateError> {
+        Ok(self.tree_state()?.stat_changes())
+    }
+
     #[instrument(skip_all)]
     fn save(&mut self) {
         self.write_proto(crate::protos::working_copy::Checkout {
@@ -1910,6 +2279,9 @@ mod tests {
     use maplit::hashset;
 
     use super::*;
+    use crate::files::{ContentTypes, MergeDrivers};
+    use crate::local_backend::LocalBackend;
+    use crate::signing::Signer;
 
     fn repo_path(value: &str) -> &RepoPath {
         RepoPath::from_internal_string(value)
@@ -2026,4 +2398,57 @@ mod tests {
         assert_eq!(file_states.get(repo_path("bc")), Some(new_state(5)));
         assert_eq!(file_states.get(repo_path("z")), None);
     }
+
+    #[test]
+    fn test_symlink_round_trip_without_symlink_support() {
+        let temp_dir = testutils::new_temp_dir();
+        let store_path = temp_dir.path().join("store");
+        let working_copy_path = temp_dir.path().join("repo");
+        let state_path = temp_dir.path().join("state");
+        for dir in [&store_path, &working_copy_path, &state_path] {
+            fs::create_dir(dir).unwrap();
+        }
+        let backend = LocalBackend::init(&store_path);
+        let store = Store::new(
+            Box::new(backend),
+            Signer::new(None, vec![]),
+            false,
+            MergeDrivers::default(),
+            ContentTypes::default(),
+        );
+
+        let path = repo_path("link");
+        let symlink_id = store.write_symlink(path, "target").unwrap();
+        let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+        tree_builder.set(path.to_owned(), TreeValue::Symlink(symlink_id));
+        let tree_id = MergedTreeId::resolved(tree_builder.write_tree().unwrap());
+        let tree = store.get_root_tree(&tree_id).unwrap();
+
+        // The symlink is materialized as a plain file containing the target
+        let mut tree_state =
+            TreeState::init(store.clone(), working_copy_path.clone(), state_path).unwrap();
+        tree_state.symlink_support = false;
+        tree_state.check_out(&tree).unwrap();
+        let disk_path = path.to_fs_path(&working_copy_path);
+        assert!(disk_path.symlink_metadata().unwrap().is_file());
+        assert_eq!(fs::read_to_string(&disk_path).unwrap(), "target");
+
+        // Snapshotting the unchanged file doesn't turn the symlink into a file
+        tree_state
+            .snapshot(SnapshotOptions::empty_for_test())
+            .unwrap();
+        assert_eq!(*tree_state.current_tree_id(), tree_id);
+
+        // Editing the file updates the symlink target
+        fs::write(&disk_path, "other target").unwrap();
+        tree_state
+            .snapshot(SnapshotOptions::empty_for_test())
+            .unwrap();
+        let new_tree = store.get_root_tree(tree_state.current_tree_id()).unwrap();
+        let value = new_tree.path_value(path).unwrap();
+        let Some(TreeValue::Symlink(id)) = value.as_normal() else {
+            panic!("unexpected value: {value:?}");
+        };
+        assert_eq!(store.read_symlink(path, id).unwrap(), "other target");
+    }
 }
@@ -29,6 +29,7 @@ use itertools::Itertools;
 
 use crate::backend;
 use crate::backend::{BackendResult, ConflictId, MergedTreeId, TreeId, TreeValue};
+use crate::files::MergeDriver;
 use crate::matchers::{EverythingMatcher, Matcher};
 use crate::merge::{Merge, MergeBuilder, MergedTreeValue};
 use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
@@ -386,6 +387,29 @@ impl MergedTree {
 
     /// Merges this tree with `other`, using `base` as base.
     pub fn merge(&self, base: &MergedTree, other: &MergedTree) -> BackendResult<MergedTree> {
+        MergedTree::Merge(self.merge_input(base, other)?).resolve()
+    }
+
+    /// Like `merge()`, but also returns the paths whose conflicts were resolved
+    /// by merging the file contents, along with the driver selected for them.
+    pub fn merge_with_resolutions(
+        &self,
+        base: &MergedTree,
+        other: &MergedTree,
+    ) -> BackendResult<(MergedTree, Vec<(RepoPathBuf, MergeDriver)>)> {
+        let unresolved = MergedTree::Merge(self.merge_input(base, other)?);
+        let merged = unresolved.resolve()?;
+        let mut resolutions = vec![];
+        for (path, _) in unresolved.conflicts() {
+            if merged.path_value(&path)?.is_resolved() {
+                let driver = self.store().merge_drivers().driver_for(&path);
+                resolutions.push((path, driver));
+            }
+        }
+        Ok((merged, resolutions))
+    }
+
+    fn merge_input(&self, base: &MergedTree, other: &MergedTree) -> BackendResult<Merge<Tree>> {
         // Convert legacy trees to merged trees and unwrap to `Merge<Tree>`
         let to_merge = |tree: &MergedTree| -> BackendResult<Merge<Tree>> {
             match tree {
@@ -400,8 +424,7 @@ impl MergedTree {
             }
         };
         let nested = Merge::from_vec(vec![to_merge(self)?, to_merge(base)?, to_merge(other)?]);
-        let flattened = MergedTree::Merge(nested.flatten().simplify());
-        flattened.resolve()
+        Ok(nested.flatten().simplify())
     }
 }
 
@@ -479,6 +502,21 @@ fn merge_trees(merge: &Merge<Tree>) -> BackendResult<Merge<Tree>> {
         return Ok(Merge::resolved(tree.clone()));
     }
 
+    // The same merges tend to recur, e.g. when a stack is rebased repeatedly,
+    // so the results are cached by the store.
+    let base_tree = merge.first();
+    let store = base_tree.store();
+    let dir = base_tree.dir();
+    let input_ids = merge.map(|tree| tree.id().clone());
+    if let Some(output_ids) = store.cached_tree_merge(dir, &input_ids) {
+        return output_ids.try_map(|id| store.get_tree(dir, id));
+    }
+    let output = merge_trees_uncached(merge)?;
+    store.cache_tree_merge(dir, input_ids, output.map(|tree| tree.id().clone()));
+    Ok(output)
+}
+
+fn merge_trees_uncached(merge: &Merge<Tree>) -> BackendResult<Merge<Tree>> {
     let base_tree = merge.first();
     let store = base_tree.store();
     let dir = base_tree.dir();
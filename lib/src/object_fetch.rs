@@ -0,0 +1,443 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A have/want protocol for copying the objects one store is missing from
+//! another.
+//!
+//! The client sends the request magic followed by the `have` and `want` commit
+//! ids. The server responds with the response magic, then the missing objects,
+//! each as a kind byte, its id and its contents, and finally `FETCH_END`. Ids,
+//! contents and lists are prefixed with their big-endian u64 length. Commits,
+//! trees and legacy conflicts are encoded as in the local backend, whichever
+//! backends the stores use.
+
+#![allow(missing_docs)]
+
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use prost::Message as _;
+
+use crate::backend::{
+    BackendError, BackendResult, CommitId, ConflictId, FileId, SymlinkId, TreeId, TreeValue,
+};
+use crate::merge::Merge;
+use crate::object_id::ObjectId as _;
+use crate::repo::Repo;
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::store::Store;
+use crate::{local_backend, revset};
+
+/// Objects transferred by [`fetch_missing()`] or [`serve_fetch()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchStats {
+    pub commits: usize,
+    pub trees: usize,
+    pub files: usize,
+    pub symlinks: usize,
+    pub conflicts: usize,
+}
+
+const FETCH_REQUEST_MAGIC: &[u8] = b"jj-fetch-request\n";
+const FETCH_RESPONSE_MAGIC: &[u8] = b"jj-fetch-response\n";
+const FETCH_END: u8 = 0;
+const FETCH_COMMIT: u8 = 1;
+const FETCH_TREE: u8 = 2;
+const FETCH_FILE: u8 = 3;
+const FETCH_SYMLINK: u8 = 4;
+const FETCH_CONFLICT: u8 = 5;
+
+/// Fetches the objects reachable from `want` that aren't reachable from
+/// `have` from a repo serving [`serve_fetch()`] at the other end of `reader`
+/// and `writer`, and writes them to `store`. `have` are typically the heads of
+/// the store's repo; ids the server doesn't know are ignored. Predecessors
+/// aren't followed. Commits are written with the signatures they were fetched
+/// with.
+pub fn fetch_missing(
+    store: &Arc<Store>,
+    have: &[CommitId],
+    want: &[CommitId],
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> BackendResult<FetchStats> {
+    store.check_writable()?;
+    writer
+        .write_all(FETCH_REQUEST_MAGIC)
+        .and_then(|()| write_fetch_ids(writer, have))
+        .and_then(|()| write_fetch_ids(writer, want))
+        .and_then(|()| writer.flush())
+        .map_err(fetch_io_err)?;
+
+    let mut magic = vec![0; FETCH_RESPONSE_MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(fetch_io_err)?;
+    if magic != FETCH_RESPONSE_MAGIC {
+        return Err(BackendError::Other("Invalid fetch response".into()));
+    }
+    let mut stats = FetchStats::default();
+    loop {
+        let mut kind = [0];
+        reader.read_exact(&mut kind).map_err(fetch_io_err)?;
+        let [kind] = kind;
+        if kind == FETCH_END {
+            break;
+        }
+        let id = read_fetch_bytes(reader).map_err(fetch_io_err)?;
+        let data = read_fetch_bytes(reader).map_err(fetch_io_err)?;
+        let written_id = match kind {
+            FETCH_COMMIT => {
+                let proto = crate::protos::local_store::Commit::decode(data.as_slice())
+                    .map_err(fetch_decode_err)?;
+                let mut commit = local_backend::commit_from_proto(proto);
+                stats.commits += 1;
+                match commit.secure_sig.take() {
+                    Some(secure_sig) => {
+                        let mut sign = |_: &[u8]| Ok(secure_sig.sig.clone());
+                        store.write_commit(commit, Some(&mut sign))?
+                    }
+                    None => store.write_commit(commit, None)?,
+                }
+                .id()
+                .to_bytes()
+            }
+            FETCH_TREE => {
+                let proto = crate::protos::local_store::Tree::decode(data.as_slice())
+                    .map_err(fetch_decode_err)?;
+                let tree = local_backend::tree_from_proto(proto);
+                stats.trees += 1;
+                store.write_tree(RepoPath::root(), tree)?.id().to_bytes()
+            }
+            FETCH_FILE => {
+                stats.files += 1;
+                store
+                    .write_file(RepoPath::root(), &mut data.as_slice())?
+                    .to_bytes()
+            }
+            FETCH_SYMLINK => {
+                let target = String::from_utf8(data).map_err(fetch_decode_err)?;
+                stats.symlinks += 1;
+                store.write_symlink(RepoPath::root(), &target)?.to_bytes()
+            }
+            FETCH_CONFLICT => {
+                let proto = crate::protos::local_store::Conflict::decode(data.as_slice())
+                    .map_err(fetch_decode_err)?;
+                let conflict = local_backend::conflict_from_proto(proto);
+                stats.conflicts += 1;
+                store
+                    .write_conflict(RepoPath::root(), &Merge::from_backend_conflict(conflict))?
+                    .to_bytes()
+            }
+            _ => {
+                return Err(BackendError::Other(
+                    format!("Invalid object kind {kind} in fetch response").into(),
+                ));
+            }
+        };
+        if written_id != id {
+            return Err(BackendError::Other(
+                format!(
+                    "Fetched object {} was written with a different id {}",
+                    hex::encode(&id),
+                    hex::encode(&written_id)
+                )
+                .into(),
+            ));
+        }
+    }
+    Ok(stats)
+}
+
+/// Serves a [`fetch_missing()`] request read from `reader`, writing the
+/// objects of `repo` the client is missing to `writer`. Returns what was sent.
+pub fn serve_fetch(
+    repo: &dyn Repo,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+) -> BackendResult<FetchStats> {
+    let mut magic = vec![0; FETCH_REQUEST_MAGIC.len()];
+    reader.read_exact(&mut magic).map_err(fetch_io_err)?;
+    if magic != FETCH_REQUEST_MAGIC {
+        return Err(BackendError::Other("Invalid fetch request".into()));
+    }
+    let have = read_fetch_ids(reader).map_err(fetch_io_err)?;
+    let want = read_fetch_ids(reader).map_err(fetch_io_err)?;
+
+    writer
+        .write_all(FETCH_RESPONSE_MAGIC)
+        .map_err(fetch_io_err)?;
+    let store = repo.store();
+    let mut stats = FetchStats::default();
+    for object in missing_objects(repo, &have, &want)? {
+        let (kind, id, data) = match object {
+            FetchObject::Commit(id) => {
+                let commit = store.get_commit(&id)?;
+                stats.commits += 1;
+                let mut proto = local_backend::commit_to_proto(commit.store_commit());
+                proto.secure_sig = commit
+                    .store_commit()
+                    .secure_sig
+                    .as_ref()
+                    .map(|secure_sig| secure_sig.sig.clone());
+                (FETCH_COMMIT, id.to_bytes(), proto.encode_to_vec())
+            }
+            FetchObject::Tree(path, id) => {
+                let tree = store.get_tree(&path, &id)?;
+                stats.trees += 1;
+                let proto = local_backend::tree_to_proto(tree.data());
+                (FETCH_TREE, id.to_bytes(), proto.encode_to_vec())
+            }
+            FetchObject::File(path, id) => {
+                let mut contents = vec![];
+                store
+                    .read_file(&path, &id)?
+                    .read_to_end(&mut contents)
+                    .map_err(|err| BackendError::ReadFile {
+                        path,
+                        id: id.clone(),
+                        source: err.into(),
+                    })?;
+                stats.files += 1;
+                (FETCH_FILE, id.to_bytes(), contents)
+            }
+            FetchObject::Symlink(path, id) => {
+                let target = store.read_symlink(&path, &id)?;
+                stats.symlinks += 1;
+                (FETCH_SYMLINK, id.to_bytes(), target.into_bytes())
+            }
+            FetchObject::Conflict(path, id) => {
+                let conflict = store.read_conflict(&path, &id)?;
+                stats.conflicts += 1;
+                let proto = local_backend::conflict_to_proto(&conflict.into_backend_conflict());
+                (FETCH_CONFLICT, id.to_bytes(), proto.encode_to_vec())
+            }
+        };
+        writer
+            .write_all(&[kind])
+            .and_then(|()| write_fetch_bytes(writer, &id))
+            .and_then(|()| write_fetch_bytes(writer, &data))
+            .map_err(fetch_io_err)?;
+    }
+    writer
+        .write_all(&[FETCH_END])
+        .and_then(|()| writer.flush())
+        .map_err(fetch_io_err)?;
+    Ok(stats)
+}
+
+/// Lists the objects reachable from `want` but not from `have`, in an order
+/// where objects come after the ones they refer to. The missing commits are
+/// found with the index, and only the tree entries that changed compared to
+/// the first parent are visited, so the cost depends on the missing commits
+/// only.
+fn missing_objects(
+    repo: &dyn Repo,
+    have: &[CommitId],
+    want: &[CommitId],
+) -> BackendResult<Vec<FetchObject>> {
+    let store = repo.store();
+    let index = repo.index();
+    if let Some(id) = want.iter().find(|id| !index.has_id(id)) {
+        return Err(BackendError::ObjectNotFound {
+            object_type: "commit".to_string(),
+            hash: id.hex(),
+            source: "No such commit in the index".into(),
+        });
+    }
+    // The client may have commits the server doesn't know about. Every store
+    // has the root commit.
+    let have = have
+        .iter()
+        .filter(|id| index.has_id(id))
+        .chain([store.root_commit_id()])
+        .cloned()
+        .collect::<Vec<_>>();
+    let missing_ids = revset::walk_revs(repo, want, &have)
+        .map_err(|err| BackendError::Other(err.into()))?
+        .iter()
+        .collect::<Vec<_>>();
+
+    let mut seen_trees = HashSet::from([store.empty_tree_id().clone()]);
+    let mut seen_objects = HashSet::new();
+    let mut objects = vec![];
+    for id in missing_ids.iter().rev() {
+        let commit = store.get_commit(id)?;
+        // The client has the objects of the first parent, or they were sent
+        // before the commit
+        let base_tree_id = match commit.parent_ids() {
+            [parent_id, ..] if parent_id != store.root_commit_id() => {
+                let parent = store.get_commit(parent_id)?;
+                parent.tree_id().to_merge().first().clone().into()
+            }
+            _ => None,
+        };
+        for tree_id in commit.tree_id().to_merge().iter() {
+            collect_tree_objects(
+                store,
+                RepoPath::root(),
+                tree_id,
+                base_tree_id.as_ref(),
+                &mut seen_trees,
+                &mut seen_objects,
+                &mut objects,
+            )?;
+        }
+        objects.push(FetchObject::Commit(id.clone()));
+    }
+    Ok(objects)
+}
+
+/// Adds the objects of the tree that aren't in the `base_id` tree at the same
+/// path, skipping subtrees that are the same in both.
+fn collect_tree_objects(
+    store: &Arc<Store>,
+    dir: &RepoPath,
+    id: &TreeId,
+    base_id: Option<&TreeId>,
+    seen_trees: &mut HashSet<TreeId>,
+    seen_objects: &mut HashSet<FetchObject>,
+    objects: &mut Vec<FetchObject>,
+) -> BackendResult<()> {
+    if base_id == Some(id) || !seen_trees.insert(id.clone()) {
+        return Ok(());
+    }
+    let tree = store.get_tree(dir, id)?;
+    let base_tree = base_id.map(|id| store.get_tree(dir, id)).transpose()?;
+    for entry in tree.entries_non_recursive() {
+        let base_value = base_tree.as_ref().and_then(|tree| tree.value(entry.name()));
+        if base_value == Some(entry.value()) {
+            continue;
+        }
+        let path = dir.join(entry.name());
+        let mut add_value = |path: RepoPathBuf, value: &TreeValue| {
+            let object = match value {
+                TreeValue::File { id, .. } => FetchObject::File(path, id.clone()),
+                TreeValue::Symlink(id) => FetchObject::Symlink(path, id.clone()),
+                TreeValue::Conflict(id) => FetchObject::Conflict(path, id.clone()),
+                TreeValue::Tree(_) | TreeValue::GitSubmodule(_) => return,
+            };
+            if seen_objects.insert(object.clone()) {
+                objects.push(object);
+            }
+        };
+        match entry.value() {
+            TreeValue::Tree(subtree_id) => {
+                let base_subtree_id = match base_value {
+                    Some(TreeValue::Tree(id)) => Some(id),
+                    _ => None,
+                };
+                collect_tree_objects(
+                    store,
+                    &path,
+                    subtree_id,
+                    base_subtree_id,
+                    seen_trees,
+                    seen_objects,
+                    objects,
+                )?;
+            }
+            TreeValue::Conflict(conflict_id) => {
+                // Legacy conflicts refer to the files of their terms
+                for value in store.read_conflict(&path, conflict_id)?.iter().flatten() {
+                    add_value(path.clone(), value);
+                }
+                add_value(path, entry.value());
+            }
+            value => add_value(path, value),
+        }
+    }
+    objects.push(FetchObject::Tree(dir.to_owned(), id.clone()));
+    Ok(())
+}
+
+/// An object to send in a fetch response. Files, symlinks and conflicts are
+/// keyed by id only, so the same contents at different paths are sent once.
+#[derive(Clone, Debug)]
+enum FetchObject {
+    Commit(CommitId),
+    Tree(RepoPathBuf, TreeId),
+    File(RepoPathBuf, FileId),
+    Symlink(RepoPathBuf, SymlinkId),
+    Conflict(RepoPathBuf, ConflictId),
+}
+
+impl FetchObject {
+    fn key(&self) -> (u8, &[u8]) {
+        match self {
+            FetchObject::Commit(id) => (FETCH_COMMIT, id.as_bytes()),
+            FetchObject::Tree(_, id) => (FETCH_TREE, id.as_bytes()),
+            FetchObject::File(_, id) => (FETCH_FILE, id.as_bytes()),
+            FetchObject::Symlink(_, id) => (FETCH_SYMLINK, id.as_bytes()),
+            FetchObject::Conflict(_, id) => (FETCH_CONFLICT, id.as_bytes()),
+        }
+    }
+}
+
+impl PartialEq for FetchObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for FetchObject {}
+
+impl Hash for FetchObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+fn fetch_io_err(err: io::Error) -> BackendError {
+    BackendError::Other(format!("Failed to transfer objects: {err}").into())
+}
+
+fn fetch_decode_err(err: impl std::fmt::Display) -> BackendError {
+    BackendError::Other(format!("Invalid object in fetch response: {err}").into())
+}
+
+fn write_fetch_bytes(writer: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_be_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_fetch_len(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    Ok(u64::from_be_bytes(len))
+}
+
+fn read_fetch_bytes(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let len = read_fetch_len(reader)?;
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn write_fetch_ids(writer: &mut dyn Write, ids: &[CommitId]) -> io::Result<()> {
+    writer.write_all(&(ids.len() as u64).to_be_bytes())?;
+    for id in ids {
+        write_fetch_bytes(writer, id.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_fetch_ids(reader: &mut dyn Read) -> io::Result<Vec<CommitId>> {
+    let len = read_fetch_len(reader)?;
+    (0..len)
+        .map(|_| Ok(CommitId::new(read_fetch_bytes(reader)?)))
+        .collect()
+}
@@ -15,7 +15,7 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Debug, Error, Formatter};
 use std::iter;
 use std::time::SystemTime;
@@ -24,7 +24,7 @@ use itertools::Itertools as _;
 use once_cell::sync::Lazy;
 use thiserror::Error;
 
-use crate::backend::{CommitId, MillisSinceEpoch, Timestamp};
+use crate::backend::{ChangeId, CommitId, MillisSinceEpoch, Timestamp};
 use crate::content_hash::ContentHash;
 use crate::merge::Merge;
 use crate::object_id::{id_type, HexPrefix, ObjectId, PrefixResolution};
@@ -271,6 +271,26 @@ pub struct View {
     // (.jj/working_copy/) has the source of truth about which commit *is* checked out (to be
     // precise: the commit to which we most recently completed an update to).
     pub wc_commit_ids: HashMap<WorkspaceId, CommitId>,
+    /// The commits previously checked out in each workspace, newest first.
+    pub checkout_history: HashMap<WorkspaceId, Vec<CommitId>>,
+    /// Labels and other metadata attached to changes, e.g. by code-review
+    /// integrations.
+    pub change_metadata: BTreeMap<ChangeId, ChangeMetadata>,
+    /// Changes whose divergence the user accepted, so `evolve()` leaves them
+    /// alone.
+    pub accepted_divergences: BTreeSet<ChangeId>,
+}
+
+/// Key-value metadata attached to a change rather than to one of its commits,
+/// so it stays with the change when the commits are rewritten.
+#[derive(ContentHash, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ChangeMetadata {
+    pub labels: BTreeSet<String>,
+    pub values: BTreeMap<String, String>,
+    /// Number of operations in a row that ended without a visible commit of
+    /// the change. The metadata is dropped once this exceeds the configured
+    /// retention.
+    pub operations_without_commits: u64,
 }
 
 /// Represents the state of the remote repo.
@@ -438,6 +458,30 @@ pub trait OpStore: Send + Sync + Debug {
         prefix: &HexPrefix,
     ) -> OpStoreResult<PrefixResolution<OperationId>>;
 
+    /// Records that the operations `removed_ids` were replaced by the tombstone
+    /// operation `tombstone_id`. The tombstone of a later truncation includes
+    /// the ids removed by earlier ones, so the new record replaces the
+    /// previous one.
+    ///
+    /// Stores that don't record truncations keep the default implementation,
+    /// which does nothing.
+    fn record_truncation(
+        &self,
+        _tombstone_id: &OperationId,
+        _removed_ids: &[OperationId],
+    ) -> OpStoreResult<()> {
+        Ok(())
+    }
+
+    /// Looks up the recorded tombstone operation which replaced the removed
+    /// operation matching the `prefix`.
+    ///
+    /// Stores that don't record truncations keep the default implementation,
+    /// which finds none.
+    fn find_truncating_tombstone(&self, _prefix: &HexPrefix) -> OpStoreResult<Option<OperationId>> {
+        Ok(None)
+    }
+
     /// Prunes unreachable operations and views.
     ///
     /// All operations and views reachable from the `head_ids` won't be
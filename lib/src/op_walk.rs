@@ -15,19 +15,25 @@
 //! Utility for operation id resolution and traversal.
 
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
 use std::slice;
 use std::sync::Arc;
 
 use itertools::Itertools as _;
 use thiserror::Error;
 
-use crate::object_id::{HexPrefix, PrefixResolution};
+use crate::backend::{BackendError, CommitId, Timestamp};
+use crate::object_id::{HexPrefix, ObjectId as _, PrefixResolution};
 use crate::op_heads_store::{OpHeadResolutionError, OpHeadsStore};
-use crate::op_store::{OpStore, OpStoreError, OpStoreResult, OperationId};
+use crate::op_store::{
+    OpStore, OpStoreError, OpStoreResult, OperationId, OperationMetadata, RefTarget, WorkspaceId,
+};
 use crate::operation::Operation;
-use crate::repo::{ReadonlyRepo, Repo as _, RepoLoader};
-use crate::{dag_walk, op_heads_store};
+use crate::refs::diff_named_ref_targets;
+use crate::repo::{ReadonlyRepo, Repo as _, RepoLoader, RepoLoaderError};
+use crate::revset::{RevsetEvaluationError, RevsetExpression};
+use crate::{dag_walk, op_heads_store, op_store};
 
 /// Error that may occur during evaluation of operation set expression.
 #[derive(Debug, Error)]
@@ -64,6 +70,12 @@ pub enum OpsetResolutionError {
     /// Operation ID prefix matches multiple operations.
     #[error(r#"Operation ID prefix "{0}" is ambiguous"#)]
     AmbiguousIdPrefix(String),
+    /// Operation was removed by `truncate_before()`.
+    #[error(
+        r#"Operation "{0}" was removed from the operation log (see tombstone operation {})"#,
+        .1.hex()
+    )]
+    TruncatedOperation(String, OperationId),
 }
 
 /// Resolves operation set expression without loading a repo.
@@ -142,11 +154,21 @@ fn resolve_single_op_from_store(
     }
     let prefix = HexPrefix::new(op_str)
         .ok_or_else(|| OpsetResolutionError::InvalidIdPrefix(op_str.to_owned()))?;
+    let truncated_error = |tombstone_id: OperationId| {
+        OpsetResolutionError::TruncatedOperation(op_str.to_owned(), tombstone_id)
+    };
     match op_store.resolve_operation_id_prefix(&prefix)? {
-        PrefixResolution::NoMatch => {
-            Err(OpsetResolutionError::NoSuchOperation(op_str.to_owned()).into())
-        }
+        PrefixResolution::NoMatch => match op_store.find_truncating_tombstone(&prefix)? {
+            Some(tombstone_id) => Err(truncated_error(tombstone_id).into()),
+            None => Err(OpsetResolutionError::NoSuchOperation(op_str.to_owned()).into()),
+        },
         PrefixResolution::SingleMatch(op_id) => {
+            // The removed operation may still exist in the store until it gets
+            // garbage collected.
+            let full_prefix = HexPrefix::from_bytes(op_id.as_bytes());
+            if let Some(tombstone_id) = op_store.find_truncating_tombstone(&full_prefix)? {
+                return Err(truncated_error(tombstone_id).into());
+            }
             let data = op_store.read_operation(&op_id)?;
             Ok(Operation::new(op_store.clone(), op_id, data))
         }
@@ -186,6 +208,17 @@ fn find_child_ops(
         .try_collect()
 }
 
+/// Returns the ids of the operations removed by `truncate_before()` if the
+/// `op` is a tombstone.
+pub fn truncated_op_ids(op: &Operation) -> impl Iterator<Item = OperationId> + '_ {
+    op.metadata()
+        .tags
+        .get(TRUNCATED_OPERATIONS_TAG)
+        .into_iter()
+        .flat_map(|ids| ids.split_whitespace())
+        .filter_map(|hex| OperationId::try_from_hex(hex).ok())
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 struct OperationByEndTime(Operation);
 
@@ -296,3 +329,310 @@ pub fn reparent_range(
         unreachable_count: unreachable_ids.len(),
     })
 }
+
+/// Operation tag listing the ids of the operations replaced by a tombstone.
+pub const TRUNCATED_OPERATIONS_TAG: &str = "truncated-operations";
+
+/// Options for `truncate_before()`.
+#[derive(Clone, Debug, Default)]
+pub struct TruncateOptions {
+    /// Description of the tombstone operation. A summary of the removed
+    /// operations is used if unspecified.
+    pub description: Option<String>,
+}
+
+/// Result of `truncate_before()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TruncateReport {
+    /// New head operation id.
+    pub new_head_id: OperationId,
+    /// Tombstone operation which replaced the removed operations, or `None` if
+    /// there was nothing to remove.
+    pub tombstone_id: Option<OperationId>,
+    /// The number of removed operations.
+    pub removed_count: usize,
+    /// The number of rewritten descendant operations.
+    pub rewritten_count: usize,
+    /// The earliest start time and the latest end time of the removed
+    /// operations.
+    pub removed_time_range: Option<(Timestamp, Timestamp)>,
+}
+
+/// Removes the ancestors of the operation `op_id`, and reparents the range
+/// `op_id::` of the `repo` head onto a tombstone operation.
+///
+/// The tombstone operation is a child of the root operation. It records the
+/// ids and the time range of the removed operations. The removed ids are also
+/// recorded in the op store, so that resolution of them fails with
+/// `OpsetResolutionError::TruncatedOperation`. The
+/// removed operations and the objects only reachable from them can then be
+/// garbage collected.
+///
+/// The current operation heads are updated, but the operation id of the
+/// working copy needs to be remapped by caller.
+pub fn truncate_before(
+    repo: &ReadonlyRepo,
+    op_id: &OperationId,
+    options: &TruncateOptions,
+) -> OpStoreResult<TruncateReport> {
+    let op_store = repo.op_store();
+    let root_op_id = op_store.root_operation_id();
+    let cut_op = Operation::new(
+        op_store.clone(),
+        op_id.clone(),
+        op_store.read_operation(op_id)?,
+    );
+    let parent_ops: Vec<_> = cut_op.parents().try_collect()?;
+    let (old_tombstones, removed_ops): (Vec<_>, Vec<_>) = walk_ancestors(&parent_ops)
+        .filter_ok(|op| op.id() != root_op_id)
+        .process_results(|ops| ops.partition(|op| truncated_op_ids(op).next().is_some()))?;
+    if removed_ops.is_empty() {
+        return Ok(TruncateReport {
+            new_head_id: repo.op_id().clone(),
+            tombstone_id: None,
+            removed_count: 0,
+            rewritten_count: 0,
+            removed_time_range: None,
+        });
+    }
+    let removed_time_range = time_range(&removed_ops);
+    // Existing tombstones are folded into the new one.
+    let (start_time, end_time) = time_range(removed_ops.iter().chain(&old_tombstones)).unwrap();
+    let removed_ids = removed_ops
+        .iter()
+        .chain(&old_tombstones)
+        .map(|op| op.id().clone())
+        .chain(old_tombstones.iter().flat_map(truncated_op_ids))
+        .collect_vec();
+
+    let root_data = op_store.read_operation(root_op_id)?;
+    let description = options
+        .description
+        .clone()
+        .unwrap_or_else(|| format!("truncate {} operations", removed_ops.len()));
+    let tags = HashMap::from([(
+        TRUNCATED_OPERATIONS_TAG.to_owned(),
+        removed_ids.iter().map(|id| id.hex()).join(" "),
+    )]);
+    let tombstone_data = op_store::Operation {
+        view_id: root_data.view_id,
+        parents: vec![root_op_id.clone()],
+        metadata: OperationMetadata {
+            start_time,
+            end_time,
+            description,
+            hostname: String::new(),
+            username: String::new(),
+            is_snapshot: false,
+            tags,
+        },
+    };
+    let tombstone_id = op_store.write_operation(&tombstone_data)?;
+    op_store.record_truncation(&tombstone_id, &removed_ids)?;
+    let tombstone_op = Operation::new(op_store.clone(), tombstone_id.clone(), tombstone_data);
+
+    let stats = reparent_range(
+        op_store.as_ref(),
+        &parent_ops,
+        slice::from_ref(repo.operation()),
+        &tombstone_op,
+    )?;
+    let [new_head_id]: [OperationId; 1] = stats.new_head_ids.try_into().unwrap();
+    repo.op_heads_store()
+        .update_op_heads(slice::from_ref(repo.op_id()), &new_head_id);
+    Ok(TruncateReport {
+        new_head_id,
+        tombstone_id: Some(tombstone_id),
+        removed_count: removed_ops.len(),
+        rewritten_count: stats.rewritten_count,
+        removed_time_range,
+    })
+}
+
+fn time_range<'a>(ops: impl IntoIterator<Item = &'a Operation>) -> Option<(Timestamp, Timestamp)> {
+    ops.into_iter()
+        .map(|op| (&op.metadata().start_time, &op.metadata().end_time))
+        .reduce(|(start1, end1), (start2, end2)| (start1.min(start2), end1.max(end2)))
+        .map(|(start, end)| (start.clone(), end.clone()))
+}
+
+/// Changes between two operations, as computed by `diff_operations()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OpRangeDiff {
+    /// Commits visible at the end of the range but not at the start, newest
+    /// first.
+    pub added_commits: Vec<CommitId>,
+    /// Commits visible at the start of the range but not at the end, newest
+    /// first.
+    pub removed_commits: Vec<CommitId>,
+    /// Commits that became visible or hidden within the range, but have the
+    /// same visibility at both ends, newest first.
+    pub transient_commits: Vec<CommitId>,
+    /// Local branches with different targets at the ends, with the targets at
+    /// the start and at the end.
+    pub branch_changes: BTreeMap<String, (RefTarget, RefTarget)>,
+    /// Local branches that were moved within the range, but have the same
+    /// target at both ends.
+    pub transient_branches: BTreeSet<String>,
+    /// Workspaces with different working-copy commits at the ends, with the
+    /// commits at the start and at the end.
+    pub wc_changes: BTreeMap<WorkspaceId, (Option<CommitId>, Option<CommitId>)>,
+}
+
+/// Error from `diff_operations()`.
+#[derive(Debug, Error)]
+pub enum OpDiffError {
+    /// The start of the range isn't an ancestor of its end.
+    #[error(
+        "Operation {} is not an ancestor of operation {}",
+        .from_op_id.hex(),
+        .to_op_id.hex()
+    )]
+    NotAncestor {
+        /// The start of the range.
+        from_op_id: OperationId,
+        /// The end of the range.
+        to_op_id: OperationId,
+    },
+    /// Failed to load an operation, its view, or the repo at the end of the
+    /// range.
+    #[error(transparent)]
+    Load(#[from] RepoLoaderError),
+}
+
+impl From<OpStoreError> for OpDiffError {
+    fn from(err: OpStoreError) -> Self {
+        OpDiffError::Load(err.into())
+    }
+}
+
+impl From<BackendError> for OpDiffError {
+    fn from(err: BackendError) -> Self {
+        OpDiffError::Load(err.into())
+    }
+}
+
+/// Compares the views of `from_op` and `to_op`, and lists what changed in
+/// between. Fails with `OpDiffError::NotAncestor` unless `from_op` is an
+/// ancestor of `to_op` (or `to_op` itself).
+///
+/// Changes that cancel out within the range (such as a commit that was added
+/// and then abandoned) aren't reported as added or removed, but as transient.
+/// They're found by comparing the view of each operation that is an ancestor
+/// of `to_op` but not of `from_op` with the views of its parents.
+pub fn diff_operations(
+    repo: &ReadonlyRepo,
+    from_op: &Operation,
+    to_op: &Operation,
+) -> Result<OpRangeDiff, OpDiffError> {
+    let from_ancestor_ids: HashSet<OperationId> = walk_ancestors(slice::from_ref(from_op))
+        .map_ok(|op| op.id().clone())
+        .try_collect()?;
+    let mut range_ops = vec![];
+    let mut is_ancestor = false;
+    for op in walk_ancestors(slice::from_ref(to_op)) {
+        let op = op?;
+        if op.id() == from_op.id() {
+            is_ancestor = true;
+        } else if !from_ancestor_ids.contains(op.id()) {
+            range_ops.push(op);
+        }
+    }
+    if !is_ancestor {
+        return Err(OpDiffError::NotAncestor {
+            from_op_id: from_op.id().clone(),
+            to_op_id: to_op.id().clone(),
+        });
+    }
+
+    // The index at `to_op` contains all commits in the range.
+    let to_repo = repo.loader().load_at(to_op)?;
+    let from_view = from_op.view()?;
+    let to_view = to_op.view()?;
+    let (added_commits, removed_commits) =
+        diff_visible_commits(to_repo.as_ref(), from_view.heads(), to_view.heads())?;
+
+    let mut changed_commits = HashSet::new();
+    let mut changed_branches = BTreeSet::new();
+    for op in range_ops {
+        let view = op.view()?;
+        for parent_op in op.parents() {
+            let parent_view = parent_op?.view()?;
+            let (added, removed) =
+                diff_visible_commits(to_repo.as_ref(), parent_view.heads(), view.heads())?;
+            changed_commits.extend(added);
+            changed_commits.extend(removed);
+            changed_branches.extend(
+                diff_named_ref_targets(parent_view.local_branches(), view.local_branches())
+                    .map(|(name, _)| name.to_owned()),
+            );
+        }
+    }
+
+    for id in added_commits.iter().chain(&removed_commits) {
+        changed_commits.remove(id);
+    }
+    let transient_commits = RevsetExpression::commits(changed_commits.into_iter().collect())
+        .evaluate_programmatic(to_repo.as_ref())
+        .map_err(to_backend_error)?
+        .iter()
+        .collect();
+    let branch_changes: BTreeMap<_, _> =
+        diff_named_ref_targets(from_view.local_branches(), to_view.local_branches())
+            .map(|(name, (from_target, to_target))| {
+                (name.to_owned(), (from_target.clone(), to_target.clone()))
+            })
+            .collect();
+    let transient_branches = changed_branches
+        .into_iter()
+        .filter(|name| !branch_changes.contains_key(name))
+        .collect();
+    let wc_changes = from_view
+        .wc_commit_ids()
+        .keys()
+        .chain(to_view.wc_commit_ids().keys())
+        .unique()
+        .filter_map(|workspace_id| {
+            let from_id = from_view.get_wc_commit_id(workspace_id);
+            let to_id = to_view.get_wc_commit_id(workspace_id);
+            (from_id != to_id).then(|| (workspace_id.clone(), (from_id.cloned(), to_id.cloned())))
+        })
+        .collect();
+    Ok(OpRangeDiff {
+        added_commits,
+        removed_commits,
+        transient_commits,
+        branch_changes,
+        transient_branches,
+        wc_changes,
+    })
+}
+
+/// Returns the commits visible from `new_heads` but not from `old_heads`, and
+/// vice versa, newest first.
+fn diff_visible_commits(
+    repo: &ReadonlyRepo,
+    old_heads: &HashSet<CommitId>,
+    new_heads: &HashSet<CommitId>,
+) -> Result<(Vec<CommitId>, Vec<CommitId>), BackendError> {
+    // Ranges only walk the commits between the heads, not all of history.
+    let old_heads = RevsetExpression::commits(old_heads.iter().cloned().collect());
+    let new_heads = RevsetExpression::commits(new_heads.iter().cloned().collect());
+    let evaluate = |expression: Rc<RevsetExpression>| -> Result<Vec<CommitId>, BackendError> {
+        let revset = expression
+            .evaluate_programmatic(repo)
+            .map_err(to_backend_error)?;
+        Ok(revset.iter().collect())
+    };
+    Ok((
+        evaluate(old_heads.range(&new_heads))?,
+        evaluate(new_heads.range(&old_heads))?,
+    ))
+}
+
+fn to_backend_error(err: RevsetEvaluationError) -> BackendError {
+    match err {
+        RevsetEvaluationError::StoreError(err) => err,
+        err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+    }
+}
@@ -96,6 +96,28 @@ pub struct Tag {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckoutHistory {
+    /// Newest first.
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub commit_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChangeMetadata {
+    #[prost(bytes = "vec", tag = "1")]
+    pub change_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(map = "string, string", tag = "2")]
+    pub values: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    #[prost(uint64, tag = "3")]
+    pub operations_without_commits: u64,
+    #[prost(string, repeated, tag = "4")]
+    pub labels: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct View {
     #[prost(bytes = "vec", repeated, tag = "1")]
     pub head_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
@@ -125,6 +147,17 @@ pub struct View {
     /// Whether "@git" branches have been migrated to remote_targets.
     #[prost(bool, tag = "10")]
     pub has_git_refs_migrated_to_remote: bool,
+    /// Commits previously checked out in each workspace.
+    #[prost(map = "string, message", tag = "11")]
+    pub checkout_history: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        CheckoutHistory,
+    >,
+    #[prost(message, repeated, tag = "13")]
+    pub change_metadata: ::prost::alloc::vec::Vec<ChangeMetadata>,
+    /// Changes whose divergence was accepted by the user.
+    #[prost(bytes = "vec", repeated, tag = "14")]
+    pub accepted_divergences: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -83,6 +83,8 @@ pub enum FileType {
     Executable = 2,
     Conflict = 3,
     GitSubmodule = 4,
+    /// Tracked, but can't be written on this platform
+    Unmaterialized = 5,
 }
 impl FileType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -96,6 +98,7 @@ impl FileType {
             FileType::Executable => "Executable",
             FileType::Conflict => "Conflict",
             FileType::GitSubmodule => "GitSubmodule",
+            FileType::Unmaterialized => "Unmaterialized",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -106,6 +109,7 @@ impl FileType {
             "Executable" => Some(Self::Executable),
             "Conflict" => Some(Self::Conflict),
             "GitSubmodule" => Some(Self::GitSubmodule),
+            "Unmaterialized" => Some(Self::Unmaterialized),
             _ => None,
         }
     }
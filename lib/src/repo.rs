@@ -15,50 +15,98 @@
 #![allow(missing_docs)]
 
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::io::Read as _;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::{fs, slice};
+use std::{fs, io, slice};
 
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
+use regex::Regex;
 use thiserror::Error;
 use tracing::instrument;
 
 use self::dirty_cell::DirtyCell;
 use crate::backend::{
     Backend, BackendError, BackendInitError, BackendLoadError, BackendResult, ChangeId, CommitId,
-    MergedTreeId, SigningFn,
+    FileId, MergedTreeId, SigningFn, Timestamp, TreeId, TreeValue,
 };
 use crate::commit::{Commit, CommitByCommitterTimestamp};
 use crate::commit_builder::CommitBuilder;
 use crate::default_index::{DefaultIndexStore, DefaultMutableIndex};
 use crate::default_submodule_store::DefaultSubmoduleStore;
-use crate::file_util::{IoResultExt as _, PathError};
+use crate::evolution::{Evolution, EvolutionError, ObsolescenceReason};
+use crate::file_util::{self, IoResultExt as _, PathError};
+use crate::fingerprint::{self, Fingerprint, FingerprintDepth};
 use crate::index::{ChangeIdIndex, Index, IndexStore, MutableIndex, ReadonlyIndex};
 use crate::local_backend::LocalBackend;
+use crate::matchers::EverythingMatcher;
+use crate::merge::{Merge, MergedTreeValue};
+use crate::merged_tree::MergedTree;
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::op_heads_store::{self, OpHeadResolutionError, OpHeadsStore};
 use crate::op_store::{
-    OpStore, OpStoreError, OperationId, RefTarget, RemoteRef, RemoteRefState, WorkspaceId,
+    ChangeMetadata, OpStore, OpStoreError, OperationId, RefTarget, RemoteRef, RemoteRefState,
+    WorkspaceId,
 };
 use crate::operation::Operation;
 use crate::refs::{
     diff_named_ref_targets, diff_named_remote_refs, merge_ref_targets, merge_remote_refs,
 };
-use crate::revset::{RevsetEvaluationError, RevsetExpression, RevsetIteratorExt};
-use crate::rewrite::{merge_commit_trees, CommitRewriter, DescendantRebaser, RebaseOptions};
+use crate::repo_format::{self, UpgradeError, UpgradeOptions, CURRENT_FORMAT_VERSION};
+use crate::repo_path::{RepoPath, RepoPathBuf};
+use crate::revset::{
+    RevsetEvaluationError, RevsetExpression, RevsetFilterPredicate, RevsetIteratorExt,
+};
+use crate::rewrite::{
+    merge_commit_trees, rebase_commit, CommitRewriter, DescendantRebaser, RebaseOptions,
+    StackAction, StackCommit, StackReport,
+};
+use crate::scratch_dir::STALE_SCRATCH_AGE;
 use crate::settings::{RepoSettings, UserSettings};
 use crate::signing::{SignInitError, Signer};
 use crate::simple_op_heads_store::SimpleOpHeadsStore;
 use crate::simple_op_store::SimpleOpStore;
-use crate::store::Store;
+use crate::store::{Store, StoreInitError};
+use crate::str_util::StringPattern;
 use crate::submodule_store::SubmoduleStore;
+use crate::time_util::DatePattern;
 use crate::transaction::Transaction;
+use crate::tree::{merge_trees, Tree};
 use crate::view::View;
-use crate::{backend, dag_walk, op_store, revset};
+use crate::{backend, dag_walk, diff, op_store, revset};
+
+/// The refs pointing at a commit, as returned by [`Repo::refs_pointing_at()`].
+/// Each list is sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Refs {
+    pub branches: Vec<String>,
+    /// `(name, remote_name)` pairs.
+    pub remote_branches: Vec<(String, String)>,
+    pub tags: Vec<String>,
+    pub git_refs: Vec<String>,
+}
+
+/// The outcome of testing a single commit in [`Repo::bisect()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BisectResult {
+    /// The commit does not have the behavior being searched for.
+    Good,
+    /// The commit has the behavior being searched for.
+    Bad,
+}
+
+/// The result of [`Repo::three_way_tree()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreeWayResult {
+    /// The merged tree, with the unresolved paths recorded as conflicts.
+    pub merged_tree_id: TreeId,
+    /// The paths that couldn't be resolved, in sorted order.
+    pub conflicts: Vec<(RepoPathBuf, MergedTreeValue)>,
+}
 
 pub trait Repo {
     fn store(&self) -> &Arc<Store>;
@@ -71,6 +119,253 @@ pub trait Repo {
 
     fn submodule_store(&self) -> &Arc<dyn SubmoduleStore>;
 
+    /// The settings the repo was loaded with.
+    fn settings(&self) -> &RepoSettings;
+
+    /// Returns the commits in `commits` none of whose parents are in
+    /// `commits`, i.e. the entry points of the set from below. This is the
+    /// counterpart of `Index::heads()`. The order of `commits` is preserved.
+    /// The commits must be present in the index, whose parent data is used
+    /// instead of reading the commits from the store.
+    fn roots_of(&self, commits: &[CommitId]) -> Vec<CommitId> {
+        let index = self.index();
+        let commit_set: HashSet<&CommitId> = commits.iter().collect();
+        commits
+            .iter()
+            .unique()
+            .filter(|id| {
+                !index
+                    .parent_ids(id)
+                    .iter()
+                    .any(|parent_id| commit_set.contains(parent_id))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the parent ids of the `commit_id` commit as recorded in the
+    /// index, without reading the commit from the store.
+    fn parent_ids(&self, commit_id: &CommitId) -> Vec<CommitId> {
+        self.index().parent_ids(commit_id)
+    }
+
+    /// Returns the visible heads in the index's topological order, most
+    /// recently indexed first. The commits aren't read from the store.
+    fn topo_sorted_heads(&self) -> Result<Vec<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let expression = RevsetExpression::commits(self.view().heads().iter().cloned().collect());
+        Ok(expression.evaluate_programmatic(self)?.iter().collect())
+    }
+
+    /// Returns the visible commits whose description matches `pattern`
+    /// anywhere, not just in the first line, newest first. Build the regex with
+    /// `RegexBuilder::case_insensitive()` (or use `(?i)`) to match
+    /// case-insensitively.
+    fn commits_matching_description(
+        &self,
+        pattern: &Regex,
+    ) -> Result<Vec<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let expression = RevsetExpression::filter(RevsetFilterPredicate::Description(
+            StringPattern::Regex(pattern.clone()),
+        ));
+        Ok(expression.evaluate_programmatic(self)?.iter().collect())
+    }
+
+    /// Returns the visible commits committed at or after `time`, newest first.
+    ///
+    /// Committer timestamps aren't recorded in the index and don't have to
+    /// increase along the history, so every visible commit has to be read.
+    fn commits_after(&self, time: &Timestamp) -> Result<Vec<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let expression = RevsetExpression::filter(RevsetFilterPredicate::CommitterDate(
+            DatePattern::AtOrAfter(time.timestamp),
+        ));
+        Ok(expression.evaluate_programmatic(self)?.iter().collect())
+    }
+
+    /// Returns the ancestors of `head` (inclusive) that changed the file at
+    /// `path`, newest first.
+    ///
+    /// With `follow_renames`, a commit that adds the file while deleting
+    /// another file with mostly the same lines is treated as a rename, and
+    /// older commits are matched against the old path. Only one path is
+    /// followed at a time, so a rename on one side of a merge applies to the
+    /// other side too.
+    fn path_history(
+        &self,
+        head: &CommitId,
+        path: &RepoPath,
+        follow_renames: bool,
+    ) -> Result<Vec<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let expression = RevsetExpression::commit(head.clone()).ancestors();
+        let mut path = path.to_owned();
+        let mut history = vec![];
+        for commit in expression
+            .evaluate_programmatic(self)?
+            .iter()
+            .commits(self.store())
+        {
+            let commit = commit.map_err(RevsetEvaluationError::StoreError)?;
+            let tree = commit.tree().map_err(RevsetEvaluationError::StoreError)?;
+            let parent_tree = commit
+                .parent_tree(self)
+                .map_err(RevsetEvaluationError::StoreError)?;
+            let value = tree
+                .path_value(&path)
+                .map_err(RevsetEvaluationError::StoreError)?;
+            let parent_value = parent_tree
+                .path_value(&path)
+                .map_err(RevsetEvaluationError::StoreError)?;
+            if value == parent_value {
+                continue;
+            }
+            history.push(commit.id().clone());
+            if follow_renames && parent_value.is_absent() {
+                if let Some(source) = find_rename_source(&parent_tree, &tree, &path, &value)
+                    .map_err(RevsetEvaluationError::StoreError)?
+                {
+                    path = source;
+                }
+            }
+        }
+        Ok(history)
+    }
+
+    /// Guesses the commit at the tip of the main line of development. That's
+    /// the target of the first of the `main`, `master`, and `trunk` local
+    /// branches that exists and isn't conflicted, then of those branches on
+    /// the `origin` remote. Without such a branch, it's the visible head with
+    /// the most ancestors (ties broken by commit id).
+    fn guess_trunk(&self) -> Result<Option<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        const TRUNK_NAMES: [&str; 3] = ["main", "master", "trunk"];
+        let view = self.view();
+        let local_targets = TRUNK_NAMES.iter().map(|name| view.get_local_branch(name));
+        let remote_targets = TRUNK_NAMES
+            .iter()
+            .map(|name| &view.get_remote_branch(name, "origin").target);
+        if let Some(id) = local_targets
+            .chain(remote_targets)
+            .find_map(|target| target.as_normal())
+        {
+            return Ok(Some(id.clone()));
+        }
+        let mut best_head: Option<(usize, &CommitId)> = None;
+        for head_id in view.heads() {
+            let num_ancestors = RevsetExpression::commit(head_id.clone())
+                .ancestors()
+                .evaluate_programmatic(self)?
+                .iter()
+                .count();
+            let is_better = best_head.map_or(true, |(best_num, best_id)| {
+                num_ancestors > best_num || (num_ancestors == best_num && head_id < best_id)
+            });
+            if is_better {
+                best_head = Some((num_ancestors, head_id));
+            }
+        }
+        Ok(best_head.map(|(_, id)| id.clone()))
+    }
+
+    /// Returns the lowest common descendants of `a` and `b` among the visible
+    /// commits, i.e. where their histories reconverge. That's the roots of the
+    /// set of commits descending from both (inclusive, so if `a` is an ancestor
+    /// of `b`, the result is `b`). Returns an empty list if they never
+    /// reconverge.
+    fn common_descendants(
+        &self,
+        a: &CommitId,
+        b: &CommitId,
+    ) -> Result<Vec<CommitId>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let expression = RevsetExpression::commit(a.clone())
+            .descendants()
+            .intersection(&RevsetExpression::commit(b.clone()).descendants())
+            .roots();
+        Ok(expression.evaluate_programmatic(self)?.iter().collect())
+    }
+
+    /// Finds the first bad commit in `good..bad`, calling `test` on the
+    /// commits to check.
+    ///
+    /// In each step, the candidate in the middle of the remaining range (in
+    /// topological order, so roughly by generation number) is tested. If it's
+    /// bad, the search continues among its ancestors; if it's good, its
+    /// ancestors are excluded. For ranges containing merges, the topological
+    /// midpoint doesn't necessarily split the range in half, but every step
+    /// still eliminates at least the tested commit.
+    ///
+    /// Returns a bad commit whose parents within the range are all good.
+    /// `test` is never called for `good` or `bad` themselves.
+    fn bisect(
+        &self,
+        good: &CommitId,
+        bad: &CommitId,
+        mut test: impl FnMut(&CommitId) -> BisectResult,
+    ) -> Result<CommitId, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let mut good_ids = vec![good.clone()];
+        let mut bad_id = bad.clone();
+        loop {
+            let bad_expression = RevsetExpression::commit(bad_id.clone());
+            let candidates = RevsetExpression::commits(good_ids.clone())
+                .range(&bad_expression)
+                .minus(&bad_expression)
+                .evaluate_programmatic(self)?
+                .iter()
+                .collect_vec();
+            let Some(midpoint) = candidates.get(candidates.len() / 2) else {
+                return Ok(bad_id);
+            };
+            match test(midpoint) {
+                BisectResult::Good => good_ids.push(midpoint.clone()),
+                BisectResult::Bad => bad_id = midpoint.clone(),
+            }
+        }
+    }
+
+    /// Returns the commits previously checked out in the workspace, newest
+    /// first, like `View::checkout_history()`, but with the commits that have
+    /// since been rewritten replaced by their successors (the first one if the
+    /// change is divergent). Consecutive repeats this creates are removed.
+    fn checkout_history(&self, workspace_id: &WorkspaceId) -> Result<Vec<CommitId>, EvolutionError>
+    where
+        Self: Sized,
+    {
+        let recorded = self.view().checkout_history(workspace_id);
+        if recorded.is_empty() {
+            return Ok(vec![]);
+        }
+        let evolution = Evolution::new(self)?;
+        let mut history = vec![];
+        for commit_id in recorded {
+            let commit_id = match evolution.obsolescence_reason(commit_id)? {
+                Some(ObsolescenceReason::Rewritten { mut successors }) => successors.swap_remove(0),
+                _ => commit_id.clone(),
+            };
+            if history.last() != Some(&commit_id) {
+                history.push(commit_id);
+            }
+        }
+        Ok(history)
+    }
+
     fn resolve_change_id(&self, change_id: &ChangeId) -> Option<Vec<CommitId>> {
         // Replace this if we added more efficient lookup method.
         let prefix = HexPrefix::from_bytes(change_id.as_bytes());
@@ -81,11 +376,195 @@ pub trait Repo {
         }
     }
 
+    /// Returns the other visible commits with the same change id as the given
+    /// commit. For a divergent commit, that's the rest of the divergent set.
+    /// The commit itself may be hidden.
+    fn change_siblings(&self, commit_id: &CommitId) -> BackendResult<Vec<CommitId>> {
+        let commit = self.store().get_commit(commit_id)?;
+        let siblings = self
+            .resolve_change_id(commit.change_id())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| id != commit_id)
+            .collect();
+        Ok(siblings)
+    }
+
+    /// Returns groups of visible commits that have the same tree, description,
+    /// and parents but different change ids, e.g. after cherry-picking a commit
+    /// onto its own parent. Groups and the commits in them are ordered newest
+    /// first.
+    fn find_duplicates(&self) -> Result<Vec<Vec<CommitId>>, RevsetEvaluationError>
+    where
+        Self: Sized,
+    {
+        let mut groups: Vec<Vec<Commit>> = vec![];
+        let mut group_indices: HashMap<(Merge<TreeId>, String, Vec<CommitId>), usize> =
+            HashMap::new();
+        for commit in RevsetExpression::visible_heads()
+            .ancestors()
+            .evaluate_programmatic(self)?
+            .iter()
+            .commits(self.store())
+        {
+            let commit = commit.map_err(RevsetEvaluationError::StoreError)?;
+            let key = (
+                commit.tree_id().to_merge(),
+                commit.description().to_owned(),
+                commit.parent_ids().to_vec(),
+            );
+            match group_indices.entry(key) {
+                Entry::Occupied(entry) => groups[*entry.get()].push(commit),
+                Entry::Vacant(entry) => {
+                    entry.insert(groups.len());
+                    groups.push(vec![commit]);
+                }
+            }
+        }
+        let duplicates = groups
+            .into_iter()
+            .filter(|commits| {
+                commits
+                    .iter()
+                    .map(|commit| commit.change_id())
+                    .unique()
+                    .count()
+                    > 1
+            })
+            .map(|commits| commits.iter().map(|commit| commit.id().clone()).collect())
+            .collect();
+        Ok(duplicates)
+    }
+
+    /// Returns the names of the refs whose target adds `commit_id`, e.g. for
+    /// decorating commits in a log. Conflicted refs are included if any of
+    /// their sides points at the commit.
+    fn refs_pointing_at(&self, commit_id: &CommitId) -> Refs {
+        let view = self.view();
+        let points_at = |target: &RefTarget| target.added_ids().contains(commit_id);
+        Refs {
+            branches: view
+                .local_branches_for_commit(commit_id)
+                .map(|(name, _)| name.to_owned())
+                .collect(),
+            remote_branches: view
+                .all_remote_branches()
+                .filter(|(_, remote_ref)| points_at(&remote_ref.target))
+                .map(|((name, remote_name), _)| (name.to_owned(), remote_name.to_owned()))
+                .collect(),
+            tags: view
+                .tags()
+                .iter()
+                .filter(|(_, target)| points_at(target))
+                .map(|(name, _)| name.clone())
+                .collect(),
+            git_refs: view
+                .git_refs()
+                .iter()
+                .filter(|(_, target)| points_at(target))
+                .map(|(name, _)| name.clone())
+                .collect(),
+        }
+    }
+
+    /// Returns the working-copy commit of the workspace, i.e. the commit its
+    /// working copy amends on each snapshot, or `None` if there's no such
+    /// workspace.
+    fn working_copy_commit(&self, workspace_id: &WorkspaceId) -> BackendResult<Option<Commit>> {
+        self.view()
+            .get_wc_commit_id(workspace_id)
+            .map(|id| self.store().get_commit(id))
+            .transpose()
+    }
+
+    /// Merges the changes from `base` to `left` and from `base` to `right`
+    /// without creating a commit, like rebasing does for trees in the legacy
+    /// format. The merged tree and its conflicts are written to the store.
+    fn three_way_tree(
+        &self,
+        base: &Tree,
+        left: &Tree,
+        right: &Tree,
+    ) -> BackendResult<ThreeWayResult> {
+        let merged_tree = merge_trees(left, base, right)?;
+        let conflicts = merged_tree
+            .conflicts()
+            .into_iter()
+            .map(|(path, conflict_id)| {
+                let conflict = self.store().read_conflict(&path, &conflict_id)?;
+                Ok::<_, BackendError>((path, conflict))
+            })
+            .try_collect()?;
+        Ok(ThreeWayResult {
+            merged_tree_id: merged_tree.id().clone(),
+            conflicts,
+        })
+    }
+
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>>;
 
     fn shortest_unique_change_id_prefix_len(&self, target_id_bytes: &ChangeId) -> usize;
 }
 
+/// Finds the file deleted between `parent_tree` and `tree` that is most likely
+/// to have been renamed to `path`. At least half of the lines have to be
+/// unchanged.
+fn find_rename_source(
+    parent_tree: &MergedTree,
+    tree: &MergedTree,
+    path: &RepoPath,
+    value: &MergedTreeValue,
+) -> BackendResult<Option<RepoPathBuf>> {
+    let store = tree.store();
+    let read_file = |path: &RepoPath, id: &FileId| -> BackendResult<Vec<u8>> {
+        let mut contents = vec![];
+        store
+            .read_file(path, id)?
+            .read_to_end(&mut contents)
+            .map_err(|err| BackendError::ReadFile {
+                path: path.to_owned(),
+                id: id.clone(),
+                source: err.into(),
+            })?;
+        Ok(contents)
+    };
+    let Some(Some(TreeValue::File { id: new_id, .. })) = value.as_resolved() else {
+        return Ok(None);
+    };
+    let new_contents = read_file(path, new_id)?;
+    let new_lines = new_contents.split_inclusive(|b| *b == b'\n').count();
+    let mut best: Option<(usize, RepoPathBuf)> = None;
+    for (old_path, values) in parent_tree.diff(tree, &EverythingMatcher) {
+        let (before, after) = values?;
+        let Some(Some(TreeValue::File { id: old_id, .. })) = before.as_resolved() else {
+            continue;
+        };
+        if !after.is_absent() {
+            continue;
+        }
+        if old_id == new_id {
+            return Ok(Some(old_path));
+        }
+        let old_contents = read_file(&old_path, old_id)?;
+        let old_lines = old_contents.split_inclusive(|b| *b == b'\n').count();
+        let (removed, _) = diff::count_changed_lines(&old_contents, &new_contents);
+        let unchanged = old_lines - removed;
+        if unchanged * 2 >= old_lines.max(new_lines).max(1)
+            && best.as_ref().map_or(true, |(count, _)| unchanged > *count)
+        {
+            best = Some((unchanged, old_path));
+        }
+    }
+    Ok(best.map(|(_, old_path)| old_path))
+}
+
+/// The state of a repo at an operation.
+///
+/// A `ReadonlyRepo` is immutable and `Send + Sync`, so one `Arc<ReadonlyRepo>`
+/// can be shared by many threads, e.g. to compute diffs or evaluate revsets in
+/// parallel. The same goes for its [`Store`], index, and [`View`]. The
+/// backends are required to be thread-safe by their traits. To make changes,
+/// each thread starts its own transaction.
 pub struct ReadonlyRepo {
     repo_path: PathBuf,
     store: Arc<Store>,
@@ -99,6 +578,9 @@ pub struct ReadonlyRepo {
     change_id_index: OnceCell<Box<dyn ChangeIdIndex>>,
     // TODO: This should eventually become part of the index and not be stored fully in memory.
     view: View,
+    stale_heads: OnceCell<Vec<CommitId>>,
+    evolution_state: OnceCell<EvolutionState>,
+    branch_last_moved_op_ids: OnceCell<HashMap<String, OperationId>>,
 }
 
 impl Debug for ReadonlyRepo {
@@ -115,6 +597,8 @@ pub enum RepoInitError {
     #[error(transparent)]
     Backend(#[from] BackendInitError),
     #[error(transparent)]
+    Config(#[from] config::ConfigError),
+    #[error(transparent)]
     Path(#[from] PathError),
 }
 
@@ -156,14 +640,25 @@ impl ReadonlyRepo {
         let backend = backend_initializer(user_settings, &store_path)?;
         let backend_path = store_path.join("type");
         fs::write(&backend_path, backend.name()).context(&backend_path)?;
-        let store = Store::new(backend, signer, user_settings.use_tree_conflict_format());
-        let repo_settings = user_settings.with_repo(&repo_path).unwrap();
+        let repo_id = hex::encode(rand::random::<[u8; 16]>());
+        let store_repo_id_path = store_path.join(REPO_ID_FILE);
+        fs::write(&store_repo_id_path, &repo_id).context(&store_repo_id_path)?;
+        let store = Store::new(
+            backend,
+            signer,
+            user_settings.use_tree_conflict_format(),
+            user_settings.merge_drivers()?,
+            user_settings.content_types()?,
+        );
+        let repo_settings = user_settings.with_repo(&repo_path)?;
 
         let op_store_path = repo_path.join("op_store");
         fs::create_dir(&op_store_path).context(&op_store_path)?;
         let op_store = op_store_initializer(user_settings, &op_store_path);
         let op_store_type_path = op_store_path.join("type");
         fs::write(&op_store_type_path, op_store.name()).context(&op_store_type_path)?;
+        let op_store_repo_id_path = op_store_path.join(REPO_ID_FILE);
+        fs::write(&op_store_repo_id_path, &repo_id).context(&op_store_repo_id_path)?;
         let op_store: Arc<dyn OpStore> = Arc::from(op_store);
 
         let op_heads_path = repo_path.join("op_heads");
@@ -189,6 +684,8 @@ impl ReadonlyRepo {
             .context(&submodule_store_type_path)?;
         let submodule_store = Arc::from(submodule_store);
 
+        repo_format::write_format_version(&repo_path, CURRENT_FORMAT_VERSION)?;
+
         let root_operation_data = op_store
             .read_operation(op_store.root_operation_id())
             .expect("failed to read root operation");
@@ -208,7 +705,10 @@ impl ReadonlyRepo {
             index_store,
             index: OnceCell::new(),
             change_id_index: OnceCell::new(),
+            evolution_state: OnceCell::new(),
+            branch_last_moved_op_ids: OnceCell::new(),
             view: root_view,
+            stale_heads: OnceCell::new(),
             submodule_store,
         });
         let mut tx = repo.start_transaction(user_settings);
@@ -218,6 +718,76 @@ impl ReadonlyRepo {
         Ok(tx.commit("initialize repo"))
     }
 
+    /// Copies this repo to a new repo directory at `dest_path`, and loads the
+    /// copy at the same operation as this repo.
+    ///
+    /// The op heads store is locked while copying, so no operations are merged
+    /// concurrently. The op heads are copied first. Objects are always written
+    /// before anything refers to them, so the copy is consistent even if other
+    /// processes keep writing to this repo. Temporary files and lock files
+    /// aren't copied. A Git repo referred to by a relative path from outside
+    /// the store, as in a colocated workspace, is copied into the store of
+    /// the copy.
+    pub fn clone_to(
+        &self,
+        user_settings: &UserSettings,
+        dest_path: &Path,
+        options: &RepoCloneOptions,
+        store_factories: &StoreFactories,
+    ) -> Result<Arc<ReadonlyRepo>, RepoCloneError> {
+        match fs::create_dir(dest_path).context(dest_path) {
+            Ok(()) => {}
+            Err(err) if err.error.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(RepoCloneError::DestinationExists(dest_path.to_owned()));
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let lock = self.op_heads_store.lock();
+        let mut names: Vec<_> = fs::read_dir(&self.repo_path)
+            .context(&self.repo_path)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .try_collect()
+            .context(&self.repo_path)?;
+        names.sort_by_key(|name| name != "op_heads");
+        for name in names {
+            let hard_link = options.share_store && name == "store";
+            file_util::copy_recursive(
+                &self.repo_path.join(&name),
+                &dest_path.join(&name),
+                hard_link,
+            )?;
+        }
+        // A relative path to a Git repo outside the store (such as the one of
+        // a colocated workspace) would be resolved relative to the copy, so
+        // copy the Git repo into the store of the copy, like an internal one.
+        let src_store_path = self.repo_path.join("store");
+        let dest_store_path = dest_path.join("store");
+        let git_target_path = dest_store_path.join("git_target");
+        if let Ok(git_target) = fs::read_to_string(&git_target_path) {
+            let git_repo_path = src_store_path.join(&git_target);
+            if Path::new(&git_target).is_relative()
+                && !file_util::normalize_path(&git_repo_path)
+                    .starts_with(file_util::normalize_path(&src_store_path))
+            {
+                file_util::copy_recursive(
+                    &git_repo_path,
+                    &dest_store_path.join("git"),
+                    options.share_store,
+                )?;
+                fs::write(&git_target_path, "git").context(&git_target_path)?;
+            }
+        }
+        drop(lock);
+
+        let loader = RepoLoader::init(user_settings, dest_path, store_factories)?;
+        let operation = Operation::new(
+            loader.op_store().clone(),
+            self.op_id().clone(),
+            self.operation.store_operation().clone(),
+        );
+        Ok(loader.load_at(&operation)?)
+    }
+
     pub fn loader(&self) -> RepoLoader {
         RepoLoader {
             repo_path: self.repo_path.clone(),
@@ -246,14 +816,74 @@ impl ReadonlyRepo {
         &self.view
     }
 
+    /// Heads of the loaded operation's view which are missing from the store,
+    /// e.g. because the store was restored from an older backup. They're
+    /// looked up on the first call, and are still part of the `view()`.
+    pub fn stale_heads(&self) -> BackendResult<&[CommitId]> {
+        let stale_heads = self.stale_heads.get_or_try_init(|| {
+            let mut stale_heads = vec![];
+            for id in self.view.heads() {
+                match self.store.get_commit(id) {
+                    Ok(_) => {}
+                    Err(BackendError::ObjectNotFound { .. }) => stale_heads.push(id.clone()),
+                    Err(err) => return Err(err),
+                }
+            }
+            stale_heads.sort();
+            Ok(stale_heads)
+        })?;
+        Ok(stale_heads)
+    }
+
+    /// Returns the metadata of local branch `name`, or `None` if there's no
+    /// such branch. Where the branches last moved is found on the first call,
+    /// by walking the operation log back once for all of them.
+    pub fn branch_metadata(
+        &self,
+        name: &str,
+    ) -> Result<Option<BranchMetadata>, BranchMetadataError> {
+        let target = self.view.get_local_branch(name);
+        if target.is_absent() {
+            return Ok(None);
+        }
+        let last_moved_op_id = self.branch_last_moved_op_ids()?[name].clone();
+        let is_obsolete = is_target_obsolete(&Evolution::new(self)?, target)?;
+        Ok(Some(BranchMetadata {
+            last_moved_op_id: Some(last_moved_op_id),
+            is_obsolete,
+        }))
+    }
+
+    /// The operation that last set the target of each local branch, computed
+    /// on the first call.
+    fn branch_last_moved_op_ids(&self) -> Result<&HashMap<String, OperationId>, OpStoreError> {
+        self.branch_last_moved_op_ids
+            .get_or_try_init(|| last_moved_op_ids(self.operation.clone(), &self.view))
+    }
+
+    /// Computes a fingerprint of the state of the repo at this operation, which
+    /// is equal for repos in the same state. See [`crate::fingerprint`].
+    pub fn state_fingerprint(
+        &self,
+        depth: FingerprintDepth,
+    ) -> Result<Fingerprint, RevsetEvaluationError> {
+        fingerprint::state_fingerprint(self, depth)
+    }
+
     pub fn readonly_index(&self) -> &dyn ReadonlyIndex {
         self.index
             .get_or_init(|| {
                 // TODO: somehow propagate error, but it's weird if all callers
                 // had Result<T, IndexReadError> signature.
-                self.index_store
-                    .get_index_at_op(&self.operation, &self.store)
-                    .unwrap()
+                if self.store.is_read_only() {
+                    self.index_store
+                        .get_index_at_op_in_memory(&self.operation, &self.store)
+                        .unwrap()
+                } else {
+                    self.index_store
+                        .get_index_at_op(&self.operation, &self.store)
+                        .unwrap()
+                }
             })
             .deref()
     }
@@ -267,6 +897,28 @@ impl ReadonlyRepo {
             .as_ref()
     }
 
+    /// The evolution state `MutableRepo::evolution_delta()` compares against,
+    /// computed on the first call.
+    fn evolution_state(&self) -> Result<&EvolutionState, EvolutionError> {
+        self.evolution_state.get_or_try_init(|| {
+            let evolution = Evolution::new(self)?;
+            let mut obsolete_ids = HashMap::new();
+            for (change_id, commit_ids) in evolution.divergent_changes() {
+                let mut ids = vec![];
+                for commit_id in commit_ids {
+                    if evolution.is_obsolete(commit_id)? {
+                        ids.push(commit_id.clone());
+                    }
+                }
+                obsolete_ids.insert(change_id.clone(), ids);
+            }
+            Ok(EvolutionState {
+                obsolete_ids,
+                num_orphans: evolution.orphan_ids()?.len(),
+            })
+        })
+    }
+
     pub fn op_heads_store(&self) -> &Arc<dyn OpHeadsStore> {
         &self.op_heads_store
     }
@@ -275,16 +927,29 @@ impl ReadonlyRepo {
         &self.index_store
     }
 
-    pub fn settings(&self) -> &RepoSettings {
-        &self.settings
-    }
-
+    /// Starts a transaction on top of this repo.
+    ///
+    /// Panics if the repo was loaded read-only. Use `try_start_transaction()`
+    /// if that's possible.
     pub fn start_transaction(
         self: &Arc<ReadonlyRepo>,
         user_settings: &UserSettings,
     ) -> Transaction {
+        self.try_start_transaction(user_settings)
+            .expect("cannot start a transaction on a read-only repo")
+    }
+
+    /// Like `start_transaction()`, but fails with `RepoLoaderError::ReadOnly`
+    /// if the repo was loaded read-only.
+    pub fn try_start_transaction(
+        self: &Arc<ReadonlyRepo>,
+        user_settings: &UserSettings,
+    ) -> Result<Transaction, RepoLoaderError> {
+        if self.store.is_read_only() {
+            return Err(RepoLoaderError::ReadOnly);
+        }
         let mut_repo = MutableRepo::new(self.clone(), self.readonly_index(), &self.view);
-        Transaction::new(mut_repo, user_settings)
+        Ok(Transaction::new(mut_repo, user_settings))
     }
 
     pub fn reload_at_head(
@@ -321,6 +986,10 @@ impl Repo for ReadonlyRepo {
         &self.submodule_store
     }
 
+    fn settings(&self) -> &RepoSettings {
+        &self.settings
+    }
+
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>> {
         self.change_id_index().resolve_prefix(prefix)
     }
@@ -341,6 +1010,8 @@ pub type SubmoduleStoreInitializer<'a> =
 
 type BackendFactory =
     Box<dyn Fn(&UserSettings, &Path) -> Result<Box<dyn Backend>, BackendLoadError>>;
+type BackendInitFactory =
+    Box<dyn Fn(&UserSettings, &Path) -> Result<Box<dyn Backend>, BackendInitError>>;
 type OpStoreFactory = Box<dyn Fn(&UserSettings, &Path) -> Box<dyn OpStore>>;
 type OpHeadsStoreFactory = Box<dyn Fn(&UserSettings, &Path) -> Box<dyn OpHeadsStore>>;
 type IndexStoreFactory =
@@ -362,6 +1033,8 @@ pub fn merge_factories_map<F>(base: &mut HashMap<String, F>, ext: HashMap<String
 
 pub struct StoreFactories {
     backend_factories: HashMap<String, BackendFactory>,
+    /// Backend initializers by [`Store::init()`] URL scheme.
+    backend_init_factories: HashMap<String, BackendInitFactory>,
     op_store_factories: HashMap<String, OpStoreFactory>,
     op_heads_store_factories: HashMap<String, OpHeadsStoreFactory>,
     index_store_factories: HashMap<String, IndexStoreFactory>,
@@ -375,7 +1048,11 @@ impl Default for StoreFactories {
         // Backends
         factories.add_backend(
             LocalBackend::name(),
-            Box::new(|_settings, store_path| Ok(Box::new(LocalBackend::load(store_path)))),
+            Box::new(|settings, store_path| {
+                Ok(Box::new(LocalBackend::load_with_settings(
+                    settings, store_path,
+                )?))
+            }),
         );
         #[cfg(feature = "git")]
         factories.add_backend(
@@ -386,6 +1063,23 @@ impl Default for StoreFactories {
                 )?))
             }),
         );
+        factories.add_backend_initializer(
+            "local",
+            Box::new(|settings, store_path| {
+                Ok(Box::new(LocalBackend::init_with_settings(
+                    settings, store_path,
+                )?))
+            }),
+        );
+        #[cfg(feature = "git")]
+        factories.add_backend_initializer(
+            "git",
+            Box::new(|settings, store_path| {
+                Ok(Box::new(crate::git_backend::GitBackend::init_internal(
+                    settings, store_path,
+                )?))
+            }),
+        );
         #[cfg(feature = "testing")]
         factories.add_backend(
             crate::secret_backend::SecretBackend::name(),
@@ -440,12 +1134,33 @@ pub enum StoreLoadError {
     Backend(#[from] BackendLoadError),
     #[error(transparent)]
     Signing(#[from] SignInitError),
+    #[error(transparent)]
+    Config(#[from] config::ConfigError),
+    #[error("Failed to read repository id")]
+    ReadRepoId(#[source] PathError),
+    #[error(
+        "The store (repository id {store_repo_id}) and the operation log (repository id \
+         {op_store_repo_id}) belong to different repositories"
+    )]
+    StoreViewMismatch {
+        store_repo_id: String,
+        op_store_repo_id: String,
+    },
+    #[error("Failed to read the repo format version")]
+    ReadFormat(#[source] UpgradeError),
+    #[error("Failed to upgrade the repo format")]
+    Upgrade(#[source] UpgradeError),
+    #[error("Unsupported repo format version {version} (the newest supported is {supported})")]
+    UnsupportedFormat { version: u32, supported: u32 },
+    #[error("The repo is in format version {version} and must be upgraded to version {current}")]
+    UpgradeRequired { version: u32, current: u32 },
 }
 
 impl StoreFactories {
     pub fn empty() -> Self {
         StoreFactories {
             backend_factories: HashMap::new(),
+            backend_init_factories: HashMap::new(),
             op_store_factories: HashMap::new(),
             op_heads_store_factories: HashMap::new(),
             index_store_factories: HashMap::new(),
@@ -456,6 +1171,7 @@ impl StoreFactories {
     pub fn merge(&mut self, ext: StoreFactories) {
         let StoreFactories {
             backend_factories,
+            backend_init_factories,
             op_store_factories,
             op_heads_store_factories,
             index_store_factories,
@@ -463,6 +1179,7 @@ impl StoreFactories {
         } = ext;
 
         merge_factories_map(&mut self.backend_factories, backend_factories);
+        merge_factories_map(&mut self.backend_init_factories, backend_init_factories);
         merge_factories_map(&mut self.op_store_factories, op_store_factories);
         merge_factories_map(&mut self.op_heads_store_factories, op_heads_store_factories);
         merge_factories_map(&mut self.index_store_factories, index_store_factories);
@@ -491,6 +1208,28 @@ impl StoreFactories {
         Ok(backend_factory(settings, store_path)?)
     }
 
+    /// Registers the function initializing backends for [`Store::init()`]
+    /// URLs with the given scheme.
+    pub fn add_backend_initializer(&mut self, scheme: &str, factory: BackendInitFactory) {
+        self.backend_init_factories
+            .insert(scheme.to_string(), factory);
+    }
+
+    /// Initializes a backend at `store_path` with the function registered for
+    /// the URL `scheme`.
+    pub fn init_backend(
+        &self,
+        settings: &UserSettings,
+        scheme: &str,
+        store_path: &Path,
+    ) -> Result<Box<dyn Backend>, StoreInitError> {
+        let backend_init_factory = self
+            .backend_init_factories
+            .get(scheme)
+            .ok_or_else(|| StoreInitError::UnsupportedScheme(scheme.to_owned()))?;
+        Ok(backend_init_factory(settings, store_path)?)
+    }
+
     pub fn add_op_store(&mut self, name: &str, factory: OpStoreFactory) {
         self.op_store_factories.insert(name.to_string(), factory);
     }
@@ -584,6 +1323,27 @@ pub fn read_store_type(
         .map_err(|source| StoreLoadError::ReadError { store, source })
 }
 
+/// Options for [`ReadonlyRepo::clone_to()`].
+#[derive(Clone, Debug, Default)]
+pub struct RepoCloneOptions {
+    /// Hard-link the files of the commit store instead of copying them. The
+    /// objects are immutable, so they can be shared. Objects written later to
+    /// either repo aren't visible to the other.
+    pub share_store: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum RepoCloneError {
+    #[error("The destination repo ({0}) already exists")]
+    DestinationExists(PathBuf),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    StoreLoad(#[from] StoreLoadError),
+    #[error(transparent)]
+    Loader(#[from] RepoLoaderError),
+}
+
 #[derive(Debug, Error)]
 pub enum RepoLoaderError {
     #[error(transparent)]
@@ -592,6 +1352,36 @@ pub enum RepoLoaderError {
     OpHeadResolution(#[from] OpHeadResolutionError),
     #[error(transparent)]
     OpStore(#[from] OpStoreError),
+    #[error("The repo is read-only")]
+    ReadOnly,
+}
+
+/// Name of the file with the repository id, which is written to both the
+/// store and the op store directories.
+pub(crate) const REPO_ID_FILE: &str = "repo_id";
+
+/// Checks that the store and the op store were initialized for the same repo.
+/// Repos initialized without the ids aren't checked.
+fn check_repo_ids(repo_path: &Path) -> Result<(), StoreLoadError> {
+    let read_repo_id = |dir: &str| {
+        let path = repo_path.join(dir).join(REPO_ID_FILE);
+        match fs::read_to_string(&path) {
+            Ok(id) => Ok(Some(id)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(StoreLoadError::ReadRepoId(PathError { path, error: err })),
+        }
+    };
+    if let (Some(store_repo_id), Some(op_store_repo_id)) =
+        (read_repo_id("store")?, read_repo_id("op_store")?)
+    {
+        if store_repo_id != op_store_repo_id {
+            return Err(StoreLoadError::StoreViewMismatch {
+                store_repo_id,
+                op_store_repo_id,
+            });
+        }
+    }
+    Ok(())
 }
 
 #[derive(Clone)]
@@ -606,17 +1396,88 @@ pub struct RepoLoader {
 }
 
 impl RepoLoader {
+    /// Upgrades a repo in an older format if
+    /// `repo_format::can_upgrade_automatically()`, and fails with
+    /// `StoreLoadError::UpgradeRequired` otherwise. See
+    /// `repo_format::upgrade()`.
     pub fn init(
         user_settings: &UserSettings,
         repo_path: &Path,
         store_factories: &StoreFactories,
     ) -> Result<Self, StoreLoadError> {
-        let store = Store::new(
+        Self::init_with_mode(user_settings, repo_path, store_factories, false)
+    }
+
+    /// Like `init()`, but repos loaded by the returned loader never write to
+    /// the repo directory. Their store refuses writes, a missing index is
+    /// built in memory only, and `ReadonlyRepo::try_start_transaction()` fails
+    /// with `RepoLoaderError::ReadOnly`. Loading at head fails the same way
+    /// if there are concurrent operations, since merging them would write a
+    /// new operation. Unlike `init()`, this also loads repos in an older
+    /// format.
+    pub fn init_read_only(
+        user_settings: &UserSettings,
+        repo_path: &Path,
+        store_factories: &StoreFactories,
+    ) -> Result<Self, StoreLoadError> {
+        Self::init_with_mode(user_settings, repo_path, store_factories, true)
+    }
+
+    fn init_with_mode(
+        user_settings: &UserSettings,
+        repo_path: &Path,
+        store_factories: &StoreFactories,
+        read_only: bool,
+    ) -> Result<Self, StoreLoadError> {
+        // A missing format file means the repo predates format versions, so
+        // make sure there's a repo at all before asking for an upgrade
+        read_store_type("commit", repo_path.join("store").join("type"))?;
+        let format_version =
+            repo_format::read_format_version(repo_path).map_err(StoreLoadError::ReadFormat)?;
+        if format_version > CURRENT_FORMAT_VERSION {
+            return Err(StoreLoadError::UnsupportedFormat {
+                version: format_version,
+                supported: CURRENT_FORMAT_VERSION,
+            });
+        }
+        // Older formats can be read, but must be upgraded before writing
+        if format_version < CURRENT_FORMAT_VERSION && !read_only {
+            if !repo_format::can_upgrade_automatically(format_version) {
+                return Err(StoreLoadError::UpgradeRequired {
+                    version: format_version,
+                    current: CURRENT_FORMAT_VERSION,
+                });
+            }
+            repo_format::upgrade(
+                repo_path,
+                CURRENT_FORMAT_VERSION,
+                &UpgradeOptions::default(),
+            )
+            .map_err(StoreLoadError::Upgrade)?;
+        }
+        check_repo_ids(repo_path)?;
+        let new_store = if read_only {
+            Store::new_read_only
+        } else {
+            Store::new
+        };
+        let store = new_store(
             store_factories.load_backend(user_settings, &repo_path.join("store"))?,
             Signer::from_settings(user_settings)?,
             user_settings.use_tree_conflict_format(),
+            user_settings.merge_drivers()?,
+            user_settings.content_types()?,
         );
-        let repo_settings = user_settings.with_repo(repo_path).unwrap();
+        let repo_settings = user_settings.with_repo(repo_path)?;
+        if !read_only {
+            // Scratch files are normally removed when dropped, so stale ones
+            // were left behind by killed processes. Failing to remove them
+            // shouldn't prevent loading the repo.
+            repo_settings
+                .scratch_dir()
+                .remove_stale(STALE_SCRATCH_AGE)
+                .ok();
+        }
         let op_store =
             Arc::from(store_factories.load_op_store(user_settings, &repo_path.join("op_store"))?);
         let op_heads_store = Arc::from(
@@ -663,19 +1524,34 @@ impl RepoLoader {
         &self,
         user_settings: &UserSettings,
     ) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
+        if self.store.is_read_only() {
+            // Resolving multiple heads would take a lock and write an operation
+            let op_id = match self.op_heads_store.get_op_heads().as_slice() {
+                [] => return Err(OpHeadResolutionError::NoHeads.into()),
+                [op_id] => op_id.clone(),
+                _ => return Err(RepoLoaderError::ReadOnly),
+            };
+            let op = Operation::new(
+                self.op_store.clone(),
+                op_id.clone(),
+                self.op_store.read_operation(&op_id)?,
+            );
+            let view = op.view()?;
+            return self._finish_load(op, view);
+        }
         let op = op_heads_store::resolve_op_heads(
             self.op_heads_store.as_ref(),
             &self.op_store,
             |op_heads| self._resolve_op_heads(op_heads, user_settings),
         )?;
         let view = op.view()?;
-        Ok(self._finish_load(op, view))
+        self._finish_load(op, view)
     }
 
     #[instrument(skip(self))]
     pub fn load_at(&self, op: &Operation) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
         let view = op.view()?;
-        Ok(self._finish_load(op.clone(), view))
+        self._finish_load(op.clone(), view)
     }
 
     pub fn create_from(
@@ -695,7 +1571,10 @@ impl RepoLoader {
             submodule_store: self.submodule_store.clone(),
             index: OnceCell::with_value(index),
             change_id_index: OnceCell::new(),
+            evolution_state: OnceCell::new(),
+            branch_last_moved_op_ids: OnceCell::new(),
             view,
+            stale_heads: OnceCell::new(),
         };
         Arc::new(repo)
     }
@@ -717,7 +1596,11 @@ impl RepoLoader {
         Ok(merged_repo.operation().clone())
     }
 
-    fn _finish_load(&self, operation: Operation, view: View) -> Arc<ReadonlyRepo> {
+    fn _finish_load(
+        &self,
+        operation: Operation,
+        view: View,
+    ) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
         let repo = ReadonlyRepo {
             repo_path: self.repo_path.clone(),
             store: self.store.clone(),
@@ -729,12 +1612,131 @@ impl RepoLoader {
             submodule_store: self.submodule_store.clone(),
             index: OnceCell::new(),
             change_id_index: OnceCell::new(),
+            evolution_state: OnceCell::new(),
+            branch_last_moved_op_ids: OnceCell::new(),
             view,
+            stale_heads: OnceCell::new(),
         };
-        Arc::new(repo)
+        Ok(Arc::new(repo))
+    }
+}
+
+/// Options for [`MutableRepo::cleanup_merged_branches()`].
+#[derive(Clone, Debug, Default)]
+pub struct CleanupMergedBranchesOptions {
+    /// Only report the merged branches without deleting them.
+    pub dry_run: bool,
+    /// Branches matching any of these patterns are never deleted.
+    pub immutable_branches: Vec<StringPattern>,
+}
+
+/// What [`MutableRepo::evolve()`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvolveSummary {
+    /// Number of orphaned commits that were rebased.
+    pub resolved_orphans: usize,
+    /// Number of divergent changes where all but one of the commits were
+    /// obsoleted by a rewrite of them.
+    pub resolved_divergences: usize,
+    /// Number of changes that are still divergent and need to be resolved by
+    /// the user. Changes whose divergence was accepted aren't counted.
+    pub remaining_divergences: usize,
+}
+
+/// The net change in evolution state during a transaction, as returned by
+/// [`MutableRepo::evolution_delta()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvolutionDelta {
+    /// Change in the number of visible commits that need rebasing because an
+    /// ancestor was rewritten or abandoned.
+    pub orphans: isize,
+    /// Change in the number of changes with more than one visible commit.
+    pub divergent_changes: isize,
+}
+
+/// Metadata of a local branch, as returned by
+/// [`ReadonlyRepo::branch_metadata()`] and [`MutableRepo::branch_metadata()`].
+/// A branch renamed by [`MutableRepo::rename_branch()`] keeps the metadata
+/// of its old name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchMetadata {
+    /// The operation that last set the branch's target. `None` if it was set
+    /// in the current transaction.
+    pub last_moved_op_id: Option<OperationId>,
+    /// Whether a commit the branch points to has been rewritten or abandoned.
+    pub is_obsolete: bool,
+}
+
+/// The obsolete visible commits and the number of orphans of a repo.
+#[derive(Debug)]
+struct EvolutionState {
+    /// The obsolete visible commits of each divergent change.
+    obsolete_ids: HashMap<ChangeId, Vec<CommitId>>,
+    num_orphans: usize,
+}
+
+/// The hidden commits written by [`MutableRepo::rebase_range()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RebaseOutcome {
+    /// The rebased commits, parents first.
+    pub commits: Vec<RebasedCommit>,
+}
+
+/// A commit rebased by [`MutableRepo::rebase_range()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RebasedCommit {
+    /// The original commit.
+    pub old_id: CommitId,
+    /// The rebased commit. Same as `old_id` if the parents didn't change.
+    pub new_id: CommitId,
+    /// The paths that have conflicts in the rebased commit.
+    pub conflicted_paths: Vec<RepoPathBuf>,
+}
+
+impl RebaseOutcome {
+    /// Whether any of the rebased commits has conflicts.
+    pub fn has_conflicts(&self) -> bool {
+        self.commits
+            .iter()
+            .any(|commit| !commit.conflicted_paths.is_empty())
+    }
+
+    /// Makes the rebased commits visible and records them as rewritten from
+    /// the originals. Branches, working copies, and descendants outside the
+    /// range follow on the next `rebase_descendants()`.
+    pub fn adopt(&self, mut_repo: &mut MutableRepo) -> BackendResult<()> {
+        let store = mut_repo.store().clone();
+        let rebased = self
+            .commits
+            .iter()
+            .filter(|commit| commit.old_id != commit.new_id)
+            .collect_vec();
+        let new_commits: Vec<Commit> = rebased
+            .iter()
+            .map(|commit| store.get_commit(&commit.new_id))
+            .try_collect()?;
+        mut_repo.add_heads(&new_commits)?;
+        for commit in rebased {
+            mut_repo.set_rewritten_commit(commit.old_id.clone(), commit.new_id.clone());
+        }
+        Ok(())
     }
 }
 
+/// The part of an earlier view to bring back with
+/// [`MutableRepo::restore_from_operation()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestoreSelector {
+    /// The local branches with these names. Branches that didn't exist at the
+    /// operation are deleted, and ones that don't exist now are recreated.
+    Branches(Vec<String>),
+    /// The working-copy commit of the workspace.
+    WorkingCopy(WorkspaceId),
+    /// The visible heads. Commits that are currently pointed to by a local
+    /// branch, a tag, or a working copy are kept visible.
+    Heads,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum Rewrite {
     /// The old commit was rewritten as this new commit. Children should be
@@ -758,6 +1760,15 @@ impl Rewrite {
     }
 }
 
+/// The in-memory state of a repo being changed by a [`Transaction`].
+///
+/// A `MutableRepo` is meant to be used from a single thread. It can be moved
+/// to another thread, but it isn't synchronized for concurrent use, and its
+/// mutating methods take `&mut self`. Concurrent changes should be made in
+/// separate transactions, whose operations are merged when the repo is
+/// reloaded.
+///
+/// [`Transaction`]: crate::transaction::Transaction
 pub struct MutableRepo {
     base_repo: Arc<ReadonlyRepo>,
     index: Box<dyn MutableIndex>,
@@ -771,6 +1782,8 @@ pub struct MutableRepo {
     //   commits. However, if the type is `Abandoned`, a new working-copy commit should be created
     //   on top of all of the new commits instead.
     parent_mapping: HashMap<CommitId, Rewrite>,
+    // Commits that are ancestors of these can't be rewritten or abandoned.
+    immutable_heads: Vec<CommitId>,
 }
 
 impl MutableRepo {
@@ -786,6 +1799,7 @@ impl MutableRepo {
             index: mut_index,
             view: DirtyCell::with_clean(mut_view),
             parent_mapping: Default::default(),
+            immutable_heads: vec![],
         }
     }
 
@@ -820,6 +1834,50 @@ impl MutableRepo {
         CommitBuilder::for_new_commit(self, settings, parents, tree_id)
     }
 
+    /// Creates a merge commit of `parents` with their trees merged, recording
+    /// any conflicts in the tree. Parents that have been rewritten or
+    /// abandoned in this transaction are replaced as in `new_parents()`.
+    pub fn create_merge(
+        &mut self,
+        settings: &UserSettings,
+        parents: &[Commit],
+    ) -> BackendResult<Commit> {
+        let parent_ids = self.new_parents(parents.iter().map(|p| p.id().clone()).collect());
+        let parents: Vec<_> = parent_ids
+            .iter()
+            .map(|id| self.store().get_commit(id))
+            .try_collect()?;
+        let merged_tree = merge_commit_trees(self, &parents)?;
+        self.new_commit(settings, parent_ids, merged_tree.id())
+            .write()
+    }
+
+    pub fn immutable_heads(&self) -> &[CommitId] {
+        &self.immutable_heads
+    }
+
+    /// Marks the ancestors of `heads` as immutable for the rest of the
+    /// transaction. Writing a rewrite of an immutable commit with
+    /// `CommitBuilder::try_write()` fails, as does `abandon_commit()`. The CLI
+    /// sets the heads from the `immutable_heads()` revset alias.
+    pub fn set_immutable_heads(&mut self, heads: Vec<CommitId>) {
+        self.immutable_heads = heads;
+    }
+
+    /// Fails with [`RepoError::Immutable`] if the commit is an ancestor of one
+    /// of the immutable heads.
+    pub fn check_mutable(&self, commit_id: &CommitId) -> Result<(), RepoError> {
+        let index = self.index();
+        if self
+            .immutable_heads
+            .iter()
+            .any(|head_id| index.is_ancestor(commit_id, head_id))
+        {
+            return Err(RepoError::Immutable(commit_id.clone()));
+        }
+        Ok(())
+    }
+
     /// Returns a [`CommitBuilder`] to rewrite an existing commit in the repo.
     pub fn rewrite_commit(
         &mut self,
@@ -831,6 +1889,20 @@ impl MutableRepo {
         // `self.rewritten_commits`
     }
 
+    /// Rewrites `commit` with a new description, keeping its tree and parents.
+    /// The original becomes obsolete, so its descendants are left as orphans
+    /// for `rebase_descendants()` or `evolve()`.
+    pub fn reword(
+        &mut self,
+        settings: &UserSettings,
+        commit: &Commit,
+        description: impl Into<String>,
+    ) -> BackendResult<Commit> {
+        self.rewrite_commit(settings, commit)
+            .set_description(description)
+            .write()
+    }
+
     /// Only called from [`CommitBuilder::write`]. Use that function instead.
     pub(crate) fn write_commit(
         &mut self,
@@ -890,6 +1962,14 @@ impl MutableRepo {
         self.record_abandoned_commit_with_parents(old_id, old_commit.parent_ids().to_vec());
     }
 
+    /// Like `record_abandoned_commit()`, but fails if the commit is immutable.
+    pub fn abandon_commit(&mut self, commit_id: &CommitId) -> Result<(), RepoError> {
+        self.check_mutable(commit_id)?;
+        let commit = self.store().get_commit(commit_id)?;
+        self.record_abandoned_commit_with_parents(commit_id.clone(), commit.parent_ids().to_vec());
+        Ok(())
+    }
+
     /// Record a commit as having been abandoned in this transaction.
     ///
     /// A later `rebase_descendants()` will rebase children of `old_id` onto
@@ -911,6 +1991,93 @@ impl MutableRepo {
         !self.parent_mapping.is_empty()
     }
 
+    /// Returns the commits recorded as rewritten or abandoned in this
+    /// transaction.
+    pub fn rewritten_commit_ids(&self) -> impl Iterator<Item = &CommitId> {
+        self.parent_mapping.keys()
+    }
+
+    /// Returns the visible descendants of the rewritten and abandoned commits,
+    /// which `rebase_descendants()` would rebase. Children come before their
+    /// parents.
+    pub fn orphan_ids(&self) -> BackendResult<Vec<CommitId>> {
+        let rewritten_expression =
+            RevsetExpression::commits(self.parent_mapping.keys().cloned().collect());
+        let orphans_expression = rewritten_expression
+            .descendants()
+            .minus(&rewritten_expression);
+        let orphans_revset =
+            orphans_expression
+                .evaluate_programmatic(self)
+                .map_err(|err| match err {
+                    RevsetEvaluationError::StoreError(err) => err,
+                    err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+                })?;
+        let orphan_ids = orphans_revset.iter().collect();
+        Ok(orphan_ids)
+    }
+
+    /// Returns how the numbers of orphans and divergent changes, as defined by
+    /// [`Evolution`], changed since the transaction started, e.g. for showing
+    /// the effect of each step of an interactive edit. Commits recorded as
+    /// rewritten or abandoned count as hidden, as they will be once their
+    /// descendants have been rebased.
+    ///
+    /// The state of the base repo is computed once. After that, only the
+    /// commits of divergent changes that gained or lost visible commits in
+    /// the transaction are read.
+    pub fn evolution_delta(&self) -> BackendResult<EvolutionDelta> {
+        self.compute_evolution_delta()
+            .map_err(evolution_error_to_backend_error)
+    }
+
+    fn compute_evolution_delta(&self) -> Result<EvolutionDelta, EvolutionError> {
+        let base_state = self.base_repo.evolution_state()?;
+
+        let base_visible =
+            RevsetExpression::commits(self.base_repo.view().heads().iter().cloned().collect())
+                .ancestors();
+        let visible = RevsetExpression::commits(self.view().heads().iter().cloned().collect())
+            .ancestors()
+            .minus(&RevsetExpression::commits(
+                self.parent_mapping.keys().cloned().collect(),
+            ));
+        let changed_change_ids: HashSet<ChangeId> = base_visible
+            .minus(&visible)
+            .union(&visible.minus(&base_visible))
+            .evaluate_programmatic(self)?
+            .commit_change_ids()
+            .map(|(_, change_id)| change_id)
+            .collect();
+
+        // The obsolete visible commits of a change only depend on which of its
+        // commits are visible
+        let evolution = Evolution::for_transaction(self)?;
+        let divergent_changes = evolution.divergent_changes();
+        let mut obsolete_ids = vec![];
+        for (change_id, commit_ids) in &divergent_changes {
+            match base_state.obsolete_ids.get(*change_id) {
+                Some(ids) if !changed_change_ids.contains(*change_id) => {
+                    obsolete_ids.extend(ids.iter().cloned());
+                }
+                _ => {
+                    for commit_id in *commit_ids {
+                        if evolution.is_obsolete(commit_id)? {
+                            obsolete_ids.push(commit_id.clone());
+                        }
+                    }
+                }
+            }
+        }
+        let num_orphans = evolution.orphan_ids_of(obsolete_ids)?.len();
+
+        Ok(EvolutionDelta {
+            orphans: num_orphans as isize - base_state.num_orphans as isize,
+            divergent_changes: divergent_changes.len() as isize
+                - base_state.obsolete_ids.len() as isize,
+        })
+    }
+
     /// Calculates new parents for a commit that's currently based on the given
     /// parents. It does that by considering how previous commits have been
     /// rewritten and abandoned.
@@ -1011,7 +2178,6 @@ impl MutableRepo {
             &new_commit_ids,
             abandoned_old_commit,
         )?;
-
         // Build a map from commit to branches pointing to it, so we don't need to scan
         // all branches each time we rebase a commit.
         // TODO: We no longer need to do this now that we update branches for all
@@ -1192,8 +2358,288 @@ impl MutableRepo {
         Ok(())
     }
 
-    /// After the rebaser returned by this function is dropped,
-    /// self.parent_mapping needs to be cleared.
+    /// Rewrites the commits in `roots::heads` in a single pass, parents first.
+    ///
+    /// The callback decides what to do with each commit. Commits in the range
+    /// are moved onto the rewritten versions of their parents, including
+    /// merge parents, and dropped parents are replaced by their own parents.
+    /// Rewritten commits keep their change ids and record the originals as
+    /// predecessors. Descendants outside the range are left to
+    /// `rebase_descendants()`.
+    pub fn transform_stack(
+        &mut self,
+        settings: &UserSettings,
+        roots: Vec<CommitId>,
+        heads: Vec<CommitId>,
+        transformer: &mut dyn FnMut(StackCommit) -> StackAction,
+    ) -> BackendResult<StackReport> {
+        let expression =
+            RevsetExpression::commits(roots).dag_range_to(&RevsetExpression::commits(heads));
+        let revset = expression
+            .evaluate_programmatic(self)
+            .map_err(|err| match err {
+                RevsetEvaluationError::StoreError(err) => err,
+                err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+            })?;
+        let mut to_visit: Vec<Commit> = revset.iter().commits(self.store()).try_collect()?;
+        drop(revset);
+        let mut report = StackReport::default();
+        // The revset yields children before parents.
+        while let Some(old_commit) = to_visit.pop() {
+            let new_parent_ids = self.new_parents(old_commit.parent_ids().to_vec());
+            let action = transformer(StackCommit {
+                old_commit: old_commit.clone(),
+                new_parent_ids: new_parent_ids.clone(),
+            });
+            let rewriter = CommitRewriter::new(self, old_commit, new_parent_ids);
+            let old_commit_id = rewriter.old_commit().id().clone();
+            let new_commit = match action {
+                StackAction::Keep if !rewriter.parents_changed() => {
+                    report.unchanged.push(old_commit_id);
+                    continue;
+                }
+                StackAction::Keep => rewriter.rebase(settings)?.write()?,
+                StackAction::Reword(description) => rewriter
+                    .rebase(settings)?
+                    .set_description(description)
+                    .write()?,
+                StackAction::Drop => {
+                    rewriter.abandon();
+                    report.dropped.push(old_commit_id);
+                    continue;
+                }
+                StackAction::ReplaceTree(tree_id) => {
+                    rewriter.reparent(settings)?.set_tree_id(tree_id).write()?
+                }
+            };
+            report
+                .rewritten
+                .insert(old_commit_id, new_commit.id().clone());
+        }
+        self.update_rewritten_references(settings)?;
+        Ok(report)
+    }
+
+    /// Rewrites the linear chain of commits in `ordering` so that they're
+    /// applied in that order on top of `base`, and rebases the descendants of
+    /// the chain onto its new top. Returns a map from old to new commit ids of
+    /// the chain and its rebased descendants.
+    ///
+    /// Each commit's changes relative to its old parent are merged into the new
+    /// parent, so the new trees may have conflicts. Every commit in the chain
+    /// is recorded as rewritten into its own new version, so branches follow
+    /// the commit they point to. The children of the old top of the chain are
+    /// moved onto the new top.
+    pub fn reorder_commits(
+        &mut self,
+        ordering: &[CommitId],
+        base: &CommitId,
+        settings: &UserSettings,
+    ) -> Result<HashMap<CommitId, CommitId>, ReorderCommitsError> {
+        let store = self.store().clone();
+        let commits: Vec<Commit> = ordering
+            .iter()
+            .map(|id| store.get_commit(id))
+            .try_collect()?;
+        let ids: HashSet<&CommitId> = ordering.iter().collect();
+        if commits.is_empty() {
+            return Ok(HashMap::new());
+        } else if ids.len() != ordering.len() {
+            return Err(ReorderCommitsError::NotLinearChain);
+        }
+        // The chain has a single commit whose parent is outside the chain, and
+        // every other commit is the only child of another commit in the chain.
+        let mut bottoms = vec![];
+        let mut children: HashMap<&CommitId, &Commit> = HashMap::new();
+        for commit in &commits {
+            let [parent_id] = commit.parent_ids() else {
+                return Err(ReorderCommitsError::NotLinearChain);
+            };
+            if !ids.contains(parent_id) {
+                bottoms.push(commit);
+            } else if children.insert(parent_id, commit).is_some() {
+                return Err(ReorderCommitsError::NotLinearChain);
+            }
+        }
+        let [bottom] = bottoms[..] else {
+            return Err(ReorderCommitsError::NotLinearChain);
+        };
+        if ids.contains(base) || self.index().is_ancestor(bottom.id(), base) {
+            return Err(ReorderCommitsError::BaseInChain(base.clone()));
+        }
+        let mut top = bottom;
+        while let Some(child) = children.get(top.id()) {
+            top = child;
+        }
+        let top_id = top.id().clone();
+
+        let child_ids: Vec<CommitId> = RevsetExpression::commit(top_id.clone())
+            .children()
+            .evaluate_programmatic(self)
+            .map_err(|err| match err {
+                RevsetEvaluationError::StoreError(err) => err,
+                err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+            })?
+            .iter()
+            .collect();
+
+        let mut rebased = HashMap::new();
+        let mut new_parent_id = base.clone();
+        for commit in commits {
+            let old_commit_id = commit.id().clone();
+            let rewriter = CommitRewriter::new(self, commit, vec![new_parent_id]);
+            let new_commit = rewriter.rebase(settings)?.write()?;
+            new_parent_id = new_commit.id().clone();
+            rebased.insert(old_commit_id, new_parent_id.clone());
+        }
+        for child_id in child_ids {
+            let child = store.get_commit(&child_id)?;
+            let new_parent_ids = child
+                .parent_ids()
+                .iter()
+                .map(|id| {
+                    if *id == top_id {
+                        new_parent_id.clone()
+                    } else {
+                        id.clone()
+                    }
+                })
+                .collect_vec();
+            let new_parent_ids = self.new_parents(new_parent_ids);
+            let rewriter = CommitRewriter::new(self, child, new_parent_ids);
+            let new_child = rewriter.rebase(settings)?.write()?;
+            rebased.insert(child_id, new_child.id().clone());
+        }
+        rebased.extend(self.rebase_descendants_return_map(settings)?);
+        Ok(rebased)
+    }
+
+    /// Squashes the linear segment of commits from `from` to `to`, inclusive,
+    /// into a single commit with `to`'s tree on top of `from`'s parents, and
+    /// returns it.
+    ///
+    /// The new commit keeps `to`'s change id, and all squashed commits are
+    /// recorded as its predecessors and as rewritten into it. Their
+    /// descendants are left as orphans for `rebase_descendants()` or
+    /// `evolve()`. The descriptions of the squashed commits are concatenated.
+    pub fn squash_range(
+        &mut self,
+        from: &Commit,
+        to: &Commit,
+        settings: &UserSettings,
+    ) -> Result<Commit, SquashRangeError> {
+        let store = self.store().clone();
+        if from.id() == store.root_commit_id() {
+            return Err(SquashRangeError::RewriteRootCommit);
+        }
+        if !self.index().is_ancestor(from.id(), to.id()) {
+            return Err(SquashRangeError::NotAncestor {
+                from: from.id().clone(),
+                to: to.id().clone(),
+            });
+        }
+        // Walk down from `to`. Every commit above `from` must have a single
+        // parent, or the segment isn't linear.
+        let mut segment = vec![to.clone()];
+        while segment.last().unwrap().id() != from.id() {
+            let [parent_id] = segment.last().unwrap().parent_ids() else {
+                return Err(SquashRangeError::NotLinearChain);
+            };
+            segment.push(store.get_commit(parent_id)?);
+        }
+        segment.reverse();
+
+        let description = segment
+            .iter()
+            .map(|commit| commit.description())
+            .filter(|description| !description.is_empty())
+            .join("\n");
+        let new_commit = self
+            .rewrite_commit(settings, to)
+            .set_parents(from.parent_ids().to_vec())
+            .set_predecessors(segment.iter().map(|commit| commit.id().clone()).collect())
+            .set_description(description)
+            .write()?;
+        for commit in &segment {
+            self.set_rewritten_commit(commit.id().clone(), new_commit.id().clone());
+        }
+        Ok(new_commit)
+    }
+
+    /// Rebases the commits in `roots::heads` onto `onto` without changing the
+    /// view, e.g. to check whether a branch rebases cleanly before moving it.
+    ///
+    /// Commits in the range are moved onto the rebased versions of their
+    /// parents, and parents outside the range are replaced by `onto`. The
+    /// rebased commits are written to the store and indexed, but they stay
+    /// hidden: no head, branch, or working copy is moved to them, and no
+    /// rewrites are recorded. Use [`RebaseOutcome::adopt()`] to make them
+    /// visible.
+    pub fn rebase_range(
+        &mut self,
+        settings: &UserSettings,
+        roots: Vec<CommitId>,
+        heads: Vec<CommitId>,
+        onto: &CommitId,
+    ) -> BackendResult<RebaseOutcome> {
+        let old_view = self.view().store_view().clone();
+        let old_parent_mapping = self.parent_mapping.clone();
+        let result = self.rebase_range_hidden(settings, roots, heads, onto);
+        self.set_view(old_view);
+        self.parent_mapping = old_parent_mapping;
+        result
+    }
+
+    fn rebase_range_hidden(
+        &mut self,
+        settings: &UserSettings,
+        roots: Vec<CommitId>,
+        heads: Vec<CommitId>,
+        onto: &CommitId,
+    ) -> BackendResult<RebaseOutcome> {
+        let revset = RevsetExpression::commits(roots)
+            .dag_range_to(&RevsetExpression::commits(heads))
+            .evaluate_programmatic(self)
+            .map_err(|err| match err {
+                RevsetEvaluationError::StoreError(err) => err,
+                err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+            })?;
+        let mut to_visit: Vec<Commit> = revset.iter().commits(self.store()).try_collect()?;
+        drop(revset);
+        let mut new_ids: HashMap<CommitId, CommitId> = HashMap::new();
+        let mut outcome = RebaseOutcome::default();
+        // The revset yields children before parents.
+        while let Some(old_commit) = to_visit.pop() {
+            let old_id = old_commit.id().clone();
+            let new_parent_ids = old_commit
+                .parent_ids()
+                .iter()
+                .map(|parent_id| new_ids.get(parent_id).unwrap_or(onto).clone())
+                .unique()
+                .collect_vec();
+            let rewriter = CommitRewriter::new(self, old_commit, new_parent_ids);
+            let new_commit = if rewriter.parents_changed() {
+                rewriter.rebase(settings)?.write()?
+            } else {
+                rewriter.old_commit().clone()
+            };
+            let conflicted_paths = new_commit
+                .tree()?
+                .conflicts()
+                .map(|(path, _)| path)
+                .collect();
+            new_ids.insert(old_id.clone(), new_commit.id().clone());
+            outcome.commits.push(RebasedCommit {
+                old_id,
+                new_id: new_commit.id().clone(),
+                conflicted_paths,
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// After the rebaser returned by this function is dropped,
+    /// self.parent_mapping needs to be cleared.
     fn rebase_descendants_return_rebaser<'settings, 'repo>(
         &'repo mut self,
         settings: &'settings UserSettings,
@@ -1271,6 +2717,29 @@ impl MutableRepo {
         Ok(num_rebased)
     }
 
+    /// Fails if a commit was recorded as rewritten into one of its own
+    /// descendants, since rebasing the commits in between onto it would make
+    /// them their own ancestors.
+    fn check_rewrites_into_descendants(&self) -> Result<(), RepoError> {
+        let index = self.index();
+        for (old_id, rewrite) in &self.parent_mapping {
+            if let Rewrite::Divergent(_) = rewrite {
+                continue;
+            }
+            if let Some(new_id) = rewrite
+                .new_parent_ids()
+                .iter()
+                .find(|new_id| index.is_ancestor(old_id, new_id))
+            {
+                return Err(RepoError::RewriteCycle {
+                    commit_id: old_id.clone(),
+                    parent_id: new_id.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     pub fn rebase_descendants_return_map(
         &mut self,
         settings: &UserSettings,
@@ -1278,6 +2747,140 @@ impl MutableRepo {
         self.rebase_descendants_with_options_return_map(settings, Default::default())
     }
 
+    /// Rebases orphans and resolves divergence until neither is left to do.
+    ///
+    /// A divergent change is resolved automatically if all but one of its
+    /// visible commits have been rewritten into that one, as happens when
+    /// concurrent operations both kept an old version. The old versions are
+    /// then recorded as rewritten, so their descendants get rebased. Other
+    /// divergent changes are counted but left alone. Changes whose divergence
+    /// was accepted with [`Self::accept_divergence()`] are neither resolved
+    /// nor counted. Instead, the children of their obsolete commits are
+    /// rebased onto what each obsolete commit was rewritten into, so the
+    /// descendants of each variant of the change stay on that variant.
+    ///
+    /// Local branches pointing at a rebased orphan or an obsolete commit move
+    /// to its replacement, like after any other rewrite. Tags are never moved.
+    ///
+    /// Fails with `RepoError::RewriteCycle` without rebasing anything if a
+    /// commit was recorded as rewritten into one of its own descendants.
+    pub fn evolve(&mut self, settings: &UserSettings) -> Result<EvolveSummary, RepoError> {
+        let mut summary = EvolveSummary::default();
+        loop {
+            self.check_rewrites_into_descendants()?;
+            summary.resolved_orphans += self.rebase_descendants(settings)?;
+            let orphans = self
+                .accepted_divergence_orphans()
+                .map_err(evolution_error_to_backend_error)?;
+            if !orphans.is_empty() {
+                for (orphan, new_parent_ids) in orphans {
+                    rebase_commit(settings, self, orphan, new_parent_ids)?;
+                    summary.resolved_orphans += 1;
+                }
+                continue;
+            }
+            let resolutions = self
+                .divergence_resolutions()
+                .map_err(evolution_error_to_backend_error)?;
+            if resolutions.is_empty() {
+                break;
+            }
+            for (obsolete_ids, latest_id) in resolutions {
+                for obsolete_id in obsolete_ids {
+                    self.set_rewritten_commit(obsolete_id, latest_id.clone());
+                }
+                summary.resolved_divergences += 1;
+            }
+        }
+        summary.remaining_divergences = Evolution::new(self)
+            .map_err(evolution_error_to_backend_error)?
+            .divergent_changes()
+            .into_keys()
+            .filter(|change_id| !self.view().is_divergence_accepted(change_id))
+            .count();
+        Ok(summary)
+    }
+
+    /// Finds the divergent changes where all visible commits but one were
+    /// rewritten into that one. Returns the obsolete commits of each such
+    /// change with the commit to replace them with.
+    fn divergence_resolutions(&self) -> Result<Vec<(Vec<CommitId>, CommitId)>, EvolutionError> {
+        let evolution = Evolution::new(self)?;
+        let mut resolutions = vec![];
+        for change_id in evolution.divergent_changes().into_keys() {
+            if self.view().is_divergence_accepted(change_id) {
+                continue;
+            }
+            resolutions.extend(evolution.divergence_resolution(change_id)?);
+        }
+        Ok(resolutions)
+    }
+
+    /// Finds the visible children of the obsolete commits of the changes whose
+    /// divergence was accepted. Returns each child with the parents to rebase
+    /// it onto, where an obsolete parent is replaced by the commit it was
+    /// rewritten into. Parents rewritten into several visible commits that
+    /// aren't obsolete are kept, since it's ambiguous which one to use.
+    fn accepted_divergence_orphans(&self) -> Result<Vec<(Commit, Vec<CommitId>)>, EvolutionError> {
+        let evolution = Evolution::new(self)?;
+        let index = self.index();
+        let mut replacements = HashMap::new();
+        for (change_id, commit_ids) in evolution.divergent_changes() {
+            if !self.view().is_divergence_accepted(change_id) {
+                continue;
+            }
+            for commit_id in commit_ids {
+                if let [latest_id] = evolution.latest_successor_ids(commit_id)?.as_slice() {
+                    replacements.insert(commit_id.clone(), latest_id.clone());
+                }
+            }
+        }
+        if replacements.is_empty() {
+            return Ok(vec![]);
+        }
+        let child_ids = RevsetExpression::commits(replacements.keys().cloned().collect())
+            .children()
+            .evaluate_programmatic(self)?
+            .iter()
+            .collect_vec();
+        let mut orphans = vec![];
+        for child_id in child_ids {
+            // Commits of the change itself are left alone
+            if replacements.contains_key(&child_id) {
+                continue;
+            }
+            let child = self.store().get_commit(&child_id)?;
+            let new_parent_ids = child
+                .parent_ids()
+                .iter()
+                .map(|id| replacements.get(id).unwrap_or(id).clone())
+                .collect_vec();
+            // Rebasing onto a descendant of the child would duplicate it
+            if new_parent_ids
+                .iter()
+                .any(|new_parent_id| index.is_ancestor(&child_id, new_parent_id))
+            {
+                continue;
+            }
+            orphans.push((child, new_parent_ids));
+        }
+        Ok(orphans)
+    }
+
+    /// Accepts the divergence of the change, so [`Self::evolve()`] leaves it
+    /// alone. The change is still reported as divergent.
+    pub fn accept_divergence(&mut self, change_id: &ChangeId) {
+        self.set_change_divergence_allowed(change_id, true);
+    }
+
+    /// Sets whether the divergence of the change is intentional, as when
+    /// keeping several variants of a change. See [`Self::evolve()`] for how
+    /// such changes are evolved.
+    pub fn set_change_divergence_allowed(&mut self, change_id: &ChangeId, allowed: bool) {
+        self.view_mut()
+            .set_divergence_accepted(change_id.clone(), allowed);
+    }
+
     pub fn set_wc_commit(
         &mut self,
         workspace_id: WorkspaceId,
@@ -1309,7 +2912,12 @@ impl MutableRepo {
                 commit.tree_id().clone(),
             )
             .write()?;
-        self.edit(workspace_id, &wc_commit)?;
+        self.edit(workspace_id.clone(), &wc_commit)?;
+        self.view_mut().record_checkout(
+            workspace_id,
+            commit.id().clone(),
+            settings.checkout_history_size(),
+        );
         Ok(wc_commit)
     }
 
@@ -1413,7 +3021,7 @@ impl MutableRepo {
                 }
             }
             _ => {
-                let missing_commits = dag_walk::topo_order_reverse_ord_ok(
+                let missing_commits = dag_walk::topo_order_reverse_ord_checked(
                     heads
                         .iter()
                         .cloned()
@@ -1447,6 +3055,35 @@ impl MutableRepo {
         self.view.mark_dirty();
     }
 
+    /// Records the commits that were visible at the start of the transaction
+    /// but are no longer reachable from any visible head, local branch, tag,
+    /// or working-copy commit as abandoned, e.g. after `remove_head()`.
+    /// Commits that were already rewritten or abandoned are skipped. Returns
+    /// the abandoned commits, children before parents.
+    pub fn abandon_unreachable(&mut self) -> BackendResult<Vec<CommitId>> {
+        let base_heads = self.base_repo.view().sorted_heads();
+        let reachable_expression = RevsetExpression::visible_heads()
+            .union(&RevsetExpression::branches(StringPattern::everything()))
+            .union(&RevsetExpression::tags())
+            .union(&RevsetExpression::working_copies())
+            .ancestors();
+        let unreachable_ids = RevsetExpression::commits(base_heads)
+            .ancestors()
+            .minus(&reachable_expression)
+            .evaluate_programmatic(self)
+            .map_err(|err| match err {
+                RevsetEvaluationError::StoreError(err) => err,
+                err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+            })?
+            .iter()
+            .filter(|id| !self.parent_mapping.contains_key(id))
+            .collect_vec();
+        for id in &unreachable_ids {
+            self.record_abandoned_commit(id.clone());
+        }
+        Ok(unreachable_ids)
+    }
+
     pub fn get_local_branch(&self, name: &str) -> RefTarget {
         self.view.with_ref(|v| v.get_local_branch(name).clone())
     }
@@ -1468,6 +3105,130 @@ impl MutableRepo {
         view.set_local_branch_target(name, new_target);
     }
 
+    /// Renames local branch `old_name` to `new_name`, keeping its target.
+    /// Remote branches aren't renamed.
+    pub fn rename_branch(
+        &mut self,
+        old_name: &str,
+        new_name: &str,
+    ) -> Result<(), RenameBranchError> {
+        let view = self.view_mut();
+        let target = view.get_local_branch(old_name).clone();
+        if target.is_absent() {
+            return Err(RenameBranchError::NoSuchBranch(old_name.to_owned()));
+        }
+        if view.get_local_branch(new_name).is_present() {
+            return Err(RenameBranchError::BranchAlreadyExists(new_name.to_owned()));
+        }
+        view.set_local_branch_target(new_name, target);
+        view.set_local_branch_target(old_name, RefTarget::absent());
+        Ok(())
+    }
+
+    /// Like [`ReadonlyRepo::branch_metadata()`]. A commit rewritten in this
+    /// transaction counts as obsolete even before its descendants have been
+    /// rebased.
+    pub fn branch_metadata(
+        &self,
+        name: &str,
+    ) -> Result<Option<BranchMetadata>, BranchMetadataError> {
+        let view = self.view();
+        let target = view.get_local_branch(name);
+        if target.is_absent() {
+            return Ok(None);
+        }
+        let base_repo = &self.base_repo;
+        let last_moved_op_id = match name_in_parent_view(view, base_repo.view(), name) {
+            Some(base_name) => Some(base_repo.branch_last_moved_op_ids()?[&base_name].clone()),
+            None => None,
+        };
+        let is_obsolete = is_target_obsolete(&Evolution::for_transaction(self)?, target)?;
+        Ok(Some(BranchMetadata {
+            last_moved_op_id,
+            is_obsolete,
+        }))
+    }
+
+    /// Deletes the local branches whose change has been merged into the
+    /// `target` branch. A branch has been merged if its commit was rewritten
+    /// into an ancestor of the target, or if its commit was merged into the
+    /// target as is, i.e. is an ancestor of the target but not on its
+    /// first-parent line. Branches that merely point to older commits of the
+    /// target's own line are kept, as are branches pointing to divergent,
+    /// split or abandoned changes, conflicted branches, and branches matching
+    /// `options.immutable_branches`. Returns the names of the deleted
+    /// branches, or of the branches that would be deleted if
+    /// `options.dry_run` is set.
+    pub fn cleanup_merged_branches(
+        &mut self,
+        target: &str,
+        options: &CleanupMergedBranchesOptions,
+    ) -> BackendResult<Vec<String>> {
+        let Some(target_id) = self.get_local_branch(target).as_normal().cloned() else {
+            return Ok(vec![]);
+        };
+        let evolution =
+            Evolution::for_transaction(self).map_err(evolution_error_to_backend_error)?;
+        let index = self.index();
+        let mut merged_branches = vec![];
+        // Branches whose commit is an ancestor of the target as is, by commit id
+        let mut ancestor_branches: HashMap<&CommitId, Vec<&str>> = HashMap::new();
+        for (name, ref_target) in self.view().local_branches() {
+            if name == target
+                || options
+                    .immutable_branches
+                    .iter()
+                    .any(|pattern| pattern.matches(name))
+            {
+                continue;
+            }
+            let Some(commit_id) = ref_target.as_normal() else {
+                continue;
+            };
+            match evolution
+                .obsolescence_reason(commit_id)
+                .map_err(evolution_error_to_backend_error)?
+            {
+                None => {
+                    if index.is_ancestor(commit_id, &target_id) {
+                        ancestor_branches.entry(commit_id).or_default().push(name);
+                    }
+                }
+                Some(ObsolescenceReason::Rewritten { successors }) => {
+                    if let [successor_id] = successors.as_slice() {
+                        if index.is_ancestor(successor_id, &target_id) {
+                            merged_branches.push(name.to_owned());
+                        }
+                    }
+                }
+                Some(ObsolescenceReason::Pruned | ObsolescenceReason::Split { .. }) => {}
+            }
+        }
+        // Walk the first-parent line of the target until all the ancestor
+        // branches on it have been found
+        let mut first_parent_id = Some(target_id);
+        while let Some(commit_id) = first_parent_id {
+            if ancestor_branches.is_empty() {
+                break;
+            }
+            ancestor_branches.remove(&commit_id);
+            first_parent_id = index.parent_ids(&commit_id).into_iter().next();
+        }
+        merged_branches.extend(
+            ancestor_branches
+                .into_values()
+                .flatten()
+                .map(|name| name.to_owned()),
+        );
+        merged_branches.sort();
+        if !options.dry_run {
+            for name in &merged_branches {
+                self.set_local_branch_target(name, RefTarget::absent());
+            }
+        }
+        Ok(merged_branches)
+    }
+
     pub fn get_remote_branch(&self, name: &str, remote_name: &str) -> RemoteRef {
         self.view
             .with_ref(|v| v.get_remote_branch(name, remote_name).clone())
@@ -1525,6 +3286,115 @@ impl MutableRepo {
         self.view_mut().set_tag_target(name, target);
     }
 
+    /// Labels the change of the given commit. Labels are recorded by change
+    /// id, so they stay with the change when its commits are rewritten.
+    pub fn add_label(&mut self, commit_id: &CommitId, label: &str) -> BackendResult<()> {
+        let commit = self.store().get_commit(commit_id)?;
+        self.view_mut().add_label(label, commit.change_id().clone());
+        Ok(())
+    }
+
+    /// Removes the label from the change of the given commit.
+    pub fn remove_label(&mut self, commit_id: &CommitId, label: &str) -> BackendResult<()> {
+        let commit = self.store().get_commit(commit_id)?;
+        self.view_mut().remove_label(label, commit.change_id());
+        Ok(())
+    }
+
+    /// Returns the metadata value stored under `key` for the change.
+    pub fn get_change_metadata(&self, change_id: &ChangeId, key: &str) -> Option<String> {
+        self.view.with_ref(|v| {
+            v.get_change_metadata(change_id)
+                .and_then(|metadata| metadata.values.get(key).cloned())
+        })
+    }
+
+    /// Stores a metadata value under `key` for the change. The metadata is
+    /// recorded by change id, so it stays with the change when its commits are
+    /// rewritten.
+    pub fn set_change_metadata(
+        &mut self,
+        change_id: &ChangeId,
+        key: &str,
+        value: impl Into<String>,
+    ) -> Result<(), ChangeMetadataTooLarge> {
+        let value = value.into();
+        if key.len() > MAX_CHANGE_METADATA_KEY_LEN {
+            return Err(ChangeMetadataTooLarge::Key { len: key.len() });
+        }
+        if value.len() > MAX_CHANGE_METADATA_VALUE_LEN {
+            return Err(ChangeMetadataTooLarge::Value {
+                key: key.to_owned(),
+                len: value.len(),
+            });
+        }
+        let mut metadata = self
+            .view()
+            .get_change_metadata(change_id)
+            .cloned()
+            .unwrap_or_default();
+        if !metadata.values.contains_key(key) && metadata.values.len() >= MAX_CHANGE_METADATA_KEYS {
+            return Err(ChangeMetadataTooLarge::TooManyKeys);
+        }
+        metadata.values.insert(key.to_owned(), value);
+        self.view_mut()
+            .set_change_metadata(change_id.clone(), metadata);
+        Ok(())
+    }
+
+    /// Removes the metadata value stored under `key` for the change.
+    pub fn remove_change_metadata(&mut self, change_id: &ChangeId, key: &str) {
+        let Some(mut metadata) = self.view().get_change_metadata(change_id).cloned() else {
+            return;
+        };
+        metadata.values.remove(key);
+        self.view_mut()
+            .set_change_metadata(change_id.clone(), metadata);
+    }
+
+    /// Counts another operation for each change with metadata but no visible
+    /// commits, and removes the metadata of changes that have had no visible
+    /// commits for more than `retention` operations. The count starts over
+    /// when a commit of the change becomes visible again. Labels are removed
+    /// as soon as the change has no visible commits.
+    pub fn age_change_metadata(&mut self, retention: u64) {
+        if self.view().change_metadata().is_empty() {
+            return;
+        }
+        let change_id_index = self.index.change_id_index(&mut self.view().heads().iter());
+        let updates = self
+            .view()
+            .change_metadata()
+            .iter()
+            .filter_map(|(change_id, metadata)| {
+                let prefix = HexPrefix::from_bytes(change_id.as_bytes());
+                let new_metadata =
+                    if change_id_index.resolve_prefix(&prefix) != PrefixResolution::NoMatch {
+                        if metadata.operations_without_commits == 0 {
+                            return None;
+                        }
+                        ChangeMetadata {
+                            operations_without_commits: 0,
+                            ..metadata.clone()
+                        }
+                    } else if metadata.operations_without_commits >= retention {
+                        ChangeMetadata::default()
+                    } else {
+                        ChangeMetadata {
+                            labels: BTreeSet::new(),
+                            values: metadata.values.clone(),
+                            operations_without_commits: metadata.operations_without_commits + 1,
+                        }
+                    };
+                Some((change_id.clone(), new_metadata))
+            })
+            .collect_vec();
+        drop(change_id_index);
+        for (change_id, metadata) in updates {
+            self.view_mut().set_change_metadata(change_id, metadata);
+        }
+    }
+
     pub fn merge_tag(&mut self, name: &str, base_target: &RefTarget, other_target: &RefTarget) {
         let view = self.view.get_mut();
         let index = self.index.as_index();
@@ -1562,6 +3432,78 @@ impl MutableRepo {
         self.view.mark_dirty();
     }
 
+    /// Restores the part of the view at `op` picked by `selector`, leaving the
+    /// rest of the current view untouched. Unlike `set_view()` with the whole
+    /// old view, this can e.g. move a branch back without undoing the
+    /// working-copy changes made since. The commits the restored part refers
+    /// to are made visible again. Restoring a part that's the same as at `op`
+    /// does nothing.
+    pub fn restore_from_operation(
+        &mut self,
+        op: &Operation,
+        selector: &RestoreSelector,
+    ) -> Result<(), RestoreFromOperationError> {
+        let old_view = op.view()?;
+        match selector {
+            RestoreSelector::Branches(names) => {
+                for name in names {
+                    let old_target = old_view.get_local_branch(name);
+                    if *old_target == self.get_local_branch(name) {
+                        continue;
+                    }
+                    let commits: Vec<_> = old_target
+                        .added_ids()
+                        .map(|id| self.store().get_commit(id))
+                        .try_collect()?;
+                    self.add_heads(&commits)?;
+                    self.set_local_branch_target(name, old_target.clone());
+                }
+            }
+            RestoreSelector::WorkingCopy(workspace_id) => {
+                let old_wc_commit_id = old_view.get_wc_commit_id(workspace_id);
+                if old_wc_commit_id == self.view().get_wc_commit_id(workspace_id) {
+                    return Ok(());
+                }
+                if let Some(commit_id) = old_wc_commit_id {
+                    let commit = self.store().get_commit(commit_id)?;
+                    self.add_head(&commit)?;
+                    self.view_mut()
+                        .set_wc_commit(workspace_id.clone(), commit_id.clone());
+                } else {
+                    self.view_mut().remove_wc_commit(workspace_id);
+                }
+            }
+            RestoreSelector::Heads => {
+                let view = self.view();
+                let referenced_ids = itertools::chain!(
+                    view.local_branches()
+                        .flat_map(|(_, target)| target.added_ids()),
+                    view.tags().values().flat_map(|target| target.added_ids()),
+                    view.wc_commit_ids().values(),
+                )
+                .cloned()
+                .collect_vec();
+                let removed_head_ids = view
+                    .heads()
+                    .iter()
+                    .filter(|id| !old_view.heads().contains(id))
+                    .cloned()
+                    .collect_vec();
+                for head_id in &removed_head_ids {
+                    self.remove_head(head_id);
+                }
+                let commits: Vec<_> = old_view
+                    .heads()
+                    .iter()
+                    .chain(&referenced_ids)
+                    .map(|id| self.store().get_commit(id))
+                    .try_collect()?;
+                self.add_heads(&commits)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn merge(&mut self, base_repo: &ReadonlyRepo, other_repo: &ReadonlyRepo) {
         // First, merge the index, so we can take advantage of a valid index when
         // merging the view. Merging in base_repo's index isn't typically
@@ -1603,9 +3545,92 @@ impl MutableRepo {
                     .set_wc_commit(workspace_id.clone(), other_wc_commit.clone());
             }
         }
-        let base_heads = base.heads().iter().cloned().collect_vec();
-        let own_heads = self.view().heads().iter().cloned().collect_vec();
-        let other_heads = other.heads().iter().cloned().collect_vec();
+        // Merge checkout histories. If both sides changed, we keep the self side.
+        let workspace_ids = other
+            .store_view()
+            .checkout_history
+            .keys()
+            .chain(base.store_view().checkout_history.keys())
+            .unique()
+            .cloned()
+            .collect_vec();
+        for workspace_id in workspace_ids {
+            let base_history = base.checkout_history(&workspace_id);
+            let other_history = other.checkout_history(&workspace_id);
+            if other_history != base_history
+                && self.view().checkout_history(&workspace_id) == base_history
+            {
+                self.view_mut()
+                    .set_checkout_history(workspace_id, other_history.to_vec());
+            }
+        }
+        // Merge change metadata. Each label and each value is merged
+        // independently of the others, and a value changed in `other` wins.
+        let change_ids = base
+            .change_metadata()
+            .keys()
+            .chain(other.change_metadata().keys())
+            .unique()
+            .cloned()
+            .collect_vec();
+        for change_id in change_ids {
+            let empty = ChangeMetadata::default();
+            let base_metadata = base.get_change_metadata(&change_id).unwrap_or(&empty);
+            let other_metadata = other.get_change_metadata(&change_id);
+            let self_metadata = self.view().get_change_metadata(&change_id).cloned();
+            // Keep the count of whichever side saw a commit of the change last
+            let operations_without_commits = match (&self_metadata, other_metadata) {
+                (Some(self_metadata), Some(other_metadata)) => self_metadata
+                    .operations_without_commits
+                    .min(other_metadata.operations_without_commits),
+                (Some(metadata), None) | (None, Some(metadata)) => {
+                    metadata.operations_without_commits
+                }
+                (None, None) => 0,
+            };
+            let other_metadata = other_metadata.unwrap_or(&empty);
+            let mut metadata = self_metadata.unwrap_or_default();
+            metadata.operations_without_commits = operations_without_commits;
+            for label in base_metadata
+                .labels
+                .symmetric_difference(&other_metadata.labels)
+            {
+                if other_metadata.labels.contains(label) {
+                    metadata.labels.insert(label.clone());
+                } else {
+                    metadata.labels.remove(label);
+                }
+            }
+            let keys = base_metadata
+                .values
+                .keys()
+                .chain(other_metadata.values.keys())
+                .unique();
+            for key in keys {
+                let other_value = other_metadata.values.get(key);
+                if other_value == base_metadata.values.get(key) {
+                    continue;
+                }
+                match other_value {
+                    Some(value) => metadata.values.insert(key.clone(), value.clone()),
+                    None => metadata.values.remove(key),
+                };
+            }
+            self.view_mut().set_change_metadata(change_id, metadata);
+        }
+        // Merge accepted divergences. A change accepted or no longer accepted
+        // in `other` wins.
+        for change_id in base
+            .accepted_divergences()
+            .symmetric_difference(other.accepted_divergences())
+        {
+            let accepted = other.is_divergence_accepted(change_id);
+            self.view_mut()
+                .set_divergence_accepted(change_id.clone(), accepted);
+        }
+        let base_heads = base.sorted_heads();
+        let own_heads = self.view().sorted_heads();
+        let other_heads = other.sorted_heads();
 
         // HACK: Don't walk long ranges of commits to find rewrites when using other
         // custom implementations. The only custom index implementation we're currently
@@ -1734,6 +3759,10 @@ impl Repo for MutableRepo {
         self.base_repo.submodule_store()
     }
 
+    fn settings(&self) -> &RepoSettings {
+        self.base_repo.settings()
+    }
+
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>> {
         let change_id_index = self.index.change_id_index(&mut self.view().heads().iter());
         change_id_index.resolve_prefix(prefix)
@@ -1761,6 +3790,115 @@ pub enum EditCommitError {
     BackendError(#[from] BackendError),
 }
 
+/// Error from attempts to restore parts of an earlier operation
+#[derive(Debug, Error)]
+pub enum RestoreFromOperationError {
+    #[error(transparent)]
+    OpStore(#[from] OpStoreError),
+    #[error(transparent)]
+    BackendError(#[from] BackendError),
+}
+
+/// Error from attempts to reorder commits
+#[derive(Debug, Error)]
+pub enum ReorderCommitsError {
+    #[error("Commits to reorder must form a linear chain")]
+    NotLinearChain,
+    #[error("Cannot reorder commits onto {} which is in or above the chain", .0.hex())]
+    BaseInChain(CommitId),
+    #[error(transparent)]
+    BackendError(#[from] BackendError),
+}
+
+/// Error from attempts to squash a range of commits
+#[derive(Debug, Error)]
+pub enum SquashRangeError {
+    #[error("Commit {} is not an ancestor of {}", from.hex(), to.hex())]
+    NotAncestor { from: CommitId, to: CommitId },
+    #[error("Commits to squash must form a linear chain")]
+    NotLinearChain,
+    #[error("Cannot rewrite the root commit")]
+    RewriteRootCommit,
+    #[error(transparent)]
+    BackendError(#[from] BackendError),
+}
+
+/// The maximum length in bytes of a change metadata key.
+pub const MAX_CHANGE_METADATA_KEY_LEN: usize = 256;
+
+/// The maximum length in bytes of a change metadata value.
+pub const MAX_CHANGE_METADATA_VALUE_LEN: usize = 4096;
+
+/// The maximum number of metadata values of a change.
+pub const MAX_CHANGE_METADATA_KEYS: usize = 64;
+
+/// Error from attempts to store change metadata beyond the size limits
+#[derive(Debug, Error)]
+pub enum ChangeMetadataTooLarge {
+    #[error(
+        "Metadata key is {len} bytes, more than the maximum of {}",
+        MAX_CHANGE_METADATA_KEY_LEN
+    )]
+    Key { len: usize },
+    #[error(
+        "Metadata value for {key} is {len} bytes, more than the maximum of {}",
+        MAX_CHANGE_METADATA_VALUE_LEN
+    )]
+    Value { key: String, len: usize },
+    #[error(
+        "Change already has the maximum of {} metadata values",
+        MAX_CHANGE_METADATA_KEYS
+    )]
+    TooManyKeys,
+}
+
+/// Error from attempts to rename a local branch
+#[derive(Debug, Error)]
+pub enum RenameBranchError {
+    #[error("No such branch: {0}")]
+    NoSuchBranch(String),
+    #[error("Branch already exists: {0}")]
+    BranchAlreadyExists(String),
+}
+
+/// Error from attempts to compute [`BranchMetadata`]
+#[derive(Debug, Error)]
+pub enum BranchMetadataError {
+    #[error(transparent)]
+    OpStore(#[from] OpStoreError),
+    #[error(transparent)]
+    Evolution(#[from] EvolutionError),
+}
+
+/// Error from attempts to write a commit with checks
+#[derive(Debug, Error)]
+pub enum RepoError {
+    #[error(
+        "Change id {change_id} is already used by unrelated commits {ids}",
+        change_id = change_id.hex(),
+        ids = commit_ids.iter().map(|id| id.hex()).join(", ")
+    )]
+    ChangeIdCollision {
+        change_id: ChangeId,
+        commit_ids: Vec<CommitId>,
+    },
+    #[error("Commit {} doesn't exist in the repo", .0.hex())]
+    MissingCommit(CommitId),
+    #[error("Commit {} is immutable", .0.hex())]
+    Immutable(CommitId),
+    #[error(
+        "Cannot rewrite commit {} onto its descendant {}",
+        commit_id.hex(),
+        parent_id.hex()
+    )]
+    RewriteCycle {
+        commit_id: CommitId,
+        parent_id: CommitId,
+    },
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+}
+
 /// Error from attempts to check out a commit
 #[derive(Debug, Error)]
 pub enum CheckOutCommitError {
@@ -1770,6 +3908,86 @@ pub enum CheckOutCommitError {
     EditCommit(#[from] EditCommitError),
 }
 
+fn evolution_error_to_backend_error(err: EvolutionError) -> BackendError {
+    match err {
+        EvolutionError::Backend(err) => err,
+        EvolutionError::Evaluation(RevsetEvaluationError::StoreError(err)) => err,
+        err => BackendError::Other(err.into()),
+    }
+}
+
+/// Walks the first parents of `op`, whose view is `view`, back to the
+/// operations that set the targets of its local branches. Returns them by
+/// branch name. The walk stops once all of them are found.
+fn last_moved_op_ids(
+    mut op: Operation,
+    view: &View,
+) -> Result<HashMap<String, OperationId>, OpStoreError> {
+    let mut view = view.clone();
+    // The branches not found yet, with their names in `view`
+    let mut pending = view
+        .local_branches()
+        .map(|(name, _)| (name.to_owned(), name.to_owned()))
+        .collect_vec();
+    let mut op_ids = HashMap::new();
+    while !pending.is_empty() {
+        let Some(parent_op) = op.parents().next().transpose()? else {
+            break;
+        };
+        let parent_view = parent_op.view()?;
+        pending.retain_mut(|(name, current_name)| {
+            match name_in_parent_view(&view, &parent_view, current_name) {
+                Some(parent_name) => {
+                    *current_name = parent_name;
+                    true
+                }
+                None => {
+                    op_ids.insert(name.clone(), op.id().clone());
+                    false
+                }
+            }
+        });
+        op = parent_op;
+        view = parent_view;
+    }
+    for (name, _) in pending {
+        op_ids.insert(name, op.id().clone());
+    }
+    Ok(op_ids)
+}
+
+/// Returns the name local branch `name` of `view` had in `parent_view`, or
+/// `None` if the branch's target was set in between. A branch that doesn't
+/// exist in `parent_view` is taken to be renamed from the only branch there
+/// with the same target and no longer in `view`.
+fn name_in_parent_view(view: &View, parent_view: &View, name: &str) -> Option<String> {
+    let target = view.get_local_branch(name);
+    let parent_target = parent_view.get_local_branch(name);
+    if parent_target == target {
+        Some(name.to_owned())
+    } else if parent_target.is_present() {
+        None
+    } else {
+        parent_view
+            .local_branches()
+            .filter(|&(old_name, old_target)| {
+                old_target == target && view.get_local_branch(old_name).is_absent()
+            })
+            .exactly_one()
+            .ok()
+            .map(|(old_name, _)| old_name.to_owned())
+    }
+}
+
+fn is_target_obsolete(evolution: &Evolution, target: &RefTarget) -> Result<bool, EvolutionError> {
+    for id in target.added_ids() {
+        if evolution.is_obsolete(id)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 mod dirty_cell {
     use std::cell::{OnceCell, RefCell};
 
@@ -0,0 +1,242 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Versioning of the on-disk repo format, and upgrades between versions.
+
+#![allow(missing_docs)]
+
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+use crate::file_util::{IoResultExt as _, PathError};
+use crate::lock::FileLock;
+use crate::repo::REPO_ID_FILE;
+
+/// The format version written by this version of the library. Repos in an
+/// older format are upgraded with [`upgrade()`] before they can be loaded for
+/// writing, automatically if [`can_upgrade_automatically()`], and repos in a
+/// newer format can't be loaded.
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+/// Name of the file in the repo directory with the format version. Repos
+/// without it are in version 1.
+const FORMAT_FILE: &str = "format";
+
+/// Name of the file in the repo directory recording an unfinished upgrade.
+const UPGRADE_JOURNAL_FILE: &str = "upgrade_journal";
+
+#[derive(Debug, Error)]
+pub enum UpgradeError {
+    #[error("Repo format version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("Cannot downgrade the repo format from version {current} to version {target}")]
+    Downgrade { current: u32, target: u32 },
+    #[error("Invalid repo format version {0:?}")]
+    InvalidVersion(String),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error("Upgrade interrupted at {0}")]
+    Interrupted(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UpgradeOptions {
+    /// Fails with `UpgradeError::Interrupted` when the upgrade reaches the
+    /// named point, as if the process had crashed there. For testing that
+    /// interrupted upgrades can be resumed.
+    #[cfg(feature = "testing")]
+    pub fail_point: Option<String>,
+}
+
+/// What [`upgrade()`] did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpgradeReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Descriptions of the migrations that were run, in order.
+    pub migrations: Vec<&'static str>,
+    /// Whether an earlier, interrupted upgrade was continued.
+    pub resumed: bool,
+}
+
+struct Migration {
+    /// The version this migration upgrades from, to the next version.
+    from_version: u32,
+    description: &'static str,
+    /// Whether the migration only adds data that older versions of the
+    /// library don't need, so it can be run without asking when the repo is
+    /// loaded for writing.
+    automatic: bool,
+    /// Runs the migration. Must be idempotent, so an interrupted migration
+    /// can be run again.
+    run: fn(&MigrationContext) -> Result<(), UpgradeError>,
+}
+
+struct MigrationContext<'a> {
+    repo_path: &'a Path,
+    #[cfg_attr(not(feature = "testing"), allow(dead_code))]
+    options: &'a UpgradeOptions,
+}
+
+impl MigrationContext<'_> {
+    #[cfg(feature = "testing")]
+    fn fail_point(&self, name: &str) -> Result<(), UpgradeError> {
+        if self.options.fail_point.as_deref() == Some(name) {
+            return Err(UpgradeError::Interrupted(name.to_owned()));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "testing"))]
+    fn fail_point(&self, _name: &str) -> Result<(), UpgradeError> {
+        Ok(())
+    }
+}
+
+/// The registered migrations, in order of `from_version`.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from_version: 1,
+    description: "Add repository ids to the store and the operation log",
+    automatic: true,
+    run: add_repo_ids,
+}];
+
+/// Whether a repo in format `version` can be upgraded to the current format
+/// without asking, because all the migrations in between are automatic.
+pub fn can_upgrade_automatically(version: u32) -> bool {
+    MIGRATIONS
+        .iter()
+        .filter(|migration| (version..CURRENT_FORMAT_VERSION).contains(&migration.from_version))
+        .all(|migration| migration.automatic)
+}
+
+/// Reads the format version of the repo at `repo_path`.
+pub fn read_format_version(repo_path: &Path) -> Result<u32, UpgradeError> {
+    let path = repo_path.join(FORMAT_FILE);
+    match fs::read_to_string(&path) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .map_err(|_| UpgradeError::InvalidVersion(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(1),
+        Err(err) => Err(PathError { path, error: err }.into()),
+    }
+}
+
+pub(crate) fn write_format_version(repo_path: &Path, version: u32) -> Result<(), PathError> {
+    write_atomically(&repo_path.join(FORMAT_FILE), version.to_string().as_bytes())
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), PathError> {
+    let dir = path.parent().unwrap();
+    let mut temp_file = NamedTempFile::new_in(dir).context(dir)?;
+    temp_file.write_all(contents).context(temp_file.path())?;
+    temp_file
+        .persist(path)
+        .map_err(|err| err.error)
+        .context(path)?;
+    Ok(())
+}
+
+/// Upgrades the repo at `repo_path` to format `target_version` by running the
+/// migrations in between. The format version is recorded after each
+/// migration, and a journal records an upgrade in progress, so an upgrade
+/// that was interrupted continues where it stopped when run again.
+pub fn upgrade(
+    repo_path: &Path,
+    target_version: u32,
+    options: &UpgradeOptions,
+) -> Result<UpgradeReport, UpgradeError> {
+    if target_version > CURRENT_FORMAT_VERSION {
+        return Err(UpgradeError::UnsupportedVersion(target_version));
+    }
+    let _lock = FileLock::lock(repo_path.join("upgrade.lock"));
+    let current = read_format_version(repo_path)?;
+    if current > CURRENT_FORMAT_VERSION {
+        return Err(UpgradeError::UnsupportedVersion(current));
+    }
+    if current > target_version {
+        return Err(UpgradeError::Downgrade {
+            current,
+            target: target_version,
+        });
+    }
+    let journal_path = repo_path.join(UPGRADE_JOURNAL_FILE);
+    let mut report = UpgradeReport {
+        from_version: current,
+        to_version: current,
+        migrations: vec![],
+        resumed: journal_path.exists(),
+    };
+    let context = MigrationContext { repo_path, options };
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| (current..target_version).contains(&migration.from_version))
+    {
+        let to_version = migration.from_version + 1;
+        write_atomically(&journal_path, to_version.to_string().as_bytes())?;
+        (migration.run)(&context)?;
+        context.fail_point("before-version-update")?;
+        write_format_version(repo_path, to_version)?;
+        report.to_version = to_version;
+        report.migrations.push(migration.description);
+    }
+    match fs::remove_file(&journal_path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(PathError {
+                path: journal_path,
+                error: err,
+            }
+            .into());
+        }
+    }
+    Ok(report)
+}
+
+/// Version 2: both the store and the operation log have a repository id file,
+/// so loading can detect directories mixed up from different repos.
+fn add_repo_ids(context: &MigrationContext) -> Result<(), UpgradeError> {
+    let store_id_path = context.repo_path.join("store").join(REPO_ID_FILE);
+    let op_store_id_path = context.repo_path.join("op_store").join(REPO_ID_FILE);
+    let read_repo_id = |path: &PathBuf| match fs::read_to_string(path) {
+        Ok(id) => Ok(Some(id)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(PathError {
+            path: path.clone(),
+            error: err,
+        }),
+    };
+    match (
+        read_repo_id(&store_id_path)?,
+        read_repo_id(&op_store_id_path)?,
+    ) {
+        // Mismatching ids are reported when loading the repo
+        (Some(_), Some(_)) => {}
+        (Some(repo_id), None) => write_atomically(&op_store_id_path, repo_id.as_bytes())?,
+        (None, Some(repo_id)) => write_atomically(&store_id_path, repo_id.as_bytes())?,
+        (None, None) => {
+            let repo_id = hex::encode(rand::random::<[u8; 16]>());
+            write_atomically(&store_id_path, repo_id.as_bytes())?;
+            context.fail_point("store-repo-id-written")?;
+            write_atomically(&op_store_id_path, repo_id.as_bytes())?;
+        }
+    }
+    Ok(())
+}
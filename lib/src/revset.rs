@@ -43,6 +43,7 @@ pub use crate::revset_parser::{
 };
 use crate::store::Store;
 use crate::str_util::StringPattern;
+use crate::time_util::{DatePattern, DatePatternParseError};
 use crate::{dsl_util, fileset, revset_parser};
 
 /// Error occurred during symbol resolution.
@@ -112,6 +113,8 @@ pub enum RevsetCommitRef {
     Tags,
     GitRefs,
     GitHead,
+    /// Visible commits of the changes with a label matching the pattern.
+    Labels(StringPattern),
 }
 
 /// A custom revset filter expression, defined by an extension.
@@ -132,6 +135,8 @@ pub enum RevsetFilterPredicate {
     Author(StringPattern),
     /// Commits with committer name or email matching the pattern.
     Committer(StringPattern),
+    /// Commits with committer timestamp matching the pattern.
+    CommitterDate(DatePattern),
     /// Commits modifying the paths specified by the fileset.
     File(FilesetExpression),
     /// Commits with conflicts
@@ -177,6 +182,11 @@ pub enum RevsetExpression {
         candidates: Rc<RevsetExpression>,
         count: usize,
     },
+    /// The first `count` commits of `candidates` in evaluation order.
+    Limit {
+        candidates: Rc<RevsetExpression>,
+        count: usize,
+    },
     Filter(RevsetFilterPredicate),
     /// Marker for subtree that should be intersected as filter.
     AsFilter(Rc<RevsetExpression>),
@@ -263,6 +273,12 @@ impl RevsetExpression {
         Rc::new(RevsetExpression::CommitRef(RevsetCommitRef::GitHead))
     }
 
+    pub fn labels(pattern: StringPattern) -> Rc<RevsetExpression> {
+        Rc::new(RevsetExpression::CommitRef(RevsetCommitRef::Labels(
+            pattern,
+        )))
+    }
+
     pub fn latest(self: &Rc<RevsetExpression>, count: usize) -> Rc<RevsetExpression> {
         Rc::new(RevsetExpression::Latest {
             candidates: self.clone(),
@@ -270,6 +286,15 @@ impl RevsetExpression {
         })
     }
 
+    /// The first `count` commits of `self` in evaluation order. Unlike
+    /// `latest()`, this doesn't evaluate the whole of `self`.
+    pub fn limit(self: &Rc<RevsetExpression>, count: usize) -> Rc<RevsetExpression> {
+        Rc::new(RevsetExpression::Limit {
+            candidates: self.clone(),
+            count,
+        })
+    }
+
     pub fn filter(predicate: RevsetFilterPredicate) -> Rc<RevsetExpression> {
         Rc::new(RevsetExpression::Filter(predicate))
     }
@@ -519,6 +544,10 @@ pub enum ResolvedExpression {
         candidates: Box<ResolvedExpression>,
         count: usize,
     },
+    Limit {
+        candidates: Box<ResolvedExpression>,
+        count: usize,
+    },
     Union(Box<ResolvedExpression>, Box<ResolvedExpression>),
     /// Intersects `candidates` with `predicate` by filtering.
     FilterWithin {
@@ -649,6 +678,11 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
         function.expect_no_arguments()?;
         Ok(RevsetExpression::git_head())
     });
+    map.insert("label", |function, _context| {
+        let [arg] = function.expect_exact_arguments()?;
+        let pattern = expect_string_pattern(arg)?;
+        Ok(RevsetExpression::labels(pattern))
+    });
     map.insert("latest", |function, context| {
         let ([candidates_arg], [count_opt_arg]) = function.expect_arguments()?;
         let candidates = lower_expression(candidates_arg, context)?;
@@ -659,6 +693,12 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
         };
         Ok(candidates.latest(count))
     });
+    map.insert("limit", |function, context| {
+        let [candidates_arg, count_arg] = function.expect_exact_arguments()?;
+        let candidates = lower_expression(candidates_arg, context)?;
+        let count = expect_literal("integer", count_arg)?;
+        Ok(candidates.limit(count))
+    });
     map.insert("merges", |function, _context| {
         function.expect_no_arguments()?;
         Ok(RevsetExpression::filter(
@@ -695,6 +735,13 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
             pattern,
         )))
     });
+    map.insert("committer_date", |function, _context| {
+        let [arg] = function.expect_exact_arguments()?;
+        let pattern = expect_date_pattern(arg)?;
+        Ok(RevsetExpression::filter(
+            RevsetFilterPredicate::CommitterDate(pattern),
+        ))
+    });
     map.insert("empty", |function, _context| {
         function.expect_no_arguments()?;
         Ok(RevsetExpression::is_empty())
@@ -749,6 +796,14 @@ pub fn expect_string_pattern(node: &ExpressionNode) -> Result<StringPattern, Rev
     revset_parser::expect_pattern_with("string pattern", node, parse_pattern)
 }
 
+fn expect_date_pattern(node: &ExpressionNode) -> Result<DatePattern, RevsetParseError> {
+    let parse_pattern = |value: &str, kind: Option<&str>| match kind {
+        Some(kind) => DatePattern::from_str_kind(value, kind),
+        None => Err(DatePatternParseError::MissingKind),
+    };
+    revset_parser::expect_pattern_with("date pattern", node, parse_pattern)
+}
+
 fn parse_remote_branches_arguments(
     function: &FunctionCallNode,
     remote_ref_state: Option<RemoteRefState>,
@@ -980,6 +1035,11 @@ fn try_transform_expression<E>(
                     candidates,
                     count: *count,
                 }),
+            RevsetExpression::Limit { candidates, count } => transform_rec(candidates, pre, post)?
+                .map(|candidates| RevsetExpression::Limit {
+                    candidates,
+                    count: *count,
+                }),
             RevsetExpression::Filter(_) => None,
             RevsetExpression::AsFilter(candidates) => {
                 transform_rec(candidates, pre, post)?.map(RevsetExpression::AsFilter)
@@ -1615,7 +1675,7 @@ fn resolve_commit_ref(
             let wc_commits = repo.view().wc_commit_ids().values().cloned().collect_vec();
             Ok(wc_commits)
         }
-        RevsetCommitRef::VisibleHeads => Ok(repo.view().heads().iter().cloned().collect_vec()),
+        RevsetCommitRef::VisibleHeads => Ok(repo.view().sorted_heads()),
         RevsetCommitRef::Root => Ok(vec![repo.store().root_commit_id().clone()]),
         RevsetCommitRef::Branches(pattern) => {
             let commit_ids = repo
@@ -1669,6 +1729,15 @@ fn resolve_commit_ref(
             Ok(commit_ids)
         }
         RevsetCommitRef::GitHead => Ok(repo.view().git_head().added_ids().cloned().collect()),
+        RevsetCommitRef::Labels(pattern) => {
+            let commit_ids = repo
+                .view()
+                .changes_labeled(pattern)
+                .filter_map(|change_id| repo.resolve_change_id(change_id))
+                .flatten()
+                .collect();
+            Ok(commit_ids)
+        }
     }
 }
 
@@ -1724,7 +1793,7 @@ fn resolve_visibility(repo: &dyn Repo, expression: &RevsetExpression) -> Resolve
     // `RevsetExpression::WithinOperation(visible_heads, expression)` node to
     // evaluate filter predicates and "all()" against that scope.
     let context = VisibilityResolutionContext {
-        visible_heads: &repo.view().heads().iter().cloned().collect_vec(),
+        visible_heads: &repo.view().sorted_heads(),
     };
     context.resolve(expression)
 }
@@ -1783,6 +1852,10 @@ impl VisibilityResolutionContext<'_> {
                 candidates: self.resolve(candidates).into(),
                 count: *count,
             },
+            RevsetExpression::Limit { candidates, count } => ResolvedExpression::Limit {
+                candidates: self.resolve(candidates).into(),
+                count: *count,
+            },
             RevsetExpression::Filter(_) | RevsetExpression::AsFilter(_) => {
                 // Top-level filter without intersection: e.g. "~author(_)" is represented as
                 // `AsFilter(NotIn(Filter(Author(_))))`.
@@ -1860,7 +1933,8 @@ impl VisibilityResolutionContext<'_> {
             | RevsetExpression::Reachable { .. }
             | RevsetExpression::Heads(_)
             | RevsetExpression::Roots(_)
-            | RevsetExpression::Latest { .. } => {
+            | RevsetExpression::Latest { .. }
+            | RevsetExpression::Limit { .. } => {
                 ResolvedPredicateExpression::Set(self.resolve(expression).into())
             }
             RevsetExpression::Filter(predicate) => {
@@ -2507,6 +2581,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_date_pattern() {
+        let settings = insta_settings();
+        let _guard = settings.bind_to_scope();
+
+        insta::assert_debug_snapshot!(
+            parse(r#"committer_date(after:"1970-01-01T00:00:01Z")"#).unwrap(),
+            @"Filter(CommitterDate(AtOrAfter(MillisSinceEpoch(1000))))");
+        insta::assert_debug_snapshot!(
+            parse(r#"committer_date(before:"1970-01-01T01:00:00+01:00")"#).unwrap(),
+            @"Filter(CommitterDate(Before(MillisSinceEpoch(0))))");
+        insta::assert_debug_snapshot!(
+            parse(r#"committer_date("1970-01-01")"#).unwrap_err().kind(),
+            @r###"Expression("Invalid date pattern")"###);
+        insta::assert_debug_snapshot!(
+            parse(r#"committer_date(after:"yesterday")"#).unwrap_err().kind(),
+            @r###"Expression("Invalid date pattern")"###);
+        insta::assert_debug_snapshot!(
+            parse(r#"committer_date(after:x|y)"#).unwrap_err().kind(),
+            @r###"Expression("Expected expression of date pattern")"###);
+    }
+
     #[test]
     fn test_parse_revset_function() {
         let settings = insta_settings();
@@ -2809,6 +2905,14 @@ mod tests {
         }
         "###);
 
+        insta::assert_debug_snapshot!(
+            optimize(parse("limit(branches() & all(), 2)").unwrap()), @r###"
+        Limit {
+            candidates: CommitRef(Branches(Substring(""))),
+            count: 2,
+        }
+        "###);
+
         insta::assert_debug_snapshot!(
             optimize(parse("present(foo ~ bar)").unwrap()), @r###"
         Present(
@@ -15,6 +15,7 @@
 #![allow(missing_docs)]
 
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use std::sync::Arc;
 
 use futures::StreamExt;
@@ -22,7 +23,9 @@ use itertools::Itertools;
 use pollster::FutureExt;
 use tracing::instrument;
 
-use crate::backend::{BackendError, BackendResult, CommitId, MergedTreeId};
+use crate::backend::{
+    BackendError, BackendResult, CommitId, MergedTreeId, MillisSinceEpoch, Timestamp,
+};
 use crate::commit::Commit;
 use crate::commit_builder::CommitBuilder;
 use crate::index::Index;
@@ -30,6 +33,7 @@ use crate::matchers::{Matcher, Visit};
 use crate::merged_tree::{MergedTree, MergedTreeBuilder};
 use crate::repo::{MutableRepo, Repo};
 use crate::repo_path::RepoPath;
+use crate::revset::{RevsetEvaluationError, RevsetExpression, RevsetIteratorExt as _};
 use crate::settings::UserSettings;
 use crate::store::Store;
 
@@ -348,6 +352,162 @@ pub struct RebaseOptions {
     pub simplify_ancestor_merge: bool,
 }
 
+/// A commit visited by [`MutableRepo::transform_stack()`].
+#[derive(Clone, Debug)]
+pub struct StackCommit {
+    /// The commit as it was before the call.
+    pub old_commit: Commit,
+    /// The parents the commit will be rewritten onto, after remapping the
+    /// rewritten and dropped parents in the stack.
+    pub new_parent_ids: Vec<CommitId>,
+}
+
+/// What [`MutableRepo::transform_stack()`] should do with a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackAction {
+    /// Keep the commit, rebasing it if its parents changed.
+    Keep,
+    /// Rebase the commit and set its description.
+    Reword(String),
+    /// Abandon the commit. Its children are moved onto its parents.
+    Drop,
+    /// Move the commit onto its new parents with the given tree, without
+    /// merging in any changes from the parents.
+    ReplaceTree(MergedTreeId),
+}
+
+/// The outcome of [`MutableRepo::transform_stack()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackReport {
+    /// Old and new ids of the rewritten commits.
+    pub rewritten: HashMap<CommitId, CommitId>,
+    /// The dropped commits, parents first.
+    pub dropped: Vec<CommitId>,
+    /// The commits left unchanged, parents first.
+    pub unchanged: Vec<CommitId>,
+}
+
+/// Which timestamps [`normalize_timestamps()`] considers invalid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimestampPolicy {
+    /// Timestamps must not decrease along first-parent chains.
+    pub monotonic: bool,
+    /// The committer timestamp must not be earlier than the author timestamp.
+    pub committer_after_author: bool,
+    /// Earlier timestamps are raised to this one.
+    pub min: Option<MillisSinceEpoch>,
+    /// Later timestamps are lowered to this one.
+    pub max: Option<MillisSinceEpoch>,
+}
+
+/// The outcome of [`normalize_timestamps()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NormalizeTimestampsReport {
+    /// Old and new ids of the rewritten commits, including the ones rewritten
+    /// only because an ancestor was.
+    pub rewritten: HashMap<CommitId, CommitId>,
+    /// The old ids of the commits whose timestamps were changed, parents
+    /// first.
+    pub corrected: Vec<CommitId>,
+}
+
+fn normalize_timestamp(
+    timestamp: &Timestamp,
+    floor: Option<MillisSinceEpoch>,
+    policy: &TimestampPolicy,
+) -> Timestamp {
+    let mut millis = timestamp.timestamp;
+    if let Some(min) = policy.min {
+        millis = millis.max(min);
+    }
+    if let Some(floor) = floor {
+        millis = millis.max(floor);
+    }
+    // The upper bound wins over the parent's timestamp, which may only be
+    // later if the parent is outside the range.
+    if let Some(max) = policy.max {
+        millis = millis.min(max);
+    }
+    Timestamp {
+        timestamp: millis,
+        tz_offset: timestamp.tz_offset,
+    }
+}
+
+/// Rewrites the commits in `range` whose timestamps violate `policy`, e.g.
+/// history imported with timestamps at the epoch.
+///
+/// A timestamp is corrected to the nearest valid one, so the result depends
+/// only on the commits: it's first raised to the policy's lower bound and to
+/// the corresponding timestamp of the (corrected) first parent, then lowered to
+/// the policy's upper bound, and finally the committer timestamp is raised to
+/// the author timestamp. The result never exceeds the upper bound. The time
+/// zone offsets are kept. Commits in the range whose parents were rewritten are
+/// rebased, and the other commits keep their ids. Change ids are preserved.
+///
+/// Descendants outside the range are left for
+/// [`MutableRepo::rebase_descendants()`] or [`MutableRepo::evolve()`].
+pub fn normalize_timestamps(
+    settings: &UserSettings,
+    mut_repo: &mut MutableRepo,
+    range: &Rc<RevsetExpression>,
+    policy: &TimestampPolicy,
+) -> BackendResult<NormalizeTimestampsReport> {
+    let store = mut_repo.store().clone();
+    let revset = range
+        .clone()
+        .evaluate_programmatic(mut_repo)
+        .map_err(|err| match err {
+            RevsetEvaluationError::StoreError(err) => err,
+            err @ RevsetEvaluationError::Other(_) => BackendError::Other(err.into()),
+        })?;
+    let mut to_visit: Vec<Commit> = revset.iter().commits(&store).try_collect()?;
+    drop(revset);
+    let mut report = NormalizeTimestampsReport::default();
+    // The revset yields children before parents.
+    while let Some(old_commit) = to_visit.pop() {
+        let new_parent_ids = mut_repo.new_parents(old_commit.parent_ids().to_vec());
+        let first_parent = store.get_commit(&new_parent_ids[0])?;
+        let (author_floor, committer_floor) =
+            if policy.monotonic && first_parent.id() != store.root_commit_id() {
+                (
+                    Some(first_parent.author().timestamp.timestamp),
+                    Some(first_parent.committer().timestamp.timestamp),
+                )
+            } else {
+                (None, None)
+            };
+        let mut author = old_commit.author().clone();
+        author.timestamp = normalize_timestamp(&author.timestamp, author_floor, policy);
+        let mut committer = old_commit.committer().clone();
+        committer.timestamp = normalize_timestamp(&committer.timestamp, committer_floor, policy);
+        if policy.committer_after_author
+            && committer.timestamp.timestamp < author.timestamp.timestamp
+        {
+            committer.timestamp.timestamp = author.timestamp.timestamp;
+        }
+        let timestamps_changed =
+            author != *old_commit.author() || committer != *old_commit.committer();
+        if !timestamps_changed && new_parent_ids == old_commit.parent_ids() {
+            continue;
+        }
+        let rewriter = CommitRewriter::new(mut_repo, old_commit, new_parent_ids);
+        let old_commit_id = rewriter.old_commit().id().clone();
+        let new_commit = rewriter
+            .rebase(settings)?
+            .set_author(author)
+            .set_committer(committer)
+            .write()?;
+        if timestamps_changed {
+            report.corrected.push(old_commit_id.clone());
+        }
+        report
+            .rewritten
+            .insert(old_commit_id, new_commit.id().clone());
+    }
+    Ok(report)
+}
+
 pub(crate) struct DescendantRebaser<'settings, 'repo> {
     settings: &'settings UserSettings,
     mut_repo: &'repo mut MutableRepo,
@@ -0,0 +1,122 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scratch files and directories, e.g. for inputs of external tools.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+use tempfile::{NamedTempFile, TempDir};
+
+/// How long an entry of the scratch directory of a repo must have been left
+/// unmodified before it's removed when the repo is loaded.
+pub const STALE_SCRATCH_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Prefix of the names of all scratch files and directories. Only entries
+/// with this prefix are removed as stale, in case the scratch directory is
+/// shared with other programs.
+const SCRATCH_PREFIX: &str = "jj-";
+
+/// A directory for scratch files and directories, which are deleted when
+/// dropped. The directory is created along with the first of them.
+///
+/// Files that are renamed into place once written, like objects in the store
+/// or files in the working copy, are never created here, but next to their
+/// destination, so that the rename doesn't cross file systems.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    /// Creates scratch files in `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ScratchDir { path: path.into() }
+    }
+
+    /// The temp directory of the OS.
+    pub fn os_temp() -> Self {
+        Self::new(std::env::temp_dir())
+    }
+
+    /// Returns the directory configured with `core.scratch-dir`, if any.
+    pub fn from_config(config: &config::Config) -> Result<Option<Self>, config::ConfigError> {
+        match config.get_string("core.scratch-dir") {
+            Ok(path) => Ok(Some(Self::new(path))),
+            Err(config::ConfigError::NotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The directory the scratch files are created in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates a scratch file named with the given prefix and suffix.
+    pub fn new_file(&self, prefix: &str, suffix: &str) -> io::Result<NamedTempFile> {
+        fs::create_dir_all(&self.path)?;
+        tempfile::Builder::new()
+            .prefix(&format!("{SCRATCH_PREFIX}{prefix}"))
+            .suffix(suffix)
+            .tempfile_in(&self.path)
+    }
+
+    /// Creates a scratch directory named with the given prefix. It's deleted
+    /// along with its contents.
+    pub fn new_dir(&self, prefix: &str) -> io::Result<TempDir> {
+        fs::create_dir_all(&self.path)?;
+        tempfile::Builder::new()
+            .prefix(&format!("{SCRATCH_PREFIX}{prefix}"))
+            .tempdir_in(&self.path)
+    }
+
+    /// Removes the scratch files and directories that haven't been modified
+    /// for `max_age`, as left behind by processes that were killed. Returns
+    /// how many were removed.
+    pub fn remove_stale(&self, max_age: Duration) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        let now = SystemTime::now();
+        let mut num_removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(SCRATCH_PREFIX)
+            {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let is_stale = now
+                .duration_since(metadata.modified()?)
+                .is_ok_and(|age| age > max_age);
+            if !is_stale {
+                continue;
+            }
+            if metadata.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+            num_removed += 1;
+        }
+        Ok(num_removed)
+    }
+}
@@ -16,15 +16,16 @@
 
 use std::any::Any;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use async_trait::async_trait;
 use futures::stream::BoxStream;
 
 use crate::backend::{
-    Backend, BackendError, BackendLoadError, BackendResult, ChangeId, Commit, CommitId, Conflict,
-    ConflictId, CopyRecord, FileId, SigningFn, SymlinkId, Tree, TreeId,
+    Backend, BackendError, BackendLoadError, BackendResult, ChangeId, Commit, CommitId,
+    CompactOptions, CompactStats, Conflict, ConflictId, CopyRecord, FileId, SigningFn, SymlinkId,
+    Tree, TreeId,
 };
 use crate::git_backend::GitBackend;
 use crate::index::Index;
@@ -180,4 +181,12 @@ impl Backend for SecretBackend {
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         self.inner.gc(index, keep_newer)
     }
+
+    fn compact(&self, options: &CompactOptions) -> BackendResult<CompactStats> {
+        self.inner.compact(options)
+    }
+
+    fn add_alternates(&self, object_dirs: &[PathBuf]) -> BackendResult<()> {
+        self.inner.add_alternates(object_dirs)
+    }
 }
@@ -22,8 +22,10 @@ use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
 
 use crate::backend::{ChangeId, Commit, Signature, Timestamp};
+use crate::files::{ContentType, ContentTypes, MergeDriver, MergeDrivers};
 use crate::fmt_util::binary_prefix;
 use crate::fsmonitor::FsmonitorSettings;
+use crate::scratch_dir::ScratchDir;
 use crate::signing::SignBehavior;
 
 #[derive(Debug, Clone)]
@@ -35,7 +37,33 @@ pub struct UserSettings {
 
 #[derive(Debug, Clone)]
 pub struct RepoSettings {
-    _config: config::Config,
+    config: config::Config,
+    scratch_dir: ScratchDir,
+}
+
+/// A `snapshot.split-rules` entry: changes to paths matching the glob
+/// `pattern` are snapshotted into a separate commit with `description`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct SnapshotSplitRule {
+    pub pattern: String,
+    pub description: String,
+}
+
+/// A `merge.drivers` entry: conflicting contents of files matching the glob
+/// `pattern` are merged with `driver`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct MergeDriverRule {
+    pub pattern: String,
+    pub driver: MergeDriver,
+}
+
+/// A `files.content-types` entry: files matching the glob `pattern` are
+/// treated as `type` instead of detecting it from their contents.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct ContentTypeRule {
+    pub pattern: String,
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
 }
 
 #[derive(Debug, Clone)]
@@ -138,9 +166,16 @@ impl UserSettings {
 
     // TODO: Reconsider UserSettings/RepoSettings abstraction. See
     // https://github.com/martinvonz/jj/issues/616#issuecomment-1345170699
-    pub fn with_repo(&self, _repo_path: &Path) -> Result<RepoSettings, config::ConfigError> {
+    pub fn with_repo(&self, repo_path: &Path) -> Result<RepoSettings, config::ConfigError> {
         let config = self.config.clone();
-        Ok(RepoSettings { _config: config })
+        let scratch_dir = match ScratchDir::from_config(&config)? {
+            Some(scratch_dir) => scratch_dir,
+            None => ScratchDir::new(repo_path.with_file_name("tmp")),
+        };
+        Ok(RepoSettings {
+            config,
+            scratch_dir,
+        })
     }
 
     pub fn get_rng(&self) -> Arc<JJRng> {
@@ -204,6 +239,29 @@ impl UserSettings {
         self.config.get_string("revsets.log").unwrap_or_default()
     }
 
+    /// The maximum number of commits to remember in each workspace's checkout
+    /// history.
+    pub fn checkout_history_size(&self) -> usize {
+        self.config
+            .get::<usize>("ui.checkout-history-size")
+            .unwrap_or(10)
+    }
+
+    /// The number of operations that metadata of a change is kept for after
+    /// the change lost its last visible commit.
+    pub fn change_metadata_retention(&self) -> u64 {
+        self.config
+            .get::<u64>("change-metadata.retention")
+            .unwrap_or(100)
+    }
+
+    /// Whether rewritten commits keep the change id of their predecessor and
+    /// record it, so they make it obsolete. Without it, each commit starts a
+    /// new change, as in a plain DAG of commits.
+    pub fn evolution_enabled(&self) -> bool {
+        evolution_enabled(&self.config)
+    }
+
     pub fn signature(&self) -> Signature {
         let timestamp = self.timestamp.clone().unwrap_or_else(Timestamp::now);
         Signature {
@@ -262,6 +320,56 @@ impl UserSettings {
         }
     }
 
+    pub fn snapshot_split_rules(&self) -> Result<Vec<SnapshotSplitRule>, config::ConfigError> {
+        Ok(self
+            .config
+            .get("snapshot.split-rules")
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    pub fn merge_drivers(&self) -> Result<MergeDrivers, config::ConfigError> {
+        let rules: Vec<MergeDriverRule> = self
+            .config
+            .get("merge.drivers")
+            .optional()?
+            .unwrap_or_default();
+        let rules: Vec<_> = rules
+            .into_iter()
+            .map(|rule| {
+                let pattern = glob::Pattern::new(&rule.pattern).map_err(|err| {
+                    config::ConfigError::Message(format!(
+                        "Invalid merge.drivers pattern {:?}: {err}",
+                        rule.pattern
+                    ))
+                })?;
+                Ok((pattern, rule.driver))
+            })
+            .collect::<Result<_, config::ConfigError>>()?;
+        Ok(MergeDrivers::new(rules))
+    }
+
+    pub fn content_types(&self) -> Result<ContentTypes, config::ConfigError> {
+        let rules: Vec<ContentTypeRule> = self
+            .config
+            .get("files.content-types")
+            .optional()?
+            .unwrap_or_default();
+        let rules: Vec<_> = rules
+            .into_iter()
+            .map(|rule| {
+                let pattern = glob::Pattern::new(&rule.pattern).map_err(|err| {
+                    config::ConfigError::Message(format!(
+                        "Invalid files.content-types pattern {:?}: {err}",
+                        rule.pattern
+                    ))
+                })?;
+                Ok((pattern, rule.content_type))
+            })
+            .collect::<Result<_, config::ConfigError>>()?;
+        Ok(ContentTypes::new(rules))
+    }
+
     // separate from sign_settings as those two are needed in pretty different
     // places
     pub fn signing_backend(&self) -> Option<String> {
@@ -281,6 +389,24 @@ impl UserSettings {
     }
 }
 
+impl RepoSettings {
+    /// Like [`UserSettings::evolution_enabled()`], as configured when the repo
+    /// was loaded.
+    pub fn evolution_enabled(&self) -> bool {
+        evolution_enabled(&self.config)
+    }
+
+    /// Where scratch files are created: `core.scratch-dir` if set, otherwise
+    /// `.jj/tmp`.
+    pub fn scratch_dir(&self) -> &ScratchDir {
+        &self.scratch_dir
+    }
+}
+
+fn evolution_enabled(config: &config::Config) -> bool {
+    config.get_bool("evolution.enabled").unwrap_or(true)
+}
+
 /// This Rng uses interior mutability to allow generating random values using an
 /// immutable reference. It also fixes a specific seedable RNG for
 /// reproducibility.
@@ -20,21 +20,21 @@ use std::fmt::Debug;
 use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
-use std::{fs, io};
+use std::{fs, io, iter};
 
 use itertools::Itertools as _;
 use prost::Message;
 use tempfile::NamedTempFile;
 use thiserror::Error;
 
-use crate::backend::{CommitId, MillisSinceEpoch, Timestamp};
+use crate::backend::{ChangeId, CommitId, MillisSinceEpoch, Timestamp};
 use crate::content_hash::blake2b_hash;
 use crate::file_util::{persist_content_addressed_temp_file, IoResultExt as _, PathError};
 use crate::merge::Merge;
 use crate::object_id::{HexPrefix, ObjectId, PrefixResolution};
 use crate::op_store::{
-    OpStore, OpStoreError, OpStoreResult, Operation, OperationId, OperationMetadata, RefTarget,
-    RemoteRef, RemoteRefState, RemoteView, View, ViewId, WorkspaceId,
+    ChangeMetadata, OpStore, OpStoreError, OpStoreResult, Operation, OperationId,
+    OperationMetadata, RefTarget, RemoteRef, RemoteRefState, RemoteView, View, ViewId, WorkspaceId,
 };
 use crate::{dag_walk, op_store};
 
@@ -92,6 +92,13 @@ impl SimpleOpStore {
     fn operation_path(&self, id: &OperationId) -> PathBuf {
         self.path.join("operations").join(id.hex())
     }
+
+    /// The file recording the last truncation: the id of the tombstone
+    /// operation on the first line, followed by the ids of the operations it
+    /// replaced, one per line.
+    fn truncation_path(&self) -> PathBuf {
+        self.path.join("truncated_operations")
+    }
 }
 
 impl OpStore for SimpleOpStore {
@@ -226,6 +233,47 @@ impl OpStore for SimpleOpStore {
             .map_err(|err| OpStoreError::Other(err.into()))
     }
 
+    fn record_truncation(
+        &self,
+        tombstone_id: &OperationId,
+        removed_ids: &[OperationId],
+    ) -> OpStoreResult<()> {
+        let contents = iter::once(tombstone_id)
+            .chain(removed_ids)
+            .map(|id| id.hex() + "\n")
+            .join("");
+        let path = self.truncation_path();
+        let write = || -> io::Result<()> {
+            let mut temp_file = NamedTempFile::new_in(&self.path)?;
+            temp_file.write_all(contents.as_bytes())?;
+            temp_file.persist(&path)?;
+            Ok(())
+        };
+        write()
+            .context(&path)
+            .map_err(|err| OpStoreError::Other(err.into()))
+    }
+
+    fn find_truncating_tombstone(&self, prefix: &HexPrefix) -> OpStoreResult<Option<OperationId>> {
+        let path = self.truncation_path();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(OpStoreError::Other(PathError { path, error: err }.into())),
+        };
+        let mut lines = contents.lines();
+        let Some(tombstone_id) = lines
+            .next()
+            .and_then(|hex| OperationId::try_from_hex(hex).ok())
+        else {
+            return Ok(None);
+        };
+        let is_removed = lines
+            .filter_map(|hex| OperationId::try_from_hex(hex).ok())
+            .any(|id| prefix.matches(&id));
+        Ok(is_removed.then_some(tombstone_id))
+    }
+
     #[tracing::instrument(skip(self))]
     fn gc(&self, head_ids: &[OperationId], keep_newer: SystemTime) -> OpStoreResult<()> {
         let to_op_id = |entry: &fs::DirEntry| -> Option<OperationId> {
@@ -411,9 +459,30 @@ fn view_to_proto(view: &View) -> crate::protos::op_store::View {
             .wc_commit_ids
             .insert(workspace_id.as_str().to_string(), commit_id.to_bytes());
     }
+    for (workspace_id, commit_ids) in &view.checkout_history {
+        proto.checkout_history.insert(
+            workspace_id.as_str().to_string(),
+            crate::protos::op_store::CheckoutHistory {
+                commit_ids: commit_ids.iter().map(|id| id.to_bytes()).collect(),
+            },
+        );
+    }
     for head_id in &view.head_ids {
         proto.head_ids.push(head_id.to_bytes());
     }
+    for (change_id, metadata) in &view.change_metadata {
+        proto
+            .change_metadata
+            .push(crate::protos::op_store::ChangeMetadata {
+                change_id: change_id.to_bytes(),
+                values: metadata.values.clone().into_iter().collect(),
+                operations_without_commits: metadata.operations_without_commits,
+                labels: metadata.labels.iter().cloned().collect(),
+            });
+    }
+    for change_id in &view.accepted_divergences {
+        proto.accepted_divergences.push(change_id.to_bytes());
+    }
 
     proto.branches = branch_views_to_proto_legacy(&view.local_branches, &view.remote_views);
 
@@ -450,9 +519,28 @@ fn view_from_proto(proto: crate::protos::op_store::View) -> View {
         view.wc_commit_ids
             .insert(WorkspaceId::new(workspace_id), CommitId::new(commit_id));
     }
+    for (workspace_id, history) in proto.checkout_history {
+        let commit_ids = history.commit_ids.into_iter().map(CommitId::new).collect();
+        view.checkout_history
+            .insert(WorkspaceId::new(workspace_id), commit_ids);
+    }
     for head_id_bytes in proto.head_ids {
         view.head_ids.insert(CommitId::new(head_id_bytes));
     }
+    for metadata in proto.change_metadata {
+        view.change_metadata.insert(
+            ChangeId::new(metadata.change_id),
+            ChangeMetadata {
+                labels: metadata.labels.into_iter().collect(),
+                values: metadata.values.into_iter().collect(),
+                operations_without_commits: metadata.operations_without_commits,
+            },
+        );
+    }
+    for change_id_bytes in proto.accepted_divergences {
+        view.accepted_divergences
+            .insert(ChangeId::new(change_id_bytes));
+    }
 
     let (local_branches, remote_views) = branch_views_from_proto_legacy(proto.branches);
     view.local_branches = local_branches;
@@ -693,7 +781,7 @@ fn remote_ref_state_from_proto(proto_value: Option<i32>) -> Option<RemoteRefStat
 mod tests {
     use insta::assert_snapshot;
     use itertools::Itertools as _;
-    use maplit::{btreemap, hashmap, hashset};
+    use maplit::{btreemap, btreeset, hashmap, hashset};
 
     use super::*;
 
@@ -741,9 +829,31 @@ mod tests {
             },
             git_head: RefTarget::normal(CommitId::from_hex("fff111")),
             wc_commit_ids: hashmap! {
-                WorkspaceId::default() => default_wc_commit_id,
-                WorkspaceId::new("test".to_string()) => test_wc_commit_id,
+                WorkspaceId::default() => default_wc_commit_id.clone(),
+                WorkspaceId::new("test".to_string()) => test_wc_commit_id.clone(),
+            },
+            checkout_history: hashmap! {
+                WorkspaceId::default() => vec![
+                    default_wc_commit_id,
+                    CommitId::from_hex("bcd111"),
+                ],
+                WorkspaceId::new("test".to_string()) => vec![test_wc_commit_id],
+            },
+            change_metadata: btreemap! {
+                ChangeId::from_hex("eee111") => ChangeMetadata {
+                    labels: btreeset! {"topic".to_string()},
+                    values: btreemap! {
+                        "review-url".to_string() => "https://example.com/1".to_string(),
+                    },
+                    operations_without_commits: 2,
+                },
+                ChangeId::from_hex("eee222") => ChangeMetadata {
+                    labels: btreeset! {"topic".to_string()},
+                    values: btreemap! {},
+                    operations_without_commits: 0,
+                },
             },
+            accepted_divergences: btreeset! {ChangeId::from_hex("eee333")},
         }
     }
 
@@ -780,7 +890,7 @@ mod tests {
         // Test exact output so we detect regressions in compatibility
         assert_snapshot!(
             ViewId::new(blake2b_hash(&create_view()).to_vec()).hex(),
-            @"f426676b3a2f7c6b9ec8677cb05ed249d0d244ab7e86a7c51117e2d8a4829db65e55970c761231e2107d303bf3d33a1f2afdd4ed2181f223e99753674b20a35e"
+            @"9333f45b10a9550e25c791b96fd44c83c698118e3a97d7083be0fccf4a88e76403024cbb6d8985843cb390f4daceb62ae9dab809a574abcc5a3983c9a4b8b63e"
         );
     }
 
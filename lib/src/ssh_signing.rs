@@ -23,12 +23,14 @@ use std::process::{Command, ExitStatus, Stdio};
 use either::Either;
 use thiserror::Error;
 
+use crate::scratch_dir::ScratchDir;
 use crate::signing::{SigStatus, SignError, SigningBackend, Verification};
 
 #[derive(Debug)]
 pub struct SshBackend {
     program: OsString,
     allowed_signers: Option<OsString>,
+    scratch_dir: ScratchDir,
 }
 
 #[derive(Debug, Error)]
@@ -78,16 +80,18 @@ fn run_command(command: &mut Command, stdin: &[u8]) -> SshResult<Vec<u8>> {
 // This attempts to convert given key data into a file and return the filepath.
 // If the given data is actually already a filepath to a key on disk then the
 // key input is returned directly.
-fn ensure_key_as_file(key: &str) -> SshResult<Either<PathBuf, tempfile::TempPath>> {
+fn ensure_key_as_file(
+    key: &str,
+    scratch_dir: &ScratchDir,
+) -> SshResult<Either<PathBuf, tempfile::TempPath>> {
     let is_inlined_ssh_key = key.starts_with("ssh-");
     if !is_inlined_ssh_key {
         let key_path = Path::new(key);
         return Ok(either::Left(key_path.to_path_buf()));
     }
 
-    let mut pub_key_file = tempfile::Builder::new()
-        .prefix("jj-signing-key-")
-        .tempfile()
+    let mut pub_key_file = scratch_dir
+        .new_file("signing-key-", "")
         .map_err(SshError::Io)?;
 
     pub_key_file
@@ -107,9 +111,17 @@ impl SshBackend {
         Self {
             program,
             allowed_signers,
+            scratch_dir: ScratchDir::os_temp(),
         }
     }
 
+    /// Creates the key and signature files in `scratch_dir` instead of the
+    /// temp directory of the OS.
+    pub fn with_scratch_dir(mut self, scratch_dir: ScratchDir) -> Self {
+        self.scratch_dir = scratch_dir;
+        self
+    }
+
     pub fn from_config(config: &config::Config) -> Self {
         Self::new(
             config
@@ -120,6 +132,12 @@ impl SshBackend {
                 .get_string("signing.backends.ssh.allowed-signers")
                 .map_or(None, |v| Some(v.into())),
         )
+        .with_scratch_dir(
+            ScratchDir::from_config(config)
+                .ok()
+                .flatten()
+                .unwrap_or_else(ScratchDir::os_temp),
+        )
     }
 
     fn create_command(&self) -> Command {
@@ -190,7 +208,7 @@ impl SigningBackend for SshBackend {
         //
         // As it expects a file and we might have an inlined public key instead, we need
         // to ensure it is written to a file first.
-        let pub_key_path = ensure_key_as_file(key)?;
+        let pub_key_path = ensure_key_as_file(key, &self.scratch_dir)?;
         let mut command = self.create_command();
 
         let path = match &pub_key_path {
@@ -210,9 +228,9 @@ impl SigningBackend for SshBackend {
     }
 
     fn verify(&self, data: &[u8], signature: &[u8]) -> Result<Verification, SignError> {
-        let mut signature_file = tempfile::Builder::new()
-            .prefix(".jj-ssh-sig-")
-            .tempfile()
+        let mut signature_file = self
+            .scratch_dir
+            .new_file("ssh-sig-", "")
             .map_err(SshError::Io)?;
         signature_file.write_all(signature).map_err(SshError::Io)?;
         signature_file.flush().map_err(SshError::Io)?;
@@ -279,7 +297,7 @@ mod tests {
     #[test]
     fn test_ssh_key_to_file_conversion_raw_key_data() {
         let keydata = "ssh-ed25519 some-key-data";
-        let path = ensure_key_as_file(keydata).unwrap();
+        let path = ensure_key_as_file(keydata, &ScratchDir::os_temp()).unwrap();
 
         let mut buf = vec![];
         let mut file = File::open(path.right().unwrap()).unwrap();
@@ -301,7 +319,7 @@ mod tests {
 
         let file_path = file.into_temp_path();
 
-        let path = ensure_key_as_file(file_path.to_str().unwrap()).unwrap();
+        let path = ensure_key_as_file(file_path.to_str().unwrap(), &ScratchDir::os_temp()).unwrap();
 
         assert_eq!(
             file_path.to_str().unwrap(),
@@ -15,27 +15,90 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::hash::Hash;
 use std::io::Read;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
+use itertools::Itertools as _;
 use pollster::FutureExt;
+use thiserror::Error;
 
 use crate::backend::{
-    self, Backend, BackendResult, ChangeId, CommitId, ConflictId, FileId, MergedTreeId, SigningFn,
-    SymlinkId, TreeId,
+    self, Backend, BackendError, BackendInitError, BackendResult, ChangeId, CommitId,
+    CompactOptions, CompactStats, ConflictId, FileId, MergedTreeId, SigningFn, SymlinkId, TreeId,
+    TreeValue,
 };
 use crate::commit::Commit;
+use crate::file_util::{IoResultExt as _, PathError};
+use crate::files::{ContentType, ContentTypes, MergeDrivers, CONTENT_TYPE_DETECTION_LEN};
 use crate::index::Index;
 use crate::merge::{Merge, MergedTreeValue};
 use crate::merged_tree::MergedTree;
+use crate::repo::StoreFactories;
 use crate::repo_path::{RepoPath, RepoPathBuf};
-use crate::signing::Signer;
+use crate::settings::UserSettings;
+use crate::signing::{SignInitError, Signer};
 use crate::tree::Tree;
 use crate::tree_builder::TreeBuilder;
 
+/// Maximum number of tree merge results kept by a `Store`. The cache is cleared
+/// when it's full.
+const TREE_MERGE_CACHE_CAPACITY: usize = 10_000;
+
+/// Key of a cached tree merge: the directory and the trees being merged.
+type TreeMergeKey = (RepoPathBuf, Merge<TreeId>);
+
+/// Maximum number of detected file content types kept by a `Store`. The cache
+/// is cleared when it's full.
+const CONTENT_TYPE_CACHE_CAPACITY: usize = 100_000;
+
+/// Error from [`Store::init()`].
+#[derive(Debug, Error)]
+pub enum StoreInitError {
+    #[error("Invalid store URL '{0}', expected '<scheme>:<path>'")]
+    InvalidUrl(String),
+    #[error("Unsupported store URL scheme '{0}'")]
+    UnsupportedScheme(String),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    Backend(#[from] BackendInitError),
+    #[error(transparent)]
+    Config(#[from] config::ConfigError),
+    #[error(transparent)]
+    SignInit(#[from] SignInitError),
+}
+
+/// Whether the error is from reading an object as the wrong kind in a backend
+/// that doesn't report it as a missing object.
+fn is_wrong_kind_error(err: &BackendError) -> bool {
+    match err {
+        #[cfg(feature = "git")]
+        BackendError::ReadObject { source, .. } => {
+            source.is::<gix::object::try_into::Error>()
+                || matches!(
+                    source.downcast_ref::<gix::object::conversion::Error>(),
+                    Some(gix::object::conversion::Error::UnexpectedType { .. })
+                )
+        }
+        _ => false,
+    }
+}
+
+/// Kind of object found by [`Store::object_kind()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Commit,
+    Tree,
+    File,
+}
+
 /// Wraps the low-level backend and makes it return more convenient types. Also
 /// adds caching.
 pub struct Store {
@@ -43,7 +106,14 @@ pub struct Store {
     signer: Signer,
     commit_cache: RwLock<HashMap<CommitId, Arc<backend::Commit>>>,
     tree_cache: RwLock<HashMap<(RepoPathBuf, TreeId), Arc<backend::Tree>>>,
+    tree_merge_cache: Mutex<HashMap<TreeMergeKey, Merge<TreeId>>>,
+    num_tree_merges: AtomicUsize,
+    content_type_cache: Mutex<HashMap<FileId, ContentType>>,
+    num_content_type_detections: AtomicUsize,
     use_tree_conflict_format: bool,
+    merge_drivers: MergeDrivers,
+    content_types: ContentTypes,
+    read_only: bool,
 }
 
 impl Debug for Store {
@@ -59,16 +129,122 @@ impl Store {
         backend: Box<dyn Backend>,
         signer: Signer,
         use_tree_conflict_format: bool,
+        merge_drivers: MergeDrivers,
+        content_types: ContentTypes,
+    ) -> Arc<Self> {
+        Self::new_with_mode(
+            backend,
+            signer,
+            use_tree_conflict_format,
+            merge_drivers,
+            content_types,
+            false,
+        )
+    }
+
+    /// Like `new()`, but the returned store refuses all writes. They panic if
+    /// debug assertions are enabled, and fail otherwise.
+    pub fn new_read_only(
+        backend: Box<dyn Backend>,
+        signer: Signer,
+        use_tree_conflict_format: bool,
+        merge_drivers: MergeDrivers,
+        content_types: ContentTypes,
+    ) -> Arc<Self> {
+        Self::new_with_mode(
+            backend,
+            signer,
+            use_tree_conflict_format,
+            merge_drivers,
+            content_types,
+            true,
+        )
+    }
+
+    fn new_with_mode(
+        backend: Box<dyn Backend>,
+        signer: Signer,
+        use_tree_conflict_format: bool,
+        merge_drivers: MergeDrivers,
+        content_types: ContentTypes,
+        read_only: bool,
     ) -> Arc<Self> {
+        // The root commit is synthesized from the backend's constants, so it's
+        // cached upfront and never has to be read from the backend.
+        let root_commit = backend::make_root_commit(
+            backend.root_change_id().clone(),
+            backend.empty_tree_id().clone(),
+        );
+        let commit_cache =
+            HashMap::from([(backend.root_commit_id().clone(), Arc::new(root_commit))]);
         Arc::new(Store {
             backend,
             signer,
-            commit_cache: Default::default(),
+            commit_cache: RwLock::new(commit_cache),
             tree_cache: Default::default(),
+            tree_merge_cache: Default::default(),
+            num_tree_merges: AtomicUsize::new(0),
+            content_type_cache: Default::default(),
+            num_content_type_detections: AtomicUsize::new(0),
             use_tree_conflict_format,
+            merge_drivers,
+            content_types,
+            read_only,
         })
     }
 
+    /// Initializes a backend at the location given by `url`, which has the
+    /// form `<scheme>:<path>`, and returns a store for it. The backend is
+    /// created by the initializer registered for the scheme in
+    /// `store_factories`. By default, the `local` scheme creates a
+    /// [`LocalBackend`](crate::local_backend::LocalBackend) and the `git`
+    /// scheme a Git backend with an internal repo, in the existing
+    /// directory at the path. A Windows path such as `C:\repo` is rejected
+    /// as missing a scheme.
+    ///
+    /// Like when initializing a repo, the backend type is recorded next to the
+    /// backend, so the store can be loaded with `store_factories`.
+    pub fn init(
+        url: &str,
+        settings: &UserSettings,
+        store_factories: &StoreFactories,
+    ) -> Result<Arc<Self>, StoreInitError> {
+        let Some((scheme, path)) = url.split_once(':').filter(|(scheme, path)| {
+            let is_drive_letter = scheme.len() == 1 && path.starts_with(['\\', '/']);
+            !scheme.is_empty()
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric())
+                && !is_drive_letter
+                && !path.is_empty()
+        }) else {
+            return Err(StoreInitError::InvalidUrl(url.to_owned()));
+        };
+        let store_path = Path::new(path);
+        let backend = store_factories.init_backend(settings, scheme, store_path)?;
+        let type_path = store_path.join("type");
+        fs::write(&type_path, backend.name()).context(&type_path)?;
+        Ok(Store::new(
+            backend,
+            Signer::from_settings(settings)?,
+            settings.use_tree_conflict_format(),
+            settings.merge_drivers()?,
+            settings.content_types()?,
+        ))
+    }
+
+    /// Whether this store was created with `new_read_only()`.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn check_writable(&self) -> BackendResult<()> {
+        if self.read_only {
+            return Err(BackendError::Other(
+                "Cannot write to a read-only store".into(),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn backend_impl(&self) -> &dyn Any {
         self.backend.as_any()
     }
@@ -82,6 +258,16 @@ impl Store {
         self.use_tree_conflict_format
     }
 
+    /// Drivers to merge conflicting file contents.
+    pub fn merge_drivers(&self) -> &MergeDrivers {
+        &self.merge_drivers
+    }
+
+    /// Content types forced by path.
+    pub fn content_types(&self) -> &ContentTypes {
+        &self.content_types
+    }
+
     pub fn commit_id_length(&self) -> usize {
         self.backend.commit_id_length()
     }
@@ -143,6 +329,7 @@ impl Store {
         sign_with: Option<&mut SigningFn>,
     ) -> BackendResult<Commit> {
         assert!(!commit.parents.is_empty());
+        self.check_writable()?;
 
         let (commit_id, commit) = self.backend.write_commit(commit, sign_with)?;
         let data = Arc::new(commit);
@@ -167,6 +354,38 @@ impl Store {
         Ok(Tree::new(self.clone(), dir.to_owned(), id.clone(), data))
     }
 
+    /// Returns the result of a previous merge of the trees `input` in `dir`.
+    pub(crate) fn cached_tree_merge(
+        &self,
+        dir: &RepoPath,
+        input: &Merge<TreeId>,
+    ) -> Option<Merge<TreeId>> {
+        let key = (dir.to_owned(), input.clone());
+        self.tree_merge_cache.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Records the result of merging the trees `input` in `dir`. The order of
+    /// the terms matters, since it determines the order of conflict sides.
+    pub(crate) fn cache_tree_merge(
+        &self,
+        dir: &RepoPath,
+        input: Merge<TreeId>,
+        output: Merge<TreeId>,
+    ) {
+        self.num_tree_merges.fetch_add(1, Ordering::Relaxed);
+        let mut locked_cache = self.tree_merge_cache.lock().unwrap();
+        if locked_cache.len() >= TREE_MERGE_CACHE_CAPACITY {
+            locked_cache.clear();
+        }
+        locked_cache.insert((dir.to_owned(), input), output);
+    }
+
+    /// Number of non-trivial tree merges computed so far, not counting the
+    /// ones served from the cache.
+    pub fn num_tree_merges(&self) -> usize {
+        self.num_tree_merges.load(Ordering::Relaxed)
+    }
+
     async fn get_backend_tree(
         &self,
         dir: &RepoPath,
@@ -186,6 +405,52 @@ impl Store {
         Ok(data)
     }
 
+    /// Probes the backend for a commit, tree, or file with the given id, e.g.
+    /// to tell what an id given by the user refers to. Returns `None` if
+    /// there's no such object.
+    ///
+    /// Trees and files are looked up at the root path, so they're only found
+    /// in backends that don't store objects by path. Symlinks and conflicts
+    /// may be reported as files, since some backends store them the same way.
+    pub fn object_kind(&self, id_bytes: &[u8]) -> BackendResult<Option<ObjectKind>> {
+        // Reading an object as the wrong kind fails like reading a missing one
+        // in some backends, and with a kind mismatch in others. Other errors,
+        // e.g. from corrupt objects, are returned.
+        fn ignore_missing<T>(result: BackendResult<T>) -> BackendResult<bool> {
+            match result {
+                Ok(_) => Ok(true),
+                Err(BackendError::ObjectNotFound { .. }) => Ok(false),
+                Err(err) if is_wrong_kind_error(&err) => Ok(false),
+                Err(err) => Err(err),
+            }
+        }
+
+        if id_bytes.len() != self.commit_id_length() {
+            return Ok(None);
+        }
+        let commit_id = CommitId::from_bytes(id_bytes);
+        if ignore_missing(self.backend.read_commit(&commit_id).block_on())? {
+            return Ok(Some(ObjectKind::Commit));
+        }
+        let tree_id = TreeId::from_bytes(id_bytes);
+        if ignore_missing(
+            self.backend
+                .read_tree(RepoPath::root(), &tree_id)
+                .block_on(),
+        )? {
+            return Ok(Some(ObjectKind::Tree));
+        }
+        let file_id = FileId::from_bytes(id_bytes);
+        if ignore_missing(
+            self.backend
+                .read_file(RepoPath::root(), &file_id)
+                .block_on(),
+        )? {
+            return Ok(Some(ObjectKind::File));
+        }
+        Ok(None)
+    }
+
     pub fn get_root_tree(self: &Arc<Self>, id: &MergedTreeId) -> BackendResult<MergedTree> {
         match &id {
             MergedTreeId::Legacy(id) => {
@@ -204,6 +469,7 @@ impl Store {
         path: &RepoPath,
         tree: backend::Tree,
     ) -> BackendResult<Tree> {
+        self.check_writable()?;
         let tree_id = self.backend.write_tree(path, &tree)?;
         let data = Arc::new(tree);
         {
@@ -227,9 +493,63 @@ impl Store {
     }
 
     pub fn write_file(&self, path: &RepoPath, contents: &mut dyn Read) -> BackendResult<FileId> {
+        self.check_writable()?;
         self.backend.write_file(path, contents)
     }
 
+    /// Classifies the file `id` at `path` as text or binary. A content type
+    /// forced for the path wins. Otherwise, it's detected from the start of
+    /// the file, and the result is cached by file id.
+    pub fn file_content_type(&self, path: &RepoPath, id: &FileId) -> BackendResult<ContentType> {
+        if let Some(content_type) = self.content_types.override_for(path) {
+            return Ok(content_type);
+        }
+        if let Some(&content_type) = self.content_type_cache.lock().unwrap().get(id) {
+            return Ok(content_type);
+        }
+        let mut start = vec![];
+        self.read_file(path, id)?
+            .take(CONTENT_TYPE_DETECTION_LEN as u64)
+            .read_to_end(&mut start)
+            .map_err(|err| BackendError::ReadFile {
+                path: path.to_owned(),
+                id: id.clone(),
+                source: err.into(),
+            })?;
+        let content_type = ContentType::detect(&start);
+        self.num_content_type_detections
+            .fetch_add(1, Ordering::Relaxed);
+        let mut locked_cache = self.content_type_cache.lock().unwrap();
+        if locked_cache.len() >= CONTENT_TYPE_CACHE_CAPACITY {
+            locked_cache.clear();
+        }
+        locked_cache.insert(id.clone(), content_type);
+        Ok(content_type)
+    }
+
+    /// Classifies a possibly conflicted value at `path`. It's binary if any of
+    /// its files is. Symlinks and other values count as text.
+    pub fn value_content_type(
+        &self,
+        path: &RepoPath,
+        value: &MergedTreeValue,
+    ) -> BackendResult<ContentType> {
+        for term in value.iter().flatten() {
+            if let TreeValue::File { id, .. } = term {
+                if self.file_content_type(path, id)? == ContentType::Binary {
+                    return Ok(ContentType::Binary);
+                }
+            }
+        }
+        Ok(ContentType::Text)
+    }
+
+    /// Number of files whose content type was detected from their contents so
+    /// far, not counting the ones served from the cache.
+    pub fn num_content_type_detections(&self) -> usize {
+        self.num_content_type_detections.load(Ordering::Relaxed)
+    }
+
     pub fn read_symlink(&self, path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
         self.read_symlink_async(path, id).block_on()
     }
@@ -243,6 +563,7 @@ impl Store {
     }
 
     pub fn write_symlink(&self, path: &RepoPath, contents: &str) -> BackendResult<SymlinkId> {
+        self.check_writable()?;
         self.backend.write_symlink(path, contents)
     }
 
@@ -260,6 +581,7 @@ impl Store {
         path: &RepoPath,
         contents: &MergedTreeValue,
     ) -> BackendResult<ConflictId> {
+        self.check_writable()?;
         self.backend
             .write_conflict(path, &contents.clone().into_backend_conflict())
     }
@@ -269,6 +591,137 @@ impl Store {
     }
 
     pub fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
+        self.check_writable()?;
         self.backend.gc(index, keep_newer)
     }
+
+    pub fn compact(&self, options: &CompactOptions) -> BackendResult<CompactStats> {
+        self.check_writable()?;
+        self.backend.compact(options)
+    }
+
+    /// Makes objects in the alternate object directories `object_dirs` (such
+    /// as the `objects` directory of another Git repository) readable through
+    /// this store. Writes still go to this store's backend. Nothing is written
+    /// to disk, so the alternates only apply to this instance. Fails with
+    /// `BackendError::Unsupported` if the backend has no alternates.
+    pub fn with_alternates(self: Arc<Self>, object_dirs: &[PathBuf]) -> BackendResult<Arc<Self>> {
+        self.backend.add_alternates(object_dirs)?;
+        Ok(self)
+    }
+
+    /// Checks that the commits reachable from `heads` can be read, and that
+    /// the parents, trees, and legacy conflicts they refer to exist. File and
+    /// symlink contents aren't read. The objects are read directly from the
+    /// backend, bypassing (and not populating) the caches.
+    pub fn fsck(&self, heads: &[CommitId]) -> FsckReport {
+        let mut report = FsckReport::default();
+        let mut visited_commits = HashSet::new();
+        let mut visited_trees = HashSet::new();
+        let mut pending = heads.iter().map(|id| (None, id.clone())).collect_vec();
+        while let Some((child_id, id)) = pending.pop() {
+            if !visited_commits.insert(id.clone()) {
+                continue;
+            }
+            let commit = match (self.backend.read_commit(&id).block_on(), child_id) {
+                (Ok(commit), _) => commit,
+                (Err(BackendError::ObjectNotFound { .. }), Some(child_id)) => {
+                    report.dangling_parents.push((child_id, id));
+                    continue;
+                }
+                (Err(err), _) => {
+                    report.unreadable.push(err);
+                    continue;
+                }
+            };
+            for tree_id in commit.root_tree.to_merge().iter() {
+                self.fsck_tree(
+                    &id,
+                    RepoPath::root(),
+                    tree_id,
+                    &mut visited_trees,
+                    &mut report,
+                );
+            }
+            pending.extend(
+                commit
+                    .parents
+                    .into_iter()
+                    .map(|parent_id| (Some(id.clone()), parent_id)),
+            );
+        }
+        report
+    }
+
+    fn fsck_tree(
+        &self,
+        commit_id: &CommitId,
+        dir: &RepoPath,
+        id: &TreeId,
+        visited: &mut HashSet<TreeId>,
+        report: &mut FsckReport,
+    ) {
+        if !visited.insert(id.clone()) {
+            return;
+        }
+        let tree = match self.backend.read_tree(dir, id).block_on() {
+            Ok(tree) => tree,
+            Err(BackendError::ObjectNotFound { .. }) => {
+                report
+                    .dangling_trees
+                    .push((commit_id.clone(), dir.to_owned(), id.clone()));
+                return;
+            }
+            Err(err) => {
+                report.unreadable.push(err);
+                return;
+            }
+        };
+        for entry in tree.entries() {
+            let path = dir.join(entry.name());
+            match entry.value() {
+                TreeValue::Tree(subtree_id) => {
+                    self.fsck_tree(commit_id, &path, subtree_id, visited, report);
+                }
+                TreeValue::Conflict(conflict_id) => {
+                    match self.backend.read_conflict(&path, conflict_id) {
+                        Ok(_) => {}
+                        Err(BackendError::ObjectNotFound { .. }) => {
+                            report.dangling_conflicts.push((
+                                commit_id.clone(),
+                                path,
+                                conflict_id.clone(),
+                            ));
+                        }
+                        Err(err) => report.unreadable.push(err),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Problems found by [`Store::fsck()`].
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// Errors reading objects other than missing objects, or missing heads.
+    pub unreadable: Vec<BackendError>,
+    /// Commits and their parents that don't exist.
+    pub dangling_parents: Vec<(CommitId, CommitId)>,
+    /// Commits and the trees (and the paths they're at) that don't exist.
+    pub dangling_trees: Vec<(CommitId, RepoPathBuf, TreeId)>,
+    /// Commits and the legacy conflict objects (and the paths they're at) that
+    /// don't exist.
+    pub dangling_conflicts: Vec<(CommitId, RepoPathBuf, ConflictId)>,
+}
+
+impl FsckReport {
+    /// Whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.unreadable.is_empty()
+            && self.dangling_parents.is_empty()
+            && self.dangling_trees.is_empty()
+            && self.dangling_conflicts.is_empty()
+    }
 }
@@ -30,6 +30,9 @@ pub enum StringPatternParseError {
     /// Failed to parse glob pattern.
     #[error(transparent)]
     GlobPattern(glob::PatternError),
+    /// Failed to parse regular expression.
+    #[error(transparent)]
+    Regex(regex::Error),
 }
 
 fn parse_glob(src: &str) -> Result<glob::Pattern, StringPatternParseError> {
@@ -38,7 +41,7 @@ fn parse_glob(src: &str) -> Result<glob::Pattern, StringPatternParseError> {
 
 /// Pattern to be tested against string property like commit description or
 /// branch name.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum StringPattern {
     /// Matches strings exactly.
     Exact(String),
@@ -52,8 +55,32 @@ pub enum StringPattern {
     Glob(glob::Pattern),
     /// Matches with a case‐insensitive Unix‐style shell wildcard pattern.
     GlobI(glob::Pattern),
+    /// Matches substrings with a regular expression.
+    Regex(regex::Regex),
+    /// Matches substrings with a case‐insensitive regular expression.
+    RegexI(regex::Regex),
+}
+
+// `regex::Regex` doesn't implement `Eq`. Compiled regexes are compared by
+// their source.
+impl PartialEq for StringPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StringPattern::Exact(a), StringPattern::Exact(b)) => a == b,
+            (StringPattern::ExactI(a), StringPattern::ExactI(b)) => a == b,
+            (StringPattern::Substring(a), StringPattern::Substring(b)) => a == b,
+            (StringPattern::SubstringI(a), StringPattern::SubstringI(b)) => a == b,
+            (StringPattern::Glob(a), StringPattern::Glob(b)) => a == b,
+            (StringPattern::GlobI(a), StringPattern::GlobI(b)) => a == b,
+            (StringPattern::Regex(a), StringPattern::Regex(b)) => a.as_str() == b.as_str(),
+            (StringPattern::RegexI(a), StringPattern::RegexI(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
 }
 
+impl Eq for StringPattern {}
+
 impl StringPattern {
     /// Pattern that matches any string.
     pub const fn everything() -> Self {
@@ -62,8 +89,8 @@ impl StringPattern {
 
     /// Parses the given string as a [`StringPattern`]. Everything before the
     /// first ":" is considered the string's prefix. If the prefix is
-    /// "exact[-i]:", "glob[-i]:", or "substring[-i]:", a pattern of the
-    /// specified kind is returned. Returns an error if the string has an
+    /// "exact[-i]:", "glob[-i]:", "regex[-i]:", or "substring[-i]:", a pattern
+    /// of the specified kind is returned. Returns an error if the string has an
     /// unrecognized prefix. Otherwise, a `StringPattern::Exact` is
     /// returned.
     pub fn parse(src: &str) -> Result<StringPattern, StringPatternParseError> {
@@ -107,6 +134,22 @@ impl StringPattern {
         Ok(StringPattern::GlobI(parse_glob(src)?))
     }
 
+    /// Parses the given string as a regular expression, which matches
+    /// substrings unless anchored.
+    pub fn regex(src: &str) -> Result<Self, StringPatternParseError> {
+        let regex = regex::Regex::new(src).map_err(StringPatternParseError::Regex)?;
+        Ok(StringPattern::Regex(regex))
+    }
+
+    /// Parses the given string as a case‐insensitive regular expression.
+    pub fn regex_i(src: &str) -> Result<Self, StringPatternParseError> {
+        let regex = regex::RegexBuilder::new(src)
+            .case_insensitive(true)
+            .build()
+            .map_err(StringPatternParseError::Regex)?;
+        Ok(StringPattern::RegexI(regex))
+    }
+
     /// Parses the given string as a pattern of the specified `kind`.
     pub fn from_str_kind(src: &str, kind: &str) -> Result<Self, StringPatternParseError> {
         match kind {
@@ -116,6 +159,8 @@ impl StringPattern {
             "substring-i" => Ok(StringPattern::substring_i(src)),
             "glob" => StringPattern::glob(src),
             "glob-i" => StringPattern::glob_i(src),
+            "regex" => StringPattern::regex(src),
+            "regex-i" => StringPattern::regex_i(src),
             _ => Err(StringPatternParseError::InvalidKind(kind.to_owned())),
         }
     }
@@ -147,14 +192,14 @@ impl StringPattern {
             StringPattern::SubstringI(needle) => needle,
             StringPattern::Glob(pattern) => pattern.as_str(),
             StringPattern::GlobI(pattern) => pattern.as_str(),
+            StringPattern::Regex(pattern) => pattern.as_str(),
+            StringPattern::RegexI(pattern) => pattern.as_str(),
         }
     }
 
     /// Converts this pattern to a glob string. Returns `None` if the pattern
     /// can't be represented as a glob.
     pub fn to_glob(&self) -> Option<Cow<'_, str>> {
-        // TODO: If we add Regex pattern, it will return None.
-        //
         // TODO: Handle trivial case‐insensitive patterns here? It might make people
         // expect they can use case‐insensitive patterns in contexts where they
         // generally can’t.
@@ -171,6 +216,8 @@ impl StringPattern {
             StringPattern::ExactI(_) => None,
             StringPattern::SubstringI(_) => None,
             StringPattern::GlobI(_) => None,
+            StringPattern::Regex(_) => None,
+            StringPattern::RegexI(_) => None,
         }
     }
 
@@ -209,6 +256,8 @@ impl StringPattern {
                     ..glob::MatchOptions::new()
                 },
             ),
+            StringPattern::Regex(pattern) => pattern.is_match(haystack),
+            StringPattern::RegexI(pattern) => pattern.is_match(haystack),
         }
     }
 
@@ -255,6 +304,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_regex_matches() {
+        let pattern = StringPattern::regex(r"^fix(es)? #\d+").unwrap();
+        assert!(pattern.matches("fix #123: crash"));
+        assert!(pattern.matches("fixes #1"));
+        assert!(!pattern.matches("Fix #1"));
+        assert!(!pattern.matches("prefix #1"));
+        let pattern = StringPattern::regex_i(r"^fix(es)? #\d+").unwrap();
+        assert!(pattern.matches("Fix #1"));
+    }
+
     #[test]
     fn test_parse() {
         // Parse specific pattern kinds.
@@ -274,6 +334,18 @@ mod tests {
             StringPattern::parse("substring-i:foo").unwrap(),
             StringPattern::from_str_kind("foo", "substring-i").unwrap()
         );
+        assert_eq!(
+            StringPattern::parse("regex-i:^fo+$").unwrap(),
+            StringPattern::from_str_kind("^fo+$", "regex-i").unwrap()
+        );
+        assert_ne!(
+            StringPattern::parse("regex-i:foo").unwrap(),
+            StringPattern::parse("regex:foo").unwrap()
+        );
+        assert!(matches! {
+            StringPattern::parse("regex:("),
+            Err(StringPatternParseError::Regex(_))
+        });
 
         // Parse a pattern that contains a : itself.
         assert_eq!(
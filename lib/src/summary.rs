@@ -0,0 +1,184 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A summary of a workspace for shell prompts and editor status bars, which
+//! are refreshed often and have to be fast.
+//!
+//! [`quick()`] only reads what's cheap: the operation, the view, the index,
+//! and the recorded working-copy state. It never reads trees or file
+//! contents. Of the commits, it only reads the working-copy commit and the
+//! history of divergent changes, so it doesn't get slower as the repo grows.
+
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::backend::{BackendError, ChangeId, CommitId};
+use crate::evolution::{Evolution, EvolutionError};
+use crate::hex_util::to_reverse_hex;
+use crate::local_working_copy::LocalWorkingCopy;
+use crate::object_id::ObjectId as _;
+use crate::op_store::{OperationId, WorkspaceId};
+use crate::repo::{Repo as _, RepoLoaderError, StoreFactories};
+use crate::settings::UserSettings;
+use crate::working_copy::{WorkingCopy, WorkingCopyStateError};
+use crate::workspace::{WorkingCopyFactories, WorkspaceLoadError, WorkspaceLoader};
+
+/// Options for [`quick()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuickSummaryOptions {
+    /// Minimum length of the short commit and change ids. They're extended
+    /// as needed to be unique.
+    pub min_short_id_len: usize,
+}
+
+/// Whether the working copy has changes that weren't snapshotted yet, as far
+/// as can be told from the file metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirtyState {
+    /// No tracked file changed since the last snapshot.
+    Clean,
+    /// At least this many tracked files changed since the last snapshot.
+    Dirty(usize),
+    /// The recorded state can't be trusted without reading file contents, or
+    /// the working copy doesn't record file metadata.
+    Unknown,
+}
+
+/// What [`quick()`] found about a workspace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuickSummary {
+    /// The operation the repo was loaded at.
+    pub operation_id: OperationId,
+    /// The workspace at the summarized path.
+    pub workspace_id: WorkspaceId,
+    /// The working-copy commit of the workspace.
+    pub commit_id: CommitId,
+    /// The change id of the working-copy commit.
+    pub change_id: ChangeId,
+    /// The shortest unique prefix of the commit id in hex.
+    pub short_commit_id: String,
+    /// The shortest unique prefix of the change id in reverse hex.
+    pub short_change_id: String,
+    /// Whether files in the working copy changed since the last snapshot.
+    /// Files that aren't tracked yet aren't noticed.
+    pub dirty: DirtyState,
+    /// The local branches pointing at the working-copy commit, sorted by
+    /// name.
+    pub branches: Vec<String>,
+    /// Number of orphans, as defined by [`Evolution`].
+    pub orphans: usize,
+    /// Number of changes with more than one visible commit.
+    pub divergent_changes: usize,
+}
+
+/// Error from [`quick()`].
+#[derive(Debug, Error)]
+pub enum QuickSummaryError {
+    /// Failed to load the workspace.
+    #[error(transparent)]
+    WorkspaceLoad(#[from] WorkspaceLoadError),
+    /// Failed to load the repo at the working-copy operation.
+    #[error(transparent)]
+    RepoLoad(#[from] RepoLoaderError),
+    /// Failed to read the working-copy state.
+    #[error(transparent)]
+    WorkingCopyState(#[from] WorkingCopyStateError),
+    /// Failed to read a commit.
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    /// Failed to find the orphans or the divergent changes.
+    #[error(transparent)]
+    Evolution(#[from] EvolutionError),
+    /// The workspace has no working-copy commit in the view.
+    #[error("Workspace {} has no working-copy commit", .0.as_str())]
+    NoWorkingCopyCommit(WorkspaceId),
+}
+
+/// Summarizes the workspace at `workspace_root` for prompt integrations. The
+/// repo is loaded read-only at its head operation.
+pub fn quick(
+    user_settings: &UserSettings,
+    workspace_root: &Path,
+    store_factories: &StoreFactories,
+    working_copy_factories: &WorkingCopyFactories,
+    options: &QuickSummaryOptions,
+) -> Result<QuickSummary, QuickSummaryError> {
+    let workspace = WorkspaceLoader::init(workspace_root)?.load_read_only(
+        user_settings,
+        store_factories,
+        working_copy_factories,
+    )?;
+    let repo = workspace.repo_loader().load_at_head(user_settings)?;
+    let working_copy = workspace.working_copy();
+    let workspace_id = working_copy.workspace_id().clone();
+    let Some(commit_id) = repo.view().get_wc_commit_id(&workspace_id).cloned() else {
+        return Err(QuickSummaryError::NoWorkingCopyCommit(workspace_id));
+    };
+    let commit = repo.store().get_commit(&commit_id)?;
+
+    let commit_hex = commit_id.hex();
+    let commit_prefix_len = repo
+        .index()
+        .shortest_unique_commit_id_prefix_len(&commit_id)
+        .max(options.min_short_id_len);
+    let change_hex = to_reverse_hex(&commit.change_id().hex()).unwrap();
+    let change_prefix_len = repo
+        .shortest_unique_change_id_prefix_len(commit.change_id())
+        .max(options.min_short_id_len);
+
+    // The recorded file states only describe the working-copy commit if its
+    // tree was checked out or snapshotted last.
+    let dirty = if working_copy.tree_id()? != commit.tree_id() {
+        DirtyState::Unknown
+    } else {
+        dirty_state(working_copy)?
+    };
+    let branches = repo
+        .view()
+        .local_branches_for_commit(&commit_id)
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    let evolution = Evolution::new(repo.as_ref())?;
+    let orphans = evolution.orphan_ids()?.len();
+    let divergent_changes = evolution.divergent_commit_ids().len();
+
+    Ok(QuickSummary {
+        operation_id: repo.op_id().clone(),
+        workspace_id,
+        change_id: commit.change_id().clone(),
+        short_commit_id: commit_hex[..commit_prefix_len.min(commit_hex.len())].to_owned(),
+        short_change_id: change_hex[..change_prefix_len.min(change_hex.len())].to_owned(),
+        commit_id,
+        dirty,
+        branches,
+        orphans,
+        divergent_changes,
+    })
+}
+
+fn dirty_state(working_copy: &dyn WorkingCopy) -> Result<DirtyState, WorkingCopyStateError> {
+    let Some(local_working_copy) = working_copy.as_any().downcast_ref::<LocalWorkingCopy>() else {
+        return Ok(DirtyState::Unknown);
+    };
+    let changes = local_working_copy.stat_changes()?;
+    let state = if changes.changed > 0 {
+        DirtyState::Dirty(changes.changed)
+    } else if changes.ambiguous > 0 {
+        DirtyState::Unknown
+    } else {
+        DirtyState::Clean
+    };
+    Ok(state)
+}
@@ -0,0 +1,124 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timestamp helpers.
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone as _};
+use thiserror::Error;
+
+use crate::backend::{MillisSinceEpoch, Timestamp};
+
+/// Error occurred during date pattern parsing.
+#[derive(Debug, Error)]
+pub enum DatePatternParseError {
+    /// Pattern kind is not specified.
+    #[error(r#"Date pattern kind "after:" or "before:" is required"#)]
+    MissingKind,
+    /// Unknown pattern kind is specified.
+    #[error(r#"Invalid date pattern kind "{0}:""#)]
+    InvalidKind(String),
+    /// Failed to parse date.
+    #[error(r#"Invalid date "{0}", expected RFC 3339 date-time or YYYY-MM-DD"#)]
+    InvalidDate(String),
+}
+
+/// Pattern to match timestamps against.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DatePattern {
+    /// Matches timestamps at or after the given instant.
+    AtOrAfter(MillisSinceEpoch),
+    /// Matches timestamps before the given instant.
+    Before(MillisSinceEpoch),
+}
+
+impl DatePattern {
+    /// Parses the given string as a date pattern of the specified `kind`.
+    ///
+    /// The date can be an RFC 3339 date-time, or `YYYY-MM-DD` which means the
+    /// start of the day in the local timezone.
+    pub fn from_str_kind(src: &str, kind: &str) -> Result<Self, DatePatternParseError> {
+        let millis = parse_date(src)?;
+        match kind {
+            "after" => Ok(DatePattern::AtOrAfter(millis)),
+            "before" => Ok(DatePattern::Before(millis)),
+            _ => Err(DatePatternParseError::InvalidKind(kind.to_owned())),
+        }
+    }
+
+    /// Returns true if the `timestamp` matches this pattern.
+    pub fn matches(&self, timestamp: &Timestamp) -> bool {
+        match self {
+            DatePattern::AtOrAfter(millis) => timestamp.timestamp >= *millis,
+            DatePattern::Before(millis) => timestamp.timestamp < *millis,
+        }
+    }
+}
+
+fn parse_date(src: &str) -> Result<MillisSinceEpoch, DatePatternParseError> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(src) {
+        return Ok(MillisSinceEpoch(datetime.timestamp_millis()));
+    }
+    NaiveDate::parse_from_str(src, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|datetime| Local.from_local_datetime(&datetime).earliest())
+        .map(|datetime| MillisSinceEpoch(datetime.timestamp_millis()))
+        .ok_or_else(|| DatePatternParseError::InvalidDate(src.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    fn timestamp(millis: i64) -> Timestamp {
+        Timestamp {
+            timestamp: MillisSinceEpoch(millis),
+            tz_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_date_pattern_from_str_kind() {
+        assert_eq!(
+            DatePattern::from_str_kind("1970-01-01T00:00:01Z", "after").unwrap(),
+            DatePattern::AtOrAfter(MillisSinceEpoch(1000))
+        );
+        assert_eq!(
+            DatePattern::from_str_kind("1970-01-01T01:00:00+01:00", "before").unwrap(),
+            DatePattern::Before(MillisSinceEpoch(0))
+        );
+        assert!(DatePattern::from_str_kind("2024-02-29", "after").is_ok());
+        assert_matches!(
+            DatePattern::from_str_kind("1970-01-01T00:00:00Z", "during"),
+            Err(DatePatternParseError::InvalidKind(_))
+        );
+        assert_matches!(
+            DatePattern::from_str_kind("yesterday", "after"),
+            Err(DatePatternParseError::InvalidDate(_))
+        );
+    }
+
+    #[test]
+    fn test_date_pattern_matches() {
+        let after = DatePattern::AtOrAfter(MillisSinceEpoch(1000));
+        assert!(!after.matches(&timestamp(999)));
+        assert!(after.matches(&timestamp(1000)));
+        assert!(after.matches(&timestamp(1001)));
+        let before = DatePattern::Before(MillisSinceEpoch(1000));
+        assert!(before.matches(&timestamp(999)));
+        assert!(!before.matches(&timestamp(1000)));
+    }
+}
@@ -39,11 +39,16 @@ use crate::{dag_walk, op_store};
 /// to the contents of the repository and a Tree represents the repository's
 /// contents after the change. See the documentation for [`op_store::Operation`]
 /// and [`op_store::View`] for more information.
+///
+/// Like [`MutableRepo`], a transaction is used from a single thread. Threads
+/// that make changes concurrently should each start their own transaction
+/// from a shared [`ReadonlyRepo`].
 pub struct Transaction {
     mut_repo: MutableRepo,
     parent_ops: Vec<Operation>,
     op_metadata: OperationMetadata,
     end_time: Option<Timestamp>,
+    change_metadata_retention: u64,
 }
 
 impl Transaction {
@@ -51,11 +56,13 @@ impl Transaction {
         let parent_ops = vec![mut_repo.base_repo().operation().clone()];
         let op_metadata = create_op_metadata(user_settings, "".to_string(), false);
         let end_time = user_settings.operation_timestamp();
+        let change_metadata_retention = user_settings.change_metadata_retention();
         Transaction {
             mut_repo,
             parent_ops,
             op_metadata,
             end_time,
+            change_metadata_retention,
         }
     }
 
@@ -105,12 +112,13 @@ impl Transaction {
     /// That means that a repo can be loaded at the operation, but the
     /// operation will not be seen when loading the repo at head.
     pub fn write(mut self, description: impl Into<String>) -> UnpublishedOperation {
-        let mut_repo = self.mut_repo;
+        let mut mut_repo = self.mut_repo;
         // TODO: Should we instead just do the rebasing here if necessary?
         assert!(
             !mut_repo.has_rewrites(),
             "BUG: Descendants have not been rebased after the last rewrites."
         );
+        mut_repo.age_change_metadata(self.change_metadata_retention);
         let base_repo = mut_repo.base_repo().clone();
         let (mut_index, view) = mut_repo.consume();
 
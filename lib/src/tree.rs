@@ -14,21 +14,30 @@
 
 #![allow(missing_docs)]
 
-use std::fmt::{Debug, Error, Formatter};
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{self, File};
 use std::hash::{Hash, Hasher};
-use std::io::Read;
+use std::io::{self, Read};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
 use itertools::Itertools;
+use pollster::FutureExt as _;
+use thiserror::Error;
 use tracing::instrument;
 
 use crate::backend::{
-    BackendError, BackendResult, ConflictId, TreeEntriesNonRecursiveIterator, TreeEntry, TreeId,
-    TreeValue,
+    BackendError, BackendResult, ConflictId, FileId, TreeEntriesNonRecursiveIterator, TreeEntry,
+    TreeId, TreeValue,
 };
-use crate::files::MergeResult;
+use crate::conflicts::{materialize_tree_value, MaterializedTreeValue};
+use crate::file_util::{self, IoResultExt as _, PathError};
+use crate::files::{ContentType, MergeDriver, MergeResult};
 use crate::matchers::{EverythingMatcher, Matcher};
 use crate::merge::{trivial_merge, Merge, MergedTreeValue};
+use crate::merged_tree::MergedTree;
 use crate::object_id::ObjectId;
 use crate::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
 use crate::store::Store;
@@ -43,7 +52,7 @@ pub struct Tree {
 }
 
 impl Debug for Tree {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("Tree")
             .field("dir", &self.dir)
             .field("id", &self.id)
@@ -105,10 +114,15 @@ impl Tree {
         self.data.entries()
     }
 
+    /// Iterates over the non-tree entries in this tree and its subtrees,
+    /// depth-first in sorted path order. Subtrees are read as the iteration
+    /// reaches them.
     pub fn entries(&self) -> TreeEntriesIterator<'static> {
         TreeEntriesIterator::new(self.clone(), &EverythingMatcher)
     }
 
+    /// Like `entries()`, but only yields entries matched by `matcher`.
+    /// Subtrees the matcher doesn't visit are skipped without being read.
     pub fn entries_matching<'matcher>(
         &self,
         matcher: &'matcher dyn Matcher,
@@ -188,6 +202,57 @@ impl Tree {
         self.conflicts_matching(&EverythingMatcher)
     }
 
+    /// Returns whether the trees have the same contents. Trees with the same
+    /// id are equal without further checks. Trees from different stores are
+    /// compared recursively, including the contents of files and symlinks,
+    /// since their ids may be computed differently.
+    pub fn content_eq(&self, other: &Tree) -> BackendResult<bool> {
+        if self.id == other.id {
+            return Ok(true);
+        }
+        if Arc::ptr_eq(&self.store, &other.store) {
+            return Ok(false);
+        }
+        let entries = self.entries_non_recursive().collect_vec();
+        let other_entries = other.entries_non_recursive().collect_vec();
+        if entries.len() != other_entries.len() {
+            return Ok(false);
+        }
+        for (entry, other_entry) in entries.iter().zip(&other_entries) {
+            if entry.name() != other_entry.name() {
+                return Ok(false);
+            }
+            let path = self.dir.join(entry.name());
+            let equal = match (entry.value(), other_entry.value()) {
+                (TreeValue::Tree(id), TreeValue::Tree(other_id)) => {
+                    let sub_tree = self.store.get_tree(&path, id)?;
+                    let other_sub_tree = other.store.get_tree(&path, other_id)?;
+                    sub_tree.content_eq(&other_sub_tree)?
+                }
+                (
+                    TreeValue::File { id, executable },
+                    TreeValue::File {
+                        id: other_id,
+                        executable: other_executable,
+                    },
+                ) => {
+                    executable == other_executable
+                        && read_file_content(&self.store, &path, id)?
+                            == read_file_content(&other.store, &path, other_id)?
+                }
+                (TreeValue::Symlink(id), TreeValue::Symlink(other_id)) => {
+                    self.store.read_symlink(&path, id)?
+                        == other.store.read_symlink(&path, other_id)?
+                }
+                (value, other_value) => value == other_value,
+            };
+            if !equal {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     pub fn has_conflict(&self) -> bool {
         !self.conflicts().is_empty()
     }
@@ -216,7 +281,6 @@ impl From<Tree> for TreeEntriesDirItem {
 
 impl<'matcher> TreeEntriesIterator<'matcher> {
     fn new(tree: Tree, matcher: &'matcher dyn Matcher) -> Self {
-        // TODO: Restrict walk according to Matcher::visit()
         Self {
             stack: vec![TreeEntriesDirItem::from(tree)],
             matcher,
@@ -395,6 +459,19 @@ fn merge_tree_value(
     })
 }
 
+fn read_file_content(store: &Store, path: &RepoPath, id: &FileId) -> BackendResult<Vec<u8>> {
+    let mut content = vec![];
+    store
+        .read_file(path, id)?
+        .read_to_end(&mut content)
+        .map_err(|err| BackendError::ReadObject {
+            object_type: id.object_type(),
+            hash: id.hex(),
+            source: err.into(),
+        })?;
+    Ok(content)
+}
+
 /// Resolves file-level conflict by merging content hunks.
 ///
 /// The input `conflict` is supposed to be simplified. It shouldn't contain
@@ -441,21 +518,19 @@ pub fn try_resolve_file_conflict(
     //    cannot
     let file_id_conflict = file_id_conflict.simplify();
 
+    let driver = store.merge_drivers().driver_for(filename);
+    if matches!(driver, MergeDriver::Text | MergeDriver::Union) {
+        // Line-based merging would mangle binary contents
+        for file_id in file_id_conflict.iter() {
+            if store.file_content_type(filename, file_id)? == ContentType::Binary {
+                return Ok(None);
+            }
+        }
+    }
     let contents: Merge<Vec<u8>> =
-        file_id_conflict.try_map(|&file_id| -> BackendResult<Vec<u8>> {
-            let mut content = vec![];
-            store
-                .read_file(filename, file_id)?
-                .read_to_end(&mut content)
-                .map_err(|err| BackendError::ReadObject {
-                    object_type: file_id.object_type(),
-                    hash: file_id.hex(),
-                    source: err.into(),
-                })?;
-            Ok(content)
-        })?;
+        file_id_conflict.try_map(|&file_id| read_file_content(store, filename, file_id))?;
     let slices = contents.map(|content| content.as_slice());
-    let merge_result = files::merge(&slices);
+    let merge_result = files::merge_with_driver(&slices, driver);
     match merge_result {
         MergeResult::Resolved(merged_content) => {
             let id = store.write_file(filename, &mut merged_content.0.as_slice())?;
@@ -464,3 +539,162 @@ pub fn try_resolve_file_conflict(
         MergeResult::Conflict(_) => Ok(None),
     }
 }
+
+/// What `export_to_dir()` does with conflicted files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportConflicts {
+    /// Write the conflict with markers, like in the working copy.
+    #[default]
+    Materialize,
+    /// Fail the export.
+    Fail,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// Allow exporting into a non-empty directory. Existing entries at paths
+    /// in the tree are replaced; other entries are left alone.
+    pub overwrite: bool,
+    pub conflicts: ExportConflicts,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    pub files: usize,
+    pub symlinks: usize,
+    pub conflicts: usize,
+    pub skipped_submodules: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Destination directory {} is not empty", .0.display())]
+    DestinationNotEmpty(PathBuf),
+    #[error("Conflict at {}", .0.as_internal_file_string())]
+    Conflict(RepoPathBuf),
+    #[error("Access denied to {}", path.as_internal_file_string())]
+    AccessDenied {
+        path: RepoPathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    Io(#[from] PathError),
+}
+
+/// Writes the contents of `tree` to `dest`, which is created if it doesn't
+/// exist. Unlike a checkout, this doesn't involve a working copy, so nothing
+/// records which files were written. Git submodules are skipped.
+///
+/// If the export fails, the files written so far are left in place.
+pub fn export_to_dir(
+    tree: &MergedTree,
+    dest: &Path,
+    options: &ExportOptions,
+) -> Result<ExportStats, ExportError> {
+    fs::create_dir_all(dest).context(dest)?;
+    if !options.overwrite && dest.read_dir().context(dest)?.next().is_some() {
+        return Err(ExportError::DestinationNotEmpty(dest.to_owned()));
+    }
+    let symlink_support = file_util::check_symlink_support().unwrap_or(false);
+    let store = tree.store();
+    let mut stats = ExportStats::default();
+    for (path, value) in tree.entries() {
+        let value = materialize_tree_value(store, &path, value?).block_on()?;
+        let disk_path = export_path(dest, &path);
+        match value {
+            MaterializedTreeValue::Absent => continue,
+            MaterializedTreeValue::AccessDenied(source) => {
+                return Err(ExportError::AccessDenied { path, source });
+            }
+            MaterializedTreeValue::GitSubmodule(_) => {
+                stats.skipped_submodules += 1;
+                continue;
+            }
+            MaterializedTreeValue::Tree(_) => {
+                panic!("unexpected tree entry at {path:?}");
+            }
+            MaterializedTreeValue::File {
+                executable,
+                mut reader,
+                ..
+            } => {
+                prepare_export_path(dest, &path)?;
+                let mut file = File::create(&disk_path).context(&disk_path)?;
+                io::copy(&mut reader, &mut file).context(&disk_path)?;
+                set_executable(&disk_path, executable)?;
+                stats.files += 1;
+            }
+            MaterializedTreeValue::Symlink { target, .. } => {
+                prepare_export_path(dest, &path)?;
+                if symlink_support {
+                    file_util::try_symlink(&target, &disk_path).context(&disk_path)?;
+                } else {
+                    fs::write(&disk_path, target).context(&disk_path)?;
+                }
+                stats.symlinks += 1;
+            }
+            MaterializedTreeValue::Conflict {
+                contents,
+                executable,
+                ..
+            } => {
+                if options.conflicts == ExportConflicts::Fail {
+                    return Err(ExportError::Conflict(path));
+                }
+                prepare_export_path(dest, &path)?;
+                fs::write(&disk_path, contents).context(&disk_path)?;
+                set_executable(&disk_path, executable)?;
+                stats.conflicts += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+fn export_path(dest: &Path, path: &RepoPath) -> PathBuf {
+    // RepoPath components are plain names, so the result can't escape `dest`
+    for component in path.components() {
+        assert!(
+            matches!(
+                Path::new(component.as_str()).components().collect_vec()[..],
+                [Component::Normal(_)]
+            ),
+            "unexpected path component in {path:?}"
+        );
+    }
+    path.to_fs_path(dest)
+}
+
+/// Creates the parent directories of `path` and removes anything at `path`
+/// itself. Entries in the way that aren't directories (including symlinks,
+/// which must not be followed) are removed too.
+fn prepare_export_path(dest: &Path, path: &RepoPath) -> Result<(), PathError> {
+    let mut dir_path = dest.to_owned();
+    for component in path.parent().unwrap().components() {
+        dir_path.push(component.as_str());
+        match dir_path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => continue,
+            Ok(_) => fs::remove_file(&dir_path).context(&dir_path)?,
+            Err(_) => {}
+        }
+        fs::create_dir(&dir_path).context(&dir_path)?;
+    }
+    let disk_path = path.to_fs_path(dest);
+    match disk_path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&disk_path).context(&disk_path),
+        Ok(_) => fs::remove_file(&disk_path).context(&disk_path),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg_attr(windows, allow(unused_variables))]
+fn set_executable(disk_path: &Path, executable: bool) -> Result<(), PathError> {
+    #[cfg(unix)]
+    {
+        let mode = if executable { 0o755 } else { 0o644 };
+        fs::set_permissions(disk_path, fs::Permissions::from_mode(mode)).context(disk_path)?;
+    }
+    Ok(())
+}
@@ -14,14 +14,16 @@
 
 #![allow(missing_docs)]
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use itertools::Itertools;
 
-use crate::backend::CommitId;
-use crate::op_store::{BranchTarget, RefTarget, RefTargetOptionExt as _, RemoteRef, WorkspaceId};
+use crate::backend::{ChangeId, CommitId};
+use crate::op_store::{
+    BranchTarget, ChangeMetadata, RefTarget, RefTargetOptionExt as _, RemoteRef, WorkspaceId,
+};
 use crate::refs::LocalAndRemoteRef;
-use crate::str_util::StringPattern;
+use crate::str_util::{StringPattern, StringPatternParseError};
 use crate::{op_store, refs};
 
 /// A wrapper around [`op_store::View`] that defines additional methods.
@@ -59,10 +61,26 @@ impl View {
         self.data.wc_commit_ids.values().contains(commit_id)
     }
 
+    /// Returns the commits previously checked out in the workspace, newest
+    /// first.
+    pub fn checkout_history(&self, workspace_id: &WorkspaceId) -> &[CommitId] {
+        self.data
+            .checkout_history
+            .get(workspace_id)
+            .map_or(&[], |ids| ids.as_slice())
+    }
+
+    /// The visible heads, in no particular order. Use `sorted_heads()` if the
+    /// order matters.
     pub fn heads(&self) -> &HashSet<CommitId> {
         &self.data.head_ids
     }
 
+    /// The visible heads, sorted by commit id.
+    pub fn sorted_heads(&self) -> Vec<CommitId> {
+        self.data.head_ids.iter().cloned().sorted().collect()
+    }
+
     /// Iterates pair of local and remote branches by branch name.
     pub fn branches(&self) -> impl Iterator<Item = (&str, BranchTarget<'_>)> {
         op_store::merge_join_branch_views(&self.data.local_branches, &self.data.remote_views)
@@ -86,6 +104,31 @@ impl View {
 
     pub fn remove_wc_commit(&mut self, workspace_id: &WorkspaceId) {
         self.data.wc_commit_ids.remove(workspace_id);
+        self.data.checkout_history.remove(workspace_id);
+    }
+
+    /// Adds `commit_id` to the front of the workspace's checkout history,
+    /// keeping at most `max_len` entries. Does nothing if `commit_id` is
+    /// already the newest entry.
+    pub fn record_checkout(
+        &mut self,
+        workspace_id: WorkspaceId,
+        commit_id: CommitId,
+        max_len: usize,
+    ) {
+        let history = self.data.checkout_history.entry(workspace_id).or_default();
+        if history.first() != Some(&commit_id) {
+            history.insert(0, commit_id);
+        }
+        history.truncate(max_len);
+    }
+
+    pub fn set_checkout_history(&mut self, workspace_id: WorkspaceId, commit_ids: Vec<CommitId>) {
+        if commit_ids.is_empty() {
+            self.data.checkout_history.remove(&workspace_id);
+        } else {
+            self.data.checkout_history.insert(workspace_id, commit_ids);
+        }
     }
 
     pub fn add_head(&mut self, head_id: &CommitId) {
@@ -125,6 +168,16 @@ impl View {
             .map(|(name, target)| (name.as_ref(), target))
     }
 
+    /// Returns the local branch `(name, target)`s matching the glob
+    /// `pattern`, e.g. `release/*`. Entries are sorted by `name`.
+    pub fn branches_matching(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<(&str, &RefTarget)>, StringPatternParseError> {
+        let pattern = StringPattern::glob(pattern)?;
+        Ok(self.local_branches_matching(&pattern).collect())
+    }
+
     pub fn get_local_branch(&self, name: &str) -> &RefTarget {
         self.data.local_branches.get(name).flatten()
     }
@@ -284,6 +337,107 @@ impl View {
         }
     }
 
+    /// Returns the changes grouped under each label, sorted by change id.
+    pub fn labels(&self) -> BTreeMap<String, Vec<ChangeId>> {
+        let mut labels: BTreeMap<String, Vec<ChangeId>> = BTreeMap::new();
+        for (change_id, metadata) in &self.data.change_metadata {
+            for label in &metadata.labels {
+                labels
+                    .entry(label.clone())
+                    .or_default()
+                    .push(change_id.clone());
+            }
+        }
+        labels
+    }
+
+    /// Iterates the changes that have a label matching the given pattern.
+    /// Entries are sorted by change id.
+    pub fn changes_labeled<'a: 'b, 'b>(
+        &'a self,
+        pattern: &'b StringPattern,
+    ) -> impl Iterator<Item = &'a ChangeId> + 'b {
+        self.data
+            .change_metadata
+            .iter()
+            .filter(move |(_, metadata)| metadata.labels.iter().any(|label| pattern.matches(label)))
+            .map(|(change_id, _)| change_id)
+    }
+
+    /// Adds the change to the label. Does nothing if it already has the label.
+    pub fn add_label(&mut self, label: &str, change_id: ChangeId) {
+        self.data
+            .change_metadata
+            .entry(change_id)
+            .or_default()
+            .labels
+            .insert(label.to_owned());
+    }
+
+    /// Removes the change from the label.
+    pub fn remove_label(&mut self, label: &str, change_id: &ChangeId) {
+        let Some(mut metadata) = self.data.change_metadata.get(change_id).cloned() else {
+            return;
+        };
+        metadata.labels.remove(label);
+        self.set_change_metadata(change_id.clone(), metadata);
+    }
+
+    /// Returns the labels and other metadata attached to each change, by
+    /// change id.
+    pub fn change_metadata(&self) -> &BTreeMap<ChangeId, ChangeMetadata> {
+        &self.data.change_metadata
+    }
+
+    pub fn get_change_metadata(&self, change_id: &ChangeId) -> Option<&ChangeMetadata> {
+        self.data.change_metadata.get(change_id)
+    }
+
+    /// Iterates the changes that have metadata with a key starting with
+    /// `key_prefix`. Entries are sorted by change id.
+    pub fn changes_with_metadata<'a: 'b, 'b>(
+        &'a self,
+        key_prefix: &'b str,
+    ) -> impl Iterator<Item = (&'a ChangeId, &'a ChangeMetadata)> + 'b {
+        self.data
+            .change_metadata
+            .iter()
+            .filter(move |(_, metadata)| {
+                metadata
+                    .values
+                    .keys()
+                    .any(|key| key.starts_with(key_prefix))
+            })
+    }
+
+    /// Sets the metadata of the change. If there are no labels and no values,
+    /// the metadata will be removed.
+    pub fn set_change_metadata(&mut self, change_id: ChangeId, metadata: ChangeMetadata) {
+        if metadata.labels.is_empty() && metadata.values.is_empty() {
+            self.data.change_metadata.remove(&change_id);
+        } else {
+            self.data.change_metadata.insert(change_id, metadata);
+        }
+    }
+
+    /// Returns the changes whose divergence the user accepted.
+    pub fn accepted_divergences(&self) -> &BTreeSet<ChangeId> {
+        &self.data.accepted_divergences
+    }
+
+    pub fn is_divergence_accepted(&self, change_id: &ChangeId) -> bool {
+        self.data.accepted_divergences.contains(change_id)
+    }
+
+    /// Records whether the user accepted the divergence of the change.
+    pub fn set_divergence_accepted(&mut self, change_id: ChangeId, accepted: bool) {
+        if accepted {
+            self.data.accepted_divergences.insert(change_id);
+        } else {
+            self.data.accepted_divergences.remove(&change_id);
+        }
+    }
+
     pub fn get_git_ref(&self, name: &str) -> &RefTarget {
         self.data.git_refs.get(name).flatten()
     }
@@ -329,6 +483,9 @@ impl View {
             git_refs,
             git_head,
             wc_commit_ids,
+            checkout_history,
+            change_metadata: _,
+            accepted_divergences: _,
         } = &self.data;
         itertools::chain!(
             head_ids,
@@ -342,7 +499,8 @@ impl View {
             }),
             git_refs.values().flat_map(ref_target_ids),
             ref_target_ids(git_head),
-            wc_commit_ids.values()
+            wc_commit_ids.values(),
+            checkout_history.values().flatten()
         )
     }
 
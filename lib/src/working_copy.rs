@@ -24,11 +24,14 @@ use thiserror::Error;
 
 use crate::backend::{BackendError, MergedTreeId};
 use crate::commit::Commit;
+use crate::fileset::{FilePattern, FilesetExpression};
 use crate::fsmonitor::FsmonitorSettings;
 use crate::gitignore::{GitIgnoreError, GitIgnoreFile};
 use crate::op_store::{OperationId, WorkspaceId};
+use crate::repo::{MutableRepo, Repo as _};
 use crate::repo_path::{RepoPath, RepoPathBuf};
-use crate::settings::HumanByteSize;
+use crate::rewrite::restore_tree;
+use crate::settings::{HumanByteSize, UserSettings};
 use crate::store::Store;
 
 /// The trait all working-copy implementations must implement.
@@ -58,6 +61,11 @@ pub trait WorkingCopy: Send {
     /// that all files should be checked out.
     fn sparse_patterns(&self) -> Result<&[RepoPathBuf], WorkingCopyStateError>;
 
+    /// Lists the files in the working copy that are not tracked, along with the
+    /// reason they are not tracked. Uses the same rules as
+    /// `LockedWorkingCopy::snapshot()` but doesn't write anything.
+    fn untracked(&self, options: SnapshotOptions) -> Result<Vec<UntrackedEntry>, SnapshotError>;
+
     /// Locks the working copy and returns an instance with methods for updating
     /// the working copy files and state.
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError>;
@@ -215,6 +223,34 @@ impl SnapshotOptions<'_> {
 /// A callback for getting progress updates.
 pub type SnapshotProgress<'a> = dyn Fn(&RepoPath) + 'a + Sync;
 
+/// A file in the working copy that is not tracked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UntrackedEntry {
+    /// The path of the file. For ignored directories, this is the path of the
+    /// directory, and the files inside it are not listed individually.
+    pub path: RepoPathBuf,
+    /// Why the file is not tracked.
+    pub reason: UntrackedReason,
+}
+
+/// The reason a file in the working copy is not tracked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UntrackedReason {
+    /// The file is not ignored. It will become tracked by the next snapshot.
+    NotIgnored,
+    /// The file matches an ignore pattern.
+    Ignored {
+        /// The file containing the pattern.
+        source_file: PathBuf,
+        /// The 1-based line number of the pattern in `source_file`.
+        line: usize,
+    },
+    /// The file is not matched by the sparse patterns.
+    SparseExcluded,
+    /// The file is larger than `SnapshotOptions::max_new_file_size`.
+    TooLarge,
+}
+
 /// Stats about a checkout operation on a working copy. All "files" mentioned
 /// below may also be symlinks or materialized conflicts.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -228,8 +264,13 @@ pub struct CheckoutStats {
     pub removed_files: u32,
     /// The number of files that were supposed to be updated or added in the
     /// working copy but were skipped because there was an untracked (probably
-    /// ignored) file in its place.
+    /// ignored) file in its place, or because the file was modified on disk
+    /// since it was last checked out or snapshotted.
     pub skipped_files: u32,
+    /// Paths that were skipped because they can't be represented on this
+    /// platform (e.g. reserved names on Windows). They're also included in
+    /// `skipped_files`.
+    pub skipped_paths: Vec<RepoPathBuf>,
 }
 
 /// The working-copy checkout failed.
@@ -246,6 +287,14 @@ pub enum CheckoutError {
     /// running (after the working copy was read by the current process).
     #[error("Concurrent checkout")]
     ConcurrentCheckout,
+    /// The new tree contains paths that can't be represented on this
+    /// platform, and the working copy was configured not to skip them. Nothing
+    /// was written to disk.
+    #[error("Cannot check out {} paths on this platform", paths.len())]
+    UnmaterializablePaths {
+        /// All the offending paths.
+        paths: Vec<RepoPathBuf>,
+    },
     /// Reading or writing from the commit backend failed.
     #[error("Internal backend error")]
     InternalBackendError(#[from] BackendError),
@@ -294,3 +343,95 @@ pub struct WorkingCopyStateError {
     #[source]
     pub err: Box<dyn std::error::Error + Send + Sync>,
 }
+
+/// The commits written by [`commit_snapshot()`].
+#[derive(Debug, Default)]
+pub struct SnapshotCommits {
+    /// The commits with the changes matching `snapshot.split-rules`, in the
+    /// order of the rules. Each commit is the parent of the next one, and the
+    /// last one is the parent of the new working-copy commit.
+    pub split_commits: Vec<Commit>,
+    /// The new working-copy commit, if the working copy had changed.
+    pub wc_commit: Option<Commit>,
+}
+
+/// Snapshots the working copy and records the changes in `mut_repo` (see
+/// [`commit_snapshot()`]). `wc_commit` must be the commit the working copy was
+/// last updated to.
+pub fn snapshot_and_commit(
+    locked_wc: &mut dyn LockedWorkingCopy,
+    mut_repo: &mut MutableRepo,
+    settings: &UserSettings,
+    wc_commit: &Commit,
+    options: SnapshotOptions,
+) -> Result<SnapshotCommits, SnapshotError> {
+    let new_tree_id = locked_wc.snapshot(options)?;
+    commit_snapshot(mut_repo, settings, wc_commit, new_tree_id)
+}
+
+/// Records the snapshotted tree `new_tree_id` in `mut_repo` by rewriting
+/// `wc_commit`.
+///
+/// Changes to paths matching a `snapshot.split-rules` pattern (relative to
+/// the workspace root) are moved to a new commit with the rule's
+/// description, inserted between `wc_commit` and its parents. A path matching
+/// several rules goes to the commit of the first rule. Rules matching no
+/// changed paths create no commits.
+///
+/// The new working-copy commit keeps the change id of `wc_commit`, which
+/// becomes obsolete. The caller is responsible for moving the workspace to it
+/// and rebasing descendants, e.g. with `MutableRepo::rebase_descendants()` or
+/// `MutableRepo::evolve()`.
+pub fn commit_snapshot(
+    mut_repo: &mut MutableRepo,
+    settings: &UserSettings,
+    wc_commit: &Commit,
+    new_tree_id: MergedTreeId,
+) -> Result<SnapshotCommits, SnapshotError> {
+    let to_other_err =
+        |message: &str, err: Box<dyn std::error::Error + Send + Sync>| SnapshotError::Other {
+            message: message.to_owned(),
+            err,
+        };
+    if new_tree_id == *wc_commit.tree_id() {
+        return Ok(SnapshotCommits::default());
+    }
+    let split_rules = settings
+        .snapshot_split_rules()
+        .map_err(|err| to_other_err("Invalid snapshot.split-rules", err.into()))?;
+    let new_tree = mut_repo.store().get_root_tree(&new_tree_id)?;
+
+    let mut split_commits: Vec<Commit> = vec![];
+    let mut parent_ids = wc_commit.parent_ids().to_vec();
+    let mut parent_tree = wc_commit.parent_tree(mut_repo)?;
+    for rule in &split_rules {
+        let pattern = FilePattern::root_file_glob(&rule.pattern).map_err(|err| {
+            to_other_err(
+                &format!("Invalid snapshot.split-rules pattern {:?}", rule.pattern),
+                err.into(),
+            )
+        })?;
+        let matcher = FilesetExpression::pattern(pattern).to_matcher();
+        let split_tree_id = restore_tree(&new_tree, &parent_tree, matcher.as_ref())?;
+        if split_tree_id == parent_tree.id() {
+            continue;
+        }
+        let commit = mut_repo
+            .new_commit(settings, parent_ids, split_tree_id)
+            .set_description(&rule.description)
+            .write()?;
+        parent_ids = vec![commit.id().clone()];
+        parent_tree = commit.tree()?;
+        split_commits.push(commit);
+    }
+
+    let new_wc_commit = mut_repo
+        .rewrite_commit(settings, wc_commit)
+        .set_parents(parent_ids)
+        .set_tree_id(new_tree_id)
+        .write()?;
+    Ok(SnapshotCommits {
+        split_commits,
+        wc_commit: Some(new_wc_commit),
+    })
+}
@@ -31,8 +31,9 @@ use crate::local_working_copy::{LocalWorkingCopy, LocalWorkingCopyFactory};
 use crate::op_store::{OperationId, WorkspaceId};
 use crate::repo::{
     read_store_type, BackendInitializer, CheckOutCommitError, IndexStoreInitializer,
-    OpHeadsStoreInitializer, OpStoreInitializer, ReadonlyRepo, Repo, RepoInitError, RepoLoader,
-    StoreFactories, StoreLoadError, SubmoduleStoreInitializer,
+    OpHeadsStoreInitializer, OpStoreInitializer, ReadonlyRepo, Repo, RepoCloneError,
+    RepoCloneOptions, RepoInitError, RepoLoader, StoreFactories, StoreLoadError,
+    SubmoduleStoreInitializer,
 };
 use crate::settings::UserSettings;
 use crate::signing::{SignInitError, Signer};
@@ -51,6 +52,10 @@ pub enum WorkspaceInitError {
     #[error(transparent)]
     CheckOutCommit(#[from] CheckOutCommitError),
     #[error(transparent)]
+    CheckOut(#[from] CheckoutError),
+    #[error(transparent)]
+    Clone(#[from] RepoCloneError),
+    #[error(transparent)]
     WorkingCopyState(#[from] WorkingCopyStateError),
     #[error(transparent)]
     Path(#[from] PathError),
@@ -58,6 +63,8 @@ pub enum WorkspaceInitError {
     Backend(#[from] BackendInitError),
     #[error(transparent)]
     SignInit(#[from] SignInitError),
+    #[error(transparent)]
+    Config(#[from] config::ConfigError),
 }
 
 #[derive(Error, Debug)]
@@ -148,8 +155,11 @@ impl Workspace {
         user_settings: &UserSettings,
         workspace_root: &Path,
     ) -> Result<(Self, Arc<ReadonlyRepo>), WorkspaceInitError> {
-        let backend_initializer: &BackendInitializer =
-            &|_settings, store_path| Ok(Box::new(LocalBackend::init(store_path)));
+        let backend_initializer: &BackendInitializer = &|settings, store_path| {
+            Ok(Box::new(LocalBackend::init_with_settings(
+                settings, store_path,
+            )?))
+        };
         let signer = Signer::from_settings(user_settings)?;
         Self::init_with_backend(user_settings, workspace_root, backend_initializer, signer)
     }
@@ -269,6 +279,7 @@ impl Workspace {
             )
             .map_err(|repo_init_err| match repo_init_err {
                 RepoInitError::Backend(err) => WorkspaceInitError::Backend(err),
+                RepoInitError::Config(err) => WorkspaceInitError::Config(err),
                 RepoInitError::Path(err) => WorkspaceInitError::Path(err),
             })?;
             let (working_copy, repo) = init_working_copy(
@@ -341,6 +352,50 @@ impl Workspace {
         Ok((workspace, repo))
     }
 
+    /// Creates a workspace at `workspace_root` backed by a copy of `repo` (see
+    /// [`ReadonlyRepo::clone_to()`]). The new workspace has the same id as
+    /// this one, and the same working-copy commit checked out. Changes in this
+    /// working copy that haven't been snapshotted aren't copied.
+    pub fn clone_to(
+        &self,
+        user_settings: &UserSettings,
+        repo: &ReadonlyRepo,
+        workspace_root: &Path,
+        options: &RepoCloneOptions,
+        store_factories: &StoreFactories,
+        working_copy_factory: &dyn WorkingCopyFactory,
+    ) -> Result<(Self, Arc<ReadonlyRepo>), WorkspaceInitError> {
+        let jj_dir = create_jj_dir(workspace_root)?;
+        let repo = repo.clone_to(
+            user_settings,
+            &jj_dir.join("repo"),
+            options,
+            store_factories,
+        )?;
+
+        let workspace_id = self.workspace_id().clone();
+        let working_copy_state_path = jj_dir.join("working_copy");
+        std::fs::create_dir(&working_copy_state_path).context(&working_copy_state_path)?;
+        let working_copy = working_copy_factory.init_working_copy(
+            repo.store().clone(),
+            workspace_root.to_path_buf(),
+            working_copy_state_path.clone(),
+            repo.op_id().clone(),
+            workspace_id.clone(),
+        )?;
+        let working_copy_type_path = working_copy_state_path.join("type");
+        fs::write(&working_copy_type_path, working_copy.name()).context(&working_copy_type_path)?;
+        let mut workspace = Workspace::new(workspace_root, working_copy, repo.loader())?;
+        if let Some(wc_commit_id) = repo.view().get_wc_commit_id(&workspace_id) {
+            let wc_commit = repo
+                .store()
+                .get_commit(wc_commit_id)
+                .map_err(CheckoutError::from)?;
+            workspace.check_out(repo.op_id().clone(), None, &wc_commit)?;
+        }
+        Ok((workspace, repo))
+    }
+
     pub fn load(
         user_settings: &UserSettings,
         workspace_path: &Path,
@@ -486,6 +541,28 @@ impl WorkspaceLoader {
         working_copy_factories: &WorkingCopyFactories,
     ) -> Result<Workspace, WorkspaceLoadError> {
         let repo_loader = RepoLoader::init(user_settings, &self.repo_dir, store_factories)?;
+        self.load_with_repo_loader(repo_loader, working_copy_factories)
+    }
+
+    /// Like `load()`, but the repo is loaded with
+    /// [`RepoLoader::init_read_only()`], so loading it never writes to the
+    /// repo directory.
+    pub fn load_read_only(
+        &self,
+        user_settings: &UserSettings,
+        store_factories: &StoreFactories,
+        working_copy_factories: &WorkingCopyFactories,
+    ) -> Result<Workspace, WorkspaceLoadError> {
+        let repo_loader =
+            RepoLoader::init_read_only(user_settings, &self.repo_dir, store_factories)?;
+        self.load_with_repo_loader(repo_loader, working_copy_factories)
+    }
+
+    fn load_with_repo_loader(
+        &self,
+        repo_loader: RepoLoader,
+        working_copy_factories: &WorkingCopyFactories,
+    ) -> Result<Workspace, WorkspaceLoadError> {
         let working_copy = self.load_working_copy(repo_loader.store(), working_copy_factories)?;
         let workspace = Workspace::new(&self.workspace_root, working_copy, repo_loader)?;
         Ok(workspace)
@@ -7,12 +7,16 @@ fn test_no_forgotten_test_files() {
 }
 
 mod test_bad_locking;
+mod test_bisect;
 mod test_commit_builder;
 mod test_commit_concurrent;
 mod test_conflicts;
 mod test_copy_tracking;
 mod test_default_revset_graph_iterator;
 mod test_diff_summary;
+mod test_evolog;
+mod test_evolution;
+mod test_fingerprint;
 mod test_git;
 mod test_git_backend;
 mod test_gpg;
@@ -27,11 +31,18 @@ mod test_merge_trees;
 mod test_merged_tree;
 mod test_mut_repo;
 mod test_operations;
+mod test_random_dag;
 mod test_refs;
 mod test_revset;
 mod test_rewrite;
 mod test_rewrite_transform;
+mod test_scratch_dir;
 mod test_signing;
 mod test_ssh_signing;
+mod test_store;
+mod test_summary;
+mod test_synthetic_repo;
+mod test_tree;
+mod test_tree_export;
 mod test_view;
 mod test_workspace;
@@ -0,0 +1,100 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use itertools::Itertools;
+use jj_lib::repo::{BisectResult, Repo as _};
+use testutils::{CommitGraphBuilder, TestRepo};
+
+#[test]
+fn test_bisect_linear() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let mut commits = vec![graph_builder.initial_commit()];
+    for _ in 0..20 {
+        let parent = commits.last().unwrap().clone();
+        commits.push(graph_builder.commit_with_parents(&[&parent]));
+    }
+    let repo = tx.commit("test");
+    let commit_ids = commits
+        .iter()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+
+    for first_bad in 1..commit_ids.len() {
+        let mut tested = vec![];
+        let found = repo
+            .bisect(&commit_ids[0], commit_ids.last().unwrap(), |id| {
+                tested.push(id.clone());
+                let pos = commit_ids.iter().position(|other| other == id).unwrap();
+                if pos < first_bad {
+                    BisectResult::Good
+                } else {
+                    BisectResult::Bad
+                }
+            })
+            .unwrap();
+        assert_eq!(found, commit_ids[first_bad]);
+        // Binary search over 20 candidates needs at most 5 steps
+        assert!(tested.len() <= 5, "tested {} commits", tested.len());
+        assert!(tested.iter().all_unique());
+    }
+}
+
+#[test]
+fn test_bisect_merge() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // F
+    // |\
+    // D E
+    // | |
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_f = graph_builder.commit_with_parents(&[&commit_d, &commit_e]);
+    let repo = tx.commit("test");
+
+    // C introduced the problem, so only C and its descendants are bad
+    let bad_ids = [commit_c.id(), commit_e.id(), commit_f.id()];
+    let found = repo
+        .bisect(commit_a.id(), commit_f.id(), |id| {
+            if bad_ids.contains(&id) {
+                BisectResult::Bad
+            } else {
+                BisectResult::Good
+            }
+        })
+        .unwrap();
+    assert_eq!(&found, commit_c.id());
+
+    // If the good commit is the bad commit's only parent, there's nothing to
+    // test
+    let found = repo.bisect(commit_b.id(), commit_d.id(), |_| {
+        panic!("nothing should be tested")
+    });
+    assert_eq!(found.unwrap(), commit_d.id().clone());
+}
@@ -12,15 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
+use assert_matches::assert_matches;
 use itertools::Itertools;
-use jj_lib::backend::{ChangeId, MillisSinceEpoch, Signature, Timestamp};
+use jj_lib::backend::{BackendError, ChangeId, CommitId, MillisSinceEpoch, Signature, Timestamp};
+use jj_lib::commit::Churn;
+use jj_lib::commit_builder::WriteEffects;
 use jj_lib::matchers::EverythingMatcher;
 use jj_lib::merged_tree::DiffSummary;
-use jj_lib::repo::Repo;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{Repo, RepoError};
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::rewrite::merge_commit_trees;
 use jj_lib::settings::UserSettings;
+use maplit::hashset;
 use test_case::test_case;
-use testutils::{assert_rebased_onto, create_tree, CommitGraphBuilder, TestRepo, TestRepoBackend};
+use testutils::{
+    assert_rebased_onto, create_random_commit, create_tree, write_random_commit,
+    CommitGraphBuilder, TestRepo, TestRepoBackend,
+};
 
 fn to_owned_path_vec(paths: &[&RepoPath]) -> Vec<RepoPathBuf> {
     paths.iter().map(|&path| path.to_owned()).collect()
@@ -195,6 +206,215 @@ fn test_rewrite(backend: TestRepoBackend) {
     );
 }
 
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_octopus_merge(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+    let store = repo.store().clone();
+
+    let paths = ["file1", "file2", "file3"].map(RepoPath::from_internal_string);
+    let mut tx = repo.start_transaction(&settings);
+    let parents = paths.map(|path| {
+        let tree = create_tree(repo, &[(path, "contents")]);
+        tx.mut_repo()
+            .new_commit(&settings, vec![store.root_commit_id().clone()], tree.id())
+            .write()
+            .unwrap()
+    });
+    let parent_ids = parents
+        .iter()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+    let merged_tree = merge_commit_trees(tx.mut_repo(), &parents).unwrap();
+    let merge = tx
+        .mut_repo()
+        .new_commit(&settings, parent_ids.clone(), merged_tree.id())
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // The parents round-trip through the backend, and the parent tree contains
+    // the files from all of them
+    let loaded_repo = testutils::load_repo_at_head(&settings, repo.repo_path());
+    let loaded_merge = loaded_repo.store().get_commit(merge.id()).unwrap();
+    assert_eq!(loaded_merge.parent_ids(), parent_ids);
+    let parent_tree = loaded_merge.parent_tree(loaded_repo.as_ref()).unwrap();
+    assert_eq!(parent_tree.id(), *merge.tree_id());
+    for path in paths {
+        assert!(parent_tree.path_value(path).unwrap().is_present());
+    }
+
+    // Ahead/behind counts follow all three parents
+    let index = loaded_repo.index();
+    assert_eq!(
+        index.ahead_behind(merge.id(), parents[0].id()).unwrap(),
+        (3, 0)
+    );
+    assert_eq!(
+        index.ahead_behind(parents[1].id(), merge.id()).unwrap(),
+        (0, 3)
+    );
+
+    // Rewriting one parent keeps the other two
+    let mut tx = loaded_repo.start_transaction(&settings);
+    let parent = loaded_repo.store().get_commit(parents[1].id()).unwrap();
+    let rewritten_parent = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &parent)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let rebase_map = tx
+        .mut_repo()
+        .rebase_descendants_return_map(&settings)
+        .unwrap();
+    let rebased_merge = assert_rebased_onto(
+        tx.mut_repo(),
+        &rebase_map,
+        &merge,
+        &[parents[0].id(), rewritten_parent.id(), parents[2].id()],
+    );
+    assert_eq!(rebased_merge.tree_id(), merge.tree_id());
+}
+
+#[test]
+fn test_write_checked_change_id_collision() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit1 = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // An unrelated commit can't take over the change id
+    let mut tx = repo.start_transaction(&settings);
+    let result = create_random_commit(tx.mut_repo(), &settings)
+        .set_change_id(commit1.change_id().clone())
+        .write_checked();
+    assert_matches!(
+        result,
+        Err(RepoError::ChangeIdCollision { change_id, commit_ids })
+            if change_id == *commit1.change_id() && commit_ids == vec![commit1.id().clone()]
+    );
+
+    // Rewriting the commit keeps the change id
+    let commit2 = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit1)
+        .set_description("rewritten")
+        .write_checked()
+        .unwrap();
+    assert_eq!(commit2.change_id(), commit1.change_id());
+
+    // A commit with a new change id is fine too
+    create_random_commit(tx.mut_repo(), &settings)
+        .write_checked()
+        .unwrap();
+}
+
+#[test]
+fn test_try_write_validates_graph() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Moving A onto its own child would leave B needing to be rebased onto a
+    // rewrite of itself
+    let mut tx = repo.start_transaction(&settings);
+    let result = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_parents(vec![commit_b.id().clone()])
+        .try_write();
+    assert_matches!(
+        result,
+        Err(RepoError::RewriteCycle { commit_id, parent_id })
+            if commit_id == *commit_a.id() && parent_id == *commit_b.id()
+    );
+    assert_eq!(tx.mut_repo().rewritten_commit_ids().count(), 0);
+
+    // Parents and predecessors must exist
+    let missing_id = CommitId::new(commit_a.id().as_bytes().iter().map(|b| !b).collect());
+    let result = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![missing_id.clone()])
+        .try_write();
+    assert_matches!(result, Err(RepoError::MissingCommit(id)) if id == missing_id);
+    let result = create_random_commit(tx.mut_repo(), &settings)
+        .set_predecessors(vec![missing_id.clone()])
+        .try_write();
+    assert_matches!(result, Err(RepoError::MissingCommit(id)) if id == missing_id);
+
+    // A valid rewrite is written as usual
+    let commit_a2 = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .try_write()
+        .unwrap();
+    assert_eq!(commit_a2.predecessor_ids(), [commit_a.id().clone()]);
+}
+
+#[test]
+fn test_try_write_parent_from_other_repo() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let other_test_repo = TestRepo::init();
+
+    let mut tx = other_test_repo.repo.start_transaction(&settings);
+    let other_commit = write_random_commit(tx.mut_repo(), &settings);
+    tx.commit("test");
+
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    let result = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![other_commit.id().clone()])
+        .try_write();
+    assert_matches!(result, Err(RepoError::MissingCommit(id)) if id == *other_commit.id());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "was read from another store")]
+fn test_rewrite_commit_from_other_repo() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let other_test_repo = TestRepo::init();
+
+    let mut tx = other_test_repo.repo.start_transaction(&settings);
+    let other_commit = write_random_commit(tx.mut_repo(), &settings);
+    tx.commit("test");
+
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    let _ = tx.mut_repo().rewrite_commit(&settings, &other_commit);
+}
+
+#[test]
+fn test_write_root_change_id() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let root_change_id = repo.store().root_change_id().clone();
+
+    let mut tx = repo.start_transaction(&settings);
+    let result = create_random_commit(tx.mut_repo(), &settings)
+        .set_change_id(root_change_id)
+        .write();
+    assert_matches!(
+        result,
+        Err(BackendError::Other(err)) if err.to_string().contains("root change id")
+    );
+}
+
 // An author field with an empty name/email should get filled in on rewrite
 #[test_case(TestRepoBackend::Local ; "local backend")]
 #[test_case(TestRepoBackend::Git ; "git backend")]
@@ -379,3 +599,126 @@ fn test_commit_builder_descendants(backend: TestRepoBackend) {
         .unwrap();
     assert!(rebase_map.is_empty());
 }
+
+#[test]
+fn test_write_with_effects() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // D C
+    // |/
+    // B E
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_a]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    // Rewriting B makes it obsolete and its descendants orphans
+    let (_, effects) = mut_repo
+        .rewrite_commit(&settings, &commit_b)
+        .set_description("rewritten")
+        .write_with_effects()
+        .unwrap();
+    assert_eq!(effects.newly_obsolete, vec![commit_b.id().clone()]);
+    assert_eq!(
+        effects.new_orphans.iter().collect::<HashSet<_>>(),
+        hashset! {commit_c.id(), commit_d.id()}
+    );
+
+    // Rewriting an orphan replaces it with another orphan, since the new commit
+    // still has the obsolete parent
+    let (new_commit_c, effects) = mut_repo
+        .rewrite_commit(&settings, &commit_c)
+        .set_description("rewritten")
+        .write_with_effects()
+        .unwrap();
+    assert_eq!(effects.newly_obsolete, vec![commit_c.id().clone()]);
+    assert_eq!(effects.new_orphans, vec![new_commit_c.id().clone()]);
+
+    // A new commit on top of an obsolete commit is an orphan
+    let (new_commit, effects) = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_b.id().clone()])
+        .write_with_effects()
+        .unwrap();
+    assert!(effects.newly_obsolete.is_empty());
+    assert_eq!(effects.new_orphans, vec![new_commit.id().clone()]);
+
+    // A new commit elsewhere changes nothing
+    let (_, effects) = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_e.id().clone()])
+        .write_with_effects()
+        .unwrap();
+    assert_eq!(effects, WriteEffects::default());
+    assert!(mut_repo.orphan_ids().unwrap().contains(commit_d.id()));
+}
+
+#[test]
+fn test_churn() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let modified_path = RepoPath::from_internal_string("modified");
+    let added_path = RepoPath::from_internal_string("dir/added");
+    let removed_path = RepoPath::from_internal_string("removed");
+    let binary_path = RepoPath::from_internal_string("binary");
+    let tree1 = create_tree(
+        repo,
+        &[
+            (modified_path, "a\nb\nc\n"),
+            (removed_path, "x\ny\n"),
+            (binary_path, "bin\0ary\n"),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (modified_path, "a\nB\nc\nd\n"),
+            (added_path, "new\n"),
+            (binary_path, "bin\0ary\nmore\nlines\n"),
+        ],
+    );
+    let mut tx = repo.start_transaction(&settings);
+    let commit1 = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree1.id(),
+        )
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+
+    assert_eq!(
+        commit1.churn(tx.repo()).unwrap(),
+        Churn {
+            insertions: 5,
+            deletions: 0,
+            files_changed: 3,
+        }
+    );
+    // The binary file's lines aren't counted
+    assert_eq!(
+        commit2.churn(tx.repo()).unwrap(),
+        Churn {
+            insertions: 3,
+            deletions: 3,
+            files_changed: 4,
+        }
+    );
+}
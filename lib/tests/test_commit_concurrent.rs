@@ -16,10 +16,22 @@ use std::cmp::max;
 use std::sync::Arc;
 use std::thread;
 
+use itertools::Itertools as _;
+use jj_lib::commit::Commit;
 use jj_lib::dag_walk;
+use jj_lib::evolog::{evolog, EvologGraph};
+use jj_lib::git_backend::GitBackend;
+use jj_lib::index::{ChangeIdIndex, Index, ReadonlyIndex};
+use jj_lib::local_backend::LocalBackend;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::store::Store;
+use jj_lib::view::View;
 use test_case::test_case;
-use testutils::{load_repo_at_head, write_random_commit, TestRepoBackend, TestWorkspace};
+use testutils::{
+    create_random_commit, load_repo_at_head, write_random_commit, TestRepoBackend, TestWorkspace,
+};
 
 fn count_non_merge_operations(repo: &Arc<ReadonlyRepo>) -> usize {
     let op_store = repo.op_store();
@@ -100,3 +112,95 @@ fn test_commit_parallel_instances(backend: TestRepoBackend) {
     // one for checking out the initial commit.
     assert_eq!(count_non_merge_operations(&repo), num_threads + 3);
 }
+
+fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+
+#[test]
+fn test_thread_safe_types() {
+    // Types that may be shared between threads. This fails to compile if any
+    // of them stops being thread-safe.
+    assert_send_sync::<Arc<ReadonlyRepo>>();
+    assert_send_sync::<Store>();
+    assert_send_sync::<View>();
+    assert_send_sync::<Commit>();
+    assert_send_sync::<MergedTree>();
+    assert_send_sync::<dyn ReadonlyIndex>();
+    assert_send_sync::<dyn Index>();
+    assert_send_sync::<dyn ChangeIdIndex>();
+    assert_send_sync::<EvologGraph>();
+    assert_send_sync::<LocalBackend>();
+    assert_send_sync::<GitBackend>();
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_read_parallel(backend: TestRepoBackend) {
+    // Computes diffs and evolution graphs from many threads sharing one repo
+    // instance, and checks that they match the results of a single thread.
+    let settings = testutils::user_settings();
+    let test_workspace = TestWorkspace::init_with_backend(&settings, backend);
+    let repo = &test_workspace.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut commits = vec![];
+    for _ in 0..10 {
+        let commit = create_random_commit(tx.mut_repo(), &settings)
+            .set_parents(vec![commits
+                .last()
+                .map_or(repo.store().root_commit_id(), Commit::id)
+                .clone()])
+            .write()
+            .unwrap();
+        commits.push(commit);
+    }
+    for commit in &mut commits[..5] {
+        *commit = tx
+            .mut_repo()
+            .rewrite_commit(&settings, commit)
+            .set_description("rewritten")
+            .write()
+            .unwrap();
+    }
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    let commits: Vec<Commit> = commits
+        .iter()
+        .map(|commit| {
+            let ids = repo.resolve_change_id(commit.change_id()).unwrap();
+            repo.store().get_commit(&ids[0]).unwrap()
+        })
+        .collect();
+
+    let summarize = |repo: &ReadonlyRepo| {
+        commits
+            .iter()
+            .map(|commit| {
+                let num_diffs = commit
+                    .parent_tree(repo)
+                    .unwrap()
+                    .diff(&commit.tree().unwrap(), &EverythingMatcher)
+                    .map(|(_, diff)| diff)
+                    .process_results(|diffs| diffs.count())
+                    .unwrap();
+                let evolog = evolog(repo, commit.change_id()).unwrap();
+                (num_diffs, evolog.nodes.len())
+            })
+            .collect_vec()
+    };
+    let expected = summarize(&repo);
+    assert!(expected.iter().all(|&(num_diffs, _)| num_diffs > 0));
+    assert_eq!(expected[0].1, 2);
+    assert_eq!(expected[9].1, 2);
+
+    thread::scope(|s| {
+        let handles = (0..8)
+            .map(|_| {
+                let repo = repo.clone();
+                s.spawn(move || summarize(&repo))
+            })
+            .collect_vec();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    });
+}
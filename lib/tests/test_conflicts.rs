@@ -15,13 +15,16 @@
 use indoc::indoc;
 use jj_lib::backend::FileId;
 use jj_lib::conflicts::{
-    extract_as_single_hunk, materialize_merge_result, parse_conflict, update_from_content,
+    extract_as_single_hunk, extract_conflict_side, materialize_merge_result, parse_conflict,
+    update_from_content, ConflictSide,
 };
+use jj_lib::files::ContentHunk;
 use jj_lib::merge::Merge;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::store::Store;
 use pollster::FutureExt;
+use testutils::test_backend::TestBackend;
 use testutils::TestRepo;
 
 #[test]
@@ -490,6 +493,46 @@ fn test_materialize_conflict_two_forward_diffs() {
     );
 }
 
+#[test]
+fn test_extract_conflict_side() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+    let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+
+    let path = RepoPath::from_internal_string("file");
+    let base_id = testutils::write_file(store, path, "base\n");
+    let left_id = testutils::write_file(store, path, "left\n");
+    let right_id = testutils::write_file(store, path, "right\n");
+    let conflict = Merge::from_removes_adds(
+        vec![Some(base_id.clone()), Some(base_id)],
+        vec![Some(left_id), Some(right_id), None],
+    );
+    let extract = |side| {
+        extract_conflict_side(&conflict, store, path, side)
+            .block_on()
+            .unwrap()
+    };
+
+    // Only the requested side is read
+    let num_file_reads = test_backend.num_file_reads();
+    assert_eq!(
+        extract(ConflictSide::Add(0)),
+        Some(ContentHunk(b"left\n".to_vec()))
+    );
+    assert_eq!(test_backend.num_file_reads(), num_file_reads + 1);
+    assert_eq!(
+        extract(ConflictSide::Remove(0)),
+        Some(ContentHunk(b"base\n".to_vec()))
+    );
+    assert_eq!(test_backend.num_file_reads(), num_file_reads + 2);
+
+    // An absent side reads as empty, and a missing term as None
+    assert_eq!(extract(ConflictSide::Add(2)), Some(ContentHunk(vec![])));
+    assert_eq!(extract(ConflictSide::Add(3)), None);
+    assert_eq!(extract(ConflictSide::Remove(2)), None);
+    assert_eq!(test_backend.num_file_reads(), num_file_reads + 2);
+}
+
 #[test]
 fn test_parse_conflict_resolved() {
     assert_eq!(
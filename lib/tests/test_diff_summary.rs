@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use itertools::Itertools as _;
+use jj_lib::diff::{self, ContentComparison, DiffOptions, FileChange, FileStat};
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
 use jj_lib::merged_tree::DiffSummary;
 use jj_lib::repo_path::{RepoPath, RepoPathBuf};
@@ -272,3 +274,317 @@ fn test_matcher_normal_cases() {
         }
     );
 }
+
+#[test]
+fn test_stat() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let clean_path = RepoPath::from_internal_string("clean");
+    let modified_path = RepoPath::from_internal_string("modified");
+    let added_path = RepoPath::from_internal_string("added");
+    let removed_path = RepoPath::from_internal_string("removed");
+    let binary_path = RepoPath::from_internal_string("binary");
+
+    let tree1 = create_tree(
+        repo,
+        &[
+            (clean_path, "clean\n"),
+            (modified_path, "a\nb\nc\nd\n"),
+            (removed_path, "x\ny\n"),
+            (binary_path, "bin\0ary\n"),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (clean_path, "clean\n"),
+            (modified_path, "a\nB\nc\nd\ne\n"),
+            (added_path, "new\n"),
+            (binary_path, "bin\0ary\nmore\n"),
+        ],
+    );
+
+    let stat = |path: &RepoPath, added_lines, removed_lines, is_binary| FileStat {
+        path: path.to_owned(),
+        added_lines,
+        removed_lines,
+        is_binary,
+        only_ignored_changes: false,
+    };
+    assert_eq!(
+        diff::stat(&tree1, &tree2).unwrap(),
+        vec![
+            stat(added_path, 1, 0, false),
+            stat(binary_path, 0, 0, true),
+            stat(modified_path, 2, 1, false),
+            stat(removed_path, 0, 2, false),
+        ]
+    );
+}
+
+#[test]
+fn test_stat_with_options() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let indent_path = RepoPath::from_internal_string("indent");
+    let trailing_path = RepoPath::from_internal_string("trailing");
+    let spaces_path = RepoPath::from_internal_string("spaces");
+    let blank_path = RepoPath::from_internal_string("blank");
+    let real_path = RepoPath::from_internal_string("real");
+
+    let tree1 = create_tree(
+        repo,
+        &[
+            (indent_path, "if x {\n  y\n}\n"),
+            (trailing_path, "a\nb\n"),
+            (spaces_path, "ab\n"),
+            (blank_path, "a\nb\n"),
+            (real_path, "a b\nc\n"),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (indent_path, "if x {\n\t    y\n}\n"),
+            (trailing_path, "a  \nb\r\n"),
+            (spaces_path, "a b\n"),
+            (blank_path, "a\n\n  \nb\n"),
+            (real_path, "a  b\nC\n\n"),
+        ],
+    );
+
+    // (path, added lines, removed lines, only ignored changes)
+    let stats = |options: &DiffOptions| {
+        diff::stat_with_options(&tree1, &tree2, options)
+            .unwrap()
+            .into_iter()
+            .map(|stat| {
+                (
+                    stat.path.as_internal_file_string().to_owned(),
+                    stat.added_lines,
+                    stat.removed_lines,
+                    stat.only_ignored_changes,
+                )
+            })
+            .collect_vec()
+    };
+    let stat = |path: &RepoPath, added_lines, removed_lines, only_ignored_changes| {
+        (
+            path.as_internal_file_string().to_owned(),
+            added_lines,
+            removed_lines,
+            only_ignored_changes,
+        )
+    };
+
+    // Nothing is ignored by default
+    assert_eq!(
+        stats(&DiffOptions::default()),
+        vec![
+            stat(blank_path, 2, 0, false),
+            stat(indent_path, 1, 1, false),
+            stat(real_path, 3, 2, false),
+            stat(spaces_path, 1, 1, false),
+            stat(trailing_path, 2, 2, false),
+        ]
+    );
+
+    let options = DiffOptions {
+        ignore_whitespace_amount: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        stats(&options),
+        vec![
+            stat(blank_path, 2, 0, false),
+            stat(indent_path, 0, 0, true),
+            stat(real_path, 2, 1, false),
+            stat(spaces_path, 1, 1, false),
+            stat(trailing_path, 0, 0, true),
+        ]
+    );
+
+    let options = DiffOptions {
+        ignore_all_whitespace: true,
+        ignore_blank_lines: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        stats(&options),
+        vec![
+            stat(blank_path, 0, 0, true),
+            stat(indent_path, 0, 0, true),
+            stat(real_path, 1, 1, false),
+            stat(spaces_path, 0, 0, true),
+            stat(trailing_path, 0, 0, true),
+        ]
+    );
+
+    assert_eq!(
+        diff::compare_contents(b"a\n", b"a\n", &options),
+        ContentComparison::Identical
+    );
+    assert_eq!(
+        diff::compare_contents(b"a\n", b" a \n", &options),
+        ContentComparison::OnlyIgnoredChanges
+    );
+    assert_eq!(
+        diff::compare_contents(b"a\n", b" a \n", &DiffOptions::default()),
+        ContentComparison::Different
+    );
+}
+
+#[test]
+fn test_changes_with_renames() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let old_path = RepoPath::from_internal_string("old");
+    let new_path = RepoPath::from_internal_string("dir/new");
+    let modified_path = RepoPath::from_internal_string("modified");
+    let added_path = RepoPath::from_internal_string("added");
+    let removed_path = RepoPath::from_internal_string("removed");
+
+    let tree1 = create_tree(
+        repo,
+        &[
+            (old_path, "a\nb\nc\nd\n"),
+            (modified_path, "before\n"),
+            (removed_path, "x\ny\n"),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (new_path, "a\nb\nc\nD\n"),
+            (modified_path, "after\n"),
+            (added_path, "new\n"),
+        ],
+    );
+
+    // The renamed file with a small edit is paired up, the unrelated files
+    // aren't
+    assert_eq!(
+        diff::changes_with_renames(&tree1, &tree2, diff::DEFAULT_RENAME_SIMILARITY).unwrap(),
+        vec![
+            FileChange::Added(added_path.to_owned()),
+            FileChange::Rename {
+                from: old_path.to_owned(),
+                to: new_path.to_owned(),
+                similarity: 75,
+            },
+            FileChange::Modified(modified_path.to_owned()),
+            FileChange::Removed(removed_path.to_owned()),
+        ]
+    );
+
+    // Below the threshold, it's reported as a removal and an addition
+    assert_eq!(
+        diff::changes_with_renames(&tree1, &tree2, 80).unwrap(),
+        vec![
+            FileChange::Added(added_path.to_owned()),
+            FileChange::Added(new_path.to_owned()),
+            FileChange::Modified(modified_path.to_owned()),
+            FileChange::Removed(old_path.to_owned()),
+            FileChange::Removed(removed_path.to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_changes_with_renames_limits() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let large_path = RepoPath::from_internal_string("large");
+    let large_moved_path = RepoPath::from_internal_string("large-moved");
+    let large_edited_path = RepoPath::from_internal_string("large-edited");
+    let edited_path = RepoPath::from_internal_string("large-edited-source");
+    let empty_path = RepoPath::from_internal_string("empty");
+    let empty_moved_path = RepoPath::from_internal_string("empty-moved");
+
+    let large = "line\n".repeat(diff::MAX_RENAME_FILE_SIZE as usize / 5 + 1);
+    let large_edited = format!("{large}edit\n");
+    let tree1 = create_tree(
+        repo,
+        &[
+            (large_path, &large),
+            (edited_path, &large),
+            (empty_path, ""),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (large_moved_path, &large),
+            (large_edited_path, &large_edited),
+            (empty_moved_path, ""),
+        ],
+    );
+
+    // Large files are only paired up if they're identical, and empty files
+    // never are
+    let changes = diff::changes_with_renames(&tree1, &tree2, 1).unwrap();
+    assert_eq!(
+        changes,
+        vec![
+            FileChange::Removed(empty_path.to_owned()),
+            FileChange::Added(empty_moved_path.to_owned()),
+            FileChange::Added(large_edited_path.to_owned()),
+            FileChange::Removed(edited_path.to_owned()),
+            FileChange::Rename {
+                from: large_path.to_owned(),
+                to: large_moved_path.to_owned(),
+                similarity: 100,
+            },
+        ]
+    );
+
+    // With too many removed and added files, only identical files are paired
+    // up
+    let num_files = (diff::MAX_RENAME_CANDIDATE_PAIRS as f64).sqrt() as usize + 1;
+    let paths = (0..num_files)
+        .map(|i| {
+            (
+                RepoPathBuf::from_internal_string(format!("old{i}")),
+                RepoPathBuf::from_internal_string(format!("new{i}")),
+            )
+        })
+        .collect_vec();
+    let contents = (0..num_files)
+        .map(|i| format!("a\nb\nc\n{i}\n"))
+        .collect_vec();
+    let old_entries = paths
+        .iter()
+        .zip(&contents)
+        .map(|((old, _), contents)| (old.as_ref(), contents.as_str()))
+        .chain([(large_path, large.as_str())])
+        .collect_vec();
+    let new_entries = paths
+        .iter()
+        .zip(&contents)
+        .map(|((_, new), contents)| (new.as_ref(), format!("{contents}edit\n")))
+        .collect_vec();
+    let new_entries = new_entries
+        .iter()
+        .map(|(path, contents)| (*path, contents.as_str()))
+        .chain([(large_moved_path, large.as_str())])
+        .collect_vec();
+    let tree1 = create_tree(repo, &old_entries);
+    let tree2 = create_tree(repo, &new_entries);
+    let changes = diff::changes_with_renames(&tree1, &tree2, 1).unwrap();
+    assert_eq!(
+        changes
+            .iter()
+            .filter(|change| matches!(change, FileChange::Rename { .. }))
+            .collect_vec(),
+        [&FileChange::Rename {
+            from: large_path.to_owned(),
+            to: large_moved_path.to_owned(),
+            similarity: 100,
+        }]
+    );
+    assert_eq!(changes.len(), 2 * num_files + 1);
+}
@@ -0,0 +1,200 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use itertools::Itertools;
+use jj_lib::backend::ChangeId;
+use jj_lib::evolog::{evolog, EvologEdgeKind};
+use testutils::{write_random_commit, TestRepo};
+
+#[test]
+fn test_evolog_rewrite() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a2 = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    let graph = evolog(repo.as_ref(), commit_a.change_id()).unwrap();
+    let nodes = graph
+        .nodes
+        .iter()
+        .map(|node| (node.commit.id().clone(), node.is_visible))
+        .collect_vec();
+    assert_eq!(
+        nodes,
+        vec![
+            (commit_a2.id().clone(), true),
+            (commit_a.id().clone(), false)
+        ]
+    );
+    let edges = graph
+        .predecessor_edges(commit_a2.id())
+        .map(|edge| (edge.predecessor.clone(), edge.kind))
+        .collect_vec();
+    assert_eq!(
+        edges,
+        vec![(commit_a.id().clone(), EvologEdgeKind::Rewrite)]
+    );
+}
+
+#[test]
+fn test_evolog_split() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // Split A into A1 and A2, the way `jj split` does
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a1 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("first")
+        .write()
+        .unwrap();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_parents(vec![commit_a1.id().clone()])
+        .set_description("second")
+        .generate_new_change_id()
+        .write()
+        .unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    let graph = evolog(repo.as_ref(), commit_a.change_id()).unwrap();
+    let node_ids = graph
+        .nodes
+        .iter()
+        .map(|node| node.commit.id().clone())
+        .collect_vec();
+    assert_eq!(node_ids.len(), 3);
+    assert!(node_ids.contains(commit_a1.id()));
+    assert!(node_ids.contains(commit_a2.id()));
+    // The split commit comes after its successors
+    assert_eq!(node_ids[2], commit_a.id().clone());
+
+    let successor_edges = graph
+        .successor_edges(commit_a.id())
+        .map(|edge| (edge.successor.clone(), edge.kind))
+        .collect_vec();
+    assert_eq!(successor_edges.len(), 2);
+    assert!(successor_edges
+        .iter()
+        .all(|(_, kind)| *kind == EvologEdgeKind::Split));
+    assert!(successor_edges.iter().any(|(id, _)| id == commit_a1.id()));
+    assert!(successor_edges.iter().any(|(id, _)| id == commit_a2.id()));
+}
+
+#[test]
+fn test_evolog_divergent_rewrite() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // Rewrite A twice, making the change divergent
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("first")
+        .write()
+        .unwrap();
+    let commit_a3 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("second")
+        .write()
+        .unwrap();
+    mut_repo.record_abandoned_commit(commit_a.id().clone());
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    // Both successors belong to the same change, so A wasn't split
+    let graph = evolog(repo.as_ref(), commit_a.change_id()).unwrap();
+    assert_eq!(graph.nodes.len(), 3);
+    let successor_edges = graph
+        .successor_edges(commit_a.id())
+        .map(|edge| (edge.successor.clone(), edge.kind))
+        .sorted_by(|(id1, _), (id2, _)| id1.cmp(id2))
+        .collect_vec();
+    let mut expected_edges = vec![
+        (commit_a2.id().clone(), EvologEdgeKind::Rewrite),
+        (commit_a3.id().clone(), EvologEdgeKind::Rewrite),
+    ];
+    expected_edges.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
+    assert_eq!(successor_edges, expected_edges);
+}
+
+#[test]
+fn test_evolog_squash() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // Squash B into A
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_predecessors(vec![commit_a.id().clone(), commit_b.id().clone()])
+        .write()
+        .unwrap();
+    mut_repo.record_abandoned_commit(commit_b.id().clone());
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    let graph = evolog(repo.as_ref(), commit_a.change_id()).unwrap();
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.nodes[0].commit.id(), commit_a2.id());
+    assert!(graph.nodes[1..].iter().all(|node| !node.is_visible));
+    let edges = graph
+        .predecessor_edges(commit_a2.id())
+        .map(|edge| (edge.predecessor.clone(), edge.kind))
+        .collect_vec();
+    assert_eq!(
+        edges,
+        vec![
+            (commit_a.id().clone(), EvologEdgeKind::Squash),
+            (commit_b.id().clone(), EvologEdgeKind::Squash)
+        ]
+    );
+
+    // Unknown changes have an empty graph
+    let graph = evolog(repo.as_ref(), &ChangeId::from_hex("0123")).unwrap();
+    assert!(graph.nodes.is_empty());
+}
@@ -0,0 +1,763 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::evolution::{
+    CommitEvolutionStatus, Evolution, EvolutionAction, EvolutionBlocker, EvolutionError, NewParent,
+    NewParentReason, ObsolescenceReason,
+};
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::{MutableRepo, Repo};
+use jj_lib::revset::RevsetExpression;
+use jj_lib::settings::UserSettings;
+use jj_lib::str_util::StringPattern;
+use testutils::{create_random_commit, write_random_commit, TestRepo};
+
+#[test]
+fn test_obsolescence_reason() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = write_random_commit(mut_repo, &settings);
+    let commit_c = write_random_commit(mut_repo, &settings);
+    let commit_d = write_random_commit(mut_repo, &settings);
+    let repo = tx.commit("test");
+
+    // Rewrite A, abandon B, split C the way `jj split` does, and rewrite D
+    // twice without hiding it
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo.reword(&settings, &commit_a, "rewritten").unwrap();
+    mut_repo.record_abandoned_commit(commit_b.id().clone());
+    let commit_c1 = mut_repo
+        .rewrite_commit(&settings, &commit_c)
+        .set_description("first")
+        .write()
+        .unwrap();
+    let commit_c2 = mut_repo
+        .rewrite_commit(&settings, &commit_c)
+        .set_parents(vec![commit_c1.id().clone()])
+        .set_description("second")
+        .generate_new_change_id()
+        .write()
+        .unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let mut commits_d2 = ["first", "second"].map(|description| {
+        mut_repo
+            .new_commit(
+                &settings,
+                commit_d.parent_ids().to_vec(),
+                commit_d.tree_id().clone(),
+            )
+            .set_change_id(commit_d.change_id().clone())
+            .set_predecessors(vec![commit_d.id().clone()])
+            .set_description(description)
+            .write()
+            .unwrap()
+            .id()
+            .clone()
+    });
+    commits_d2.sort();
+    let repo = tx.commit("test");
+
+    let evolution = Evolution::new(repo.as_ref()).unwrap();
+    assert_eq!(
+        evolution.obsolescence_reason(commit_a.id()).unwrap(),
+        Some(ObsolescenceReason::Rewritten {
+            successors: vec![commit_a2.id().clone()]
+        })
+    );
+    assert_eq!(evolution.obsolescence_reason(commit_a2.id()).unwrap(), None);
+    assert_eq!(
+        evolution.obsolescence_reason(commit_b.id()).unwrap(),
+        Some(ObsolescenceReason::Pruned)
+    );
+    let mut pieces = vec![commit_c1.id().clone(), commit_c2.id().clone()];
+    pieces.sort();
+    assert_eq!(
+        evolution.obsolescence_reason(commit_c.id()).unwrap(),
+        Some(ObsolescenceReason::Split { pieces })
+    );
+    // D is still visible, but obsolete
+    assert!(repo.view().heads().contains(commit_d.id()));
+    assert_eq!(
+        evolution.obsolescence_reason(commit_d.id()).unwrap(),
+        Some(ObsolescenceReason::Rewritten {
+            successors: commits_d2.to_vec()
+        })
+    );
+
+    for commit_id in [commit_a.id(), commit_b.id(), commit_c.id(), commit_d.id()] {
+        assert!(evolution.is_obsolete(commit_id).unwrap());
+    }
+    for commit_id in [
+        commit_a2.id(),
+        commit_c1.id(),
+        repo.store().root_commit_id(),
+    ] {
+        assert!(!evolution.is_obsolete(commit_id).unwrap());
+    }
+}
+
+#[test]
+fn test_evolution_scoped_to_branch() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_f = write_random_commit(mut_repo, &settings);
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(commit_f.id().clone()));
+    let repo = tx.commit("test");
+
+    //   M
+    //  /|
+    // B |
+    // | |
+    // A A2  F2
+    //
+    // A2 was rewritten from A, but A is still visible. F2 was rewritten from F,
+    // which is hidden.
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_a.parent_ids().to_vec(),
+            commit_a.tree_id().clone(),
+        )
+        .set_change_id(commit_a.change_id().clone())
+        .set_predecessors(vec![commit_a.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let commit_m = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_b.id().clone(), commit_a2.id().clone()])
+        .write()
+        .unwrap();
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit_m.id().clone()));
+    let commit_f2 = mut_repo.reword(&settings, &commit_f, "rewritten").unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    let main = RevsetExpression::branches(StringPattern::exact("main"));
+    let scoped = Evolution::scoped_to(repo.as_ref(), &main).unwrap();
+    let unscoped = Evolution::new(repo.as_ref()).unwrap();
+    for evolution in [&scoped, &unscoped] {
+        assert_eq!(
+            evolution.obsolescence_reason(commit_a.id()).unwrap(),
+            Some(ObsolescenceReason::Rewritten {
+                successors: vec![commit_a2.id().clone()]
+            })
+        );
+        assert!(!evolution.is_obsolete(commit_b.id()).unwrap());
+        assert!(evolution.is_orphan(commit_b.id()).unwrap());
+        assert!(evolution.is_orphan(commit_m.id()).unwrap());
+        assert!(!evolution.is_orphan(commit_a2.id()).unwrap());
+        assert!(evolution.is_divergent(commit_a.id()).unwrap());
+        assert!(evolution.is_divergent(commit_a2.id()).unwrap());
+        assert!(!evolution.is_divergent(commit_m.id()).unwrap());
+        // Hidden commits are always in scope
+        assert_eq!(
+            evolution.obsolescence_reason(commit_f.id()).unwrap(),
+            Some(ObsolescenceReason::Rewritten {
+                successors: vec![commit_f2.id().clone()]
+            })
+        );
+    }
+    assert_eq!(
+        *scoped.orphan_ids().unwrap(),
+        vec![commit_m.id().clone(), commit_b.id().clone()]
+    );
+
+    // The feature branch is out of scope
+    assert!(!scoped.is_in_scope(commit_f2.id()));
+    assert_matches!(
+        scoped.obsolescence_reason(commit_f2.id()),
+        Err(EvolutionError::NotInScope(id)) if id == *commit_f2.id()
+    );
+    assert_matches!(
+        scoped.is_orphan(commit_f2.id()),
+        Err(EvolutionError::NotInScope(id)) if id == *commit_f2.id()
+    );
+    assert_matches!(
+        scoped.is_divergent(commit_f2.id()),
+        Err(EvolutionError::NotInScope(id)) if id == *commit_f2.id()
+    );
+    assert!(!unscoped.is_obsolete(commit_f2.id()).unwrap());
+}
+
+#[test]
+fn test_evolution_unknown_commit() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Ids that aren't in the repo aren't reported as pruned
+    let evolution = Evolution::new(repo.as_ref()).unwrap();
+    let unknown_id = CommitId::new(vec![0xab; repo.store().commit_id_length()]);
+    assert_matches!(
+        evolution.obsolescence_reason(&unknown_id),
+        Err(EvolutionError::UnknownCommit(id)) if id == unknown_id
+    );
+    assert_matches!(
+        evolution.is_orphan(&unknown_id),
+        Err(EvolutionError::UnknownCommit(_))
+    );
+}
+
+#[test]
+fn test_evolution_scoped_ancestor_rewritten_elsewhere() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
+    let repo = tx.commit("test");
+
+    // B   A2
+    // |
+    // A
+    //
+    // A was rewritten as A2 on the feature branch, without rebasing main.
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_a.parent_ids().to_vec(),
+            commit_a.tree_id().clone(),
+        )
+        .set_change_id(commit_a.change_id().clone())
+        .set_predecessors(vec![commit_a.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(commit_a2.id().clone()));
+    let repo = tx.commit("test");
+
+    // The successor is found even though it's out of scope
+    let main = RevsetExpression::branches(StringPattern::exact("main"));
+    let scoped = Evolution::scoped_to(repo.as_ref(), &main).unwrap();
+    assert_eq!(
+        scoped.obsolescence_reason(commit_a.id()).unwrap(),
+        Some(ObsolescenceReason::Rewritten {
+            successors: vec![commit_a2.id().clone()]
+        })
+    );
+    assert!(scoped.is_orphan(commit_b.id()).unwrap());
+    assert_eq!(*scoped.orphan_ids().unwrap(), vec![commit_b.id().clone()]);
+    assert_matches!(
+        scoped.obsolescence_reason(commit_a2.id()),
+        Err(EvolutionError::NotInScope(id)) if id == *commit_a2.id()
+    );
+}
+
+#[test]
+fn test_evolution_disabled() {
+    let config = testutils::base_config()
+        .set_override("evolution.enabled", false)
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // The rewrite starts a new change, and isn't linked to the original
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_ne!(commit_a2.change_id(), commit_a.change_id());
+    assert!(commit_a2.predecessor_ids().is_empty());
+    let evolution = Evolution::for_transaction(mut_repo).unwrap();
+    assert!(!evolution.is_obsolete(commit_a.id()).unwrap());
+    assert!(!evolution.is_orphan(commit_b.id()).unwrap());
+
+    // Descendants are still rebased, but nothing is obsolete afterwards
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    let evolution = Evolution::new(repo.as_ref()).unwrap();
+    assert_eq!(evolution.obsolescence_reason(commit_a.id()).unwrap(), None);
+    assert_eq!(evolution.obsolescence_reason(commit_b.id()).unwrap(), None);
+    assert!(evolution.orphan_ids().unwrap().is_empty());
+    let head_ids = repo.view().heads().iter().collect_vec();
+    assert_eq!(head_ids.len(), 1);
+    let commit_b2 = repo.store().get_commit(head_ids[0]).unwrap();
+    assert_eq!(commit_b2.parent_ids(), &[commit_a2.id().clone()]);
+}
+
+/// Writes a rewrite of `commit` onto `parent_ids` without hiding `commit`, as
+/// if from a concurrent operation.
+fn write_concurrent_rewrite(
+    mut_repo: &mut MutableRepo,
+    settings: &UserSettings,
+    commit: &Commit,
+    parent_ids: Vec<CommitId>,
+) -> Commit {
+    create_random_commit(mut_repo, settings)
+        .set_parents(parent_ids)
+        .set_change_id(commit.change_id().clone())
+        .set_predecessors(vec![commit.id().clone()])
+        .write()
+        .unwrap()
+}
+
+fn new_parent(old_parent: &Commit, new_parents: &[&Commit], reason: NewParentReason) -> NewParent {
+    NewParent {
+        old_parent_id: old_parent.id().clone(),
+        new_parent_ids: new_parents.iter().map(|c| c.id().clone()).collect(),
+        reason,
+    }
+}
+
+#[test]
+fn test_analyze_orphans() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // C
+    // B   D
+    // |  /|
+    // A   E
+    // | /
+    // root
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_c = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_b.id().clone()])
+        .write()
+        .unwrap();
+    let commit_e = write_random_commit(mut_repo, &settings);
+    let commit_d = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone(), commit_e.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Rewrite A into A2 without hiding A
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = mut_repo.store().root_commit_id().clone();
+    let commit_a2 = write_concurrent_rewrite(mut_repo, &settings, &commit_a, vec![root_id]);
+
+    let evolution = Evolution::new(mut_repo).unwrap();
+    let mut divergent_ids = vec![commit_a.id().clone(), commit_a2.id().clone()];
+    divergent_ids.sort();
+    assert_eq!(
+        evolution.analyze(commit_a.id(), &[]).unwrap(),
+        CommitEvolutionStatus {
+            obsolescence: Some(ObsolescenceReason::Rewritten {
+                successors: vec![commit_a2.id().clone()]
+            }),
+            is_orphan: false,
+            divergent_commit_ids: divergent_ids.clone(),
+            is_divergence_accepted: false,
+            action: EvolutionAction::Replace {
+                replacement_id: commit_a2.id().clone()
+            },
+        }
+    );
+    assert_eq!(
+        evolution.analyze(commit_a2.id(), &[]).unwrap(),
+        CommitEvolutionStatus {
+            obsolescence: None,
+            is_orphan: false,
+            divergent_commit_ids: divergent_ids,
+            is_divergence_accepted: false,
+            action: EvolutionAction::None,
+        }
+    );
+    let status_b = evolution.analyze(commit_b.id(), &[]).unwrap();
+    assert!(status_b.is_orphan);
+    assert!(status_b.divergent_commit_ids.is_empty());
+    assert_eq!(
+        status_b.action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_a,
+                &[&commit_a2],
+                NewParentReason::Rewritten
+            )]
+        }
+    );
+    assert_eq!(
+        evolution.analyze(commit_c.id(), &[]).unwrap().action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_b,
+                &[&commit_b],
+                NewParentReason::RebasedFirst
+            )]
+        }
+    );
+    assert_eq!(
+        evolution.analyze(commit_d.id(), &[]).unwrap().action,
+        EvolutionAction::Rebase {
+            parents: vec![
+                new_parent(&commit_a, &[&commit_a2], NewParentReason::Rewritten),
+                new_parent(&commit_e, &[&commit_e], NewParentReason::Unchanged),
+            ]
+        }
+    );
+    let status_e = evolution.analyze(commit_e.id(), &[]).unwrap();
+    assert!(!status_e.is_orphan);
+    assert_eq!(status_e.action, EvolutionAction::None);
+
+    // Immutable commits aren't rewritten or hidden
+    let immutable_heads = [commit_c.id().clone(), commit_e.id().clone()];
+    for commit in [&commit_a, &commit_b, &commit_c] {
+        assert_eq!(
+            evolution
+                .analyze(commit.id(), &immutable_heads)
+                .unwrap()
+                .action,
+            EvolutionAction::Blocked(EvolutionBlocker::Immutable)
+        );
+    }
+    assert_matches!(
+        evolution
+            .analyze(commit_d.id(), &immutable_heads)
+            .unwrap()
+            .action,
+        EvolutionAction::Rebase { .. }
+    );
+    assert_eq!(
+        evolution
+            .analyze(commit_e.id(), &immutable_heads)
+            .unwrap()
+            .action,
+        EvolutionAction::None
+    );
+
+    // Evolving does what the analysis said
+    drop(evolution);
+    mut_repo.evolve(&settings).unwrap();
+    let new_commit = |commit: &Commit| {
+        let ids = mut_repo.resolve_change_id(commit.change_id()).unwrap();
+        assert_eq!(ids.len(), 1);
+        mut_repo.store().get_commit(&ids[0]).unwrap()
+    };
+    assert_eq!(new_commit(&commit_a).id(), commit_a2.id());
+    let commit_b2 = new_commit(&commit_b);
+    assert_eq!(commit_b2.parent_ids(), &[commit_a2.id().clone()]);
+    assert_eq!(
+        new_commit(&commit_c).parent_ids(),
+        &[commit_b2.id().clone()]
+    );
+    assert_eq!(
+        new_commit(&commit_d).parent_ids(),
+        &[commit_a2.id().clone(), commit_e.id().clone()]
+    );
+}
+
+#[test]
+fn test_analyze_unresolved_divergence() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Rewrite A into both A2 and A3 without hiding A, and add an unrelated
+    // commit to B's change
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = mut_repo.store().root_commit_id().clone();
+    let commit_a2 = write_concurrent_rewrite(mut_repo, &settings, &commit_a, vec![root_id.clone()]);
+    let commit_a3 = write_concurrent_rewrite(mut_repo, &settings, &commit_a, vec![root_id]);
+    let commit_b2 = create_random_commit(mut_repo, &settings)
+        .set_change_id(commit_b.change_id().clone())
+        .write()
+        .unwrap();
+
+    let evolution = Evolution::new(mut_repo).unwrap();
+    let status_a = evolution.analyze(commit_a.id(), &[]).unwrap();
+    assert_eq!(status_a.divergent_commit_ids.len(), 3);
+    assert_eq!(
+        status_a.action,
+        EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+            commit_id: commit_a.id().clone()
+        })
+    );
+    for commit in [&commit_a2, &commit_a3, &commit_b2] {
+        assert_eq!(
+            evolution.analyze(commit.id(), &[]).unwrap().action,
+            EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+                commit_id: commit.id().clone()
+            })
+        );
+    }
+    // B is blocked on its parent's divergence rather than its own
+    let status_b = evolution.analyze(commit_b.id(), &[]).unwrap();
+    assert!(status_b.is_orphan);
+    assert_eq!(status_b.divergent_commit_ids.len(), 2);
+    assert_eq!(
+        status_b.action,
+        EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+            commit_id: commit_a.id().clone()
+        })
+    );
+}
+
+#[test]
+fn test_analyze_accepted_divergence() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // X has a child, Y has a child and a grandchild
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_x = write_random_commit(mut_repo, &settings);
+    let commit_x_child = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_x.id().clone()])
+        .write()
+        .unwrap();
+    let commit_y = write_random_commit(mut_repo, &settings);
+    let commit_y_child = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_y.id().clone()])
+        .write()
+        .unwrap();
+    let commit_y_grandchild = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_y_child.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Rewrite X into X2 and Y into Y2 and Y3, all without hiding the
+    // originals, and accept both divergences
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = mut_repo.store().root_commit_id().clone();
+    let commit_x2 = write_concurrent_rewrite(mut_repo, &settings, &commit_x, vec![root_id.clone()]);
+    let commit_y2 = write_concurrent_rewrite(mut_repo, &settings, &commit_y, vec![root_id.clone()]);
+    let commit_y3 = write_concurrent_rewrite(mut_repo, &settings, &commit_y, vec![root_id]);
+    mut_repo.accept_divergence(commit_x.change_id());
+    mut_repo.accept_divergence(commit_y.change_id());
+
+    let evolution = Evolution::new(mut_repo).unwrap();
+    // The obsolete variants are kept
+    for commit in [&commit_x, &commit_y] {
+        let status = evolution.analyze(commit.id(), &[]).unwrap();
+        assert!(status.is_divergence_accepted);
+        assert!(status.obsolescence.is_some());
+        assert_eq!(status.action, EvolutionAction::None);
+    }
+    assert_eq!(
+        evolution.analyze(commit_x_child.id(), &[]).unwrap().action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_x,
+                &[&commit_x2],
+                NewParentReason::Rewritten
+            )]
+        }
+    );
+    let mut candidates = vec![commit_y2.id().clone(), commit_y3.id().clone()];
+    candidates.sort();
+    assert_eq!(
+        evolution.analyze(commit_y_child.id(), &[]).unwrap().action,
+        EvolutionAction::Blocked(EvolutionBlocker::AmbiguousParent {
+            parent_id: commit_y.id().clone(),
+            candidates
+        })
+    );
+    assert_eq!(
+        evolution
+            .analyze(commit_y_grandchild.id(), &[])
+            .unwrap()
+            .action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_y_child,
+                &[&commit_y_child],
+                NewParentReason::RebasedFirst
+            )]
+        }
+    );
+}
+
+#[test]
+fn test_analyze_parent_rewritten_into_descendant() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Rewrite A into A2 on top of B
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 =
+        write_concurrent_rewrite(mut_repo, &settings, &commit_a, vec![commit_b.id().clone()]);
+
+    // Replacing A with A2 would make A2 its own ancestor
+    let evolution = Evolution::new(mut_repo).unwrap();
+    assert_eq!(
+        evolution.analyze(commit_a.id(), &[]).unwrap().action,
+        EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+            commit_id: commit_a.id().clone()
+        })
+    );
+    assert_eq!(
+        evolution.analyze(commit_b.id(), &[]).unwrap().action,
+        EvolutionAction::Blocked(EvolutionBlocker::UnresolvedDivergence {
+            commit_id: commit_a.id().clone()
+        })
+    );
+
+    // With the divergence accepted, B would have to go onto its own
+    // descendant
+    drop(evolution);
+    mut_repo.accept_divergence(commit_a.change_id());
+    let evolution = Evolution::new(mut_repo).unwrap();
+    assert_eq!(
+        evolution.analyze(commit_b.id(), &[]).unwrap().action,
+        EvolutionAction::Blocked(EvolutionBlocker::ParentRewrittenIntoDescendant {
+            parent_id: commit_a.id().clone(),
+            new_parent_id: commit_a2.id().clone()
+        })
+    );
+}
+
+#[test]
+fn test_analyze_in_transaction() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Each of A, C and E has a child
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let [(commit_a, commit_b), (commit_c, commit_d), (commit_e, commit_f)] = [(); 3].map(|()| {
+        let parent = write_random_commit(mut_repo, &settings);
+        let child = create_random_commit(mut_repo, &settings)
+            .set_parents(vec![parent.id().clone()])
+            .write()
+            .unwrap();
+        (parent, child)
+    });
+    let repo = tx.commit("test");
+
+    // Rewrite A, abandon C, and split E the way `jj split` does
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo.reword(&settings, &commit_a, "rewritten").unwrap();
+    mut_repo.record_abandoned_commit(commit_c.id().clone());
+    let commit_e1 = mut_repo
+        .rewrite_commit(&settings, &commit_e)
+        .set_description("first")
+        .write()
+        .unwrap();
+    let commit_e2 = mut_repo
+        .rewrite_commit(&settings, &commit_e)
+        .set_parents(vec![commit_e1.id().clone()])
+        .set_description("second")
+        .generate_new_change_id()
+        .write()
+        .unwrap();
+
+    let evolution = Evolution::for_transaction(mut_repo).unwrap();
+    let status_a = evolution.analyze(commit_a.id(), &[]).unwrap();
+    assert_eq!(
+        status_a.obsolescence,
+        Some(ObsolescenceReason::Rewritten {
+            successors: vec![commit_a2.id().clone()]
+        })
+    );
+    assert_eq!(status_a.action, EvolutionAction::None);
+    assert_eq!(
+        evolution.analyze(commit_b.id(), &[]).unwrap().action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_a,
+                &[&commit_a2],
+                NewParentReason::Rewritten
+            )]
+        }
+    );
+    let root_commit = mut_repo.store().root_commit();
+    assert_eq!(
+        evolution.analyze(commit_d.id(), &[]).unwrap().action,
+        EvolutionAction::Rebase {
+            parents: vec![new_parent(
+                &commit_c,
+                &[&root_commit],
+                NewParentReason::Abandoned
+            )]
+        }
+    );
+    let mut pieces = vec![commit_e1.id().clone(), commit_e2.id().clone()];
+    pieces.sort();
+    assert_eq!(
+        evolution.analyze(commit_f.id(), &[]).unwrap().action,
+        EvolutionAction::Blocked(EvolutionBlocker::AmbiguousParent {
+            parent_id: commit_e.id().clone(),
+            candidates: pieces
+        })
+    );
+}
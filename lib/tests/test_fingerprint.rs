@@ -0,0 +1,122 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use jj_lib::backend::ChangeId;
+use jj_lib::commit::Commit;
+use jj_lib::fingerprint::FingerprintDepth;
+use jj_lib::op_store::{RefTarget, WorkspaceId};
+use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
+use jj_lib::settings::UserSettings;
+use testutils::TestRepo;
+
+fn stable_settings() -> UserSettings {
+    UserSettings::from_config(
+        testutils::base_config()
+            .add_source(config::File::from_str(
+                r#"
+                    debug.commit-timestamp = "2001-02-03T04:05:06+07:00"
+                    debug.operation-timestamp = "2001-02-03T04:05:06+07:00"
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap(),
+    )
+}
+
+fn write_commit(mut_repo: &mut MutableRepo, settings: &UserSettings, name: &str) -> Commit {
+    let store = mut_repo.store().clone();
+    mut_repo
+        .new_commit(
+            settings,
+            vec![store.root_commit_id().clone()],
+            store.empty_merged_tree_id(),
+        )
+        .set_change_id(ChangeId::try_from_hex(&name.repeat(32)).unwrap())
+        .set_description(name)
+        .write()
+        .unwrap()
+}
+
+/// Creates a repo with the commits "a" and "b" written in the given order, and
+/// optionally a hidden commit "c".
+fn build_repo(names: &[&str], with_hidden_commit: bool) -> (TestRepo, Arc<ReadonlyRepo>) {
+    let settings = stable_settings();
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    for name in names {
+        let commit = write_commit(tx.mut_repo(), &settings, name);
+        if *name == "a" {
+            tx.mut_repo()
+                .set_local_branch_target("main", RefTarget::normal(commit.id().clone()));
+            tx.mut_repo()
+                .set_wc_commit(WorkspaceId::default(), commit.id().clone())
+                .unwrap();
+        }
+    }
+    if with_hidden_commit {
+        let commit = write_commit(tx.mut_repo(), &settings, "c");
+        tx.mut_repo().remove_head(commit.id());
+    }
+    let repo = tx.commit("test");
+    (test_repo, repo)
+}
+
+#[test]
+fn test_fingerprint_identical_repos() {
+    let (_test_repo1, repo1) = build_repo(&["a", "b"], false);
+    let (_test_repo2, repo2) = build_repo(&["a", "b"], false);
+    for depth in [FingerprintDepth::Shallow, FingerprintDepth::Deep] {
+        let fingerprint1 = repo1.state_fingerprint(depth).unwrap();
+        let fingerprint2 = repo2.state_fingerprint(depth).unwrap();
+        assert_eq!(fingerprint1, fingerprint2);
+        assert_eq!(fingerprint1.to_hex().len(), 128);
+    }
+    assert_ne!(
+        repo1.state_fingerprint(FingerprintDepth::Shallow).unwrap(),
+        repo1.state_fingerprint(FingerprintDepth::Deep).unwrap()
+    );
+}
+
+#[test]
+fn test_fingerprint_insertion_order() {
+    // The order the commits were added to the index in doesn't matter
+    let (_test_repo1, repo1) = build_repo(&["a", "b"], false);
+    let (_test_repo2, repo2) = build_repo(&["b", "a"], false);
+    assert_eq!(repo1.op_id(), repo2.op_id());
+    for depth in [FingerprintDepth::Shallow, FingerprintDepth::Deep] {
+        assert_eq!(
+            repo1.state_fingerprint(depth).unwrap(),
+            repo2.state_fingerprint(depth).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_fingerprint_hidden_commit() {
+    // A hidden commit changes only the deep fingerprint
+    let (_test_repo1, repo1) = build_repo(&["a", "b"], false);
+    let (_test_repo2, repo2) = build_repo(&["a", "b"], true);
+    assert_eq!(repo1.op_id(), repo2.op_id());
+    assert_eq!(
+        repo1.state_fingerprint(FingerprintDepth::Shallow).unwrap(),
+        repo2.state_fingerprint(FingerprintDepth::Shallow).unwrap()
+    );
+    assert_ne!(
+        repo1.state_fingerprint(FingerprintDepth::Deep).unwrap(),
+        repo2.state_fingerprint(FingerprintDepth::Deep).unwrap()
+    );
+}
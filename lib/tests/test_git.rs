@@ -26,14 +26,17 @@ use jj_lib::commit_builder::CommitBuilder;
 use jj_lib::git;
 use jj_lib::git::{
     FailedRefExportReason, GitBranchPushTargets, GitFetchError, GitImportError, GitPushError,
-    GitRefUpdate, RefName, SubmoduleConfig,
+    GitRefUpdate, GitResetHeadError, RefName, SubmoduleConfig,
 };
 use jj_lib::git_backend::GitBackend;
+use jj_lib::matchers::EverythingMatcher;
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef, RemoteRefState};
+use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
 use jj_lib::refs::BranchPushUpdate;
 use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo};
 use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::RevsetExpression;
+use jj_lib::scratch_dir::ScratchDir;
 use jj_lib::settings::{GitSettings, UserSettings};
 use jj_lib::signing::Signer;
 use jj_lib::str_util::StringPattern;
@@ -2210,6 +2213,103 @@ fn test_init() {
     assert!(!repo.view().heads().contains(&jj_id(&initial_git_commit)));
 }
 
+#[test]
+fn test_bare_repo_without_working_copy() {
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let temp_dir = testutils::new_temp_dir();
+    let git_repo_dir = temp_dir.path().join("git");
+    let jj_repo_dir = temp_dir.path().join("jj");
+    let git_repo = git2::Repository::init_bare(git_repo_dir).unwrap();
+    let signature = git2::Signature::now("Someone", "someone@example.com").unwrap();
+    let write_git_commit = |content: &str, parents: &[&git2::Commit]| {
+        let blob_id = git_repo.blob(content.as_bytes()).unwrap();
+        let mut tree_builder = git_repo.treebuilder(None).unwrap();
+        tree_builder.insert("file", blob_id, 0o100644).unwrap();
+        let tree = git_repo.find_tree(tree_builder.write().unwrap()).unwrap();
+        let oid = git_repo
+            .commit(
+                Some("refs/heads/main"),
+                &signature,
+                &signature,
+                content,
+                &tree,
+                parents,
+            )
+            .unwrap();
+        git_repo.find_commit(oid).unwrap()
+    };
+    let git_commit1 = write_git_commit("1", &[]);
+    let git_commit2 = write_git_commit("2", &[&git_commit1]);
+
+    std::fs::create_dir(&jj_repo_dir).unwrap();
+    let repo = ReadonlyRepo::init(
+        &settings,
+        &jj_repo_dir,
+        &|settings, store_path| {
+            Ok(Box::new(GitBackend::init_external(
+                settings,
+                store_path,
+                git_repo.path(),
+            )?))
+        },
+        Signer::from_settings(&settings).unwrap(),
+        ReadonlyRepo::default_op_store_initializer(),
+        ReadonlyRepo::default_op_heads_store_initializer(),
+        ReadonlyRepo::default_index_store_initializer(),
+        ReadonlyRepo::default_submodule_store_initializer(),
+    )
+    .unwrap();
+    assert_eq!(get_git_backend(&repo).git_workdir(), None);
+
+    let mut tx = repo.start_transaction(&settings);
+    git::import_refs(tx.mut_repo(), &git_settings).unwrap();
+    let repo = tx.commit("import");
+    assert_eq!(
+        *repo.view().get_local_branch("main"),
+        RefTarget::normal(jj_id(&git_commit2))
+    );
+
+    // Walk the history of the imported branch
+    let ancestors = RevsetExpression::commit(jj_id(&git_commit2))
+        .ancestors()
+        .evaluate_programmatic(repo.as_ref())
+        .unwrap()
+        .iter()
+        .collect_vec();
+    assert_eq!(
+        ancestors,
+        vec![
+            jj_id(&git_commit2),
+            jj_id(&git_commit1),
+            repo.store().root_commit_id().clone()
+        ]
+    );
+
+    // Diff the trees of the imported commits
+    let commit1 = repo.store().get_commit(&jj_id(&git_commit1)).unwrap();
+    let commit2 = repo.store().get_commit(&jj_id(&git_commit2)).unwrap();
+    let diff = commit1
+        .tree()
+        .unwrap()
+        .diff(&commit2.tree().unwrap(), &EverythingMatcher)
+        .map(|(path, diff)| (path, diff.unwrap()))
+        .collect_vec();
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].0.as_internal_file_string(), "file");
+
+    // Updating the Git working copy fails because there is none
+    let mut tx = repo.start_transaction(&settings);
+    let wc_commit = tx
+        .mut_repo()
+        .check_out(WorkspaceId::default(), &settings, &commit2)
+        .unwrap();
+    assert_matches!(
+        git::reset_head(tx.mut_repo(), &git_repo, &wc_commit),
+        Err(GitResetHeadError::NoWorkingCopy)
+    );
+}
+
 #[test]
 fn test_fetch_empty_repo() {
     let test_data = GitRepoData::create();
@@ -3400,6 +3500,7 @@ ignoreThisSection = foo
 ignoreThisSection = foo
 "#
         .as_bytes(),
+        &ScratchDir::os_temp(),
     )
     .unwrap();
     let expected = btreemap! {
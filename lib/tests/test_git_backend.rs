@@ -13,16 +13,21 @@
 // limitations under the License.
 
 use std::collections::HashSet;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use jj_lib::backend::CommitId;
+use assert_matches::assert_matches;
+use jj_lib::backend::{BackendError, CommitId};
 use jj_lib::git_backend::GitBackend;
 use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPath;
 use maplit::hashset;
-use testutils::{create_random_commit, CommitGraphBuilder, TestRepo, TestRepoBackend};
+use testutils::{
+    create_random_commit, load_repo_at_head, read_file, write_file, CommitGraphBuilder, TestRepo,
+    TestRepoBackend,
+};
 
 fn get_git_backend(repo: &Arc<ReadonlyRepo>) -> &GitBackend {
     repo.store()
@@ -191,3 +196,70 @@ fn test_gc() {
     repo.store().gc(base_index.as_index(), now()).unwrap();
     assert_eq!(collect_no_gc_refs(git_repo_path), hashset! {});
 }
+
+#[test]
+fn test_store_with_alternates() {
+    let settings = testutils::user_settings();
+    let parent_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let child_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let path = RepoPath::from_internal_string("file");
+    let parent_file_id = write_file(parent_repo.repo.store(), path, "parent contents\n");
+
+    // Without alternates, the child doesn't see the parent's objects
+    let child_store = child_repo.repo.store().clone();
+    assert_matches!(
+        child_store.read_file(path, &parent_file_id).err(),
+        Some(BackendError::ObjectNotFound { .. })
+    );
+
+    // Reads fall through to the parent's objects
+    let parent_objects_dir = get_git_backend(&parent_repo.repo)
+        .git_repo_path()
+        .join("objects");
+    let child_repo_path = child_repo.repo.repo_path();
+    let child_store = load_repo_at_head(&settings, child_repo_path)
+        .store()
+        .clone()
+        .with_alternates(&[parent_objects_dir])
+        .unwrap();
+    assert_eq!(
+        read_file(&child_store, path, &parent_file_id),
+        b"parent contents\n"
+    );
+
+    // The alternates aren't written to the child's Git repo
+    let alternates_path = get_git_backend(&child_repo.repo)
+        .git_repo_path()
+        .join("objects")
+        .join("info")
+        .join("alternates");
+    assert!(!alternates_path.exists());
+    let reloaded_child_store = load_repo_at_head(&settings, child_repo_path)
+        .store()
+        .clone();
+    assert_matches!(
+        reloaded_child_store.read_file(path, &parent_file_id).err(),
+        Some(BackendError::ObjectNotFound { .. })
+    );
+
+    // Writes go to the child only
+    let child_file_id = write_file(&child_store, path, "child contents\n");
+    assert_matches!(
+        parent_repo
+            .repo
+            .store()
+            .read_file(path, &child_file_id)
+            .err(),
+        Some(BackendError::ObjectNotFound { .. })
+    );
+}
+
+#[test]
+fn test_store_with_alternates_unsupported() {
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let store = test_repo.repo.store().clone();
+    assert_matches!(
+        store.with_alternates(&[PathBuf::from("objects")]),
+        Err(BackendError::Unsupported(_))
+    );
+}
@@ -727,6 +727,80 @@ fn test_reindex_missing_commit() {
     assert_matches!(err, DefaultIndexStoreError::IndexCommits { op_id, .. } if op_id == *bad_op_id);
 }
 
+#[test]
+fn test_parent_ids_without_store_reads() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store().clone();
+    let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+
+    // o D
+    // |\
+    // o | C
+    // | o B
+    // |/
+    // o A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let commits = [commit_a, commit_b, commit_c, commit_d];
+
+    // Served by the mutable index
+    let num_commit_reads = test_backend.num_commit_reads();
+    for commit in &commits {
+        assert_eq!(tx.repo().parent_ids(commit.id()), commit.parent_ids());
+    }
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads);
+
+    // Served by the readonly index
+    let repo = tx.commit("test");
+    let num_commit_reads = test_backend.num_commit_reads();
+    for commit in &commits {
+        assert_eq!(repo.parent_ids(commit.id()), commit.parent_ids());
+    }
+    assert!(repo.parent_ids(store.root_commit_id()).is_empty());
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads);
+}
+
+#[test]
+fn test_reindex_parent_cycle() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = child_commit(tx.mut_repo(), &settings, &commit_a)
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Overwrite A with data making B its parent, which can only happen with
+    // corrupted or hand-crafted data.
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let mut data_a = commit_a.store_commit().clone();
+    data_a.parents = vec![commit_b.id().clone()];
+    test_backend.write_commit_unchecked(commit_a.id().clone(), data_a);
+    let repo = load_repo_at_head(&settings, repo.repo_path()); // discard cache
+
+    let default_index_store: &DefaultIndexStore =
+        repo.index_store().as_any().downcast_ref().unwrap();
+    default_index_store.reinit().unwrap();
+    let err = default_index_store
+        .build_index_at_operation(repo.operation(), repo.store())
+        .unwrap_err();
+    let mut cycle_ids = vec![commit_a.id().clone(), commit_b.id().clone()];
+    cycle_ids.sort();
+    assert_matches!(
+        err,
+        DefaultIndexStoreError::CycleDetected { commits } if commits == cycle_ids
+    );
+}
+
 /// Test that .jj/repo/index/type is created when the repo is created.
 #[test]
 fn test_index_store_type() {
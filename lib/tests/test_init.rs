@@ -14,13 +14,21 @@
 
 use std::path::{Path, PathBuf};
 
+use assert_matches::assert_matches;
+use jj_lib::backend::{self, ChangeId};
+use jj_lib::files::{ContentTypes, MergeDrivers};
 use jj_lib::git_backend::GitBackend;
+use jj_lib::local_backend::LocalBackend;
+use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::WorkspaceId;
-use jj_lib::repo::Repo;
+use jj_lib::repo::{Repo, StoreFactories};
 use jj_lib::settings::UserSettings;
+use jj_lib::signing::Signer;
+use jj_lib::store::{Store, StoreInitError};
 use jj_lib::workspace::Workspace;
 use test_case::test_case;
-use testutils::{write_random_commit, TestRepoBackend, TestWorkspace};
+use testutils::test_backend::TestBackend;
+use testutils::{write_random_commit, TestRepo, TestRepoBackend, TestWorkspace};
 
 fn canonicalize(input: &Path) -> (PathBuf, PathBuf) {
     let uncanonical = input.join("..").join(input.file_name().unwrap());
@@ -180,3 +188,123 @@ fn test_init_checkout(backend: TestRepoBackend) {
     assert_eq!(wc_commit.committer().name, settings.user_name());
     assert_eq!(wc_commit.committer().email, settings.user_email());
 }
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_init_root_commit(backend: TestRepoBackend) {
+    // The root commit is derived from constants, so it's the same in every repo
+    // using the same backend type, and shares its change id across backends
+    let test_repo1 = TestRepo::init_with_backend(backend);
+    let test_repo2 = TestRepo::init_with_backend(backend);
+    let store1 = test_repo1.repo.store();
+    let store2 = test_repo2.repo.store();
+    assert_eq!(store1.root_commit_id(), store2.root_commit_id());
+    assert!(store1.root_commit_id().as_bytes().iter().all(|&b| b == 0));
+    assert_eq!(store1.empty_tree_id(), store2.empty_tree_id());
+    assert_eq!(store1.root_change_id(), &ChangeId::from_bytes(&[0; 16]));
+
+    let root_commit = store1.root_commit();
+    assert_eq!(root_commit.id(), store1.root_commit_id());
+    assert_eq!(root_commit.change_id(), store1.root_change_id());
+    assert!(root_commit.parent_ids().is_empty());
+    assert_eq!(*root_commit.tree_id(), store1.empty_merged_tree_id());
+    assert_eq!(root_commit, store1.root_commit());
+}
+
+#[test]
+fn test_init_root_commit_not_read_from_backend() {
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Test);
+    let store = test_repo.repo.store();
+    let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+
+    let num_commit_reads = test_backend.num_commit_reads();
+    for _ in 0..3 {
+        let root_commit = store.root_commit();
+        assert_eq!(root_commit.id(), store.root_commit_id());
+        store.get_commit(store.root_commit_id()).unwrap();
+    }
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads);
+}
+
+#[test_case("local" ; "local backend")]
+#[test_case("git" ; "git backend")]
+fn test_init_store_from_url(scheme: &str) {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let store_path = temp_dir.path();
+    let url = format!("{scheme}:{}", store_path.to_str().unwrap());
+    let store = Store::init(&url, &settings, &StoreFactories::default()).unwrap();
+    assert_eq!(store.backend_impl().is::<GitBackend>(), scheme == "git");
+
+    let commit = store
+        .write_commit(
+            backend::Commit {
+                parents: vec![store.root_commit_id().clone()],
+                predecessors: vec![],
+                root_tree: store.empty_merged_tree_id(),
+                change_id: ChangeId::new(vec![1; 16]),
+                description: "written via url".to_string(),
+                description_lossy: false,
+                author: settings.signature(),
+                committer: settings.signature(),
+                secure_sig: None,
+            },
+            None,
+        )
+        .unwrap();
+
+    // The store can be loaded by the backend type recorded at init
+    let backend = StoreFactories::default()
+        .load_backend(&settings, store_path)
+        .unwrap();
+    let loaded_store = Store::new(
+        backend,
+        Signer::new(None, vec![]),
+        false,
+        MergeDrivers::default(),
+        ContentTypes::default(),
+    );
+    let loaded_commit = loaded_store.get_commit(commit.id()).unwrap();
+    assert_eq!(loaded_commit.id(), commit.id());
+    assert_eq!(loaded_commit.description(), "written via url");
+    assert_eq!(loaded_commit.parent_ids(), [store.root_commit_id().clone()]);
+}
+
+#[test]
+fn test_init_store_from_invalid_url() {
+    let settings = testutils::user_settings();
+    let store_factories = StoreFactories::default();
+    let temp_dir = testutils::new_temp_dir();
+    let path = temp_dir.path().to_str().unwrap();
+    assert_matches!(
+        Store::init(&format!("s3:{path}"), &settings, &store_factories),
+        Err(StoreInitError::UnsupportedScheme(scheme)) if scheme == "s3"
+    );
+    assert_matches!(
+        Store::init(path, &settings, &store_factories),
+        Err(StoreInitError::InvalidUrl(_))
+    );
+    assert_matches!(
+        Store::init("local:", &settings, &store_factories),
+        Err(StoreInitError::InvalidUrl(_))
+    );
+    // A Windows path isn't mistaken for a URL with a one-letter scheme
+    assert_matches!(
+        Store::init(r"C:\repo", &settings, &store_factories),
+        Err(StoreInitError::InvalidUrl(url)) if url == r"C:\repo"
+    );
+}
+
+#[test]
+fn test_init_store_from_registered_scheme() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let mut store_factories = StoreFactories::default();
+    store_factories.add_backend_initializer(
+        "s3",
+        Box::new(|_settings, store_path| Ok(Box::new(LocalBackend::init(store_path)))),
+    );
+    let url = format!("s3:{}", temp_dir.path().to_str().unwrap());
+    let store = Store::init(&url, &settings, &store_factories).unwrap();
+    assert!(store.backend_impl().is::<LocalBackend>());
+}
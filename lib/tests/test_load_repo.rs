@@ -12,8 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use jj_lib::repo::RepoLoader;
-use testutils::{write_random_commit, TestRepo};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assert_matches::assert_matches;
+use jj_lib::backend::BackendError;
+use jj_lib::default_index::DefaultIndexStore;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::{Repo as _, RepoLoader, RepoLoaderError, StoreLoadError};
+use jj_lib::repo_format::{self, UpgradeError, UpgradeOptions, CURRENT_FORMAT_VERSION};
+use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::RevsetExpression;
+use maplit::hashset;
+use testutils::test_backend::TestBackend;
+use testutils::{write_random_commit, TestRepo, TestRepoBackend};
 
 #[test]
 fn test_load_at_operation() {
@@ -51,3 +64,278 @@ fn test_load_at_operation() {
     let old_repo = loader.load_at(repo.operation()).unwrap();
     assert!(old_repo.view().heads().contains(commit.id()));
 }
+
+#[test]
+fn test_load_store_view_mismatch() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+
+    // The store and the op store have the same id
+    let store_repo_id = fs::read_to_string(repo_path.join("store").join("repo_id")).unwrap();
+    let op_store_repo_id_path = repo_path.join("op_store").join("repo_id");
+    assert_eq!(
+        fs::read_to_string(&op_store_repo_id_path).unwrap(),
+        store_repo_id
+    );
+
+    // Pretend that the op store was copied from another repo
+    fs::write(&op_store_repo_id_path, "0123456789abcdef").unwrap();
+    let result = RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories());
+    assert_matches!(
+        result.err(),
+        Some(StoreLoadError::StoreViewMismatch {
+            store_repo_id: id1,
+            op_store_repo_id: id2,
+        }) if id1 == store_repo_id && id2 == "0123456789abcdef"
+    );
+
+    // Repos without ids aren't checked
+    fs::remove_file(&op_store_repo_id_path).unwrap();
+    assert!(RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories()).is_ok());
+}
+
+#[test]
+fn test_load_stale_heads() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commits");
+    assert!(repo.stale_heads().unwrap().is_empty());
+
+    // Lose the object of one of the heads
+    fs::remove_file(
+        repo.repo_path()
+            .join("store")
+            .join("commits")
+            .join(commit_b.id().hex()),
+    )
+    .unwrap();
+    let repo = testutils::load_repo_at_head(&settings, repo.repo_path());
+    assert_eq!(repo.stale_heads().unwrap(), [commit_b.id().clone()]);
+    // The view is left as the operation recorded it
+    assert_eq!(
+        *repo.view().heads(),
+        hashset! {commit_a.id().clone(), commit_b.id().clone()}
+    );
+    assert_eq!(
+        repo.store().get_commit(commit_a.id()).unwrap().id(),
+        commit_a.id()
+    );
+}
+
+#[test]
+fn test_load_reads_no_commits() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Test);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    for _ in 0..5 {
+        write_random_commit(tx.mut_repo(), &settings);
+    }
+    let repo = tx.commit("add commits");
+
+    // Stale heads are only looked up when asked for
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let num_commit_reads = test_backend.num_commit_reads();
+    let repo = testutils::load_repo_at_head(&settings, repo.repo_path());
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads);
+    assert!(repo.stale_heads().unwrap().is_empty());
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads + 5);
+}
+
+#[test]
+fn test_load_read_only() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("add commits");
+
+    // Remove the index so it would have to be rebuilt
+    let index_store: &DefaultIndexStore = repo.index_store().as_any().downcast_ref().unwrap();
+    index_store.reinit().unwrap();
+    let repo_path = repo.repo_path();
+    let dir_contents_before = dir_contents(repo_path);
+
+    let loader =
+        RepoLoader::init_read_only(&settings, repo_path, &TestRepo::default_store_factories())
+            .unwrap();
+    let read_only_repo = loader.load_at_head(&settings).unwrap();
+    assert!(read_only_repo.store().is_read_only());
+    let visible_ids: HashSet<_> = RevsetExpression::visible_heads()
+        .ancestors()
+        .evaluate_programmatic(read_only_repo.as_ref())
+        .unwrap()
+        .iter()
+        .collect();
+    assert!(visible_ids.contains(commit_a.id()));
+    assert!(visible_ids.contains(commit_b.id()));
+    assert_eq!(dir_contents(repo_path), dir_contents_before);
+
+    assert_matches!(
+        read_only_repo.try_start_transaction(&settings).err(),
+        Some(RepoLoaderError::ReadOnly)
+    );
+    assert_matches!(
+        read_only_repo
+            .store()
+            .write_symlink(RepoPath::from_internal_string("link"), "target"),
+        Err(BackendError::Other(_))
+    );
+    assert_eq!(dir_contents(repo_path), dir_contents_before);
+}
+
+/// Lists the files under `dir` recursively, along with their contents.
+fn dir_contents(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut entries = vec![];
+    let mut pending = vec![dir.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let contents = fs::read(&path).unwrap();
+                entries.push((path, contents));
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
+
+#[test]
+fn test_load_old_format() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    assert_eq!(
+        repo_format::read_format_version(repo_path).unwrap(),
+        CURRENT_FORMAT_VERSION
+    );
+
+    // Repos created before the format was versioned can be loaded read-only
+    // without upgrading them
+    fs::remove_file(repo_path.join("format")).unwrap();
+    fs::remove_file(repo_path.join("store").join("repo_id")).unwrap();
+    assert_eq!(repo_format::read_format_version(repo_path).unwrap(), 1);
+    let loader =
+        RepoLoader::init_read_only(&settings, repo_path, &TestRepo::default_store_factories())
+            .unwrap();
+    assert!(loader.store().is_read_only());
+    let repo = loader.load_at_head(&settings).unwrap();
+    assert_matches!(
+        repo.try_start_transaction(&settings).err(),
+        Some(RepoLoaderError::ReadOnly)
+    );
+    assert_eq!(repo_format::read_format_version(repo_path).unwrap(), 1);
+
+    // Loading them for writing upgrades them, since the migrations only add
+    // optional files
+    assert!(repo_format::can_upgrade_automatically(1));
+    RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories()).unwrap();
+    assert_eq!(
+        repo_format::read_format_version(repo_path).unwrap(),
+        CURRENT_FORMAT_VERSION
+    );
+    assert!(repo_path.join("store").join("repo_id").exists());
+
+    // Repos in a newer format can't be loaded at all
+    fs::write(repo_path.join("format"), "99").unwrap();
+    assert_matches!(
+        RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories()).err(),
+        Some(StoreLoadError::UnsupportedFormat { version: 99, .. })
+    );
+}
+
+#[test]
+fn test_upgrade_format() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let store_repo_id_path = repo_path.join("store").join("repo_id");
+    let op_store_repo_id_path = repo_path.join("op_store").join("repo_id");
+    let repo_id = fs::read_to_string(&store_repo_id_path).unwrap();
+    fs::remove_file(repo_path.join("format")).unwrap();
+    fs::remove_file(&op_store_repo_id_path).unwrap();
+
+    let report = repo_format::upgrade(
+        repo_path,
+        CURRENT_FORMAT_VERSION,
+        &UpgradeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(report.from_version, 1);
+    assert_eq!(report.to_version, CURRENT_FORMAT_VERSION);
+    assert_eq!(report.migrations.len(), 1);
+    assert!(!report.resumed);
+    assert_eq!(fs::read_to_string(&op_store_repo_id_path).unwrap(), repo_id);
+    let loader =
+        RepoLoader::init(&settings, repo_path, &TestRepo::default_store_factories()).unwrap();
+    assert!(!loader.store().is_read_only());
+
+    // Upgrading again does nothing
+    let report = repo_format::upgrade(
+        repo_path,
+        CURRENT_FORMAT_VERSION,
+        &UpgradeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(report.from_version, CURRENT_FORMAT_VERSION);
+    assert!(report.migrations.is_empty());
+
+    // Downgrades are rejected
+    assert_matches!(
+        repo_format::upgrade(repo_path, 1, &UpgradeOptions::default()),
+        Err(UpgradeError::Downgrade {
+            current: CURRENT_FORMAT_VERSION,
+            target: 1
+        })
+    );
+}
+
+#[test]
+fn test_upgrade_format_resume() {
+    let test_repo = TestRepo::init();
+    let repo_path = test_repo.repo.repo_path();
+    let store_repo_id_path = repo_path.join("store").join("repo_id");
+    let op_store_repo_id_path = repo_path.join("op_store").join("repo_id");
+    fs::remove_file(repo_path.join("format")).unwrap();
+    fs::remove_file(&store_repo_id_path).unwrap();
+    fs::remove_file(&op_store_repo_id_path).unwrap();
+
+    // Crash after the store id was written but before the op store id was
+    let options = UpgradeOptions {
+        fail_point: Some("store-repo-id-written".to_owned()),
+    };
+    assert_matches!(
+        repo_format::upgrade(repo_path, CURRENT_FORMAT_VERSION, &options),
+        Err(UpgradeError::Interrupted(_))
+    );
+    assert_eq!(repo_format::read_format_version(repo_path).unwrap(), 1);
+    assert!(repo_path.join("upgrade_journal").exists());
+    let repo_id = fs::read_to_string(&store_repo_id_path).unwrap();
+    assert!(!op_store_repo_id_path.exists());
+
+    // Running the upgrade again finishes it and keeps the id already written
+    let report = repo_format::upgrade(
+        repo_path,
+        CURRENT_FORMAT_VERSION,
+        &UpgradeOptions::default(),
+    )
+    .unwrap();
+    assert!(report.resumed);
+    assert_eq!(report.to_version, CURRENT_FORMAT_VERSION);
+    assert!(!repo_path.join("upgrade_journal").exists());
+    assert_eq!(fs::read_to_string(&store_repo_id_path).unwrap(), repo_id);
+    assert_eq!(fs::read_to_string(&op_store_repo_id_path).unwrap(), repo_id);
+}
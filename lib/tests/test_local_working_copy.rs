@@ -12,19 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs::File;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 #[cfg(unix)]
 use std::os::unix::net::UnixListener;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use assert_matches::assert_matches;
 use indoc::indoc;
 use itertools::Itertools;
 use jj_lib::backend::{MergedTreeId, TreeId, TreeValue};
 use jj_lib::file_util::{check_symlink_support, try_symlink};
 use jj_lib::fsmonitor::FsmonitorSettings;
-use jj_lib::local_working_copy::LocalWorkingCopy;
+use jj_lib::local_working_copy::{LocalWorkingCopy, PathRules, TreeState};
 use jj_lib::merge::{Merge, MergedTreeValue};
 use jj_lib::merged_tree::{MergedTree, MergedTreeBuilder};
 use jj_lib::op_store::{OperationId, WorkspaceId};
@@ -32,9 +35,13 @@ use jj_lib::repo::{ReadonlyRepo, Repo};
 use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
 use jj_lib::secret_backend::SecretBackend;
 use jj_lib::settings::UserSettings;
-use jj_lib::working_copy::{CheckoutStats, SnapshotError, SnapshotOptions};
+use jj_lib::working_copy::{
+    self, CheckoutError, CheckoutStats, SnapshotError, SnapshotOptions, UntrackedEntry,
+    UntrackedReason,
+};
 use jj_lib::workspace::{default_working_copy_factories, LockedWorkspace, Workspace};
 use test_case::test_case;
+use testutils::test_backend::TestBackend;
 use testutils::{
     commit_with_tree, create_tree, write_random_commit, TestRepo, TestRepoBackend, TestWorkspace,
 };
@@ -481,6 +488,7 @@ fn test_conflicting_changes_on_disk() {
             added_files: 3,
             removed_files: 0,
             skipped_files: 3,
+            skipped_paths: vec![],
         }
     );
 
@@ -637,6 +645,7 @@ fn test_materialize_snapshot_conflicted_files() {
             added_files: 2,
             removed_files: 0,
             skipped_files: 0,
+            skipped_paths: vec![],
         }
     );
 
@@ -741,6 +750,103 @@ fn test_snapshot_racy_timestamps() {
     }
 }
 
+#[test]
+fn test_external_modification() {
+    // Tests that a file modified on disk behind the working copy's back is
+    // picked up by the next snapshot without re-hashing the unmodified files,
+    // and isn't overwritten by a checkout.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let store = repo.store().clone();
+    let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+
+    let modified_path = RepoPath::from_internal_string("modified");
+    let untouched_path = RepoPath::from_internal_string("dir/untouched");
+    let tree1 = create_tree(
+        &repo,
+        &[
+            (modified_path, "contents 1"),
+            (untouched_path, "contents 1"),
+        ],
+    );
+    let tree2 = create_tree(
+        &repo,
+        &[
+            (modified_path, "contents 2"),
+            (untouched_path, "contents 2"),
+        ],
+    );
+    let commit2 = commit_with_tree(&store, tree2.id());
+    test_workspace
+        .workspace
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &commit_with_tree(&store, tree1.id()),
+        )
+        .unwrap();
+
+    // Backdate the files so their states can't be considered racy, and record
+    // the new states.
+    let past = SystemTime::now() - Duration::from_secs(60);
+    for path in [modified_path, untouched_path] {
+        let file = File::options()
+            .write(true)
+            .open(path.to_fs_path(&workspace_root))
+            .unwrap();
+        file.set_modified(past).unwrap();
+    }
+    assert_eq!(test_workspace.snapshot().unwrap().id(), tree1.id());
+
+    // Only the modified file is hashed again
+    std::fs::write(modified_path.to_fs_path(&workspace_root), "external edit").unwrap();
+    let expected_tree = create_tree(
+        &repo,
+        &[
+            (modified_path, "external edit"),
+            (untouched_path, "contents 1"),
+        ],
+    );
+    let num_file_writes = test_backend.num_file_writes();
+    assert_eq!(test_workspace.snapshot().unwrap().id(), expected_tree.id());
+    assert_eq!(test_backend.num_file_writes(), num_file_writes + 1);
+
+    // A checkout leaves the modified file alone
+    std::fs::write(modified_path.to_fs_path(&workspace_root), "another edit").unwrap();
+    let stats = test_workspace
+        .workspace
+        .check_out(repo.op_id().clone(), None, &commit2)
+        .unwrap();
+    assert_eq!(
+        stats,
+        CheckoutStats {
+            updated_files: 2,
+            added_files: 0,
+            removed_files: 0,
+            skipped_files: 1,
+            skipped_paths: vec![],
+        }
+    );
+    assert_eq!(
+        std::fs::read_to_string(modified_path.to_fs_path(&workspace_root)).unwrap(),
+        "another edit"
+    );
+    assert_eq!(
+        std::fs::read_to_string(untouched_path.to_fs_path(&workspace_root)).unwrap(),
+        "contents 2"
+    );
+    let expected_tree = create_tree(
+        &repo,
+        &[
+            (modified_path, "another edit"),
+            (untouched_path, "contents 2"),
+        ],
+    );
+    assert_eq!(test_workspace.snapshot().unwrap().id(), expected_tree.id());
+}
+
 #[cfg(unix)]
 #[test]
 fn test_snapshot_special_file() {
@@ -800,6 +906,182 @@ fn test_snapshot_special_file() {
     );
 }
 
+#[test]
+fn test_checkout_unmaterializable_paths() {
+    // Tests that paths the platform can't represent are skipped on checkout and
+    // kept in the tree by the next snapshot. Windows rules are injected so the
+    // test works on any host.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let temp_dir = testutils::new_temp_dir();
+    let wc_dir = temp_dir.path().join("repo");
+    let state_dir = temp_dir.path().join("state");
+    std::fs::create_dir(&wc_dir).unwrap();
+    std::fs::create_dir(&state_dir).unwrap();
+
+    let normal_path = RepoPath::from_internal_string("normal");
+    let reserved_path = RepoPath::from_internal_string("aux.c");
+    let reserved_dir_path = RepoPath::from_internal_string("con/file");
+    let trailing_dot_path = RepoPath::from_internal_string("dir/name.");
+    let long_path_string = "d/".repeat(150) + "file";
+    let long_path = RepoPath::from_internal_string(&long_path_string);
+    let tree = create_tree(
+        repo,
+        &[
+            (normal_path, "normal"),
+            (reserved_path, "reserved"),
+            (reserved_dir_path, "reserved dir"),
+            (trailing_dot_path, "trailing dot"),
+            (long_path, "long"),
+        ],
+    );
+
+    let mut tree_state = TreeState::init(repo.store().clone(), wc_dir.clone(), state_dir).unwrap();
+    tree_state.set_path_rules(PathRules::windows());
+    let stats = tree_state.check_out(&tree).unwrap();
+    assert_eq!(stats.added_files, 5);
+    assert_eq!(stats.skipped_files, 4);
+    assert_eq!(
+        stats.skipped_paths,
+        to_owned_path_vec(&[
+            reserved_path,
+            reserved_dir_path,
+            long_path,
+            trailing_dot_path
+        ])
+    );
+    assert!(normal_path.to_fs_path(&wc_dir).is_file());
+    assert!(!reserved_path.to_fs_path(&wc_dir).exists());
+    assert!(!reserved_dir_path.to_fs_path(&wc_dir).exists());
+    assert!(!trailing_dot_path.to_fs_path(&wc_dir).exists());
+    assert!(!long_path.to_fs_path(&wc_dir).exists());
+
+    // The skipped paths are tracked but not dropped by the snapshot
+    tree_state
+        .snapshot(SnapshotOptions::empty_for_test())
+        .unwrap();
+    assert_eq!(*tree_state.current_tree_id(), tree.id());
+    assert_eq!(tree_state.file_states().paths().count(), 5);
+
+    // Changing a skipped path keeps it skipped, and removing it is fine
+    let tree2 = create_tree(
+        repo,
+        &[
+            (normal_path, "normal"),
+            (reserved_path, "reserved modified"),
+            (long_path, "long"),
+        ],
+    );
+    let stats = tree_state.check_out(&tree2).unwrap();
+    assert_eq!(
+        stats,
+        CheckoutStats {
+            updated_files: 1,
+            added_files: 0,
+            removed_files: 2,
+            skipped_files: 1,
+            skipped_paths: to_owned_path_vec(&[reserved_path]),
+        }
+    );
+    tree_state
+        .snapshot(SnapshotOptions::empty_for_test())
+        .unwrap();
+    assert_eq!(*tree_state.current_tree_id(), tree2.id());
+}
+
+#[test]
+fn test_checkout_unmaterializable_paths_strict() {
+    // Tests that in strict mode, the checkout fails upfront with all the paths
+    // that can't be represented, without writing anything.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let temp_dir = testutils::new_temp_dir();
+    let wc_dir = temp_dir.path().join("repo");
+    let state_dir = temp_dir.path().join("state");
+    std::fs::create_dir(&wc_dir).unwrap();
+    std::fs::create_dir(&state_dir).unwrap();
+
+    let normal_path = RepoPath::from_internal_string("normal");
+    let reserved_path = RepoPath::from_internal_string("dir/NUL");
+    let trailing_space_path = RepoPath::from_internal_string("name ");
+    let tree = create_tree(
+        repo,
+        &[
+            (normal_path, "normal"),
+            (reserved_path, "reserved"),
+            (trailing_space_path, "trailing space"),
+        ],
+    );
+
+    let mut tree_state = TreeState::init(repo.store().clone(), wc_dir.clone(), state_dir).unwrap();
+    tree_state.set_path_rules(PathRules::windows());
+    tree_state.set_strict_path_rules(true);
+    let err = tree_state.check_out(&tree).unwrap_err();
+    assert_matches!(
+        err,
+        CheckoutError::UnmaterializablePaths { paths }
+            if paths == to_owned_path_vec(&[reserved_path, trailing_space_path])
+    );
+    assert!(!normal_path.to_fs_path(&wc_dir).exists());
+    assert_eq!(
+        *tree_state.current_tree_id(),
+        repo.store().empty_merged_tree_id()
+    );
+
+    // The same tree is fine with the rules of Unix
+    tree_state.set_path_rules(PathRules::unix());
+    let stats = tree_state.check_out(&tree).unwrap();
+    assert_eq!(stats.added_files, 3);
+    assert!(stats.skipped_paths.is_empty());
+}
+
+#[test]
+fn test_checkout_keeps_files_at_unmaterialized_paths() {
+    // Tests that files the user created at paths we couldn't write are left
+    // alone when the paths are later modified or removed in the tree.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let temp_dir = testutils::new_temp_dir();
+    let wc_dir = temp_dir.path().join("repo");
+    let state_dir = temp_dir.path().join("state");
+    std::fs::create_dir(&wc_dir).unwrap();
+    std::fs::create_dir(&state_dir).unwrap();
+
+    let modified_path = RepoPath::from_internal_string("aux.c");
+    let removed_path = RepoPath::from_internal_string("nul.txt");
+    let tree1 = create_tree(repo, &[(modified_path, "aux"), (removed_path, "nul")]);
+    let tree2 = create_tree(repo, &[(modified_path, "aux modified")]);
+
+    let mut tree_state = TreeState::init(repo.store().clone(), wc_dir.clone(), state_dir).unwrap();
+    tree_state.set_path_rules(PathRules::windows());
+    let stats = tree_state.check_out(&tree1).unwrap();
+    assert_eq!(stats.skipped_files, 2);
+    std::fs::write(modified_path.to_fs_path(&wc_dir), "user aux").unwrap();
+    std::fs::write(removed_path.to_fs_path(&wc_dir), "user nul").unwrap();
+
+    // Once the paths can be written, the files on disk still aren't ours
+    tree_state.set_path_rules(PathRules::unix());
+    let stats = tree_state.check_out(&tree2).unwrap();
+    assert_eq!(
+        stats,
+        CheckoutStats {
+            updated_files: 1,
+            added_files: 0,
+            removed_files: 1,
+            skipped_files: 1,
+            skipped_paths: vec![],
+        }
+    );
+    assert_eq!(
+        std::fs::read_to_string(modified_path.to_fs_path(&wc_dir)).unwrap(),
+        "user aux"
+    );
+    assert_eq!(
+        std::fs::read_to_string(removed_path.to_fs_path(&wc_dir)).unwrap(),
+        "user nul"
+    );
+}
+
 #[test]
 fn test_gitignores() {
     // Tests that .gitignore files are respected.
@@ -859,6 +1141,82 @@ fn test_gitignores() {
     );
 }
 
+#[test]
+fn test_untracked() {
+    // Tests that untracked files are listed along with the reason they're not
+    // tracked.
+
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let dir_path = RepoPath::from_internal_string("dir");
+    let tracked_path = RepoPath::from_internal_string("dir/tracked");
+    let plain_path = RepoPath::from_internal_string("dir/plain");
+    let nested_gitignore_path = RepoPath::from_internal_string("dir/sub/.gitignore");
+    let ignored_path = RepoPath::from_internal_string("dir/sub/debug.log");
+    let outside_path = RepoPath::from_internal_string("outside");
+
+    let mut locked_ws = test_workspace
+        .workspace
+        .start_working_copy_mutation()
+        .unwrap();
+    locked_ws
+        .locked_wc()
+        .set_sparse_patterns(to_owned_path_vec(&[dir_path]))
+        .unwrap();
+    locked_ws.finish(repo.op_id().clone()).unwrap();
+
+    testutils::write_working_copy_file(&workspace_root, tracked_path, "contents");
+    test_workspace.snapshot().unwrap();
+
+    testutils::write_working_copy_file(&workspace_root, tracked_path, "modified");
+    testutils::write_working_copy_file(&workspace_root, plain_path, "contents");
+    testutils::write_working_copy_file(
+        &workspace_root,
+        nested_gitignore_path,
+        "# comment\n*.log\n",
+    );
+    testutils::write_working_copy_file(&workspace_root, ignored_path, "contents");
+    testutils::write_working_copy_file(&workspace_root, outside_path, "contents");
+
+    let wc = test_workspace.workspace.working_copy();
+    let untracked = wc.untracked(SnapshotOptions::empty_for_test()).unwrap();
+    assert_eq!(
+        untracked,
+        vec![
+            UntrackedEntry {
+                path: plain_path.to_owned(),
+                reason: UntrackedReason::NotIgnored,
+            },
+            UntrackedEntry {
+                path: nested_gitignore_path.to_owned(),
+                reason: UntrackedReason::NotIgnored,
+            },
+            UntrackedEntry {
+                path: ignored_path.to_owned(),
+                reason: UntrackedReason::Ignored {
+                    source_file: nested_gitignore_path.to_fs_path(&workspace_root),
+                    line: 2,
+                },
+            },
+            UntrackedEntry {
+                path: outside_path.to_owned(),
+                reason: UntrackedReason::SparseExcluded,
+            },
+        ]
+    );
+
+    // Nothing was snapshotted by listing the untracked files
+    let wc = test_workspace.workspace.working_copy();
+    let tree = repo.store().get_root_tree(wc.tree_id().unwrap()).unwrap();
+    assert_eq!(
+        tree.entries().map(|(path, _value)| path).collect_vec(),
+        to_owned_path_vec(&[tracked_path])
+    );
+}
+
 #[test]
 fn test_gitignores_in_ignored_dir() {
     // Tests that .gitignore files in an ignored directory are ignored, i.e. that
@@ -1153,6 +1511,77 @@ fn test_existing_directory_symlink() {
     assert!(!workspace_root.parent().unwrap().join("escaped").exists());
 }
 
+#[cfg(unix)]
+#[test]
+fn test_executable_and_symlink_round_trip() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = test_workspace.repo.clone();
+    let store = repo.store().clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let executable_path = RepoPath::from_internal_string("executable");
+    let normal_path = RepoPath::from_internal_string("normal");
+    let symlink_path = RepoPath::from_internal_string("symlink");
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    testutils::write_executable_file(&mut tree_builder, executable_path, "#!/bin/sh\n");
+    testutils::write_normal_file(&mut tree_builder, normal_path, "contents");
+    testutils::write_symlink(&mut tree_builder, symlink_path, "normal");
+    let tree_id = MergedTreeId::resolved(tree_builder.write_tree().unwrap());
+    let commit = commit_with_tree(&store, tree_id.clone());
+    test_workspace
+        .workspace
+        .check_out(repo.op_id().clone(), None, &commit)
+        .unwrap();
+
+    // The modes are materialized on disk
+    let mode = |path: &RepoPath| {
+        let metadata = path.to_fs_path(&workspace_root).symlink_metadata().unwrap();
+        metadata.permissions().mode() & 0o111
+    };
+    assert_ne!(mode(executable_path), 0);
+    assert_eq!(mode(normal_path), 0);
+    let symlink_disk_path = symlink_path.to_fs_path(&workspace_root);
+    assert!(symlink_disk_path.symlink_metadata().unwrap().is_symlink());
+    assert_eq!(
+        std::fs::read_link(&symlink_disk_path).unwrap(),
+        Path::new("normal")
+    );
+    assert_eq!(test_workspace.snapshot().unwrap().id(), tree_id);
+
+    // Changed modes are detected by snapshot
+    let set_mode = |path: &RepoPath, mode| {
+        let disk_path = path.to_fs_path(&workspace_root);
+        std::fs::set_permissions(disk_path, std::fs::Permissions::from_mode(mode)).unwrap();
+    };
+    set_mode(executable_path, 0o644);
+    set_mode(normal_path, 0o755);
+    std::fs::remove_file(&symlink_disk_path).unwrap();
+    std::fs::write(&symlink_disk_path, "normal").unwrap();
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(
+        tree.path_value(executable_path).unwrap().into_resolved(),
+        Ok(Some(TreeValue::File {
+            id: testutils::write_file(&store, executable_path, "#!/bin/sh\n"),
+            executable: false,
+        }))
+    );
+    assert_eq!(
+        tree.path_value(normal_path).unwrap().into_resolved(),
+        Ok(Some(TreeValue::File {
+            id: testutils::write_file(&store, normal_path, "contents"),
+            executable: true,
+        }))
+    );
+    assert_eq!(
+        tree.path_value(symlink_path).unwrap().into_resolved(),
+        Ok(Some(TreeValue::File {
+            id: testutils::write_file(&store, symlink_path, "normal"),
+            executable: false,
+        }))
+    );
+}
+
 #[test]
 fn test_fsmonitor() {
     let settings = testutils::user_settings();
@@ -1280,3 +1709,161 @@ fn test_snapshot_max_new_file_size() {
         "the failure should be attributed to new file size"
     );
 }
+
+#[test]
+fn test_snapshot_rewrites_wc_commit() {
+    // Tests that each snapshot rewrites the working-copy commit in place, and
+    // that its descendants can then be evolved onto the new version.
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let workspace_id = test_workspace.workspace.workspace_id().clone();
+    let file_path = RepoPath::from_internal_string("file");
+
+    let mut snapshot = |repo: &Arc<ReadonlyRepo>| {
+        let wc_commit = repo.working_copy_commit(&workspace_id).unwrap().unwrap();
+        let mut locked_ws = test_workspace
+            .workspace
+            .start_working_copy_mutation()
+            .unwrap();
+        let mut tx = repo.start_transaction(&settings);
+        working_copy::snapshot_and_commit(
+            locked_ws.locked_wc(),
+            tx.mut_repo(),
+            &settings,
+            &wc_commit,
+            SnapshotOptions::empty_for_test(),
+        )
+        .unwrap();
+        let summary = tx.mut_repo().evolve(&settings).unwrap();
+        let repo = tx.commit("test");
+        locked_ws.finish(repo.op_id().clone()).unwrap();
+        (repo, wc_commit, summary)
+    };
+
+    testutils::write_working_copy_file(&workspace_root, file_path, "1");
+    let repo = test_workspace.repo.clone();
+    let (repo, wc_commit0, _) = snapshot(&repo);
+    let wc_commit1 = repo.working_copy_commit(&workspace_id).unwrap().unwrap();
+    assert_eq!(wc_commit1.change_id(), wc_commit0.change_id());
+    assert_eq!(wc_commit1.predecessor_ids(), [wc_commit0.id().clone()]);
+    assert_eq!(
+        *wc_commit1.tree_id(),
+        create_tree(&repo, &[(file_path, "1")]).id()
+    );
+    // The old version is obsolete, so it's no longer visible
+    assert_eq!(
+        repo.resolve_change_id(wc_commit0.change_id()),
+        Some(vec![wc_commit1.id().clone()])
+    );
+
+    let mut tx = repo.start_transaction(&settings);
+    let child = testutils::create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![wc_commit1.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    testutils::write_working_copy_file(&workspace_root, file_path, "2");
+    let (repo, _, summary) = snapshot(&repo);
+    assert_eq!(summary.resolved_orphans, 1);
+    let wc_commit2 = repo.working_copy_commit(&workspace_id).unwrap().unwrap();
+    assert_eq!(wc_commit2.change_id(), wc_commit0.change_id());
+    assert_eq!(wc_commit2.predecessor_ids(), [wc_commit1.id().clone()]);
+    assert_eq!(
+        repo.resolve_change_id(wc_commit0.change_id()),
+        Some(vec![wc_commit2.id().clone()])
+    );
+    let new_child_ids = repo.resolve_change_id(child.change_id()).unwrap();
+    assert_eq!(new_child_ids.len(), 1);
+    assert_ne!(new_child_ids[0], *child.id());
+    assert_eq!(
+        repo.parent_ids(&new_child_ids[0]),
+        [wc_commit2.id().clone()]
+    );
+}
+
+#[test]
+fn test_snapshot_split_rules() {
+    let settings = UserSettings::from_config(
+        testutils::base_config()
+            .add_source(config::File::from_str(
+                indoc! {r#"
+                    [[snapshot.split-rules]]
+                    pattern = "vendored/**"
+                    description = "vendor sync"
+                "#},
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap(),
+    );
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let workspace_id = test_workspace.workspace.workspace_id().clone();
+    let vendored_path = RepoPath::from_internal_string("vendored/lib.c");
+    let src_path = RepoPath::from_internal_string("src/main.c");
+
+    let mut snapshot_and_commit = |repo: &Arc<ReadonlyRepo>| {
+        let wc_commit_id = repo.view().get_wc_commit_id(&workspace_id).unwrap();
+        let wc_commit = repo.store().get_commit(wc_commit_id).unwrap();
+        let mut locked_ws = test_workspace
+            .workspace
+            .start_working_copy_mutation()
+            .unwrap();
+        let mut tx = repo.start_transaction(&settings);
+        let commits = working_copy::snapshot_and_commit(
+            locked_ws.locked_wc(),
+            tx.mut_repo(),
+            &settings,
+            &wc_commit,
+            SnapshotOptions::empty_for_test(),
+        )
+        .unwrap();
+        tx.mut_repo().rebase_descendants(&settings).unwrap();
+        let repo = tx.commit("test");
+        locked_ws.finish(repo.op_id().clone()).unwrap();
+        (repo, wc_commit, commits)
+    };
+
+    // Changes to matching paths are moved to a new parent commit
+    testutils::write_working_copy_file(&workspace_root, vendored_path, "vendored");
+    testutils::write_working_copy_file(&workspace_root, src_path, "src");
+    let repo = test_workspace.repo.clone();
+    let (repo, old_wc_commit, commits) = snapshot_and_commit(&repo);
+    let [split_commit] = &commits.split_commits[..] else {
+        panic!("expected one split commit: {:?}", commits.split_commits);
+    };
+    let wc_commit = commits.wc_commit.unwrap();
+    assert_eq!(split_commit.description(), "vendor sync");
+    assert_eq!(split_commit.parent_ids(), old_wc_commit.parent_ids());
+    assert_eq!(
+        *split_commit.tree_id(),
+        create_tree(&repo, &[(vendored_path, "vendored")]).id()
+    );
+    assert_eq!(wc_commit.change_id(), old_wc_commit.change_id());
+    assert_eq!(wc_commit.parent_ids(), [split_commit.id().clone()]);
+    assert_eq!(
+        *wc_commit.tree_id(),
+        create_tree(&repo, &[(vendored_path, "vendored"), (src_path, "src")]).id()
+    );
+    assert_eq!(
+        repo.view().get_wc_commit_id(&workspace_id),
+        Some(wc_commit.id())
+    );
+
+    // Without changes to matching paths, no commit is added
+    testutils::write_working_copy_file(&workspace_root, src_path, "src modified");
+    let (repo, _, commits) = snapshot_and_commit(&repo);
+    assert!(commits.split_commits.is_empty());
+    let new_wc_commit = commits.wc_commit.unwrap();
+    assert_eq!(new_wc_commit.parent_ids(), [split_commit.id().clone()]);
+    assert_eq!(
+        *new_wc_commit.tree_id(),
+        create_tree(
+            &repo,
+            &[(vendored_path, "vendored"), (src_path, "src modified")]
+        )
+        .id()
+    );
+}
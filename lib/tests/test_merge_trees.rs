@@ -14,11 +14,17 @@
 
 use itertools::Itertools;
 use jj_lib::backend::TreeValue;
+use jj_lib::diff;
+use jj_lib::files::{ContentType, MergeDriver};
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::{RepoPath, RepoPathComponent};
 use jj_lib::rewrite::rebase_commit;
+use jj_lib::settings::UserSettings;
 use jj_lib::tree::{merge_trees, Tree};
-use testutils::{create_single_tree, create_tree, TestRepo};
+use test_case::test_case;
+use testutils::{create_single_tree, create_tree, TestRepo, TestRepoBackend};
 
 #[test]
 fn test_same_type() {
@@ -641,3 +647,276 @@ fn test_simplify_conflict_after_resolving_parent() {
 
 // TODO: Add tests for simplification of multi-way conflicts. Both the content
 // and the executable bit need testing.
+
+#[test]
+fn test_content_eq() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let git_test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let git_repo = &git_test_repo.repo;
+
+    let file_path = RepoPath::from_internal_string("dir/file");
+    let other_path = RepoPath::from_internal_string("other");
+    let tree = create_single_tree(repo, &[(file_path, "a"), (other_path, "b")]);
+    let same_tree = create_single_tree(repo, &[(file_path, "a"), (other_path, "b")]);
+    let different_tree = create_single_tree(repo, &[(file_path, "a"), (other_path, "c")]);
+
+    // Trees in the same store are compared by id
+    assert_eq!(tree.id(), same_tree.id());
+    assert!(tree.content_eq(&same_tree).unwrap());
+    assert!(!tree.content_eq(&different_tree).unwrap());
+
+    // Trees in stores with different hash schemes are compared by contents
+    let git_tree = create_single_tree(git_repo, &[(file_path, "a"), (other_path, "b")]);
+    let git_different_tree = create_single_tree(git_repo, &[(file_path, "a"), (other_path, "c")]);
+    let git_fewer_tree = create_single_tree(git_repo, &[(file_path, "a")]);
+    assert_ne!(tree.id(), git_tree.id());
+    assert!(tree.content_eq(&git_tree).unwrap());
+    assert!(git_tree.content_eq(&tree).unwrap());
+    assert!(!tree.content_eq(&git_different_tree).unwrap());
+    assert!(!tree.content_eq(&git_fewer_tree).unwrap());
+    assert!(different_tree.content_eq(&git_different_tree).unwrap());
+}
+
+#[test]
+fn test_merge_drivers() {
+    let config = testutils::base_config()
+        .add_source(config::File::from_str(
+            r#"
+            [[merge.drivers]]
+            pattern = "Cargo.lock"
+            driver = "ours"
+            [[merge.drivers]]
+            pattern = "*.md"
+            driver = "union"
+            "#,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let repo = &test_repo.repo;
+
+    // Set up a repo like this:
+    // D
+    // | B
+    // |/
+    // A
+    //
+    // B and D make conflicting changes to all files. We then rebase B onto D.
+    let changelog_path = RepoPath::from_internal_string("CHANGELOG.md");
+    let lock_path = RepoPath::from_internal_string("Cargo.lock");
+    let other_path = RepoPath::from_internal_string("other");
+    let create_tree_with = |line: &str| {
+        create_tree(
+            repo,
+            &[
+                (changelog_path, &format!("# Changes\n{line}\n")),
+                (lock_path, &format!("{line}\n")),
+                (other_path, &format!("{line}\n")),
+            ],
+        )
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let tree_a = create_tree(
+        repo,
+        &[
+            (changelog_path, "# Changes\n"),
+            (lock_path, "a\n"),
+            (other_path, "a\n"),
+        ],
+    );
+    let commit_a = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree_a.id(),
+        )
+        .write()
+        .unwrap();
+    let tree_b = create_tree_with("b");
+    let commit_b = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit_a.id().clone()], tree_b.id())
+        .write()
+        .unwrap();
+    let tree_d = create_tree_with("d");
+    let commit_d = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit_a.id().clone()], tree_d.id())
+        .write()
+        .unwrap();
+
+    let commit_b2 = rebase_commit(
+        &settings,
+        tx.mut_repo(),
+        commit_b,
+        vec![commit_d.id().clone()],
+    )
+    .unwrap();
+    let tree_b2 = commit_b2.tree().unwrap();
+    // The changelog gets both entries, and the lock file takes the destination
+    // side
+    assert_eq!(
+        tree_b2.path_value(changelog_path).unwrap().into_resolved(),
+        Ok(Some(TreeValue::File {
+            id: testutils::write_file(repo.store(), changelog_path, "# Changes\nd\nb\n"),
+            executable: false,
+        }))
+    );
+    assert_eq!(
+        tree_b2.path_value(lock_path).unwrap(),
+        tree_d.path_value(lock_path).unwrap()
+    );
+    // Other files still conflict
+    assert!(!tree_b2.path_value(other_path).unwrap().is_resolved());
+
+    // The auto-resolutions are reported
+    let (merged_tree, resolutions) = tree_d.merge_with_resolutions(&tree_a, &tree_b).unwrap();
+    assert_eq!(
+        merged_tree.path_value(changelog_path).unwrap(),
+        tree_b2.path_value(changelog_path).unwrap()
+    );
+    assert_eq!(
+        resolutions,
+        vec![
+            (changelog_path.to_owned(), MergeDriver::Union),
+            (lock_path.to_owned(), MergeDriver::Ours),
+        ]
+    );
+}
+
+#[test_case(false; "detected")]
+#[test_case(true; "forced text")]
+fn test_content_types_utf16(force_text: bool) {
+    let mut config = testutils::base_config();
+    if force_text {
+        config = config.add_source(config::File::from_str(
+            r#"
+            [[files.content-types]]
+            pattern = "*.utf16"
+            type = "text"
+            "#,
+            config::FileFormat::Toml,
+        ));
+    }
+    let settings = UserSettings::from_config(config.build().unwrap());
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    // UTF-16LE text has NUL bytes. The sides change different lines, so the
+    // contents merge cleanly as text.
+    let path = RepoPath::from_internal_string("file.utf16");
+    let utf16 = |text: &str| -> String { text.chars().flat_map(|c| [c, '\0']).collect() };
+    let base = create_single_tree(repo, &[(path, &utf16("a\nb\nc\n"))]);
+    let left = create_single_tree(repo, &[(path, &utf16("A\nb\nc\n"))]);
+    let right = create_single_tree(repo, &[(path, &utf16("a\nb\nC\n"))]);
+
+    let expected_content_type = if force_text {
+        ContentType::Text
+    } else {
+        ContentType::Binary
+    };
+    let file_id = match base.path_value(path).unwrap() {
+        Some(TreeValue::File { id, .. }) => id,
+        other => panic!("unexpected value: {other:?}"),
+    };
+    let num_detections_before = store.num_content_type_detections();
+    assert_eq!(
+        store.file_content_type(path, &file_id).unwrap(),
+        expected_content_type
+    );
+    assert_eq!(
+        store.file_content_type(path, &file_id).unwrap(),
+        expected_content_type
+    );
+    // The second call was served from the cache, and forced types aren't
+    // detected at all
+    assert_eq!(
+        store.num_content_type_detections() - num_detections_before,
+        usize::from(!force_text)
+    );
+
+    // Diffs and merges agree on the classification
+    let stat = diff::stat(
+        &MergedTree::legacy(base.clone()),
+        &MergedTree::legacy(left.clone()),
+    )
+    .unwrap();
+    assert_eq!(stat.len(), 1);
+    assert_eq!(stat[0].is_binary, !force_text);
+    assert_eq!(stat[0].added_lines, usize::from(force_text));
+    let merged = MergedTree::legacy(left)
+        .merge(&MergedTree::legacy(base), &MergedTree::legacy(right))
+        .unwrap();
+    let merged_value = merged.path_value(path).unwrap();
+    if force_text {
+        assert_eq!(
+            merged_value.into_resolved(),
+            Ok(Some(TreeValue::File {
+                id: testutils::write_file(store, path, &utf16("A\nb\nC\n")),
+                executable: false,
+            }))
+        );
+    } else {
+        assert!(!merged_value.is_resolved());
+    }
+}
+
+#[test]
+fn test_three_way_tree() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let conflicted_path = RepoPath::from_internal_string("file1");
+    let right_path = RepoPath::from_internal_string("file2");
+    let left_path = RepoPath::from_internal_string("dir/file3");
+    let base = create_single_tree(
+        repo,
+        &[(conflicted_path, "a"), (right_path, "a"), (left_path, "a")],
+    );
+    let left = create_single_tree(
+        repo,
+        &[(conflicted_path, "b"), (right_path, "a"), (left_path, "b")],
+    );
+    let right = create_single_tree(
+        repo,
+        &[(conflicted_path, "c"), (right_path, "c"), (left_path, "a")],
+    );
+
+    let result = repo.three_way_tree(&base, &left, &right).unwrap();
+    let merged_tree = store
+        .get_tree(RepoPath::root(), &result.merged_tree_id)
+        .unwrap();
+    assert_eq!(
+        merged_tree.path_value(right_path).unwrap(),
+        right.path_value(right_path).unwrap()
+    );
+    assert_eq!(
+        merged_tree.path_value(left_path).unwrap(),
+        left.path_value(left_path).unwrap()
+    );
+    assert_eq!(
+        result.conflicts,
+        vec![(
+            conflicted_path.to_owned(),
+            Merge::from_removes_adds(
+                [base.path_value(conflicted_path).unwrap()],
+                [
+                    left.path_value(conflicted_path).unwrap(),
+                    right.path_value(conflicted_path).unwrap(),
+                ],
+            ),
+        )]
+    );
+
+    // Rebasing produces the same conflicts
+    let merged = MergedTree::legacy(left)
+        .merge(&MergedTree::legacy(base), &MergedTree::legacy(right))
+        .unwrap();
+    assert_eq!(merged.conflicts().collect_vec(), result.conflicts);
+}
@@ -12,12 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
 use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::evolution::Evolution;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
-use jj_lib::repo::Repo;
+use jj_lib::repo::{
+    BranchMetadata, ChangeMetadataTooLarge, CleanupMergedBranchesOptions, EvolutionDelta,
+    MutableRepo, ReadonlyRepo, RenameBranchError, Repo, RepoError, MAX_CHANGE_METADATA_KEYS,
+    MAX_CHANGE_METADATA_KEY_LEN, MAX_CHANGE_METADATA_VALUE_LEN,
+};
+use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::RevsetExpression;
+use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::settings::UserSettings;
+use jj_lib::str_util::StringPattern;
 use maplit::hashset;
 use testutils::{
-    assert_rebased_onto, create_random_commit, write_random_commit, CommitGraphBuilder, TestRepo,
+    assert_rebased_onto, create_random_commit, create_tree, write_random_commit,
+    CommitGraphBuilder, TestRepo,
 };
 
 #[test]
@@ -62,6 +78,142 @@ fn test_checkout() {
     assert_eq!(repo.view().get_wc_commit_id(&ws_id), Some(wc_commit.id()));
 }
 
+#[test]
+fn test_checkout_history() {
+    // Test that MutableRepo::check_out() records the checked-out commits, and
+    // that reading the history maps rewritten commits to their successors
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let commit_c = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    let ws_id = WorkspaceId::default();
+    let mut repo = repo;
+    for commit in [&commit_a, &commit_b, &commit_c, &commit_c] {
+        let mut tx = repo.start_transaction(&settings);
+        tx.mut_repo()
+            .check_out(ws_id.clone(), &settings, commit)
+            .unwrap();
+        tx.mut_repo().rebase_descendants(&settings).unwrap();
+        repo = tx.commit("test");
+    }
+    // Consecutive repeats are recorded once
+    assert_eq!(
+        repo.view().checkout_history(&ws_id),
+        [
+            commit_c.id().clone(),
+            commit_b.id().clone(),
+            commit_a.id().clone()
+        ]
+    );
+    assert!(repo
+        .view()
+        .checkout_history(&WorkspaceId::new("other".to_string()))
+        .is_empty());
+
+    let mut tx = repo.start_transaction(&settings);
+    let rewritten_b = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_b)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(
+        repo.checkout_history(&ws_id).unwrap(),
+        [
+            commit_c.id().clone(),
+            rewritten_b.id().clone(),
+            commit_a.id().clone()
+        ]
+    );
+    // The recorded history isn't rewritten
+    assert_eq!(
+        repo.view().checkout_history(&ws_id),
+        [
+            commit_c.id().clone(),
+            commit_b.id().clone(),
+            commit_a.id().clone()
+        ]
+    );
+}
+
+#[test]
+fn test_labels() {
+    // Test that labels follow rewrites and are removed with the change's last
+    // visible commit
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let resolve_label = |repo: &dyn Repo, label: &str| -> Vec<CommitId> {
+        RevsetExpression::labels(StringPattern::exact(label))
+            .evaluate_programmatic(repo)
+            .unwrap()
+            .iter()
+            .collect()
+    };
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo().add_label(commit_a.id(), "topic").unwrap();
+    tx.mut_repo().add_label(commit_b.id(), "topic").unwrap();
+    tx.mut_repo().add_label(commit_b.id(), "other").unwrap();
+    tx.mut_repo().remove_label(commit_b.id(), "other").unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(repo.view().labels().keys().collect_vec(), vec!["topic"]);
+    assert_eq!(
+        resolve_label(repo.as_ref(), "topic")
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {commit_a.id().clone(), commit_b.id().clone()}
+    );
+
+    let mut tx = repo.start_transaction(&settings);
+    let rewritten_a = tx
+        .mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(
+        resolve_label(repo.as_ref(), "topic")
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {rewritten_a.id().clone(), commit_b.id().clone()}
+    );
+
+    // Abandoning the only commit of a change removes it from the label, and
+    // the label once it's empty
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo()
+        .record_abandoned_commit(rewritten_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(
+        repo.view().labels().get("topic"),
+        Some(&vec![commit_b.change_id().clone()])
+    );
+    assert_eq!(
+        resolve_label(repo.as_ref(), "topic"),
+        vec![commit_b.id().clone()]
+    );
+
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().remove_head(commit_b.id());
+    let repo = tx.commit("test");
+    assert!(repo.view().labels().is_empty());
+    assert!(resolve_label(repo.as_ref(), "topic").is_empty());
+}
+
 #[test]
 fn test_edit_previous_not_empty() {
     // Test that MutableRepo::edit() does not usually abandon the previous
@@ -437,6 +589,85 @@ fn test_remove_head() {
     assert!(repo.index().has_id(commit3.id()));
 }
 
+#[test]
+fn test_abandon_unreachable() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // C D E
+    // |/ /
+    // B /
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_a]);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_e.id().clone()));
+    let repo = tx.commit("test");
+
+    // C is no longer reachable, but E still is through the branch, and B and A
+    // through D
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo.remove_head(commit_c.id());
+    mut_repo.remove_head(commit_e.id());
+    assert_eq!(
+        mut_repo.abandon_unreachable().unwrap(),
+        vec![commit_c.id().clone()]
+    );
+    assert!(mut_repo.has_rewrites());
+    // Nothing more to abandon
+    assert_eq!(mut_repo.abandon_unreachable().unwrap(), vec![]);
+    mut_repo.rebase_descendants(&settings).unwrap();
+    assert_eq!(*mut_repo.view().heads(), hashset! {commit_d.id().clone()});
+}
+
+#[test]
+fn test_roots_of() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // D
+    // |\
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let mut_repo = tx.mut_repo();
+
+    // The bottom of a chain is its only root
+    assert_eq!(
+        mut_repo.roots_of(&[
+            commit_d.id().clone(),
+            commit_b.id().clone(),
+            commit_a.id().clone()
+        ]),
+        vec![commit_a.id().clone()]
+    );
+    // A merge with one parent in the set is not a root
+    assert_eq!(
+        mut_repo.roots_of(&[commit_b.id().clone(), commit_d.id().clone()]),
+        vec![commit_b.id().clone()]
+    );
+    assert_eq!(
+        mut_repo.roots_of(&[commit_c.id().clone(), commit_b.id().clone()]),
+        vec![commit_c.id().clone(), commit_b.id().clone()]
+    );
+    assert!(mut_repo.roots_of(&[]).is_empty());
+}
+
 #[test]
 fn test_has_changed() {
     // Test that MutableRepo::has_changed() reports changes iff the view has changed
@@ -599,6 +830,599 @@ fn test_rename_remote() {
     );
 }
 
+#[test]
+fn test_rename_branch() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let commit2 = write_random_commit(mut_repo, &settings);
+    let target1 = RefTarget::normal(commit1.id().clone());
+    let target2 = RefTarget::normal(commit2.id().clone());
+    mut_repo.set_local_branch_target("main", target1.clone());
+    mut_repo.set_local_branch_target("release/1", target2.clone());
+    mut_repo.set_local_branch_target("hotfix", target2.clone());
+
+    mut_repo.rename_branch("hotfix", "release/2").unwrap();
+    assert_eq!(mut_repo.get_local_branch("hotfix"), RefTarget::absent());
+    assert_eq!(mut_repo.get_local_branch("release/2"), target2);
+    assert_eq!(
+        mut_repo.view().branches_matching("release/*").unwrap(),
+        [("release/1", &target2), ("release/2", &target2)]
+    );
+    assert_eq!(
+        mut_repo.view().branches_matching("*a*").unwrap(),
+        [
+            ("main", &target1),
+            ("release/1", &target2),
+            ("release/2", &target2)
+        ]
+    );
+    assert_eq!(mut_repo.view().branches_matching("*fix*").unwrap(), []);
+    assert!(mut_repo.view().branches_matching("[").is_err());
+
+    // Renaming over an existing branch or a missing branch fails
+    assert_matches!(
+        mut_repo.rename_branch("main", "release/1"),
+        Err(RenameBranchError::BranchAlreadyExists(name)) if name == "release/1"
+    );
+    assert_matches!(
+        mut_repo.rename_branch("hotfix", "main2"),
+        Err(RenameBranchError::NoSuchBranch(name)) if name == "hotfix"
+    );
+    assert_eq!(mut_repo.get_local_branch("main"), target1);
+    assert_eq!(mut_repo.get_local_branch("release/1"), target2);
+    assert_eq!(mut_repo.get_local_branch("main2"), RefTarget::absent());
+}
+
+#[test]
+fn test_branch_metadata() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    let commit1 = write_random_commit(tx.mut_repo(), &settings);
+    let commit2 = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit1.id().clone()));
+    tx.mut_repo()
+        .set_local_branch_target("feature", RefTarget::normal(commit2.id().clone()));
+    // Branches set in the transaction haven't been moved by any operation yet
+    assert_eq!(
+        tx.mut_repo().branch_metadata("main").unwrap(),
+        Some(BranchMetadata {
+            last_moved_op_id: None,
+            is_obsolete: false,
+        })
+    );
+    let repo1 = tx.commit("test");
+    assert_eq!(
+        repo1.branch_metadata("main").unwrap(),
+        Some(BranchMetadata {
+            last_moved_op_id: Some(repo1.op_id().clone()),
+            is_obsolete: false,
+        })
+    );
+    assert_eq!(repo1.branch_metadata("missing").unwrap(), None);
+
+    // A renamed branch keeps the operation that last moved it
+    let mut tx = repo1.start_transaction(&settings);
+    tx.mut_repo().rename_branch("feature", "topic").unwrap();
+    assert_eq!(
+        tx.mut_repo()
+            .branch_metadata("topic")
+            .unwrap()
+            .unwrap()
+            .last_moved_op_id,
+        Some(repo1.op_id().clone())
+    );
+    let repo2 = tx.commit("test");
+    assert_eq!(
+        repo2.branch_metadata("topic").unwrap(),
+        Some(BranchMetadata {
+            last_moved_op_id: Some(repo1.op_id().clone()),
+            is_obsolete: false,
+        })
+    );
+
+    // Branches last moved by different operations are found by the same walk
+    let mut tx = repo2.start_transaction(&settings);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit2.id().clone()));
+    let repo3 = tx.commit("test");
+    let last_moved_op_id = |name| {
+        repo3
+            .branch_metadata(name)
+            .unwrap()
+            .unwrap()
+            .last_moved_op_id
+    };
+    assert_eq!(last_moved_op_id("main"), Some(repo3.op_id().clone()));
+    assert_eq!(last_moved_op_id("topic"), Some(repo1.op_id().clone()));
+
+    // Rewriting the target without evolving makes the branch obsolete
+    let mut tx = repo2.start_transaction(&settings);
+    tx.mut_repo()
+        .rewrite_commit(&settings, &commit1)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_eq!(
+        tx.mut_repo().branch_metadata("main").unwrap(),
+        Some(BranchMetadata {
+            last_moved_op_id: Some(repo1.op_id().clone()),
+            is_obsolete: true,
+        })
+    );
+    assert!(
+        !tx.mut_repo()
+            .branch_metadata("topic")
+            .unwrap()
+            .unwrap()
+            .is_obsolete
+    );
+}
+
+#[test]
+fn test_cleanup_merged_branches() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_base = write_random_commit(mut_repo, &settings);
+    let commit_feature = write_random_commit(mut_repo, &settings);
+    let commit_unmerged = write_random_commit(mut_repo, &settings);
+    let commit_release = write_random_commit(mut_repo, &settings);
+    let commit_divergent = write_random_commit(mut_repo, &settings);
+    let commit_feature2 = mut_repo
+        .rewrite_commit(&settings, &commit_feature)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let commit_divergent2 = mut_repo
+        .rewrite_commit(&settings, &commit_divergent)
+        .set_description("rewritten 1")
+        .write()
+        .unwrap();
+    mut_repo
+        .rewrite_commit(&settings, &commit_divergent)
+        .set_description("rewritten 2")
+        .write()
+        .unwrap();
+    let commit_main = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![
+            commit_base.id().clone(),
+            commit_feature2.id().clone(),
+            commit_release.id().clone(),
+            commit_divergent2.id().clone(),
+        ])
+        .write()
+        .unwrap();
+    // The branches point to the commits from before the rewrites
+    let set_branch = |mut_repo: &mut MutableRepo, name: &str, commit: &Commit| {
+        mut_repo.set_local_branch_target(name, RefTarget::normal(commit.id().clone()));
+    };
+    set_branch(mut_repo, "main", &commit_main);
+    // An older commit of main's own line, which was never merged
+    set_branch(mut_repo, "base", &commit_base);
+    set_branch(mut_repo, "feature", &commit_feature);
+    set_branch(mut_repo, "unmerged", &commit_unmerged);
+    set_branch(mut_repo, "release", &commit_release);
+    set_branch(mut_repo, "divergent", &commit_divergent);
+
+    let options = CleanupMergedBranchesOptions {
+        dry_run: true,
+        immutable_branches: vec![StringPattern::exact("release")],
+    };
+    assert_eq!(
+        mut_repo.cleanup_merged_branches("main", &options).unwrap(),
+        ["feature"]
+    );
+    assert!(mut_repo.get_local_branch("feature").is_present());
+
+    let options = CleanupMergedBranchesOptions {
+        dry_run: false,
+        ..options
+    };
+    assert_eq!(
+        mut_repo.cleanup_merged_branches("main", &options).unwrap(),
+        ["feature"]
+    );
+    assert_eq!(mut_repo.get_local_branch("feature"), RefTarget::absent());
+    assert!(mut_repo.get_local_branch("main").is_present());
+    assert!(mut_repo.get_local_branch("base").is_present());
+    assert!(mut_repo.get_local_branch("unmerged").is_present());
+    assert!(mut_repo.get_local_branch("release").is_present());
+    assert!(mut_repo.get_local_branch("divergent").is_present());
+
+    // Without the pattern, the branch merged as is gets deleted too
+    assert_eq!(
+        mut_repo
+            .cleanup_merged_branches("main", &Default::default())
+            .unwrap(),
+        ["release"]
+    );
+    assert!(mut_repo.get_local_branch("base").is_present());
+}
+
+#[test]
+fn test_immutable_heads() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_immutable_heads(vec![commit_b.id().clone()]);
+
+    // The immutable head and its ancestors can't be rewritten or abandoned
+    for commit in [&commit_a, &commit_b] {
+        assert_matches!(
+            mut_repo
+                .rewrite_commit(&settings, commit)
+                .set_description("rewritten")
+                .try_write(),
+            Err(RepoError::Immutable(id)) if id == *commit.id()
+        );
+        assert_matches!(
+            mut_repo.abandon_commit(commit.id()),
+            Err(RepoError::Immutable(id)) if id == *commit.id()
+        );
+    }
+    assert!(!mut_repo.has_rewrites());
+
+    // Copying an immutable commit as a new change is fine
+    mut_repo
+        .rewrite_commit(&settings, &commit_b)
+        .generate_new_change_id()
+        .write()
+        .unwrap();
+
+    // Descendants can still be rewritten
+    mut_repo
+        .rewrite_commit(&settings, &commit_c)
+        .set_description("rewritten")
+        .try_write()
+        .unwrap();
+    mut_repo.abandon_commit(commit_c.id()).unwrap();
+}
+
+#[test]
+fn test_create_merge() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let path = RepoPath::from_internal_string("file");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = repo.store().root_commit_id().clone();
+    let mut write_commit = |parent_id: &CommitId, contents: &str| {
+        let tree = create_tree(repo, &[(path, contents)]);
+        mut_repo
+            .new_commit(&settings, vec![parent_id.clone()], tree.id())
+            .write()
+            .unwrap()
+    };
+    let commit_base = write_commit(&root_id, "base");
+    let commit_a = write_commit(commit_base.id(), "a");
+    let commit_b = write_commit(commit_base.id(), "b");
+
+    // Rewrite the tip of the branch to merge without rebasing descendants
+    let tree_b2 = create_tree(repo, &[(path, "b2")]);
+    let commit_b2 = mut_repo
+        .rewrite_commit(&settings, &commit_b)
+        .set_tree_id(tree_b2.id())
+        .write()
+        .unwrap();
+
+    let merge = mut_repo
+        .create_merge(&settings, &[commit_a.clone(), commit_b.clone()])
+        .unwrap();
+    assert_eq!(
+        merge.parent_ids(),
+        [commit_a.id().clone(), commit_b2.id().clone()]
+    );
+    let expected_tree = merge_commit_trees(mut_repo, &[commit_a, commit_b2]).unwrap();
+    assert_eq!(merge.tree_id(), &expected_tree.id());
+    assert!(merge.has_conflict().unwrap());
+}
+
+#[test]
+fn test_reword() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_b2 = mut_repo.reword(&settings, &commit_b, "reworded").unwrap();
+    assert_eq!(commit_b2.description(), "reworded");
+    assert_eq!(commit_b2.change_id(), commit_b.change_id());
+    assert_eq!(commit_b2.predecessor_ids(), [commit_b.id().clone()]);
+    assert_eq!(commit_b2.parent_ids(), commit_b.parent_ids());
+    assert_eq!(commit_b2.tree_id(), commit_b.tree_id());
+    assert_eq!(
+        mut_repo.rewritten_commit_ids().collect_vec(),
+        [commit_b.id()]
+    );
+    assert_eq!(mut_repo.orphan_ids().unwrap(), [commit_c.id().clone()]);
+
+    // The orphan is rebased onto the reworded commit on evolution
+    let rebased = mut_repo.rebase_descendants_return_map(&settings).unwrap();
+    assert_rebased_onto(mut_repo, &rebased, &commit_c, &[commit_b2.id()]);
+}
+
+fn num_divergent_changes(repo: &dyn Repo, hidden_ids: Vec<CommitId>) -> usize {
+    RevsetExpression::all()
+        .minus(&RevsetExpression::commits(hidden_ids))
+        .evaluate_programmatic(repo)
+        .unwrap()
+        .commit_change_ids()
+        .map(|(_, change_id)| change_id)
+        .counts()
+        .values()
+        .filter(|&&count| count > 1)
+        .count()
+}
+
+/// Checks `evolution_delta()` against the counts computed from scratch, with
+/// the rewritten commits counted as hidden.
+fn assert_evolution_delta(mut_repo: &MutableRepo, expected: EvolutionDelta) {
+    let delta = mut_repo.evolution_delta().unwrap();
+    assert_eq!(delta, expected);
+    let base_repo = mut_repo.base_repo().as_ref();
+    let num_orphans = Evolution::for_transaction(mut_repo)
+        .unwrap()
+        .orphan_ids()
+        .unwrap()
+        .len();
+    let num_base_orphans = Evolution::new(base_repo)
+        .unwrap()
+        .orphan_ids()
+        .unwrap()
+        .len();
+    assert_eq!(
+        delta.orphans,
+        num_orphans as isize - num_base_orphans as isize
+    );
+    assert_eq!(
+        delta.divergent_changes,
+        num_divergent_changes(mut_repo, mut_repo.rewritten_commit_ids().cloned().collect())
+            as isize
+            - num_divergent_changes(base_repo, vec![]) as isize
+    );
+}
+
+#[test]
+fn test_evolution_delta() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // D
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    graph_builder.commit_with_parents(&[&commit_c]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    assert_evolution_delta(mut_repo, EvolutionDelta::default());
+
+    // Rewriting B orphans C and D
+    mut_repo.reword(&settings, &commit_b, "rewritten").unwrap();
+    assert_evolution_delta(
+        mut_repo,
+        EvolutionDelta {
+            orphans: 2,
+            divergent_changes: 0,
+        },
+    );
+
+    // Evolving rebases them
+    mut_repo.evolve(&settings).unwrap();
+    assert_evolution_delta(mut_repo, EvolutionDelta::default());
+
+    // Rewriting A, without hiding it, makes its change divergent and orphans
+    // B, C, and D
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo
+        .new_commit(
+            &settings,
+            commit_a.parent_ids().to_vec(),
+            commit_a.tree_id().clone(),
+        )
+        .set_change_id(commit_a.change_id().clone())
+        .set_predecessors(vec![commit_a.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_evolution_delta(
+        mut_repo,
+        EvolutionDelta {
+            orphans: 3,
+            divergent_changes: 1,
+        },
+    );
+    let repo = tx.commit("test");
+
+    // Evolving from there clears the orphans and the divergence the
+    // transaction started with
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    assert_evolution_delta(mut_repo, EvolutionDelta::default());
+    mut_repo.evolve(&settings).unwrap();
+    assert_evolution_delta(
+        mut_repo,
+        EvolutionDelta {
+            orphans: -3,
+            divergent_changes: -1,
+        },
+    );
+}
+
+#[test]
+fn test_change_metadata() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = write_random_commit(mut_repo, &settings);
+    mut_repo
+        .set_change_metadata(commit_a.change_id(), "review.url", "https://example.com/1")
+        .unwrap();
+    mut_repo
+        .set_change_metadata(commit_a.change_id(), "review.state", "draft")
+        .unwrap();
+    mut_repo
+        .set_change_metadata(commit_b.change_id(), "other", "value")
+        .unwrap();
+    // Values are size-capped
+    assert_matches!(
+        mut_repo.set_change_metadata(
+            commit_b.change_id(),
+            "big",
+            "x".repeat(MAX_CHANGE_METADATA_VALUE_LEN + 1)
+        ),
+        Err(ChangeMetadataTooLarge::Value { len, .. }) if len == MAX_CHANGE_METADATA_VALUE_LEN + 1
+    );
+    // So are keys and the number of keys
+    assert_matches!(
+        mut_repo.set_change_metadata(
+            commit_b.change_id(),
+            &"x".repeat(MAX_CHANGE_METADATA_KEY_LEN + 1),
+            "value"
+        ),
+        Err(ChangeMetadataTooLarge::Key { len }) if len == MAX_CHANGE_METADATA_KEY_LEN + 1
+    );
+    for i in 1..MAX_CHANGE_METADATA_KEYS {
+        mut_repo
+            .set_change_metadata(commit_b.change_id(), &format!("key{i}"), "value")
+            .unwrap();
+    }
+    assert_matches!(
+        mut_repo.set_change_metadata(commit_b.change_id(), "one-too-many", "value"),
+        Err(ChangeMetadataTooLarge::TooManyKeys)
+    );
+    // Existing keys can still be updated
+    mut_repo
+        .set_change_metadata(commit_b.change_id(), "key1", "new value")
+        .unwrap();
+    for i in 1..MAX_CHANGE_METADATA_KEYS {
+        mut_repo.remove_change_metadata(commit_b.change_id(), &format!("key{i}"));
+    }
+    let repo = tx.commit("test");
+
+    // The metadata can be read through the change id of the rewritten commit
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a2 = tx
+        .mut_repo()
+        .reword(&settings, &commit_a, "reworded")
+        .unwrap();
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    let mut tx = repo.start_transaction(&settings);
+    assert_eq!(
+        tx.mut_repo()
+            .get_change_metadata(commit_a2.change_id(), "review.url"),
+        Some("https://example.com/1".to_string())
+    );
+    assert_eq!(
+        tx.mut_repo()
+            .get_change_metadata(commit_b.change_id(), "big"),
+        None
+    );
+    assert_eq!(
+        repo.view()
+            .changes_with_metadata("review.")
+            .map(|(change_id, _)| change_id)
+            .collect_vec(),
+        [commit_a2.change_id()]
+    );
+
+    // Removing the last value removes the change's metadata
+    tx.mut_repo()
+        .remove_change_metadata(commit_b.change_id(), "other");
+    let repo = tx.commit("test");
+    assert_eq!(repo.view().get_change_metadata(commit_b.change_id()), None);
+}
+
+#[test]
+fn test_change_metadata_retention() {
+    let config = testutils::base_config()
+        .set_override("change-metadata.retention", 2)
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_change_metadata(commit_a.change_id(), "key", "value")
+        .unwrap();
+    let repo = tx.commit("test");
+    let operations_without_commits = |repo: &ReadonlyRepo| {
+        repo.view()
+            .get_change_metadata(commit_a.change_id())
+            .map(|metadata| metadata.operations_without_commits)
+    };
+    assert_eq!(operations_without_commits(&repo), Some(0));
+
+    // The metadata is kept for a while after the change disappears
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().record_abandoned_commit(commit_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(operations_without_commits(&repo), Some(1));
+    let repo = repo.start_transaction(&settings).commit("test");
+    assert_eq!(operations_without_commits(&repo), Some(2));
+
+    // The count starts over if the change becomes visible again
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().add_head(&commit_a).unwrap();
+    let repo = tx.commit("test");
+    assert_eq!(operations_without_commits(&repo), Some(0));
+
+    // The metadata is removed once the retention window has passed
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().record_abandoned_commit(commit_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let mut repo = tx.commit("test");
+    for _ in 0..2 {
+        repo = repo.start_transaction(&settings).commit("test");
+    }
+    assert_eq!(operations_without_commits(&repo), None);
+}
+
 #[test]
 fn test_remove_wc_commit_previous_not_discardable() {
     // Test that MutableRepo::remove_wc_commit() does not usually abandon the
@@ -649,3 +1473,92 @@ fn test_remove_wc_commit_previous_discardable() {
     mut_repo.rebase_descendants(&settings).unwrap();
     assert!(!mut_repo.view().heads().contains(old_wc_commit.id()));
 }
+
+#[test]
+fn test_change_siblings() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    let other_commit = write_random_commit(mut_repo, &settings);
+    assert_eq!(mut_repo.change_siblings(commit1.id()).unwrap(), vec![]);
+
+    // Rewrite commit1 twice to make it divergent
+    let commit2 = mut_repo
+        .rewrite_commit(&settings, &commit1)
+        .set_description("side 1")
+        .write()
+        .unwrap();
+    let commit3 = mut_repo
+        .rewrite_commit(&settings, &commit1)
+        .set_description("side 2")
+        .write()
+        .unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    assert_eq!(
+        repo.change_siblings(commit2.id()).unwrap(),
+        vec![commit3.id().clone()]
+    );
+    assert_eq!(
+        repo.change_siblings(commit3.id()).unwrap(),
+        vec![commit2.id().clone()]
+    );
+    // The hidden original sees both sides
+    assert_eq!(
+        repo.change_siblings(commit1.id())
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {commit2.id().clone(), commit3.id().clone()}
+    );
+    assert_eq!(repo.change_siblings(other_commit.id()).unwrap(), vec![]);
+}
+
+#[test]
+fn test_find_duplicates() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings);
+    write_random_commit(mut_repo, &settings);
+    assert_eq!(
+        mut_repo.find_duplicates().unwrap(),
+        Vec::<Vec<CommitId>>::new()
+    );
+
+    // Cherry-pick commit1 onto its own parent, and once more with a different
+    // description
+    let commit2 = mut_repo
+        .new_commit(
+            &settings,
+            commit1.parent_ids().to_vec(),
+            commit1.tree_id().clone(),
+        )
+        .set_description(commit1.description())
+        .write()
+        .unwrap();
+    mut_repo
+        .new_commit(
+            &settings,
+            commit1.parent_ids().to_vec(),
+            commit1.tree_id().clone(),
+        )
+        .set_description("reworded")
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    assert_ne!(commit1.change_id(), commit2.change_id());
+    assert_eq!(
+        repo.find_duplicates().unwrap(),
+        vec![vec![commit2.id().clone(), commit1.id().clone()]]
+    );
+}
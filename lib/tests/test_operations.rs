@@ -21,10 +21,14 @@ use assert_matches::assert_matches;
 use itertools::Itertools as _;
 use jj_lib::backend::CommitId;
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_walk::{self, OpsetEvaluationError, OpsetResolutionError};
+use jj_lib::op_store::{RefTarget, WorkspaceId};
+use jj_lib::op_walk::{
+    self, OpDiffError, OpRangeDiff, OpsetEvaluationError, OpsetResolutionError, TruncateOptions,
+};
 use jj_lib::operation::Operation;
-use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo::{ReadonlyRepo, Repo, RestoreSelector};
 use jj_lib::settings::UserSettings;
+use maplit::{btreemap, btreeset, hashset};
 use testutils::{create_random_commit, write_random_commit, TestRepo};
 
 fn list_dir(dir: &Path) -> Vec<String> {
@@ -421,21 +425,22 @@ fn test_resolve_op_id() {
     let mut operations = Vec::new();
     // The actual value of `i` doesn't matter, we just need to make sure we end
     // up with hashes with ambiguous prefixes.
-    for i in (1..7).chain([16]) {
+    for i in (1..7).chain([11, 16]) {
         let tx = repo.start_transaction(&settings);
         let repo = tx.commit(format!("transaction {i}"));
         operations.push(repo.operation().clone());
     }
-    // "2" and "0" are ambiguous
+    // "f" and "0" are ambiguous
     insta::assert_debug_snapshot!(operations.iter().map(|op| op.id().hex()).collect_vec(), @r###"
     [
-        "5aebb24d08d6f5282d9e06bded4b51febbb4ff4bc822cdd4db9043961339955d6af912e783c3864245867d0cf6d609cb004c5ff0cef5e914c15a415ba92e38a2",
-        "ad1cda629b220f2651d972475fba75e47c30cd57862c51a727e57b7b6e2fbaa937d0bcb881cf6fcff30ba1a088a4ad588cf880765b9d9680b551cdc446f3489e",
-        "2feaf9eb61232582d35f1cb0203b69425f7a7d07140a5f8ff0d0bf98dd9f433b941e4c9e0882f65a007266affee275e235ae1614b159ceadbf9fe6879077a5da",
-        "0b183be5767c3ff9945c8a9e3ac7639d249ff9873985a551f4c2070782aea8b5018ebff906036d1658038708ab9de0d867be385b1181aa7a81669e2ef6852355",
-        "d1c3031dff7b1db4db1bfb592a9aa0ea6faae9300033ef20cb6da488e4b90524c22af8e2541cdb99ca0ee2be3299c213c0f8d48390cd5f11462c4f80c5790f68",
-        "2369ba98e2596715606978a72608970287aaa064ec890f87c29b7e7df64fdf02b7f24b8b03ee845e132f3e19e3929de359b6cfe1328b42946c45ac5ff80705ca",
-        "00271842a189d274a2c97ac28f937584a47b84463d5b408c6f998089131e0e1329a287667b2ac5d63f8c576e95323bcf992c99caa4ef4612a1c3798fe8a3f74f",
+        "676430cb5853b921a3dfc8c4dbe1d7a14bace58f3ac483d22179ade190fe74dd10a49e88c2d4cf83a7501fa395526094a1ef3aa92d14c30c2f6d63903258b7f9",
+        "9ac103d4097e72daacafd682d66156db35e895b74c3cef56e950eb9b805f06bb538786f2dc2ea9937f2770bd9137cdca7ca699531cc37d0fcf58c3339dcb7cf7",
+        "49adf97c75361b7dbd8ee124176401aa4394b1495f44c9504e6e41c9298b61dd9a725162c23b3c29df921299d418692d7cc816fa5f67df3f53efbe4f3d82de39",
+        "b2c17a4728ce9d93d9f02ba308ca08496ffcbd566a61e32e75ffe5b844623d884a0286eb66b97bf5d00d028a0906d9c46fd97b6ec10a6f502d6cb2737b0b56c6",
+        "b36617a9bde55d9314eaf3deb860e898148b071b988f4a9e90d96d771d202bacb85f1b0aa0708d3233a6d0d940f9acf585192fd71ad2e1333ca1713c2b8d21d9",
+        "f28e8849baed70104b9f5c859f5737837f7ca0c08abbe8a9f73e76bc2a332eaa7b92c59055410eaee6b907471c71f1ef2ccdd76771b87bed62d7a7c70b0a32b4",
+        "f05cb6af8429851103f4a5ab0aed17fa7b8d8ab37a0edf9cadabbfa57c3a861dad1ad0100d374302f96d6a7e68aa5bc880482aef084c5ae0270aaee6d58ebb10",
+        "085d0334f8c199389a84dcf113ea0db9b4de64b9ac142e76900f25cdb4f5d7871e1d2ea514a85b24364f7d6ea2978396a9acbb68dbbfcf057ca032df2c3eaa0d",
     ]
     "###);
 
@@ -456,7 +461,7 @@ fn test_resolve_op_id() {
     );
     // Ambiguous id
     assert_matches!(
-        resolve("2"),
+        resolve("f"),
         Err(OpsetEvaluationError::OpsetResolution(
             OpsetResolutionError::AmbiguousIdPrefix(_)
         ))
@@ -483,7 +488,7 @@ fn test_resolve_op_id() {
     };
     assert_eq!(resolve(&root_operation.id().hex()).unwrap(), root_operation);
     assert_eq!(resolve("000").unwrap(), root_operation);
-    assert_eq!(resolve("002").unwrap(), operations[6]);
+    assert_eq!(resolve("08").unwrap(), operations[7]);
     assert_matches!(
         resolve("0"),
         Err(OpsetEvaluationError::OpsetResolution(
@@ -669,3 +674,313 @@ fn test_gc() {
     assert_eq!(expected_op_entries.len(), 2);
     assert_eq!(expected_view_entries.len(), 2);
 }
+
+#[test]
+fn test_truncate_before() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo_0 = test_repo.repo;
+    let op_store = repo_0.op_store();
+    let op_dir = repo_0.repo_path().join("op_store").join("operations");
+    let view_dir = repo_0.repo_path().join("op_store").join("views");
+
+    // Set up linear operation graph:
+    // E
+    // D
+    // C
+    // B
+    // A
+    // 0 (initialize repo)
+    // root
+    let random_tx = |repo: &Arc<ReadonlyRepo>| {
+        let mut tx = repo.start_transaction(&settings);
+        write_random_commit(tx.mut_repo(), &settings);
+        tx
+    };
+    let repo_a = random_tx(&repo_0).commit("op A");
+    let repo_b = random_tx(&repo_a).commit("op B");
+    let repo_c = random_tx(&repo_b).commit("op C");
+    let repo_d = random_tx(&repo_c).commit("op D");
+    let repo_e = random_tx(&repo_d).commit("op E");
+    let removed_repos = [&repo_0, &repo_a, &repo_b, &repo_c];
+
+    // Truncate 0|A|B|C:
+    // E'
+    // D'
+    // tombstone
+    // root
+    let report =
+        op_walk::truncate_before(&repo_e, repo_d.op_id(), &TruncateOptions::default()).unwrap();
+    assert_eq!(report.removed_count, 4);
+    assert_eq!(report.rewritten_count, 2);
+    assert_eq!(
+        report.removed_time_range,
+        Some((
+            repo_0.operation().metadata().start_time.clone(),
+            repo_c.operation().metadata().end_time.clone(),
+        ))
+    );
+    let tombstone_id = report.tombstone_id.unwrap();
+    let repo_loader = repo_e.loader();
+    assert_eq!(
+        repo_loader.op_heads_store().get_op_heads(),
+        vec![report.new_head_id.clone()]
+    );
+
+    let head_op = Operation::new(
+        op_store.clone(),
+        report.new_head_id.clone(),
+        op_store.read_operation(&report.new_head_id).unwrap(),
+    );
+    let ops: Vec<_> = op_walk::walk_ancestors(slice::from_ref(&head_op))
+        .try_collect()
+        .unwrap();
+    assert_eq!(
+        ops.iter()
+            .map(|op| op.metadata().description.as_str())
+            .collect_vec(),
+        ["op E", "op D", "truncate 4 operations", ""]
+    );
+    assert_eq!(ops[2].id(), &tombstone_id);
+    assert_eq!(ops[2].view_id(), ops[3].view_id());
+    assert_eq!(
+        op_walk::truncated_op_ids(&ops[2]).collect_vec(),
+        [&repo_c, &repo_b, &repo_a, &repo_0].map(|repo| repo.op_id().clone())
+    );
+    assert_eq!(ops[1].view_id(), repo_d.operation().view_id());
+
+    // Removed operations can no longer be resolved, even before gc
+    let assert_truncated = || {
+        for repo in removed_repos {
+            assert_matches!(
+                op_walk::resolve_op_for_load(&repo_loader, &repo.op_id().hex()),
+                Err(OpsetEvaluationError::OpsetResolution(
+                    OpsetResolutionError::TruncatedOperation(_, id)
+                )) if id == tombstone_id
+            );
+        }
+    };
+    assert_truncated();
+    assert!(op_walk::resolve_op_for_load(&repo_loader, &ops[1].id().hex()).is_ok());
+
+    // Removed operations and their views become garbage
+    op_store
+        .gc(slice::from_ref(&report.new_head_id), SystemTime::now())
+        .unwrap();
+    let op_entries = list_dir(&op_dir);
+    let view_entries = list_dir(&view_dir);
+    for repo in removed_repos {
+        assert!(!op_entries.contains(&repo.op_id().hex()));
+        assert!(!view_entries.contains(&repo.operation().view_id().hex()));
+    }
+    assert!(view_entries.contains(&repo_d.operation().view_id().hex()));
+    assert_truncated();
+
+    // Nothing to truncate
+    let repo = repo_loader.load_at(&head_op).unwrap();
+    let report = op_walk::truncate_before(&repo, ops[1].id(), &TruncateOptions::default()).unwrap();
+    assert_eq!(report.new_head_id, *head_op.id());
+    assert_eq!(report.tombstone_id, None);
+    assert_eq!(report.removed_count, 0);
+}
+
+#[test]
+fn test_diff_operations() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Start with commits A and X, and main pointing to A
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_x = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_a.id().clone()));
+    let from_repo = tx.commit("op1");
+
+    // Add B and temporary commit T, move main to B, and add branch temp
+    let mut tx = from_repo.start_transaction(&settings);
+    let commit_b = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_t = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
+    tx.mut_repo()
+        .set_local_branch_target("temp", RefTarget::normal(commit_t.id().clone()));
+    let repo = tx.commit("op2");
+
+    // Abandon T and remove temp, add C, and check out B
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().record_abandoned_commit(commit_t.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("temp", RefTarget::absent());
+    let commit_c = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_b.id().clone()])
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_wc_commit(WorkspaceId::default(), commit_b.id().clone())
+        .unwrap();
+    let repo = tx.commit("op3");
+
+    // Move main to C and abandon X
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_c.id().clone()));
+    tx.mut_repo().record_abandoned_commit(commit_x.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let to_repo = tx.commit("op4");
+
+    let diff =
+        op_walk::diff_operations(&to_repo, from_repo.operation(), to_repo.operation()).unwrap();
+    assert_eq!(
+        diff,
+        OpRangeDiff {
+            added_commits: vec![commit_c.id().clone(), commit_b.id().clone()],
+            removed_commits: vec![commit_x.id().clone()],
+            transient_commits: vec![commit_t.id().clone()],
+            branch_changes: btreemap! {
+                "main".to_owned() => (
+                    RefTarget::normal(commit_a.id().clone()),
+                    RefTarget::normal(commit_c.id().clone()),
+                ),
+            },
+            transient_branches: btreeset! {"temp".to_owned()},
+            wc_changes: btreemap! {
+                WorkspaceId::default() => (None, Some(commit_b.id().clone())),
+            },
+        }
+    );
+
+    // An empty range
+    let diff =
+        op_walk::diff_operations(&to_repo, to_repo.operation(), to_repo.operation()).unwrap();
+    assert_eq!(diff, OpRangeDiff::default());
+
+    // A reversed range
+    assert_matches!(
+        op_walk::diff_operations(&to_repo, to_repo.operation(), from_repo.operation()),
+        Err(OpDiffError::NotAncestor { from_op_id, to_op_id })
+            if from_op_id == *to_repo.op_id() && to_op_id == *from_repo.op_id()
+    );
+}
+
+#[test]
+fn test_restore_from_operation_branch() {
+    // Test that restoring a branch from an earlier operation leaves the
+    // working-copy commit alone
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let workspace_id = WorkspaceId::default();
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    let wc_commit1 = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_local_branch_target("a", RefTarget::normal(commit_a.id().clone()));
+    tx.mut_repo()
+        .set_local_branch_target("b", RefTarget::normal(commit_b.id().clone()));
+    tx.mut_repo()
+        .set_wc_commit(workspace_id.clone(), wc_commit1.id().clone())
+        .unwrap();
+    let repo1 = tx.commit("op1");
+
+    // An operation that moves both branch "a" and the working-copy commit
+    let mut tx = repo1.start_transaction(&settings);
+    let wc_commit2 = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_local_branch_target("a", RefTarget::normal(commit_b.id().clone()));
+    tx.mut_repo()
+        .set_wc_commit(workspace_id.clone(), wc_commit2.id().clone())
+        .unwrap();
+    let repo2 = tx.commit("op2");
+
+    let mut tx = repo2.start_transaction(&settings);
+    tx.mut_repo()
+        .restore_from_operation(
+            repo1.operation(),
+            &RestoreSelector::Branches(vec!["a".to_owned(), "b".to_owned()]),
+        )
+        .unwrap();
+    let repo3 = tx.commit("restore");
+    assert_eq!(
+        *repo3.view().get_local_branch("a"),
+        RefTarget::normal(commit_a.id().clone())
+    );
+    assert_eq!(
+        *repo3.view().get_local_branch("b"),
+        RefTarget::normal(commit_b.id().clone())
+    );
+    assert_eq!(
+        repo3.view().get_wc_commit_id(&workspace_id),
+        Some(wc_commit2.id())
+    );
+
+    // Restoring only the working-copy commit leaves the branches alone
+    let mut tx = repo2.start_transaction(&settings);
+    tx.mut_repo()
+        .restore_from_operation(
+            repo1.operation(),
+            &RestoreSelector::WorkingCopy(workspace_id.clone()),
+        )
+        .unwrap();
+    let repo4 = tx.commit("restore");
+    assert_eq!(
+        repo4.view().get_wc_commit_id(&workspace_id),
+        Some(wc_commit1.id())
+    );
+    assert_eq!(
+        *repo4.view().get_local_branch("a"),
+        RefTarget::normal(commit_b.id().clone())
+    );
+    assert!(repo4.view().heads().contains(wc_commit2.id()));
+}
+
+#[test]
+fn test_restore_from_operation_deleted_branch() {
+    // Test that restoring a branch that was deleted since recreates it, and
+    // makes its abandoned target visible again
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let commit_b = write_random_commit(tx.mut_repo(), &settings);
+    tx.mut_repo()
+        .set_local_branch_target("a", RefTarget::normal(commit_a.id().clone()));
+    let repo1 = tx.commit("op1");
+
+    let mut tx = repo1.start_transaction(&settings);
+    tx.mut_repo()
+        .set_local_branch_target("a", RefTarget::absent());
+    tx.mut_repo().record_abandoned_commit(commit_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo2 = tx.commit("op2");
+    assert!(!repo2.view().heads().contains(commit_a.id()));
+
+    let mut tx = repo2.start_transaction(&settings);
+    tx.mut_repo()
+        .restore_from_operation(
+            repo1.operation(),
+            &RestoreSelector::Branches(vec!["a".to_owned()]),
+        )
+        .unwrap();
+    let repo3 = tx.commit("restore");
+    assert_eq!(
+        *repo3.view().get_local_branch("a"),
+        RefTarget::normal(commit_a.id().clone())
+    );
+    assert_eq!(
+        *repo3.view().heads(),
+        hashset! {commit_a.id().clone(), commit_b.id().clone()}
+    );
+}
@@ -0,0 +1,77 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use itertools::Itertools as _;
+use jj_lib::backend::CommitId;
+use jj_lib::repo::Repo;
+use jj_lib::revset::RevsetExpression;
+use testutils::arbitrary_dag;
+
+const NUM_SEEDS: u64 = 20;
+const NUM_COMMITS: usize = 50;
+const REWRITE_PROBABILITY: f64 = 0.3;
+
+#[test]
+fn test_orphans_match_reference() {
+    for seed in 0..NUM_SEEDS {
+        let fixture = arbitrary_dag(seed, NUM_COMMITS, REWRITE_PROBABILITY);
+        let obsolete = fixture.reference_obsolete_set();
+        let obsolete_expression = RevsetExpression::commits(obsolete.into_iter().collect());
+        let orphans: HashSet<CommitId> = obsolete_expression
+            .descendants()
+            .minus(&obsolete_expression)
+            .evaluate_programmatic(fixture.tx.repo())
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(orphans, fixture.reference_orphans(), "seed: {seed}");
+    }
+}
+
+#[test]
+fn test_index_heads_match_reference() {
+    for seed in 0..NUM_SEEDS {
+        let fixture = arbitrary_dag(seed, NUM_COMMITS, REWRITE_PROBABILITY);
+        let index = fixture.tx.repo().index();
+        let all_ids = fixture
+            .commits
+            .iter()
+            .map(|commit| commit.id().clone())
+            .collect_vec();
+        // Every other commit, to also test sets that aren't closed under
+        // ancestry
+        let some_ids = all_ids.iter().step_by(2).cloned().collect_vec();
+        for ids in [all_ids, some_ids] {
+            let heads: HashSet<CommitId> = index.heads(&mut ids.iter()).into_iter().collect();
+            assert_eq!(heads, fixture.reference_heads(&ids), "seed: {seed}");
+        }
+    }
+}
+
+#[test]
+fn test_new_parents_not_obsolete() {
+    for seed in 0..NUM_SEEDS {
+        let fixture = arbitrary_dag(seed, NUM_COMMITS, REWRITE_PROBABILITY);
+        let obsolete = fixture.reference_obsolete_set();
+        for commit in &fixture.commits {
+            let new_parent_ids = fixture.tx.repo().new_parents(commit.parent_ids().to_vec());
+            assert!(
+                new_parent_ids.iter().all(|id| !obsolete.contains(id)),
+                "seed: {seed}, commit: {commit:?}, new parents: {new_parent_ids:?}"
+            );
+        }
+    }
+}
@@ -32,8 +32,9 @@ use jj_lib::revset::{
     RevsetAliasesMap, RevsetExpression, RevsetExtensions, RevsetFilterPredicate,
     RevsetParseContext, RevsetResolutionError, RevsetWorkspaceContext, SymbolResolverExtension,
 };
-use jj_lib::settings::GitSettings;
+use jj_lib::settings::{GitSettings, UserSettings};
 use jj_lib::workspace::Workspace;
+use regex::Regex;
 use test_case::test_case;
 use testutils::{
     create_random_commit, create_tree, write_random_commit, CommitGraphBuilder, TestRepo,
@@ -2316,6 +2317,66 @@ fn test_evaluate_expression_latest() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_limit() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let mut graph_builder = CommitGraphBuilder::new(&settings, mut_repo);
+    let mut commits = vec![graph_builder.initial_commit()];
+    for i in 1..30 {
+        let mut parents = vec![&commits[i - 1]];
+        if i % 3 == 0 {
+            parents.push(&commits[i / 2]);
+        } else if i % 7 == 0 {
+            parents = vec![&commits[i / 3]];
+        }
+        let commit = graph_builder.commit_with_parents(&parents);
+        commits.push(commit);
+    }
+    let hex = |i: usize| commits[i].id().hex();
+
+    // The limited set is a prefix of the full set in evaluation order
+    let expressions = [
+        "all()".to_owned(),
+        format!("::{}", hex(29)),
+        format!("{}::", hex(5)),
+        format!("{}..{}", hex(10), hex(27)),
+        format!("::{} | ::{}", hex(13), hex(20)),
+        format!("::{} & ::{}", hex(26), hex(28)),
+        format!("all() ~ ::{}", hex(18)),
+        "heads(all())".to_owned(),
+        "merges()".to_owned(),
+    ];
+    for expression in &expressions {
+        let all_ids = resolve_commit_ids(mut_repo, expression);
+        for count in [0, 1, 5, 100] {
+            assert_eq!(
+                resolve_commit_ids(mut_repo, &format!("limit({expression}, {count})")),
+                all_ids.iter().take(count).cloned().collect_vec(),
+                "limit({expression}, {count})"
+            );
+        }
+    }
+
+    // Limited sets can be combined with other sets
+    assert_eq!(
+        resolve_commit_ids(mut_repo, &format!("limit(::{}, 2) | {}", hex(29), hex(0))),
+        vec![
+            commits[29].id().clone(),
+            commits[28].id().clone(),
+            commits[0].id().clone(),
+        ]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, &format!("::{} ~ limit(all(), 1)", hex(29))),
+        resolve_commit_ids(mut_repo, &format!("::{}-", hex(29)))
+    );
+}
+
 #[test]
 fn test_evaluate_expression_merges() {
     let settings = testutils::user_settings();
@@ -2386,6 +2447,25 @@ fn test_evaluate_expression_description() {
         resolve_commit_ids(mut_repo, "visible_heads() & description(\"commit 2\")"),
         vec![]
     );
+    // Can match the full description with a regex, case-insensitively
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"description(regex:"^commit [23]$")"#),
+        vec![commit3.id().clone(), commit2.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"description(regex:"^COMMIT 1$")"#),
+        vec![]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"description(regex-i:"^COMMIT 1$")"#),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        mut_repo
+            .commits_matching_description(&Regex::new("2$").unwrap())
+            .unwrap(),
+        vec![commit2.id().clone()]
+    );
 }
 
 #[test]
@@ -2613,6 +2693,174 @@ fn test_evaluate_expression_committer() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_committer_date() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let settings_at = |timestamp: &str| {
+        let config = testutils::base_config()
+            .add_source(config::File::from_str(
+                &format!(r#"debug.commit-timestamp = "{timestamp}""#),
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        UserSettings::from_config(config)
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit1 = write_random_commit(mut_repo, &settings_at("2024-02-01T10:00:00+00:00"));
+    let commit2 = create_random_commit(mut_repo, &settings_at("2024-02-01T12:00:00+01:00"))
+        .set_parents(vec![commit1.id().clone()])
+        .write()
+        .unwrap();
+    let commit3 = create_random_commit(mut_repo, &settings_at("2024-02-02T10:00:00+00:00"))
+        .set_parents(vec![commit2.id().clone()])
+        .write()
+        .unwrap();
+
+    // The cutoff is inclusive, and time zones are taken into account
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"committer_date(after:"2024-02-01T11:00:00Z")"#),
+        vec![commit3.id().clone(), commit2.id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(mut_repo, r#"committer_date(before:"2024-02-01T11:00:00Z")"#),
+        vec![commit1.id().clone(), repo.store().root_commit_id().clone()]
+    );
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            r#"committer_date(after:"2024-02-01T10:00:01Z") & committer_date(before:"2024-02-02T00:00:00Z")"#
+        ),
+        vec![commit2.id().clone()]
+    );
+    // Searches only among candidates if specified
+    assert_eq!(
+        resolve_commit_ids(
+            mut_repo,
+            &format!(
+                r#"::{} & committer_date(after:"2024-02-01T00:00:00Z")"#,
+                commit1.id().hex()
+            )
+        ),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        mut_repo
+            .commits_after(&commit2.committer().timestamp)
+            .unwrap(),
+        vec![commit3.id().clone(), commit2.id().clone()]
+    );
+}
+
+#[test]
+fn test_common_descendants() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // F
+    // |
+    // E
+    // |\
+    // C D G
+    // |/  |
+    // B   H
+    // |  /
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_c, &commit_d]);
+    let commit_f = graph_builder.commit_with_parents(&[&commit_e]);
+    let commit_h = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_g = graph_builder.commit_with_parents(&[&commit_h]);
+    let repo = tx.commit("test");
+
+    // The branches reconverge at the merge commit
+    assert_eq!(
+        repo.common_descendants(commit_c.id(), commit_d.id())
+            .unwrap(),
+        vec![commit_e.id().clone()]
+    );
+    // A commit is its own descendant
+    assert_eq!(
+        repo.common_descendants(commit_b.id(), commit_e.id())
+            .unwrap(),
+        vec![commit_e.id().clone()]
+    );
+    assert_eq!(
+        repo.common_descendants(commit_f.id(), commit_f.id())
+            .unwrap(),
+        vec![commit_f.id().clone()]
+    );
+    // Branches that never reconverge
+    assert!(repo
+        .common_descendants(commit_c.id(), commit_g.id())
+        .unwrap()
+        .is_empty());
+    assert!(repo
+        .common_descendants(commit_e.id(), commit_h.id())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_path_history() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+
+    let old_path = RepoPath::from_internal_string("old");
+    let new_path = RepoPath::from_internal_string("new");
+    let other_path = RepoPath::from_internal_string("other");
+    let tree1 = create_tree(repo, &[(old_path, "a\nb\nc\nd\n"), (other_path, "1")]);
+    let tree2 = create_tree(repo, &[(old_path, "a\nb\nc\nd\ne\n"), (other_path, "1")]);
+    let tree3 = create_tree(repo, &[(old_path, "a\nb\nc\nd\ne\n"), (other_path, "2")]);
+    // Renamed with a small change
+    let tree4 = create_tree(repo, &[(new_path, "a\nb\nc\nd\ne\nf\n"), (other_path, "2")]);
+    let tree5 = create_tree(repo, &[(new_path, "a\nb\nc\nd\ne\ng\n"), (other_path, "2")]);
+
+    let mut parent_ids = vec![repo.store().root_commit_id().clone()];
+    let mut commit_ids = vec![];
+    for tree in [tree1, tree2, tree3, tree4, tree5] {
+        let commit = mut_repo
+            .new_commit(&settings, parent_ids, tree.id())
+            .write()
+            .unwrap();
+        parent_ids = vec![commit.id().clone()];
+        commit_ids.push(commit.id().clone());
+    }
+    let [commit1, commit2, _commit3, commit4, commit5] = commit_ids.try_into().unwrap();
+
+    assert_eq!(
+        mut_repo.path_history(&commit5, new_path, false).unwrap(),
+        vec![commit5.clone(), commit4.clone()]
+    );
+    assert_eq!(
+        mut_repo.path_history(&commit5, new_path, true).unwrap(),
+        vec![
+            commit5.clone(),
+            commit4.clone(),
+            commit2.clone(),
+            commit1.clone()
+        ]
+    );
+    assert_eq!(
+        mut_repo.path_history(&commit4, old_path, false).unwrap(),
+        vec![commit4.clone(), commit2.clone(), commit1.clone()]
+    );
+}
+
 #[test]
 fn test_evaluate_expression_union() {
     let settings = testutils::user_settings();
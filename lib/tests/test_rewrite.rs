@@ -12,15 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
+use assert_matches::assert_matches;
 use itertools::Itertools as _;
+use jj_lib::backend::{MillisSinceEpoch, Signature, Timestamp};
 use jj_lib::commit::Commit;
+use jj_lib::evolution::Evolution;
 use jj_lib::matchers::{EverythingMatcher, FilesMatcher};
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::op_store::{RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
-use jj_lib::repo::Repo;
+use jj_lib::repo::{
+    EvolveSummary, MutableRepo, ReorderCommitsError, Repo, RepoError, SquashRangeError,
+};
 use jj_lib::repo_path::RepoPath;
+use jj_lib::revset::RevsetExpression;
 use jj_lib::rewrite::{
-    rebase_commit_with_options, restore_tree, CommitRewriter, EmptyBehaviour, RebaseOptions,
+    normalize_timestamps, rebase_commit_with_options, restore_tree, CommitRewriter, EmptyBehaviour,
+    RebaseOptions, TimestampPolicy,
 };
 use maplit::{hashmap, hashset};
 use test_case::test_case;
@@ -1769,3 +1778,1042 @@ fn test_rebase_abandoning_empty() {
         hashset! {new_commit_f.id().clone(), new_wc_commit_id.clone()}
     );
 }
+
+#[test]
+fn test_reorder_commits() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Swap B and C, with D on top of the chain:
+    //
+    // D         D'
+    // C         B'
+    // B    =>   C'
+    // A         A
+    let path_f = RepoPath::from_internal_string("f");
+    let path_b = RepoPath::from_internal_string("b");
+    let path_c = RepoPath::from_internal_string("c");
+    let path_d = RepoPath::from_internal_string("d");
+    let tree_a = create_tree(repo, &[(path_f, "a\n")]);
+    let tree_b = create_tree(repo, &[(path_f, "a\n"), (path_b, "b\n")]);
+    let tree_c = create_tree(repo, &[(path_f, "c\n"), (path_b, "b\n"), (path_c, "c\n")]);
+    let tree_d = create_tree(
+        repo,
+        &[
+            (path_f, "c\n"),
+            (path_b, "b\n"),
+            (path_c, "c\n"),
+            (path_d, "d\n"),
+        ],
+    );
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let mut parent_id = repo.store().root_commit_id().clone();
+    let mut commits = vec![];
+    for tree in [tree_a, tree_b, tree_c, tree_d] {
+        let commit = mut_repo
+            .new_commit(&settings, vec![parent_id], tree.id())
+            .write()
+            .unwrap();
+        parent_id = commit.id().clone();
+        commits.push(commit);
+    }
+    let [commit_a, commit_b, commit_c, commit_d] = commits.try_into().unwrap();
+    mut_repo.set_local_branch_target("top", RefTarget::normal(commit_c.id().clone()));
+
+    let rebased = mut_repo
+        .reorder_commits(
+            &[commit_c.id().clone(), commit_b.id().clone()],
+            commit_a.id(),
+            &settings,
+        )
+        .unwrap();
+    assert_eq!(rebased.len(), 3);
+    let store = mut_repo.store();
+    let new_commit_c = store.get_commit(&rebased[commit_c.id()]).unwrap();
+    let new_commit_b = store.get_commit(&rebased[commit_b.id()]).unwrap();
+    let new_commit_d = store.get_commit(&rebased[commit_d.id()]).unwrap();
+    assert_eq!(new_commit_c.parent_ids(), [commit_a.id().clone()]);
+    assert_eq!(new_commit_b.parent_ids(), [new_commit_c.id().clone()]);
+    assert_eq!(new_commit_d.parent_ids(), [new_commit_b.id().clone()]);
+    assert_eq!(new_commit_c.change_id(), commit_c.change_id());
+    assert_eq!(new_commit_b.change_id(), commit_b.change_id());
+
+    // C's changes are applied to A, then B's changes on top
+    let entries = |tree: &MergedTree| {
+        tree.entries()
+            .map(|(path, value)| (path, value.unwrap()))
+            .collect_vec()
+    };
+    assert_eq!(
+        entries(&new_commit_c.tree().unwrap()),
+        entries(&create_tree(repo, &[(path_f, "c\n"), (path_c, "c\n")]))
+    );
+    assert_eq!(
+        entries(&new_commit_b.tree().unwrap()),
+        entries(&commit_c.tree().unwrap())
+    );
+    assert_eq!(
+        entries(&new_commit_d.tree().unwrap()),
+        entries(&commit_d.tree().unwrap())
+    );
+    assert_eq!(
+        *mut_repo.view().heads(),
+        hashset! {new_commit_d.id().clone()}
+    );
+    // The branch follows C rather than moving to the new top of the chain
+    assert_eq!(
+        mut_repo.get_local_branch("top"),
+        RefTarget::normal(new_commit_c.id().clone())
+    );
+}
+
+#[test]
+fn test_reorder_commits_invalid() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // B and C are siblings
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let mut_repo = tx.mut_repo();
+
+    assert_matches!(
+        mut_repo.reorder_commits(
+            &[commit_b.id().clone(), commit_c.id().clone()],
+            commit_a.id(),
+            &settings
+        ),
+        Err(ReorderCommitsError::NotLinearChain)
+    );
+    assert_matches!(
+        mut_repo.reorder_commits(
+            &[commit_c.id().clone(), commit_c.id().clone()],
+            commit_a.id(),
+            &settings
+        ),
+        Err(ReorderCommitsError::NotLinearChain)
+    );
+    assert_matches!(
+        mut_repo.reorder_commits(
+            &[commit_d.id().clone(), commit_c.id().clone()],
+            commit_c.id(),
+            &settings
+        ),
+        Err(ReorderCommitsError::BaseInChain(_))
+    );
+    assert_matches!(
+        mut_repo.reorder_commits(&[commit_c.id().clone()], commit_d.id(), &settings),
+        Err(ReorderCommitsError::BaseInChain(_))
+    );
+    assert!(!mut_repo.has_rewrites());
+}
+
+#[test]
+fn test_squash_range() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Squash B through D into a single commit on top of A. E is left as an
+    // orphan until the descendants are rebased.
+    //
+    // E
+    // D
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_d]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    assert_matches!(
+        mut_repo.squash_range(&commit_c, &commit_b, &settings),
+        Err(SquashRangeError::NotAncestor { .. })
+    );
+    let new_commit = mut_repo
+        .squash_range(&commit_b, &commit_d, &settings)
+        .unwrap();
+    assert_eq!(new_commit.parent_ids(), [commit_a.id().clone()]);
+    assert_eq!(new_commit.tree_id(), commit_d.tree_id());
+    assert_eq!(new_commit.change_id(), commit_d.change_id());
+    assert_eq!(
+        new_commit.predecessor_ids(),
+        [
+            commit_b.id().clone(),
+            commit_c.id().clone(),
+            commit_d.id().clone()
+        ]
+    );
+    assert_eq!(mut_repo.orphan_ids().unwrap(), vec![commit_e.id().clone()]);
+
+    let rebased = mut_repo.rebase_descendants_return_map(&settings).unwrap();
+    assert_eq!(rebased.len(), 1);
+    let new_commit_e = assert_rebased_onto(mut_repo, &rebased, &commit_e, &[new_commit.id()]);
+    assert_eq!(
+        *mut_repo.view().heads(),
+        hashset! {new_commit_e.id().clone()}
+    );
+}
+
+#[test]
+fn test_rebase_range_clean() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Rebase C::D onto B, then adopt the result.
+    //
+    // D feature
+    // C B main
+    // |/
+    // A
+    let path_main = RepoPath::from_internal_string("main");
+    let path_feature = RepoPath::from_internal_string("feature");
+    let tree_a = create_tree(repo, &[]);
+    let tree_b = create_tree(repo, &[(path_main, "main")]);
+    let tree_c = create_tree(repo, &[(path_feature, "c")]);
+    let tree_d = create_tree(repo, &[(path_feature, "d")]);
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let mut write_commit = |parents: Vec<_>, tree: &MergedTree| {
+        mut_repo
+            .new_commit(&settings, parents, tree.id())
+            .write()
+            .unwrap()
+    };
+    let commit_a = write_commit(vec![repo.store().root_commit_id().clone()], &tree_a);
+    let commit_b = write_commit(vec![commit_a.id().clone()], &tree_b);
+    let commit_c = write_commit(vec![commit_a.id().clone()], &tree_c);
+    let commit_d = write_commit(vec![commit_c.id().clone()], &tree_d);
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit_b.id().clone()));
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(commit_d.id().clone()));
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let outcome = mut_repo
+        .rebase_range(
+            &settings,
+            vec![commit_c.id().clone()],
+            vec![commit_d.id().clone()],
+            commit_b.id(),
+        )
+        .unwrap();
+    assert!(!outcome.has_conflicts());
+    assert_eq!(
+        outcome
+            .commits
+            .iter()
+            .map(|commit| &commit.old_id)
+            .collect_vec(),
+        [commit_c.id(), commit_d.id()]
+    );
+    let new_commit_c = repo.store().get_commit(&outcome.commits[0].new_id).unwrap();
+    let new_commit_d = repo.store().get_commit(&outcome.commits[1].new_id).unwrap();
+    assert_eq!(new_commit_c.parent_ids(), [commit_b.id().clone()]);
+    assert_eq!(new_commit_d.parent_ids(), [new_commit_c.id().clone()]);
+    let new_tree_d = new_commit_d.tree().unwrap();
+    assert_eq!(
+        new_tree_d.path_value(path_main).unwrap(),
+        tree_b.path_value(path_main).unwrap()
+    );
+    assert_eq!(
+        new_tree_d.path_value(path_feature).unwrap(),
+        tree_d.path_value(path_feature).unwrap()
+    );
+    // Nothing visible changed yet
+    assert!(!mut_repo.has_rewrites());
+    assert_eq!(mut_repo.view().store_view(), repo.view().store_view());
+
+    outcome.adopt(mut_repo).unwrap();
+    mut_repo.rebase_descendants(&settings).unwrap();
+    assert_eq!(
+        *mut_repo.view().heads(),
+        hashset! {new_commit_d.id().clone()}
+    );
+    assert_eq!(
+        mut_repo.get_local_branch("feature"),
+        RefTarget::normal(new_commit_d.id().clone())
+    );
+    assert_eq!(
+        mut_repo.get_local_branch("main"),
+        RefTarget::normal(commit_b.id().clone())
+    );
+}
+
+#[test]
+fn test_rebase_range_conflict() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Rebase C onto B, which changes the same file.
+    //
+    // C B
+    // |/
+    // A
+    let path = RepoPath::from_internal_string("file");
+    let tree_a = create_tree(repo, &[(path, "a")]);
+    let tree_b = create_tree(repo, &[(path, "b")]);
+    let tree_c = create_tree(repo, &[(path, "c")]);
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = mut_repo
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree_a.id(),
+        )
+        .write()
+        .unwrap();
+    let commit_b = mut_repo
+        .new_commit(&settings, vec![commit_a.id().clone()], tree_b.id())
+        .write()
+        .unwrap();
+    let commit_c = mut_repo
+        .new_commit(&settings, vec![commit_a.id().clone()], tree_c.id())
+        .write()
+        .unwrap();
+    mut_repo.set_local_branch_target("feature", RefTarget::normal(commit_c.id().clone()));
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let outcome = tx
+        .mut_repo()
+        .rebase_range(
+            &settings,
+            vec![commit_c.id().clone()],
+            vec![commit_c.id().clone()],
+            commit_b.id(),
+        )
+        .unwrap();
+    assert!(outcome.has_conflicts());
+    assert_eq!(outcome.commits.len(), 1);
+    assert_eq!(outcome.commits[0].old_id, *commit_c.id());
+    assert_eq!(outcome.commits[0].conflicted_paths, [path.to_owned()]);
+    assert_eq!(tx.mut_repo().view().store_view(), repo.view().store_view());
+    let repo = tx.commit("test rebase");
+
+    // The hidden commit can still be loaded by id
+    let new_id = &outcome.commits[0].new_id;
+    assert!(!repo.view().heads().contains(new_id));
+    assert!(repo.index().has_id(new_id));
+    let new_commit_c = repo.store().get_commit(new_id).unwrap();
+    assert_eq!(new_commit_c.parent_ids(), [commit_b.id().clone()]);
+    assert!(new_commit_c.has_conflict().unwrap());
+    assert_eq!(
+        repo.view().get_local_branch("feature"),
+        &RefTarget::normal(commit_c.id().clone())
+    );
+}
+
+#[test]
+fn test_orphans_blocked_on() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Rewrite A, making B and C orphans. C can't be rebased before B.
+    //
+    // C
+    // B   E
+    // A   D
+    // | /
+    // root
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.initial_commit();
+    let commit_e = graph_builder.commit_with_parents(&[&commit_d]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let evolution = Evolution::for_transaction(mut_repo).unwrap();
+    assert_eq!(
+        evolution.orphans_blocked_on(commit_b.id()).unwrap(),
+        vec![commit_c.id().clone()]
+    );
+    assert!(evolution
+        .orphans_blocked_on(commit_c.id())
+        .unwrap()
+        .is_empty());
+    // Commits that aren't orphans block nothing
+    assert!(evolution
+        .orphans_blocked_on(commit_a.id())
+        .unwrap()
+        .is_empty());
+    assert!(evolution
+        .orphans_blocked_on(commit_d.id())
+        .unwrap()
+        .is_empty());
+    assert!(evolution
+        .orphans_blocked_on(commit_e.id())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_evolve() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // B  D
+    // A  C  E
+    //  \ | /
+    //   root
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.initial_commit();
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_e = graph_builder.initial_commit();
+    let repo = tx.commit("test");
+
+    // Rewrite A, making B an orphan. Add a rewrite of C without hiding C, as
+    // if from a concurrent operation, and an unrelated commit with E's change
+    // id.
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let commit_c2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_c.parent_ids().to_vec(),
+            commit_c.tree_id().clone(),
+        )
+        .set_change_id(commit_c.change_id().clone())
+        .set_predecessors(vec![commit_c.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let commit_e2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_e.parent_ids().to_vec(),
+            commit_e.tree_id().clone(),
+        )
+        .set_change_id(commit_e.change_id().clone())
+        .set_description("diverged")
+        .write()
+        .unwrap();
+
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(
+        summary,
+        EvolveSummary {
+            resolved_orphans: 2,
+            resolved_divergences: 1,
+            remaining_divergences: 1,
+        }
+    );
+    let visible_commit = |commit: &Commit| {
+        let ids = mut_repo.resolve_change_id(commit.change_id()).unwrap();
+        assert_eq!(ids.len(), 1);
+        mut_repo.store().get_commit(&ids[0]).unwrap()
+    };
+    assert_eq!(
+        visible_commit(&commit_b).parent_ids(),
+        &[commit_a2.id().clone()]
+    );
+    assert_eq!(visible_commit(&commit_c).id(), commit_c2.id());
+    assert_eq!(
+        visible_commit(&commit_d).parent_ids(),
+        &[commit_c2.id().clone()]
+    );
+    // The divergence of E has to be resolved by the user
+    assert_eq!(
+        mut_repo
+            .resolve_change_id(commit_e.change_id())
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {commit_e.id().clone(), commit_e2.id().clone()}
+    );
+    assert!(!mut_repo.has_rewrites());
+}
+
+#[test]
+fn test_evolve_accepted_divergence() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // Add a rewrite of A without hiding A, as if from a concurrent operation,
+    // and accept the divergence
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_a.parent_ids().to_vec(),
+            commit_a.tree_id().clone(),
+        )
+        .set_change_id(commit_a.change_id().clone())
+        .set_predecessors(vec![commit_a.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    mut_repo.accept_divergence(commit_a.change_id());
+
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(summary, EvolveSummary::default());
+    assert_eq!(
+        mut_repo
+            .resolve_change_id(commit_a.change_id())
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {commit_a.id().clone(), commit_a2.id().clone()}
+    );
+    let evolution = Evolution::new(mut_repo).unwrap();
+    assert!(evolution.is_divergent(commit_a.id()).unwrap());
+    let repo = tx.commit("test");
+    assert!(repo.view().is_divergence_accepted(commit_a.change_id()));
+}
+
+#[test]
+fn test_evolve_intentional_divergence() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Two variants of change X, each with a child
+    //
+    // Ya  Yb
+    // Xa  Xb
+    //  \ /
+    //  root
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_xa = write_random_commit(mut_repo, &settings);
+    let commit_xb = create_random_commit(mut_repo, &settings)
+        .set_change_id(commit_xa.change_id().clone())
+        .write()
+        .unwrap();
+    let commit_ya = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_xa.id().clone()])
+        .write()
+        .unwrap();
+    let commit_yb = create_random_commit(mut_repo, &settings)
+        .set_parents(vec![commit_xb.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Add a rewrite of each variant without hiding it, as if from concurrent
+    // operations
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let rewrite_variant = |mut_repo: &mut MutableRepo, commit: &Commit| {
+        mut_repo
+            .new_commit(
+                &settings,
+                commit.parent_ids().to_vec(),
+                commit.tree_id().clone(),
+            )
+            .set_change_id(commit.change_id().clone())
+            .set_predecessors(vec![commit.id().clone()])
+            .set_description("rewritten")
+            .write()
+            .unwrap()
+    };
+    let commit_xa2 = rewrite_variant(mut_repo, &commit_xa);
+    let commit_xb2 = rewrite_variant(mut_repo, &commit_xb);
+
+    // It's ambiguous which variant to evolve Ya and Yb onto
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(
+        summary,
+        EvolveSummary {
+            resolved_orphans: 0,
+            resolved_divergences: 0,
+            remaining_divergences: 1,
+        }
+    );
+
+    // Once the divergence is allowed, each child follows its own variant, and
+    // the variants are left alone
+    mut_repo.set_change_divergence_allowed(commit_xa.change_id(), true);
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(
+        summary,
+        EvolveSummary {
+            resolved_orphans: 2,
+            resolved_divergences: 0,
+            remaining_divergences: 0,
+        }
+    );
+    assert_eq!(
+        mut_repo
+            .resolve_change_id(commit_xa.change_id())
+            .unwrap()
+            .into_iter()
+            .collect::<HashSet<_>>(),
+        hashset! {
+            commit_xa.id().clone(),
+            commit_xb.id().clone(),
+            commit_xa2.id().clone(),
+            commit_xb2.id().clone(),
+        }
+    );
+    let visible_commit = |commit: &Commit| {
+        let ids = mut_repo.resolve_change_id(commit.change_id()).unwrap();
+        assert_eq!(ids.len(), 1);
+        mut_repo.store().get_commit(&ids[0]).unwrap()
+    };
+    assert_eq!(
+        visible_commit(&commit_ya).parent_ids(),
+        &[commit_xa2.id().clone()]
+    );
+    assert_eq!(
+        visible_commit(&commit_yb).parent_ids(),
+        &[commit_xb2.id().clone()]
+    );
+    let evolution = Evolution::new(mut_repo).unwrap();
+    assert!(evolution
+        .is_intentionally_divergent(commit_xa2.id())
+        .unwrap());
+    assert!(!evolution
+        .is_intentionally_divergent(commit_ya.id())
+        .unwrap());
+
+    // Disallowing the divergence again makes it count
+    mut_repo.set_change_divergence_allowed(commit_xa.change_id(), false);
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(summary.remaining_divergences, 1);
+}
+
+#[test]
+fn test_evolve_moves_branches() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // B  D
+    // A  C
+    //  \ |
+    //   root
+    //
+    // Branches point to B and C, and a tag to B.
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.initial_commit();
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_local_branch_target("b", RefTarget::normal(commit_b.id().clone()));
+    mut_repo.set_local_branch_target("c", RefTarget::normal(commit_c.id().clone()));
+    mut_repo.set_tag_target("v1", RefTarget::normal(commit_b.id().clone()));
+    let repo = tx.commit("test");
+
+    // Rewrite A, making B an orphan, and add a rewrite of C without hiding C
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a2 = mut_repo
+        .rewrite_commit(&settings, &commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let commit_c2 = mut_repo
+        .new_commit(
+            &settings,
+            commit_c.parent_ids().to_vec(),
+            commit_c.tree_id().clone(),
+        )
+        .set_change_id(commit_c.change_id().clone())
+        .set_predecessors(vec![commit_c.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    mut_repo.evolve(&settings).unwrap();
+
+    let visible_commit = |commit: &Commit| {
+        let ids = mut_repo.resolve_change_id(commit.change_id()).unwrap();
+        assert_eq!(ids.len(), 1);
+        mut_repo.store().get_commit(&ids[0]).unwrap()
+    };
+    let new_commit_b = visible_commit(&commit_b);
+    assert_eq!(new_commit_b.parent_ids(), &[commit_a2.id().clone()]);
+    assert_eq!(
+        visible_commit(&commit_d).parent_ids(),
+        &[commit_c2.id().clone()]
+    );
+    assert_eq!(
+        mut_repo.get_local_branch("b"),
+        RefTarget::normal(new_commit_b.id().clone())
+    );
+    assert_eq!(
+        mut_repo.get_local_branch("c"),
+        RefTarget::normal(commit_c2.id().clone())
+    );
+    // The tag stays on the old commit
+    assert_eq!(
+        mut_repo.get_tag("v1"),
+        RefTarget::normal(commit_b.id().clone())
+    );
+}
+
+#[test]
+fn test_evolve_onto_descendant() {
+    // Test that a rewrite recorded into a descendant of the rewritten commit is
+    // reported instead of rebasing the descendants onto themselves
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // C
+    // B
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    mut_repo.set_rewritten_commit(commit_a.id().clone(), commit_c.id().clone());
+    assert_matches!(
+        mut_repo.evolve(&settings),
+        Err(RepoError::RewriteCycle { commit_id, parent_id })
+            if commit_id == *commit_a.id() && parent_id == *commit_c.id()
+    );
+    // Nothing was rebased
+    assert_eq!(*mut_repo.view().heads(), hashset! {commit_c.id().clone()});
+}
+
+#[test]
+fn test_rebase_descendants_reuses_tree_merges() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    // A chain of 20 commits on top of A, each adding a file
+    let path_a = RepoPath::from_internal_string("dir/a");
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![store.root_commit_id().clone()],
+            create_tree(repo, &[(path_a, "a\n")]).id(),
+        )
+        .write()
+        .unwrap();
+    let mut path_contents = vec![(path_a.to_owned(), "a\n".to_owned())];
+    let mut parent = commit_a.clone();
+    for i in 0..20 {
+        path_contents.push((
+            RepoPath::from_internal_string(&format!("dir/file{i}")).to_owned(),
+            format!("{i}\n"),
+        ));
+        let tree = create_tree(
+            repo,
+            &path_contents
+                .iter()
+                .map(|(path, contents)| (path.as_ref(), contents.as_str()))
+                .collect_vec(),
+        );
+        parent = tx
+            .mut_repo()
+            .new_commit(&settings, vec![parent.id().clone()], tree.id())
+            .write()
+            .unwrap();
+    }
+    let head_id = parent.id().clone();
+    let repo = tx.commit("test");
+
+    // Rewrites A and returns the tree of the rebased head
+    let rebase_chain = || {
+        let mut tx = repo.start_transaction(&settings);
+        let new_tree = create_tree(&repo, &[(path_a, "modified\n")]);
+        tx.mut_repo()
+            .rewrite_commit(&settings, &commit_a)
+            .set_tree_id(new_tree.id())
+            .write()
+            .unwrap();
+        let rebase_map = tx
+            .mut_repo()
+            .rebase_descendants_return_map(&settings)
+            .unwrap();
+        assert_eq!(rebase_map.len(), 20);
+        let new_head = store.get_commit(&rebase_map[&head_id]).unwrap();
+        new_head
+            .tree()
+            .unwrap()
+            .entries()
+            .map(|(path, value)| (path, value.unwrap()))
+            .collect_vec()
+    };
+
+    let num_merges_before = store.num_tree_merges();
+    let uncached_entries = rebase_chain();
+    let num_uncached_merges = store.num_tree_merges() - num_merges_before;
+    // At least the root and "dir" trees are merged for each commit
+    assert!(num_uncached_merges >= 20);
+
+    // Doing the same again reuses all the merges
+    let num_merges_before = store.num_tree_merges();
+    let cached_entries = rebase_chain();
+    assert_eq!(store.num_tree_merges(), num_merges_before);
+    assert_eq!(cached_entries, uncached_entries);
+}
+
+#[test]
+fn test_normalize_timestamps() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let signature = |millis: i64| Signature {
+        name: "Some One".to_string(),
+        email: "some.one@example.com".to_string(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(millis),
+            tz_offset: 60,
+        },
+    };
+    let write_commit =
+        |mut_repo: &mut MutableRepo, parent: Option<&Commit>, author: i64, committer: i64| {
+            let mut builder = create_random_commit(mut_repo, &settings)
+                .set_author(signature(author))
+                .set_committer(signature(committer));
+            if let Some(parent) = parent {
+                builder = builder.set_parents(vec![parent.id().clone()]);
+            }
+            builder.write().unwrap()
+        };
+
+    // E (outside the range)
+    // D
+    // C committed before it was authored
+    // B at the epoch
+    // A
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_commit(mut_repo, None, 1000, 2000);
+    let commit_b = write_commit(mut_repo, Some(&commit_a), 0, 0);
+    let commit_c = write_commit(mut_repo, Some(&commit_b), 5000, 3000);
+    let commit_d = write_commit(mut_repo, Some(&commit_c), 6000, 6000);
+    let commit_e = write_commit(mut_repo, Some(&commit_d), 7000, 7000);
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let range = RevsetExpression::commits(vec![
+        commit_a.id().clone(),
+        commit_b.id().clone(),
+        commit_c.id().clone(),
+        commit_d.id().clone(),
+    ]);
+    let policy = TimestampPolicy {
+        monotonic: true,
+        committer_after_author: true,
+        ..Default::default()
+    };
+    let report = normalize_timestamps(&settings, mut_repo, &range, &policy).unwrap();
+    assert_eq!(
+        report.corrected,
+        vec![commit_b.id().clone(), commit_c.id().clone()]
+    );
+    assert_eq!(report.rewritten.len(), 3);
+    assert!(!report.rewritten.contains_key(commit_a.id()));
+
+    let store = mut_repo.store().clone();
+    let new_commit_b = store.get_commit(&report.rewritten[commit_b.id()]).unwrap();
+    let new_commit_c = store.get_commit(&report.rewritten[commit_c.id()]).unwrap();
+    let new_commit_d = store.get_commit(&report.rewritten[commit_d.id()]).unwrap();
+    // B is raised to A's timestamps
+    assert_eq!(new_commit_b.author(), &signature(1000));
+    assert_eq!(new_commit_b.committer(), &signature(2000));
+    assert_eq!(new_commit_b.parent_ids(), &[commit_a.id().clone()]);
+    // C's committer timestamp is raised to its author timestamp
+    assert_eq!(new_commit_c.author(), &signature(5000));
+    assert_eq!(new_commit_c.committer(), &signature(5000));
+    assert_eq!(new_commit_c.parent_ids(), &[new_commit_b.id().clone()]);
+    // D is only rebased, keeping its timestamps
+    assert_eq!(new_commit_d.author(), &signature(6000));
+    assert_eq!(new_commit_d.committer(), &signature(6000));
+    assert_eq!(new_commit_d.parent_ids(), &[new_commit_c.id().clone()]);
+    for (old_commit, new_commit) in [
+        (&commit_b, &new_commit_b),
+        (&commit_c, &new_commit_c),
+        (&commit_d, &new_commit_d),
+    ] {
+        assert_eq!(new_commit.change_id(), old_commit.change_id());
+        assert_eq!(new_commit.predecessor_ids(), &[old_commit.id().clone()]);
+    }
+
+    // E is rebased by evolution, and the originals become hidden
+    let summary = mut_repo.evolve(&settings).unwrap();
+    assert_eq!(summary.resolved_orphans, 1);
+    let head_ids = mut_repo.view().heads().iter().cloned().collect_vec();
+    assert_eq!(head_ids.len(), 1);
+    let new_commit_e = store.get_commit(&head_ids[0]).unwrap();
+    assert_eq!(new_commit_e.parent_ids(), &[new_commit_d.id().clone()]);
+    assert_eq!(new_commit_e.predecessor_ids(), &[commit_e.id().clone()]);
+    let visible_ids: HashSet<_> = RevsetExpression::all()
+        .evaluate_programmatic(mut_repo)
+        .unwrap()
+        .iter()
+        .collect();
+    assert!(visible_ids.contains(commit_a.id()));
+    for commit in [&commit_b, &commit_c, &commit_d, &commit_e] {
+        assert!(!visible_ids.contains(commit.id()));
+    }
+}
+
+#[test]
+fn test_normalize_timestamps_max_bound() {
+    // Tests that the upper bound is applied after raising a commit to its
+    // parent's timestamps, so no corrected timestamp exceeds it.
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let signature = |millis: i64| Signature {
+        name: "Some One".to_string(),
+        email: "some.one@example.com".to_string(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(millis),
+            tz_offset: 0,
+        },
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = create_random_commit(tx.mut_repo(), &settings)
+        .set_author(signature(9000))
+        .set_committer(signature(9000))
+        .write()
+        .unwrap();
+    let commit_b = create_random_commit(tx.mut_repo(), &settings)
+        .set_parents(vec![commit_a.id().clone()])
+        .set_author(signature(1000))
+        .set_committer(signature(1000))
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // A is outside the range and later than the bound
+    let mut tx = repo.start_transaction(&settings);
+    let range = RevsetExpression::commit(commit_b.id().clone());
+    let policy = TimestampPolicy {
+        monotonic: true,
+        max: Some(MillisSinceEpoch(5000)),
+        ..Default::default()
+    };
+    let report = normalize_timestamps(&settings, tx.mut_repo(), &range, &policy).unwrap();
+    assert_eq!(report.corrected, vec![commit_b.id().clone()]);
+    let new_commit_b = repo
+        .store()
+        .get_commit(&report.rewritten[commit_b.id()])
+        .unwrap();
+    assert_eq!(new_commit_b.author(), &signature(5000));
+    assert_eq!(new_commit_b.committer(), &signature(5000));
+}
+
+#[test]
+fn test_rebase_descendants_reuses_tree_merges_in_chain() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    // A chain of 20 commits on top of A, alternately changing and restoring a
+    // file three directories deep. Rewriting another file in the same
+    // directory in A makes each rebase merge the root tree and the three
+    // directories, so a naive rebase of the chain does 20 * 4 tree merges.
+    // The merges of the even and of the odd commits are identical.
+    let other_path = RepoPath::from_internal_string("a/b/c/other");
+    let toggled_path = RepoPath::from_internal_string("a/b/c/toggled");
+    let tree_with = |other: &str, toggled: &str| {
+        create_tree(repo, &[(other_path, other), (toggled_path, toggled)])
+    };
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![store.root_commit_id().clone()],
+            tree_with("base\n", "off\n").id(),
+        )
+        .write()
+        .unwrap();
+    let mut chain = vec![];
+    let mut parent = commit_a.clone();
+    for i in 0..20 {
+        let toggled = if i % 2 == 0 { "on\n" } else { "off\n" };
+        parent = tx
+            .mut_repo()
+            .new_commit(
+                &settings,
+                vec![parent.id().clone()],
+                tree_with("base\n", toggled).id(),
+            )
+            .write()
+            .unwrap();
+        chain.push(parent.clone());
+    }
+    let repo = tx.commit("test");
+
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo()
+        .rewrite_commit(&settings, &commit_a)
+        .set_tree_id(tree_with("modified\n", "off\n").id())
+        .write()
+        .unwrap();
+    let num_merges_before = store.num_tree_merges();
+    let rebase_map = tx
+        .mut_repo()
+        .rebase_descendants_return_map(&settings)
+        .unwrap();
+    let num_merges = store.num_tree_merges() - num_merges_before;
+    assert_eq!(rebase_map.len(), 20);
+    // Only the first two rebases merge trees, the others reuse their results
+    assert_eq!(num_merges, 2 * 4);
+
+    // The trees are the same as merged without the cache
+    for (i, commit) in chain.iter().enumerate() {
+        let toggled = if i % 2 == 0 { "on\n" } else { "off\n" };
+        let new_commit = store.get_commit(&rebase_map[commit.id()]).unwrap();
+        assert_eq!(
+            new_commit.tree_id(),
+            &tree_with("modified\n", toggled).id(),
+            "commit {i}"
+        );
+    }
+}
@@ -15,6 +15,7 @@
 use std::collections::HashMap;
 
 use jj_lib::repo::Repo;
+use jj_lib::rewrite::{StackAction, StackCommit};
 use maplit::hashset;
 use testutils::{CommitGraphBuilder, TestRepo};
 
@@ -121,3 +122,163 @@ fn test_transform_descendants_sync_linearize_merge() {
 
     assert_eq!(new_commit_c.parent_ids(), vec![commit_b.id().clone()]);
 }
+
+// Reword B in the stack A..C. D is outside the range and gets rebased
+// afterwards.
+//
+// D
+// C
+// B
+// A
+#[test]
+fn test_transform_stack_reword() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+
+    let mut visited = vec![];
+    let report = tx
+        .mut_repo()
+        .transform_stack(
+            &settings,
+            vec![commit_a.id().clone()],
+            vec![commit_c.id().clone()],
+            &mut |stack_commit: StackCommit| {
+                visited.push(stack_commit.old_commit.id().clone());
+                if stack_commit.old_commit == commit_b {
+                    StackAction::Reword("reworded".to_string())
+                } else {
+                    StackAction::Keep
+                }
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        visited,
+        vec![
+            commit_a.id().clone(),
+            commit_b.id().clone(),
+            commit_c.id().clone()
+        ]
+    );
+    assert_eq!(report.unchanged, vec![commit_a.id().clone()]);
+    assert!(report.dropped.is_empty());
+    assert_eq!(report.rewritten.len(), 2);
+
+    let mut_repo = tx.mut_repo();
+    let store = mut_repo.store().clone();
+    let new_commit_b = store
+        .get_commit(report.rewritten.get(commit_b.id()).unwrap())
+        .unwrap();
+    let new_commit_c = store
+        .get_commit(report.rewritten.get(commit_c.id()).unwrap())
+        .unwrap();
+    assert_eq!(new_commit_b.description(), "reworded");
+    assert_eq!(new_commit_b.change_id(), commit_b.change_id());
+    assert_eq!(new_commit_b.predecessor_ids(), [commit_b.id().clone()]);
+    assert_eq!(new_commit_b.parent_ids(), [commit_a.id().clone()]);
+    assert_eq!(new_commit_b.tree_id(), commit_b.tree_id());
+    assert_eq!(new_commit_c.description(), commit_c.description());
+    assert_eq!(new_commit_c.change_id(), commit_c.change_id());
+    assert_eq!(new_commit_c.predecessor_ids(), [commit_c.id().clone()]);
+    assert_eq!(new_commit_c.parent_ids(), [new_commit_b.id().clone()]);
+
+    // D is left to the usual rebase, after which nothing else needs rebasing
+    let rebased = mut_repo.rebase_descendants_return_map(&settings).unwrap();
+    assert_eq!(rebased.len(), 1);
+    let new_commit_d = store
+        .get_commit(rebased.get(commit_d.id()).unwrap())
+        .unwrap();
+    assert_eq!(new_commit_d.parent_ids(), [new_commit_c.id().clone()]);
+    assert_eq!(mut_repo.rebase_descendants(&settings).unwrap(), 0);
+    assert_eq!(
+        *mut_repo.view().heads(),
+        hashset! {new_commit_d.id().clone()}
+    );
+}
+
+// Drop the top of the stack B..D, and reword B. The merge M within the range
+// is moved onto the rewritten B and C is replaced by its parent A. E is outside
+// the range.
+//
+//   E
+// D M
+// |/|
+// C |
+// | B
+// |/
+// A
+#[test]
+fn test_transform_stack_drop_and_merge() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let commit_m = graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let commit_e = graph_builder.commit_with_parents(&[&commit_m]);
+
+    let report = tx
+        .mut_repo()
+        .transform_stack(
+            &settings,
+            vec![commit_b.id().clone(), commit_c.id().clone()],
+            vec![commit_d.id().clone(), commit_m.id().clone()],
+            &mut |stack_commit: StackCommit| {
+                if stack_commit.old_commit == commit_b {
+                    StackAction::Reword("reworded".to_string())
+                } else if stack_commit.old_commit == commit_c || stack_commit.old_commit == commit_d
+                {
+                    StackAction::Drop
+                } else {
+                    StackAction::Keep
+                }
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        report.dropped,
+        vec![commit_c.id().clone(), commit_d.id().clone()]
+    );
+    assert!(report.unchanged.is_empty());
+    assert_eq!(report.rewritten.len(), 2);
+
+    let mut_repo = tx.mut_repo();
+    let store = mut_repo.store().clone();
+    let new_commit_b = store
+        .get_commit(report.rewritten.get(commit_b.id()).unwrap())
+        .unwrap();
+    let new_commit_m = store
+        .get_commit(report.rewritten.get(commit_m.id()).unwrap())
+        .unwrap();
+    assert_eq!(new_commit_b.change_id(), commit_b.change_id());
+    assert_eq!(
+        new_commit_m.parent_ids(),
+        [new_commit_b.id().clone(), commit_a.id().clone()]
+    );
+    assert_eq!(new_commit_m.change_id(), commit_m.change_id());
+
+    let rebased = mut_repo.rebase_descendants_return_map(&settings).unwrap();
+    assert_eq!(rebased.len(), 1);
+    let new_commit_e = store
+        .get_commit(rebased.get(commit_e.id()).unwrap())
+        .unwrap();
+    assert_eq!(new_commit_e.parent_ids(), [new_commit_m.id().clone()]);
+    assert_eq!(mut_repo.rebase_descendants(&settings).unwrap(), 0);
+    assert_eq!(
+        *mut_repo.view().heads(),
+        hashset! {new_commit_e.id().clone()}
+    );
+}
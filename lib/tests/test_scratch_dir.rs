@@ -0,0 +1,90 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use jj_lib::local_working_copy::TreeState;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::settings::UserSettings;
+use testutils::{create_tree, load_repo_at_head, new_temp_dir, TestRepo};
+
+#[test]
+fn test_scratch_dir_configured() {
+    let temp_dir = new_temp_dir();
+    let scratch_path = temp_dir.path().join("scratch");
+    let config = testutils::base_config()
+        .set_override("core.scratch-dir", scratch_path.to_str().unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let repo = &test_repo.repo;
+    let scratch_dir = repo.settings().scratch_dir();
+    assert_eq!(scratch_dir.path(), scratch_path);
+
+    // Check out a conflicted tree in a scratch directory, as is done for diff
+    // editors
+    let file_path = RepoPath::from_internal_string("file");
+    let base_tree = create_tree(repo, &[(file_path, "base\n")]);
+    let side1_tree = create_tree(repo, &[(file_path, "side 1\n")]);
+    let side2_tree = create_tree(repo, &[(file_path, "side 2\n")]);
+    let merged_tree = side1_tree.merge(&base_tree, &side2_tree).unwrap();
+    assert!(merged_tree.has_conflict());
+    let checkout_dir = scratch_dir.new_dir("checkout-").unwrap();
+    assert!(checkout_dir.path().starts_with(&scratch_path));
+    let wc_dir = checkout_dir.path().join("wc");
+    let state_dir = checkout_dir.path().join("state");
+    fs::create_dir(&wc_dir).unwrap();
+    fs::create_dir(&state_dir).unwrap();
+    let mut tree_state = TreeState::init(repo.store().clone(), wc_dir.clone(), state_dir).unwrap();
+    tree_state.check_out(&merged_tree).unwrap();
+    let materialized = fs::read_to_string(file_path.to_fs_path(&wc_dir)).unwrap();
+    assert!(materialized.contains("<<<<<<<"));
+
+    // The scratch files are removed along with the scratch directory
+    drop(tree_state);
+    drop(checkout_dir);
+    assert_eq!(fs::read_dir(&scratch_path).unwrap().count(), 0);
+}
+
+#[test]
+fn test_scratch_dir_remove_stale() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_settings(&settings);
+    let repo_path = test_repo.repo.repo_path();
+    let scratch_path = test_repo.repo.settings().scratch_dir().path().to_owned();
+    assert_eq!(scratch_path, repo_path.with_file_name("tmp"));
+
+    fs::create_dir_all(&scratch_path).unwrap();
+    let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+    let stale_path = scratch_path.join("jj-stale");
+    let stale_file = fs::File::create(&stale_path).unwrap();
+    stale_file.set_modified(two_days_ago).unwrap();
+    drop(stale_file);
+    let fresh_path = scratch_path.join("jj-fresh");
+    fs::File::create(&fresh_path).unwrap();
+    // Files that weren't created by us are left alone, however old
+    let foreign_path = scratch_path.join("foreign");
+    let foreign_file = fs::File::create(&foreign_path).unwrap();
+    foreign_file.set_modified(two_days_ago).unwrap();
+    drop(foreign_file);
+
+    load_repo_at_head(&settings, repo_path);
+    assert!(!stale_path.exists());
+    assert!(fresh_path.exists());
+    assert!(foreign_path.exists());
+}
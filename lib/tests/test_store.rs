@@ -0,0 +1,374 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::{fs, thread};
+
+use itertools::Itertools as _;
+use jj_lib::backend::{CommitId, TreeValue};
+use jj_lib::object_fetch::{self, FetchStats};
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo::{ReadonlyRepo, Repo};
+use jj_lib::repo_path::RepoPath;
+use jj_lib::settings::UserSettings;
+use jj_lib::signing::Signer;
+use jj_lib::store::{ObjectKind, Store};
+use test_case::test_case;
+use testutils::test_signing_backend::TestSigningBackend;
+use testutils::{
+    create_tree, write_random_commit, CommitGraphBuilder, TestRepo, TestRepoBackend, TestWorkspace,
+};
+
+/// Returns the path of the file storing the object on disk.
+fn object_path(repo: &ReadonlyRepo, backend: TestRepoBackend, id: &impl ObjectId) -> PathBuf {
+    let store_path = repo.repo_path().join("store");
+    let hex = id.hex();
+    match backend {
+        TestRepoBackend::Git => store_path
+            .join("git")
+            .join("objects")
+            .join(&hex[..2])
+            .join(&hex[2..]),
+        TestRepoBackend::Local => store_path.join(format!("{}s", id.object_type())).join(hex),
+        TestRepoBackend::Test => panic!("the test backend doesn't store objects on disk"),
+    }
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_fsck_healthy(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
+    graph_builder.commit_with_parents(&[&commit_b, &commit_c]);
+    let repo = tx.commit("test");
+
+    let heads = repo.view().heads().iter().cloned().collect_vec();
+    let report = repo.store().fsck(&heads);
+    assert!(report.is_ok(), "{report:?}");
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_object_kind(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let path = RepoPath::from_internal_string("dir/file");
+    let tree = create_tree(repo, &[(path, "contents")]);
+    let mut tx = repo.start_transaction(&settings);
+    let commit = tx
+        .mut_repo()
+        .new_commit(&settings, vec![store.root_commit_id().clone()], tree.id())
+        .write()
+        .unwrap();
+    tx.commit("test");
+    let Some(TreeValue::File { id: file_id, .. }) =
+        tree.path_value(path).unwrap().into_resolved().unwrap()
+    else {
+        panic!("expected a file at {path:?}");
+    };
+    let tree_id = tree.id().to_merge().into_resolved().unwrap();
+
+    assert_eq!(
+        store.object_kind(commit.id().as_bytes()).unwrap(),
+        Some(ObjectKind::Commit)
+    );
+    assert_eq!(
+        store
+            .object_kind(store.root_commit_id().as_bytes())
+            .unwrap(),
+        Some(ObjectKind::Commit)
+    );
+    assert_eq!(
+        store.object_kind(tree_id.as_bytes()).unwrap(),
+        Some(ObjectKind::Tree)
+    );
+    assert_eq!(
+        store.object_kind(file_id.as_bytes()).unwrap(),
+        Some(ObjectKind::File)
+    );
+    let unknown_id = vec![0xab; store.commit_id_length()];
+    assert_eq!(store.object_kind(&unknown_id).unwrap(), None);
+    assert_eq!(store.object_kind(b"short").unwrap(), None);
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_object_kind_corrupt(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    tx.commit("test");
+
+    // A corrupt object isn't reported as missing
+    let path = object_path(repo, backend, commit.id());
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, b"corrupt").unwrap();
+    assert!(store.object_kind(commit.id().as_bytes()).is_err());
+}
+
+#[test_case(TestRepoBackend::Local ; "local backend")]
+#[test_case(TestRepoBackend::Git ; "git backend")]
+fn test_fsck_dangling(backend: TestRepoBackend) {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(backend);
+    let repo = &test_repo.repo;
+
+    let dir_path = RepoPath::from_internal_string("dir");
+    let file_path = RepoPath::from_internal_string("dir/file");
+    let tree = create_tree(repo, &[(file_path, "contents")]);
+    let mut tx = repo.start_transaction(&settings);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let commit_a = tx
+        .mut_repo()
+        .new_commit(&settings, vec![root_commit_id], tree.id())
+        .write()
+        .unwrap();
+    let commit_b = tx
+        .mut_repo()
+        .new_commit(&settings, vec![commit_a.id().clone()], tree.id())
+        .write()
+        .unwrap();
+    let repo = tx.commit("test");
+
+    // Remove a parent commit and a subtree from the backend's storage
+    let subtree_id = match tree.path_value(dir_path).unwrap().into_resolved() {
+        Ok(Some(TreeValue::Tree(id))) => id,
+        value => panic!("unexpected value: {value:?}"),
+    };
+    fs::remove_file(object_path(&repo, backend, commit_a.id())).unwrap();
+    fs::remove_file(object_path(&repo, backend, &subtree_id)).unwrap();
+
+    let report = repo.store().fsck(&[commit_b.id().clone()]);
+    assert!(!report.is_ok());
+    assert_eq!(
+        report.dangling_parents,
+        vec![(commit_b.id().clone(), commit_a.id().clone())]
+    );
+    assert_eq!(
+        report.dangling_trees,
+        vec![(commit_b.id().clone(), dir_path.to_owned(), subtree_id)]
+    );
+    assert!(report.dangling_conflicts.is_empty());
+    assert!(report.unreadable.is_empty(), "{:?}", report.unreadable);
+}
+
+/// One direction of an in-memory connection between two threads.
+fn channel_pipe() -> (ChannelWriter, ChannelReader) {
+    let (sender, receiver) = mpsc::channel();
+    let reader = ChannelReader {
+        receiver,
+        buf: vec![],
+        pos: 0,
+    };
+    (ChannelWriter(sender), reader)
+}
+
+struct ChannelWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct ChannelReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            match self.receiver.recv() {
+                Ok(data) => {
+                    self.buf = data;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Fetches `want` from `server` into `client`, returning what the client
+/// received and what the server sent.
+fn fetch(
+    client: &Arc<Store>,
+    server: &Arc<ReadonlyRepo>,
+    have: &[CommitId],
+    want: &[CommitId],
+) -> (FetchStats, FetchStats) {
+    let (mut request_writer, mut request_reader) = channel_pipe();
+    let (mut response_writer, mut response_reader) = channel_pipe();
+    thread::scope(|scope| {
+        let server_thread = scope.spawn(move || {
+            object_fetch::serve_fetch(server.as_ref(), &mut request_reader, &mut response_writer)
+        });
+        let received = object_fetch::fetch_missing(
+            client,
+            have,
+            want,
+            &mut response_reader,
+            &mut request_writer,
+        )
+        .unwrap();
+        (received, server_thread.join().unwrap().unwrap())
+    })
+}
+
+#[test]
+fn test_fetch_missing() {
+    let settings = testutils::user_settings();
+    let server_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let client_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let server_store = server_repo.repo.store();
+    let client_store = client_repo.repo.store();
+
+    let a_path = RepoPath::from_internal_string("a");
+    let b_path = RepoPath::from_internal_string("b");
+    let c_path = RepoPath::from_internal_string("dir/c");
+    let tree1 = create_tree(&server_repo.repo, &[(a_path, "a")]);
+    let tree2 = create_tree(&server_repo.repo, &[(a_path, "a"), (b_path, "b")]);
+    let tree3 = create_tree(
+        &server_repo.repo,
+        &[(a_path, "a"), (b_path, "b2"), (c_path, "c")],
+    );
+    let mut tx = server_repo.repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let root_id = server_store.root_commit_id().clone();
+    let commit1 = mut_repo
+        .new_commit(&settings, vec![root_id], tree1.id())
+        .write()
+        .unwrap();
+    let commit2 = mut_repo
+        .new_commit(&settings, vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    let commit3 = mut_repo
+        .new_commit(&settings, vec![commit2.id().clone()], tree3.id())
+        .write()
+        .unwrap();
+    let server = tx.commit("test");
+
+    // The client gets the first commit
+    let (received, sent) = fetch(client_store, &server, &[], &[commit1.id().clone()]);
+    let expected = FetchStats {
+        commits: 1,
+        trees: 1,
+        files: 1,
+        ..Default::default()
+    };
+    assert_eq!(received, expected);
+    assert_eq!(sent, expected);
+
+    // Then only the objects it doesn't have yet. The file at `a` is unchanged.
+    let (received, sent) = fetch(
+        client_store,
+        &server,
+        &[commit1.id().clone()],
+        &[commit3.id().clone()],
+    );
+    let expected = FetchStats {
+        commits: 2,
+        trees: 3,
+        files: 3,
+        ..Default::default()
+    };
+    assert_eq!(received, expected);
+    assert_eq!(sent, expected);
+
+    let fetched = client_store.get_commit(commit3.id()).unwrap();
+    assert_eq!(fetched.parent_ids(), [commit2.id().clone()]);
+    assert_eq!(fetched.tree_id(), commit3.tree_id());
+    assert_eq!(
+        fetched
+            .tree()
+            .unwrap()
+            .entries()
+            .map(|(path, value)| (path, value.unwrap()))
+            .collect_vec(),
+        tree3
+            .entries()
+            .map(|(path, value)| (path, value.unwrap()))
+            .collect_vec()
+    );
+
+    // Nothing is sent if the client has everything
+    let (received, _) = fetch(
+        client_store,
+        &server,
+        &[commit3.id().clone()],
+        &[commit2.id().clone()],
+    );
+    assert_eq!(received, FetchStats::default());
+}
+
+#[test]
+fn test_fetch_missing_signed() {
+    let config = testutils::base_config()
+        .set_override("signing.key", "impeccable")
+        .unwrap()
+        .set_override("signing.sign-all", true)
+        .unwrap()
+        .build()
+        .unwrap();
+    let settings = UserSettings::from_config(config);
+    let signer = Signer::new(Some(Box::new(TestSigningBackend)), vec![]);
+    let server_workspace =
+        TestWorkspace::init_with_backend_and_signer(&settings, TestRepoBackend::Local, signer);
+    let client_repo = TestRepo::init_with_backend(TestRepoBackend::Local);
+    let client_store = client_repo.repo.store();
+
+    let mut tx = server_workspace.repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let server = tx.commit("test");
+    assert!(commit.is_signed());
+
+    // The commit is written with its signature, and so with the same id
+    let (received, _) = fetch(client_store, &server, &[], &[commit.id().clone()]);
+    assert_eq!(received.commits, 1);
+    let fetched = client_store.get_commit(commit.id()).unwrap();
+    assert_eq!(
+        fetched.store_commit().secure_sig,
+        commit.store_commit().secure_sig
+    );
+}
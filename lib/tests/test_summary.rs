@@ -0,0 +1,153 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::File;
+use std::time::{Duration, SystemTime};
+
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::{RefTarget, WorkspaceId};
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::summary::{self, DirtyState, QuickSummary, QuickSummaryOptions};
+use jj_lib::workspace::default_working_copy_factories;
+use testutils::test_backend::TestBackend;
+use testutils::{
+    create_tree, write_random_commit, write_working_copy_file, TestRepo, TestWorkspace,
+};
+
+fn quick_summary(test_workspace: &TestWorkspace) -> QuickSummary {
+    summary::quick(
+        &testutils::user_settings(),
+        test_workspace.workspace.workspace_root(),
+        &TestRepo::default_store_factories(),
+        &default_working_copy_factories(),
+        &QuickSummaryOptions::default(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_quick_summary() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let workspace_id = WorkspaceId::default();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let path = RepoPath::from_internal_string("file");
+    let tree = create_tree(repo, &[(path, "contents")]);
+    let mut tx = repo.start_transaction(&settings);
+    let commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree.id(),
+        )
+        .write()
+        .unwrap();
+    tx.mut_repo().edit(workspace_id.clone(), &commit).unwrap();
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit.id().clone()));
+    // Other heads don't add to the cost
+    for _ in 0..10 {
+        write_random_commit(tx.mut_repo(), &settings);
+    }
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+    test_workspace
+        .workspace
+        .check_out(repo.op_id().clone(), None, &commit)
+        .unwrap();
+
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let num_commit_reads = test_backend.num_commit_reads();
+    let num_file_reads = test_backend.num_file_reads();
+    let summary = quick_summary(&test_workspace);
+    // Nothing but the working-copy commit is read
+    assert_eq!(test_backend.num_file_reads(), num_file_reads);
+    assert_eq!(test_backend.num_commit_reads(), num_commit_reads + 1);
+
+    assert_eq!(summary.operation_id, *repo.op_id());
+    assert_eq!(summary.workspace_id, workspace_id);
+    assert_eq!(summary.commit_id, *commit.id());
+    assert_eq!(summary.change_id, *commit.change_id());
+    assert!(commit.id().hex().starts_with(&summary.short_commit_id));
+    assert!(!summary.short_commit_id.is_empty());
+    assert!(!summary.short_change_id.is_empty());
+    assert_eq!(summary.branches, ["main"]);
+    assert_eq!(summary.orphans, 0);
+    assert_eq!(summary.divergent_changes, 0);
+
+    // Files modified after the working-copy state was written are clean
+    let tree_state_file = File::options()
+        .write(true)
+        .open(workspace_root.join(".jj/working_copy/tree_state"))
+        .unwrap();
+    tree_state_file
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(quick_summary(&test_workspace).dirty, DirtyState::Clean);
+    // Files modified at the same time or later may have changed again within
+    // the mtime granularity
+    tree_state_file
+        .set_modified(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    assert_eq!(quick_summary(&test_workspace).dirty, DirtyState::Unknown);
+
+    // A modified file is noticed without snapshotting
+    write_working_copy_file(&workspace_root, path, "modified contents");
+    let summary = quick_summary(&test_workspace);
+    assert_eq!(summary.dirty, DirtyState::Dirty(1));
+    assert_eq!(test_backend.num_file_reads(), num_file_reads);
+}
+
+#[test]
+fn test_quick_summary_evolution() {
+    let settings = testutils::user_settings();
+    let test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+
+    // B
+    // A A'
+    //
+    // A' was rewritten from A, but A is still visible, making B an orphan.
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    mut_repo
+        .new_commit(
+            &settings,
+            vec![commit_a.id().clone()],
+            commit_a.tree_id().clone(),
+        )
+        .write()
+        .unwrap();
+    mut_repo
+        .new_commit(
+            &settings,
+            commit_a.parent_ids().to_vec(),
+            commit_a.tree_id().clone(),
+        )
+        .set_change_id(commit_a.change_id().clone())
+        .set_predecessors(vec![commit_a.id().clone()])
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.commit("test");
+
+    let summary = quick_summary(&test_workspace);
+    assert_eq!(summary.divergent_changes, 1);
+    assert_eq!(summary.orphans, 1);
+}
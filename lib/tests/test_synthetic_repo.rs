@@ -0,0 +1,121 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use itertools::Itertools as _;
+use jj_lib::backend::TreeValue;
+use jj_lib::commit::Commit;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::op_store::WorkspaceId;
+use jj_lib::repo::Repo;
+use jj_lib::revset::RevsetExpression;
+use maplit::hashset;
+use test_case::test_case;
+use testutils::{read_file, synthetic_repo, SyntheticRepoSpec};
+
+#[test_case(0, 3, 10; "flat")]
+#[test_case(2, 3, 0; "nested, empty files")]
+#[test_case(3, 2, 100; "nested")]
+fn test_synthetic_repo_shape(tree_depth: usize, tree_width: usize, file_size: usize) {
+    let spec = SyntheticRepoSpec {
+        seed: 0,
+        n_commits: 5,
+        tree_width,
+        tree_depth,
+        file_size,
+        files_changed_per_commit: 2,
+    };
+    let synthetic = synthetic_repo(&spec);
+    let repo = &synthetic.test_workspace.repo;
+    let expected_file_count = tree_width.pow(tree_depth as u32 + 1);
+    assert_eq!(synthetic.file_paths.len(), expected_file_count);
+
+    // The commits form a linear history on top of the root commit
+    assert_eq!(synthetic.commits.len(), 5);
+    let ancestors = RevsetExpression::commit(synthetic.commits[4].id().clone())
+        .ancestors()
+        .evaluate_programmatic(repo.as_ref())
+        .unwrap()
+        .iter()
+        .collect_vec();
+    let mut expected_ancestors = synthetic
+        .commits
+        .iter()
+        .rev()
+        .map(|commit| commit.id().clone())
+        .collect_vec();
+    expected_ancestors.push(repo.store().root_commit_id().clone());
+    assert_eq!(ancestors, expected_ancestors);
+    // The working-copy commit is still on top of the root commit
+    let wc_commit_id = repo
+        .view()
+        .get_wc_commit_id(&WorkspaceId::default())
+        .unwrap();
+    assert_eq!(
+        *repo.view().heads(),
+        hashset! {synthetic.commits[4].id().clone(), wc_commit_id.clone()}
+    );
+
+    // Every tree has all the files, with the requested size
+    for commit in &synthetic.commits {
+        let tree = commit.tree().unwrap();
+        let entries = tree
+            .entries()
+            .map(|(path, value)| (path, value.unwrap()))
+            .collect_vec();
+        let paths = entries.iter().map(|(path, _)| path.clone()).collect_vec();
+        assert_eq!(paths, synthetic.file_paths);
+        for (path, value) in &entries {
+            let Some(Some(TreeValue::File { id, .. })) = value.as_resolved() else {
+                panic!("unexpected value at {path:?}: {value:?}");
+            };
+            assert_eq!(read_file(repo.store(), path, id).len(), file_size);
+        }
+    }
+
+    // Each commit after the first changes the requested number of files, unless
+    // the files are empty and so can't change
+    for (parent, child) in synthetic.commits.iter().tuple_windows() {
+        let changed_count = parent
+            .tree()
+            .unwrap()
+            .diff(&child.tree().unwrap(), &EverythingMatcher)
+            .count();
+        if file_size == 0 {
+            assert_eq!(changed_count, 0);
+        } else {
+            assert_eq!(changed_count, 2);
+        }
+    }
+}
+
+#[test]
+fn test_synthetic_repo_deterministic() {
+    let spec = SyntheticRepoSpec {
+        seed: 42,
+        n_commits: 3,
+        tree_width: 2,
+        tree_depth: 1,
+        file_size: 50,
+        files_changed_per_commit: 1,
+    };
+    let synthetic1 = synthetic_repo(&spec);
+    let synthetic2 = synthetic_repo(&spec);
+    let tree_ids = |commits: &[Commit]| {
+        commits
+            .iter()
+            .map(|commit| commit.tree_id().clone())
+            .collect_vec()
+    };
+    assert_eq!(tree_ids(&synthetic1.commits), tree_ids(&synthetic2.commits));
+}
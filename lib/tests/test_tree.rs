@@ -0,0 +1,112 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use itertools::Itertools as _;
+use jj_lib::matchers::{EverythingMatcher, FilesMatcher, Matcher, NothingMatcher, PrefixMatcher};
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use testutils::test_backend::TestBackend;
+use testutils::{create_single_tree, load_repo_at_head, TestRepo};
+
+#[test]
+fn test_entries_matching_prunes_subtrees() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let tree = create_single_tree(
+        repo,
+        &[
+            (RepoPath::from_internal_string("a/x"), "contents"),
+            (RepoPath::from_internal_string("b/file"), "contents"),
+            (RepoPath::from_internal_string("b/d/z"), "contents"),
+            (RepoPath::from_internal_string("b/c/y"), "contents"),
+            (RepoPath::from_internal_string("c/w"), "contents"),
+            (RepoPath::from_internal_string("top"), "contents"),
+        ],
+    );
+
+    // Load the repo again so nothing is cached in the store
+    let repo = load_repo_at_head(&settings, repo.repo_path());
+    let store = repo.store();
+    let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+    let num_tree_reads = test_backend.num_tree_reads();
+    let tree = store.get_tree(RepoPath::root(), tree.id()).unwrap();
+
+    let matcher = PrefixMatcher::new([RepoPath::from_internal_string("b")]);
+    let mut entries = tree.entries_matching(&matcher);
+    // The subtrees are only read as the iteration reaches them
+    assert_eq!(test_backend.num_tree_reads(), num_tree_reads + 1);
+    let (first_path, _) = entries.next().unwrap();
+    assert_eq!(first_path.as_internal_file_string(), "b/c/y");
+    assert_eq!(test_backend.num_tree_reads(), num_tree_reads + 3);
+
+    let paths = [first_path]
+        .into_iter()
+        .chain(entries.map(|(path, _)| path))
+        .map(|path| path.as_internal_file_string().to_owned())
+        .collect_vec();
+    assert_eq!(paths, ["b/c/y", "b/d/z", "b/file"]);
+    // "a" and "c" were never read
+    assert_eq!(test_backend.num_tree_reads(), num_tree_reads + 4);
+}
+
+#[test]
+fn test_entries_matching_same_as_filtered_entries() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let tree = create_single_tree(
+        repo,
+        &[
+            (RepoPath::from_internal_string("a"), "contents"),
+            (RepoPath::from_internal_string("a.txt"), "contents"),
+            (RepoPath::from_internal_string("b/a"), "contents"),
+            (RepoPath::from_internal_string("b/b/a"), "contents"),
+            (RepoPath::from_internal_string("b/b/b/a"), "contents"),
+            (RepoPath::from_internal_string("b/c"), "contents"),
+            (RepoPath::from_internal_string("b_c/a"), "contents"),
+            (RepoPath::from_internal_string("c/a/b"), "contents"),
+        ],
+    );
+
+    // Every entry is listed once, in sorted path order
+    let all_paths = tree.entries().map(|(path, _)| path).collect_vec();
+    assert!(all_paths.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(all_paths.len(), 8);
+
+    let matchers: Vec<Box<dyn Matcher>> = vec![
+        Box::new(EverythingMatcher),
+        Box::new(NothingMatcher),
+        Box::new(PrefixMatcher::new([RepoPath::from_internal_string("b/b")])),
+        Box::new(PrefixMatcher::new([
+            RepoPath::from_internal_string("a"),
+            RepoPath::from_internal_string("c"),
+        ])),
+        Box::new(FilesMatcher::new([
+            RepoPath::from_internal_string("a.txt"),
+            RepoPath::from_internal_string("b/b/b/a"),
+            RepoPath::from_internal_string("b/b/b"),
+            RepoPath::from_internal_string("missing/a"),
+        ])),
+    ];
+    for matcher in &matchers {
+        let expected = tree
+            .entries()
+            .filter(|(path, _)| matcher.matches(path))
+            .collect_vec();
+        let actual = tree.entries_matching(matcher.as_ref()).collect_vec();
+        assert_eq!(actual, expected, "matcher: {matcher:?}");
+    }
+}
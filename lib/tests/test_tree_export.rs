@@ -0,0 +1,181 @@
+// Copyright 2024 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use assert_matches::assert_matches;
+use jj_lib::backend::MergedTreeId;
+use jj_lib::file_util::check_symlink_support;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::tree::{export_to_dir, ExportConflicts, ExportError, ExportOptions, ExportStats};
+use testutils::{commit_with_tree, create_tree, TestRepo, TestWorkspace};
+
+/// Lists the files and symlinks under `dir`, skipping `.jj`, with their
+/// contents or targets and whether they're executable.
+fn dir_contents(dir: &Path) -> Vec<(PathBuf, String, bool)> {
+    let mut entries = vec![];
+    let mut pending = vec![dir.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap() == ".jj" {
+                continue;
+            }
+            let metadata = path.symlink_metadata().unwrap();
+            let relative = path.strip_prefix(dir).unwrap().to_owned();
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_symlink() {
+                let target = fs::read_link(&path).unwrap();
+                entries.push((relative, target.to_str().unwrap().to_owned(), false));
+            } else {
+                #[cfg(unix)]
+                let executable = metadata.permissions().mode() & 0o111 != 0;
+                #[cfg(windows)]
+                let executable = false;
+                entries.push((relative, fs::read_to_string(&path).unwrap(), executable));
+            }
+        }
+    }
+    entries.sort();
+    entries
+}
+
+#[test]
+fn test_export_to_dir_matches_checkout() {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let store = repo.store().clone();
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+
+    let mut tree_builder = store.tree_builder(store.empty_tree_id().clone());
+    testutils::write_executable_file(
+        &mut tree_builder,
+        RepoPath::from_internal_string("bin/run"),
+        "#!/bin/sh\n",
+    );
+    testutils::write_normal_file(
+        &mut tree_builder,
+        RepoPath::from_internal_string("dir/sub/normal"),
+        "contents",
+    );
+    testutils::write_symlink(
+        &mut tree_builder,
+        RepoPath::from_internal_string("dir/link"),
+        "sub/normal",
+    );
+    let tree_id = MergedTreeId::resolved(tree_builder.write_tree().unwrap());
+    let commit = commit_with_tree(&store, tree_id);
+    test_workspace
+        .workspace
+        .check_out(repo.op_id().clone(), None, &commit)
+        .unwrap();
+
+    let temp_dir = testutils::new_temp_dir();
+    let dest = temp_dir.path().join("export");
+    let stats = export_to_dir(&commit.tree().unwrap(), &dest, &ExportOptions::default()).unwrap();
+    assert_eq!(
+        stats,
+        ExportStats {
+            files: 2,
+            symlinks: 1,
+            conflicts: 0,
+            skipped_submodules: 0,
+        }
+    );
+    assert_eq!(dir_contents(&dest), dir_contents(&workspace_root));
+    if check_symlink_support().unwrap() {
+        assert!(dest.join("dir").join("link").is_symlink());
+    }
+    #[cfg(unix)]
+    assert_eq!(
+        dir_contents(&dest)[0],
+        (PathBuf::from("bin/run"), "#!/bin/sh\n".to_owned(), true)
+    );
+}
+
+#[test]
+fn test_export_to_dir_non_empty_dest() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let path = RepoPath::from_internal_string("file");
+    let tree = create_tree(repo, &[(path, "new\n")]);
+    let temp_dir = testutils::new_temp_dir();
+    let dest = temp_dir.path();
+    fs::write(dest.join("file"), "old\n").unwrap();
+    fs::write(dest.join("other"), "other\n").unwrap();
+
+    // Refuses to write into a non-empty directory by default
+    assert_matches!(
+        export_to_dir(&tree, dest, &ExportOptions::default()),
+        Err(ExportError::DestinationNotEmpty(path)) if path == dest
+    );
+    assert_eq!(fs::read_to_string(dest.join("file")).unwrap(), "old\n");
+
+    // Replaces the paths in the tree when overwriting, and keeps the rest
+    let options = ExportOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+    export_to_dir(&tree, dest, &options).unwrap();
+    assert_eq!(fs::read_to_string(dest.join("file")).unwrap(), "new\n");
+    assert_eq!(fs::read_to_string(dest.join("other")).unwrap(), "other\n");
+}
+
+#[test]
+fn test_export_to_dir_conflicts() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let conflict_path = RepoPath::from_internal_string("conflict");
+    let clean_path = RepoPath::from_internal_string("clean");
+    let base_tree = create_tree(repo, &[(conflict_path, "base\n"), (clean_path, "clean\n")]);
+    let side1_tree = create_tree(repo, &[(conflict_path, "side1\n"), (clean_path, "clean\n")]);
+    let side2_tree = create_tree(repo, &[(conflict_path, "side2\n"), (clean_path, "clean\n")]);
+    let merged_tree = side1_tree.merge(&base_tree, &side2_tree).unwrap();
+
+    // Materialized with conflict markers
+    let temp_dir = testutils::new_temp_dir();
+    let dest = temp_dir.path().join("materialize");
+    let stats = export_to_dir(&merged_tree, &dest, &ExportOptions::default()).unwrap();
+    assert_eq!(stats.files, 1);
+    assert_eq!(stats.conflicts, 1);
+    insta::assert_snapshot!(fs::read_to_string(dest.join("conflict")).unwrap(), @r###"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -base
+    +side1
+    +++++++ Contents of side #2
+    side2
+    >>>>>>> Conflict 1 of 1 ends
+    "###);
+
+    // Or reported as an error
+    let dest = temp_dir.path().join("fail");
+    let options = ExportOptions {
+        conflicts: ExportConflicts::Fail,
+        ..Default::default()
+    };
+    assert_matches!(
+        export_to_dir(&merged_tree, &dest, &options),
+        Err(ExportError::Conflict(path)) if path.as_ref() == conflict_path
+    );
+    assert!(!dest.join("conflict").exists());
+}
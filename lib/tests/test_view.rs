@@ -14,8 +14,10 @@
 
 use std::collections::BTreeMap;
 
+use itertools::Itertools as _;
+use jj_lib::backend::{MillisSinceEpoch, Signature, Timestamp};
 use jj_lib::op_store::{BranchTarget, RefTarget, RemoteRef, RemoteRefState, WorkspaceId};
-use jj_lib::repo::Repo;
+use jj_lib::repo::{Refs, Repo};
 use maplit::{btreemap, hashset};
 use test_case::test_case;
 use testutils::{
@@ -72,6 +74,140 @@ fn test_heads_merge() {
     assert_eq!(*repo.view().heads(), hashset! {merge.id().clone()});
 }
 
+#[test]
+fn test_sorted_heads() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+
+    let mut create_commit = |millis: i64| {
+        let signature = Signature {
+            name: "Some One".to_string(),
+            email: "some.one@example.com".to_string(),
+            timestamp: Timestamp {
+                timestamp: MillisSinceEpoch(millis),
+                tz_offset: 0,
+            },
+        };
+        create_random_commit(tx.mut_repo(), &settings)
+            .set_committer(signature)
+            .write()
+            .unwrap()
+    };
+    let commit1 = create_commit(2000);
+    let commit2 = create_commit(1000);
+    let commit3 = create_commit(2000);
+    let commit4 = create_commit(3000);
+    let repo = tx.commit("test");
+
+    let ids = [&commit1, &commit2, &commit3, &commit4].map(|commit| commit.id().clone());
+    let expected_sorted = ids.iter().cloned().sorted().collect_vec();
+    // Committer timestamps don't matter, the heads are sorted by index position
+    let expected_topo_sorted = ids.iter().rev().cloned().collect_vec();
+
+    // The order doesn't change between calls or when the repo is reloaded
+    let reloaded_repo = testutils::load_repo_at_head(&settings, repo.repo_path());
+    for repo in [&repo, &reloaded_repo, &repo] {
+        assert_eq!(repo.view().sorted_heads(), expected_sorted);
+        assert_eq!(repo.topo_sorted_heads().unwrap(), expected_topo_sorted);
+    }
+}
+
+#[test]
+fn test_guess_trunk_branch() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.initial_commit();
+    tx.mut_repo()
+        .set_local_branch_target("feature", RefTarget::normal(commit_c.id().clone()));
+    tx.mut_repo()
+        .set_local_branch_target("main", RefTarget::normal(commit_d.id().clone()));
+    let repo = tx.commit("test");
+
+    // The main branch is chosen even though it isn't the biggest head
+    assert_eq!(repo.guess_trunk().unwrap(), Some(commit_d.id().clone()));
+}
+
+#[test]
+fn test_guess_trunk_biggest_head() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    assert_eq!(
+        repo.guess_trunk().unwrap(),
+        Some(repo.store().root_commit_id().clone())
+    );
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut graph_builder = CommitGraphBuilder::new(&settings, tx.mut_repo());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    graph_builder.commit_with_parents(&[&commit_a]);
+    graph_builder.initial_commit();
+    let repo = tx.commit("test");
+
+    assert_eq!(repo.guess_trunk().unwrap(), Some(commit_c.id().clone()));
+}
+
+#[test]
+fn test_refs_pointing_at() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = write_random_commit(mut_repo, &settings);
+    let root_commit_id = mut_repo.store().root_commit_id().clone();
+    mut_repo.set_local_branch_target("main", RefTarget::normal(commit_a.id().clone()));
+    mut_repo.set_local_branch_target(
+        "conflicted",
+        RefTarget::from_legacy_form(
+            [root_commit_id],
+            [commit_a.id().clone(), commit_b.id().clone()],
+        ),
+    );
+    mut_repo.set_local_branch_target("other", RefTarget::normal(commit_b.id().clone()));
+    mut_repo.set_remote_branch(
+        "main",
+        "origin",
+        RemoteRef {
+            target: RefTarget::normal(commit_a.id().clone()),
+            state: RemoteRefState::Tracking,
+        },
+    );
+    mut_repo.set_tag_target("v1", RefTarget::normal(commit_a.id().clone()));
+    mut_repo.set_git_ref_target("refs/heads/main", RefTarget::normal(commit_a.id().clone()));
+    let repo = tx.commit("test");
+
+    assert_eq!(
+        repo.refs_pointing_at(commit_a.id()),
+        Refs {
+            branches: vec!["conflicted".to_owned(), "main".to_owned()],
+            remote_branches: vec![("main".to_owned(), "origin".to_owned())],
+            tags: vec!["v1".to_owned()],
+            git_refs: vec!["refs/heads/main".to_owned()],
+        }
+    );
+    assert_eq!(
+        repo.refs_pointing_at(commit_b.id()).branches,
+        vec!["conflicted".to_owned(), "other".to_owned()]
+    );
+    assert_eq!(
+        repo.refs_pointing_at(repo.store().root_commit_id()),
+        Refs::default()
+    );
+}
+
 #[test]
 fn test_merge_views_heads() {
     // Tests merging of the view's heads (by performing concurrent operations).
@@ -330,6 +466,127 @@ fn test_merge_views_tags() {
     );
 }
 
+#[test]
+fn test_merge_views_labels() {
+    // Tests merging of labels (by performing concurrent operations)
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = write_random_commit(mut_repo, &settings);
+    let commit_c = write_random_commit(mut_repo, &settings);
+    mut_repo.add_label(commit_a.id(), "topic").unwrap();
+    mut_repo.add_label(commit_b.id(), "topic").unwrap();
+    let repo = tx.commit("test");
+
+    let mut tx1 = repo.start_transaction(&settings);
+    tx1.mut_repo().remove_label(commit_a.id(), "topic").unwrap();
+    tx1.mut_repo().add_label(commit_c.id(), "other").unwrap();
+
+    let mut tx2 = repo.start_transaction(&settings);
+    tx2.mut_repo().add_label(commit_c.id(), "topic").unwrap();
+
+    let repo = commit_transactions(&settings, vec![tx1, tx2]);
+    let mut expected_topic = vec![commit_b.change_id().clone(), commit_c.change_id().clone()];
+    expected_topic.sort();
+    assert_eq!(
+        repo.view().labels(),
+        btreemap! {
+            "other".to_string() => vec![commit_c.change_id().clone()],
+            "topic".to_string() => expected_topic,
+        }
+    );
+}
+
+#[test]
+fn test_merge_views_change_metadata() {
+    // Tests merging of change metadata (by performing concurrent operations)
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut_repo = tx.mut_repo();
+    let commit_a = write_random_commit(mut_repo, &settings);
+    let commit_b = write_random_commit(mut_repo, &settings);
+    let change_a = commit_a.change_id();
+    let change_b = commit_b.change_id();
+    mut_repo.set_change_metadata(change_a, "url", "a1").unwrap();
+    mut_repo
+        .set_change_metadata(change_a, "state", "draft")
+        .unwrap();
+    mut_repo.set_change_metadata(change_b, "url", "b1").unwrap();
+    let repo = tx.commit("test");
+
+    let mut tx1 = repo.start_transaction(&settings);
+    tx1.mut_repo()
+        .set_change_metadata(change_a, "state", "approved")
+        .unwrap();
+    tx1.mut_repo().remove_change_metadata(change_b, "url");
+
+    let mut tx2 = repo.start_transaction(&settings);
+    tx2.mut_repo()
+        .set_change_metadata(change_a, "url", "a2")
+        .unwrap();
+    tx2.mut_repo()
+        .set_change_metadata(change_b, "state", "draft")
+        .unwrap();
+
+    let repo = commit_transactions(&settings, vec![tx1, tx2]);
+    let values = |change_id| {
+        repo.view()
+            .get_change_metadata(change_id)
+            .map(|metadata| metadata.values.clone())
+    };
+    assert_eq!(
+        values(change_a),
+        Some(btreemap! {
+            "state".to_string() => "approved".to_string(),
+            "url".to_string() => "a2".to_string(),
+        })
+    );
+    assert_eq!(
+        values(change_b),
+        Some(btreemap! {"state".to_string() => "draft".to_string()})
+    );
+}
+
+#[test]
+fn test_merge_views_change_metadata_retention() {
+    // Tests that merging keeps the count of operations without visible commits
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction(&settings);
+    let commit_a = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+    let mut tx = repo.start_transaction(&settings);
+    tx.mut_repo().record_abandoned_commit(commit_a.id().clone());
+    tx.mut_repo().rebase_descendants(&settings).unwrap();
+    let repo = tx.commit("test");
+
+    let mut tx1 = repo.start_transaction(&settings);
+    write_random_commit(tx1.mut_repo(), &settings);
+
+    let mut tx2 = repo.start_transaction(&settings);
+    tx2.mut_repo()
+        .set_change_metadata(commit_a.change_id(), "url", "a1")
+        .unwrap();
+
+    let repo = commit_transactions(&settings, vec![tx1, tx2]);
+    // One operation counted by tx2, and one by the merge
+    assert_eq!(
+        repo.view()
+            .get_change_metadata(commit_a.change_id())
+            .map(|metadata| metadata.operations_without_commits),
+        Some(2)
+    );
+}
+
 #[test]
 fn test_merge_views_git_refs() {
     // Tests merging of git refs (by performing concurrent operations). See
@@ -12,15 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::thread;
+use std::{fs, thread};
 
 use assert_matches::assert_matches;
+use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::WorkspaceId;
-use jj_lib::repo::Repo;
+use jj_lib::op_walk;
+use jj_lib::repo::{ReadonlyRepo, Repo, RepoCloneOptions};
+use jj_lib::repo_path::RepoPath;
 use jj_lib::workspace::{
     default_working_copy_factories, default_working_copy_factory, Workspace, WorkspaceLoadError,
 };
-use testutils::{TestRepo, TestWorkspace};
+use test_case::test_case;
+use testutils::{
+    write_random_commit, write_working_copy_file, TestRepo, TestRepoBackend, TestWorkspace,
+};
 
 #[test]
 fn test_load_bad_path() {
@@ -87,6 +93,146 @@ fn test_init_additional_workspace() {
     assert_eq!(same_workspace.workspace_root(), ws2.workspace_root());
 }
 
+#[test_case(false ; "copied store")]
+#[test_case(true ; "shared store")]
+fn test_clone_to(share_store: bool) {
+    let settings = testutils::user_settings();
+    let mut test_workspace = TestWorkspace::init_with_backend(&settings, TestRepoBackend::Local);
+    let workspace_root = test_workspace.workspace.workspace_root().clone();
+    let workspace_id = test_workspace.workspace.workspace_id().clone();
+
+    // Record a file in the working-copy commit, and create another commit in a
+    // second operation
+    let file_path = RepoPath::from_internal_string("file");
+    write_working_copy_file(&workspace_root, file_path, "contents");
+    let tree = test_workspace.snapshot().unwrap();
+    let repo = &test_workspace.repo;
+    let mut tx = repo.start_transaction(&settings);
+    let wc_commit = tx
+        .mut_repo()
+        .new_commit(
+            &settings,
+            vec![repo.store().root_commit_id().clone()],
+            tree.id(),
+        )
+        .write()
+        .unwrap();
+    tx.mut_repo()
+        .set_wc_commit(workspace_id.clone(), wc_commit.id().clone())
+        .unwrap();
+    let repo = tx.commit("test");
+    let mut tx = repo.start_transaction(&settings);
+    write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+    // A change that isn't snapshotted isn't copied
+    write_working_copy_file(
+        &workspace_root,
+        RepoPath::from_internal_string("dirty"),
+        "dirty",
+    );
+
+    let clone_root = test_workspace.root_dir().join("clone");
+    fs::create_dir(&clone_root).unwrap();
+    let (_clone_workspace, clone_repo) = test_workspace
+        .workspace
+        .clone_to(
+            &settings,
+            &repo,
+            &clone_root,
+            &RepoCloneOptions { share_store },
+            &TestRepo::default_store_factories(),
+            &*default_working_copy_factory(),
+        )
+        .unwrap();
+    assert_eq!(clone_repo.op_id(), repo.op_id());
+
+    let clone_workspace = Workspace::load(
+        &settings,
+        &clone_root,
+        &TestRepo::default_store_factories(),
+        &default_working_copy_factories(),
+    )
+    .unwrap();
+    let clone_repo = clone_workspace
+        .repo_loader()
+        .load_at_head(&settings)
+        .unwrap();
+    assert_eq!(clone_repo.op_id(), repo.op_id());
+    assert_eq!(clone_repo.view().heads(), repo.view().heads());
+    let num_ops =
+        |repo: &ReadonlyRepo| op_walk::walk_ancestors(&[repo.operation().clone()]).count();
+    assert_eq!(num_ops(&clone_repo), num_ops(&repo));
+    assert_eq!(
+        clone_repo.view().get_wc_commit_id(&workspace_id),
+        Some(wc_commit.id())
+    );
+    assert_eq!(
+        clone_workspace.working_copy().tree_id().unwrap(),
+        wc_commit.tree_id()
+    );
+    assert_eq!(fs::read(clone_root.join("file")).unwrap(), b"contents");
+    assert!(!clone_root.join("dirty").exists());
+    for head_id in repo.view().heads() {
+        clone_repo.store().get_commit(head_id).unwrap();
+    }
+
+    // With a shared store, the objects aren't duplicated
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+        let commit_path = clone_root
+            .join(".jj")
+            .join("repo")
+            .join("store")
+            .join("commits")
+            .join(wc_commit.id().hex());
+        let num_links = fs::metadata(commit_path).unwrap().nlink();
+        assert_eq!(num_links > 1, share_store);
+    }
+}
+
+#[test]
+fn test_clone_to_colocated() {
+    let settings = testutils::user_settings();
+    let temp_dir = testutils::new_temp_dir();
+    let workspace_root = temp_dir.path().join("repo");
+    fs::create_dir(&workspace_root).unwrap();
+    let (workspace, repo) = Workspace::init_colocated_git(&settings, &workspace_root).unwrap();
+    let mut tx = repo.start_transaction(&settings);
+    let commit = write_random_commit(tx.mut_repo(), &settings);
+    let repo = tx.commit("test");
+
+    // The Git repo is copied into the store of the clone instead of being
+    // resolved relative to it
+    let clone_root = temp_dir.path().join("clone");
+    fs::create_dir(&clone_root).unwrap();
+    let (_clone_workspace, clone_repo) = workspace
+        .clone_to(
+            &settings,
+            &repo,
+            &clone_root,
+            &RepoCloneOptions { share_store: false },
+            &TestRepo::default_store_factories(),
+            &*default_working_copy_factory(),
+        )
+        .unwrap();
+    let clone_store_path = clone_repo.repo_path().join("store");
+    assert_eq!(
+        fs::read_to_string(clone_store_path.join("git_target")).unwrap(),
+        "git"
+    );
+    assert!(clone_store_path.join("git").join("HEAD").exists());
+    assert_eq!(
+        clone_repo.store().get_commit(commit.id()).unwrap().id(),
+        commit.id()
+    );
+    // The original still uses its own Git repo
+    assert_eq!(
+        fs::read_to_string(repo.repo_path().join("store").join("git_target")).unwrap(),
+        "../../../.git"
+    );
+}
+
 /// Test cross-thread access to a workspace, which requires it to be Send
 #[test]
 fn test_sendable() {
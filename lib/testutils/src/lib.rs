@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::{Read, Write};
@@ -31,7 +31,7 @@ use jj_lib::local_backend::LocalBackend;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::object_id::ObjectId;
 use jj_lib::repo::{MutableRepo, ReadonlyRepo, Repo, RepoLoader, StoreFactories};
-use jj_lib::repo_path::{RepoPath, RepoPathBuf};
+use jj_lib::repo_path::{RepoPath, RepoPathBuf, RepoPathComponent};
 use jj_lib::secret_backend::SecretBackend;
 use jj_lib::settings::UserSettings;
 use jj_lib::signing::Signer;
@@ -41,6 +41,9 @@ use jj_lib::tree::Tree;
 use jj_lib::tree_builder::TreeBuilder;
 use jj_lib::working_copy::{SnapshotError, SnapshotOptions};
 use jj_lib::workspace::Workspace;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom as _;
+use rand::{Rng as _, SeedableRng as _};
 use tempfile::TempDir;
 
 use crate::test_backend::TestBackend;
@@ -121,7 +124,9 @@ impl TestRepoBackend {
     ) -> Result<Box<dyn Backend>, BackendInitError> {
         match self {
             TestRepoBackend::Git => Ok(Box::new(GitBackend::init_internal(settings, store_path)?)),
-            TestRepoBackend::Local => Ok(Box::new(LocalBackend::init(store_path))),
+            TestRepoBackend::Local => Ok(Box::new(LocalBackend::init_with_settings(
+                settings, store_path,
+            )?)),
             TestRepoBackend::Test => Ok(Box::new(TestBackend::init(store_path))),
         }
     }
@@ -366,6 +371,7 @@ pub fn commit_with_tree(store: &Arc<Store>, tree_id: MergedTreeId) -> Commit {
         root_tree: tree_id,
         change_id: ChangeId::from_hex("abcd"),
         description: "description".to_string(),
+        description_lossy: false,
         author: signature.clone(),
         committer: signature,
         secure_sig: None,
@@ -460,6 +466,252 @@ impl<'settings, 'repo> CommitGraphBuilder<'settings, 'repo> {
     }
 }
 
+/// A random commit graph built by [`arbitrary_dag()`], along with a model of
+/// the graph to check the repo's algorithms against.
+pub struct DagFixture {
+    /// The seed the graph was generated from. Include it in assertion messages
+    /// so failures can be reproduced.
+    pub seed: u64,
+    pub test_repo: TestRepo,
+    /// The transaction the commits were written in. Rewritten and abandoned
+    /// commits are recorded in it, but their descendants haven't been rebased.
+    pub tx: Transaction,
+    /// The commits written, in order. Parents are always written before their
+    /// children.
+    pub commits: Vec<Commit>,
+    /// The successors of rewritten commits. Abandoned commits have no
+    /// successors.
+    pub successors: HashMap<CommitId, Vec<CommitId>>,
+}
+
+impl DagFixture {
+    /// Commits that have been rewritten or abandoned.
+    pub fn reference_obsolete_set(&self) -> HashSet<CommitId> {
+        self.successors.keys().cloned().collect()
+    }
+
+    /// Commits that aren't obsolete but have an obsolete ancestor, and so need
+    /// to be rebased.
+    pub fn reference_orphans(&self) -> HashSet<CommitId> {
+        let obsolete = self.reference_obsolete_set();
+        let mut tainted = HashSet::new();
+        let mut orphans = HashSet::new();
+        for commit in &self.commits {
+            let id = commit.id();
+            let has_tainted_parent = commit.parent_ids().iter().any(|id| tainted.contains(id));
+            if has_tainted_parent && !obsolete.contains(id) {
+                orphans.insert(id.clone());
+            }
+            if has_tainted_parent || obsolete.contains(id) {
+                tainted.insert(id.clone());
+            }
+        }
+        orphans
+    }
+
+    /// The commits in `ids` that aren't ancestors of other commits in `ids`.
+    pub fn reference_heads(&self, ids: &[CommitId]) -> HashSet<CommitId> {
+        let parents: HashMap<&CommitId, &[CommitId]> = self
+            .commits
+            .iter()
+            .map(|commit| (commit.id(), commit.parent_ids()))
+            .collect();
+        let mut ancestors_of_others = HashSet::new();
+        for id in ids {
+            let mut pending = parents.get(id).map_or(vec![], |ids| ids.to_vec());
+            while let Some(ancestor_id) = pending.pop() {
+                if let Some(ids) = parents.get(&ancestor_id) {
+                    if !ancestors_of_others.contains(&ancestor_id) {
+                        pending.extend_from_slice(ids);
+                    }
+                }
+                ancestors_of_others.insert(ancestor_id);
+            }
+        }
+        ids.iter()
+            .filter(|id| !ancestors_of_others.contains(id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Builds a random history of `n_commits` steps. At each step, a live
+/// (neither rewritten nor abandoned) commit is rewritten or abandoned with
+/// probability `rewrite_probability`; otherwise a new commit or merge commit is
+/// created on top of live commits.
+pub fn arbitrary_dag(seed: u64, n_commits: usize, rewrite_probability: f64) -> DagFixture {
+    let settings = user_settings();
+    let test_repo = TestRepo::init();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut tx = test_repo.repo.start_transaction(&settings);
+    let root_commit = test_repo.repo.store().root_commit();
+    let mut commits: Vec<Commit> = vec![];
+    let mut successors: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
+    let mut live: Vec<Commit> = vec![];
+    for i in 0..n_commits {
+        if !live.is_empty() && rng.gen_bool(rewrite_probability) {
+            let old_commit = live.swap_remove(rng.gen_range(0..live.len()));
+            if rng.gen_bool(0.25) {
+                tx.mut_repo()
+                    .record_abandoned_commit(old_commit.id().clone());
+                successors.insert(old_commit.id().clone(), vec![]);
+            } else {
+                let new_commit = tx
+                    .mut_repo()
+                    .rewrite_commit(&settings, &old_commit)
+                    .set_description(format!("commit {i}"))
+                    .write()
+                    .unwrap();
+                successors.insert(old_commit.id().clone(), vec![new_commit.id().clone()]);
+                commits.push(new_commit.clone());
+                live.push(new_commit);
+            }
+        } else {
+            let mut parent_ids = vec![];
+            let num_parents = if live.len() >= 2 && rng.gen_bool(0.2) {
+                2
+            } else {
+                1
+            };
+            for parent in live.choose_multiple(&mut rng, num_parents) {
+                parent_ids.push(parent.id().clone());
+            }
+            if parent_ids.is_empty() {
+                parent_ids.push(root_commit.id().clone());
+            }
+            let new_commit = tx
+                .mut_repo()
+                .new_commit(&settings, parent_ids, root_commit.tree_id().clone())
+                .set_description(format!("commit {i}"))
+                .write()
+                .unwrap();
+            commits.push(new_commit.clone());
+            live.push(new_commit);
+        }
+    }
+    DagFixture {
+        seed,
+        test_repo,
+        tx,
+        commits,
+        successors,
+    }
+}
+
+/// Shape of a repository built by [`synthetic_repo()`].
+#[derive(Clone, Debug)]
+pub struct SyntheticRepoSpec {
+    pub seed: u64,
+    /// Number of commits on top of the root commit. They form a linear
+    /// history.
+    pub n_commits: usize,
+    /// Number of subdirectories in each directory above the deepest level, and
+    /// of files in each directory at the deepest level.
+    pub tree_width: usize,
+    /// Number of directory levels above the files. With depth 0, all files are
+    /// at the root.
+    pub tree_depth: usize,
+    /// Size of each file in bytes.
+    pub file_size: usize,
+    /// Number of files modified by each commit after the first, which adds all
+    /// files.
+    pub files_changed_per_commit: usize,
+}
+
+/// A repository built by [`synthetic_repo()`]. The working copy is still at
+/// the root commit.
+pub struct SyntheticRepo {
+    pub spec: SyntheticRepoSpec,
+    /// The workspace. Its repo includes the commits.
+    pub test_workspace: TestWorkspace,
+    /// The commits written, oldest first. Each commit is the parent of the
+    /// next.
+    pub commits: Vec<Commit>,
+    /// The paths of the files in each commit's tree, sorted.
+    pub file_paths: Vec<RepoPathBuf>,
+}
+
+/// Builds a repository with the shape given by `spec`, for benchmarks. File
+/// contents are random but depend only on the seed.
+pub fn synthetic_repo(spec: &SyntheticRepoSpec) -> SyntheticRepo {
+    let settings = user_settings();
+    let mut test_workspace = TestWorkspace::init(&settings);
+    let repo = &test_workspace.repo;
+    let store = repo.store();
+    let mut rng = StdRng::seed_from_u64(spec.seed);
+
+    let mut file_paths = vec![RepoPathBuf::root()];
+    for _ in 0..spec.tree_depth {
+        file_paths = file_paths
+            .iter()
+            .flat_map(|dir| {
+                (0..spec.tree_width)
+                    .map(move |i| dir.join(RepoPathComponent::new(&format!("dir{i}"))))
+            })
+            .collect();
+    }
+    file_paths = file_paths
+        .iter()
+        .flat_map(|dir| {
+            (0..spec.tree_width).map(move |i| dir.join(RepoPathComponent::new(&format!("file{i}"))))
+        })
+        .sorted()
+        .collect();
+
+    let mut tx = repo.start_transaction(&settings);
+    let mut commits: Vec<Commit> = vec![];
+    let mut parent = store.root_commit();
+    let mut tree_id = store.empty_tree_id().clone();
+    for i in 0..spec.n_commits {
+        let changed_paths = if i == 0 {
+            file_paths.iter().collect_vec()
+        } else {
+            file_paths
+                .choose_multiple(&mut rng, spec.files_changed_per_commit)
+                .collect_vec()
+        };
+        let mut tree_builder = store.tree_builder(tree_id);
+        for path in changed_paths {
+            let contents = synthetic_file_contents(&mut rng, spec.file_size);
+            write_normal_file(&mut tree_builder, path, &contents);
+        }
+        tree_id = tree_builder.write_tree().unwrap();
+        let commit = tx
+            .mut_repo()
+            .new_commit(
+                &settings,
+                vec![parent.id().clone()],
+                MergedTreeId::Legacy(tree_id.clone()),
+            )
+            .set_description(format!("commit {i}"))
+            .write()
+            .unwrap();
+        commits.push(commit.clone());
+        parent = commit;
+    }
+    test_workspace.repo = tx.commit("create synthetic commits");
+
+    SyntheticRepo {
+        spec: spec.clone(),
+        test_workspace,
+        commits,
+        file_paths,
+    }
+}
+
+/// Random lines of lowercase letters, `size` bytes in total.
+fn synthetic_file_contents(rng: &mut StdRng, size: usize) -> String {
+    let mut contents = String::with_capacity(size);
+    while contents.len() < size {
+        if contents.len() % 32 == 31 {
+            contents.push('\n');
+        } else {
+            contents.push(rng.gen_range('a'..='z'));
+        }
+    }
+    contents
+}
+
 fn assert_in_rebased_map(
     repo: &impl Repo,
     rebased: &HashMap<CommitId, CommitId>,
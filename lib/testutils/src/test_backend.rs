@@ -23,8 +23,9 @@ use std::time::SystemTime;
 use async_trait::async_trait;
 use futures::stream::BoxStream;
 use jj_lib::backend::{
-    make_root_commit, Backend, BackendError, BackendResult, ChangeId, Commit, CommitId, Conflict,
-    ConflictId, CopyRecord, FileId, SecureSig, SigningFn, SymlinkId, Tree, TreeId,
+    make_root_commit, Backend, BackendError, BackendResult, ChangeId, Commit, CommitId,
+    CompactOptions, CompactStats, Conflict, ConflictId, CopyRecord, FileId, SecureSig, SigningFn,
+    SymlinkId, Tree, TreeId,
 };
 use jj_lib::index::Index;
 use jj_lib::object_id::ObjectId;
@@ -47,6 +48,10 @@ pub struct TestBackendData {
     files: HashMap<RepoPathBuf, HashMap<FileId, Vec<u8>>>,
     symlinks: HashMap<RepoPathBuf, HashMap<SymlinkId, String>>,
     conflicts: HashMap<RepoPathBuf, HashMap<ConflictId, Conflict>>,
+    num_commit_reads: usize,
+    num_tree_reads: usize,
+    num_file_reads: usize,
+    num_file_writes: usize,
 }
 
 fn get_hash(content: &(impl jj_lib::content_hash::ContentHash + ?Sized)) -> Vec<u8> {
@@ -108,6 +113,32 @@ impl TestBackend {
     pub fn remove_commit_unchecked(&self, id: &CommitId) {
         self.locked_data().commits.remove(id);
     }
+
+    /// Number of commits read from this backend so far, including the root
+    /// commit.
+    pub fn num_commit_reads(&self) -> usize {
+        self.locked_data().num_commit_reads
+    }
+
+    /// Number of trees read from this backend so far, not including the empty
+    /// tree.
+    pub fn num_tree_reads(&self) -> usize {
+        self.locked_data().num_tree_reads
+    }
+
+    /// Number of files read from this backend so far.
+    pub fn num_file_reads(&self) -> usize {
+        self.locked_data().num_file_reads
+    }
+
+    /// Number of files written to this backend so far.
+    pub fn num_file_writes(&self) -> usize {
+        self.locked_data().num_file_writes
+    }
+
+    pub fn write_commit_unchecked(&self, id: CommitId, contents: Commit) {
+        self.locked_data().commits.insert(id, contents);
+    }
 }
 
 impl Debug for TestBackend {
@@ -152,8 +183,9 @@ impl Backend for TestBackend {
     }
 
     async fn read_file(&self, path: &RepoPath, id: &FileId) -> BackendResult<Box<dyn Read>> {
-        match self
-            .locked_data()
+        let mut data = self.locked_data();
+        data.num_file_reads += 1;
+        match data
             .files
             .get(path)
             .and_then(|items| items.get(id))
@@ -172,8 +204,9 @@ impl Backend for TestBackend {
         let mut bytes = Vec::new();
         contents.read_to_end(&mut bytes).unwrap();
         let id = FileId::new(get_hash(&bytes));
-        self.locked_data()
-            .files
+        let mut data = self.locked_data();
+        data.num_file_writes += 1;
+        data.files
             .entry(path.to_owned())
             .or_default()
             .insert(id.clone(), bytes);
@@ -211,8 +244,9 @@ impl Backend for TestBackend {
         if id == &self.empty_tree_id {
             return Ok(Tree::default());
         }
-        match self
-            .locked_data()
+        let mut data = self.locked_data();
+        data.num_tree_reads += 1;
+        match data
             .trees
             .get(path)
             .and_then(|items| items.get(id))
@@ -265,13 +299,15 @@ impl Backend for TestBackend {
     }
 
     async fn read_commit(&self, id: &CommitId) -> BackendResult<Commit> {
+        let mut data = self.locked_data();
+        data.num_commit_reads += 1;
         if id == &self.root_commit_id {
             return Ok(make_root_commit(
                 self.root_change_id.clone(),
                 self.empty_tree_id.clone(),
             ));
         }
-        match self.locked_data().commits.get(id).cloned() {
+        match data.commits.get(id).cloned() {
             None => Err(BackendError::ObjectNotFound {
                 object_type: "commit".to_string(),
                 hash: id.hex(),
@@ -313,4 +349,8 @@ impl Backend for TestBackend {
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {
         Ok(())
     }
+
+    fn compact(&self, _options: &CompactOptions) -> BackendResult<CompactStats> {
+        Ok(CompactStats::default())
+    }
 }